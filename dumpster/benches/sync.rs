@@ -0,0 +1,145 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Microbenchmarks of the basic operations on a sync [`Gc`], each timed on its own, on one thread
+//! and on as many threads as the host can run at once (but at least two).
+//!
+//! Automatic collection is turned off, so that no collection is ever timed along with an
+//! operation.
+//! With more than one thread, every thread runs the operation at the same time, on `Gc`s to the
+//! same allocation where the operation works on an existing one, and the time reported is that
+//! of the slowest thread.
+
+use std::{
+    hint::black_box,
+    sync::Barrier,
+    thread::{available_parallelism, scope},
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use dumpster::sync::{set_collect_condition, Gc};
+
+/// The number of iterations run between drops of their outputs, which bounds the memory taken up
+/// by large payloads.
+const BATCH: u64 = 1024;
+
+/// Time `iters` runs of `routine` on each of `n_threads` threads at once, including the calling
+/// thread.
+///
+/// Each run is given a fresh input made by `setup`, and its output is kept until a batch of runs is
+/// done, so that neither making the input nor dropping the output is timed.
+/// Returns the time taken by the slowest thread.
+fn on_threads<S, R>(
+    n_threads: usize,
+    iters: u64,
+    setup: impl Fn() -> S + Sync,
+    routine: impl Fn(S) -> R + Sync,
+) -> Duration {
+    let barrier = Barrier::new(n_threads);
+    let run = || {
+        let mut inputs = Vec::with_capacity(BATCH as usize);
+        let mut outputs = Vec::with_capacity(BATCH as usize);
+        let mut elapsed = Duration::ZERO;
+        let mut left = iters;
+        barrier.wait();
+        while left > 0 {
+            let n = left.min(BATCH);
+            inputs.extend((0..n).map(|_| setup()));
+            let start = Instant::now();
+            outputs.extend(inputs.drain(..).map(&routine));
+            elapsed += start.elapsed();
+            outputs.clear();
+            left -= n;
+        }
+        elapsed
+    };
+    scope(|s| {
+        let others = (1..n_threads).map(|_| s.spawn(run)).collect::<Vec<_>>();
+        others
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .fold(run(), Duration::max)
+    })
+}
+
+/// Run every benchmark on `n_threads` threads at once.
+fn bench_threads(c: &mut Criterion, n_threads: usize) {
+    let mut group = c.benchmark_group(format!("sync/{n_threads}t"));
+    group.bench_function("new/small", |b| {
+        b.iter_custom(|iters| on_threads(n_threads, iters, || (), |()| Gc::new(black_box(0u64))));
+    });
+    group.bench_function("new/large", |b| {
+        b.iter_custom(|iters| {
+            on_threads(
+                n_threads,
+                iters,
+                || (),
+                |()| Gc::new(black_box([0u64; 512])),
+            )
+        });
+    });
+
+    // the calling thread owns these allocations, so only it counts their references without
+    // touching the shared count
+    let gc = Gc::new(0u64);
+    group.bench_function("clone_drop", |b| {
+        b.iter_custom(|iters| {
+            on_threads(n_threads, iters, || (), |()| drop(black_box(gc.clone())))
+        });
+    });
+    group.bench_function("deref", |b| {
+        b.iter_custom(|iters| on_threads(n_threads, iters, || (), |()| **black_box(&gc)));
+    });
+
+    // the first drop of a reference to a clean allocation adds it to the dirty allocations of the
+    // dropping thread, while every later drop finds it there already
+    group.bench_function("drop/clean", |b| {
+        b.iter_custom(|iters| {
+            on_threads(
+                n_threads,
+                iters,
+                || {
+                    let gc = Gc::new(Some(Gc::new(0u64)));
+                    (gc.clone(), gc)
+                },
+                |(clean, kept)| {
+                    drop(clean);
+                    kept
+                },
+            )
+        });
+    });
+    let gc = Gc::new(Some(Gc::new(0u64)));
+    group.bench_function("drop/dirty", |b| {
+        b.iter_custom(|iters| on_threads(n_threads, iters, || gc.clone(), drop));
+    });
+    group.finish();
+}
+
+/// Benchmark every operation on one thread, and then on every thread the host can run at once.
+/// A host which can only run one thread still gets two, so that contention is measured at all.
+fn threads(c: &mut Criterion) {
+    set_collect_condition(|_| false);
+    let n_threads = available_parallelism().map_or(1, usize::from).max(2);
+    bench_threads(c, 1);
+    bench_threads(c, n_threads);
+}
+
+criterion_group!(benches, threads);
+criterion_main!(benches);
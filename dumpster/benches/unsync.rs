@@ -0,0 +1,91 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Microbenchmarks of the basic operations on an unsync [`Gc`], each timed on its own.
+//!
+//! Automatic collection is turned off, so that no collection is ever timed along with an
+//! operation.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use dumpster::unsync::{set_collect_condition, Gc};
+
+/// The number of iterations run between drops of their outputs, which bounds the memory taken up
+/// by large payloads.
+const BATCH: BatchSize = BatchSize::NumIterations(1024);
+
+/// Benchmark the allocation of small and large values, without freeing them.
+fn new(c: &mut Criterion) {
+    let mut group = c.benchmark_group("unsync/new");
+    group.bench_function("small", |b| {
+        b.iter_batched(|| (), |()| Gc::new(black_box(0u64)), BATCH);
+    });
+    group.bench_function("large", |b| {
+        b.iter_batched(|| (), |()| Gc::new(black_box([0u64; 512])), BATCH);
+    });
+    group.finish();
+}
+
+/// Benchmark cloning a `Gc` and dropping the clone right away.
+fn clone_drop(c: &mut Criterion) {
+    let gc = Gc::new(0u64);
+    c.bench_function("unsync/clone_drop", |b| {
+        b.iter(|| drop(black_box(gc.clone())))
+    });
+}
+
+/// Benchmark reading through a `Gc`.
+fn deref(c: &mut Criterion) {
+    let gc = Gc::new(0u64);
+    c.bench_function("unsync/deref", |b| b.iter(|| **black_box(&gc)));
+}
+
+/// Benchmark dropping a reference to an allocation which may be part of a cycle, so that dropping
+/// it marks the allocation dirty.
+///
+/// The first drop of a reference to a clean allocation adds it to the dirty allocations, while
+/// every later drop finds it there already.
+fn drop_dirty(c: &mut Criterion) {
+    let mut group = c.benchmark_group("unsync/drop");
+    group.bench_function("clean", |b| {
+        b.iter_batched(
+            || {
+                let gc = Gc::new(Some(Gc::new(0u64)));
+                (gc.clone(), gc)
+            },
+            |(clean, kept)| {
+                drop(clean);
+                kept
+            },
+            BATCH,
+        );
+    });
+    let gc = Gc::new(Some(Gc::new(0u64)));
+    drop(gc.clone());
+    group.bench_function("dirty", |b| b.iter_batched(|| gc.clone(), drop, BATCH));
+    group.finish();
+}
+
+/// Turn off automatic collection before running any benchmark.
+fn setup(_: &mut Criterion) {
+    set_collect_condition(|_| false);
+}
+
+criterion_group!(benches, setup, new, clone_drop, deref, drop_dirty);
+criterion_main!(benches);
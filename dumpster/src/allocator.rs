@@ -0,0 +1,96 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Garbage-collected allocations made through a custom [`Allocator`].
+//!
+//! An allocation may be freed long after the `Gc` which made it is gone, and by a collection which
+//! knows nothing of its allocator, so the allocator is moved into memory of its own, which the
+//! allocation points to and which is freed along with it.
+
+use std::{
+    alloc::{handle_alloc_error, Allocator, Layout},
+    ptr::{addr_of, read, NonNull},
+};
+
+#[derive(Clone, Copy)]
+/// A handle to the allocator which made an allocation, through which the allocation must be
+/// freed.
+pub(crate) struct AllocHandle(NonNull<Held<()>>);
+
+#[repr(C)]
+/// An allocator which has been moved into memory allocated by itself.
+struct Held<A> {
+    /// The function which frees an allocation through `alloc`, and then frees this.
+    free: unsafe fn(NonNull<Held<()>>, NonNull<u8>, Layout),
+    /// The allocator.
+    alloc: A,
+}
+
+impl AllocHandle {
+    /// Allocate memory with `layout` through `alloc`, returning it along with the handle through
+    /// which it must be freed.
+    ///
+    /// # Panics
+    ///
+    /// This function calls [`handle_alloc_error`] if `alloc` fails to allocate.
+    pub fn allocate<A: Allocator + 'static>(
+        alloc: A,
+        layout: Layout,
+    ) -> (NonNull<u8>, AllocHandle) {
+        let ptr = alloc
+            .allocate(layout)
+            .unwrap_or_else(|_| handle_alloc_error(layout))
+            .cast::<u8>();
+        let held_layout = Layout::new::<Held<A>>();
+        let Ok(held) = alloc.allocate(held_layout) else {
+            unsafe { alloc.deallocate(ptr, layout) };
+            handle_alloc_error(held_layout)
+        };
+        let held = held.cast::<Held<A>>();
+        unsafe {
+            held.as_ptr().write(Held {
+                free: free_through::<A>,
+                alloc,
+            });
+        }
+        (ptr, AllocHandle(held.cast()))
+    }
+
+    /// Free `ptr` through the allocator behind this handle, and then the handle itself.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been allocated with `layout` by [`AllocHandle::allocate`] along with this
+    /// handle, and neither may be used again.
+    pub unsafe fn free(self, ptr: NonNull<u8>, layout: Layout) {
+        // every `Held` starts with its function, whatever its allocator is
+        (self.0.as_ref().free)(self.0, ptr, layout);
+    }
+}
+
+/// Free `ptr` through the allocator in `held`, and then free `held` and drop the allocator.
+///
+/// # Safety
+///
+/// `held` must point to a `Held<A>`, and the requirements of [`AllocHandle::free`] must hold.
+unsafe fn free_through<A: Allocator>(held: NonNull<Held<()>>, ptr: NonNull<u8>, layout: Layout) {
+    let held = held.cast::<Held<A>>();
+    let alloc = read(addr_of!((*held.as_ptr()).alloc));
+    alloc.deallocate(ptr, layout);
+    alloc.deallocate(held.cast(), Layout::new::<Held<A>>());
+}
@@ -0,0 +1,146 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Garbage-collectable boxed closures.
+
+use std::fmt;
+
+use crate::{Collectable, Visitor};
+
+/// The boxed closure stored inside of a [`GcCallback`].
+type BoxedFn<Args, Out, R> = Box<dyn Fn(&[R], Args) -> Out>;
+
+/// A boxed closure which may refer to garbage-collected values.
+///
+/// A plain `Box<dyn Fn(..)>` cannot be [`Collectable`], since there is no way to find the `Gc`s
+/// that a closure has captured.
+/// Instead, a `GcCallback` owns its captured roots explicitly and hands them to the closure every
+/// time it is called.
+/// The closure itself must not capture any `Gc`s by value; if it does, those `Gc`s will be treated
+/// as permanently reachable and any cycle through them will leak.
+///
+/// `Args` is the argument type of the callback (use a tuple to pass several arguments), `Out` is
+/// its return type, and `R` is the type of each captured root.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::Gc, Collectable, GcCallback};
+/// use std::cell::{Cell, RefCell};
+///
+/// #[derive(Collectable)]
+/// struct Button {
+///     clicks: Cell<u32>,
+///     on_click: RefCell<Option<GcCallback<u32, (), Gc<Button>>>>,
+/// }
+///
+/// let button = Gc::new(Button {
+///     clicks: Cell::new(0),
+///     on_click: RefCell::new(None),
+/// });
+///
+/// let callback = GcCallback::new(|roots: &[Gc<Button>], n: u32| {
+///     roots[0].clicks.set(roots[0].clicks.get() + n);
+/// })
+/// .capturing(button.clone());
+/// *button.on_click.borrow_mut() = Some(callback);
+///
+/// button.on_click.borrow().as_ref().unwrap().call(2);
+/// assert_eq!(button.clicks.get(), 2);
+///
+/// // `button` refers to itself through its callback, but can still be collected.
+/// drop(button);
+/// dumpster::unsync::collect();
+/// ```
+pub struct GcCallback<Args, Out, R = ()> {
+    /// The roots captured by this callback, which are passed to `f` on every call.
+    roots: Vec<R>,
+    /// The underlying closure.
+    f: BoxedFn<Args, Out, R>,
+}
+
+impl<Args, Out, R> GcCallback<Args, Out, R> {
+    /// Construct a new callback with no captured roots.
+    ///
+    /// Roots may then be added with [`GcCallback::capturing`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::GcCallback;
+    ///
+    /// let double = GcCallback::<u32, u32>::new(|_, x| 2 * x);
+    /// assert_eq!(double.call(4), 8);
+    /// ```
+    pub fn new(f: impl Fn(&[R], Args) -> Out + 'static) -> GcCallback<Args, Out, R> {
+        GcCallback {
+            roots: Vec::new(),
+            f: Box::new(f),
+        }
+    }
+
+    #[must_use]
+    /// Add a root to the set of values captured by this callback.
+    ///
+    /// Roots are passed to the closure in the order in which they were captured.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::{unsync::Gc, GcCallback};
+    ///
+    /// let a = Gc::new(1);
+    /// let b = Gc::new(2);
+    /// let sum = GcCallback::new(|roots: &[Gc<i32>], ()| roots.iter().map(|x| **x).sum::<i32>())
+    ///     .capturing(a)
+    ///     .capturing(b);
+    ///
+    /// assert_eq!(sum.call(()), 3);
+    /// ```
+    pub fn capturing(mut self, root: R) -> GcCallback<Args, Out, R> {
+        self.roots.push(root);
+        self
+    }
+
+    /// Call this callback with some arguments.
+    pub fn call(&self, args: Args) -> Out {
+        (self.f)(&self.roots, args)
+    }
+
+    #[must_use]
+    /// Get the roots captured by this callback.
+    pub fn roots(&self) -> &[R] {
+        &self.roots
+    }
+}
+
+unsafe impl<Args, Out, R: Collectable> Collectable for GcCallback<Args, Out, R> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.roots.accept(visitor)
+    }
+
+    const IS_LEAF: bool = R::IS_LEAF;
+}
+
+impl<Args, Out, R: fmt::Debug> fmt::Debug for GcCallback<Args, Out, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GcCallback")
+            .field("roots", &self.roots)
+            .finish_non_exhaustive()
+    }
+}
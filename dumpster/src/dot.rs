@@ -0,0 +1,143 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Dumping the garbage-collected heap as a Graphviz graph.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    ptr,
+};
+
+use crate::{sync, unsync, Collectable, Visitor};
+
+/// A single allocation to be drawn in a heap dump.
+pub(crate) struct DotNode {
+    /// The address of the value stored in the allocation, which identifies the node.
+    pub addr: usize,
+    /// The name of the type stored in the allocation.
+    pub type_name: &'static str,
+    /// The size of the allocation, in bytes.
+    pub size: usize,
+    /// The number of `Gc`s pointing to the allocation.
+    pub ref_count: usize,
+    /// The edges out of this allocation, as found by an [`EdgeFinder`].
+    pub edges: Vec<DotEdge>,
+}
+
+/// A reference from one allocation to another in a heap dump.
+pub(crate) struct DotEdge {
+    /// The address of the value which is pointed to.
+    pub to: usize,
+    /// The path of fields and variants through which the reference was found.
+    pub label: String,
+}
+
+/// A visitor which finds every `Gc` owned by a value, without modifying any of them.
+#[derive(Default)]
+pub(crate) struct EdgeFinder {
+    /// The fields and variants entered so far.
+    path: Vec<&'static str>,
+    /// The edges found so far.
+    pub edges: Vec<DotEdge>,
+}
+
+impl EdgeFinder {
+    /// Record an edge to the value at `to`, labeled with the current path.
+    fn push<T: ?Sized>(&mut self, to: Option<&T>) {
+        // a dead `Gc` points to nothing, so there is no edge to draw
+        if let Some(value) = to {
+            self.edges.push(DotEdge {
+                to: value_addr(value),
+                label: self.path.join("."),
+            });
+        }
+    }
+}
+
+impl Visitor for EdgeFinder {
+    fn visit_sync<T>(&mut self, gc: &sync::Gc<T>)
+    where
+        T: Collectable + Send + Sync + ?Sized,
+    {
+        self.push(sync::Gc::try_deref(gc));
+    }
+
+    fn visit_unsync<T>(&mut self, gc: &unsync::Gc<T>)
+    where
+        T: Collectable + ?Sized,
+    {
+        self.push(unsync::Gc::try_deref(gc));
+    }
+
+    fn enter_field(&mut self, name: &'static str) {
+        self.path.push(name);
+    }
+
+    fn exit_field(&mut self) {
+        self.path.pop();
+    }
+
+    fn enter_variant(&mut self, name: &'static str) {
+        self.path.push(name);
+    }
+
+    fn exit_variant(&mut self) {
+        self.path.pop();
+    }
+}
+
+/// Get the address of a value, ignoring any pointer metadata.
+pub(crate) fn value_addr<T: ?Sized>(value: &T) -> usize {
+    ptr::from_ref(value).cast::<()>() as usize
+}
+
+/// Escape `s` so that it can be placed inside a quoted Graphviz string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Write `nodes` to `writer` as a Graphviz digraph.
+///
+/// The nodes are sorted by address first, so that the same heap always produces the same output.
+pub(crate) fn write_dot(mut writer: impl Write, mut nodes: Vec<DotNode>) -> io::Result<()> {
+    nodes.sort_unstable_by_key(|node| node.addr);
+    let mut out = String::from("digraph dumpster {\n");
+    for node in &nodes {
+        // writing to a `String` cannot fail
+        let _ = writeln!(
+            out,
+            "    \"{:#x}\" [label=\"{}\\n{} bytes, {} refs\"];",
+            node.addr,
+            escape(node.type_name),
+            node.size,
+            node.ref_count
+        );
+    }
+    for node in &nodes {
+        for edge in &node.edges {
+            let _ = write!(out, "    \"{:#x}\" -> \"{:#x}\"", node.addr, edge.to);
+            if !edge.label.is_empty() {
+                let _ = write!(out, " [label=\"{}\"]", escape(&edge.label));
+            }
+            out.push_str(";\n");
+        }
+    }
+    out.push_str("}\n");
+    writer.write_all(out.as_bytes())
+}
@@ -0,0 +1,83 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Reports of the allocations which a collection would free.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A single allocation listed in a [`DryRunReport`].
+pub struct GarbageAllocation {
+    /// The address of the value stored in the allocation, as given by
+    /// [`unsync::Gc::as_ptr`](crate::unsync::Gc::as_ptr) or
+    /// [`sync::Gc::as_ptr`](crate::sync::Gc::as_ptr).
+    pub addr: usize,
+    /// The name of the type stored in the allocation, as given by [`std::any::type_name`].
+    pub type_name: &'static str,
+    /// The size of the allocation, in bytes.
+    pub size: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// A list of the garbage-collected allocations which a collection would free, as returned by
+/// [`unsync::collect_dry_run`](crate::unsync::collect_dry_run) and
+/// [`sync::collect_dry_run`](crate::sync::collect_dry_run).
+pub struct DryRunReport {
+    /// Every allocation which would be freed, in no particular order.
+    pub allocations: Vec<GarbageAllocation>,
+}
+
+impl DryRunReport {
+    #[must_use]
+    /// Determine whether nothing would be freed.
+    pub fn is_empty(&self) -> bool {
+        self.allocations.is_empty()
+    }
+
+    #[must_use]
+    /// Get the number of allocations which would be freed.
+    pub fn count(&self) -> usize {
+        self.allocations.len()
+    }
+
+    #[must_use]
+    /// Get the total size, in bytes, of all allocations which would be freed.
+    pub fn bytes(&self) -> usize {
+        self.allocations.iter().map(|a| a.size).sum()
+    }
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} garbage-collected allocations ({} bytes) would be freed:",
+            self.count(),
+            self.bytes()
+        )?;
+        for allocation in &self.allocations {
+            writeln!(
+                f,
+                "  {:#x}: {} ({} bytes)",
+                allocation.addr, allocation.type_name, allocation.size
+            )?;
+        }
+        Ok(())
+    }
+}
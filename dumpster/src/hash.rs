@@ -0,0 +1,167 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A cheap hasher for the tables of allocations kept by the garbage collector.
+//!
+//! Those tables are keyed by the address of an allocation, which needs none of the protection
+//! against collisions that the standard library's default hasher pays for on every lookup.
+
+use std::{
+    hash::{BuildHasher, Hasher},
+    mem::align_of,
+};
+
+/// The number of low bits of an address which carry no information, because every allocation is
+/// aligned to at least a pointer's width.
+const ALIGN_BITS: u32 = align_of::<usize>().trailing_zeros();
+
+/// The multiplier for Fibonacci hashing: 2^64 divided by the golden ratio, rounded to an odd
+/// number.
+const FIBONACCI: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// How far the product is rotated before it is used as a hash.
+///
+/// Multiplication only carries information from the low bits of the address into the high bits of
+/// the product, but a hash table picks a bucket from the low bits of the hash, so some of the high
+/// bits are rotated down.
+const ROTATION: u32 = 26;
+
+#[derive(Clone, Copy, Debug, Default)]
+/// A hasher for addresses, which uses multiplicative (Fibonacci) hashing.
+pub(crate) struct PtrHasher {
+    /// The hash of everything written so far.
+    hash: u64,
+}
+
+impl Hasher for PtrHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        // only addresses are expected, but anything else must still hash consistently
+        for &byte in bytes {
+            self.hash = (self.hash.rotate_left(8) ^ u64::from(byte)).wrapping_mul(FIBONACCI);
+        }
+    }
+
+    fn write_usize(&mut self, addr: usize) {
+        self.hash = (self.hash ^ (addr >> ALIGN_BITS) as u64).wrapping_mul(FIBONACCI);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash.rotate_left(ROTATION)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+/// A builder for [`PtrHasher`]s, for use in a `HashMap`.
+pub(crate) struct BuildPtrHasher;
+
+impl BuildHasher for BuildPtrHasher {
+    type Hasher = PtrHasher;
+
+    fn build_hasher(&self) -> PtrHasher {
+        PtrHasher::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+
+    /// Get the addresses of `n` allocations of `size` bytes each, laid out one after another.
+    fn addresses(n: usize, size: usize) -> Vec<usize> {
+        (0..n).map(|i| 0x1000_0000 + i * size).collect()
+    }
+
+    #[test]
+    /// Test that every key inserted into a table using the hasher can be found again.
+    fn finds_keys() {
+        for size in [8, 16, 24, 64, 4096] {
+            let mut map = HashMap::with_hasher(BuildPtrHasher);
+            for (i, addr) in addresses(10_000, size).into_iter().enumerate() {
+                assert_eq!(map.insert(addr, i), None);
+            }
+            for (i, addr) in addresses(10_000, size).into_iter().enumerate() {
+                assert_eq!(map.get(&addr), Some(&i));
+                assert_eq!(map.get(&(addr + 1)), None);
+            }
+            for addr in addresses(10_000, size).into_iter().step_by(2) {
+                assert!(map.remove(&addr).is_some());
+            }
+            assert_eq!(map.len(), 5_000);
+            assert!(addresses(10_000, size)
+                .iter()
+                .enumerate()
+                .all(|(i, addr)| map.contains_key(addr) == (i % 2 == 1)));
+        }
+    }
+
+    #[test]
+    /// Test that a table stays consistent while keys which collide are inserted and removed in an
+    /// interleaved order.
+    fn colliding_keys() {
+        use std::collections::BTreeMap;
+
+        // addresses which differ only in their highest bits collide in the low bits of the product
+        // before it is rotated, so these probe into the same run of a table without the rotation
+        let colliding = (0..64)
+            .map(|i| 0x1000_0000 + (i << (usize::BITS - 10)))
+            .collect::<Vec<usize>>();
+        let mut map = HashMap::with_hasher(BuildPtrHasher);
+        let mut model = BTreeMap::new();
+        let mut state = 12345u32;
+        for step in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let key = colliding[state as usize % colliding.len()];
+            // `u32::is_multiple_of` would need Rust 1.87
+            #[allow(clippy::manual_is_multiple_of)]
+            if state % 3 == 0 {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, step), model.insert(key, step));
+            }
+            assert_eq!(map.len(), model.len());
+        }
+        // no key is held twice, and none is lost
+        for key in &colliding {
+            assert_eq!(map.get(key), model.get(key));
+        }
+        assert_eq!(map.keys().collect::<HashSet<_>>().len(), map.len());
+    }
+
+    #[test]
+    /// Test that addresses which differ only above their alignment are spread across the buckets a
+    /// table would pick from the low bits of their hashes.
+    fn spreads_addresses() {
+        const BUCKETS: usize = 1 << 12;
+        for size in [8, 16, 32, 4096] {
+            let buckets = addresses(BUCKETS, size)
+                .into_iter()
+                .map(|addr| BuildPtrHasher.hash_one(addr) % BUCKETS as u64)
+                .collect::<HashSet<_>>();
+            // a perfect hash would fill every bucket, and a random one about 63% of them
+            assert!(
+                buckets.len() > BUCKETS / 2,
+                "allocations of {size} bytes only fill {} of {BUCKETS} buckets",
+                buckets.len()
+            );
+        }
+    }
+}
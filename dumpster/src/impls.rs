@@ -21,32 +21,74 @@
 #![allow(deprecated)]
 
 use std::{
+    any::TypeId,
     borrow::Cow,
     cell::{Cell, OnceCell, RefCell},
+    cmp::{Ordering, Reverse},
     collections::{
         hash_map::{DefaultHasher, RandomState},
         BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque,
     },
-    ffi::{OsStr, OsString},
+    ffi::{CStr, CString, OsStr, OsString},
     hash::{BuildHasher, BuildHasherDefault, SipHasher},
     marker::PhantomData,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     num::{
         NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
-        NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+        NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize, Saturating, Wrapping,
     },
-    ops::Deref,
+    ops::{Bound, ControlFlow, Deref, Range, RangeFrom, RangeInclusive, RangeTo},
     path::{Path, PathBuf},
     rc::Rc,
     sync::{
         atomic::{
-            AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize, AtomicU16, AtomicU32,
-            AtomicU64, AtomicU8, AtomicUsize,
+            AtomicBool, AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize, AtomicPtr,
+            AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering as AtomicOrdering,
         },
-        Mutex, MutexGuard, RwLock, RwLockReadGuard, TryLockError,
+        Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, TryLockError,
     },
+    task::Poll,
+    time::{Duration, Instant, SystemTime},
 };
 
-use crate::{Collectable, Visitor};
+use crate::{Collectable, GcFree, Visitor};
+
+#[cfg(feature = "arcstr")]
+mod arcstr;
+#[cfg(feature = "arrayvec")]
+mod arrayvec;
+#[cfg(feature = "bytes")]
+mod bytes;
+#[cfg(feature = "compact_str")]
+mod compact_str;
+#[cfg(feature = "dashmap")]
+mod dashmap;
+#[cfg(feature = "either")]
+mod either;
+#[cfg(feature = "futures")]
+mod futures;
+#[cfg(feature = "generational-arena")]
+mod generational_arena;
+#[cfg(any(feature = "im", feature = "im-rc"))]
+mod im;
+#[cfg(feature = "indexmap")]
+mod indexmap;
+#[cfg(feature = "once_cell")]
+mod once_cell;
+#[cfg(feature = "parking_lot")]
+mod parking_lot;
+#[cfg(feature = "serde_json")]
+mod serde_json;
+#[cfg(feature = "slab")]
+mod slab;
+#[cfg(feature = "slotmap")]
+mod slotmap;
+#[cfg(feature = "smol_str")]
+mod smol_str;
+#[cfg(feature = "tinyvec")]
+mod tinyvec;
+#[cfg(feature = "tokio")]
+mod tokio;
 
 /// Implement `Collectable` trivially for some parametric `?Sized` type.
 macro_rules! param_trivial_impl_unsized {
@@ -56,28 +98,81 @@ macro_rules! param_trivial_impl_unsized {
             fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
                 Ok(())
             }
+
+            const IS_LEAF: bool = true;
         }
     };
 }
 
 param_trivial_impl_unsized!(MutexGuard<'static, T>);
 param_trivial_impl_unsized!(RwLockReadGuard<'static, T>);
-param_trivial_impl_unsized!(&'static T);
 param_trivial_impl_unsized!(PhantomData<T>);
 
+/// A shared reference does not own its referent, so it is a leaf no matter what it points to:
+/// any `Gc` behind it is accounted for by whichever value actually owns it.
+unsafe impl<T: ?Sized> Collectable for &T {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
 unsafe impl<T: Collectable + ?Sized> Collectable for Box<T> {
+    #[inline]
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
         (**self).accept(visitor)
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: GcFree + ?Sized> Collectable for Rc<T> {
+    #[inline]
+    /// Accept a visitor without tracing through the pointee.
+    ///
+    /// The pointee of an `Rc` may be shared with owners that the garbage collector knows nothing
+    /// about, so it must never be traced.
+    /// This is sound only because `T` is [`GcFree`].
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
 }
 
+unsafe impl<T: GcFree + ?Sized> Collectable for Arc<T> {
+    #[inline]
+    /// Accept a visitor without tracing through the pointee.
+    ///
+    /// The pointee of an `Arc` may be shared with owners that the garbage collector knows nothing
+    /// about, so it must never be traced.
+    /// This is sound only because `T` is [`GcFree`].
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl<T: GcFree + ?Sized> GcFree for Rc<T> {}
+unsafe impl<T: GcFree + ?Sized> GcFree for Arc<T> {}
+unsafe impl<T: GcFree + ?Sized> GcFree for Box<T> {}
+unsafe impl<T: GcFree> GcFree for Option<T> {}
+unsafe impl<T: GcFree> GcFree for Vec<T> {}
+unsafe impl<T: GcFree> GcFree for [T] {}
+unsafe impl<T: GcFree, const N: usize> GcFree for [T; N] {}
+
 unsafe impl<T> Collectable for BuildHasherDefault<T> {
     fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
         Ok(())
     }
+
+    const IS_LEAF: bool = true;
 }
 
-unsafe impl<'a, T: ToOwned> Collectable for Cow<'a, T>
+unsafe impl<T: ToOwned> Collectable for Cow<'_, T>
 where
     T::Owned: Collectable,
 {
@@ -87,18 +182,28 @@ where
         }
         Ok(())
     }
+
+    const IS_LEAF: bool = T::Owned::IS_LEAF;
 }
 
 unsafe impl<T: Collectable + ?Sized> Collectable for RefCell<T> {
     #[inline]
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if T::IS_LEAF {
+            return Ok(());
+        }
         self.try_borrow().map_err(|_| ())?.accept(visitor)
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
 unsafe impl<T: Collectable + ?Sized> Collectable for Mutex<T> {
     #[inline]
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if T::IS_LEAF {
+            return Ok(());
+        }
         self.try_lock()
             .map_err(|e| match e {
                 TryLockError::Poisoned(_) => panic!(),
@@ -107,11 +212,16 @@ unsafe impl<T: Collectable + ?Sized> Collectable for Mutex<T> {
             .deref()
             .accept(visitor)
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
 unsafe impl<T: Collectable + ?Sized> Collectable for RwLock<T> {
     #[inline]
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if T::IS_LEAF {
+            return Ok(());
+        }
         self.try_read()
             .map_err(|e| match e {
                 TryLockError::Poisoned(_) => panic!(),
@@ -120,6 +230,8 @@ unsafe impl<T: Collectable + ?Sized> Collectable for RwLock<T> {
             .deref()
             .accept(visitor)
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
 unsafe impl<T: Collectable> Collectable for Option<T> {
@@ -130,6 +242,8 @@ unsafe impl<T: Collectable> Collectable for Option<T> {
             None => Ok(()),
         }
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
 unsafe impl<T: Collectable, E: Collectable> Collectable for Result<T, E> {
@@ -140,33 +254,139 @@ unsafe impl<T: Collectable, E: Collectable> Collectable for Result<T, E> {
             Err(e) => e.accept(visitor),
         }
     }
+
+    const IS_LEAF: bool = T::IS_LEAF && E::IS_LEAF;
+}
+
+unsafe impl<T: Collectable> Collectable for Bound<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        match self {
+            Bound::Included(x) | Bound::Excluded(x) => x.accept(visitor),
+            Bound::Unbounded => Ok(()),
+        }
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<B: Collectable, C: Collectable> Collectable for ControlFlow<B, C> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        match self {
+            ControlFlow::Continue(c) => c.accept(visitor),
+            ControlFlow::Break(b) => b.accept(visitor),
+        }
+    }
+
+    const IS_LEAF: bool = B::IS_LEAF && C::IS_LEAF;
+}
+
+unsafe impl<T: Collectable> Collectable for Poll<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        match self {
+            Poll::Ready(x) => x.accept(visitor),
+            Poll::Pending => Ok(()),
+        }
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
+unsafe impl<T: Collectable> Collectable for Range<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.start.accept(visitor)?;
+        self.end.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable> Collectable for RangeInclusive<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.start().accept(visitor)?;
+        self.end().accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable> Collectable for RangeFrom<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.start.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable> Collectable for RangeTo<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.end.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+/// Implement [`Collectable`] for a single-field tuple struct wrapping some `T`, delegating to the
+/// wrapped value.
+macro_rules! collectable_wrapper_impl {
+    ($($x: ident),* $(,)?) => {
+        $(
+            unsafe impl<T: Collectable> Collectable for $x<T> {
+                #[inline]
+                fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+                    self.0.accept(visitor)
+                }
+
+                const IS_LEAF: bool = T::IS_LEAF;
+            }
+        )*
+    };
+}
+
+collectable_wrapper_impl!(Wrapping, Saturating, Reverse);
+
 unsafe impl<T: Copy + Collectable> Collectable for Cell<T> {
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
         self.get().accept(visitor)
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
 unsafe impl<T: Collectable> Collectable for OnceCell<T> {
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
         self.get().map_or(Ok(()), |x| x.accept(visitor))
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
 /// Implement [`Collectable`] for a collection data structure which has some method `iter()` that
 /// iterates over all elements of the data structure and `iter_mut()` which does the same over
 /// mutable references.
+///
+/// A collection of leaves is not iterated at all.
 macro_rules! collectable_collection_impl {
     ($x: ty) => {
         unsafe impl<T: Collectable> Collectable for $x {
             #[inline]
             fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+                if T::IS_LEAF {
+                    return Ok(());
+                }
                 for elem in self {
                     elem.accept(visitor)?;
                 }
                 Ok(())
             }
+
+            const IS_LEAF: bool = T::IS_LEAF;
         }
     };
 }
@@ -183,36 +403,51 @@ unsafe impl<K: Collectable, V: Collectable, S: BuildHasher + Collectable> Collec
     for HashMap<K, V, S>
 {
     fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
-        for (k, v) in self {
-            k.accept(visitor)?;
-            v.accept(visitor)?;
+        if !(K::IS_LEAF && V::IS_LEAF) {
+            for (k, v) in self {
+                k.accept(visitor)?;
+                v.accept(visitor)?;
+            }
         }
         self.hasher().accept(visitor)
     }
+
+    const IS_LEAF: bool = K::IS_LEAF && V::IS_LEAF && S::IS_LEAF;
 }
 
 unsafe impl<K: Collectable, V: Collectable> Collectable for BTreeMap<K, V> {
     fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
         for (k, v) in self {
             k.accept(visitor)?;
             v.accept(visitor)?;
         }
         Ok(())
     }
+
+    const IS_LEAF: bool = K::IS_LEAF && V::IS_LEAF;
 }
 
 unsafe impl<T: Collectable, const N: usize> Collectable for [T; N] {
     #[inline]
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if T::IS_LEAF {
+            return Ok(());
+        }
         for elem in self {
             elem.accept(visitor)?;
         }
         Ok(())
     }
+
+    const IS_LEAF: bool = T::IS_LEAF;
 }
 
 /// Implement [`Collectable`] for a trivially-collected type which contains no  [`Gc`]s in its
 /// fields.
+/// Such a type is also [`GcFree`].
 macro_rules! collectable_trivial_impl {
     ($x: ty) => {
         unsafe impl Collectable for $x {
@@ -220,7 +455,11 @@ macro_rules! collectable_trivial_impl {
             fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
                 Ok(())
             }
+
+            const IS_LEAF: bool = true;
         }
+
+        unsafe impl GcFree for $x {}
     };
 }
 
@@ -245,40 +484,96 @@ collectable_trivial_impl!(char);
 collectable_trivial_impl!(f32);
 collectable_trivial_impl!(f64);
 
-collectable_trivial_impl!(AtomicU8);
-collectable_trivial_impl!(AtomicU16);
-collectable_trivial_impl!(AtomicU32);
-collectable_trivial_impl!(AtomicU64);
-collectable_trivial_impl!(AtomicUsize);
-collectable_trivial_impl!(AtomicI8);
-collectable_trivial_impl!(AtomicI16);
-collectable_trivial_impl!(AtomicI32);
-collectable_trivial_impl!(AtomicI64);
-collectable_trivial_impl!(AtomicIsize);
-
-collectable_trivial_impl!(NonZeroU8);
-collectable_trivial_impl!(NonZeroU16);
-collectable_trivial_impl!(NonZeroU32);
-collectable_trivial_impl!(NonZeroU64);
-collectable_trivial_impl!(NonZeroU128);
-collectable_trivial_impl!(NonZeroUsize);
-collectable_trivial_impl!(NonZeroI8);
-collectable_trivial_impl!(NonZeroI16);
-collectable_trivial_impl!(NonZeroI32);
-collectable_trivial_impl!(NonZeroI64);
-collectable_trivial_impl!(NonZeroI128);
-collectable_trivial_impl!(NonZeroIsize);
-
-collectable_trivial_impl!(String);
-collectable_trivial_impl!(str);
-collectable_trivial_impl!(PathBuf);
-collectable_trivial_impl!(Path);
-collectable_trivial_impl!(OsString);
-collectable_trivial_impl!(OsStr);
+/// Implement [`Collectable`] trivially for a list of "plain data" types, none of which can ever own
+/// a [`Gc`].
+macro_rules! leaf_collectable {
+    ($($x: ty),* $(,)?) => {
+        $(collectable_trivial_impl!($x);)*
+    };
+}
+
+// an atomic can only ever hold plain data
+leaf_collectable!(
+    AtomicBool,
+    AtomicU8,
+    AtomicU16,
+    AtomicU32,
+    AtomicU64,
+    AtomicUsize,
+    AtomicI8,
+    AtomicI16,
+    AtomicI32,
+    AtomicI64,
+    AtomicIsize,
+);
+
+unsafe impl<T> Collectable for AtomicPtr<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+leaf_collectable!(
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
+    NonZeroU128,
+    NonZeroUsize,
+    NonZeroI8,
+    NonZeroI16,
+    NonZeroI32,
+    NonZeroI64,
+    NonZeroI128,
+    NonZeroIsize,
+);
+
+leaf_collectable!(String, str, PathBuf, Path, OsString, OsStr, CString, CStr);
+leaf_collectable!(Duration, Instant, SystemTime);
+leaf_collectable!(
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6
+);
+leaf_collectable!(TypeId, Ordering, AtomicOrdering);
+
+#[cfg(feature = "uuid")]
+leaf_collectable!(::uuid::Uuid);
+#[cfg(feature = "chrono")]
+leaf_collectable!(
+    ::chrono::DateTime<::chrono::Utc>,
+    ::chrono::DateTime<::chrono::Local>,
+    ::chrono::DateTime<::chrono::FixedOffset>,
+    ::chrono::NaiveDate,
+    ::chrono::NaiveTime,
+    ::chrono::NaiveDateTime,
+    ::chrono::TimeDelta,
+    ::chrono::Utc,
+    ::chrono::Local,
+    ::chrono::FixedOffset,
+    ::chrono::Weekday,
+    ::chrono::Month,
+);
+#[cfg(feature = "time")]
+leaf_collectable!(
+    ::time::OffsetDateTime,
+    ::time::PrimitiveDateTime,
+    ::time::UtcOffset,
+    ::time::Date,
+    ::time::Time,
+    ::time::Duration,
+    ::time::Weekday,
+    ::time::Month,
+);
 
 collectable_trivial_impl!(DefaultHasher);
 collectable_trivial_impl!(RandomState);
-collectable_trivial_impl!(Rc<str>);
 collectable_trivial_impl!(SipHasher);
 
 /// Implement [`Collectable`] for a tuple.
@@ -292,6 +587,8 @@ macro_rules! collectable_tuple {
                 $(($args).accept(visitor)?;)*
                 Ok(())
             }
+
+            const IS_LEAF: bool = $($args::IS_LEAF)&&*;
         }
     }
 }
@@ -313,6 +610,8 @@ macro_rules! collectable_fn {
     ($ty:ty $(,$args:ident)*) => {
         unsafe impl<Ret $(,$args)*> Collectable for $ty {
             fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> { Ok(()) }
+
+            const IS_LEAF: bool = true;
         }
     }
 }
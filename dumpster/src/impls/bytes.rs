@@ -0,0 +1,45 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`bytes`](::bytes) buffers.
+
+use ::bytes::{Bytes, BytesMut};
+
+use crate::{Collectable, GcFree, Visitor};
+
+unsafe impl Collectable for Bytes {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl GcFree for Bytes {}
+
+unsafe impl Collectable for BytesMut {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl GcFree for BytesMut {}
@@ -0,0 +1,77 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`dashmap`](::dashmap) concurrent collections.
+//!
+//! Each shard is only read-locked without blocking.
+//! If any shard is currently locked for writing, `accept` returns `Err(())` and the allocation
+//! owning the map is treated as reachable, so the collector never waits on (or deadlocks with) a
+//! thread holding a shard lock.
+
+use std::hash::{BuildHasher, Hash};
+
+use ::dashmap::{DashMap, DashSet};
+
+use crate::{Collectable, Visitor};
+
+unsafe impl<K, V, S> Collectable for DashMap<K, V, S>
+where
+    K: Collectable + Eq + Hash,
+    V: Collectable,
+    S: BuildHasher + Clone + Collectable,
+{
+    fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for shard in self.shards() {
+            let guard = shard.try_read().ok_or(())?;
+            // SAFETY: the read guard keeps the table alive and unmodified while we iterate.
+            for bucket in unsafe { guard.iter() } {
+                let (k, v) = unsafe { bucket.as_ref() };
+                k.accept(visitor)?;
+                v.get().accept(visitor)?;
+            }
+        }
+        self.hasher().accept(visitor)
+    }
+
+    const IS_LEAF: bool = K::IS_LEAF && V::IS_LEAF && S::IS_LEAF;
+}
+
+unsafe impl<T, S> Collectable for DashSet<T, S>
+where
+    T: Collectable + Eq + Hash,
+    S: BuildHasher + Clone,
+{
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for shard in self.shards() {
+            let guard = shard.try_read().ok_or(())?;
+            // SAFETY: the read guard keeps the table alive and unmodified while we iterate.
+            for bucket in unsafe { guard.iter() } {
+                unsafe { bucket.as_ref() }.0.accept(visitor)?;
+            }
+        }
+        Ok(())
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
@@ -0,0 +1,37 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementation of [`Collectable`] for [`Either`].
+
+use ::either::Either;
+
+use crate::{Collectable, GcFree, Visitor};
+
+unsafe impl<L: Collectable, R: Collectable> Collectable for Either<L, R> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        match self {
+            Either::Left(l) => l.accept(visitor),
+            Either::Right(r) => r.accept(visitor),
+        }
+    }
+
+    const IS_LEAF: bool = L::IS_LEAF && R::IS_LEAF;
+}
+
+unsafe impl<L: GcFree, R: GcFree> GcFree for Either<L, R> {}
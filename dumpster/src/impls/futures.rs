@@ -0,0 +1,74 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`futures`](::futures) synchronization primitives.
+//!
+//! Locks are only acquired without waiting, returning `Err(())` if the lock is held so that the
+//! allocation owning it is treated as reachable until a later collection.
+//!
+//! `BiLock` is different: both of its halves own the same value, and the other half may live
+//! outside of any `Gc`, so it is only `Collectable` as an opaque leaf when its value is
+//! [`GcFree`].
+//! It is only available with the `futures-bilock` feature, since `futures` itself only exposes it
+//! behind its `unstable` feature.
+
+#[cfg(feature = "futures-bilock")]
+use ::futures::lock::BiLock;
+use ::futures::lock::Mutex;
+
+#[cfg(feature = "futures-bilock")]
+use crate::GcFree;
+use crate::{Collectable, Visitor};
+
+unsafe impl<T: Collectable + ?Sized> Collectable for Mutex<T> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        self.try_lock().ok_or(())?.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+#[cfg(feature = "futures-bilock")]
+/// A `BiLock` cannot hold a `Gc`, since its other half may live outside of any `Gc`:
+///
+/// ```compile_fail
+/// use dumpster::sync::Gc;
+/// use futures::lock::BiLock;
+///
+/// let (half_a, half_b) = BiLock::new(None::<Gc<u8>>);
+/// let gc = Gc::new(half_a);
+/// ```
+unsafe impl<T: GcFree> Collectable for BiLock<T> {
+    #[inline]
+    /// Accept a visitor without locking or tracing through the shared value.
+    ///
+    /// The value behind a `BiLock` is co-owned by its other half, which the garbage collector
+    /// knows nothing about, so it must never be traced.
+    /// This is sound only because `T` is [`GcFree`].
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+#[cfg(feature = "futures-bilock")]
+unsafe impl<T: GcFree> GcFree for BiLock<T> {}
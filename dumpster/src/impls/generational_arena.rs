@@ -0,0 +1,48 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`generational_arena`](::generational_arena) arenas.
+
+use ::generational_arena::{Arena, Index};
+
+use crate::{Collectable, GcFree, Visitor};
+
+unsafe impl<T: Collectable> Collectable for Arena<T> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for (_, elem) in self {
+            elem.accept(visitor)?;
+        }
+        Ok(())
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl Collectable for Index {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl GcFree for Index {}
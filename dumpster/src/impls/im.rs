@@ -0,0 +1,93 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for the persistent collections of [`im`] and [`im-rc`].
+//!
+//! The two crates share one API, differing only in whether their nodes are shared through `Arc` or
+//! `Rc`, so both get their implementations from the same macro.
+//!
+//! [`im`]: https://docs.rs/im
+//! [`im-rc`]: https://docs.rs/im-rc
+//!
+//! Persistent collections share structure between clones, so a single element may be stored in
+//! several containers at once, some of which may live outside of any `Gc`.
+//! A shared node holds only one reference to each of its elements no matter how many containers
+//! reach it, so tracing it from each of them would count more edges than there are references.
+//! These collections are therefore opaque leaves, which are only `Collectable` when their elements
+//! are [`GcFree`].
+
+use std::hash::BuildHasher;
+
+use crate::{Collectable, GcFree, Visitor};
+
+/// Implement [`Collectable`] and [`GcFree`] for an opaque leaf type with the given generics.
+macro_rules! opaque_leaf_impl {
+    ($(#[$attr: meta])* impl<$($param: ident),*> for $ty: ty where $($bounds: tt)*) => {
+        $(#[$attr])*
+        unsafe impl<$($param),*> Collectable for $ty
+        where
+            $($bounds)*
+        {
+            #[inline]
+            /// Accept a visitor without tracing through the elements, which may be shared with
+            /// other collections.
+            fn accept<Z: Visitor>(&self, _: &mut Z) -> Result<(), ()> {
+                Ok(())
+            }
+
+            const IS_LEAF: bool = true;
+        }
+
+        unsafe impl<$($param),*> GcFree for $ty where $($bounds)* {}
+    };
+}
+
+/// Implement [`Collectable`] and [`GcFree`] for each persistent collection of the crate named by
+/// `$krate`.
+macro_rules! persistent_collection_impls {
+    ($krate: ident) => {
+        opaque_leaf_impl!(
+            #[doc = concat!(
+                "A `", stringify!($krate), "::Vector` cannot hold a `Gc`, since it may share its ",
+                "nodes with clones outside of any `Gc`:\n",
+                "\n",
+                "```compile_fail\n",
+                "use dumpster::unsync::Gc;\n",
+                "\n",
+                "let shared = ", stringify!($krate), "::Vector::unit(Gc::new(0));\n",
+                "let gc = Gc::new(shared.clone());\n",
+                "```",
+            )]
+            impl<T> for ::$krate::Vector<T> where T: GcFree + Clone
+        );
+        opaque_leaf_impl!(
+            impl<K, V, S> for ::$krate::HashMap<K, V, S>
+            where K: GcFree, V: GcFree, S: BuildHasher + GcFree
+        );
+        opaque_leaf_impl!(
+            impl<T, S> for ::$krate::HashSet<T, S> where T: GcFree, S: BuildHasher + GcFree
+        );
+        opaque_leaf_impl!(impl<K, V> for ::$krate::OrdMap<K, V> where K: GcFree + Ord, V: GcFree);
+        opaque_leaf_impl!(impl<T> for ::$krate::OrdSet<T> where T: GcFree + Ord);
+    };
+}
+
+#[cfg(feature = "im")]
+persistent_collection_impls!(im);
+#[cfg(feature = "im-rc")]
+persistent_collection_impls!(im_rc);
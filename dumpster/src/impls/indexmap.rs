@@ -0,0 +1,56 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`indexmap`] collections.
+
+use std::hash::BuildHasher;
+
+use ::indexmap::{IndexMap, IndexSet};
+
+use crate::{Collectable, Visitor};
+
+unsafe impl<K: Collectable, V: Collectable, S: BuildHasher + Collectable> Collectable
+    for IndexMap<K, V, S>
+{
+    fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for (k, v) in self {
+            k.accept(visitor)?;
+            v.accept(visitor)?;
+        }
+        self.hasher().accept(visitor)
+    }
+
+    const IS_LEAF: bool = K::IS_LEAF && V::IS_LEAF && S::IS_LEAF;
+}
+
+unsafe impl<T: Collectable, S: BuildHasher + Collectable> Collectable for IndexSet<T, S> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for elem in self {
+            elem.accept(visitor)?;
+        }
+        self.hasher().accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF && S::IS_LEAF;
+}
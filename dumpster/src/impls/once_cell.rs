@@ -0,0 +1,59 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`once_cell`](::once_cell) types.
+//!
+//! Cells are only traced once they have been initialized.
+//! Collection never forces a [`Lazy`](::once_cell::sync::Lazy) to run its initializer, and never
+//! waits on a cell which is being initialized by another thread.
+
+use ::once_cell::{sync, unsync};
+
+use crate::{Collectable, Visitor};
+
+unsafe impl<T: Collectable> Collectable for sync::OnceCell<T> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.get().map_or(Ok(()), |x| x.accept(visitor))
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable> Collectable for unsync::OnceCell<T> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.get().map_or(Ok(()), |x| x.accept(visitor))
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable, F: FnOnce() -> T> Collectable for sync::Lazy<T, F> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        sync::Lazy::get(self).map_or(Ok(()), |x| x.accept(visitor))
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable, F: FnOnce() -> T> Collectable for unsync::Lazy<T, F> {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        unsync::Lazy::get(self).map_or(Ok(()), |x| x.accept(visitor))
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
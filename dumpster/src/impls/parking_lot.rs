@@ -0,0 +1,75 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`parking_lot`] locks.
+//!
+//! Each of these implementations only attempts to acquire its lock.
+//! If the lock is already held, `accept` returns `Err(())` instead of blocking, which tells the
+//! collector to treat the owning allocation as reachable.
+
+use ::parking_lot::{FairMutex, Mutex, ReentrantMutex, RwLock};
+
+use crate::{Collectable, Visitor};
+
+unsafe impl<T: Collectable + ?Sized> Collectable for Mutex<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        self.try_lock().ok_or(())?.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable + ?Sized> Collectable for FairMutex<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        self.try_lock().ok_or(())?.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable + ?Sized> Collectable for ReentrantMutex<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        self.try_lock().ok_or(())?.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable + ?Sized> Collectable for RwLock<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        self.try_read().ok_or(())?.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
@@ -0,0 +1,124 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`slotmap`](::slotmap) collections.
+//!
+//! Only the live values of each map are visited.
+//! Slot keys are plain indices and can never refer to a `Gc`, so the maps place no `Collectable`
+//! bound on their key type; this allows keys made with [`new_key_type!`](::slotmap::new_key_type)
+//! to be used without any extra work.
+
+use std::hash::BuildHasher;
+
+use ::slotmap::{
+    DefaultKey, DenseSlotMap, HopSlotMap, Key, KeyData, SecondaryMap, SlotMap, SparseSecondaryMap,
+};
+
+use crate::{Collectable, GcFree, Visitor};
+
+unsafe impl<K: Key, V: Collectable> Collectable for SlotMap<K, V> {
+    fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for v in self.values() {
+            v.accept(visitor)?;
+        }
+        Ok(())
+    }
+
+    const IS_LEAF: bool = V::IS_LEAF;
+}
+
+unsafe impl<K: Key, V: Collectable> Collectable for DenseSlotMap<K, V> {
+    fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for v in self.values() {
+            v.accept(visitor)?;
+        }
+        Ok(())
+    }
+
+    const IS_LEAF: bool = V::IS_LEAF;
+}
+
+unsafe impl<K: Key, V: Collectable> Collectable for HopSlotMap<K, V> {
+    fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for v in self.values() {
+            v.accept(visitor)?;
+        }
+        Ok(())
+    }
+
+    const IS_LEAF: bool = V::IS_LEAF;
+}
+
+unsafe impl<K: Key, V: Collectable> Collectable for SecondaryMap<K, V> {
+    fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for v in self.values() {
+            v.accept(visitor)?;
+        }
+        Ok(())
+    }
+
+    const IS_LEAF: bool = V::IS_LEAF;
+}
+
+unsafe impl<K: Key, V: Collectable, S: BuildHasher> Collectable for SparseSecondaryMap<K, V, S> {
+    fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        for v in self.values() {
+            v.accept(visitor)?;
+        }
+        Ok(())
+    }
+
+    const IS_LEAF: bool = V::IS_LEAF;
+}
+
+unsafe impl Collectable for DefaultKey {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl GcFree for DefaultKey {}
+
+unsafe impl Collectable for KeyData {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl GcFree for KeyData {}
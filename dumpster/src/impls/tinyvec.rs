@@ -0,0 +1,49 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`tinyvec`](::tinyvec) vectors.
+//!
+//! Only the live elements are visited.
+//! The unused inline slots of a tinyvec vector are filled with `Default` values, which never
+//! refer to any live allocation and so are skipped.
+
+use ::tinyvec::{Array, ArrayVec, TinyVec};
+
+use crate::{Collectable, Visitor};
+
+unsafe impl<A: Array> Collectable for ArrayVec<A>
+where
+    A::Item: Collectable,
+{
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.as_slice().accept(visitor)
+    }
+
+    const IS_LEAF: bool = A::Item::IS_LEAF;
+}
+
+unsafe impl<A: Array> Collectable for TinyVec<A>
+where
+    A::Item: Collectable,
+{
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.as_slice().accept(visitor)
+    }
+
+    const IS_LEAF: bool = A::Item::IS_LEAF;
+}
@@ -0,0 +1,116 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementations of [`Collectable`] for [`tokio::sync`](::tokio::sync) types.
+//!
+//! The locks only attempt to acquire their guard, returning `Err(())` if the lock is already held
+//! so that the collector treats the owning allocation as reachable instead of blocking.
+//!
+//! Channel handles are opaque: the values queued inside a channel are shared between every handle
+//! to it, so they are never traced.
+//! Any `Gc` sitting in a channel is therefore treated as a root until it is received.
+
+use ::tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex, Notify, OnceCell, RwLock, Semaphore};
+
+use crate::{Collectable, GcFree, Visitor};
+
+unsafe impl<T: Collectable + ?Sized> Collectable for Mutex<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        self.try_lock().map_err(|_| ())?.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable + ?Sized> Collectable for RwLock<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        if Self::IS_LEAF {
+            return Ok(());
+        }
+        self.try_read().map_err(|_| ())?.accept(visitor)
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+unsafe impl<T: Collectable> Collectable for OnceCell<T> {
+    #[inline]
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.get().map_or(Ok(()), |x| x.accept(visitor))
+    }
+
+    const IS_LEAF: bool = T::IS_LEAF;
+}
+
+/// Implement [`Collectable`] for a channel handle without tracing the values inside the channel.
+macro_rules! opaque_channel_impl {
+    ($($x: ty),* $(,)?) => {
+        $(
+            unsafe impl<T> Collectable for $x {
+                #[inline]
+                fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+                    Ok(())
+                }
+
+                const IS_LEAF: bool = true;
+            }
+        )*
+    };
+}
+
+opaque_channel_impl!(
+    mpsc::Sender<T>,
+    mpsc::Receiver<T>,
+    mpsc::WeakSender<T>,
+    mpsc::UnboundedSender<T>,
+    mpsc::UnboundedReceiver<T>,
+    mpsc::WeakUnboundedSender<T>,
+    oneshot::Sender<T>,
+    oneshot::Receiver<T>,
+    watch::Sender<T>,
+    watch::Receiver<T>,
+    broadcast::Sender<T>,
+    broadcast::Receiver<T>,
+);
+
+unsafe impl Collectable for Notify {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl GcFree for Notify {}
+
+unsafe impl Collectable for Semaphore {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+unsafe impl GcFree for Semaphore {}
@@ -0,0 +1,59 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Inspection of the allocations which currently exist.
+
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A description of a single garbage-collected allocation which currently exists, as yielded by
+/// [`unsync::iter_allocations`](crate::unsync::iter_allocations) and
+/// [`sync::iter_allocations`](crate::sync::iter_allocations).
+///
+/// This is only available with the `heap-inspection` feature.
+pub struct AllocationInfo {
+    /// The address of the value stored in the allocation, as given by
+    /// [`unsync::Gc::as_ptr`](crate::unsync::Gc::as_ptr) or
+    /// [`sync::Gc::as_ptr`](crate::sync::Gc::as_ptr).
+    pub addr: usize,
+    /// The name of the type stored in the allocation, as given by [`std::any::type_name`].
+    pub type_name: &'static str,
+    /// The size of the allocation, in bytes.
+    pub size: usize,
+    /// The number of `Gc`s pointing to the allocation.
+    pub ref_count: usize,
+}
+
+/// Count the number of allocations and bytes of each type among `allocations`, which are given as
+/// pairs of a type name and a size in bytes.
+///
+/// The census is sorted by type name.
+pub(crate) fn census(
+    allocations: impl IntoIterator<Item = (&'static str, usize)>,
+) -> Vec<(String, usize, usize)> {
+    let mut counts = BTreeMap::<&'static str, (usize, usize)>::new();
+    for (type_name, size) in allocations {
+        let (count, bytes) = counts.entry(type_name).or_default();
+        *count += 1;
+        *bytes += size;
+    }
+    counts
+        .into_iter()
+        .map(|(type_name, (count, bytes))| (type_name.to_owned(), count, bytes))
+        .collect()
+}
@@ -0,0 +1,119 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Reporting of collector activity through `tracing` and `log`.
+//!
+//! Everything in this module compiles down to nothing unless the `tracing` or `log` feature is
+//! enabled.
+
+use std::time::Duration;
+
+/// A span covering a single collection, which is closed by [`CollectSpan::finish`].
+pub(crate) struct CollectSpan {
+    #[cfg(feature = "tracing")]
+    /// The entered `tracing` span.
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "log")]
+    /// The name of the garbage collector running the collection.
+    flavor: &'static str,
+}
+
+impl CollectSpan {
+    #[inline]
+    /// Enter the span for a collection by the garbage collector named `flavor`.
+    pub fn enter(flavor: &'static str) -> CollectSpan {
+        let _ = flavor;
+        CollectSpan {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                target: "dumpster",
+                "dumpster::collect",
+                flavor,
+                allocations_examined = tracing::field::Empty,
+                allocations_freed = tracing::field::Empty,
+                bytes_freed = tracing::field::Empty,
+                duration = tracing::field::Empty,
+            )
+            .entered(),
+            #[cfg(feature = "log")]
+            flavor,
+        }
+    }
+
+    #[inline]
+    #[allow(clippy::unused_self)]
+    /// Record the outcome of the collection and leave its span.
+    pub fn finish(
+        self,
+        allocations_examined: usize,
+        allocations_freed: usize,
+        bytes_freed: usize,
+        duration: Duration,
+    ) {
+        let _ = (
+            allocations_examined,
+            allocations_freed,
+            bytes_freed,
+            duration,
+        );
+        #[cfg(feature = "tracing")]
+        {
+            self.span
+                .record("allocations_examined", allocations_examined)
+                .record("allocations_freed", allocations_freed)
+                .record("bytes_freed", bytes_freed)
+                .record("duration", tracing::field::debug(duration));
+        }
+        #[cfg(feature = "log")]
+        log::debug!(
+            target: "dumpster",
+            "{} collection examined {allocations_examined} allocations and freed \
+             {allocations_freed} ({bytes_freed} bytes) in {duration:?}",
+            self.flavor,
+        );
+    }
+}
+
+#[inline]
+/// Report that a thread's table of dirty allocations filled up, holding `len` allocations, and had
+/// to be handed over to the collector early.
+pub(crate) fn dirty_table_full(len: usize) {
+    let _ = len;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(target: "dumpster", len, "dirty allocation table is full");
+    #[cfg(feature = "log")]
+    log::debug!(target: "dumpster", "dirty allocation table is full ({len} allocations)");
+}
+
+#[inline]
+/// Report that a value of type `type_name` could not be traced, and so was assumed to be
+/// reachable.
+pub(crate) fn trace_error(type_name: &'static str) {
+    let _ = type_name;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        target: "dumpster",
+        type_name,
+        "could not trace a value, so it is assumed to be reachable"
+    );
+    #[cfg(feature = "log")]
+    log::debug!(
+        target: "dumpster",
+        "could not trace a value of type {type_name}, so it is assumed to be reachable"
+    );
+}
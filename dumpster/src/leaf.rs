@@ -0,0 +1,169 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Declaring plain-old-data types as [`Collectable`](crate::Collectable) leaves.
+
+use crate::GcFree;
+
+/// Do nothing, but only compile if `T` is [`GcFree`].
+///
+/// This is used by [`collectable_leaf!`](crate::collectable_leaf) to check every field of a leaf
+/// type.
+#[doc(hidden)]
+pub const fn assert_gc_free<T: GcFree + ?Sized>() {}
+
+/// Define one or more types which can never own a garbage-collected pointer, implementing
+/// [`Collectable`](crate::Collectable) and [`GcFree`](crate::GcFree) for them.
+///
+/// The macro takes ordinary `struct` and `enum` definitions, emits them unchanged, and implements
+/// `Collectable` as a no-op for each of them.
+/// Unlike a hand-written `unsafe impl`, this is checked: every field type must itself be
+/// [`GcFree`], so adding a [`sync::Gc`](crate::sync::Gc) or [`unsync::Gc`](crate::unsync::Gc)
+/// field to a leaf later on becomes a compile error instead of a silent leak.
+///
+/// Only types without generic parameters are supported.
+/// For anything more involved, use `#[derive(Collectable)]`.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{collectable_leaf, unsync::Gc};
+///
+/// collectable_leaf! {
+///     #[derive(Debug, Clone)]
+///     pub struct MyConfig {
+///         pub name: String,
+///         pub retries: u32,
+///     }
+///
+///     pub struct MyStats(pub u64, pub u64);
+///
+///     pub enum Level {
+///         Quiet,
+///         Verbose { depth: u8 },
+///     }
+/// }
+///
+/// let config = Gc::new(MyConfig {
+///     name: "leaf".into(),
+///     retries: 3,
+/// });
+/// assert_eq!(config.retries, 3);
+/// ```
+///
+/// A leaf may not contain a `Gc`:
+///
+/// ```compile_fail
+/// use dumpster::{collectable_leaf, unsync::Gc};
+///
+/// collectable_leaf! {
+///     pub struct Sneaky {
+///         pub name: String,
+///         pub hidden: Option<Gc<String>>,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! collectable_leaf {
+    () => {};
+    (@impl $name:ident; $($field:ty,)*) => {
+        const _: () = {
+            $($crate::__assert_gc_free::<$field>();)*
+        };
+
+        unsafe impl $crate::Collectable for $name {
+            #[inline]
+            fn accept<V: $crate::Visitor>(&self, _: &mut V) -> ::core::result::Result<(), ()> {
+                ::core::result::Result::Ok(())
+            }
+
+            const IS_LEAF: bool = true;
+        }
+
+        unsafe impl $crate::GcFree for $name {}
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $ty),*
+        }
+
+        $crate::collectable_leaf!(@impl $name; $($ty,)*);
+        $crate::collectable_leaf!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident (
+            $($(#[$field_attr:meta])* $field_vis:vis $ty:ty),* $(,)?
+        );
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis struct $name($($(#[$field_attr])* $field_vis $ty),*);
+
+        $crate::collectable_leaf!(@impl $name; $($ty,)*);
+        $crate::collectable_leaf!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis struct $name;
+
+        $crate::collectable_leaf!(@impl $name;);
+        $crate::collectable_leaf!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident
+                $(($($(#[$tuple_attr:meta])* $tuple_ty:ty),* $(,)?))?
+                $({$($(#[$named_attr:meta])* $named:ident : $named_ty:ty),* $(,)?})?
+                $(= $discriminant:expr)?
+            ),* $(,)?
+        }
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $(
+                $(#[$variant_attr])*
+                $variant
+                $(($($(#[$tuple_attr])* $tuple_ty),*))?
+                $({$($(#[$named_attr])* $named: $named_ty),*})?
+                $(= $discriminant)?
+            ),*
+        }
+
+        $crate::collectable_leaf!(
+            @impl $name;
+            $($($($tuple_ty,)*)? $($($named_ty,)*)?)*
+        );
+        $crate::collectable_leaf!($($rest)*);
+    };
+}
@@ -0,0 +1,77 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Reports of garbage-collected allocations which were never freed.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A single allocation listed in a [`LeakReport`].
+pub struct LeakedAllocation {
+    /// The name of the type stored in the allocation, as given by [`std::any::type_name`].
+    pub type_name: &'static str,
+    /// The size of the allocation, in bytes.
+    pub size: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// A list of garbage-collected allocations which could not be freed, as returned by
+/// [`unsync::take_leak_report`](crate::unsync::take_leak_report) and
+/// [`sync::leak_report`](crate::sync::leak_report).
+///
+/// This is only available with the `leak-detection` feature.
+pub struct LeakReport {
+    /// Every allocation which was leaked.
+    pub allocations: Vec<LeakedAllocation>,
+}
+
+impl LeakReport {
+    #[must_use]
+    /// Determine whether nothing was leaked.
+    pub fn is_empty(&self) -> bool {
+        self.allocations.is_empty()
+    }
+
+    #[must_use]
+    /// Get the number of leaked allocations.
+    pub fn count(&self) -> usize {
+        self.allocations.len()
+    }
+
+    #[must_use]
+    /// Get the total size, in bytes, of all leaked allocations.
+    pub fn bytes(&self) -> usize {
+        self.allocations.iter().map(|a| a.size).sum()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} garbage-collected allocations ({} bytes) were leaked:",
+            self.count(),
+            self.bytes()
+        )?;
+        for allocation in &self.allocations {
+            writeln!(f, "  {} ({} bytes)", allocation.type_name, allocation.size)?;
+        }
+        Ok(())
+    }
+}
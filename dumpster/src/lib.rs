@@ -129,7 +129,8 @@
 //!
 //! # Optional features
 //!
-//! `dumpster` has two optional features: `derive` and `coerce-unsized`.
+//! `dumpster` has four optional features for its core functionality: `derive`, `coerce-unsized`,
+//! `allocator-api`, and `tagged-ptr`.
 //!
 //! `derive` is enabled by default.
 //! It enables the derive macro for `Collectable`, which makes it easy for users to implement their
@@ -169,6 +170,115 @@ let gc1: Gc<[u8]> = Gc::new([1, 2, 3]);
 //! dumpster = { version = "0.1.2", features = ["coerce-unsized"]}
 //! ```
 //!
+//! `allocator-api` is also disabled by default, and also requires nightly Rust.
+//! It adds `Gc::new_in` to each garbage collector, which places an allocation in memory from any
+//! `std::alloc::Allocator`, such as an arena, and frees it through the same allocator.
+//!
+//! `tagged-ptr` is enabled by default.
+//! It packs the bookkeeping which each [`sync::Gc`] keeps for collections into the unused low bits
+//! of its pointer, so that a `sync::Gc` is the same size as a reference.
+//! Without it, each `sync::Gc` holds that bookkeeping in an extra word of its own.
+//!
+//! ## Third-party types
+//!
+//! `dumpster` can also implement [`Collectable`] for types from other crates.
+//! Each of these implementations is disabled by default and lives behind a feature with the same
+//! name as the crate it supports:
+//!
+//! - `indexmap`: `IndexMap` and `IndexSet`.
+//! - `arrayvec`: `ArrayVec` and `ArrayString`.
+//! - `either`: `Either`.
+//! - `parking_lot`: `Mutex`, `RwLock`, `FairMutex`, and `ReentrantMutex`.
+//! - `tokio`: `Mutex`, `RwLock`, and `OnceCell` from `tokio::sync`, plus its channel handles.
+//! - `im`: `Vector`, `HashMap`, `HashSet`, `OrdMap` and `OrdSet` from `im`, holding [`GcFree`]
+//!   elements.
+//! - `im-rc`: `Vector`, `HashMap`, `HashSet`, `OrdMap` and `OrdSet` from `im-rc`, holding
+//!   [`GcFree`] elements.
+//! - `slotmap`: `SlotMap`, `DenseSlotMap`, `HopSlotMap`, `SecondaryMap` and `SparseSecondaryMap`,
+//!   plus the key types.
+//! - `bytes`: `Bytes` and `BytesMut`.
+//! - `serde_json`: `Value`, `Map<String, Value>` and `Number`.
+//! - `dashmap`: `DashMap` and `DashSet`.
+//! - `once_cell`: `OnceCell` and `Lazy` from both `once_cell::sync` and `once_cell::unsync`.
+//! - `tinyvec`: `TinyVec` and `ArrayVec` from `tinyvec`.
+//! - `compact_str`: `CompactString`.
+//! - `smol_str`: `SmolStr`.
+//! - `arcstr`: `ArcStr` and `Substr`.
+//! - `slab`: `Slab`.
+//! - `generational-arena`: `Arena` and `Index`.
+//! - `futures`: `futures::lock::Mutex`.
+//! - `futures-bilock`: `futures::lock::BiLock` holding a [`GcFree`] value, which `futures` only
+//!   provides behind its `unstable` feature.
+//! - `uuid`: `Uuid`.
+//! - `chrono`: the date, time and time zone types from `chrono`.
+//! - `time`: the date, time and offset types from `time`.
+//!
+//! ## Testing
+//!
+//! The `testing` feature, which is disabled by default, enables the `testing` module.
+//! It contains scaffolding which is useful for testing code that uses `dumpster`, such as a
+//! counter for dropped values and a way to collect on every drop of a `Gc`.
+//!
+//! ## Leak detection
+//!
+//! The `leak-detection` feature, which is disabled by default, makes each garbage collector keep
+//! track of the type and size of every allocation it manages.
+//! When a thread exits, any `unsync` allocations which it could not free (for instance, because a
+//! `Gc` was passed to [`std::mem::forget`]) are printed to standard error, and can be retrieved
+//! with `unsync::take_leak_report`.
+//! `sync` allocations outlive the threads which created them, so they are instead reported on
+//! demand by `sync::leak_report`.
+//! This costs some time and memory on every allocation, so it is meant for debugging.
+//!
+//! ## Instrumentation
+//!
+//! The `tracing` and `log` features, which are disabled by default, report the activity of the
+//! garbage collectors through the crate of the same name.
+//! With `tracing`, every collection runs inside a `DEBUG`-level span named `dumpster::collect`,
+//! which records the number of allocations examined and freed, the number of bytes freed, and
+//! how long the collection took.
+//! With `log`, the same summary is logged at the `debug` level once each collection finishes.
+//! Both also report unusual events at the `debug` level, such as a value which could not be
+//! traced.
+//! All of these use the target `dumpster`.
+//! When neither feature is enabled, none of this costs anything at runtime.
+//!
+//! ## Heap dumps
+//!
+//! The `heap-dump` feature, which is disabled by default, enables `unsync::dump_heap_dot` and
+//! `sync::dump_heap_dot`.
+//! These write the graph of every live allocation in the Graphviz DOT format, which is useful for
+//! finding out why some value is not being collected.
+//! Like `leak-detection`, this makes every allocation register itself with the garbage collector,
+//! costing some time and memory.
+//!
+//! ## Heap inspection
+//!
+//! The `heap-inspection` feature, which is disabled by default, enables `unsync::iter_allocations`
+//! and `sync::iter_allocations`.
+//! These list every live allocation along with its address, size, reference count, and the name
+//! of the type it stores, which is useful for an in-program debugging view of the heap.
+//! Like `leak-detection`, this makes every allocation register itself with the garbage collector,
+//! costing some time and memory.
+//!
+//! ## Parallel collection
+//!
+//! The `parallel-collect` feature, which is disabled by default, lets a `sync` collection with
+//! many dirty allocations search the heap on several threads at once, one for each available core
+//! unless `sync::set_collect_workers` says otherwise.
+//! Finalizers are still run, and garbage is still dropped, on the thread doing the collection.
+//!
+//! ## Epoch-based destruction
+//!
+//! The `epoch` feature, which is disabled by default, keeps a `sync` collection from dropping the
+//! garbage it finds by itself.
+//! Instead, the garbage is retired into an epoch-based queue, and destroyed in small batches by
+//! whichever threads next make or drop `Gc`s, which spreads out the pauses of a large collection.
+//! Explicit calls to `sync::collect`, `sync::collect_await` and `sync::try_collect` still destroy
+//! all retired garbage before they return, so their behavior is unchanged.
+//! If dropping retired garbage panics, that allocation is leaked, and the panic is resumed by the
+//! next explicit collection.
+//!
 //! # License
 //!
 //! `dumpster` is licensed under the GNU GPLv3 any later version of the GPL at your choice.
@@ -182,19 +292,54 @@ let gc1: Gc<[u8]> = Gc::new([1, 2, 3]);
 #![allow(clippy::multiple_crate_versions, clippy::result_unit_err)]
 #![cfg_attr(feature = "coerce-unsized", feature(coerce_unsized))]
 #![cfg_attr(feature = "coerce-unsized", feature(unsize))]
-#![cfg_attr(feature = "coerce-unsized", feature(strict_provenance))]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
+#[cfg(feature = "allocator-api")]
+mod allocator;
+mod callback;
+#[cfg(feature = "heap-dump")]
+mod dot;
+mod dry_run;
+mod hash;
 mod impls;
-
+#[cfg(feature = "heap-inspection")]
+mod inspect;
+mod instrument;
+mod leaf;
+#[cfg(feature = "leak-detection")]
+mod leak;
+mod mode;
 mod ptr;
+mod ratio;
+mod reach;
+mod remote;
+mod stats;
 pub mod sync;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod unsync;
+mod unwind;
+
+pub use callback::GcCallback;
+pub use dry_run::{DryRunReport, GarbageAllocation};
+#[cfg(feature = "heap-inspection")]
+pub use inspect::AllocationInfo;
+#[doc(hidden)]
+pub use leaf::assert_gc_free as __assert_gc_free;
+#[cfg(feature = "leak-detection")]
+pub use leak::{LeakReport, LeakedAllocation};
+pub use mode::CollectionMode;
+pub use reach::GcId;
+pub use stats::GcStats;
+pub use unwind::{collection_panic_policy, set_collection_panic_policy, PanicPolicy};
 
 /// The trait that any garbage-collectable data must implement.
 ///
 /// This trait should usually be implemented by using `#[derive(Collectable)]`, using the provided
 /// macro.
 /// Only data structures using raw pointers or other magic should manually implement `Collectable`.
+/// A type which cannot be annotated with the derive, such as generated code, can instead be given
+/// an implementation with [`impl_collectable!`], which only needs a list of its fields.
 ///
 /// # Safety
 ///
@@ -202,6 +347,17 @@ pub mod unsync;
 /// typically double-frees or use-after-frees.
 /// This includes [`Collectable::accept`], even though it is a safe function, since its correctness
 /// is required for safety.
+/// If [`Collectable::IS_LEAF`] is `true`, `accept` must never visit a garbage-collected pointer,
+/// since containers of the type will not call it at all.
+///
+/// # Resurrection
+///
+/// When a collection destroys a value, every [`sync::Gc`] and [`unsync::Gc`] inside it which
+/// points to other garbage is already dead, so the [`Drop`] implementation of the value cannot
+/// bring any of the garbage back to life.
+/// Cloning such a `Gc` panics, and `try_clone` returns `None`.
+/// A value which needs to keep some of its cycle alive must do so in a
+/// [finalizer](Finalize#resurrection) instead.
 ///
 /// # Examples
 ///
@@ -273,6 +429,147 @@ pub unsafe trait Collectable {
     /// attempting to borrow from a [`RefCell`](std::cell::RefCell) which has already been
     /// mutably borrowed).
     fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()>;
+
+    /// Whether this type can never own a garbage-collected pointer, so that
+    /// [`Collectable::accept`] never visits anything.
+    ///
+    /// Containers such as `Vec<T>` skip their elements entirely when `T::IS_LEAF` is `true`, so
+    /// tracing a `Vec<u8>` takes constant time instead of time linear in its length.
+    /// It is always correct, if sometimes slower, to leave this as `false`.
+    /// `#[derive(Collectable)]` never sets it by itself; `#[collectable(leaf)]` on the type opts in,
+    /// setting it to `true` when every traced field is a leaf.
+    const IS_LEAF: bool = false;
+}
+
+/// A marker for types which can never own a garbage-collected pointer, no matter what value they
+/// hold.
+///
+/// `dumpster` uses this trait to decide which shared pointers it is allowed to skip over while
+/// tracing.
+/// In particular, [`Rc<T>`](std::rc::Rc) and [`Arc<T>`](std::sync::Arc) are [`Collectable`] only
+/// when `T: GcFree`, and their `accept` implementations never look at the pointee.
+///
+/// # Safety
+///
+/// A type implementing `GcFree` must not transitively own a [`sync::Gc`] or an [`unsync::Gc`],
+/// including through interior mutability, trait objects, or raw pointers.
+///
+/// To see why this matters, consider what would happen if an `Arc` did trace through its pointee.
+/// The collector assumes that every `Gc` it finds while visiting an allocation is owned by that
+/// allocation and nobody else.
+/// However, the value behind an `Arc` is co-owned by every clone of the `Arc`, some of which may
+/// live outside of any garbage-collected allocation.
+/// If two allocations shared one `Arc<Gc<T>>`, the collector would see two edges to the inner
+/// allocation while its reference count is only one, and could conclude that the inner allocation
+/// is unreachable while the `Arc` still points to it.
+/// Requiring `GcFree` rules this situation out entirely.
+///
+/// For plain data types, prefer [`collectable_leaf!`] over a hand-written implementation: it
+/// checks at compile time that every field is itself `GcFree`.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::Gc, Collectable, GcFree};
+/// use std::{cell::RefCell, sync::Arc};
+///
+/// struct Config {
+///     name: String,
+/// }
+///
+/// // SAFETY: `Config` only contains a `String`, which cannot own a `Gc`.
+/// unsafe impl GcFree for Config {}
+///
+/// #[derive(Collectable)]
+/// struct Node {
+///     config: Arc<Config>,
+///     next: RefCell<Option<Gc<Node>>>,
+/// }
+///
+/// let config = Arc::new(Config {
+///     name: "shared".into(),
+/// });
+/// let node = Gc::new(Node {
+///     config: config.clone(),
+///     next: RefCell::new(None),
+/// });
+/// *node.next.borrow_mut() = Some(node.clone());
+/// drop(node);
+///
+/// dumpster::unsync::collect();
+/// assert_eq!(Arc::strong_count(&config), 1);
+/// ```
+pub unsafe trait GcFree {}
+
+/// A value which runs some code just before it is destroyed by the garbage collector.
+///
+/// Unlike [`Drop::drop`], which runs after every [`sync::Gc`] and [`unsync::Gc`] owned by a
+/// value in a garbage cycle has been made dead, [`Finalize::finalize`] runs while they all still
+/// point to living allocations, so a finalizer can read the rest of its cycle.
+/// Finalizers only run for allocations made with [`unsync::Gc::new_finalized`] or
+/// [`sync::Gc::new_finalized`].
+///
+/// # Order of finalization
+///
+/// When a collection finds unreachable allocations, it runs the finalizers of all of them, in no
+/// particular order, before it destroys any of them.
+/// An allocation which is freed because its last `Gc` was dropped is finalized just before its
+/// value is dropped.
+/// Each finalizer runs at most once.
+///
+/// Collections requested while a collection is running finalizers are skipped.
+/// Garbage left over when a thread exits is finalized as that thread's garbage collector is torn
+/// down, so the finalizers of [`unsync::Gc`]s must not create or drop any `unsync::Gc` then.
+///
+/// # Resurrection
+///
+/// A finalizer may keep an unreachable allocation alive by storing a clone of a `Gc` to it
+/// somewhere reachable.
+/// A collection which ran any finalizers checks again which allocations are unreachable before it
+/// destroys them, so the resurrected allocation, along with everything reachable from it,
+/// survives.
+/// Its finalizer has already run, and will not run again when it becomes unreachable once more.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::Gc, Collectable, Finalize};
+/// use std::cell::{Cell, OnceCell};
+///
+/// thread_local! {
+///     static CLOSED: Cell<usize> = const { Cell::new(0) };
+/// }
+///
+/// #[derive(Collectable)]
+/// struct Handle {
+///     peer: OnceCell<Gc<Handle>>,
+///     fd: usize,
+/// }
+///
+/// impl Finalize for Handle {
+///     fn finalize(&self) {
+///         // the peer is still alive, even though the two handles form a garbage cycle
+///         CLOSED.with(|c| c.set(c.get() + self.fd + self.peer.get().unwrap().fd));
+///     }
+/// }
+///
+/// let a = Gc::new_finalized(Handle {
+///     peer: OnceCell::new(),
+///     fd: 1,
+/// });
+/// let b = Gc::new_finalized(Handle {
+///     peer: OnceCell::new(),
+///     fd: 2,
+/// });
+/// let _ = a.peer.set(b.clone());
+/// let _ = b.peer.set(a.clone());
+/// drop((a, b));
+/// dumpster::unsync::collect();
+/// assert_eq!(CLOSED.with(Cell::get), 6);
+/// ```
+pub trait Finalize {
+    /// Run code which must happen before this value is destroyed.
+    fn finalize(&self);
 }
 
 /// A visitor for a garbage collected value.
@@ -302,6 +599,47 @@ pub trait Visitor {
     fn visit_unsync<T>(&mut self, gc: &unsync::Gc<T>)
     where
         T: Collectable + ?Sized;
+
+    /// Note that the following visits come from the field `name` of the value being accepted.
+    ///
+    /// `#[derive(Collectable)]` calls this before delegating to each traced field, and calls
+    /// [`Visitor::exit_field`] once the field has been accepted.
+    /// Tuple fields are named by their index, such as `"0"`.
+    /// Diagnostic visitors can use these hooks to label the edges they find; the default
+    /// implementation does nothing.
+    ///
+    /// If accepting a field returns an error, the traversal is abandoned and the matching call to
+    /// `exit_field` is never made.
+    #[inline]
+    fn enter_field(&mut self, name: &'static str) {
+        let _ = name;
+    }
+
+    /// Note that the field most recently entered with [`Visitor::enter_field`] has been fully
+    /// accepted.
+    ///
+    /// The default implementation does nothing.
+    #[inline]
+    fn exit_field(&mut self) {}
+
+    /// Note that the following visits come from the enum variant `name` of the value being
+    /// accepted.
+    ///
+    /// `#[derive(Collectable)]` calls this when accepting an enum, before visiting the fields of
+    /// the active variant, and calls [`Visitor::exit_variant`] afterward.
+    /// As with [`Visitor::enter_field`], the matching exit is skipped if accepting a field fails.
+    /// The default implementation does nothing.
+    #[inline]
+    fn enter_variant(&mut self, name: &'static str) {
+        let _ = name;
+    }
+
+    /// Note that the variant most recently entered with [`Visitor::enter_variant`] has been fully
+    /// accepted.
+    ///
+    /// The default implementation does nothing.
+    #[inline]
+    fn exit_variant(&mut self) {}
 }
 
 // Re-export #[derive(Collectable)].
@@ -318,6 +656,15 @@ extern crate dumpster_derive;
 /// This enables users of `dumpster` to easily store custom types inside a `Gc`.
 /// To do so, simply annotate your type with `#[derive(Collectable)]`.
 ///
+/// The macro works on structs and enums, but not on unions.
+/// A type parameter is only required to be `Collectable` if it is used in the type of some
+/// field; parameters which only appear inside of a `PhantomData` or behind a shared reference
+/// are left unbounded.
+///
+/// The generated implementation reports the name of each traced field and enum variant to the
+/// visitor through [`Visitor::enter_field`] and [`Visitor::enter_variant`], so diagnostic
+/// visitors can tell which field an edge came from.
+///
 /// # Examples
 ///
 /// ```
@@ -327,6 +674,212 @@ extern crate dumpster_derive;
 /// struct Foo {
 ///     bar: Option<Box<Foo>>,
 /// }
+///
+/// #[derive(Collectable)]
+/// enum Expr<T> {
+///     Literal(T),
+///     Add(Box<Expr<T>>, Box<Expr<T>>),
+///     Empty,
+/// }
+/// ```
+///
+/// # Lifetimes
+///
+/// Types with lifetime parameters can derive `Collectable` as well.
+/// Shared references are always leaves, since they never own the `Gc`s they might point to.
+/// However, a `Gc` can only hold `'static` data, so a type which stores a `Gc` to itself must
+/// require its lifetimes to be `'static`.
+///
+/// ```
+/// use dumpster::{unsync::Gc, Collectable};
+///
+/// #[derive(Collectable)]
+/// struct Token<'src> {
+///     text: &'src str,
+///     children: Vec<Token<'src>>,
+/// }
+///
+/// #[derive(Collectable)]
+/// struct Span<'src>
+/// where
+///     'src: 'static,
+/// {
+///     text: &'src str,
+///     parent: Option<Gc<Span<'src>>>,
+/// }
+/// ```
+///
+/// # Bounds
+///
+/// The inferred bounds may be too strict, for instance when a field's type is only
+/// `Collectable` for some of its parameters.
+/// They can be replaced with `#[collectable(bound = "...")]`: on the type itself, this
+/// replaces every inferred bound, while on a field it replaces only the bounds inferred from
+/// that field.
+///
+/// ```
+/// use dumpster::{Collectable, Visitor};
+///
+/// /// A map whose keys can never hold a `Gc`.
+/// struct KeyedMap<K, V> {
+///     keys: Vec<K>,
+///     values: Vec<V>,
+/// }
+///
+/// unsafe impl<K, V: Collectable> Collectable for KeyedMap<K, V> {
+///     fn accept<Z: Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+///         self.values.accept(visitor)
+///     }
+/// }
+///
+/// #[derive(Collectable)]
+/// #[collectable(bound = "V: Collectable")]
+/// struct Registry<K, V> {
+///     entries: KeyedMap<K, V>,
+/// }
+///
+/// #[derive(Collectable)]
+/// struct Index<K, V> {
+///     #[collectable(bound = "V: Collectable")]
+///     entries: KeyedMap<K, V>,
+///     len: usize,
+/// }
+/// ```
+///
+/// # Custom tracing
+///
+/// A field whose type does not implement `Collectable`, and which cannot implement it because
+/// of the orphan rule, may name a module to trace it with `#[collectable(unsafe_with = "...")]`.
+/// The module must contain a function `accept` with the same signature as
+/// [`Collectable::accept`], taking a reference to the field as its first argument.
+/// Such a field imposes no inferred bounds.
+///
+/// ## Safety
+///
+/// The derived implementation trusts the `accept` function as much as a hand-written
+/// implementation of [`Collectable::accept`], which is why the attribute is prefixed with
+/// `unsafe_`.
+/// The function must visit every garbage-collected pointer owned by the field exactly once, and
+/// must not trace through a shared pointer such as an [`Rc`](std::rc::Rc) or an
+/// [`Arc`](std::sync::Arc), whose pointee may be co-owned by handles the collector knows nothing
+/// about.
+/// Visiting a pointer twice, or one the field does not own, makes the collector free allocations
+/// which are still reachable.
+/// Missing a pointer only makes the collector treat whatever it points to as reachable, leaking
+/// it.
+///
+/// ```
+/// use dumpster::{unsync::Gc, Collectable, Visitor};
+/// use std::panic::AssertUnwindSafe;
+///
+/// mod unwind_safe {
+///     use dumpster::{Collectable, Visitor};
+///     use std::panic::AssertUnwindSafe;
+///
+///     pub fn accept<T: Collectable, V: Visitor>(
+///         field: &AssertUnwindSafe<T>,
+///         visitor: &mut V,
+///     ) -> Result<(), ()> {
+///         field.0.accept(visitor)
+///     }
+/// }
+///
+/// #[derive(Collectable)]
+/// struct Task {
+///     #[collectable(unsafe_with = "unwind_safe")]
+///     parent: AssertUnwindSafe<Option<Gc<Task>>>,
+/// }
+/// ```
+///
+/// # Re-exports
+///
+/// The derived implementation refers to the `dumpster` crate by name.
+/// If `dumpster` is only available through a re-export, its path can be given with
+/// `#[collectable(crate = "...")]`.
+///
+/// ```
+/// # mod mygame { pub mod gc { pub use dumpster::*; } }
+/// use mygame::gc::{unsync::Gc, Collectable};
+///
+/// #[derive(Collectable)]
+/// #[collectable(crate = "mygame::gc")]
+/// struct Player {
+///     target: Option<Gc<Player>>,
+/// }
+/// ```
+///
+/// # Finalizers
+///
+/// `#[collectable(noop_finalize)]` on the type also implements [`Finalize`] for it, with a
+/// finalizer that does nothing.
+///
+/// ```
+/// use dumpster::{unsync::Gc, Collectable};
+///
+/// #[derive(Collectable)]
+/// #[collectable(noop_finalize)]
+/// struct Leaf(u8);
+///
+/// let leaf = Gc::new_finalized(Leaf(0));
+/// ```
+///
+/// # Leaves
+///
+/// The derived implementation never sets [`Collectable::IS_LEAF`] on its own.
+/// `#[collectable(leaf)]` on the type sets it to `true` when every traced field is a leaf, so that
+/// containers of the type skip tracing it.
+/// Fields skipped with `#[collectable(unsafe_skip)]` count as leaves, while fields visited with
+/// `#[collectable(unsafe_with = "...")]` and fields whose type names the type being derived never
+/// do.
+///
+/// ```
+/// use dumpster::Collectable;
+///
+/// #[derive(Collectable)]
+/// #[collectable(leaf)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// assert!(<Vec<Point> as Collectable>::IS_LEAF);
+/// ```
+///
+/// Types which contain each other through owned containers, rather than through a `Gc`, would make
+/// their flags depend on each other, which the compiler rejects as a cycle, so at most one of them
+/// may be marked as a leaf.
+///
+/// # Skipping fields
+///
+/// A field whose type cannot implement `Collectable`, such as a raw pointer or an FFI handle,
+/// may be left out of the derived implementation with `#[collectable(unsafe_skip)]`.
+/// This is a safety claim: the skipped field must never own a `Gc`, either directly or through
+/// some indirection.
+/// If it does, the garbage collector will free allocations which are still reachable through
+/// it.
+///
+/// ```
+/// use dumpster::{unsync::Gc, Collectable};
+/// use std::ffi::c_void;
+///
+/// #[derive(Collectable)]
+/// struct Window {
+///     #[collectable(unsafe_skip)]
+///     handle: *mut c_void,
+///     parent: Option<Gc<Window>>,
+/// }
+/// ```
+///
+/// Fields which plainly contain a `Gc` cannot be skipped.
+///
+/// ```compile_fail
+/// use dumpster::{unsync::Gc, Collectable};
+///
+/// #[derive(Collectable)]
+/// struct Node {
+///     #[collectable(unsafe_skip)]
+///     next: Option<Gc<Node>>,
+/// }
 /// ```
 pub use dumpster_derive::Collectable;
 
@@ -357,6 +910,9 @@ fn contains_gcs<T: Collectable + ?Sized>(x: &T) -> Result<bool, ()> {
         }
     }
 
+    if T::IS_LEAF {
+        return Ok(false);
+    }
     let mut visit = ContainsGcs(false);
     x.accept(&mut visit)?;
     Ok(visit.0)
@@ -0,0 +1,44 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Control over when collections may be started automatically.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// When dropping a `Gc` may start a collection, as set for a thread by
+/// [`unsync::set_collection_mode`](crate::unsync::set_collection_mode) or
+/// [`sync::set_collection_mode`](crate::sync::set_collection_mode).
+pub enum CollectionMode {
+    #[default]
+    /// Dropping a `Gc` runs a collection as soon as the collect condition says so.
+    Automatic,
+    /// Dropping a `Gc` never runs a collection.
+    ///
+    /// Instead, when the collect condition says that a collection is due, that is remembered
+    /// until the thread next calls `safe_point`, which then runs the collection.
+    /// Explicit calls to `collect` are not affected.
+    AtSafePointsOnly,
+    /// Dropping a `Gc` never runs a collection, nor waits for one.
+    ///
+    /// Instead, when the collect condition says that a collection is due, a request is queued,
+    /// which is serviced by the next call to `service_collections`, or by the background
+    /// collector of the `sync` module if one is running.
+    /// Requests made before the queued collection starts are coalesced into it, so they never
+    /// pile up.
+    /// Explicit calls to `collect` are not affected.
+    Queued,
+}
@@ -31,7 +31,16 @@ use std::{
 /// interpretation.
 /// We trust that all pointers (even to `?Sized` or `dyn` types) are 2 words or fewer in size.
 /// This is a hack! Like, a big hack!
-pub(crate) struct Erased([usize; 2]);
+///
+/// The words are stored as pointers rather than integers so that the address keeps its provenance
+/// while it is erased; an integer cannot carry provenance, and a pointer rebuilt from one may not
+/// be dereferenced.
+pub(crate) struct Erased([*const (); 2]);
+
+// SAFETY: an erased pointer is only data until it is specified, and whoever specifies it is
+// responsible for the pointee being safe to access from their thread.
+unsafe impl Send for Erased {}
+unsafe impl Sync for Erased {}
 
 impl Erased {
     /// Construct a new erased pointer to some data from a reference
@@ -42,7 +51,7 @@ impl Erased {
     /// `ErasedPtr`.
     /// To my knowledge, there are no pointer types with this property.
     pub fn new<T: ?Sized>(reference: NonNull<T>) -> Erased {
-        let mut ptr = Erased([0; 2]);
+        let mut ptr = Erased([std::ptr::null(); 2]);
         let ptr_size = size_of::<NonNull<T>>();
         // Extract out the pointer as raw memory
         assert!(
@@ -114,6 +123,7 @@ impl<T: ?Sized> Nullable<T> {
         }
     }
 
+    #[cfg(not(feature = "tagged-ptr"))]
     #[allow(clippy::unused_self)]
     /// Convert this pointer to a null pointer.
     pub fn as_null(self) -> Nullable<T> {
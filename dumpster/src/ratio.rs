@@ -0,0 +1,61 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The adaptive ratio of dropped to existing `Gc`s at which the default collect conditions start
+//! a collection.
+//!
+//! After each collection the ratio is doubled if the collection freed little of what it examined,
+//! and halved if it freed most of it, much as tracing collectors grow and shrink their heaps.
+//! The ratio is kept between [`MIN_RATIO`] and [`MAX_RATIO`], so that every collection is still
+//! paid for by a number of drops proportional to the number of `Gc`s it may have to examine.
+
+/// The ratio with which the default conditions start, which collects once more `Gc`s have been
+/// dropped than exist.
+pub(crate) const INITIAL_RATIO: f64 = 1.0;
+
+/// The smallest ratio to which a run of productive collections may shrink the ratio.
+const MIN_RATIO: f64 = 0.25;
+
+/// The largest ratio to which a run of unproductive collections may grow the ratio.
+const MAX_RATIO: f64 = 16.0;
+
+/// The factor by which the ratio grows or shrinks after each collection.
+const STEP: f64 = 2.0;
+
+#[must_use]
+/// Compute the ratio to use after a collection which examined `examined` allocations and freed
+/// `freed` of them.
+///
+/// A collection which frees under a quarter of what it examines grows the ratio, and one which
+/// frees over half of it shrinks the ratio.
+pub(crate) fn adapt(ratio: f64, examined: usize, freed: usize) -> f64 {
+    if freed.saturating_mul(4) <= examined {
+        (ratio * STEP).min(MAX_RATIO)
+    } else if freed.saturating_mul(2) > examined {
+        (ratio / STEP).max(MIN_RATIO)
+    } else {
+        ratio
+    }
+}
+
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+/// Determine whether more than `ratio` times as many `Gc`s have been `dropped` as are `existing`.
+pub(crate) fn exceeds(dropped: usize, existing: usize, ratio: f64) -> bool {
+    dropped as f64 > existing as f64 * ratio
+}
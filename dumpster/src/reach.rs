@@ -0,0 +1,118 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Queries about which allocations can be reached from which.
+
+use std::collections::HashSet;
+
+use crate::{sync, unsync, Collectable, Visitor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// An identifier for a garbage-collected allocation, as returned by
+/// [`unsync::path_from`](crate::unsync::path_from) and [`sync::path_from`](crate::sync::path_from).
+///
+/// Two `Gc`s have the same identifier exactly when they point to the same allocation, for as long
+/// as that allocation exists.
+pub struct GcId(usize);
+
+impl GcId {
+    #[must_use]
+    /// Get the address of the value in the allocation, as given by
+    /// [`unsync::Gc::as_ptr`](crate::unsync::Gc::as_ptr) or
+    /// [`sync::Gc::as_ptr`](crate::sync::Gc::as_ptr).
+    pub fn addr(self) -> usize {
+        self.0
+    }
+
+    /// Get the identifier of the allocation holding `value`.
+    pub(crate) fn of<T: ?Sized>(value: &T) -> GcId {
+        GcId(std::ptr::from_ref(value).cast::<()>() as usize)
+    }
+}
+
+/// A visitor which searches the graph of allocations for a path to one allocation.
+///
+/// The search only reads the allocations it passes through, without marking them as accessed or
+/// otherwise changing the state of the collector.
+pub(crate) struct PathFinder {
+    /// The allocation being searched for.
+    target: GcId,
+    /// Every allocation which has been entered so far.
+    visited: HashSet<GcId>,
+    /// The allocations on the way from the root to the one currently being searched.
+    path: Vec<GcId>,
+    /// Whether `target` has been found, in which case `path` ends with it.
+    found: bool,
+}
+
+impl PathFinder {
+    /// Find a path from the allocation holding `root` to `target`, where `root` and `target` are
+    /// both values stored in garbage-collected allocations.
+    ///
+    /// The path starts with `root` and ends with `target`.
+    pub fn search<T: Collectable + ?Sized>(root: &T, target: GcId) -> Option<Vec<GcId>> {
+        let mut finder = PathFinder {
+            target,
+            visited: HashSet::new(),
+            path: Vec::new(),
+            found: false,
+        };
+        finder.enter(root);
+        finder.found.then_some(finder.path)
+    }
+
+    /// Search through `value`, which is stored in a garbage-collected allocation, unless it has
+    /// already been searched.
+    fn enter<T: Collectable + ?Sized>(&mut self, value: &T) {
+        let id = GcId::of(value);
+        if self.found || !self.visited.insert(id) {
+            return;
+        }
+        self.path.push(id);
+        if id == self.target {
+            self.found = true;
+            return;
+        }
+        // a value which cannot be traced right now is treated as though it owns no `Gc`s, and
+        // the parents of every value on the path are still being traced, so none can be freed
+        let _ = value.accept(self);
+        if !self.found {
+            self.path.pop();
+        }
+    }
+}
+
+impl Visitor for PathFinder {
+    fn visit_sync<T>(&mut self, gc: &sync::Gc<T>)
+    where
+        T: Collectable + Send + Sync + ?Sized,
+    {
+        if let Some(value) = sync::Gc::peek(gc) {
+            self.enter(value);
+        }
+    }
+
+    fn visit_unsync<T>(&mut self, gc: &unsync::Gc<T>)
+    where
+        T: Collectable + ?Sized,
+    {
+        if let Some(value) = unsync::Gc::try_deref(gc) {
+            self.enter(value);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Implementing [`Collectable`](crate::Collectable) for types which cannot be annotated with
+//! `#[derive(Collectable)]`.
+
+/// Implement [`Collectable`](crate::Collectable) for a type defined elsewhere, by listing the
+/// fields which may own a garbage-collected pointer.
+///
+/// This is meant for types which no derive can be put on, such as code generated by a build script
+/// or by another macro, or types from a module which is vendored unchanged.
+/// The macro is written as `unsafe impl`, followed by the type's path and a list of field accessors
+/// in braces, each of which is a field name optionally followed by more field names or tuple
+/// indices, as in `parent.inner` or `0.children`.
+/// The implementation visits each listed field, in order, so every field must be accessible from
+/// where the macro is invoked and its type must itself be `Collectable`.
+///
+/// A generic type is written after `unsafe impl<...>`, naming its type parameters, and may be
+/// followed by a `where` clause giving their bounds.
+/// A type which never owns a garbage-collected pointer is written after `unsafe impl leaf`, and
+/// its implementation visits nothing.
+///
+/// The orphan rule still applies to the implementation the macro expands to.
+/// To implement `Collectable` for a type from another crate, wrap it in a local newtype and list
+/// its fields through the newtype, as in `0.children`.
+///
+/// # Safety
+///
+/// As with a hand-written implementation of [`Collectable::accept`](crate::Collectable::accept),
+/// the list of fields is trusted, which is why the macro must be invoked with `unsafe`.
+///
+/// - No field may be listed twice, and no two listed fields may share a garbage-collected pointer,
+///   or the collector will free allocations which are still reachable.
+/// - A field accessor must not dereference through a shared pointer such as an
+///   [`Rc`](std::rc::Rc) or an [`Arc`](std::sync::Arc), since whatever it reaches is co-owned
+///   by other handles the collector knows nothing about.
+/// - A type written after `leaf` must never own a garbage-collected pointer at all.
+///
+/// A field which owns a garbage-collected pointer but is left out of the list only makes the
+/// collector treat whatever it points to as reachable, leaking it.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{impl_collectable, unsync::Gc};
+/// use std::cell::RefCell;
+///
+/// mod generated {
+///     use dumpster::unsync::Gc;
+///     use std::cell::RefCell;
+///
+///     pub struct Link<T> {
+///         pub next: RefCell<Option<Gc<Node>>>,
+///         pub weight: T,
+///     }
+///
+///     pub struct Node {
+///         pub name: String,
+///         pub out: Link<u32>,
+///         pub label: Label,
+///     }
+///
+///     pub struct Label(pub String);
+/// }
+///
+/// // SAFETY: `out.next` is the only field of `Node` which can own a `Gc`.
+/// impl_collectable!(unsafe impl generated::Node { out.next });
+/// // SAFETY: `weight` is `'static` plain data, so `next` is the only field which can own a `Gc`.
+/// impl_collectable!(unsafe impl<T> generated::Link<T> { next } where T: 'static);
+/// // SAFETY: `Label` only holds a `String`.
+/// impl_collectable!(unsafe impl leaf generated::Label);
+///
+/// let node = Gc::new(generated::Node {
+///     name: "a".into(),
+///     out: generated::Link {
+///         next: RefCell::new(None),
+///         weight: 1,
+///     },
+///     label: generated::Label("first".into()),
+/// });
+/// *node.out.next.borrow_mut() = Some(node.clone());
+/// drop(node);
+/// dumpster::unsync::collect();
+/// ```
+///
+/// The macro does not accept an invocation without `unsafe`:
+///
+/// ```compile_fail
+/// use dumpster::{impl_collectable, unsync::Gc};
+///
+/// pub struct Node {
+///     pub next: Option<Gc<Node>>,
+/// }
+///
+/// impl_collectable!(Node { next });
+/// ```
+#[macro_export]
+macro_rules! impl_collectable {
+    (unsafe impl<$($param:ident),* $(,)?> leaf $ty:ty $(where $($bound:tt)+)?) => {
+        unsafe impl<$($param),*> $crate::Collectable for $ty $(where $($bound)+)? {
+            #[inline]
+            fn accept<V: $crate::Visitor>(&self, _: &mut V) -> ::core::result::Result<(), ()> {
+                ::core::result::Result::Ok(())
+            }
+
+            const IS_LEAF: bool = true;
+        }
+    };
+    (unsafe impl leaf $ty:ty $(where $($bound:tt)+)?) => {
+        $crate::impl_collectable!(unsafe impl<> leaf $ty $(where $($bound)+)?);
+    };
+    (
+        unsafe impl<$($param:ident),* $(,)?> $ty:ty {
+            $($head:tt $(. $tail:tt)*),* $(,)?
+        }
+        $(where $($bound:tt)+)?
+    ) => {
+        unsafe impl<$($param),*> $crate::Collectable for $ty $(where $($bound)+)? {
+            #[inline]
+            fn accept<V: $crate::Visitor>(
+                &self,
+                visitor: &mut V,
+            ) -> ::core::result::Result<(), ()> {
+                $($crate::Collectable::accept(&self.$head $(.$tail)*, visitor)?;)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    };
+    (unsafe impl $ty:ty { $($fields:tt)* } $(where $($bound:tt)+)?) => {
+        $crate::impl_collectable!(unsafe impl<> $ty { $($fields)* } $(where $($bound)+)?);
+    };
+}
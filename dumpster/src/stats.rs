@@ -0,0 +1,60 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Cumulative statistics about garbage collection.
+
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// A snapshot of statistics about the garbage collector, as returned by
+/// [`unsync::stats`](crate::unsync::stats) and [`sync::stats`](crate::sync::stats).
+///
+/// The cumulative counters count everything since the program started, or since the last call to
+/// the matching `reset_stats`.
+/// Allocations which are freed because their reference count reached zero are not counted as freed
+/// by a collection.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect, reset_stats, stats, Gc};
+///
+/// reset_stats();
+/// let gc = Gc::new(0);
+/// drop(gc.clone());
+/// collect();
+///
+/// let stats = stats();
+/// assert_eq!(stats.collections, 1);
+/// assert_eq!(stats.live_allocations, 1);
+/// ```
+pub struct GcStats {
+    /// The number of collections which have run to completion.
+    pub collections: usize,
+    /// The total number of allocations freed by collections.
+    pub allocations_freed: usize,
+    /// The total size, in bytes, of the allocations freed by collections.
+    pub bytes_freed: usize,
+    /// The number of allocations which currently exist.
+    pub live_allocations: usize,
+    /// The total size, in bytes, of the allocations which currently exist.
+    pub live_bytes: usize,
+    /// The total time spent collecting.
+    pub time_collecting: Duration,
+}
@@ -0,0 +1,284 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! An optional background thread which runs collections on behalf of the rest of the program.
+
+use std::{
+    cell::Cell,
+    io,
+    panic::resume_unwind,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread::{self, JoinHandle},
+};
+
+use parking_lot::{Condvar, Mutex, MutexGuard};
+
+#[cfg(feature = "epoch")]
+use super::collect::drain_retired;
+use super::collect::{collect_all, deliver_dumpster, n_gcs_dropped};
+
+#[derive(Clone, Debug)]
+/// Configuration for the background collector started by [`spawn_collector`].
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::CollectorConfig;
+///
+/// let config = CollectorConfig::new().thread_name("gc").max_pending(10_000);
+/// ```
+pub struct CollectorConfig {
+    /// The name of the collector thread.
+    thread_name: String,
+    /// The number of [`Gc`](super::Gc)s which may be dropped without being collected before
+    /// dropping threads are made to wait for the collector.
+    max_pending: usize,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        CollectorConfig {
+            thread_name: String::from("dumpster-collector"),
+            max_pending: 1 << 20,
+        }
+    }
+}
+
+impl CollectorConfig {
+    #[must_use]
+    /// Construct the default configuration for a background collector.
+    pub fn new() -> CollectorConfig {
+        CollectorConfig::default()
+    }
+
+    #[must_use]
+    /// Set the name of the collector thread.
+    ///
+    /// By default, the thread is named `dumpster-collector`.
+    pub fn thread_name(mut self, name: impl Into<String>) -> CollectorConfig {
+        self.thread_name = name.into();
+        self
+    }
+
+    #[must_use]
+    /// Set the number of [`Gc`](super::Gc)s which may be dropped since the last collection before
+    /// a thread dropping another one is blocked until the collector catches up.
+    ///
+    /// This bounds the amount of garbage that can pile up when the program produces garbage
+    /// faster than the collector can clean it.
+    /// By default, this is 2<sup>20</sup>.
+    pub fn max_pending(mut self, max_pending: usize) -> CollectorConfig {
+        self.max_pending = max_pending;
+        self
+    }
+}
+
+/// The state shared between the collector thread and the rest of the program.
+struct State {
+    /// Whether a collection has been requested since the collector last started one.
+    requested: bool,
+    /// Whether the collector should shut down.
+    shutdown: bool,
+    /// The number of collections the collector has finished.
+    n_collections: usize,
+    /// The handle to the collector thread, if it is running.
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Whether a background collector is running.
+/// This is checked every time a [`Gc`](super::Gc) is dropped, so it is kept outside of `STATE`.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// The configured value of [`CollectorConfig::max_pending`].
+static MAX_PENDING: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// The state of the background collector.
+static STATE: Mutex<State> = Mutex::new(State {
+    requested: false,
+    shutdown: false,
+    n_collections: 0,
+    handle: None,
+});
+/// Notified when the collector should wake up.
+static WAKE: Condvar = Condvar::new();
+/// Notified every time the collector finishes a collection.
+static DONE: Condvar = Condvar::new();
+
+thread_local! {
+    /// Whether the current thread is the background collector.
+    static IS_COLLECTOR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Start a background thread which runs all automatically-triggered collections.
+///
+/// Without a background collector, a collection is run on whichever thread happens to drop the
+/// [`Gc`](super::Gc) that makes the collect condition fire, which can add unpredictable latency to
+/// that thread.
+/// Once a background collector is running, dropping a `Gc` only hands the dropping thread's
+/// garbage over to the collector when the condition fires, and the collector thread does the
+/// actual work.
+/// Explicit calls to [`collect`](super::collect) still collect on the calling thread.
+///
+/// If garbage is dropped faster than the collector can keep up with, so that more than
+/// [`CollectorConfig::max_pending`] `Gc`s have been dropped since the last collection, dropping
+/// threads are blocked until the collector finishes its next collection.
+/// Threads in [`CollectionMode::Queued`](crate::CollectionMode::Queued) are never blocked, and
+/// the collector runs the collections they queue.
+///
+/// # Errors
+///
+/// This function returns an error if a background collector is already running, or if the
+/// collector thread could not be spawned.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{shutdown_collector, spawn_collector, CollectorConfig};
+///
+/// spawn_collector(CollectorConfig::new())?;
+///
+/// // ... do work with `Gc`s ...
+///
+/// shutdown_collector();
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn spawn_collector(config: CollectorConfig) -> io::Result<()> {
+    let mut state = STATE.lock();
+    if state.handle.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a background collector is already running",
+        ));
+    }
+    let handle = thread::Builder::new()
+        .name(config.thread_name)
+        .spawn(run_collector)?;
+    state.requested = false;
+    state.shutdown = false;
+    state.handle = Some(handle);
+    MAX_PENDING.store(config.max_pending, Ordering::Relaxed);
+    RUNNING.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Stop the background collector started by [`spawn_collector`], waiting for it to finish a last
+/// collection and exit.
+///
+/// Afterward, collections are once again run on the thread which triggers them.
+/// If no background collector is running, this does nothing.
+///
+/// This must not be called from a `Drop` implementation of a value being collected, since the
+/// collector would then wait on itself.
+///
+/// # Panics
+///
+/// If the collector thread panicked, this function resumes the panic on the calling thread.
+pub fn shutdown_collector() {
+    let handle = {
+        let mut state = STATE.lock();
+        let Some(handle) = state.handle.take() else {
+            return;
+        };
+        RUNNING.store(false, Ordering::Release);
+        state.shutdown = true;
+        WAKE.notify_one();
+        handle
+    };
+    if let Err(panic) = handle.join() {
+        resume_unwind(panic);
+    }
+}
+
+/// The main loop of the collector thread.
+fn run_collector() {
+    IS_COLLECTOR.with(|c| c.set(true));
+    let mut state = STATE.lock();
+    loop {
+        while !state.requested && !state.shutdown {
+            WAKE.wait(&mut state);
+        }
+        state.requested = false;
+        let shutdown = state.shutdown;
+        MutexGuard::unlocked(&mut state, || {
+            collect_all();
+            // the garbage is destroyed here, rather than by the threads which requested collection
+            #[cfg(feature = "epoch")]
+            drain_retired();
+        });
+        state.n_collections = state.n_collections.wrapping_add(1);
+        DONE.notify_all();
+        if shutdown {
+            return;
+        }
+    }
+}
+
+#[cfg(feature = "epoch")]
+/// Determine whether a background collector is running on some other thread, which then destroys
+/// all garbage.
+pub(super) fn collector_elsewhere() -> bool {
+    RUNNING.load(Ordering::Acquire) && !IS_COLLECTOR.with(Cell::get)
+}
+
+/// Wake the background collector, if there is one, to run a collection which was queued by a
+/// thread in [`CollectionMode::Queued`](crate::CollectionMode::Queued), without waiting for it.
+///
+/// The collector's state is only ever locked briefly, so this never waits on a collection.
+pub(super) fn wake_collector() {
+    if !RUNNING.load(Ordering::Acquire) || IS_COLLECTOR.with(Cell::get) {
+        return;
+    }
+    let mut state = STATE.lock();
+    if state.handle.is_some() {
+        state.requested = true;
+        WAKE.notify_one();
+    }
+}
+
+/// Hand the work of a collection off to the background collector, if there is one.
+///
+/// `triggered` is whether the collect condition has fired.
+/// Returns `false` if there is no background collector, in which case the caller must collect
+/// for itself.
+pub(super) fn offload_collection(triggered: bool) -> bool {
+    if !RUNNING.load(Ordering::Acquire) {
+        return false;
+    }
+    if IS_COLLECTOR.with(Cell::get) {
+        // the collector delivers its own garbage before every collection
+        return true;
+    }
+    let overfull = n_gcs_dropped() > MAX_PENDING.load(Ordering::Relaxed);
+    if !triggered && !overfull {
+        return true;
+    }
+
+    deliver_dumpster();
+    let mut state = STATE.lock();
+    if state.handle.is_none() {
+        // the collector was shut down while we weren't looking
+        return false;
+    }
+    state.requested = true;
+    WAKE.notify_one();
+    if overfull {
+        let n_collections = state.n_collections;
+        while state.n_collections == n_collections {
+            DONE.wait(&mut state);
+        }
+    }
+    true
+}
@@ -19,30 +19,70 @@
 //! A synchronized collection algorithm.
 
 use std::{
-    alloc::{dealloc, Layout},
+    alloc::Layout,
     cell::{Cell, RefCell},
-    collections::{hash_map::Entry, HashMap},
-    mem::{replace, swap, take, transmute},
-    ptr::{drop_in_place, NonNull},
-    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+    collections::{
+        hash_map::{Entry, VacantEntry},
+        HashMap, HashSet,
+    },
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem::{replace, swap, take},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    ptr::{addr_of, NonNull},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use parking_lot::{Mutex, RwLock};
 
 use once_cell::sync::Lazy;
 
-use crate::{ptr::Erased, Collectable, Visitor};
+#[cfg(feature = "heap-dump")]
+use crate::dot::{value_addr, write_dot, DotNode, EdgeFinder};
+#[cfg(feature = "epoch")]
+use crate::unwind::drop_retired;
+use crate::{
+    hash::BuildPtrHasher,
+    instrument::{dirty_table_full, trace_error, CollectSpan},
+    ptr::Erased,
+    ratio,
+    unwind::{drop_collected, resume_caught},
+    Collectable, CollectionMode, DryRunReport, GarbageAllocation, GcStats, Visitor,
+};
+#[cfg(feature = "leak-detection")]
+use crate::{LeakReport, LeakedAllocation};
+
+use super::{
+    background::{offload_collection, wake_collector},
+    free_box,
+    tag::TAGS_MAY_ALIAS,
+    BoxedCollectCondition, CollectCondition, CollectHooks, CollectInfo, CollectResult, Finalizer,
+    Gc, GcBox, CURRENT_TAG, DIRTY, FINALIZER, NEXT_SERIAL, ROOTED, SERIAL,
+};
+
+#[cfg(feature = "epoch")]
+mod epoch;
+#[cfg(feature = "parallel-collect")]
+mod parallel;
 
-use super::{default_collect_condition, CollectCondition, CollectInfo, Gc, GcBox, CURRENT_TAG};
+#[cfg(feature = "epoch")]
+pub use epoch::drain_retired;
+
+#[cfg(feature = "parallel-collect")]
+pub use parallel::set_collect_workers;
 
 /// The garbage truck, which is a global data structure containing information about allocations
 /// which might need to be collected.
 struct GarbageTruck {
     /// The contents of the garbage truck, containing all the allocations which need to be
     /// collected and have already been delivered by a [`Dumpster`].
-    contents: Mutex<HashMap<AllocationId, TrashCan>>,
+    contents: Mutex<AllocationMap<TrashCan>>,
     /// A lock used for synchronizing threads that are awaiting completion of a collection process.
-    /// This lock should be acquired for reads by threads running a collection and for writes by
+    /// This lock should be acquired for writes by threads running a collection and for reads by
     /// threads awaiting collection completion.
     collecting_lock: RwLock<()>,
     /// The number of [`Gc`]s dropped since the last time [`Dumpster::collect_all()`] was called.
@@ -50,24 +90,114 @@ struct GarbageTruck {
     /// The number of [`Gc`]s currently existing (which have not had their internals replaced with
     /// `None`).
     n_gcs_existing: AtomicUsize,
+    /// The number of allocations which currently exist.
+    n_allocations: AtomicUsize,
+    /// The total size, in bytes, of all allocations which currently exist.
+    heap_bytes: AtomicUsize,
+    /// The number of collections finished since statistics were last reset.
+    n_collections: AtomicUsize,
+    /// The number of allocations freed by collections since statistics were last reset.
+    total_freed: AtomicUsize,
+    /// The number of bytes freed by collections since statistics were last reset.
+    total_bytes_freed: AtomicUsize,
+    /// The time, in nanoseconds, spent collecting since statistics were last reset.
+    nanos_collecting: AtomicU64,
+    /// The bits of the ratio of dropped to existing [`Gc`]s at which the default collect condition
+    /// starts a collection, which adapts to how much each collection frees.
+    drop_ratio: AtomicU64,
+    /// The hooks to call around each collection.
+    hooks: Mutex<Option<Arc<CollectHooks>>>,
     /// The function which determines whether a collection should be triggered.
-    /// This pointer value should always be cast to a [`CollectCondition`], but since `AtomicPtr`
-    /// doesn't handle function pointers correctly, we just cast to `*mut ()`.
-    collect_condition: AtomicPtr<()>,
+    collect_condition: RwLock<CollectCondition>,
+    /// The collection mode of threads which have not set their own.
+    default_mode: RwLock<CollectionMode>,
+    /// Whether a thread in [`CollectionMode::Queued`] has requested a collection which has not
+    /// started yet.
+    /// Any number of requests made before the next collection starts are serviced by it.
+    collection_requested: AtomicBool,
+    /// The number of allocations which the truck and the scratch structures of a collection are
+    /// created to hold.
+    reserved: AtomicUsize,
+    /// Whether collections destroy garbage in the order in which it was allocated.
+    deterministic: AtomicBool,
+    /// The finalizers of the allocations which have one that has not been run yet.
+    finalizers: Mutex<AllocationMap<Finalizer>>,
+    /// The number of [`Root`](super::Root)s to each allocation which has any.
+    roots: Mutex<AllocationMap<usize>>,
+    /// The serial numbers of the allocations made while collections were deterministic.
+    serials: Mutex<AllocationMap<u64>>,
+    /// The tables left over from the last collection, emptied and ready to be reused.
+    scratch: Mutex<Scratch>,
+}
+
+/// The number of collections after which the scratch tables of the truck are shrunk to fit the
+/// largest of those collections.
+const SHRINK_INTERVAL: usize = 64;
+
+/// The most times a collection searches the heap again because some `Gc`s had ambiguous tags.
+/// Whatever is still ambiguous after that is left for the next collection.
+const MAX_TAG_RETRIES: usize = 4;
+
+/// The number of `Gc`s a thread makes or drops between adding its counts to the garbage truck.
+const FLUSH_INTERVAL: usize = 64;
+
+#[derive(Clone, Copy)]
+/// The changes to the counts of existing and dropped `Gc`s made on one thread which have yet to be
+/// added to the garbage truck.
+struct UnflushedCounts {
+    /// The change in the number of existing `Gc`s, which wraps around if it is negative.
+    existing: usize,
+    /// The number of `Gc`s dropped.
+    dropped: usize,
+    /// The number of `Gc`s made with `Gc::new` or dropped, which does not include clones.
+    n_ops: usize,
+}
+
+impl UnflushedCounts {
+    /// Counts with no changes in them.
+    const ZERO: UnflushedCounts = UnflushedCounts {
+        existing: 0,
+        dropped: 0,
+        n_ops: 0,
+    };
+}
+
+#[derive(Default)]
+/// The tables needed by every collection, which are kept between collections so that a large heap
+/// does not pay to allocate them again each time it is collected.
+struct Scratch {
+    /// An empty table to take the place of the contents of the truck while they are collected.
+    cans: AllocationMap<TrashCan>,
+    /// An empty reference graph.
+    graph: AllocationMap<AllocationInfo>,
+    /// An empty list of the allocations to destroy.
+    condemned: Vec<Condemned>,
+    /// The largest reference graph built since the tables were last shrunk.
+    high_water: usize,
+    /// The number of collections since the tables were last shrunk.
+    n_uses: usize,
 }
 
 /// A structure containing the global information for the garbage collector.
 struct Dumpster {
     /// A lookup table for the allocations which may need to be cleaned up later.
-    contents: RefCell<HashMap<AllocationId, TrashCan>>,
+    contents: RefCell<AllocationMap<TrashCan>>,
     /// The number of times an allocation on this thread has been dropped.
     n_drops: Cell<usize>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 /// A unique identifier for an allocation.
 struct AllocationId(NonNull<GcBox<()>>);
 
+/// A table keyed by allocation, hashed by address.
+type AllocationMap<V> = HashMap<AllocationId, V, BuildPtrHasher>;
+
+/// Construct an empty [`AllocationMap`] with room for at least `capacity` allocations.
+fn allocation_map<V>(capacity: usize) -> AllocationMap<V> {
+    HashMap::with_capacity_and_hasher(capacity, BuildPtrHasher)
+}
+
 #[derive(Debug)]
 /// The information which describes an allocation that may need to be cleaned up later.
 struct TrashCan {
@@ -75,7 +205,7 @@ struct TrashCan {
     ptr: Erased,
     /// The function which can be used to build a reference graph.
     /// This function is safe to call on `ptr`.
-    dfs_fn: unsafe fn(Erased, &mut HashMap<AllocationId, AllocationInfo>),
+    dfs_fn: unsafe fn(Erased, &mut Builder<'_>),
 }
 
 #[derive(Debug)]
@@ -86,6 +216,11 @@ struct AllocationInfo {
     /// Function for dropping the allocation when its weak and strong count hits zero.
     /// Should have the same behavior as dropping a Gc normally to a reference count of zero.
     weak_drop_fn: unsafe fn(Erased),
+    /// The function which can be used to build a reference graph from this allocation again, if
+    /// it must be checked once more.
+    dfs_fn: unsafe fn(Erased, &mut Builder<'_>),
+    /// The function which describes the allocation in a [`DryRunReport`].
+    describe_fn: unsafe fn(Erased) -> GarbageAllocation,
     /// Information about this allocation's reachability.
     reachability: Reachability,
 }
@@ -102,22 +237,69 @@ enum Reachability {
         /// It is the difference between the allocations indegree in the "true" reference graph vs
         /// the one we are currently building.
         n_unaccounted: usize,
-        /// A function used to destroy the allocation.
-        destroy_fn: unsafe fn(Erased, &HashMap<AllocationId, AllocationInfo>),
+        /// A function used to destroy the allocation, along with others of the same type.
+        destroy_fn: DestroyFn,
     },
     /// The allocation here is reachable.
     /// No further information is needed.
     Reachable,
 }
 
+/// A function which runs one pass of destroying a batch of condemned allocations, all of the same
+/// type.
+type DestroyFn = unsafe fn(&[Condemned], DestroyPass);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A pass over the condemned allocations of a collection.
+///
+/// Releasing the references inside a condemned allocation looks at the header of every allocation
+/// they point to, so every condemned allocation is released before any of them is freed.
+enum DestroyPass {
+    /// Release every reference inside the allocations, killing the ones to condemned allocations.
+    Release,
+    /// Drop and free the allocations.
+    Free,
+}
+
+/// A reachable allocation whose last reference was held by a reference graph, with the function
+/// which destroys it.
+type WeakDestroy = (AllocationId, unsafe fn(Erased), Erased);
+
+#[derive(Clone, Copy)]
+/// An unreachable allocation which a collection is about to destroy.
+struct Condemned {
+    /// The function which destroys allocations of the same type as this one.
+    destroy_fn: DestroyFn,
+    /// An erased pointer to the allocation.
+    ptr: Erased,
+}
+
 /// The global garbage truck.
 /// All [`TrashCans`] should eventually end up in here.
+// `std::sync::LazyLock` would need Rust 1.80
+#[allow(clippy::non_std_lazy_statics)]
 static GARBAGE_TRUCK: Lazy<GarbageTruck> = Lazy::new(|| GarbageTruck {
-    contents: Mutex::new(HashMap::new()),
+    contents: Mutex::new(AllocationMap::default()),
     collecting_lock: RwLock::new(()),
     n_gcs_dropped: AtomicUsize::new(0),
     n_gcs_existing: AtomicUsize::new(0),
-    collect_condition: AtomicPtr::new(default_collect_condition as *mut ()),
+    n_allocations: AtomicUsize::new(0),
+    heap_bytes: AtomicUsize::new(0),
+    n_collections: AtomicUsize::new(0),
+    total_freed: AtomicUsize::new(0),
+    total_bytes_freed: AtomicUsize::new(0),
+    nanos_collecting: AtomicU64::new(0),
+    drop_ratio: AtomicU64::new(ratio::INITIAL_RATIO.to_bits()),
+    hooks: Mutex::new(None),
+    collect_condition: RwLock::new(CollectCondition::default()),
+    default_mode: RwLock::new(CollectionMode::Automatic),
+    collection_requested: AtomicBool::new(false),
+    reserved: AtomicUsize::new(0),
+    deterministic: AtomicBool::new(false),
+    finalizers: Mutex::new(AllocationMap::default()),
+    roots: Mutex::new(AllocationMap::default()),
+    serials: Mutex::new(AllocationMap::default()),
+    scratch: Mutex::new(Scratch::default()),
 });
 
 thread_local! {
@@ -125,52 +307,725 @@ thread_local! {
     /// Allocations which are "dirty" will be transferred to this dumpster before being moved into
     /// the garbage truck for final collection.
     static DUMPSTER: Dumpster = Dumpster {
-        contents: RefCell::new(HashMap::new()),
+        contents: RefCell::new(allocation_map(
+            INITIAL_CAPACITY.with(Cell::take).unwrap_or(0),
+        )),
         n_drops: Cell::new(0),
     };
 
+    /// The capacity with which `DUMPSTER` will be created, or `None` if it already has been.
+    static INITIAL_CAPACITY: Cell<Option<usize>> = const { Cell::new(Some(0)) };
+
     /// Whether the currently-running thread is doing a cleanup.
     /// This cannot be stored in `DUMPSTER` because otherwise it would cause weird use-after-drop
     /// behavior.
     static CLEANING: Cell<bool> = const { Cell::new(false) };
+
+    /// The allocations made on this thread while it was doing a cleanup, which the cleanup knows
+    /// nothing about.
+    static FRESH: RefCell<HashSet<AllocationId, BuildPtrHasher>> =
+        RefCell::new(HashSet::default());
+
+    /// The collect condition for this thread, overriding the one in `GARBAGE_TRUCK` if set.
+    static LOCAL_CONDITION: RefCell<Option<CollectCondition>> = const { RefCell::new(None) };
+
+    /// The number of [`PauseGuard`]s alive on this thread.
+    static N_PAUSES: Cell<usize> = const { Cell::new(0) };
+
+    /// When dropping a `Gc` on this thread may start a collection, or `None` if this thread
+    /// follows the default set by [`set_default_collection_mode`].
+    static MODE: Cell<Option<CollectionMode>> = const { Cell::new(None) };
+
+    /// Whether the collect condition has fired on this thread since its last safe point, while
+    /// collections were only allowed at safe points.
+    static COLLECTION_DUE: Cell<bool> = const { Cell::new(false) };
+
+    /// Whether a collection hook is currently running on this thread.
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+
+    /// The number of allocations and bytes freed by the collection running on this thread.
+    static COLLECTED: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+
+    /// The changes to the counts of `Gc`s made on this thread which have yet to be added to
+    /// `GARBAGE_TRUCK`.
+    /// They are added when this thread's dumpster is dropped, so a thread which never drops a `Gc`
+    /// never adds what it counted here since the last flush.
+    static UNFLUSHED: Cell<UnflushedCounts> = const { Cell::new(UnflushedCounts::ZERO) };
+}
+
+/// Deliver this thread's dumpster to the garbage truck, without collecting.
+pub fn deliver_dumpster() {
+    DUMPSTER.with(|d| d.deliver_to(&GARBAGE_TRUCK));
+}
+
+/// Deliver this thread's dumpster to the garbage truck and collect everything in the truck.
+/// Ensures that all allocations dropped on the calling thread are cleaned up.
+pub fn collect_all() {
+    if IN_HOOK.with(Cell::get) {
+        return;
+    }
+    // this collection does the work of any which was put off until a safe point
+    COLLECTION_DUE.with(|d| d.set(false));
+    DUMPSTER.with(|d| d.deliver_to(&GARBAGE_TRUCK));
+    GARBAGE_TRUCK.collect_all();
 }
 
 #[allow(clippy::module_name_repetitions)]
 /// Collect all allocations in the garbage truck (but not necessarily the dumpster), then await
-/// completion of the collection.
-/// Ensures that all allocations dropped on the calling thread are cleaned up
+/// completion of any collection which was started in the meantime by another thread.
 pub fn collect_all_await() {
-    DUMPSTER.with(|d| d.deliver_to(&GARBAGE_TRUCK));
-    GARBAGE_TRUCK.collect_all();
+    if IN_HOOK.with(Cell::get) {
+        // we are holding the collecting lock, so waiting for it would deadlock
+        return;
+    }
+    collect_all();
     drop(GARBAGE_TRUCK.collecting_lock.read());
 }
 
+/// Deliver this thread's dumpster to the garbage truck, then find everything in the truck which a
+/// collection would free, without freeing anything.
+///
+/// This searches the heap for garbage exactly as [`collect`](super::collect) does, but instead of
+/// destroying the garbage, it returns a description of every allocation it found.
+/// No finalizers are run, so an allocation which a [finalizer](crate::Finalize) would resurrect
+/// is still listed.
+/// Allocations dropped on other threads are only included once those threads have delivered their
+/// dumpsters, so the report is most useful at a point where no other thread is working with `Gc`s.
+///
+/// If this is called from inside a collection hook, the report is empty.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{sync::{collect, collect_dry_run, Gc}, Collectable};
+/// use std::sync::Mutex;
+///
+/// #[derive(Collectable)]
+/// struct Cycle(Mutex<Option<Gc<Cycle>>>);
+///
+/// let gc = Gc::new(Cycle(Mutex::new(None)));
+/// *gc.0.lock().unwrap() = Some(gc.clone());
+/// let addr = Gc::as_ptr(&gc) as usize;
+/// drop(gc);
+///
+/// let report = collect_dry_run();
+/// assert_eq!(report.count(), 1);
+/// assert_eq!(report.allocations[0].addr, addr);
+///
+/// // nothing was freed, so the cycle is still there to be collected
+/// collect();
+/// assert!(collect_dry_run().is_empty());
+/// ```
+pub fn collect_dry_run() -> DryRunReport {
+    if IN_HOOK.with(Cell::get) {
+        // we are holding the collecting lock, so taking it again would deadlock
+        return DryRunReport::default();
+    }
+    DUMPSTER.with(|d| d.deliver_to(&GARBAGE_TRUCK));
+    GARBAGE_TRUCK.collect_dry_run()
+}
+
+/// Deliver this thread's dumpster to the garbage truck, then collect everything in the truck only
+/// if no other thread is currently collecting.
+///
+/// Returns whether a collection was run.
+pub fn try_collect_all() -> bool {
+    if IN_HOOK.with(Cell::get) {
+        return false;
+    }
+    DUMPSTER.with(|d| d.deliver_to(&GARBAGE_TRUCK));
+    GARBAGE_TRUCK.try_collect_all()
+}
+
 /// Notify that a `Gc` was destroyed, and update the tracking count for the number of dropped and
 /// existing `Gc`s.
 ///
 /// This may trigger a linear-time cleanup of all allocations, but this will be guaranteed to
 /// occur with less-than-linear frequency, so it's always O(1).
 pub fn notify_dropped_gc() {
-    GARBAGE_TRUCK.n_gcs_existing.fetch_sub(1, Ordering::Relaxed);
-    GARBAGE_TRUCK.n_gcs_dropped.fetch_add(1, Ordering::Relaxed);
+    count_locally(|counts| UnflushedCounts {
+        existing: counts.existing.wrapping_sub(1),
+        dropped: counts.dropped + 1,
+        n_ops: counts.n_ops + 1,
+    });
     DUMPSTER.with(|dumpster| {
         dumpster.n_drops.set(dumpster.n_drops.get() + 1);
         if dumpster.is_full() {
+            dirty_table_full(dumpster.contents.borrow().len());
             dumpster.deliver_to(&GARBAGE_TRUCK);
         }
     });
 
-    if (unsafe {
-        transmute::<_, CollectCondition>(GARBAGE_TRUCK.collect_condition.load(Ordering::Relaxed))
-    })(&CollectInfo { _private: () })
+    if N_PAUSES.with(Cell::get) == 0 {
+        maybe_collect();
+    }
+}
+
+/// Run a collection, or hand one off to the background collector, if the collect condition for
+/// this thread says so.
+///
+/// If collections may only run at safe points on this thread, the collection is instead put off
+/// until the next call to [`safe_point`], and if they are queued, it is requested from
+/// [`service_collections`].
+fn maybe_collect() {
+    // a value dropped by a collection may make and drop `Gc`s of its own, and the collection
+    // still holds the collecting lock
+    if IN_HOOK.with(Cell::get) || currently_cleaning() {
+        return;
+    }
+    let mode = current_mode();
+    let pending = match mode {
+        CollectionMode::Automatic => false,
+        CollectionMode::AtSafePointsOnly => COLLECTION_DUE.with(Cell::get),
+        CollectionMode::Queued => GARBAGE_TRUCK.collection_requested.load(Ordering::Relaxed),
+    };
+    if pending {
+        return;
+    }
+    let info = CollectInfo { _private: () };
+    // the condition may replace itself, so it must not be borrowed while it runs
+    let condition = LOCAL_CONDITION
+        .with(|c| c.borrow().clone())
+        .unwrap_or_else(|| GARBAGE_TRUCK.collect_condition.read().clone());
+    let triggered = condition.should_collect(&info);
+    match mode {
+        CollectionMode::Automatic => {
+            if !offload_collection(triggered) && triggered {
+                collect_all();
+            }
+        }
+        CollectionMode::AtSafePointsOnly => {
+            if triggered {
+                COLLECTION_DUE.with(|d| d.set(true));
+            }
+        }
+        CollectionMode::Queued => {
+            if triggered {
+                request_collection();
+            }
+        }
+    }
+}
+
+/// Get the collection mode of this thread, which is the default one unless it has set its own.
+fn current_mode() -> CollectionMode {
+    MODE.with(Cell::get)
+        .unwrap_or_else(|| *GARBAGE_TRUCK.default_mode.read())
+}
+
+/// Queue a collection, to be run by [`service_collections`] or by the background collector,
+/// without running or waiting for it.
+///
+/// This thread's dumpster is handed over first, so that the collection sees its garbage.
+fn request_collection() {
+    DUMPSTER.with(|d| d.deliver_to(&GARBAGE_TRUCK));
+    GARBAGE_TRUCK
+        .collection_requested
+        .store(true, Ordering::Release);
+    wake_collector();
+}
+
+/// Set when dropping a [`Gc`] on this thread may start a collection.
+///
+/// With [`CollectionMode::AtSafePointsOnly`], dropping a `Gc` on this thread never starts a
+/// collection, nor hands one off to the background collector.
+/// When the collect condition for this thread fires, that is remembered, and the collection is run
+/// at the next call to [`safe_point`] on this thread.
+/// Other threads are not affected, and may still collect garbage which this thread dropped.
+///
+/// With [`CollectionMode::Queued`], dropping a `Gc` on this thread never runs a collection nor
+/// waits for one, even if a background collector is falling behind.
+/// When the collect condition fires, this thread's garbage is handed over and a collection is
+/// requested, to be run by [`service_collections`] on any thread or by the background collector.
+///
+/// This overrides the mode set by [`set_default_collection_mode`] for this thread.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{
+///     sync::{safe_point, set_collection_mode, Gc},
+///     CollectionMode,
+/// };
+///
+/// set_collection_mode(CollectionMode::AtSafePointsOnly);
+/// for _ in 0..3 {
+///     // any number of `Gc`s may be dropped here without this thread starting a collection
+///     drop(Gc::new(0));
+///     safe_point();
+/// }
+/// # set_collection_mode(CollectionMode::Automatic);
+/// ```
+pub fn set_collection_mode(mode: CollectionMode) {
+    MODE.with(|m| m.set(Some(mode)));
+}
+
+#[must_use]
+/// Get the collection mode of this thread, as set by [`set_collection_mode`], or by
+/// [`set_default_collection_mode`] if this thread has not set its own.
+pub fn collection_mode() -> CollectionMode {
+    current_mode()
+}
+
+/// Set the collection mode of every thread which has not set its own with
+/// [`set_collection_mode`].
+///
+/// This affects threads which already exist as well as those started later.
+/// By default, it is [`CollectionMode::Automatic`].
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{
+///     sync::{collection_mode, set_default_collection_mode},
+///     CollectionMode,
+/// };
+///
+/// set_default_collection_mode(CollectionMode::Queued);
+/// let mode = std::thread::spawn(collection_mode).join().unwrap();
+/// assert_eq!(mode, CollectionMode::Queued);
+/// # set_default_collection_mode(CollectionMode::Automatic);
+/// ```
+pub fn set_default_collection_mode(mode: CollectionMode) {
+    *GARBAGE_TRUCK.default_mode.write() = mode;
+}
+
+/// Run a collection, or hand one off to the background collector, if the collect condition for
+/// this thread has fired since its last safe point or collection.
+///
+/// This is meant to be called regularly, such as once per frame, when the collection mode for this
+/// thread is [`CollectionMode::AtSafePointsOnly`].
+///
+/// Returns whether a collection was run or handed off.
+pub fn safe_point() -> bool {
+    if !COLLECTION_DUE.with(Cell::get) || IN_HOOK.with(Cell::get) {
+        return false;
+    }
+    COLLECTION_DUE.with(|d| d.set(false));
+    if !offload_collection(true) {
+        collect_all();
+        #[cfg(feature = "epoch")]
+        drain_retired();
+    }
+    true
+}
+
+/// Run the collection which threads in [`CollectionMode::Queued`] have requested, if there is
+/// one.
+///
+/// However many times the collect condition fired on those threads, their requests are coalesced
+/// into a single collection.
+/// If a background collector is running, it services the requests by itself, so this only needs
+/// to be called without one, such as regularly from a thread which is allowed to pause.
+/// Any other collection also services the requests made before it started.
+///
+/// Returns whether a collection was run.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{
+///     sync::{service_collections, set_collection_mode, Gc},
+///     CollectionMode,
+/// };
+///
+/// set_collection_mode(CollectionMode::Queued);
+/// // dropping these never runs a collection on this thread
+/// for _ in 0..1000 {
+///     drop(Gc::new(0));
+/// }
+/// // some other thread, or this one at a convenient time, does the work instead
+/// std::thread::spawn(service_collections).join().unwrap();
+/// # dumpster::sync::set_collection_mode(CollectionMode::Automatic);
+/// ```
+pub fn service_collections() -> bool {
+    if IN_HOOK.with(Cell::get)
+        || !GARBAGE_TRUCK
+            .collection_requested
+            .swap(false, Ordering::Acquire)
     {
-        GARBAGE_TRUCK.collect_all();
+        return false;
     }
+    collect_all();
+    #[cfg(feature = "epoch")]
+    drain_retired();
+    true
 }
 
 /// Notify that a [`Gc`] was created, and increment the number of total existing `Gc`s.
 pub fn notify_created_gc() {
-    GARBAGE_TRUCK.n_gcs_existing.fetch_add(1, Ordering::Relaxed);
+    count_locally(|counts| UnflushedCounts {
+        existing: counts.existing.wrapping_add(1),
+        n_ops: counts.n_ops + 1,
+        ..counts
+    });
+}
+
+/// Notify that a [`Gc`] was cloned.
+///
+/// This only touches the counts belonging to this thread, and never adds them to the garbage
+/// truck, so that cloning never contends with other threads.
+pub fn notify_cloned_gc() {
+    let _ = UNFLUSHED.try_with(|c| {
+        c.set(UnflushedCounts {
+            existing: c.get().existing.wrapping_add(1),
+            ..c.get()
+        });
+    });
+}
+
+/// Notify that a [`Gc`] inside an allocation which a collection is destroying was dropped.
+///
+/// The collection has already released or cut off the reference, so it is not counted as a drop,
+/// which would only bring the next collection forward; it just stops being counted as existing.
+pub fn notify_destroyed_gc() {
+    let _ = UNFLUSHED.try_with(|c| {
+        c.set(UnflushedCounts {
+            existing: c.get().existing.wrapping_sub(1),
+            ..c.get()
+        });
+    });
+}
+
+/// Update the counts of `Gc`s on this thread with `f`, and add them to the garbage truck once
+/// enough `Gc`s have been made or dropped since they were last added.
+fn count_locally(f: impl FnOnce(UnflushedCounts) -> UnflushedCounts) {
+    let Ok(counts) = UNFLUSHED.try_with(|c| {
+        c.set(f(c.get()));
+        c.get()
+    }) else {
+        return;
+    };
+    if counts.n_ops >= FLUSH_INTERVAL {
+        flush_counts();
+    }
+}
+
+/// Add the counts of `Gc`s made and dropped on this thread to the garbage truck.
+fn flush_counts() {
+    let counts = UNFLUSHED
+        .try_with(|c| c.replace(UnflushedCounts::ZERO))
+        .unwrap_or(UnflushedCounts::ZERO);
+    if counts.existing != 0 {
+        GARBAGE_TRUCK
+            .n_gcs_existing
+            .fetch_add(counts.existing, Ordering::Relaxed);
+    }
+    if counts.dropped != 0 {
+        GARBAGE_TRUCK
+            .n_gcs_dropped
+            .fetch_add(counts.dropped, Ordering::Relaxed);
+    }
+    #[cfg(feature = "epoch")]
+    epoch::reclaim();
+}
+
+/// Get the counts of `Gc`s made and dropped on this thread which have yet to be added to the
+/// garbage truck.
+fn unflushed() -> UnflushedCounts {
+    UNFLUSHED
+        .try_with(Cell::get)
+        .unwrap_or(UnflushedCounts::ZERO)
+}
+
+/// Notify that a new allocation with layout `layout` was made.
+pub fn notify_allocated(layout: Layout) {
+    GARBAGE_TRUCK.n_allocations.fetch_add(1, Ordering::Relaxed);
+    GARBAGE_TRUCK
+        .heap_bytes
+        .fetch_add(layout.size(), Ordering::Relaxed);
+}
+
+/// Notify that an allocation with layout `layout` was freed.
+pub fn notify_deallocated(layout: Layout) {
+    GARBAGE_TRUCK.n_allocations.fetch_sub(1, Ordering::Relaxed);
+    GARBAGE_TRUCK
+        .heap_bytes
+        .fetch_sub(layout.size(), Ordering::Relaxed);
+}
+
+#[cfg(any(
+    feature = "leak-detection",
+    feature = "heap-dump",
+    feature = "heap-inspection"
+))]
+#[derive(Clone, Copy)]
+/// The information recorded about every allocation for diagnostics.
+struct LiveAllocation {
+    #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+    /// The name of the type stored in the allocation.
+    type_name: &'static str,
+    #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+    /// The size of the allocation, in bytes.
+    size: usize,
+    #[cfg(any(feature = "heap-dump", feature = "heap-inspection"))]
+    /// An erased pointer to the allocation.
+    ptr: Erased,
+    #[cfg(feature = "heap-dump")]
+    /// The function which describes the allocation for a heap dump.
+    describe_fn: unsafe fn(Erased) -> DotNode,
+    #[cfg(feature = "heap-inspection")]
+    /// The function which describes the allocation for `iter_allocations`.
+    inspect_fn: unsafe fn(Erased) -> crate::AllocationInfo,
+}
+
+#[cfg(any(
+    feature = "leak-detection",
+    feature = "heap-dump",
+    feature = "heap-inspection"
+))]
+/// Every allocation which currently exists, keyed by its address.
+///
+/// An allocation is removed from this map before its value is dropped, so a thread holding the lock
+/// may inspect any value in the map.
+static LIVE: Mutex<HashMap<usize, LiveAllocation, BuildPtrHasher>> =
+    Mutex::new(HashMap::with_hasher(BuildPtrHasher));
+
+#[cfg(any(
+    feature = "leak-detection",
+    feature = "heap-dump",
+    feature = "heap-inspection"
+))]
+/// Record that the allocation behind `ptr` has been made.
+pub fn register_live<T: Collectable + Send + Sync + ?Sized>(ptr: NonNull<GcBox<T>>) {
+    LIVE.lock().insert(
+        ptr.as_ptr().cast::<()>() as usize,
+        LiveAllocation {
+            #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+            type_name: std::any::type_name::<T>(),
+            #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+            size: Layout::for_value(unsafe { ptr.as_ref() }).size(),
+            #[cfg(any(feature = "heap-dump", feature = "heap-inspection"))]
+            ptr: Erased::new(ptr),
+            #[cfg(feature = "heap-dump")]
+            describe_fn: describe::<T>,
+            #[cfg(feature = "heap-inspection")]
+            inspect_fn: inspect::<T>,
+        },
+    );
+}
+
+#[cfg(any(
+    feature = "leak-detection",
+    feature = "heap-dump",
+    feature = "heap-inspection"
+))]
+/// Record that the allocation at `ptr` is about to be dropped and freed.
+pub fn forget_live<T: ?Sized>(ptr: *const T) {
+    LIVE.lock().remove(&(ptr.cast::<()>() as usize));
+}
+
+#[cfg(feature = "heap-dump")]
+/// Describe the allocation behind an erased pointer for a heap dump, without modifying it.
+///
+/// # Safety
+///
+/// `ptr` must have been created as a pointer to a `GcBox<T>`, and the allocation must not be
+/// dropped while this function runs.
+unsafe fn describe<T: Collectable + Send + Sync + ?Sized>(ptr: Erased) -> DotNode {
+    let box_ref = ptr.specify::<GcBox<T>>().as_ref();
+    let mut finder = EdgeFinder::default();
+    // if the value can't be inspected right now, its edges are simply left out
+    let _ = box_ref.value.accept(&mut finder);
+    DotNode {
+        addr: value_addr(&box_ref.value),
+        type_name: std::any::type_name::<T>(),
+        size: Layout::for_value(box_ref).size(),
+        ref_count: box_ref.ref_count(),
+        edges: finder.edges,
+    }
+}
+
+#[cfg(feature = "heap-inspection")]
+/// Describe the allocation behind an erased pointer for [`iter_allocations`].
+///
+/// # Safety
+///
+/// `ptr` must have been created as a pointer to a `GcBox<T>`, and the allocation must not be
+/// freed while this function runs.
+unsafe fn inspect<T: Collectable + Send + Sync + ?Sized>(ptr: Erased) -> crate::AllocationInfo {
+    let box_ref = ptr.specify::<GcBox<T>>().as_ref();
+    crate::AllocationInfo {
+        addr: std::ptr::from_ref(&box_ref.value).cast::<()>() as usize,
+        type_name: std::any::type_name::<T>(),
+        size: Layout::for_value(box_ref).size(),
+        ref_count: box_ref.ref_count(),
+    }
+}
+
+#[cfg(feature = "heap-inspection")]
+/// Iterate over a snapshot of every garbage-collected allocation which currently exists, on any
+/// thread.
+///
+/// The snapshot is taken all at once when this function is called, so `Gc`s may be created and
+/// dropped freely while iterating.
+/// Other threads may also create and drop `Gc`s while the snapshot is taken; an allocation which
+/// is freed by another thread meanwhile is either listed completely or not at all.
+/// This includes allocations which are garbage but have not been collected yet.
+/// This is only available with the `heap-inspection` feature.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{iter_allocations, Gc};
+///
+/// let gc = Gc::new(0u32);
+/// let info = iter_allocations()
+///     .find(|info| info.addr == Gc::as_ptr(&gc) as usize)
+///     .unwrap();
+/// assert_eq!(info.type_name, "u32");
+/// assert_eq!(info.ref_count, 1);
+/// ```
+pub fn iter_allocations() -> impl Iterator<Item = crate::AllocationInfo> {
+    LIVE.lock()
+        .values()
+        .map(|live| unsafe { (live.inspect_fn)(live.ptr) })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+#[cfg(feature = "heap-inspection")]
+#[must_use]
+/// Count the garbage-collected allocations, on any thread, by the type of their value.
+///
+/// The census is laid out as described in [`unsync::type_census`](crate::unsync::type_census).
+/// It is built from the table which the collector already keeps of every allocation, without
+/// looking at any values, so it may be called at any time.
+/// Allocations created and freed by other threads while the census is taken are either counted
+/// or not, but are never partially counted.
+/// This is only available with the `heap-inspection` feature.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{type_census, Gc};
+///
+/// let gc = Gc::new(0u64);
+/// assert!(type_census().iter().any(|(name, count, _)| name == "u64" && *count >= 1));
+/// ```
+pub fn type_census() -> Vec<(String, usize, usize)> {
+    crate::inspect::census(LIVE.lock().values().map(|live| (live.type_name, live.size)))
+}
+
+#[cfg(feature = "heap-dump")]
+/// Write every garbage-collected allocation, on any thread, to `writer` as a Graphviz digraph.
+///
+/// The graph is drawn as described in [`unsync::dump_heap_dot`](crate::unsync::dump_heap_dot).
+/// The allocations are inspected while no collection is running, and nothing is modified, so the
+/// heap is quiescent as far as the collector is concerned.
+/// Other threads may still clone and drop `Gc`s during the dump; an allocation which is freed by
+/// another thread meanwhile is either drawn completely or not at all.
+/// This is only available with the `heap-dump` feature.
+///
+/// # Errors
+///
+/// This function returns an error if writing to `writer` fails.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{dump_heap_dot, Gc};
+///
+/// let gc = Gc::new(0u32);
+/// let mut dot = Vec::new();
+/// dump_heap_dot(&mut dot).unwrap();
+///
+/// let dot = String::from_utf8(dot).unwrap();
+/// assert!(dot.contains(&format!("{:p}", Gc::as_ptr(&gc))));
+/// ```
+pub fn dump_heap_dot(writer: impl std::io::Write) -> std::io::Result<()> {
+    // a hook runs while its thread already holds the collecting lock
+    let collecting_guard =
+        (!IN_HOOK.with(Cell::get)).then(|| GARBAGE_TRUCK.collecting_lock.write());
+    let nodes = LIVE
+        .lock()
+        .values()
+        .map(|live| unsafe { (live.describe_fn)(live.ptr) })
+        .collect::<Vec<_>>();
+    drop(collecting_guard);
+    write_dot(writer, nodes)
+}
+
+#[cfg(feature = "leak-detection")]
+#[must_use]
+/// Collect all garbage, then report every allocation which still exists.
+///
+/// `sync` allocations are not owned by any one thread, so there is no point at which the garbage
+/// collector can tell on its own that an allocation has been leaked.
+/// Instead, this should be called once the program expects no `Gc`s to be left, such as at the end
+/// of `main` or of a test; anything listed in the report was leaked or is still reachable.
+/// This is only available with the `leak-detection` feature.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{leak_report, Gc};
+///
+/// std::mem::forget(Gc::new(0u32));
+///
+/// let report = leak_report();
+/// assert_eq!(report.count(), 1);
+/// assert_eq!(report.allocations[0].type_name, "u32");
+/// ```
+pub fn leak_report() -> LeakReport {
+    collect_all_await();
+    LeakReport {
+        allocations: LIVE
+            .lock()
+            .values()
+            .map(|live| LeakedAllocation {
+                type_name: live.type_name,
+                size: live.size,
+            })
+            .collect(),
+    }
+}
+
+/// Notify that an allocation with layout `layout` was freed by a collection.
+fn notify_collected(layout: Layout) {
+    COLLECTED.with(|c| {
+        let (n, bytes) = c.get();
+        c.set((n + 1, bytes + layout.size()));
+    });
+    GARBAGE_TRUCK.total_freed.fetch_add(1, Ordering::Relaxed);
+    GARBAGE_TRUCK
+        .total_bytes_freed
+        .fetch_add(layout.size(), Ordering::Relaxed);
+}
+
+#[must_use]
+/// Get a snapshot of the garbage collector's statistics, shared across all threads.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{stats, Gc};
+///
+/// let gc = Gc::new(0);
+/// assert!(stats().live_allocations >= 1);
+/// ```
+pub fn stats() -> GcStats {
+    GcStats {
+        collections: GARBAGE_TRUCK.n_collections.load(Ordering::Relaxed),
+        allocations_freed: GARBAGE_TRUCK.total_freed.load(Ordering::Relaxed),
+        bytes_freed: GARBAGE_TRUCK.total_bytes_freed.load(Ordering::Relaxed),
+        live_allocations: GARBAGE_TRUCK.n_allocations.load(Ordering::Relaxed),
+        live_bytes: GARBAGE_TRUCK.heap_bytes.load(Ordering::Relaxed),
+        time_collecting: Duration::from_nanos(
+            GARBAGE_TRUCK.nanos_collecting.load(Ordering::Relaxed),
+        ),
+    }
+}
+
+/// Reset the cumulative statistics returned by [`stats`] to zero.
+///
+/// The number and size of live allocations are not affected.
+/// A collection which is running while the statistics are reset may still be partially counted.
+pub fn reset_stats() {
+    GARBAGE_TRUCK.n_collections.store(0, Ordering::Relaxed);
+    GARBAGE_TRUCK.total_freed.store(0, Ordering::Relaxed);
+    GARBAGE_TRUCK.total_bytes_freed.store(0, Ordering::Relaxed);
+    GARBAGE_TRUCK.nanos_collecting.store(0, Ordering::Relaxed);
 }
 
 /// Mark an allocation as "dirty," implying that it may or may not be inaccessible and need to
@@ -194,16 +1049,23 @@ where
             .is_none()
         {
             box_ref.weak.fetch_add(1, Ordering::Acquire);
+            box_ref.set_flag(DIRTY);
         }
     });
 }
 
 /// Mark an allocation as "clean," implying that it has already been cleaned up and does not
 /// need to be cleaned again.
+///
+/// This thread's dumpster is only searched if the allocation has been put in a dumpster since the
+/// last search.
 pub(super) fn mark_clean<T>(allocation: &GcBox<T>)
 where
     T: Collectable + Send + Sync + ?Sized,
 {
+    if allocation.clear_flag(DIRTY) & DIRTY == 0 {
+        return;
+    }
     DUMPSTER.with(|dumpster| {
         if dumpster
             .contents
@@ -216,50 +1078,467 @@ where
     });
 }
 
-#[allow(clippy::missing_panics_doc)]
-/// Set the function which determines whether the garbage collector should be run.
+#[allow(clippy::missing_panics_doc)]
+/// Set the function which determines whether the garbage collector should be run.
+///
+/// `f` will be periodically called by the garbage collector to determine whether it should perform
+/// a full traversal of the heap.
+/// When `f` returns true, a traversal will begin.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{set_collect_condition, CollectInfo};
+///
+/// /// This function will make sure a GC traversal never happens unless directly activated.
+/// fn never_collect(_: &CollectInfo) -> bool {
+///     false
+/// }
+///
+/// set_collect_condition(never_collect);
+/// ```
+pub fn set_collect_condition(f: fn(&CollectInfo) -> bool) {
+    set_collect_condition_with(CollectCondition::custom(f));
+}
+
+/// Set the condition which determines whether the garbage collector should be run.
+///
+/// This works like [`set_collect_condition`], but takes a [`CollectCondition`], which may be
+/// combined out of several policies.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{set_collect_condition_with, CollectCondition};
+///
+/// set_collect_condition_with(CollectCondition::never());
+/// // dropping a `Gc` will now never start a collection
+/// set_collect_condition_with(CollectCondition::default());
+/// ```
+pub fn set_collect_condition_with(condition: CollectCondition) {
+    *GARBAGE_TRUCK.collect_condition.write() = condition;
+}
+
+#[allow(clippy::must_use_candidate)]
+/// Set a closure which determines whether the garbage collector should be run, returning the
+/// condition which was previously set.
+///
+/// This works like [`set_collect_condition`], except that `f` may capture state, and that the
+/// previous condition is handed back so that it can be restored later.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{set_collect_condition_boxed, CollectInfo};
+/// use std::sync::{
+///     atomic::{AtomicBool, Ordering},
+///     Arc,
+/// };
+///
+/// let enabled = Arc::new(AtomicBool::new(true));
+/// let switch = enabled.clone();
+/// let previous = set_collect_condition_boxed(move |info: &CollectInfo| {
+///     switch.load(Ordering::Relaxed) && info.n_gcs_dropped_since_last_collect() > 100
+/// });
+///
+/// // turn off automatic collection for a while
+/// enabled.store(false, Ordering::Relaxed);
+///
+/// // go back to whatever was there before
+/// set_collect_condition_boxed(previous);
+/// ```
+pub fn set_collect_condition_boxed(
+    f: impl Fn(&CollectInfo) -> bool + Send + Sync + 'static,
+) -> BoxedCollectCondition {
+    let previous = replace(
+        &mut *GARBAGE_TRUCK.collect_condition.write(),
+        CollectCondition::custom(f),
+    );
+    Box::new(move |info| previous.should_collect(info))
+}
+
+/// Set the function which determines whether the garbage collector should be run, for the calling
+/// thread only.
+///
+/// Whenever a [`Gc`] is dropped on this thread, `f` is consulted instead of the condition set by
+/// [`set_collect_condition`].
+/// This allows threads with different needs to use different policies; for instance, a
+/// latency-sensitive thread may never collect, leaving its garbage to be cleaned up by collections
+/// on other threads.
+/// Use [`clear_collect_condition_local`] to go back to the global condition.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{clear_collect_condition_local, set_collect_condition_local, CollectInfo};
+///
+/// fn never_collect(_: &CollectInfo) -> bool {
+///     false
+/// }
+///
+/// std::thread::spawn(|| {
+///     set_collect_condition_local(never_collect);
+///     // dropping a `Gc` on this thread will never start a collection
+///     clear_collect_condition_local();
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub fn set_collect_condition_local(f: fn(&CollectInfo) -> bool) {
+    set_collect_condition_local_with(CollectCondition::custom(f));
+}
+
+/// Set the condition which determines whether the garbage collector should be run, for the
+/// calling thread only.
+///
+/// This works like [`set_collect_condition_local`], but takes a [`CollectCondition`], which may
+/// be combined out of several policies.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{
+///     clear_collect_condition_local, set_collect_condition_local_with, CollectCondition,
+/// };
+///
+/// std::thread::spawn(|| {
+///     set_collect_condition_local_with(CollectCondition::every_n_drops(1_000));
+///     // this thread now collects after every 1,000 drops
+///     clear_collect_condition_local();
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub fn set_collect_condition_local_with(condition: CollectCondition) {
+    LOCAL_CONDITION.with(|c| c.replace(Some(condition)));
+}
+
+#[must_use = "collection is only paused while the guard is alive"]
+/// Prevent dropping a [`Gc`] on this thread from starting a collection until the returned guard is
+/// dropped.
+///
+/// While any guard is alive, dropping a `Gc` on this thread still records that it was dropped, but
+/// the collect condition is not consulted.
+/// When the last guard on this thread is dropped, the collect condition is checked once, and a
+/// collection is run if it returns `true`.
+/// This is useful for short sections of code where an unexpected collection would take too long.
+///
+/// Guards may be nested.
+/// A guard only pauses collection on the thread which created it: other threads may still
+/// collect, and may clean up garbage which this thread dropped before the guard was created.
+/// Explicit calls to [`collect`](super::collect) are not affected by a guard.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{pause_collection, Gc};
+///
+/// let gc = Gc::new(0);
+/// let guard = pause_collection();
+/// // this cannot cause a collection on this thread
+/// drop(gc);
+/// // but this can
+/// drop(guard);
+/// ```
+pub fn pause_collection() -> PauseGuard {
+    N_PAUSES.with(|n| n.set(n.get() + 1));
+    PauseGuard {
+        _not_send: PhantomData,
+    }
+}
+
+#[derive(Debug)]
+/// A guard which prevents automatic collections on this thread while it is alive.
+///
+/// This is created by [`pause_collection`].
+pub struct PauseGuard {
+    /// The guard must be dropped on the thread which created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        let n_pauses = N_PAUSES.with(|n| {
+            n.set(n.get() - 1);
+            n.get()
+        });
+        if n_pauses == 0 {
+            maybe_collect();
+        }
+    }
+}
+
+/// Set the hooks which are called around every collection on any thread, replacing any hooks which
+/// were previously set.
+///
+/// Hooks are called on the thread running the collection, while it holds the lock which prevents
+/// other collections from running.
+/// To remove the hooks, set them to [`CollectHooks::default`].
+/// Hooks may be replaced from inside a hook, in which case the new hooks take effect at the next
+/// collection.
+///
+/// A hook must not try to start another collection: any collection requested while a hook is
+/// running on the same thread, whether explicitly through [`collect`](super::collect) or by
+/// dropping a [`Gc`], does nothing.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{collect, set_collect_hooks, CollectHooks};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static N_COLLECTIONS: AtomicUsize = AtomicUsize::new(0);
+///
+/// set_collect_hooks(CollectHooks::new().on_end(|result| {
+///     N_COLLECTIONS.fetch_add(1, Ordering::Relaxed);
+///     println!("collection took {:?}", result.duration);
+/// }));
+/// collect();
+/// assert!(N_COLLECTIONS.load(Ordering::Relaxed) >= 1);
+/// ```
+pub fn set_collect_hooks(hooks: CollectHooks) {
+    *GARBAGE_TRUCK.hooks.lock() = Some(Arc::new(hooks));
+}
+
+/// A guard which marks that a collection hook is running on this thread until it is dropped, even
+/// if the hook panics.
+struct HookGuard;
+
+impl HookGuard {
+    /// Mark that a hook is running.
+    fn new() -> HookGuard {
+        IN_HOOK.with(|h| h.set(true));
+        HookGuard
+    }
+}
+
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        IN_HOOK.with(|h| h.set(false));
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+/// Replace the collect condition for the calling thread, returning the previous one.
+pub(crate) fn replace_collect_condition_local(
+    f: Option<CollectCondition>,
+) -> Option<CollectCondition> {
+    LOCAL_CONDITION.with(|c| c.replace(f))
+}
+
+/// Remove the condition set by [`set_collect_condition_local`] for the calling thread, so that the
+/// condition set by [`set_collect_condition`] is used again.
+pub fn clear_collect_condition_local() {
+    LOCAL_CONDITION.with(|c| c.replace(None));
+}
+
+/// Determine whether this thread is currently cleaning.
+pub fn currently_cleaning() -> bool {
+    CLEANING.with(Cell::get)
+}
+
+/// Start or stop cleaning on this thread, returning whether it was cleaning before.
+///
+/// Once it stops, the allocations made while it cleaned are no different from any other.
+pub(super) fn set_cleaning(cleaning: bool) -> bool {
+    let was_cleaning = CLEANING.try_with(|c| c.replace(cleaning)).unwrap_or(true);
+    if !cleaning {
+        let _ = FRESH.try_with(|f| f.borrow_mut().clear());
+    }
+    was_cleaning
+}
+
+/// Note that the allocation behind `ptr` has just been made, in case this thread is cleaning.
+pub(super) fn note_allocated<T: Collectable + Send + Sync + ?Sized>(ptr: NonNull<GcBox<T>>) {
+    if CLEANING.try_with(Cell::get) == Ok(true) {
+        let _ = FRESH.try_with(|f| f.borrow_mut().insert(AllocationId::from(ptr)));
+    }
+}
+
+/// Note that the allocation behind `ptr` is being freed, so that its address may be reused.
+pub(super) fn note_destroyed<T: Collectable + Send + Sync + ?Sized>(ptr: NonNull<GcBox<T>>) {
+    if CLEANING.try_with(Cell::get) == Ok(true) {
+        let _ = FRESH.try_with(|f| f.borrow_mut().remove(&AllocationId::from(ptr)));
+    }
+}
+
+/// Determine whether the allocation behind `ptr` was made while this thread was cleaning.
+///
+/// The cleanup treats such an allocation as reachable, and has not accounted for any of its
+/// references, so they are counted as usual.
+pub(super) fn is_fresh<T: Collectable + Send + Sync + ?Sized>(ptr: NonNull<GcBox<T>>) -> bool {
+    FRESH
+        .try_with(|f| f.borrow().contains(&AllocationId::from(ptr)))
+        .unwrap_or(false)
+}
+
+/// Get the number of `[Gc]`s dropped since the last collection.
+///
+/// Other threads only add their drops to this count every [`FLUSH_INTERVAL`] `Gc`s, so it may
+/// lag behind their most recent drops.
+pub fn n_gcs_dropped() -> usize {
+    GARBAGE_TRUCK.n_gcs_dropped.load(Ordering::Relaxed) + unflushed().dropped
+}
+
+#[allow(clippy::cast_possible_wrap)]
+/// Get the number of `[Gc]`s currently existing in the entire program.
+///
+/// Other threads only add the `Gc`s they make and drop to this count every [`FLUSH_INTERVAL`]
+/// `Gc`s, and their clones only then, so `Gc`s cloned on one thread and dropped on another may
+/// leave the count too low, or even below zero.
+/// Every allocation which has not been freed has at least one `Gc`, unless it is waiting to be
+/// collected, so the count is never reported as less than the number of allocations.
+pub fn n_gcs_existing() -> usize {
+    let existing = GARBAGE_TRUCK
+        .n_gcs_existing
+        .load(Ordering::Relaxed)
+        .wrapping_add(unflushed().existing);
+    usize::try_from(existing as isize)
+        .unwrap_or(0)
+        .max(n_allocations())
+}
+
+/// Get the number of allocations currently existing in the entire program.
+pub fn n_allocations() -> usize {
+    GARBAGE_TRUCK.n_allocations.load(Ordering::Relaxed)
+}
+
+/// Get the total size, in bytes, of all allocations currently existing in the entire program.
+pub fn heap_bytes() -> usize {
+    GARBAGE_TRUCK.heap_bytes.load(Ordering::Relaxed)
+}
+
+/// Get the ratio of dropped to existing [`Gc`]s at which the default collect condition currently
+/// starts a collection.
+pub fn drop_ratio() -> f64 {
+    f64::from_bits(GARBAGE_TRUCK.drop_ratio.load(Ordering::Relaxed))
+}
+
+/// Reserve room in the garbage collector for at least `additional` more allocations.
 ///
-/// `f` will be periodically called by the garbage collector to determine whether it should perform
-/// a full traversal of the heap.
-/// When `f` returns true, a traversal will begin.
+/// After reserving, the calling thread's table of allocations which may need to be collected can
+/// grow by `additional` entries without reallocating, as can the global table those are delivered
+/// to.
+/// The next collections will also allocate their working memory up front for that many more
+/// allocations than exist now.
 ///
 /// # Examples
 ///
 /// ```
-/// use dumpster::sync::{set_collect_condition, CollectInfo};
-///
-/// /// This function will make sure a GC traversal never happens unless directly activated.
-/// fn never_collect(_: &CollectInfo) -> bool {
-///     false
-/// }
+/// use dumpster::sync::{dirty_capacity, reserve};
 ///
-/// set_collect_condition(never_collect);
+/// reserve(1000);
+/// assert!(dirty_capacity() >= 1000);
 /// ```
-pub fn set_collect_condition(f: CollectCondition) {
-    GARBAGE_TRUCK
-        .collect_condition
-        .store(f as *mut (), Ordering::Relaxed);
+pub fn reserve(additional: usize) {
+    DUMPSTER.with(|d| d.contents.borrow_mut().reserve(additional));
+    GARBAGE_TRUCK.contents.lock().reserve(additional);
+    GARBAGE_TRUCK.reserved.fetch_max(
+        GARBAGE_TRUCK.n_allocations.load(Ordering::Relaxed) + additional,
+        Ordering::Relaxed,
+    );
 }
 
-/// Determine whether this thread is currently cleaning.
-pub fn currently_cleaning() -> bool {
-    CLEANING.with(Cell::get)
+#[allow(clippy::must_use_candidate)]
+/// Set the number of allocations which the calling thread's table of allocations that may need to
+/// be collected will have room for when it is first used.
+///
+/// This must be called before any [`Gc`] is dropped on this thread.
+/// Returns `true` if the capacity was set, and `false` if the table was already in use, in which
+/// case [`reserve`] must be used instead.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{dirty_capacity, set_initial_capacity};
+///
+/// std::thread::spawn(|| {
+///     assert!(set_initial_capacity(1000));
+///     assert!(dirty_capacity() >= 1000);
+///     assert!(!set_initial_capacity(2000));
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub fn set_initial_capacity(capacity: usize) -> bool {
+    INITIAL_CAPACITY.with(|c| {
+        let unused = c.get().is_some();
+        if unused {
+            c.set(Some(capacity));
+        }
+        unused
+    })
 }
 
-/// Get the number of `[Gc]`s dropped since the last collection.
-pub fn n_gcs_dropped() -> usize {
-    GARBAGE_TRUCK.n_gcs_dropped.load(Ordering::Relaxed)
+#[must_use]
+/// Get the number of allocations which may need to be collected that the calling thread's table
+/// can track without reallocating.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{dirty_capacity, reserve};
+///
+/// reserve(100);
+/// assert!(dirty_capacity() >= 100);
+/// ```
+pub fn dirty_capacity() -> usize {
+    DUMPSTER.with(|d| d.contents.borrow().capacity())
 }
 
-/// Get the number of `[Gc]`s currently existing in the entire program.
-pub fn n_gcs_existing() -> usize {
-    GARBAGE_TRUCK.n_gcs_existing.load(Ordering::Relaxed)
+/// Set whether collections destroy unreachable allocations in the order in which they were
+/// allocated.
+///
+/// By default, the order in which a collection drops garbage is unspecified, and may change from
+/// one run of a program to the next.
+/// When this is enabled, finalizers are run and values are dropped oldest first, so that the side
+/// effects of a collection can be reproduced exactly.
+/// Allocations made concurrently by different threads are still ordered by whichever was made
+/// first.
+/// Only allocations made while this is enabled are ordered: the garbage allocated before it was
+/// enabled is destroyed first, in no particular order.
+///
+/// This applies to every thread, and makes collections somewhat slower.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{sync::{collect, set_deterministic, Gc}, Collectable};
+/// use std::sync::Mutex;
+///
+/// #[derive(Collectable)]
+/// struct Node {
+///     name: &'static str,
+///     next: Mutex<Option<Gc<Node>>>,
+/// }
+///
+/// impl Drop for Node {
+///     fn drop(&mut self) {
+///         println!("dropping {}", self.name);
+///     }
+/// }
+///
+/// set_deterministic(true);
+///
+/// let first = Gc::new(Node { name: "first", next: Mutex::new(None) });
+/// let second = Gc::new(Node { name: "second", next: Mutex::new(Some(first.clone())) });
+/// *first.next.lock().unwrap() = Some(second.clone());
+/// drop((first, second));
+///
+/// // prints "dropping first", then "dropping second"
+/// collect();
+/// ```
+pub fn set_deterministic(deterministic: bool) {
+    GARBAGE_TRUCK
+        .deterministic
+        .store(deterministic, Ordering::Relaxed);
 }
 
 impl Dumpster {
     /// Deliver all [`TrashCans`] contained by this dumpster to the garbage collect, removing them
     /// from the local dumpster storage and adding them to the global truck.
     fn deliver_to(&self, garbage_truck: &GarbageTruck) {
+        flush_counts();
         self.n_drops.set(0);
         let mut guard = garbage_truck.contents.lock();
         for (id, can) in self.contents.borrow_mut().drain() {
@@ -285,56 +1564,510 @@ impl GarbageTruck {
     /// If so, drop those allocations.
     fn collect_all(&self) {
         let collecting_guard = self.collecting_lock.write();
-        self.n_gcs_dropped.store(0, Ordering::Relaxed);
-        let to_collect = take(&mut *self.contents.lock());
-        let mut ref_graph = HashMap::with_capacity(to_collect.len());
+        self.collect_locked();
+        drop(collecting_guard);
+    }
 
-        CURRENT_TAG.fetch_add(1, Ordering::Release);
+    /// Collect all allocations in the truck, as in [`GarbageTruck::collect_all`], but only if no
+    /// other collection is currently running.
+    ///
+    /// Returns whether a collection was run.
+    fn try_collect_all(&self) -> bool {
+        let Some(collecting_guard) = self.collecting_lock.try_write() else {
+            return false;
+        };
+        self.collect_locked();
+        drop(collecting_guard);
+        true
+    }
 
-        for (_, TrashCan { ptr, dfs_fn }) in to_collect {
-            unsafe { dfs_fn(ptr, &mut ref_graph) };
+    /// Perform a collection, calling the collection hooks before and after.
+    /// The caller must hold `collecting_lock` for writing.
+    fn collect_locked(&self) {
+        let span = CollectSpan::enter("sync");
+        // this collection services any which was requested before it started
+        self.collection_requested.swap(false, Ordering::Acquire);
+        let hooks = self.hooks.lock().clone();
+        if let Some(on_start) = hooks.as_ref().and_then(|h| h.on_start.as_ref()) {
+            let _guard = HookGuard::new();
+            on_start(&CollectInfo { _private: () });
         }
+        let result = self.collect_unhooked();
+        if let Some(on_end) = hooks.as_ref().and_then(|h| h.on_end.as_ref()) {
+            let _guard = HookGuard::new();
+            on_end(&result);
+        }
+        span.finish(
+            result.allocations_examined,
+            result.allocations_freed,
+            result.bytes_freed,
+            result.duration,
+        );
+        resume_caught();
+    }
 
-        let root_ids = ref_graph
+    /// Find the allocations which a collection would free, without freeing them or running any
+    /// finalizers.
+    fn collect_dry_run(&self) -> DryRunReport {
+        let _collecting_guard = self.collecting_lock.write();
+        let reserved = self.reserved.load(Ordering::Relaxed);
+        // the graph borrows the weak references held by the truck, and hands them back once it is
+        // done, so that the next collection still sees the same garbage
+        let mut scratch = self.take_scratch(reserved);
+        let mut in_truck = replace(&mut *self.contents.lock(), take(&mut scratch.cans));
+        let mut to_collect = in_truck
             .iter()
-            .filter_map(|(&k, v)| match v.reachability {
-                Reachability::Reachable => Some(k),
-                Reachability::Unknown { n_unaccounted, .. } => (n_unaccounted > 0
-                    || unsafe { k.0.as_ref().weak.load(Ordering::Acquire) > 1 })
-                .then_some(k),
+            .map(|(&id, can)| {
+                let can = TrashCan {
+                    ptr: can.ptr,
+                    dfs_fn: can.dfs_fn,
+                };
+                (id, can)
             })
-            .collect::<Vec<_>>();
-        for root_id in root_ids {
-            mark(root_id, &mut ref_graph);
+            .collect();
+        let mut weak_destroys = Vec::new();
+        let mut n_retries = 0;
+        loop {
+            let ambiguous = find_reachable(&mut to_collect, &mut scratch.graph);
+            if ambiguous.is_empty() || n_retries == MAX_TAG_RETRIES {
+                for id in ambiguous {
+                    mark(id, &mut scratch.graph);
+                }
+                break;
+            }
+            n_retries += 1;
+            for (id, node) in scratch.graph.drain() {
+                match node.reachability {
+                    Reachability::Unknown { .. } => {
+                        to_collect.insert(
+                            id,
+                            TrashCan {
+                                ptr: node.ptr,
+                                dfs_fn: node.dfs_fn,
+                            },
+                        );
+                    }
+                    Reachability::Reachable => {
+                        if !in_truck.contains_key(&id) && release_reachable(id, &node) {
+                            weak_destroys.push((node.weak_drop_fn, node.ptr));
+                        }
+                    }
+                }
+            }
+        }
+        let allocations = scratch
+            .graph
+            .values()
+            .filter(|node| matches!(node.reachability, Reachability::Unknown { .. }))
+            .map(|node| unsafe { (node.describe_fn)(node.ptr) })
+            .collect();
+
+        for (&id, node) in &scratch.graph {
+            if !in_truck.contains_key(&id) && release_reachable(id, node) {
+                weak_destroys.push((node.weak_drop_fn, node.ptr));
+            }
+        }
+        let mut contents = self.contents.lock();
+        for (id, can) in in_truck.drain() {
+            if contents.insert(id, can).is_some() {
+                unsafe { id.0.as_ref() }
+                    .weak
+                    .fetch_sub(1, Ordering::Release);
+            }
         }
+        drop(contents);
+        scratch.cans = in_truck;
+        self.recycle_scratch(scratch);
+        // these allocations were not garbage, but lost their last `Gc` while the graph was built
+        for (drop_fn, ptr) in weak_destroys {
+            unsafe { drop_fn(ptr) };
+        }
+        resume_caught();
+        DryRunReport { allocations }
+    }
 
-        CLEANING.with(|c| c.set(true));
+    /// Perform a collection without calling any hooks.
+    /// The caller must hold `collecting_lock` for writing.
+    fn collect_unhooked(&self) -> CollectResult {
+        let start = Instant::now();
+        COLLECTED.with(|c| c.set((0, 0)));
+        self.n_gcs_dropped.store(0, Ordering::Relaxed);
+        let reserved = self.reserved.load(Ordering::Relaxed);
+        let Scratch {
+            cans,
+            graph: mut ref_graph,
+            mut condemned,
+            ..
+        } = self.take_scratch(reserved);
+        let mut to_collect = replace(&mut *self.contents.lock(), cans);
+        let mut allocations_examined = 0;
         // set of allocations which must be destroyed because we were the last weak pointer to it
         let mut weak_destroys = Vec::new();
-        for (id, node) in &ref_graph {
-            let header_ref = unsafe { id.0.as_ref() };
-            match node.reachability {
-                Reachability::Unknown { destroy_fn, .. } => unsafe {
-                    destroy_fn(node.ptr, &ref_graph);
-                },
-                Reachability::Reachable => {
-                    if header_ref.weak.fetch_sub(1, Ordering::Release) == 1
-                        && header_ref.strong.load(Ordering::Acquire) == 0
-                    {
-                        // we are the last reference to the allocation.
-                        // mark to be cleaned up later
-                        // no real synchronization loss to storing the guard because we had the last
-                        // reference anyway
-                        weak_destroys.push((node.weak_drop_fn, node.ptr));
+
+        let mut n_retries = 0;
+        loop {
+            let ambiguous = find_reachable(&mut to_collect, &mut ref_graph);
+            allocations_examined += ref_graph.len();
+            if ambiguous.is_empty() || n_retries == MAX_TAG_RETRIES {
+                for id in ambiguous {
+                    let node = &ref_graph[&id];
+                    keep_dirty(
+                        id,
+                        TrashCan {
+                            ptr: node.ptr,
+                            dfs_fn: node.dfs_fn,
+                        },
+                    );
+                    mark(id, &mut ref_graph);
+                }
+                if !finalize_unreachable(&ref_graph) {
+                    break;
+                }
+            } else {
+                n_retries += 1;
+            }
+            // everything which was unreachable must be checked again before it can be destroyed,
+            // since the ambiguous allocations may yet be reachable, and finalizers may resurrect
+            // the allocations they can see
+            for (id, node) in ref_graph.drain() {
+                match node.reachability {
+                    Reachability::Unknown { .. } => {
+                        // the weak reference held by the graph now belongs to the trash can
+                        to_collect.insert(
+                            id,
+                            TrashCan {
+                                ptr: node.ptr,
+                                dfs_fn: node.dfs_fn,
+                            },
+                        );
+                    }
+                    Reachability::Reachable => {
+                        if release_reachable(id, &node) {
+                            weak_destroys.push((id, node.weak_drop_fn, node.ptr));
+                        }
                     }
                 }
-            };
+            }
         }
-        CLEANING.with(|c| c.set(false));
-        for (drop_fn, ptr) in weak_destroys {
+
+        #[cfg(feature = "epoch")]
+        let _retiring = epoch::Retiring::new();
+        set_cleaning(true);
+        // if a value panics while it is dropped, the bookkeeping must still be finished before
+        // the panic resumes
+        let dropped = catch_unwind(AssertUnwindSafe(|| {
+            destroy_unreachable(&ref_graph, &mut condemned, &mut weak_destroys);
+        }));
+        set_cleaning(false);
+        if self.deterministic.load(Ordering::Relaxed) {
+            sort_by_serial(&mut weak_destroys, |&(id, ..)| id);
+        }
+        for (_, drop_fn, ptr) in weak_destroys {
             unsafe { drop_fn(ptr) };
         }
-        drop(collecting_guard);
+        self.recycle_scratch(Scratch {
+            cans: to_collect,
+            graph: ref_graph,
+            condemned,
+            ..Scratch::default()
+        });
+
+        let duration = start.elapsed();
+        self.n_collections.fetch_add(1, Ordering::Relaxed);
+        self.nanos_collecting.fetch_add(
+            u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        let (allocations_freed, bytes_freed) = COLLECTED.with(Cell::get);
+        // only one collection runs at a time, so nothing else can change the ratio meanwhile
+        self.drop_ratio.store(
+            ratio::adapt(drop_ratio(), allocations_examined, allocations_freed).to_bits(),
+            Ordering::Relaxed,
+        );
+        if let Err(payload) = dropped {
+            resume_unwind(payload);
+        }
+        CollectResult {
+            allocations_examined,
+            allocations_freed,
+            bytes_freed,
+            duration,
+        }
+    }
+
+    /// Take the scratch tables for a collection, with room for at least `capacity` allocations.
+    ///
+    /// The tables are not kept locked while the collection runs, so if anything else takes them in
+    /// the meantime it is given fresh tables instead of waiting.
+    fn take_scratch(&self, capacity: usize) -> Scratch {
+        let mut scratch = take(&mut *self.scratch.lock());
+        scratch.cans.reserve(capacity);
+        scratch.graph.reserve(capacity);
+        scratch
+    }
+
+    /// Empty the tables of a finished collection and keep them for the next one.
+    ///
+    /// Every [`SHRINK_INTERVAL`] collections, tables which have grown much larger than any of
+    /// those collections needed are shrunk back down.
+    fn recycle_scratch(&self, mut scratch: Scratch) {
+        let used = scratch.graph.len();
+        scratch.cans.clear();
+        scratch.graph.clear();
+        scratch.condemned.clear();
+
+        let mut kept = self.scratch.lock();
+        // when collections are nested, the larger of their tables are the ones worth keeping
+        if kept.graph.capacity() > scratch.graph.capacity() {
+            return;
+        }
+        scratch.high_water = kept.high_water.max(used);
+        scratch.n_uses = kept.n_uses + 1;
+        if scratch.n_uses >= SHRINK_INTERVAL {
+            let target = (scratch.high_water * 2).max(self.reserved.load(Ordering::Relaxed));
+            scratch.cans.shrink_to(target);
+            scratch.graph.shrink_to(target);
+            scratch.condemned.shrink_to(target);
+            scratch.high_water = 0;
+            scratch.n_uses = 0;
+        }
+        *kept = scratch;
+    }
+}
+
+/// Build the reference graph of every allocation reachable from those in `to_collect` into the
+/// empty `ref_graph`, and then mark which of them are reachable from outside of the
+/// garbage-collected heap.
+///
+/// `to_collect` is left empty, and the graph holds a weak reference to every allocation in it.
+///
+/// A `Gc` whose tag was ambiguous is counted as if its tag had been left over from an earlier
+/// sweep, which can only be wrong if the allocation holding it is reachable.
+/// Returns the allocations holding such a `Gc` which were not found to be reachable.
+/// They are left unmarked, so they must either be searched again with fresh tags or be marked.
+fn find_reachable(
+    to_collect: &mut AllocationMap<TrashCan>,
+    ref_graph: &mut AllocationMap<AllocationInfo>,
+) -> Vec<AllocationId> {
+    ref_graph.reserve(to_collect.len());
+
+    CURRENT_TAG.fetch_add(1, Ordering::Release);
+
+    #[cfg(feature = "parallel-collect")]
+    let (mut forced, mut ambiguous) = parallel::build(to_collect, ref_graph);
+    #[cfg(not(feature = "parallel-collect"))]
+    let (mut forced, mut ambiguous) = (Vec::new(), Vec::new());
+    let mut builder = Builder {
+        ref_graph,
+        #[cfg(feature = "parallel-collect")]
+        worker: None,
+        ambiguous: Vec::new(),
+    };
+    for (_, TrashCan { ptr, dfs_fn }) in to_collect.drain() {
+        unsafe { dfs_fn(ptr, &mut builder) };
+    }
+    ambiguous.append(&mut builder.ambiguous);
+    for &(holder, target) in &ambiguous {
+        match &mut ref_graph.get_mut(&holder).unwrap().reachability {
+            Reachability::Unknown { children, .. } => children.push(target),
+            Reachability::Reachable => {
+                forced.push(target);
+                continue;
+            }
+        }
+        if let Reachability::Unknown { n_unaccounted, .. } =
+            &mut ref_graph.get_mut(&target).unwrap().reachability
+        {
+            *n_unaccounted = n_unaccounted.saturating_sub(1);
+        }
+    }
+
+    let root_ids = ref_graph
+        .iter()
+        .filter_map(|(&k, v)| match v.reachability {
+            Reachability::Reachable => Some(k),
+            Reachability::Unknown { n_unaccounted, .. } => (n_unaccounted > 0
+                || unsafe { k.0.as_ref().weak.load(Ordering::Acquire) > 1 })
+            .then_some(k),
+        })
+        .chain(forced)
+        .collect::<Vec<_>>();
+    for root_id in root_ids {
+        mark(root_id, ref_graph);
+    }
+    ambiguous
+        .into_iter()
+        .map(|(holder, _)| holder)
+        .filter(|id| matches!(ref_graph[id].reachability, Reachability::Unknown { .. }))
+        .collect()
+}
+
+/// Run the finalizers which have not yet been run of every allocation in `graph` which is not
+/// reachable.
+///
+/// No collections are run while the finalizers do.
+/// Returns whether any finalizer was run.
+fn finalize_unreachable(graph: &AllocationMap<AllocationInfo>) -> bool {
+    let _guard = HookGuard::new();
+    let mut ran = false;
+    for_each_node(graph, |id, node| {
+        if let Reachability::Unknown { .. } = node.reachability {
+            // the graph's weak reference keeps the allocation from being freed, even if a
+            // finalizer drops its last `Gc`
+            ran |= unsafe { id.0.as_ref() }.finalize();
+        }
+    });
+    ran
+}
+
+/// Call `f` on every node in `graph`.
+///
+/// If collections are deterministic, the nodes are visited in the order in which their
+/// allocations were made.
+fn for_each_node(
+    graph: &AllocationMap<AllocationInfo>,
+    mut f: impl FnMut(AllocationId, &AllocationInfo),
+) {
+    if GARBAGE_TRUCK.deterministic.load(Ordering::Relaxed) {
+        let mut ids = graph.keys().copied().collect::<Vec<_>>();
+        // the graph holds a weak reference to every allocation in it, so none have been freed
+        sort_by_serial(&mut ids, |&id| id);
+        for id in ids {
+            f(id, &graph[&id]);
+        }
+    } else {
+        for (&id, node) in graph {
+            f(id, node);
+        }
+    }
+}
+
+/// Sort `items` by the serial numbers of the allocations `id_of` picks out of them, oldest first.
+///
+/// The allocations which were made before collections were deterministic have no serial number,
+/// and come before all of the others in no particular order.
+/// None of the allocations may have been freed.
+fn sort_by_serial<I>(items: &mut [I], id_of: impl Fn(&I) -> AllocationId) {
+    let serials = GARBAGE_TRUCK.serials.lock();
+    items.sort_by_cached_key(|item| {
+        let id = id_of(item);
+        if unsafe { id.0.as_ref() }.has_flag(SERIAL) {
+            serials.get(&id).copied()
+        } else {
+            None
+        }
+    });
+}
+
+/// Give the newly constructed allocation behind `ptr` its entries in the side tables of the
+/// garbage truck: `finalizer` as the function which runs its finalizer, and a serial number if
+/// collections are deterministic.
+pub(super) fn register_side_entries<T: Collectable + Send + Sync + ?Sized>(
+    ptr: NonNull<GcBox<T>>,
+    finalizer: Option<Finalizer>,
+) {
+    let box_ref = unsafe { ptr.as_ref() };
+    let id = AllocationId::from(ptr);
+    if let Some(finalizer) = finalizer {
+        GARBAGE_TRUCK.finalizers.lock().insert(id, finalizer);
+        box_ref.set_flag(FINALIZER);
+    }
+    if GARBAGE_TRUCK.deterministic.load(Ordering::Relaxed) {
+        let serial = NEXT_SERIAL.fetch_add(1, Ordering::Relaxed);
+        GARBAGE_TRUCK.serials.lock().insert(id, serial);
+        box_ref.set_flag(SERIAL);
+    }
+}
+
+/// Remove every entry which the allocation behind `ptr` has in the side tables of the garbage
+/// truck, so that its address may be reused.
+///
+/// # Safety
+///
+/// `ptr` must point to an allocation which is about to be freed.
+/// Its value may already have been dropped, but its header must not have been.
+pub(super) unsafe fn forget_side_entries<T: Collectable + Send + Sync + ?Sized>(
+    ptr: NonNull<GcBox<T>>,
+) {
+    let shared = (*addr_of!((*ptr.as_ptr()).shared)).load(Ordering::Acquire);
+    let id = AllocationId::from(ptr);
+    if shared & FINALIZER != 0 {
+        GARBAGE_TRUCK.finalizers.lock().remove(&id);
+    }
+    if shared & ROOTED != 0 {
+        GARBAGE_TRUCK.roots.lock().remove(&id);
+    }
+    if shared & SERIAL != 0 {
+        GARBAGE_TRUCK.serials.lock().remove(&id);
+    }
+}
+
+/// Take the finalizer of `allocation` out of the side table of the garbage truck, so that it is
+/// only ever run once.
+pub(super) fn take_finalizer<T: Collectable + Send + Sync + ?Sized>(
+    allocation: &GcBox<T>,
+) -> Option<Finalizer> {
+    GARBAGE_TRUCK
+        .finalizers
+        .lock()
+        .remove(&AllocationId::from(allocation))
+}
+
+/// Count a new [`Root`](super::Root) to `allocation`.
+pub(super) fn add_root<T: Collectable + Send + Sync + ?Sized>(allocation: &GcBox<T>) {
+    let mut roots = GARBAGE_TRUCK.roots.lock();
+    let n_roots = roots.entry(AllocationId::from(allocation)).or_insert(0);
+    *n_roots += 1;
+    if *n_roots == 1 {
+        allocation.set_flag(ROOTED);
+    }
+}
+
+/// Count the loss of a [`Root`](super::Root) to `allocation`.
+pub(super) fn remove_root<T: Collectable + Send + Sync + ?Sized>(allocation: &GcBox<T>) {
+    let mut roots = GARBAGE_TRUCK.roots.lock();
+    let Entry::Occupied(mut n_roots) = roots.entry(AllocationId::from(allocation)) else {
+        return;
+    };
+    *n_roots.get_mut() -= 1;
+    if *n_roots.get() == 0 {
+        n_roots.remove();
+        allocation.clear_flag(ROOTED);
+    }
+}
+
+/// Release the weak reference which a reference graph holds to the reachable allocation `id`,
+/// described by `node`.
+///
+/// If the allocation is overdrawn, it is put back in the truck first, so that the next collection
+/// checks it again.
+///
+/// Returns whether that was the last reference to the allocation, in which case it must be
+/// destroyed once the collection is done.
+fn release_reachable(id: AllocationId, node: &AllocationInfo) -> bool {
+    let header_ref = unsafe { id.0.as_ref() };
+    if header_ref.is_overdrawn() {
+        keep_dirty(
+            id,
+            TrashCan {
+                ptr: node.ptr,
+                dfs_fn: node.dfs_fn,
+            },
+        );
+    }
+    header_ref.weak.fetch_sub(1, Ordering::Release) == 1 && header_ref.ref_count() == 0
+}
+
+/// Put the allocation `id` in the truck for the next collection, unless it is already there.
+///
+/// This is for allocations which are overdrawn, whose owning thread may drop their last reference
+/// without marking them dirty, so that only a collection can find out.
+fn keep_dirty(id: AllocationId, can: TrashCan) {
+    if let Entry::Vacant(v) = GARBAGE_TRUCK.contents.lock().entry(id) {
+        v.insert(can);
+        unsafe { id.0.as_ref() }
+            .weak
+            .fetch_add(1, Ordering::Acquire);
     }
 }
 
@@ -344,77 +2077,128 @@ impl GarbageTruck {
 /// # Inputs
 ///
 /// - `ptr`: A pointer to the allocation that we should start constructing from.
-/// - `ref_graph`: A lookup from allocation IDs to node information about that allocation.
+/// - `builder`: The reference graph under construction.
 ///
 /// # Effects
 ///
-/// `ref_graph` will be expanded to include all allocations reachable from `ptr`.
+/// The graph will be expanded to include all allocations reachable from `ptr`.
 ///
 /// # Safety
 ///
 /// `ptr` must have been created as a pointer to a `GcBox<T>`.
-unsafe fn dfs<T: Collectable + Send + Sync + ?Sized>(
-    ptr: Erased,
-    ref_graph: &mut HashMap<AllocationId, AllocationInfo>,
-) {
+unsafe fn dfs<T: Collectable + Send + Sync + ?Sized>(ptr: Erased, builder: &mut Builder<'_>) {
     let box_ref = unsafe { ptr.specify::<GcBox<T>>().as_ref() };
     let starting_id = AllocationId::from(box_ref);
-    let Entry::Vacant(v) = ref_graph.entry(starting_id) else {
+    let Some(v) = builder.claim(starting_id) else {
         // the weak count was incremented by another DFS operation elsewhere.
         // Decrement it to have only one from us.
         box_ref.weak.fetch_sub(1, Ordering::Release);
         return;
     };
-    let strong_count = box_ref.strong.load(Ordering::Acquire);
+    let strong_count = box_ref.ref_count();
     v.insert(AllocationInfo {
         ptr,
         weak_drop_fn: drop_weak_zero::<T>,
+        dfs_fn: dfs::<T>,
+        describe_fn: describe_garbage::<T>,
         reachability: Reachability::Unknown {
             children: Vec::new(),
             n_unaccounted: strong_count,
-            destroy_fn: destroy_erased::<T>,
+            destroy_fn: destroy_batch::<T>,
         },
     });
 
-    if box_ref
+    if box_ref.is_rooted() {
+        // a rooted allocation is known to be reachable, so there is nothing to find inside it
+        builder.mark(starting_id);
+        return;
+    }
+
+    let traced = box_ref
         .value
         .accept(&mut Dfs {
-            ref_graph,
+            builder: &mut *builder,
             current_id: starting_id,
         })
-        .is_err()
-        || box_ref.generation.load(Ordering::Acquire) >= CURRENT_TAG.load(Ordering::Relaxed)
+        .is_ok();
+    if !traced {
+        trace_error(std::any::type_name::<T>());
+    }
+    if !traced || box_ref.generation.load(Ordering::Acquire) >= CURRENT_TAG.load(Ordering::Relaxed)
     {
         // box_ref.value was accessed while we worked
         // mark this allocation as reachable
-        mark(starting_id, ref_graph);
+        builder.mark(starting_id);
     }
 }
 
-#[derive(Debug)]
-/// The visitor structure used for building the found-reference-graph of allocations.
-struct Dfs<'a> {
+/// A reference graph under construction.
+struct Builder<'a> {
     /// The reference graph.
     /// Each allocation is assigned a node.
-    ref_graph: &'a mut HashMap<AllocationId, AllocationInfo>,
+    ref_graph: &'a mut AllocationMap<AllocationInfo>,
+    #[cfg(feature = "parallel-collect")]
+    /// If the graph is being built by several threads at once, the state of the thread building
+    /// this part of it.
+    worker: Option<parallel::Worker<'a>>,
+    /// The allocations holding a `Gc` whose tag may have been left over from an earlier sweep,
+    /// each paired with the allocation which that `Gc` points to.
+    ambiguous: Vec<(AllocationId, AllocationId)>,
+}
+
+impl Builder<'_> {
+    /// Claim the allocation `id`, so that it is given a node in this graph and searched by this
+    /// builder.
+    ///
+    /// Returns `None` if the allocation has already been claimed, by this builder or another.
+    fn claim(&mut self, id: AllocationId) -> Option<VacantEntry<'_, AllocationId, AllocationInfo>> {
+        let Entry::Vacant(v) = self.ref_graph.entry(id) else {
+            return None;
+        };
+        #[cfg(feature = "parallel-collect")]
+        if let Some(worker) = &self.worker {
+            if !worker.claim(id) {
+                return None;
+            }
+        }
+        Some(v)
+    }
+
+    /// Mark the allocation `id`, which must have a node in this graph, as reachable.
+    fn mark(&mut self, id: AllocationId) {
+        #[cfg(feature = "parallel-collect")]
+        if let Some(worker) = &mut self.worker {
+            // what `id` refers to may belong to other threads' parts of the graph, so marking it
+            // must wait until the parts are put together
+            worker.forced.push(id);
+            return;
+        }
+        mark(id, self.ref_graph);
+    }
+}
+
+/// The visitor structure used for building the found-reference-graph of allocations.
+struct Dfs<'a, 'b> {
+    /// The reference graph under construction.
+    builder: &'a mut Builder<'b>,
     /// The allocation ID currently being visited.
     /// Used for knowing which node is the parent of another.
     current_id: AllocationId,
 }
 
-impl<'a> Visitor for Dfs<'a> {
+impl Visitor for Dfs<'_, '_> {
     fn visit_sync<T>(&mut self, gc: &Gc<T>)
     where
         T: Collectable + Send + Sync + ?Sized,
     {
-        let ptr = unsafe { (*gc.ptr.get()).unwrap() };
+        let ptr = gc.box_ptr().unwrap();
         let box_ref = unsafe { ptr.as_ref() };
         let current_tag = CURRENT_TAG.load(Ordering::Relaxed);
-        if gc.tag.swap(current_tag, Ordering::Relaxed) >= current_tag
-            || box_ref.generation.load(Ordering::Acquire) >= current_tag
+        let retagged = gc.retag(current_tag);
+        if retagged && !TAGS_MAY_ALIAS || box_ref.generation.load(Ordering::Acquire) >= current_tag
         {
             // This pointer was already tagged by this sweep, so it must have been moved by
-            mark(self.current_id, self.ref_graph);
+            self.builder.mark(self.current_id);
             return;
         }
 
@@ -423,6 +2207,7 @@ impl<'a> Visitor for Dfs<'a> {
         let Reachability::Unknown {
             ref mut children, ..
         } = self
+            .builder
             .ref_graph
             .get_mut(&self.current_id)
             .unwrap()
@@ -432,47 +2217,77 @@ impl<'a> Visitor for Dfs<'a> {
             // its ref graph
             return;
         };
-        children.push(new_id);
+        if retagged {
+            // the tag may instead be left over from an earlier sweep, which can only be told once
+            // the rest of the graph is built, so the reference is not counted until then
+            self.builder.ambiguous.push((self.current_id, new_id));
+        } else {
+            children.push(new_id);
+        }
 
-        match self.ref_graph.entry(new_id) {
+        match self.builder.ref_graph.entry(new_id) {
             Entry::Occupied(mut o) => match o.get_mut().reachability {
                 Reachability::Unknown {
                     ref mut n_unaccounted,
                     ..
-                } => {
-                    *n_unaccounted -= 1;
+                } if !retagged => {
+                    *n_unaccounted = n_unaccounted.saturating_sub(1);
                 }
-                Reachability::Reachable => (),
+                _ => (),
             },
             Entry::Vacant(v) => {
+                #[cfg(feature = "parallel-collect")]
+                if let Some(worker) = &mut self.builder.worker {
+                    if !worker.claim(new_id) {
+                        // another thread is searching this allocation, and accounts for this
+                        // reference once the parts of the graph are put together
+                        if !retagged {
+                            worker.found_elsewhere(new_id);
+                        }
+                        return;
+                    }
+                }
                 // This allocation has never been visited by the reference graph builder
-                let strong_count = box_ref.strong.load(Ordering::Acquire);
+                let strong_count = box_ref.ref_count();
                 box_ref.weak.fetch_add(1, Ordering::Acquire);
                 v.insert(AllocationInfo {
                     ptr: Erased::new(ptr),
                     weak_drop_fn: drop_weak_zero::<T>,
+                    dfs_fn: dfs::<T>,
+                    describe_fn: describe_garbage::<T>,
                     reachability: Reachability::Unknown {
                         children: Vec::new(),
-                        n_unaccounted: strong_count - 1,
-                        destroy_fn: destroy_erased::<T>,
+                        // the counts may have been read while they changed, but if so, the
+                        // allocation's generation shows it and it is marked reachable anyway
+                        n_unaccounted: strong_count.saturating_sub(usize::from(!retagged)),
+                        destroy_fn: destroy_batch::<T>,
                     },
                 });
 
+                if box_ref.is_rooted() {
+                    // a rooted allocation is known to be reachable, and its references are left
+                    // unaccounted for, so anything else found behind it is marked as well
+                    self.builder.mark(new_id);
+                    return;
+                }
+
                 // Save the previously visited ID, then carry on to the next one
                 swap(&mut new_id, &mut self.current_id);
 
-                if box_ref.value.accept(self).is_err()
-                    || box_ref.generation.load(Ordering::Acquire) >= current_tag
-                {
+                let traced = box_ref.value.accept(self).is_ok();
+                if !traced {
+                    trace_error(std::any::type_name::<T>());
+                }
+                if !traced || box_ref.generation.load(Ordering::Acquire) >= current_tag {
                     // On failure, this means `**gc` is accessible, and should be marked
                     // as such
-                    mark(self.current_id, self.ref_graph);
+                    self.builder.mark(self.current_id);
                 }
 
                 // Restore current_id and carry on
                 swap(&mut new_id, &mut self.current_id);
             }
-        };
+        }
     }
 
     fn visit_unsync<T>(&mut self, _: &crate::unsync::Gc<T>)
@@ -485,7 +2300,7 @@ impl<'a> Visitor for Dfs<'a> {
 
 /// Traverse the reference graph, marking `root` and any allocations reachable from `root` as
 /// reachable.
-fn mark(root: AllocationId, graph: &mut HashMap<AllocationId, AllocationInfo>) {
+fn mark(root: AllocationId, graph: &mut AllocationMap<AllocationInfo>) {
     let node = graph.get_mut(&root).unwrap();
     if let Reachability::Unknown { children, .. } =
         replace(&mut node.reachability, Reachability::Reachable)
@@ -496,36 +2311,112 @@ fn mark(root: AllocationId, graph: &mut HashMap<AllocationId, AllocationInfo>) {
     }
 }
 
-/// Destroy an allocation, obliterating its GCs, dropping it, and deallocating it.
+/// Describe the allocation behind an erased pointer for a [`DryRunReport`].
 ///
 /// # Safety
 ///
 /// `ptr` must have been created from a pointer to a `GcBox<T>`.
-unsafe fn destroy_erased<T: Collectable + Send + Sync + ?Sized>(
+unsafe fn describe_garbage<T: Collectable + Send + Sync + ?Sized>(
     ptr: Erased,
-    graph: &HashMap<AllocationId, AllocationInfo>,
+) -> GarbageAllocation {
+    let box_ref = ptr.specify::<GcBox<T>>().as_ref();
+    GarbageAllocation {
+        addr: std::ptr::from_ref(&box_ref.value).cast::<()>() as usize,
+        type_name: std::any::type_name::<T>(),
+        size: Layout::for_value(box_ref).size(),
+    }
+}
+
+/// Destroy every allocation which a collection found to be unreachable in `graph`, and release
+/// the weak references which the graph holds to the reachable ones.
+///
+/// The reachable allocations whose last reference was held by the graph are added to
+/// `weak_destroys`, to be destroyed once the collection is done.
+/// The unreachable ones are gathered into `condemned`, and destroyed in batches of the same type,
+/// so that each type's destruction runs in a tight loop instead of through a call which is hard
+/// to predict.
+/// If collections are deterministic, the order of allocation is kept instead, so only runs of
+/// allocations of the same type are batched.
+fn destroy_unreachable(
+    graph: &AllocationMap<AllocationInfo>,
+    condemned: &mut Vec<Condemned>,
+    weak_destroys: &mut Vec<WeakDestroy>,
 ) {
-    /// A visitor for decrementing the reference count of pointees.
-    struct PrepareForDestruction<'a> {
-        /// The reference graph.
-        /// Must have been populated with reachability already.
-        graph: &'a HashMap<AllocationId, AllocationInfo>,
+    for_each_node(graph, |id, node| match node.reachability {
+        Reachability::Unknown { destroy_fn, .. } => {
+            unsafe { id.0.as_ref() }.condemn();
+            condemned.push(Condemned {
+                destroy_fn,
+                ptr: node.ptr,
+            });
+        }
+        Reachability::Reachable => {
+            if release_reachable(id, node) {
+                // we are the last reference to the allocation.
+                // mark to be cleaned up later
+                // no real synchronization loss to storing the guard because we had
+                // the last reference anyway
+                weak_destroys.push((id, node.weak_drop_fn, node.ptr));
+            }
+        }
+    });
+    if !GARBAGE_TRUCK.deterministic.load(Ordering::Relaxed) {
+        condemned.sort_by_key(|c| c.destroy_fn as usize);
+    }
+    for pass in [DestroyPass::Release, DestroyPass::Free] {
+        for batch in condemned.chunk_by(|a, b| a.destroy_fn as usize == b.destroy_fn as usize) {
+            unsafe { (batch[0].destroy_fn)(batch, pass) };
+        }
+    }
+}
+
+/// Run one pass of destroying a batch of unreachable allocations, as in [`release_condemned`] or
+/// [`destroy_erased`].
+///
+/// # Safety
+///
+/// Every allocation in `batch` must have been created from a pointer to a `GcBox<T>`.
+/// Every allocation condemned by the running collection must have been released before any of
+/// them is freed.
+unsafe fn destroy_batch<T: Collectable + Send + Sync + ?Sized>(
+    batch: &[Condemned],
+    pass: DestroyPass,
+) {
+    for condemned in batch {
+        match pass {
+            DestroyPass::Release => release_condemned::<T>(condemned.ptr),
+            DestroyPass::Free => destroy_erased::<T>(condemned.ptr),
+        }
     }
+}
+
+/// Release every reference inside a condemned allocation, obliterating the ones to other
+/// condemned allocations.
+///
+/// # Safety
+///
+/// `ptr` must have been created from a pointer to a `GcBox<T>`, every allocation condemned by the
+/// running collection must already have been marked as such, and none of them may have been freed.
+unsafe fn release_condemned<T: Collectable + Send + Sync + ?Sized>(ptr: Erased) {
+    /// A visitor for decrementing the reference count of pointees.
+    struct PrepareForDestruction;
 
-    impl Visitor for PrepareForDestruction<'_> {
+    impl Visitor for PrepareForDestruction {
         fn visit_sync<T>(&mut self, gc: &crate::sync::Gc<T>)
         where
             T: Collectable + Send + Sync + ?Sized,
         {
-            let id = AllocationId::from(unsafe { (*gc.ptr.get()).unwrap() });
-            if matches!(self.graph[&id].reachability, Reachability::Reachable) {
-                unsafe {
-                    id.0.as_ref().strong.fetch_sub(1, Ordering::Release);
-                }
-            } else {
-                unsafe {
-                    gc.ptr.get().write((*gc.ptr.get()).as_null());
-                }
+            let ptr = gc.box_ptr().unwrap();
+            if unsafe { ptr.as_ref() }.is_condemned() {
+                unsafe { gc.kill() };
+            } else if unsafe { ptr.as_ref() }.release_shared().is_none() {
+                keep_dirty(
+                    AllocationId::from(ptr),
+                    TrashCan {
+                        ptr: Erased::new(ptr),
+                        dfs_fn: dfs::<T>,
+                    },
+                );
             }
         }
 
@@ -537,14 +2428,55 @@ unsafe fn destroy_erased<T: Collectable + Send + Sync + ?Sized>(
         }
     }
 
-    let specified = ptr.specify::<GcBox<T>>().as_mut();
-    specified
+    ptr.specify::<GcBox<T>>()
+        .as_ref()
         .value
-        .accept(&mut PrepareForDestruction { graph })
+        .accept(&mut PrepareForDestruction)
         .expect("allocation assumed to be unreachable but somehow was accessed");
+}
+
+/// Drop and deallocate a condemned allocation, whose references have all been released.
+///
+/// # Safety
+///
+/// `ptr` must have been created from a pointer to a `GcBox<T>`, and must have been released by
+/// [`release_condemned`].
+unsafe fn destroy_erased<T: Collectable + Send + Sync + ?Sized>(ptr: Erased) {
+    let specified = ptr.specify::<GcBox<T>>().as_mut();
     let layout = Layout::for_value(specified);
-    drop_in_place(specified);
-    dealloc(std::ptr::from_mut::<GcBox<T>>(specified).cast(), layout);
+    #[cfg(any(
+        feature = "leak-detection",
+        feature = "heap-dump",
+        feature = "heap-inspection"
+    ))]
+    forget_live(std::ptr::from_mut::<GcBox<T>>(specified));
+    #[cfg(feature = "epoch")]
+    {
+        // the allocation is as good as freed, so this collection counts it
+        notify_collected(layout);
+        epoch::retire(ptr, layout, destroy_retired::<T>);
+    }
+    #[cfg(not(feature = "epoch"))]
+    if drop_collected(specified) {
+        free_box(std::ptr::from_mut::<GcBox<T>>(specified), layout);
+        notify_deallocated(layout);
+        notify_collected(layout);
+    }
+}
+
+#[cfg(feature = "epoch")]
+/// Drop and free an allocation with layout `layout` which a collection retired.
+///
+/// # Safety
+///
+/// `ptr` must have been created from a pointer to a `GcBox<T>`, and must have been retired by a
+/// collection which has already released every `Gc` inside it.
+unsafe fn destroy_retired<T: Collectable + Send + Sync + ?Sized>(ptr: Erased, layout: Layout) {
+    let specified = ptr.specify::<GcBox<T>>().as_ptr();
+    if drop_retired(specified) {
+        free_box(specified, layout);
+        notify_deallocated(layout);
+    }
 }
 
 /// Function for handling dropping an allocation when its weak and strong reference count reach
@@ -554,13 +2486,33 @@ unsafe fn destroy_erased<T: Collectable + Send + Sync + ?Sized>(
 ///
 /// `ptr` must have been created as a pointer to a `GcBox<T>`.
 unsafe fn drop_weak_zero<T: Collectable + Send + Sync + ?Sized>(ptr: Erased) {
-    let mut specified = ptr.specify::<GcBox<T>>();
+    let specified = ptr.specify::<GcBox<T>>();
     assert_eq!(specified.as_ref().weak.load(Ordering::Relaxed), 0);
-    assert_eq!(specified.as_ref().strong.load(Ordering::Relaxed), 0);
+    assert_eq!(specified.as_ref().ref_count(), 0);
 
+    {
+        // the collecting lock may still be held, so no collection may be started
+        let _guard = HookGuard::new();
+        specified.as_ref().finalize();
+    }
     let layout = Layout::for_value(specified.as_ref());
-    drop_in_place(specified.as_mut());
-    dealloc(specified.as_ptr().cast(), layout);
+    #[cfg(any(
+        feature = "leak-detection",
+        feature = "heap-dump",
+        feature = "heap-inspection"
+    ))]
+    forget_live(specified.as_ptr());
+    if drop_collected(specified.as_ptr()) {
+        free_box(specified.as_ptr(), layout);
+        notify_deallocated(layout);
+        notify_collected(layout);
+    }
+}
+
+impl Hash for AllocationId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.0.as_ptr() as usize);
+    }
 }
 
 unsafe impl Send for AllocationId {}
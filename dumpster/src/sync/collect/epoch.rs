@@ -0,0 +1,120 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Deferred destruction of garbage through epoch-based reclamation.
+//!
+//! With the `epoch` feature, a collection does not drop and free the garbage it finds by itself.
+//! Once the `Gc`s inside an unreachable allocation have been cut off from the rest of the heap, the
+//! allocation is retired into an epoch queue, and it is only destroyed after every thread has
+//! passed a quiescent point since.
+//! A thread passes a quiescent point whenever it adds its counts of `Gc`s to the garbage truck,
+//! and then destroys a bounded batch of retired allocations, so that the work of dropping garbage
+//! is spread over every thread instead of falling on whichever one ran the collection.
+
+use std::{
+    alloc::Layout,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::yield_now,
+};
+
+use crossbeam_epoch::Guard;
+
+use crate::{ptr::Erased, unwind::resume_retired, CollectionMode};
+
+use super::{
+    super::background::collector_elsewhere, current_mode, currently_cleaning, set_cleaning,
+};
+
+/// The number of allocations which have been retired but not yet destroyed.
+static N_RETIRED: AtomicUsize = AtomicUsize::new(0);
+
+/// A pin of a thread which is retiring garbage.
+///
+/// Nothing retired while it is held can be destroyed, and once it is dropped, everything retired
+/// is handed over to the other threads.
+pub(super) struct Retiring(Guard);
+
+impl Retiring {
+    /// Pin this thread to retire garbage.
+    pub(super) fn new() -> Retiring {
+        Retiring(crossbeam_epoch::pin())
+    }
+}
+
+impl Drop for Retiring {
+    fn drop(&mut self) {
+        self.0.flush();
+    }
+}
+
+/// Retire the allocation `ptr`, with layout `layout`, so that `destroy` is called on it once every
+/// thread has passed a quiescent point.
+///
+/// # Safety
+///
+/// `ptr` must be unreachable, every `Gc` inside it must already have been killed or had its
+/// reference released, and `destroy` must be safe to call on it from any thread.
+pub(super) unsafe fn retire(ptr: Erased, layout: Layout, destroy: unsafe fn(Erased, Layout)) {
+    N_RETIRED.fetch_add(1, Ordering::Relaxed);
+    crossbeam_epoch::pin().defer_unchecked(move || {
+        // the `Gc`s inside the value have already been accounted for, so dropping them must not
+        // change any reference counts
+        let cleaning = set_cleaning(true);
+        destroy(ptr, layout);
+        set_cleaning(cleaning);
+        N_RETIRED.fetch_sub(1, Ordering::Release);
+    });
+}
+
+/// Pass a quiescent point, destroying a batch of retired allocations if there are any.
+///
+/// While a background collector is running, it destroys all garbage itself, so other threads
+/// leave the retired allocations to it.
+/// Threads in [`CollectionMode::Queued`] also leave them to others, so that dropping a `Gc` never
+/// destroys garbage which it did not own.
+pub(super) fn reclaim() {
+    if N_RETIRED.load(Ordering::Relaxed) > 0
+        && !currently_cleaning()
+        && !collector_elsewhere()
+        && current_mode() != CollectionMode::Queued
+    {
+        crossbeam_epoch::pin().flush();
+    }
+}
+
+/// Destroy every retired allocation, waiting for any which other threads are destroying.
+///
+/// If dropping a retired value panicked since this was last called, the panic is resumed once
+/// everything else has been destroyed.
+pub fn drain_retired() {
+    if currently_cleaning() {
+        // this thread is pinned while it destroys garbage, so no epoch could pass
+        return;
+    }
+    let mut last = N_RETIRED.load(Ordering::Acquire);
+    while last > 0 {
+        crossbeam_epoch::pin().flush();
+        let now = N_RETIRED.load(Ordering::Acquire);
+        if now == last {
+            // another thread is pinned, and will be done soon
+            yield_now();
+        }
+        last = now;
+    }
+    resume_retired();
+}
@@ -0,0 +1,204 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Building the reference graph of a collection on several threads at once.
+//!
+//! The dirty allocations are split between the threads, each of which searches from its share of
+//! them and builds its own part of the graph.
+//! Every allocation is claimed by exactly one thread, which gives it a node and searches inside
+//! it; any other thread which finds a reference to it only counts that reference.
+//! Once every thread is done, the parts are put together and the counted references are
+//! accounted for, after which the graph is exactly the one a single thread would have built.
+
+use std::{
+    collections::HashSet,
+    hash::BuildHasher,
+    num::NonZeroUsize,
+    panic::resume_unwind,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{available_parallelism, scope},
+};
+
+use parking_lot::Mutex;
+
+use super::{
+    allocation_map, AllocationId, AllocationInfo, AllocationMap, BuildPtrHasher, Builder,
+    Reachability, TrashCan,
+};
+
+/// The fewest dirty allocations which each thread building the graph must be given.
+/// With fewer, the cost of starting the threads outweighs the work they would share.
+const MIN_PER_WORKER: usize = 1024;
+
+/// The number of separately-locked shards in the set of claimed allocations.
+const N_SHARDS: usize = 64;
+
+/// The number of threads which build the reference graph of a collection, or 0 to use one for
+/// every available core.
+static N_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the number of threads which build the reference graph during each `sync` collection.
+///
+/// The dirty allocations are split between the threads, so a collection only uses as many as it
+/// has enough dirty allocations to keep busy.
+/// Passing 0 restores the default, which is one thread for every available core, and passing 1
+/// builds every graph on the collecting thread alone.
+/// Garbage is always dropped on the collecting thread.
+///
+/// This is only available with the `parallel-collect` feature.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{collect, set_collect_workers};
+///
+/// set_collect_workers(4);
+/// collect();
+/// ```
+pub fn set_collect_workers(n_workers: usize) {
+    N_WORKERS.store(n_workers, Ordering::Relaxed);
+}
+
+/// Get the number of threads which may build the reference graph of a collection.
+fn n_workers() -> usize {
+    match N_WORKERS.load(Ordering::Relaxed) {
+        0 => available_parallelism().map_or(1, NonZeroUsize::get),
+        n => n,
+    }
+}
+
+/// The set of allocations which have been claimed by a thread building the graph.
+struct Claims([Mutex<HashSet<AllocationId, BuildPtrHasher>>; N_SHARDS]);
+
+impl Claims {
+    /// Claim the allocation `id` for the calling thread.
+    ///
+    /// Returns `false` if it had already been claimed.
+    fn claim(&self, id: AllocationId) -> bool {
+        // the low bits pick a bucket inside the shard, so the shard is picked from higher ones
+        let shard = (BuildPtrHasher.hash_one(id) >> 32) as usize % N_SHARDS;
+        self.0[shard].lock().insert(id)
+    }
+}
+
+/// The state of one thread building part of the reference graph.
+pub(super) struct Worker<'a> {
+    /// The allocations claimed by every thread.
+    claims: &'a Claims,
+    /// The number of references this thread found to each allocation claimed by another thread.
+    elsewhere: AllocationMap<usize>,
+    /// The allocations found to be reachable, which are marked once the graph is complete.
+    pub forced: Vec<AllocationId>,
+}
+
+impl Worker<'_> {
+    /// Claim the allocation `id` for this thread.
+    ///
+    /// Returns `false` if another thread had already claimed it.
+    pub fn claim(&self, id: AllocationId) -> bool {
+        self.claims.claim(id)
+    }
+
+    /// Count a reference to the allocation `id`, which another thread has claimed.
+    pub fn found_elsewhere(&mut self, id: AllocationId) {
+        *self.elsewhere.entry(id).or_insert(0) += 1;
+    }
+}
+
+/// Build the reference graph of every allocation reachable from those in `to_collect` into the
+/// empty `ref_graph` on several threads, if there are enough of them to be worth it.
+///
+/// If the graph was built, `to_collect` is left empty, and the graph is left unmarked.
+/// Returns the allocations which were found to be reachable while the graph was built, which must
+/// be marked along with the roots of the graph, and the references whose tags were ambiguous.
+pub(super) fn build(
+    to_collect: &mut AllocationMap<TrashCan>,
+    ref_graph: &mut AllocationMap<AllocationInfo>,
+) -> (Vec<AllocationId>, Vec<(AllocationId, AllocationId)>) {
+    let n_workers = n_workers().min(to_collect.len() / MIN_PER_WORKER);
+    if n_workers < 2 {
+        return (Vec::new(), Vec::new());
+    }
+    let cans = to_collect.drain().map(|(_, can)| can).collect::<Vec<_>>();
+    let claims = Claims(std::array::from_fn(|_| Mutex::new(HashSet::default())));
+
+    let parts = scope(|s| {
+        let handles = cans
+            .chunks(cans.len().div_ceil(n_workers))
+            .map(|cans| {
+                let claims = &claims;
+                s.spawn(move || {
+                    let mut graph = allocation_map(cans.len());
+                    let mut builder = Builder {
+                        ref_graph: &mut graph,
+                        worker: Some(Worker {
+                            claims,
+                            elsewhere: AllocationMap::default(),
+                            forced: Vec::new(),
+                        }),
+                        ambiguous: Vec::new(),
+                    };
+                    for can in cans {
+                        unsafe { (can.dfs_fn)(can.ptr, &mut builder) };
+                    }
+                    let Builder {
+                        worker:
+                            Some(Worker {
+                                elsewhere, forced, ..
+                            }),
+                        ambiguous,
+                        ..
+                    } = builder
+                    else {
+                        unreachable!("the worker is never taken from its builder")
+                    };
+                    (graph, elsewhere, forced, ambiguous)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|payload| resume_unwind(payload))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // every allocation was claimed by one thread, so the parts do not overlap
+    let mut forced = Vec::new();
+    let mut ambiguous = Vec::new();
+    let mut found_elsewhere = Vec::with_capacity(parts.len());
+    for (graph, elsewhere, part_forced, part_ambiguous) in parts {
+        ref_graph.extend(graph);
+        found_elsewhere.push(elsewhere);
+        forced.extend(part_forced);
+        ambiguous.extend(part_ambiguous);
+    }
+    for (id, n_found) in found_elsewhere.into_iter().flatten() {
+        if let Reachability::Unknown { n_unaccounted, .. } = &mut ref_graph
+            .get_mut(&id)
+            .expect("every claimed allocation has a node")
+            .reachability
+        {
+            *n_unaccounted = n_unaccounted.saturating_sub(n_found);
+        }
+    }
+    (forced, ambiguous)
+}
@@ -22,6 +22,14 @@
 //! just work.
 //! Those with more particular needs (such as benchmarking) should turn toward
 //! [`set_collect_condition`] in order to tune exactly when the garbage collector does cleanups.
+//! Programs which cannot afford to have a collection run on an arbitrary thread can move all
+//! automatic collections onto a dedicated thread with [`spawn_collector`].
+//!
+//! Each allocation is biased toward the thread which made it, which clones and drops its `Gc`s
+//! without contending with other threads.
+//! When other threads drop more of its references than they made, only a collection can tell
+//! whether the last one is gone, so such an allocation is freed by the next collection rather than
+//! as soon as its last `Gc` is dropped.
 //!
 //! # Examples
 //!
@@ -37,25 +45,45 @@
 //! // contents of the Gc are automatically freed
 //! ```
 
+mod background;
 mod collect;
+mod tag;
 #[cfg(test)]
 mod tests;
 
+#[cfg(feature = "allocator-api")]
+use std::alloc::Allocator;
 use std::{
     alloc::{dealloc, Layout},
     borrow::Borrow,
-    cell::UnsafeCell,
+    cell::{Cell, UnsafeCell},
     fmt::Debug,
+    mem::ManuallyDrop,
     ops::Deref,
-    ptr::{addr_of, addr_of_mut, drop_in_place, NonNull},
-    sync::atomic::{fence, AtomicUsize, Ordering},
+    ptr::{addr_of, addr_of_mut, drop_in_place, read, NonNull},
+    sync::{
+        atomic::{fence, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
-use crate::{contains_gcs, ptr::Nullable, Collectable, Visitor};
+#[cfg(feature = "allocator-api")]
+use crate::allocator::AllocHandle;
+use crate::{
+    contains_gcs,
+    ptr::Nullable,
+    ratio,
+    reach::{GcId, PathFinder},
+    Collectable, Finalize, Visitor,
+};
 
+#[cfg(feature = "epoch")]
+use self::collect::drain_retired;
 use self::collect::{
-    collect_all_await, currently_cleaning, mark_clean, mark_dirty, n_gcs_dropped, n_gcs_existing,
-    notify_created_gc, notify_dropped_gc,
+    collect_all, collect_all_await, currently_cleaning, drop_ratio, heap_bytes, mark_clean,
+    mark_dirty, n_allocations, n_gcs_dropped, n_gcs_existing, notify_allocated, notify_cloned_gc,
+    notify_created_gc, notify_deallocated, notify_destroyed_gc, notify_dropped_gc, try_collect_all,
 };
 
 /// A thread-safe garbage-collected pointer.
@@ -93,9 +121,17 @@ use self::collect::{
 /// result in the program panicking to keep the program from accessing memory after freeing it.
 /// If you're accessing a `Gc` during a `Drop` implementation, make sure to use the fallible
 /// operations [`Gc::try_deref`] and [`Gc::try_clone`].
+///
+/// Once a collection has found an object to be unreachable, nothing can keep it alive: cloning a
+/// `Gc` to it panics, and [`Gc::try_clone`] returns `None`, so a `Drop` implementation cannot
+/// resurrect the object.
+/// A [finalizer](crate::Finalize) runs before the object is destroyed, and may resurrect it
+/// instead.
 pub struct Gc<T: Collectable + Send + Sync + ?Sized + 'static> {
     /// The pointer to the allocation.
+    /// With the `tagged-ptr` feature, its low bits hold the tag of this pointer.
     ptr: UnsafeCell<Nullable<GcBox<T>>>,
+    #[cfg(not(feature = "tagged-ptr"))]
     /// The tag information of this pointer, used for mutation detection when marking.
     tag: AtomicUsize,
 }
@@ -104,16 +140,120 @@ pub struct Gc<T: Collectable + Send + Sync + ?Sized + 'static> {
 /// All new allocations are minted with the current tag.
 static CURRENT_TAG: AtomicUsize = AtomicUsize::new(0);
 
+/// The serial number which will be given to the next allocation made while collections are
+/// deterministic.
+static NEXT_SERIAL: AtomicU64 = AtomicU64::new(0);
+
+/// The token which will be given to the next thread to make or touch a `Gc`.
+/// Tokens are never reused, so an allocation can never mistake a new thread for its owner.
+static NEXT_THREAD_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// The token of this thread, or 0 if it has not been given one yet.
+    static THREAD_TOKEN: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Get the token which identifies this thread as the owner of the allocations it makes, or 0 if
+/// the thread has no token, which matches no allocation.
+///
+/// A thread has no token while it is exiting, or once every token which fits in a [`GcBox`] has
+/// been given out; it then counts all of its references in the shared counts.
+fn thread_token() -> u32 {
+    THREAD_TOKEN
+        .try_with(|token| {
+            if token.get() == 0 {
+                let next = NEXT_THREAD_TOKEN.fetch_add(1, Ordering::Relaxed);
+                token.set(u32::try_from(next).unwrap_or(0));
+            }
+            token.get()
+        })
+        .unwrap_or(0)
+}
+
+/// The number of low bits of [`GcBox::shared`] which hold flags rather than the shared count.
+///
+/// From the lowest bit up, the word holds:
+///
+/// - [`MERGED`], set once the owning thread has given up its biased count;
+/// - [`FINALIZER`], set while the value has a finalizer which has not been run;
+/// - [`CONDEMNED`], set by a collection on an allocation it found unreachable, just before it
+///   destroys the allocation;
+/// - [`DIRTY`], set when the allocation is put in a dumpster;
+/// - [`ROOTED`], set while a [`Root`] holds the allocation;
+/// - [`SERIAL`], set if the allocation was given a serial number;
+/// - the shared count itself, in two's complement, in all the remaining bits.
+///
+/// Adding or subtracting [`ONE_SHARED`] changes the count without touching the flags, even when
+/// the count goes below zero, and the flags are only ever changed with `fetch_or` and `fetch_and`,
+/// so that no change to the word is lost.
+///
+/// The finalizer, the number of roots, and the serial number live in tables in the garbage truck,
+/// which are only looked at when the matching flag is set.
+const FLAG_BITS: u32 = 6;
+
+/// The bit of [`GcBox::shared`] which is set once the owning thread has given up its biased count.
+const MERGED: usize = 1;
+
+/// The bit of [`GcBox::shared`] which is set while the value has a finalizer which has not been
+/// run.
+const FINALIZER: usize = 1 << 1;
+
+/// The bit of [`GcBox::shared`] which is set once a collection has condemned the allocation.
+const CONDEMNED: usize = 1 << 2;
+
+/// The bit of [`GcBox::shared`] which is set when the allocation is put in a dumpster.
+///
+/// It is cleared by the first thread to look for the allocation in its own dumpster after that,
+/// so a thread which finds it clear may skip looking, at worst leaving an entry behind for a
+/// collection to deal with.
+const DIRTY: usize = 1 << 3;
+
+/// The bit of [`GcBox::shared`] which is set while a [`Root`] holds the allocation.
+const ROOTED: usize = 1 << 4;
+
+/// The bit of [`GcBox::shared`] which is set if the allocation was given a serial number.
+const SERIAL: usize = 1 << 5;
+
+/// The amount by which one reference changes [`GcBox::shared`].
+const ONE_SHARED: usize = 1 << FLAG_BITS;
+
+/// The largest number of references which the shared count of an allocation can hold, which is
+/// 2<sup>57</sup> - 1 on 64-bit targets and 2<sup>25</sup> - 1 on 32-bit ones.
+/// Counting any more aborts the process.
+///
+/// The owning thread counts up to [`u32::MAX`] more in the biased count.
+const MAX_SHARED: isize = isize::MAX >> FLAG_BITS;
+
+#[allow(clippy::cast_possible_wrap)]
+/// Get the number of references counted in a value of [`GcBox::shared`], which may be negative.
+fn shared_count(shared: usize) -> isize {
+    shared as isize >> FLAG_BITS
+}
+
 #[repr(C)]
 /// The backing allocation for a [`Gc`].
+///
+/// The header takes up four words on 64-bit targets: the owner and its biased count share one,
+/// and the shared count, the weak count, and the generation take one each.
 struct GcBox<T>
 where
     T: Collectable + Send + Sync + ?Sized,
 {
-    /// The "strong" count, which is the number of extant `Gc`s to this allocation.
-    /// If the strong count is zero, a value contained in the allocation may be dropped, but the
-    /// allocation itself must still be valid.
-    strong: AtomicUsize,
+    /// The token of the thread which made this allocation, and which counts the references it
+    /// makes and drops in `biased` without any atomic read-modify-write.
+    owner: u32,
+    /// The number of references counted by the owning thread.
+    /// Only the owning thread changes this count, and once it reaches zero the count is merged
+    /// into `shared` and never used again.
+    biased: AtomicU32,
+    /// The number of references counted by every other thread, which is negative if they have
+    /// dropped references that the owning thread made, packed with the flags of the allocation as
+    /// laid out in [`FLAG_BITS`].
+    ///
+    /// The sum of both counts is the number of extant `Gc`s to this allocation.
+    /// If it is zero, a value contained in the allocation may be dropped, but the allocation
+    /// itself must still be valid.
+    shared: AtomicUsize,
     /// The "weak" count, which is the number of references to this allocation stored in to-collect
     /// buffers by the collection algorithm.
     /// If the weak count is zero, the allocation may be destroyed.
@@ -122,10 +262,194 @@ where
     /// The generation number is assigned to the global generation every time a strong reference is
     /// created or destroyed or a `Gc` pointing to this allocation is dereferenced.
     generation: AtomicUsize,
+    #[cfg(feature = "allocator-api")]
+    /// The allocator which made this allocation, or `None` if it came from the global allocator.
+    alloc: Option<AllocHandle>,
     /// The actual data stored in the allocation.
     value: T,
 }
 
+/// A function which runs the finalizer of the value in the allocation behind a pointer.
+type Finalizer = unsafe fn(NonNull<()>);
+
+/// Run the finalizer of the value in an allocation.
+///
+/// # Safety
+///
+/// `ptr` must point to a live `GcBox<T>`.
+unsafe fn finalize_value<T>(ptr: NonNull<()>)
+where
+    T: Collectable + Finalize + Send + Sync,
+{
+    ptr.cast::<GcBox<T>>().as_ref().value.finalize();
+}
+
+impl<T> GcBox<T>
+where
+    T: Collectable + Send + Sync,
+{
+    /// Construct the contents of a new allocation, with `value` as its value.
+    ///
+    /// Its finalizer and serial number, if any, are only registered once it has been moved to
+    /// where it will stay.
+    fn new(value: T) -> GcBox<T> {
+        // a thread which is exiting has no token, so it gives up the biased count from the start
+        let owner = thread_token();
+        let (biased, shared) = if owner == 0 {
+            (0, ONE_SHARED | MERGED)
+        } else {
+            (1, 0)
+        };
+        GcBox {
+            owner,
+            biased: AtomicU32::new(biased),
+            shared: AtomicUsize::new(shared),
+            weak: AtomicUsize::new(0),
+            generation: AtomicUsize::new(CURRENT_TAG.load(Ordering::Acquire)),
+            #[cfg(feature = "allocator-api")]
+            alloc: None,
+            value,
+        }
+    }
+}
+
+impl<T> GcBox<T>
+where
+    T: Collectable + Send + Sync + ?Sized,
+{
+    /// Run the finalizer of this allocation, unless it has none or it has already been run.
+    ///
+    /// Returns whether the finalizer was run.
+    fn finalize(&self) -> bool {
+        if !self.has_flag(FINALIZER) || self.clear_flag(FINALIZER) & FINALIZER == 0 {
+            return false;
+        }
+        let Some(finalizer) = collect::take_finalizer(self) else {
+            return false;
+        };
+        unsafe { finalizer(NonNull::from(self).cast()) };
+        true
+    }
+
+    /// Determine whether `flag` is set in the shared word of this allocation.
+    fn has_flag(&self, flag: usize) -> bool {
+        self.shared.load(Ordering::Acquire) & flag != 0
+    }
+
+    /// Set `flag` in the shared word of this allocation, returning the old value of the word.
+    fn set_flag(&self, flag: usize) -> usize {
+        self.shared.fetch_or(flag, Ordering::AcqRel)
+    }
+
+    /// Clear `flag` in the shared word of this allocation, returning the old value of the word.
+    fn clear_flag(&self, flag: usize) -> usize {
+        self.shared.fetch_and(!flag, Ordering::AcqRel)
+    }
+
+    /// Determine whether this allocation is held by a [`Root`], and so is known to be reachable.
+    fn is_rooted(&self) -> bool {
+        self.has_flag(ROOTED)
+    }
+
+    /// Get the biased count of this allocation if the calling thread owns it and has not merged
+    /// the count yet, or `None` if references must be counted in the shared count instead.
+    fn biased_here(&self) -> Option<u32> {
+        // a thread which has no token owns nothing, so there is no need to give it one here
+        if self.owner != THREAD_TOKEN.try_with(Cell::get).unwrap_or(0) {
+            return None;
+        }
+        Some(self.biased.load(Ordering::Relaxed)).filter(|&n| n > 0)
+    }
+
+    /// Get the total number of `Gc`s to this allocation, counted by every thread.
+    ///
+    /// The two counts are read separately, so if they change while they are read, the total may be
+    /// off; every change to the counts also bumps the generation of the allocation, which a
+    /// collection checks after it reads them.
+    fn ref_count(&self) -> usize {
+        loop {
+            let shared = self.shared.load(Ordering::Acquire);
+            let biased = self.biased.load(Ordering::Acquire);
+            if self.shared.load(Ordering::Acquire) == shared {
+                let total = shared_count(shared).saturating_add_unsigned(biased as usize);
+                return usize::try_from(total).unwrap_or(0);
+            }
+        }
+    }
+
+    /// Count a new reference to this allocation made by the calling thread.
+    ///
+    /// Once the biased count is full, the owning thread counts any more references in the shared
+    /// count, as other threads do.
+    fn acquire(&self) {
+        if let Some(n) = self.biased_here().filter(|&n| n < u32::MAX) {
+            self.biased.store(n + 1, Ordering::Release);
+        } else {
+            let old = self.shared.fetch_add(ONE_SHARED, Ordering::Acquire);
+            if shared_count(old) >= MAX_SHARED {
+                // the count has wrapped around, so the allocation could be freed while in use
+                std::process::abort();
+            }
+        }
+    }
+
+    /// Count the loss of a reference to this allocation from another thread, or from the biased
+    /// count once it has been merged.
+    ///
+    /// Returns whether that was the last reference, or `None` if it may have been the last one but
+    /// the biased count has yet to be merged, so that only a collection can tell.
+    fn release_shared(&self) -> Option<bool> {
+        let old = self.shared.fetch_sub(ONE_SHARED, Ordering::AcqRel);
+        if old & MERGED == 0 {
+            // the owning thread may drop its own references without looking at the shared count
+            (shared_count(old) > 0).then_some(false)
+        } else {
+            Some(shared_count(old) == 1)
+        }
+    }
+
+    /// Determine whether other threads have dropped more references to this allocation than they
+    /// made, while its owner still counts references of its own.
+    ///
+    /// The owner may then drop the last reference without noticing, so until the biased count is
+    /// merged, the allocation must stay dirty for collections to check.
+    fn is_overdrawn(&self) -> bool {
+        let shared = self.shared.load(Ordering::Acquire);
+        shared & MERGED == 0 && shared_count(shared) < 0
+    }
+
+    /// Mark this allocation as condemned by the running collection, which is about to destroy it.
+    ///
+    /// No `Gc` to the allocation remains, so only the collecting thread looks at the flag.
+    fn condemn(&self) {
+        self.set_flag(CONDEMNED);
+    }
+
+    /// Determine whether the running collection has condemned this allocation.
+    fn is_condemned(&self) -> bool {
+        self.has_flag(CONDEMNED)
+    }
+}
+
+/// Free the memory of an allocation with layout `layout`, whose value has been dropped, through
+/// whichever allocator made it.
+///
+/// # Safety
+///
+/// `ptr` must point to an allocation whose value has been dropped, and which may not be used again.
+unsafe fn free_box<T>(ptr: *mut GcBox<T>, layout: Layout)
+where
+    T: Collectable + Send + Sync + ?Sized,
+{
+    collect::forget_side_entries(NonNull::new_unchecked(ptr));
+    #[cfg(feature = "allocator-api")]
+    if let Some(alloc) = read(addr_of!((*ptr).alloc)) {
+        alloc.free(NonNull::new_unchecked(ptr).cast(), layout);
+        return;
+    }
+    dealloc(ptr.cast(), layout);
+}
+
 unsafe impl<T> Send for Gc<T> where T: Collectable + Send + Sync + ?Sized {}
 unsafe impl<T> Sync for Gc<T> where T: Collectable + Send + Sync + ?Sized {}
 
@@ -145,16 +469,76 @@ unsafe impl<T> Sync for Gc<T> where T: Collectable + Send + Sync + ?Sized {}
 /// collect(); // the vector originally in `gc` _might_ be dropped now, but could be dropped later
 /// ```
 pub fn collect() {
+    collect_all();
+    #[cfg(feature = "epoch")]
+    drain_retired();
+}
+
+/// Run a collection, then block until every collection in flight has finished.
+///
+/// Unlike [`collect`], this also waits for collections started concurrently by other threads, so
+/// that the heap is quiescent when it returns.
+/// This is useful at test boundaries or before inspecting the heap.
+/// Waiting threads are put to sleep rather than spinning.
+///
+/// Allocations which were dropped on other threads may still be held in those threads' local
+/// buffers, and so are only guaranteed to be collected once those threads have called
+/// [`collect`] or [`try_collect`] themselves.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{collect_await, Gc};
+///
+/// let gc = Gc::new(vec![1, 2, 3]);
+/// std::thread::spawn(move || drop(gc)).join().unwrap();
+///
+/// collect_await(); // the vector has been dropped by now
+/// ```
+pub fn collect_await() {
     collect_all_await();
+    #[cfg(feature = "epoch")]
+    drain_retired();
+}
+
+#[must_use]
+/// Run a collection, unless another thread is already collecting.
+///
+/// This never blocks on other collections.
+/// Returns `true` if a collection was run, and `false` if one was already in progress.
+/// In the latter case, allocations dropped on this thread are handed over to the collector anyway,
+/// and will be collected by a later collection.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{collect_await, try_collect, Gc};
+///
+/// let gc = Gc::new(vec![1, 2, 3]);
+/// drop(gc);
+///
+/// if !try_collect() {
+///     // someone else is collecting; wait for them instead
+///     collect_await();
+/// }
+/// ```
+pub fn try_collect() -> bool {
+    let collected = try_collect_all();
+    #[cfg(feature = "epoch")]
+    if collected {
+        drain_retired();
+    }
+    collected
 }
 
 #[derive(Debug)]
-/// Information passed to a [`CollectCondition`] used to determine whether the garbage collector
+/// Information passed to a collect condition used to determine whether the garbage collector
 /// should start collecting.
 ///
 /// A `CollectInfo` is exclusively created by being passed as an argument to the collection
 /// condition.
-/// To set a custom collection condition, refer to [`set_collect_condition`].
+/// To set a custom collection condition, refer to [`set_collect_condition`], or to
+/// [`set_collect_condition_local`] to set one for a single thread.
 ///
 /// # Examples
 ///
@@ -172,21 +556,226 @@ pub struct CollectInfo {
     _private: (),
 }
 
-/// A function which determines whether the garbage collector should start collecting.
-/// This type primarily exists so that it can be used with [`set_collect_condition`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// A summary of the work done by a single collection, as passed to the hook set by
+/// [`CollectHooks::on_end`].
+pub struct CollectResult {
+    /// The number of allocations which the collector traversed while looking for garbage.
+    pub allocations_examined: usize,
+    /// The number of allocations which were freed.
+    pub allocations_freed: usize,
+    /// The total size, in bytes, of the allocations which were freed.
+    ///
+    /// This counts the whole allocation backing each [`Gc`], including its reference counts.
+    pub bytes_freed: usize,
+    /// How long the collection took.
+    pub duration: Duration,
+}
+
+/// A hook called when a collection starts.
+type StartHook = Box<dyn Fn(&CollectInfo) + Send + Sync>;
+/// A hook called when a collection finishes.
+type EndHook = Box<dyn Fn(&CollectResult) + Send + Sync>;
+
+#[derive(Default)]
+/// Callbacks which are called around every collection, as set by [`set_collect_hooks`].
+pub struct CollectHooks {
+    /// Called before each collection.
+    on_start: Option<StartHook>,
+    /// Called after each collection.
+    on_end: Option<EndHook>,
+}
+
+impl CollectHooks {
+    #[must_use]
+    /// Construct a set of hooks which do nothing.
+    pub fn new() -> CollectHooks {
+        CollectHooks::default()
+    }
+
+    #[must_use]
+    /// Set the hook which is called before each collection.
+    pub fn on_start(mut self, f: impl Fn(&CollectInfo) + Send + Sync + 'static) -> CollectHooks {
+        self.on_start = Some(Box::new(f));
+        self
+    }
+
+    #[must_use]
+    /// Set the hook which is called after each collection, with a summary of its work.
+    pub fn on_end(mut self, f: impl Fn(&CollectResult) + Send + Sync + 'static) -> CollectHooks {
+        self.on_end = Some(Box::new(f));
+        self
+    }
+}
+
+impl Debug for CollectHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CollectHooks")
+            .field("on_start", &self.on_start.is_some())
+            .field("on_end", &self.on_end.is_some())
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+/// A condition which determines whether the garbage collector should start collecting, built out
+/// of common policies.
 ///
-/// # Examples
+/// Conditions are combined with [`CollectCondition::and`] and [`CollectCondition::or`], and
+/// installed with [`set_collect_condition_with`] or
+/// [`set_collect_condition_local_with`].
+/// Building a condition allocates, but checking one never does, so even an elaborate condition is
+/// cheap to check every time a [`Gc`] is dropped.
 ///
-/// ```rust
-/// use dumpster::sync::{set_collect_condition, CollectInfo};
+/// # Examples
 ///
-/// fn always_collect(_: &CollectInfo) -> bool {
-///     true
-/// }
+/// ```
+/// use dumpster::sync::{set_collect_condition_with, CollectCondition};
 ///
-/// set_collect_condition(always_collect);
+/// // collect when the heap is over 64 MiB, or after every 10,000 drops, whichever comes first
+/// set_collect_condition_with(
+///     CollectCondition::heap_bytes_above(64 << 20).or(CollectCondition::every_n_drops(10_000)),
+/// );
 /// ```
-pub type CollectCondition = fn(&CollectInfo) -> bool;
+pub struct CollectCondition(Arc<Condition>);
+
+/// The policies out of which a [`CollectCondition`] is built.
+enum Condition {
+    /// Never collect.
+    Never,
+    /// Collect as [`default_collect_condition`] does.
+    Default,
+    /// Collect once at least this many `Gc`s have been dropped since the last collection.
+    EveryNDrops(usize),
+    /// Collect once more than this many times as many `Gc`s have been dropped since the last
+    /// collection as now exist.
+    DropRatio(f64),
+    /// Collect whenever the heap is larger than this many bytes.
+    HeapBytesAbove(usize),
+    /// Collect when both conditions say so.
+    And(CollectCondition, CollectCondition),
+    /// Collect when either condition says so.
+    Or(CollectCondition, CollectCondition),
+    /// Collect when a function says so.
+    Custom(Box<dyn Fn(&CollectInfo) -> bool + Send + Sync>),
+}
+
+impl CollectCondition {
+    #[must_use]
+    /// Construct a condition which never starts a collection.
+    ///
+    /// Garbage is then only freed by explicit calls to [`collect`](super::collect).
+    pub fn never() -> CollectCondition {
+        CollectCondition(Arc::new(Condition::Never))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection once at least `n` [`Gc`]s have been dropped
+    /// since the last collection, on any thread.
+    pub fn every_n_drops(n: usize) -> CollectCondition {
+        CollectCondition(Arc::new(Condition::EveryNDrops(n)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection once more than `ratio` times as many
+    /// [`Gc`]s have been dropped since the last collection as now exist, counting all threads.
+    ///
+    /// Unlike the default condition, which adapts its ratio to how much each collection frees,
+    /// this condition always uses `ratio`.
+    /// A smaller ratio collects more often, which suits heaps where most dropped `Gc`s leave
+    /// behind unreachable cycles, and a larger one collects less often, which suits heaps where
+    /// most allocations are freed by their reference counts alone.
+    /// The ratio the default condition starts with is `1.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::sync::{set_collect_condition_with, CollectCondition};
+    ///
+    /// // collect once four times as many `Gc`s have been dropped as exist
+    /// set_collect_condition_with(CollectCondition::drop_ratio(4.0));
+    /// ```
+    pub fn drop_ratio(ratio: f64) -> CollectCondition {
+        CollectCondition(Arc::new(Condition::DropRatio(ratio)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection whenever the garbage-collected heap is larger
+    /// than `bytes`, as [`collect_when_heap_exceeds`] does.
+    pub fn heap_bytes_above(bytes: usize) -> CollectCondition {
+        CollectCondition(Arc::new(Condition::HeapBytesAbove(bytes)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection whenever `f` returns `true`.
+    pub fn custom(f: impl Fn(&CollectInfo) -> bool + Send + Sync + 'static) -> CollectCondition {
+        CollectCondition(Arc::new(Condition::Custom(Box::new(f))))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection only when both `self` and `other` would.
+    ///
+    /// `other` is not checked if `self` returns `false`.
+    pub fn and(self, other: CollectCondition) -> CollectCondition {
+        CollectCondition(Arc::new(Condition::And(self, other)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection when either `self` or `other` would.
+    ///
+    /// `other` is not checked if `self` returns `true`.
+    pub fn or(self, other: CollectCondition) -> CollectCondition {
+        CollectCondition(Arc::new(Condition::Or(self, other)))
+    }
+
+    #[must_use]
+    /// Determine whether this condition would start a collection, given the state of the heap in
+    /// `info`.
+    pub fn should_collect(&self, info: &CollectInfo) -> bool {
+        match &*self.0 {
+            Condition::Never => false,
+            Condition::Default => default_collect_condition(info),
+            Condition::EveryNDrops(n) => info.n_gcs_dropped_since_last_collect() >= *n,
+            Condition::DropRatio(ratio) => ratio::exceeds(
+                info.n_gcs_dropped_since_last_collect(),
+                info.n_gcs_existing(),
+                *ratio,
+            ),
+            Condition::HeapBytesAbove(bytes) => info.heap_bytes() > *bytes,
+            Condition::And(a, b) => a.should_collect(info) && b.should_collect(info),
+            Condition::Or(a, b) => a.should_collect(info) || b.should_collect(info),
+            Condition::Custom(f) => f(info),
+        }
+    }
+}
+
+impl Default for CollectCondition {
+    /// Construct a condition which behaves like [`default_collect_condition`].
+    fn default() -> CollectCondition {
+        CollectCondition(Arc::new(Condition::Default))
+    }
+}
+
+impl Debug for CollectCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &*self.0 {
+            Condition::Never => f.write_str("Never"),
+            Condition::Default => f.write_str("Default"),
+            Condition::EveryNDrops(n) => f.debug_tuple("EveryNDrops").field(n).finish(),
+            Condition::DropRatio(ratio) => f.debug_tuple("DropRatio").field(ratio).finish(),
+            Condition::HeapBytesAbove(bytes) => {
+                f.debug_tuple("HeapBytesAbove").field(bytes).finish()
+            }
+            Condition::And(a, b) => f.debug_tuple("And").field(a).field(b).finish(),
+            Condition::Or(a, b) => f.debug_tuple("Or").field(a).field(b).finish(),
+            Condition::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// A collect condition which may capture state, as returned by [`set_collect_condition_boxed`].
+pub type BoxedCollectCondition = Box<dyn Fn(&CollectInfo) -> bool + Send + Sync>;
 
 #[must_use]
 /// The default collection condition used by the garbage collector.
@@ -194,6 +783,13 @@ pub type CollectCondition = fn(&CollectInfo) -> bool;
 /// There are no guarantees about what this function returns, other than that it will return `true`
 /// with sufficient frequency to ensure that all `Gc` operations are amortized _O(1)_ in runtime.
 ///
+/// Currently, it collects once more than [`CollectInfo::drop_ratio`] times as many `Gc`s have
+/// been dropped since the last collection as now exist.
+/// That ratio adapts to the heap: it grows after collections which free little, so that heaps
+/// whose garbage is mostly freed by reference counting are collected rarely, and shrinks after
+/// collections which free a lot, so that heaps full of unreachable cycles are collected often.
+/// To use a fixed ratio instead, see [`CollectCondition::drop_ratio`].
+///
 /// This function isn't really meant to be called by users, but rather it's supposed to be handed
 /// off to [`set_collect_condition`] to return to the default operating mode of the library.
 ///
@@ -216,10 +812,126 @@ pub type CollectCondition = fn(&CollectInfo) -> bool;
 /// set_collect_condition(default_collect_condition);
 /// ```
 pub fn default_collect_condition(info: &CollectInfo) -> bool {
-    info.n_gcs_dropped_since_last_collect() > info.n_gcs_existing()
+    ratio::exceeds(
+        info.n_gcs_dropped_since_last_collect(),
+        info.n_gcs_existing(),
+        info.drop_ratio(),
+    )
 }
 
-pub use collect::set_collect_condition;
+/// Construct a collection condition which starts a collection whenever the garbage-collected heap
+/// across all threads is larger than `bytes`.
+///
+/// The size of the heap is measured as in [`CollectInfo::heap_bytes`].
+/// This is a better measure of memory pressure than the default condition when the sizes of
+/// allocations vary a lot.
+///
+/// If the live data alone is larger than `bytes`, every drop of a [`Gc`] will start a collection,
+/// so `bytes` should be chosen with some headroom above the expected amount of live data.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{collect_when_heap_exceeds, set_collect_condition_boxed};
+///
+/// // collect whenever the heap grows past 64 MiB
+/// set_collect_condition_boxed(collect_when_heap_exceeds(64 << 20));
+/// ```
+pub fn collect_when_heap_exceeds(
+    bytes: usize,
+) -> impl Fn(&CollectInfo) -> bool + Copy + Send + Sync + 'static {
+    move |info| info.heap_bytes() > bytes
+}
+
+#[must_use]
+/// Determine whether `target` can be reached from `root` by following `Gc`s.
+///
+/// This is useful for finding out why an allocation is still alive: if some allocation which is
+/// itself reachable can reach `target`, then `target` will not be collected.
+/// Every allocation is reachable from itself.
+/// See [`path_from`] for the allocations on the way from `root` to `target`.
+///
+/// The search traces each allocation at most once, so it terminates even if the heap has cycles.
+/// It only reads the heap, and never changes the state of the garbage collector.
+/// Values which cannot be traced right now (such as those inside a locked
+/// [`Mutex`](std::sync::Mutex)) are treated as though they
+/// own no `Gc`s.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{sync::{is_reachable_from, Gc}, Collectable};
+///
+/// #[derive(Collectable)]
+/// struct Node(Vec<Gc<Node>>);
+///
+/// let leaf = Gc::new(Node(Vec::new()));
+/// let parent = Gc::new(Node(vec![leaf.clone()]));
+/// assert!(is_reachable_from(&parent, &leaf));
+/// assert!(!is_reachable_from(&leaf, &parent));
+/// ```
+pub fn is_reachable_from<T, U>(root: &Gc<T>, target: &Gc<U>) -> bool
+where
+    T: Collectable + Send + Sync + ?Sized,
+    U: Collectable + Send + Sync + ?Sized,
+{
+    path_from(root, target).is_some()
+}
+
+#[must_use]
+/// Find a way to reach `target` from `root` by following `Gc`s.
+///
+/// The path lists the allocations passed through on the way, starting with `root` and ending with
+/// `target`, or is `None` if `target` cannot be reached from `root`.
+/// No guarantee is made about which path is found if there are several.
+/// The search works as described in [`is_reachable_from`].
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{sync::{path_from, Gc}, Collectable};
+///
+/// #[derive(Collectable)]
+/// struct Node(Vec<Gc<Node>>);
+///
+/// let leaf = Gc::new(Node(Vec::new()));
+/// let middle = Gc::new(Node(vec![leaf.clone()]));
+/// let root = Gc::new(Node(vec![middle.clone()]));
+///
+/// let path = path_from(&root, &leaf).unwrap();
+/// let addrs = path.iter().map(|id| id.addr()).collect::<Vec<_>>();
+/// assert_eq!(
+///     addrs,
+///     [&root, &middle, &leaf].map(|gc| Gc::as_ptr(gc).cast::<()>() as usize),
+/// );
+/// ```
+pub fn path_from<T, U>(root: &Gc<T>, target: &Gc<U>) -> Option<Vec<GcId>>
+where
+    T: Collectable + Send + Sync + ?Sized,
+    U: Collectable + Send + Sync + ?Sized,
+{
+    let target = GcId::of(Gc::peek(target)?);
+    PathFinder::search(Gc::peek(root)?, target)
+}
+
+pub use background::{shutdown_collector, spawn_collector, CollectorConfig};
+#[cfg(feature = "heap-dump")]
+pub use collect::dump_heap_dot;
+#[cfg(feature = "leak-detection")]
+pub use collect::leak_report;
+#[cfg(any(test, feature = "testing"))]
+pub(crate) use collect::replace_collect_condition_local;
+#[cfg(feature = "parallel-collect")]
+pub use collect::set_collect_workers;
+pub use collect::{
+    clear_collect_condition_local, collect_dry_run, collection_mode, dirty_capacity,
+    pause_collection, reserve, reset_stats, safe_point, service_collections, set_collect_condition,
+    set_collect_condition_boxed, set_collect_condition_local, set_collect_condition_local_with,
+    set_collect_condition_with, set_collect_hooks, set_collection_mode,
+    set_default_collection_mode, set_deterministic, set_initial_capacity, stats, PauseGuard,
+};
+#[cfg(feature = "heap-inspection")]
+pub use collect::{iter_allocations, type_census};
 
 impl<T> Gc<T>
 where
@@ -235,19 +947,114 @@ where
     /// let _ = Gc::new(0);
     /// ```
     pub fn new(value: T) -> Gc<T>
+    where
+        T: Sized,
+    {
+        Gc::allocate(value, None)
+    }
+
+    /// Construct a new garbage-collected value which runs its finalizer just before it is
+    /// destroyed.
+    ///
+    /// See [`Finalize`] for when the finalizer runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::{sync::Gc, Collectable, Finalize};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// static FINALIZED: AtomicBool = AtomicBool::new(false);
+    ///
+    /// #[derive(Collectable)]
+    /// struct File;
+    ///
+    /// impl Finalize for File {
+    ///     fn finalize(&self) {
+    ///         FINALIZED.store(true, Ordering::Relaxed);
+    ///     }
+    /// }
+    ///
+    /// drop(Gc::new_finalized(File));
+    /// assert!(FINALIZED.load(Ordering::Relaxed));
+    /// ```
+    pub fn new_finalized(value: T) -> Gc<T>
+    where
+        T: Finalize + Sized,
+    {
+        Gc::allocate(value, Some(finalize_value::<T>))
+    }
+
+    /// Construct a new garbage-collected value with `finalizer` as the function which runs its
+    /// finalizer.
+    fn allocate(value: T, finalizer: Option<Finalizer>) -> Gc<T>
     where
         T: Sized,
     {
         notify_created_gc();
-        Gc {
-            ptr: UnsafeCell::new(Nullable::new(NonNull::from(Box::leak(Box::new(GcBox {
-                strong: AtomicUsize::new(1),
-                weak: AtomicUsize::new(0),
-                generation: AtomicUsize::new(CURRENT_TAG.load(Ordering::Acquire)),
-                value,
-            }))))),
-            tag: AtomicUsize::new(0),
+        notify_allocated(Layout::new::<GcBox<T>>());
+        Gc::register(
+            NonNull::from(Box::leak(Box::new(GcBox::new(value)))),
+            finalizer,
+        )
+    }
+
+    #[cfg(feature = "allocator-api")]
+    /// Construct a new garbage-collected value in memory from `alloc`.
+    ///
+    /// The allocation is freed through `alloc` once it is destroyed, which may happen during a
+    /// later collection on any thread, so `alloc` is kept alive until then.
+    ///
+    /// This is only available with the `allocator-api` feature, which requires nightly Rust.
+    ///
+    /// # Panics
+    ///
+    /// This function calls [`handle_alloc_error`](std::alloc::handle_alloc_error) if `alloc` fails
+    /// to allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    ///
+    /// use dumpster::sync::Gc;
+    /// use std::alloc::System;
+    ///
+    /// let gc = Gc::new_in(0, System);
+    /// assert_eq!(*gc, 0);
+    /// ```
+    pub fn new_in<A: Allocator + Send + Sync + 'static>(value: T, alloc: A) -> Gc<T>
+    where
+        T: Sized,
+    {
+        notify_created_gc();
+        notify_allocated(Layout::new::<GcBox<T>>());
+        let (block, handle) = AllocHandle::allocate(alloc, Layout::new::<GcBox<T>>());
+        let ptr = block.cast::<GcBox<T>>();
+        unsafe {
+            ptr.as_ptr().write(GcBox {
+                alloc: Some(handle),
+                ..GcBox::new(value)
+            });
         }
+        Gc::register(ptr, None)
+    }
+
+    /// Make the first `Gc` to a newly constructed allocation, whose value has `finalizer` as the
+    /// function which runs its finalizer.
+    fn register(ptr: NonNull<GcBox<T>>, finalizer: Option<Finalizer>) -> Gc<T>
+    where
+        T: Sized,
+    {
+        collect::register_side_entries(ptr, finalizer);
+        #[cfg(any(
+            feature = "leak-detection",
+            feature = "heap-dump",
+            feature = "heap-inspection"
+        ))]
+        collect::register_live(ptr);
+        collect::note_allocated(ptr);
+        Gc::from_ptr(Nullable::new(ptr), 0)
     }
 
     /// Attempt to dereference this `Gc`.
@@ -295,17 +1102,24 @@ where
     /// ```
     pub fn try_deref(gc: &Gc<T>) -> Option<&T> {
         #[allow(clippy::unnecessary_lazy_evaluations)]
-        unsafe {
-            (!(*gc.ptr.get()).is_null()).then(|| &**gc)
-        }
+        (!gc.box_ptr().is_null()).then(|| &**gc)
+    }
+
+    /// Get a reference to the value behind this `Gc`, or `None` if it is dead, without marking the
+    /// allocation as accessed.
+    ///
+    /// This must only be used to inspect the value, since a collection running at the same time
+    /// will not notice anything which is done through the reference.
+    pub(crate) fn peek(gc: &Gc<T>) -> Option<&T> {
+        unsafe { gc.box_ptr().as_option().map(|ptr| &ptr.as_ref().value) }
     }
 
     /// Attempt to clone this `Gc`.
     ///
     /// This function will return `None` if `self` is a "dead" `Gc`, which points to an
-    /// already-deallocated object.
+    /// already-deallocated object, or to an object which a collection is destroying.
     /// This can only occur if a `Gc` is accessed during the `Drop` implementation of a
-    /// [`Collectable`] object.
+    /// [`Collectable`] object, which can never keep a collected object alive.
     ///
     /// For a version which panics instead of returning `None`, consider using [`Clone`].
     ///
@@ -343,7 +1157,10 @@ where
     /// # dumpster::sync::collect();
     /// ```
     pub fn try_clone(gc: &Gc<T>) -> Option<Gc<T>> {
-        unsafe { (!(*gc.ptr.get()).is_null()).then(|| gc.clone()) }
+        gc.box_ptr()
+            .as_option()
+            .is_some_and(|ptr| !unsafe { ptr.as_ref() }.is_condemned())
+            .then(|| gc.clone())
     }
 
     /// Provides a raw pointer to the data.
@@ -365,7 +1182,7 @@ where
     /// ```
     pub fn as_ptr(gc: &Gc<T>) -> *const T {
         unsafe {
-            let ptr = NonNull::as_ptr((*gc.ptr.get()).unwrap());
+            let ptr = NonNull::as_ptr(gc.box_ptr().unwrap());
             addr_of_mut!((*ptr).value)
         }
     }
@@ -387,7 +1204,30 @@ where
     /// assert!(!Gc::ptr_eq(&gc1, &gc3));
     /// ```
     pub fn ptr_eq(this: &Gc<T>, other: &Gc<T>) -> bool {
-        unsafe { *this.ptr.get() }.as_option() == unsafe { *other.ptr.get() }.as_option()
+        this.box_ptr().as_option() == other.box_ptr().as_option()
+    }
+
+    /// Register this `Gc` as a root of the heap.
+    ///
+    /// See [`Root`] for details.
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if `this` points to an already-collected object, which can only
+    /// happen during the `Drop` implementation of a [`Collectable`] value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::sync::Gc;
+    ///
+    /// let root = Gc::into_root(Gc::new(5));
+    /// assert_eq!(*root, 5);
+    /// ```
+    pub fn into_root(this: Gc<T>) -> Root<T> {
+        let box_ref = unsafe { this.box_ptr().unwrap().as_ref() };
+        collect::add_root(box_ref);
+        Root { gc: this }
     }
 }
 
@@ -398,11 +1238,16 @@ where
     /// Clone a garbage-collected reference.
     /// This does not clone the underlying data.
     ///
+    /// Cloning only increments the reference count of the allocation, so it never blocks, never
+    /// allocates, and never waits on a collection running on another thread.
+    ///
     /// # Panics
     ///
-    /// This function will panic if the `Gc` being cloned points to a deallocated object.
+    /// This function will panic if the `Gc` being cloned points to a deallocated object, or to an
+    /// object which a collection is destroying.
     /// This is only possible if said `Gc` is accessed during the `Drop` implementation of a
-    /// `Collectable` value.
+    /// `Collectable` value, so such an implementation cannot resurrect the object by storing a
+    /// clone of the `Gc` somewhere reachable.
     ///
     /// For a fallible version, refer to [`Gc::try_clone`].
     ///
@@ -441,20 +1286,25 @@ where
     /// ```
     fn clone(&self) -> Gc<T> {
         let box_ref = unsafe {
-            (*self.ptr.get()).expect("attempt to clone Gc to already-deallocated object. \
+            self.box_ptr().expect("attempt to clone Gc to already-deallocated object. \
             This means a Gc was accessed during a Drop implementation, likely implying a bug in your code.").as_ref()
         };
-        // increment strong count before generation to ensure cleanup never underestimates ref count
-        box_ref.strong.fetch_add(1, Ordering::Acquire);
+        // a collection kills every `Gc` it finds to an allocation it condemns, but one which a
+        // faulty `Collectable` implementation hid from it would otherwise resurrect the allocation
+        // just before it is freed
+        assert!(
+            !box_ref.is_condemned(),
+            "attempt to clone Gc to an object which is being collected. \
+            A Drop implementation cannot keep a collected object alive."
+        );
+        // increment the count before generation to ensure cleanup never underestimates ref count
+        box_ref.acquire();
         box_ref
             .generation
             .store(CURRENT_TAG.load(Ordering::Acquire), Ordering::Release);
-        notify_created_gc();
+        notify_cloned_gc();
         // mark_clean(box_ref); // causes performance drops
-        Gc {
-            ptr: UnsafeCell::new(unsafe { *self.ptr.get() }),
-            tag: AtomicUsize::new(CURRENT_TAG.load(Ordering::Acquire)),
-        }
+        Gc::from_ptr(self.box_ptr(), CURRENT_TAG.load(Ordering::Acquire))
     }
 }
 
@@ -463,38 +1313,75 @@ where
     T: Collectable + Send + Sync + ?Sized,
 {
     fn drop(&mut self) {
-        if currently_cleaning() {
+        if currently_cleaning() && !self.box_ptr().as_option().is_some_and(collect::is_fresh) {
+            // the collection has already released or cut off this reference
+            notify_destroyed_gc();
             return;
         }
-        let Some(mut ptr) = unsafe { *self.ptr.get() }.as_option() else {
+        let Some(mut ptr) = self.box_ptr().as_option() else {
             return;
         };
         let box_ref = unsafe { ptr.as_ref() };
+        if let Some(n @ 2..) = box_ref.biased_here() {
+            // this thread still counts another reference, so this cannot be the last one, and
+            // nothing else may touch the allocation once it is given up
+            if contains_gcs(&box_ref.value).unwrap_or(true) {
+                // this thread's dumpster keeps the allocation from being freed until then
+                mark_dirty(ptr);
+            }
+            box_ref
+                .generation
+                .store(CURRENT_TAG.load(Ordering::Relaxed), Ordering::Release);
+            box_ref.biased.store(n - 1, Ordering::Release);
+            notify_dropped_gc();
+            return;
+        }
         box_ref.weak.fetch_add(1, Ordering::AcqRel); // ensures that this allocation wasn't freed
                                                      // while we weren't looking
         box_ref
             .generation
             .store(CURRENT_TAG.load(Ordering::Relaxed), Ordering::Release);
-        match box_ref.strong.fetch_sub(1, Ordering::AcqRel) {
-            0 => unreachable!("strong cannot reach zero while a Gc to it exists"),
-            1 => {
+        let last = if box_ref.biased_here().is_some() {
+            // this was the last reference counted by the owner, so its count is merged, after
+            // which the shared count is the whole count
+            box_ref.biased.store(0, Ordering::Release);
+            let old = box_ref.shared.fetch_or(MERGED, Ordering::AcqRel);
+            Some(shared_count(old) == 0)
+        } else {
+            box_ref.release_shared()
+        };
+        match last {
+            Some(true) => {
                 mark_clean(box_ref);
                 if box_ref.weak.fetch_sub(1, Ordering::Release) == 1 {
                     // destroyed the last weak reference! we can safely deallocate this
                     let layout = Layout::for_value(box_ref);
                     fence(Ordering::Acquire);
+                    box_ref.finalize();
                     unsafe {
+                        #[cfg(any(
+                            feature = "leak-detection",
+                            feature = "heap-dump",
+                            feature = "heap-inspection"
+                        ))]
+                        collect::forget_live(ptr.as_ptr());
+                        collect::note_destroyed(ptr);
                         drop_in_place(ptr.as_mut());
-                        dealloc(ptr.as_ptr().cast(), layout);
+                        free_box(ptr.as_ptr(), layout);
                     }
+                    notify_deallocated(layout);
                 }
             }
-            _ => {
+            Some(false) => {
                 if contains_gcs(&box_ref.value).unwrap_or(true) {
                     mark_dirty(ptr);
                 }
                 box_ref.weak.fetch_sub(1, Ordering::Release);
             }
+            None => {
+                mark_dirty(ptr);
+                box_ref.weak.fetch_sub(1, Ordering::Release);
+            }
         }
         notify_dropped_gc();
     }
@@ -505,6 +1392,11 @@ impl CollectInfo {
     /// Get the number of times that a [`Gc`] has been dropped since the last time a collection
     /// operation was performed.
     ///
+    /// Each thread counts the `Gc`s it drops on its own, and only adds them to this count every
+    /// few dozen drops or when it delivers its garbage, so the drops of other threads may be
+    /// counted late.
+    /// Collect conditions should therefore not depend on this count being exact.
+    ///
     /// # Examples
     ///
     /// ```
@@ -524,6 +1416,9 @@ impl CollectInfo {
     #[must_use]
     /// Get the total number of [`Gc`]s which currently exist.
     ///
+    /// As with [`CollectInfo::n_gcs_dropped_since_last_collect`], other threads add the `Gc`s they
+    /// make, clone, and drop to this count in batches, so it is only approximate.
+    ///
     /// # Examples
     ///
     /// ```
@@ -539,6 +1434,74 @@ impl CollectInfo {
     pub fn n_gcs_existing(&self) -> usize {
         n_gcs_existing()
     }
+
+    #[must_use]
+    /// Get the number of garbage-collected allocations which currently exist.
+    ///
+    /// Many [`Gc`]s may point to the same allocation, so this is at most
+    /// [`CollectInfo::n_gcs_existing`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::sync::{set_collect_condition, CollectInfo};
+    ///
+    /// // Collection condition for whether many allocations currently exist.
+    /// fn do_many_allocations_exist(info: &CollectInfo) -> bool {
+    ///     info.n_allocations() > 1000
+    /// }
+    ///
+    /// set_collect_condition(do_many_allocations_exist);
+    /// ```
+    pub fn n_allocations(&self) -> usize {
+        n_allocations()
+    }
+
+    #[must_use]
+    /// Get the total size, in bytes, of all garbage-collected allocations which currently exist.
+    ///
+    /// This counts the whole allocation backing each [`Gc`], including its reference counts, but
+    /// not any memory which the stored values own indirectly (such as the buffer of a `Vec`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::sync::{set_collect_condition, CollectInfo};
+    ///
+    /// // Collection condition for whether the heap has grown past a megabyte.
+    /// fn is_heap_large(info: &CollectInfo) -> bool {
+    ///     info.heap_bytes() > 1 << 20
+    /// }
+    ///
+    /// set_collect_condition(is_heap_large);
+    /// ```
+    pub fn heap_bytes(&self) -> usize {
+        heap_bytes()
+    }
+
+    #[must_use]
+    /// Get the ratio of dropped to existing [`Gc`]s at which [`default_collect_condition`] would
+    /// currently start a collection.
+    ///
+    /// The ratio starts at `1.0`, grows after each collection which frees little of what it
+    /// examines, and shrinks after each which frees most of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::sync::{set_collect_condition, CollectInfo};
+    ///
+    /// // collect half as often as the default condition would
+    /// fn lazy_collect_condition(info: &CollectInfo) -> bool {
+    ///     info.n_gcs_dropped_since_last_collect() as f64
+    ///         > info.n_gcs_existing() as f64 * info.drop_ratio() * 2.0
+    /// }
+    ///
+    /// set_collect_condition(lazy_collect_condition);
+    /// ```
+    pub fn drop_ratio(&self) -> f64 {
+        drop_ratio()
+    }
 }
 
 unsafe impl<T: Collectable + Send + Sync + ?Sized> Collectable for Gc<T> {
@@ -596,13 +1559,13 @@ impl<T: Collectable + Send + Sync + ?Sized> Deref for Gc<T> {
     /// ```
     fn deref(&self) -> &Self::Target {
         let box_ref = unsafe {
-            (*self.ptr.get()).expect(
+            self.box_ptr().expect(
             "Attempting to dereference Gc to already-deallocated object.\
             This is caused by accessing a Gc during a Drop implementation, likely implying a bug in your code."
         ).as_ref()
         };
         let current_tag = CURRENT_TAG.load(Ordering::Acquire);
-        self.tag.store(current_tag, Ordering::Release);
+        self.tag_with(current_tag);
         box_ref.generation.store(current_tag, Ordering::Release);
         &box_ref.value
     }
@@ -671,11 +1634,112 @@ where
 
 impl<T: Collectable + Send + Sync + ?Sized> Debug for Gc<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Gc({:?}, {})",
-            self.ptr,
-            self.tag.load(Ordering::Acquire)
-        )
+        write!(f, "Gc({:?}, {})", self.box_ptr(), self.tag())
+    }
+}
+
+/// A [`Gc`] which is registered as a root of the heap.
+///
+/// The garbage collector knows that a rooted allocation is reachable, so it never looks inside it
+/// while searching for garbage.
+/// This makes collections cheaper when a large structure lives for a long time, such as the global
+/// environment of an interpreter: instead of counting the references across the whole structure
+/// every time, a collection stops as soon as it reaches the root.
+/// Nothing reachable from a `Root` is ever freed.
+/// Once every `Root` to an allocation is dropped, it is collected as usual.
+///
+/// A `Root` is created with [`Gc::into_root`].
+/// It is not [`Collectable`], since a `Root` stored inside the garbage-collected heap would keep
+/// itself alive forever.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::sync::{collect, Gc, Root};
+/// use std::sync::Mutex;
+///
+/// let globals: Root<Mutex<Vec<Gc<i32>>>> = Gc::into_root(Gc::new(Mutex::new(Vec::new())));
+/// globals.lock().unwrap().push(Gc::new(1));
+///
+/// // this collection does not look inside `globals`
+/// collect();
+/// assert_eq!(*globals.lock().unwrap()[0], 1);
+///
+/// // `globals` can be collected like any other allocation from now on
+/// let globals: Gc<_> = Root::into_gc(globals);
+/// ```
+pub struct Root<T: Collectable + Send + Sync + ?Sized + 'static> {
+    /// The pointer to the rooted allocation.
+    gc: Gc<T>,
+}
+
+impl<T: Collectable + Send + Sync + ?Sized> Root<T> {
+    /// Get the [`Gc`] held by this root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::sync::{Gc, Root};
+    ///
+    /// let gc = Gc::new(3);
+    /// let root = Gc::into_root(gc.clone());
+    /// assert!(Gc::ptr_eq(Root::as_gc(&root), &gc));
+    /// ```
+    pub fn as_gc(this: &Root<T>) -> &Gc<T> {
+        &this.gc
+    }
+
+    /// Unregister this root, turning it back into an ordinary [`Gc`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::sync::{Gc, Root};
+    ///
+    /// let root = Gc::into_root(Gc::new(3));
+    /// let gc: Gc<i32> = Root::into_gc(root);
+    /// ```
+    pub fn into_gc(this: Root<T>) -> Gc<T> {
+        let this = ManuallyDrop::new(this);
+        Root::unroot(&this);
+        unsafe { read(addr_of!(this.gc)) }
+    }
+
+    /// Remove this root's registration from its allocation.
+    fn unroot(this: &Root<T>) {
+        // a dead `Gc` can only be found while collecting, when the allocation no longer matters
+        if let Some(ptr) = this.gc.box_ptr().as_option() {
+            collect::remove_root(unsafe { ptr.as_ref() });
+        }
+    }
+}
+
+impl<T: Collectable + Send + Sync + ?Sized> Deref for Root<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.gc
+    }
+}
+
+impl<T: Collectable + Send + Sync + ?Sized> Clone for Root<T> {
+    fn clone(&self) -> Self {
+        Gc::into_root(self.gc.clone())
+    }
+}
+
+impl<T: Collectable + Send + Sync + ?Sized> Drop for Root<T> {
+    /// Unregister this root.
+    ///
+    /// If no other `Root` to the same allocation remains, the allocation may be collected once it
+    /// becomes unreachable.
+    fn drop(&mut self) {
+        Root::unroot(self);
+    }
+}
+
+impl<T: Collectable + Send + Sync + ?Sized> Debug for Root<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Root").field(&self.gc).finish()
     }
 }
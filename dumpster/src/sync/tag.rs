@@ -0,0 +1,178 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Reading and writing the pointer and tag of a [`Gc`].
+//!
+//! Each `Gc` is tagged with the sweep which last accessed it, so that a collection can tell when
+//! a `Gc` was moved while it searched the heap.
+//! With the `tagged-ptr` feature, the tag is packed into the low bits of the pointer, which are
+//! always zero since every allocation is aligned, so that a `Gc` takes no more space than the
+//! pointer itself.
+//! Only the low bits of the sweep's tag fit there, so a `Gc` which was last tagged a multiple of
+//! that many sweeps ago looks as if it was tagged by the current one.
+//! A collection which finds such a `Gc` in an allocation it cannot otherwise show to be reachable
+//! searches that allocation again with a fresh tag.
+
+#[cfg(feature = "tagged-ptr")]
+use std::{
+    cell::UnsafeCell,
+    mem::{align_of, size_of, MaybeUninit},
+    ptr::{copy_nonoverlapping, null_mut},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+#[cfg(not(feature = "tagged-ptr"))]
+use std::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{ptr::Nullable, Collectable};
+
+use super::{Gc, GcBox};
+
+/// Whether a `Gc` which looks as if it was tagged by the current sweep may instead have been
+/// tagged by an earlier one.
+pub(super) const TAGS_MAY_ALIAS: bool = cfg!(feature = "tagged-ptr");
+
+#[cfg(feature = "tagged-ptr")]
+/// The bits of a pointer to a [`GcBox`] which hold the tag of its `Gc`.
+const TAG_MASK: usize = align_of::<GcBox<()>>() - 1;
+
+#[cfg(feature = "tagged-ptr")]
+impl<T: Collectable + Send + Sync + ?Sized> Gc<T> {
+    /// Make a `Gc` from a pointer to its allocation, tagged with `tag`.
+    pub(super) fn from_ptr(ptr: Nullable<GcBox<T>>, tag: usize) -> Gc<T> {
+        let gc = Gc {
+            ptr: UnsafeCell::new(ptr),
+        };
+        let word = gc.word();
+        word.store(
+            word.load(Ordering::Relaxed)
+                .map_addr(|a| a | tag & TAG_MASK),
+            Ordering::Relaxed,
+        );
+        gc
+    }
+
+    /// Get the word of the pointer which holds its address and tag.
+    fn word(&self) -> &AtomicPtr<u8> {
+        // SAFETY: the address comes first in every pointer, and is only ever accessed atomically
+        unsafe { &*self.ptr.get().cast::<AtomicPtr<u8>>() }
+    }
+
+    /// Get the pointer to this `Gc`'s allocation, which is null if this `Gc` is dead.
+    pub(super) fn box_ptr(&self) -> Nullable<GcBox<T>> {
+        let addr = self
+            .word()
+            .load(Ordering::Relaxed)
+            .map_addr(|a| a & !TAG_MASK);
+        let mut ptr = MaybeUninit::<Nullable<GcBox<T>>>::uninit();
+        unsafe {
+            // the metadata of a pointer to a `!Sized` type follows its address, and never changes
+            ptr.as_mut_ptr().cast::<*mut u8>().write(addr);
+            copy_nonoverlapping(
+                self.ptr.get().cast::<u8>().add(size_of::<*mut u8>()),
+                ptr.as_mut_ptr().cast::<u8>().add(size_of::<*mut u8>()),
+                size_of::<Nullable<GcBox<T>>>() - size_of::<*mut u8>(),
+            );
+            ptr.assume_init()
+        }
+    }
+
+    /// Get the tag of this `Gc`.
+    pub(super) fn tag(&self) -> usize {
+        self.word().load(Ordering::Acquire).addr() & TAG_MASK
+    }
+
+    /// Tag this `Gc` with `tag`, the tag of the current sweep.
+    pub(super) fn tag_with(&self, tag: usize) {
+        let _ = self
+            .word()
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |p| {
+                (p.addr() & TAG_MASK != tag & TAG_MASK)
+                    .then(|| p.map_addr(|a| a & !TAG_MASK | tag & TAG_MASK))
+            });
+    }
+
+    /// Tag this `Gc` with `tag`, the tag of the current sweep.
+    ///
+    /// Returns `true` if this `Gc` had already been tagged during the current sweep.
+    /// It may also return `true` for a `Gc` which was last tagged by an earlier sweep.
+    pub(super) fn retag(&self, tag: usize) -> bool {
+        let Ok(old) = self
+            .word()
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |p| {
+                Some(p.map_addr(|a| a & !TAG_MASK | tag & TAG_MASK))
+            })
+        else {
+            unreachable!("the tag is always updated")
+        };
+        old.addr() & TAG_MASK == tag & TAG_MASK
+    }
+
+    /// Make this `Gc` dead, so that it no longer points to its allocation.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may be able to access this `Gc`.
+    pub(super) unsafe fn kill(&self) {
+        self.word().store(null_mut(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(not(feature = "tagged-ptr"))]
+impl<T: Collectable + Send + Sync + ?Sized> Gc<T> {
+    /// Make a `Gc` from a pointer to its allocation, tagged with `tag`.
+    pub(super) fn from_ptr(ptr: Nullable<GcBox<T>>, tag: usize) -> Gc<T> {
+        Gc {
+            ptr: UnsafeCell::new(ptr),
+            tag: AtomicUsize::new(tag),
+        }
+    }
+
+    /// Get the pointer to this `Gc`'s allocation, which is null if this `Gc` is dead.
+    pub(super) fn box_ptr(&self) -> Nullable<GcBox<T>> {
+        unsafe { *self.ptr.get() }
+    }
+
+    /// Get the tag of this `Gc`.
+    pub(super) fn tag(&self) -> usize {
+        self.tag.load(Ordering::Acquire)
+    }
+
+    /// Tag this `Gc` with `tag`, the tag of the current sweep.
+    pub(super) fn tag_with(&self, tag: usize) {
+        self.tag.store(tag, Ordering::Release);
+    }
+
+    /// Tag this `Gc` with `tag`, the tag of the current sweep.
+    ///
+    /// Returns `true` if this `Gc` had already been tagged during the current sweep.
+    pub(super) fn retag(&self, tag: usize) -> bool {
+        self.tag.swap(tag, Ordering::Relaxed) >= tag
+    }
+
+    /// Make this `Gc` dead, so that it no longer points to its allocation.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may be able to access this `Gc`.
+    pub(super) unsafe fn kill(&self) {
+        self.ptr.get().write((*self.ptr.get()).as_null());
+    }
+}
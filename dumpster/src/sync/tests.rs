@@ -17,22 +17,39 @@
 */
 
 use std::{
-    collections::{hash_map::Entry, HashMap},
-    mem::{swap, take, transmute, MaybeUninit},
+    cell::Cell,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    mem::{align_of, size_of, swap, take, transmute, MaybeUninit},
     ptr::NonNull,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
 };
 
-use crate::Visitor;
+use crate::{
+    graph,
+    reach::GcId,
+    testing::{sync::with_aggressive_collection, DropCounter, DropToken},
+    CollectionMode, Finalize, Visitor,
+};
 
 use super::*;
 
+/// Held by tests which change how automatic collections happen for the whole process, and by tests
+/// which would be disturbed by such a change.
+static GLOBAL_POLICY_LOCK: Mutex<()> = Mutex::new(());
+
+/// Destroy the garbage which automatic collections have retired but not yet destroyed, if
+/// destruction is deferred with the `epoch` feature.
+fn settle() {
+    #[cfg(feature = "epoch")]
+    drain_retired();
+}
+
 struct DropCount<'a>(&'a AtomicUsize);
 
-impl<'a> Drop for DropCount<'a> {
+impl Drop for DropCount<'_> {
     fn drop(&mut self) {
         self.0.fetch_add(1, Ordering::Release);
     }
@@ -110,30 +127,17 @@ fn self_referential() {
 
 #[test]
 fn two_cycle() {
-    static DROP_0: AtomicUsize = AtomicUsize::new(0);
-    static DROP_1: AtomicUsize = AtomicUsize::new(0);
-
-    let gc0 = Gc::new(MultiRef {
-        refs: Mutex::new(Vec::new()),
-        count: DropCount(&DROP_0),
-    });
-    let gc1 = Gc::new(MultiRef {
-        refs: Mutex::new(vec![Gc::clone(&gc0)]),
-        count: DropCount(&DROP_1),
-    });
-    gc0.refs.lock().unwrap().push(Gc::clone(&gc1));
+    let counter = DropCounter::new();
+    graph!(sync, counter; gc0, gc1; gc0 -> gc1, gc1 -> gc0);
 
     collect();
-    assert_eq!(DROP_0.load(Ordering::Acquire), 0);
-    assert_eq!(DROP_0.load(Ordering::Acquire), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc0);
     collect();
-    assert_eq!(DROP_0.load(Ordering::Acquire), 0);
-    assert_eq!(DROP_0.load(Ordering::Acquire), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc1);
     collect();
-    assert_eq!(DROP_0.load(Ordering::Acquire), 1);
-    assert_eq!(DROP_0.load(Ordering::Acquire), 1);
+    assert_eq!(counter.count(), 2);
 }
 
 #[test]
@@ -167,57 +171,41 @@ fn self_ref_two_cycle() {
 
 #[test]
 fn parallel_loop() {
-    static COUNT_1: AtomicUsize = AtomicUsize::new(0);
-    static COUNT_2: AtomicUsize = AtomicUsize::new(0);
-    static COUNT_3: AtomicUsize = AtomicUsize::new(0);
-    static COUNT_4: AtomicUsize = AtomicUsize::new(0);
-
-    let gc1 = Gc::new(MultiRef {
-        count: DropCount(&COUNT_1),
-        refs: Mutex::new(Vec::new()),
-    });
-    let gc2 = Gc::new(MultiRef {
-        count: DropCount(&COUNT_2),
-        refs: Mutex::new(vec![Gc::clone(&gc1)]),
-    });
-    let gc3 = Gc::new(MultiRef {
-        count: DropCount(&COUNT_3),
-        refs: Mutex::new(vec![Gc::clone(&gc1)]),
-    });
-    let gc4 = Gc::new(MultiRef {
-        count: DropCount(&COUNT_4),
-        refs: Mutex::new(vec![Gc::clone(&gc2), Gc::clone(&gc3)]),
-    });
-    gc1.refs.lock().unwrap().push(Gc::clone(&gc4));
+    let counter = DropCounter::new();
+    graph!(
+        sync, counter;
+        gc1, gc2, gc3, gc4;
+        gc2 -> gc1, gc3 -> gc1, gc4 -> gc2, gc4 -> gc3, gc1 -> gc4
+    );
 
-    assert_eq!(COUNT_1.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_2.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_3.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_4.load(Ordering::Acquire), 0);
     drop(gc1);
     collect();
-    assert_eq!(COUNT_1.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_2.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_3.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_4.load(Ordering::Acquire), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc2);
     collect();
-    assert_eq!(COUNT_1.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_2.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_3.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_4.load(Ordering::Acquire), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc3);
     collect();
-    assert_eq!(COUNT_1.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_2.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_3.load(Ordering::Acquire), 0);
-    assert_eq!(COUNT_4.load(Ordering::Acquire), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc4);
     collect();
-    assert_eq!(COUNT_1.load(Ordering::Acquire), 1);
-    assert_eq!(COUNT_2.load(Ordering::Acquire), 1);
-    assert_eq!(COUNT_3.load(Ordering::Acquire), 1);
-    assert_eq!(COUNT_4.load(Ordering::Acquire), 1);
+    assert_eq!(counter.count(), 4);
+}
+
+#[test]
+/// Test that a graph of nodes survives a collection on every drop until it becomes garbage.
+fn aggressive_collection() {
+    let counter = DropCounter::new();
+    with_aggressive_collection(|| {
+        graph!(sync, counter; a, b, c; a -> b, b -> c, c -> a, b -> b);
+        drop(a);
+        drop(c);
+        assert_eq!(counter.count(), 0);
+        assert_eq!(b.edges().len(), 2);
+        drop(b);
+        collect_await();
+        assert_eq!(counter.count(), 3);
+    });
 }
 
 #[test]
@@ -258,6 +246,8 @@ fn eventually_collect() {
     static COUNT_1: AtomicUsize = AtomicUsize::new(0);
     static COUNT_2: AtomicUsize = AtomicUsize::new(0);
 
+    // this relies on the default collect condition running on this thread
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
     let gc1 = Gc::new(MultiRef {
         refs: Mutex::new(Vec::new()),
         count: DropCount(&COUNT_1),
@@ -290,6 +280,12 @@ fn coerce_array() {
     let gc1: Gc<[u8; 3]> = Gc::new([0, 0, 0]);
     let gc2: Gc<[u8]> = gc1;
     assert_eq!(gc2.len(), 3);
+    #[cfg(feature = "tagged-ptr")]
+    assert_eq!(
+        std::mem::size_of::<Gc<[u8]>>(),
+        2 * std::mem::size_of::<usize>()
+    );
+    #[cfg(not(feature = "tagged-ptr"))]
     assert_eq!(
         std::mem::size_of::<Gc<[u8]>>(),
         3 * std::mem::size_of::<usize>()
@@ -648,3 +644,2048 @@ fn escape_dead_pointer() {
     collect();
     println!("{}", ESCAPED.lock().unwrap().as_ref().unwrap().x);
 }
+
+#[test]
+/// Test that `collect_await` leaves no garbage behind, even while other threads are collecting.
+fn collect_await_concurrent() {
+    const N_THREADS: usize = 8;
+    const N_CYCLES: usize = 50;
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    std::thread::scope(|s| {
+        for _ in 0..N_THREADS {
+            s.spawn(|| {
+                for i in 0..N_CYCLES {
+                    let gc0 = Gc::new(MultiRef {
+                        refs: Mutex::new(Vec::new()),
+                        count: DropCount(&DROPS),
+                    });
+                    let gc1 = Gc::new(MultiRef {
+                        refs: Mutex::new(vec![Gc::clone(&gc0)]),
+                        count: DropCount(&DROPS),
+                    });
+                    gc0.refs.lock().unwrap().push(Gc::clone(&gc1));
+                    drop(gc0);
+                    drop(gc1);
+                    if i % 10 == 9 {
+                        // it doesn't matter whether this succeeds; the garbage is delivered either
+                        // way.
+                        // this must also happen at the end of the thread, since a thread's local
+                        // buffer may not be delivered by the time the thread is joined.
+                        let _ = try_collect();
+                    }
+                }
+            });
+        }
+    });
+
+    collect_await();
+    assert_eq!(DROPS.load(Ordering::Acquire), 2 * N_THREADS * N_CYCLES);
+}
+
+#[test]
+/// Test that `try_collect` runs a collection when nobody else is collecting.
+fn try_collect_uncontended() {
+    static DROP_0: AtomicUsize = AtomicUsize::new(0);
+
+    let gc0 = Gc::new(MultiRef {
+        refs: Mutex::new(Vec::new()),
+        count: DropCount(&DROP_0),
+    });
+    gc0.refs.lock().unwrap().push(Gc::clone(&gc0));
+    drop(gc0);
+
+    // other tests may be collecting at the same time, so keep trying until we get our turn
+    while !try_collect() {
+        std::thread::yield_now();
+    }
+    assert_eq!(DROP_0.load(Ordering::Acquire), 1);
+}
+
+#[test]
+/// Test that the heap statistics given to a collect condition account for every allocation.
+fn collect_info_statistics() {
+    static MAX_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    static MAX_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+    // other tests share the global heap, so only lower bounds can be checked here
+    fn record(info: &CollectInfo) -> bool {
+        MAX_ALLOCATIONS.fetch_max(info.n_allocations(), Ordering::Relaxed);
+        MAX_BYTES.fetch_max(info.heap_bytes(), Ordering::Relaxed);
+        default_collect_condition(info)
+    }
+
+    set_collect_condition(record);
+    let gcs = (0..1000).map(|_| Gc::new([0u8; 64])).collect::<Vec<_>>();
+    drop(Gc::clone(&gcs[0]));
+    set_collect_condition(default_collect_condition);
+
+    assert!(MAX_ALLOCATIONS.load(Ordering::Relaxed) >= 1000);
+    assert!(MAX_BYTES.load(Ordering::Relaxed) >= 1000 * Layout::new::<GcBox<[u8; 64]>>().size());
+    drop(gcs);
+}
+
+#[test]
+/// Test that threads dropping garbage never run a collection themselves while a background
+/// collector is running.
+fn background_collector() {
+    const N_THREADS: usize = 4;
+    const N_CYCLES: usize = 500;
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    static DROPS_ON_REQUEST: AtomicUsize = AtomicUsize::new(0);
+
+    struct Cycle {
+        next: Mutex<Option<Gc<Cycle>>>,
+    }
+
+    impl Drop for Cycle {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+            if std::thread::current().name() == Some("request") {
+                DROPS_ON_REQUEST.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    unsafe impl Collectable for Cycle {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    // a small limit forces the request threads to hand their garbage over and wait
+    spawn_collector(CollectorConfig::new().max_pending(64)).unwrap();
+    assert_eq!(
+        spawn_collector(CollectorConfig::new()).unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+
+    std::thread::scope(|s| {
+        for _ in 0..N_THREADS {
+            std::thread::Builder::new()
+                .name(String::from("request"))
+                .spawn_scoped(s, || {
+                    for _ in 0..N_CYCLES {
+                        let gc0 = Gc::new(Cycle {
+                            next: Mutex::new(None),
+                        });
+                        let gc1 = Gc::new(Cycle {
+                            next: Mutex::new(Some(Gc::clone(&gc0))),
+                        });
+                        *gc0.next.lock().unwrap() = Some(gc1);
+                    }
+                })
+                .unwrap();
+        }
+    });
+
+    shutdown_collector();
+    // shutting down twice is harmless
+    shutdown_collector();
+
+    // other tests may collect some of our garbage, and each thread's last few cycles may not have
+    // been handed over, so we can only check that the collector did some of the work
+    assert!(DROPS.load(Ordering::Relaxed) > 0);
+    assert_eq!(DROPS_ON_REQUEST.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+/// Test that a thread-local collect condition only affects the thread which set it.
+fn local_collect_condition() {
+    const N_CYCLES: usize = 100;
+    static DROPS_ON_QUIET: AtomicUsize = AtomicUsize::new(0);
+    static DROPS_ON_LOUD: AtomicUsize = AtomicUsize::new(0);
+
+    struct Cycle {
+        next: Mutex<Option<Gc<Cycle>>>,
+    }
+
+    impl Drop for Cycle {
+        fn drop(&mut self) {
+            match std::thread::current().name() {
+                Some("quiet") => DROPS_ON_QUIET.fetch_add(1, Ordering::Relaxed),
+                Some("loud") => DROPS_ON_LOUD.fetch_add(1, Ordering::Relaxed),
+                _ => 0,
+            };
+        }
+    }
+
+    unsafe impl Collectable for Cycle {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    fn make_garbage() {
+        for _ in 0..N_CYCLES {
+            let gc0 = Gc::new(Cycle {
+                next: Mutex::new(None),
+            });
+            let gc1 = Gc::new(Cycle {
+                next: Mutex::new(Some(Gc::clone(&gc0))),
+            });
+            *gc0.next.lock().unwrap() = Some(gc1);
+        }
+    }
+
+    fn never_collect(_: &CollectInfo) -> bool {
+        false
+    }
+
+    fn always_collect(_: &CollectInfo) -> bool {
+        true
+    }
+
+    // a background collector would take the loud thread's collections away from it
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    std::thread::scope(|s| {
+        std::thread::Builder::new()
+            .name(String::from("quiet"))
+            .spawn_scoped(s, || {
+                set_collect_condition_local(never_collect);
+                make_garbage();
+                clear_collect_condition_local();
+            })
+            .unwrap();
+        std::thread::Builder::new()
+            .name(String::from("loud"))
+            .spawn_scoped(s, || {
+                set_collect_condition_local(always_collect);
+                make_garbage();
+                collect();
+            })
+            .unwrap();
+    });
+
+    assert_eq!(DROPS_ON_QUIET.load(Ordering::Relaxed), 0);
+    assert!(DROPS_ON_LOUD.load(Ordering::Relaxed) > 0);
+    collect_await();
+}
+
+#[test]
+/// Test that a cyclic graph dropped while collection is paused is only freed once every guard has
+/// been released.
+fn pause_guard() {
+    const N: usize = 10;
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    fn always_collect(_: &CollectInfo) -> bool {
+        true
+    }
+
+    // a background collector would run the collection in our place
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    set_collect_condition_local(always_collect);
+    let gcs = (0..N)
+        .map(|_| {
+            Gc::new(MultiRef {
+                refs: Mutex::new(Vec::new()),
+                count: DropCount(&DROP_COUNT),
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, gc) in gcs.iter().enumerate() {
+        gc.refs.lock().unwrap().push(gcs[(i + 1) % N].clone());
+    }
+
+    let outer = pause_collection();
+    let inner = pause_collection();
+    drop(gcs);
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), 0);
+
+    drop(inner);
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), 0);
+
+    drop(outer);
+    settle();
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), N);
+    clear_collect_condition_local();
+}
+#[test]
+/// Test that this thread starts no collection between safe points, however much garbage it drops,
+/// and that the next safe point frees all of it.
+fn safe_points_only() {
+    const N: usize = 1_000;
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    fn always_collect(_: &CollectInfo) -> bool {
+        true
+    }
+
+    // a background collector would run the collection in our place
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    set_collect_condition_local(always_collect);
+    set_collection_mode(CollectionMode::AtSafePointsOnly);
+    assert_eq!(collection_mode(), CollectionMode::AtSafePointsOnly);
+    for _ in 0..N {
+        let a = Gc::new(MultiRef {
+            refs: Mutex::new(Vec::new()),
+            count: DropCount(&DROP_COUNT),
+        });
+        let b = Gc::new(MultiRef {
+            refs: Mutex::new(vec![a.clone()]),
+            count: DropCount(&DROP_COUNT),
+        });
+        a.refs.lock().unwrap().push(b);
+    }
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), 0);
+
+    assert!(safe_point());
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), 2 * N);
+    assert!(!safe_point());
+
+    set_collection_mode(CollectionMode::Automatic);
+    clear_collect_condition_local();
+}
+
+thread_local! {
+    /// The number of collections started on this thread while [`count_starts_here`] hooks are
+    /// set.
+    static N_STARTS_HERE: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Set collection hooks which count the collections started on each thread in
+/// [`N_STARTS_HERE`].
+fn count_starts_here() {
+    set_collect_hooks(CollectHooks::new().on_start(|_| N_STARTS_HERE.with(|n| n.set(n.get() + 1))));
+}
+
+#[test]
+/// Test that dropping `Gc`s in the queued mode never runs a collection, however often the collect
+/// condition fires, and that one serviced collection frees all the garbage.
+fn queued_collections() {
+    use crate::testing::sync::Node;
+
+    const N: usize = 1_000;
+
+    fn always_collect(_: &CollectInfo) -> bool {
+        true
+    }
+
+    // hooks are global, and a background collector would service the requests in our place
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    count_starts_here();
+    set_collect_condition_local(always_collect);
+    set_collection_mode(CollectionMode::Queued);
+    assert_eq!(collection_mode(), CollectionMode::Queued);
+
+    let counter = DropCounter::new();
+    for _ in 0..N {
+        let gc = Gc::new(Node::new(&counter));
+        Node::link(&gc, &gc);
+    }
+    assert_eq!(N_STARTS_HERE.with(Cell::get), 0);
+    assert_eq!(counter.count(), 0);
+
+    if service_collections() {
+        // the requests were coalesced into a single collection
+        assert_eq!(N_STARTS_HERE.with(Cell::get), 1);
+    } else {
+        // another test's collection serviced the requests first
+        collect_await();
+    }
+    assert_eq!(counter.count(), N);
+    assert!(!service_collections());
+
+    set_collection_mode(CollectionMode::Automatic);
+    clear_collect_condition_local();
+    set_collect_hooks(CollectHooks::default());
+}
+
+#[test]
+/// Test that the background collector services the collections queued by a thread, which never
+/// collects by itself.
+fn queued_collections_background() {
+    use crate::testing::sync::Node;
+
+    const N: usize = 1_000;
+
+    fn always_collect(_: &CollectInfo) -> bool {
+        true
+    }
+
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    count_starts_here();
+    spawn_collector(CollectorConfig::new()).unwrap();
+    set_collect_condition_local(always_collect);
+    set_collection_mode(CollectionMode::Queued);
+
+    let counter = DropCounter::new();
+    for _ in 0..N {
+        let gc = Gc::new(Node::new(&counter));
+        Node::link(&gc, &gc);
+    }
+    // garbage dropped while a request is pending is handed over with the next request
+    for _ in 0..10_000 {
+        if counter.count() == N {
+            break;
+        }
+        drop(Gc::new(()));
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(counter.count(), N);
+    assert_eq!(N_STARTS_HERE.with(Cell::get), 0);
+
+    shutdown_collector();
+    set_collection_mode(CollectionMode::Automatic);
+    clear_collect_condition_local();
+    set_collect_hooks(CollectHooks::default());
+}
+
+#[test]
+/// Test that the default collection mode applies to every thread which has not set its own.
+fn default_collection_mode() {
+    // other tests' threads follow the default too
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    set_default_collection_mode(CollectionMode::Queued);
+    assert_eq!(collection_mode(), CollectionMode::Queued);
+    std::thread::spawn(|| {
+        assert_eq!(collection_mode(), CollectionMode::Queued);
+        set_collection_mode(CollectionMode::AtSafePointsOnly);
+        assert_eq!(collection_mode(), CollectionMode::AtSafePointsOnly);
+    })
+    .join()
+    .unwrap();
+    set_default_collection_mode(CollectionMode::Automatic);
+    assert_eq!(collection_mode(), CollectionMode::Automatic);
+}
+
+#[test]
+/// Test that the global statistics account for a collection of a cycle.
+fn gc_stats() {
+    const N: usize = 3;
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    // other tests share the global heap and collect at the same time, so only lower bounds can
+    // be checked here
+    reset_stats();
+    let size = Layout::new::<GcBox<MultiRef>>().size();
+    let gcs = (0..N)
+        .map(|_| {
+            Gc::new(MultiRef {
+                refs: Mutex::new(Vec::new()),
+                count: DropCount(&DROP_COUNT),
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, gc) in gcs.iter().enumerate() {
+        gc.refs.lock().unwrap().push(gcs[(i + 1) % N].clone());
+    }
+    let allocated = stats();
+    assert!(allocated.live_allocations >= N);
+    assert!(allocated.live_bytes >= N * size);
+
+    drop(gcs);
+    collect_await();
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), N);
+    let collected = stats();
+    assert!(collected.collections >= 1);
+    assert!(collected.allocations_freed >= N);
+    assert!(collected.bytes_freed >= N * size);
+}
+
+#[test]
+/// Test that collection hooks are called around collections, can't start a collection themselves,
+/// and can be removed.
+fn collect_hooks() {
+    static STARTS: AtomicUsize = AtomicUsize::new(0);
+    static ENDS: AtomicUsize = AtomicUsize::new(0);
+    static MAX_PAUSE_NANOS: AtomicUsize = AtomicUsize::new(0);
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    // hooks are global, so other tests' collections may call them too
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    set_collect_hooks(
+        CollectHooks::new()
+            .on_start(|_| {
+                STARTS.fetch_add(1, Ordering::Relaxed);
+                // these would deadlock if they were allowed to collect
+                collect();
+                collect_await();
+                assert!(!try_collect());
+            })
+            .on_end(|result| {
+                ENDS.fetch_add(1, Ordering::Relaxed);
+                MAX_PAUSE_NANOS.fetch_max(
+                    usize::try_from(result.duration.as_nanos()).unwrap(),
+                    Ordering::Relaxed,
+                );
+            }),
+    );
+
+    let gc = Gc::new(MultiRef {
+        refs: Mutex::new(Vec::new()),
+        count: DropCount(&DROP_COUNT),
+    });
+    gc.refs.lock().unwrap().push(Gc::clone(&gc));
+    drop(gc);
+    collect_await();
+
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), 1);
+    assert!(STARTS.load(Ordering::Relaxed) >= 1);
+    assert!(ENDS.load(Ordering::Relaxed) >= 1);
+    assert!(MAX_PAUSE_NANOS.load(Ordering::Relaxed) > 0);
+
+    set_collect_hooks(CollectHooks::default());
+    // wait out any collection which started with the old hooks
+    collect();
+    let n_ends = ENDS.load(Ordering::Relaxed);
+    collect();
+    assert_eq!(ENDS.load(Ordering::Relaxed), n_ends);
+}
+
+#[test]
+/// Test that a closure can be installed as the collect condition, and that the previous condition
+/// can be restored.
+fn boxed_collect_condition() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    fn make_cycle() {
+        let gc = Gc::new(MultiRef {
+            refs: Mutex::new(Vec::new()),
+            count: DropCount(&DROP_COUNT),
+        });
+        gc.refs.lock().unwrap().push(Gc::clone(&gc));
+    }
+
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    let enabled = Arc::new(AtomicBool::new(false));
+    let switch = Arc::clone(&enabled);
+    let previous = set_collect_condition_boxed(move |_| switch.load(Ordering::Relaxed));
+
+    // the kill-switch is off, so nothing is collected automatically
+    make_cycle();
+    make_cycle();
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), 0);
+
+    // dropping the next `Gc` collects everything this thread has dropped
+    enabled.store(true, Ordering::Relaxed);
+    make_cycle();
+    settle();
+    assert_eq!(DROP_COUNT.load(Ordering::Acquire), 3);
+
+    let ours = set_collect_condition_boxed(previous);
+    assert!(ours(&CollectInfo { _private: () }));
+    enabled.store(false, Ordering::Relaxed);
+    assert!(!ours(&CollectInfo { _private: () }));
+    drop(ours);
+    assert_eq!(Arc::strong_count(&enabled), 1);
+}
+#[test]
+/// Test that a composed collect condition fires when either of its parts would, under scripted
+/// workloads on this thread.
+fn composed_collect_condition() {
+    thread_local! {
+        static FIRED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Record how many times `condition` would have fired, without ever collecting.
+    fn record(condition: CollectCondition) -> CollectCondition {
+        condition.and(CollectCondition::custom(|_| {
+            FIRED.with(|f| f.set(f.get() + 1));
+            false
+        }))
+    }
+
+    fn drops(n: usize) -> usize {
+        let gc = Gc::new(0u8);
+        FIRED.with(|f| f.set(0));
+        for _ in 0..n {
+            drop(gc.clone());
+        }
+        FIRED.with(Cell::get)
+    }
+
+    // other threads may collect at any time, so the heap and drop counts are only pinned down by
+    // extreme thresholds
+    let never = || CollectCondition::heap_bytes_above(usize::MAX);
+    set_collect_condition_local_with(record(
+        never().or(CollectCondition::every_n_drops(usize::MAX)),
+    ));
+    assert_eq!(drops(100), 0);
+
+    set_collect_condition_local_with(record(
+        CollectCondition::heap_bytes_above(0).or(CollectCondition::every_n_drops(usize::MAX)),
+    ));
+    assert_eq!(drops(100), 100);
+
+    set_collect_condition_local_with(record(never().or(CollectCondition::every_n_drops(0))));
+    assert_eq!(drops(100), 100);
+
+    set_collect_condition_local_with(record(CollectCondition::heap_bytes_above(0).and(never())));
+    assert_eq!(drops(100), 0);
+
+    set_collect_condition_local_with(record(CollectCondition::never()));
+    assert_eq!(drops(100), 0);
+
+    clear_collect_condition_local();
+}
+
+#[test]
+/// Test that a fixed drop ratio is never met when it is infinite, and that the adaptive ratio stays
+/// within its bounds however other threads' collections move it.
+fn drop_ratio_condition() {
+    thread_local! {
+        static CHECKED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // other threads may collect at any time, so only an extreme ratio is pinned down
+    set_collect_condition_local_with(CollectCondition::drop_ratio(f64::INFINITY).or(
+        CollectCondition::custom(|info| {
+            assert!((0.25..=16.0).contains(&info.drop_ratio()));
+            CHECKED.with(|c| c.set(c.get() + 1));
+            false
+        }),
+    ));
+    let gc = Gc::new(0u8);
+    for _ in 0..100 {
+        drop(gc.clone());
+        collect();
+    }
+    assert_eq!(CHECKED.with(Cell::get), 100);
+
+    clear_collect_condition_local();
+}
+
+#[test]
+/// Test that the heap size counts large payloads, and that a byte-threshold condition fires once
+/// the threshold is crossed.
+fn heap_threshold() {
+    const N: usize = 4;
+
+    let size = Layout::new::<GcBox<[u8; 1 << 13]>>().size();
+    let info = CollectInfo { _private: () };
+    assert!(!collect_when_heap_exceeds(usize::MAX)(&info));
+
+    // other tests share the global heap, so only lower bounds can be checked here
+    let gcs = (0..N).map(|_| Gc::new([0u8; 1 << 13])).collect::<Vec<_>>();
+    assert!(info.heap_bytes() >= N * size);
+    assert!(collect_when_heap_exceeds(N * size - 1)(&info));
+    drop(gcs);
+}
+
+#[test]
+#[cfg(feature = "leak-detection")]
+/// Test that allocations which can never be freed show up in the leak report.
+fn leak_detection() {
+    struct Leaked;
+    struct Freed;
+
+    unsafe impl Collectable for Leaked {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    unsafe impl Collectable for Freed {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    std::mem::forget(Gc::new(Leaked));
+    drop(Gc::new(Freed));
+
+    // other tests share the global heap, so only look at our own types
+    let report = leak_report();
+    assert_eq!(
+        report
+            .allocations
+            .iter()
+            .filter(|a| a.type_name.ends_with("Leaked"))
+            .count(),
+        1
+    );
+    assert!(!report
+        .allocations
+        .iter()
+        .any(|a| a.type_name.ends_with("Freed")));
+}
+
+#[test]
+#[cfg(feature = "heap-dump")]
+/// Test that a heap dump draws a cycle of allocations and the edges between them.
+fn heap_dump_dot() {
+    let counter = DropCounter::new();
+    graph!(sync, counter; a, b; a -> b, b -> a, b -> b);
+    let a_addr = format!("{:p}", Gc::as_ptr(&a));
+    let b_addr = format!("{:p}", Gc::as_ptr(&b));
+
+    let mut dot = Vec::new();
+    dump_heap_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+
+    let size = Layout::new::<GcBox<crate::testing::sync::Node>>().size();
+    assert!(dot.contains(&format!(
+        r#""{a_addr}" [label="dumpster::testing::sync::Node\n{size} bytes, 2 refs"]"#
+    )));
+    assert!(dot.contains(&format!(
+        r#""{b_addr}" [label="dumpster::testing::sync::Node\n{size} bytes, 3 refs"]"#
+    )));
+    assert!(dot.contains(&format!(r#""{a_addr}" -> "{b_addr}";"#)));
+    assert!(dot.contains(&format!(r#""{b_addr}" -> "{a_addr}";"#)));
+    assert!(dot.contains(&format!(r#""{b_addr}" -> "{b_addr}";"#)));
+
+    drop((a, b));
+    collect();
+    assert_eq!(counter.count(), 2);
+}
+
+#[test]
+#[cfg(feature = "heap-inspection")]
+/// Test that iterating over the live allocations finds a cycle with the right type names and
+/// reference counts, and that it is gone once collected.
+fn iter_allocations_lists_live() {
+    let counter = DropCounter::new();
+    graph!(sync, counter; a, b; a -> b, b -> a, b -> b);
+    let addrs = [Gc::as_ptr(&a) as usize, Gc::as_ptr(&b) as usize];
+
+    let mut found = iter_allocations()
+        .filter(|info| addrs.contains(&info.addr))
+        .map(|info| (info.addr, info.type_name, info.ref_count))
+        .collect::<Vec<_>>();
+    found.sort_unstable_by_key(|&(addr, ..)| addr != addrs[0]);
+    let node = std::any::type_name::<crate::testing::sync::Node>();
+    assert_eq!(found, [(addrs[0], node, 2), (addrs[1], node, 3)]);
+
+    drop((a, b));
+    collect();
+    assert_eq!(counter.count(), 2);
+    assert!(iter_allocations().all(|info| !addrs.contains(&info.addr)));
+}
+#[test]
+#[cfg(feature = "heap-inspection")]
+/// Test that the type census counts the allocations of each type, and drops a collected cycle.
+fn type_census_counts() {
+    struct Cycle {
+        next: Mutex<Option<Gc<Cycle>>>,
+    }
+
+    unsafe impl Collectable for Cycle {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    struct Leaf {
+        _bytes: [u8; 24],
+    }
+
+    unsafe impl Collectable for Leaf {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    fn count_of<T>() -> (usize, usize) {
+        let name = std::any::type_name::<T>();
+        type_census()
+            .into_iter()
+            .find(|(type_name, ..)| type_name == name)
+            .map_or((0, 0), |(_, count, bytes)| (count, bytes))
+    }
+
+    let cycle_size = Layout::new::<GcBox<Cycle>>().size();
+    let leaf_size = Layout::new::<GcBox<Leaf>>().size();
+    let cycles = (0..3)
+        .map(|_| {
+            Gc::new(Cycle {
+                next: Mutex::new(None),
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, gc) in cycles.iter().enumerate() {
+        *gc.next.lock().unwrap() = Some(cycles[(i + 1) % cycles.len()].clone());
+    }
+    let leaves = (0..5)
+        .map(|_| Gc::new(Leaf { _bytes: [0; 24] }))
+        .collect::<Vec<_>>();
+    assert_eq!(count_of::<Cycle>(), (3, 3 * cycle_size));
+    assert_eq!(count_of::<Leaf>(), (5, 5 * leaf_size));
+
+    let census = type_census();
+    assert!(census.windows(2).all(|w| w[0].0 < w[1].0));
+
+    drop(cycles);
+    collect();
+    assert_eq!(count_of::<Cycle>(), (0, 0));
+    assert_eq!(count_of::<Leaf>(), (5, 5 * leaf_size));
+
+    drop(leaves);
+    collect();
+    assert_eq!(count_of::<Leaf>(), (0, 0));
+}
+
+#[test]
+/// Test that reserving room ahead of a burst of dirty allocations means that this thread's table
+/// tracking them is never reallocated.
+fn reserve_avoids_reallocation() {
+    const N: usize = 10_000;
+    std::thread::spawn(|| {
+        assert!(set_initial_capacity(16));
+        assert!(dirty_capacity() >= 16);
+        assert!(!set_initial_capacity(N));
+
+        set_collect_condition_local(|_| false);
+        reserve(N);
+        let capacity = dirty_capacity();
+        assert!(capacity >= N);
+
+        let gcs = (0..N).map(Gc::new).collect::<Vec<_>>();
+        for gc in &gcs {
+            drop(gc.clone());
+        }
+        assert_eq!(dirty_capacity(), capacity);
+
+        drop(gcs);
+        clear_collect_condition_local();
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+#[cfg(feature = "parallel-collect")]
+/// Test that a reference graph built by several threads at once frees exactly the garbage, when
+/// allocations which refer to each other are split between the threads.
+fn parallel_graph() {
+    use crate::testing::sync::Node;
+
+    const N_BLOCKS: usize = 1024;
+    const BLOCK_SIZE: usize = 16;
+
+    set_collect_workers(4);
+    let counter = DropCounter::new();
+    let nodes = (0..N_BLOCKS * BLOCK_SIZE)
+        .map(|_| Gc::new(Node::new(&counter)))
+        .collect::<Vec<_>>();
+    // small, tangled blocks, so that the search from any dirty allocation stays shallow
+    for block in nodes.chunks(BLOCK_SIZE) {
+        for (i, node) in block.iter().enumerate() {
+            Node::link(node, &block[(i + 1) % BLOCK_SIZE]);
+            Node::link(node, &block[0]);
+        }
+    }
+    // every fourth block is still reachable, from any one of its nodes
+    let live = nodes
+        .iter()
+        .skip(3)
+        .step_by(4 * BLOCK_SIZE)
+        .cloned()
+        .collect::<Vec<_>>();
+    drop(nodes);
+
+    collect();
+    assert_eq!(counter.count(), (N_BLOCKS - N_BLOCKS / 4) * BLOCK_SIZE);
+    drop(live);
+    collect();
+    assert_eq!(counter.count(), N_BLOCKS * BLOCK_SIZE);
+    set_collect_workers(0);
+}
+
+#[test]
+/// Test that many allocations which are dirty at once, on several threads, are all collected
+/// exactly once.
+fn many_dirty_across_threads() {
+    use crate::testing::sync::Node;
+    use std::sync::Barrier;
+
+    const N_THREADS: usize = 8;
+    const N_PER_THREAD: usize = 2_000;
+
+    let counter = DropCounter::new();
+    let heads = Mutex::new(Vec::new());
+    let barrier = Barrier::new(N_THREADS);
+    std::thread::scope(|s| {
+        let threads = (0..N_THREADS)
+            .map(|_| {
+                s.spawn(|| {
+                    set_collect_condition_local(|_| false);
+                    // pairs of nodes in cycles, which can only be freed by a collection
+                    let nodes = (0..N_PER_THREAD)
+                        .map(|_| Gc::new(Node::new(&counter)))
+                        .collect::<Vec<_>>();
+                    for pair in nodes.chunks(2) {
+                        Node::link(&pair[0], &pair[1]);
+                        Node::link(&pair[1], &pair[0]);
+                    }
+                    heads.lock().unwrap().push(nodes[0].clone());
+                    barrier.wait();
+
+                    // tie the allocations of all the threads together
+                    let heads = heads.lock().unwrap().clone();
+                    let i = heads.iter().position(|h| Gc::ptr_eq(h, &nodes[0])).unwrap();
+                    Node::link(&nodes[0], &heads[(i + 1) % N_THREADS]);
+                    drop(heads);
+                    drop(nodes);
+
+                    // every node on every thread is dirty before any of them is delivered
+                    barrier.wait();
+                    assert_eq!(counter.count(), 0);
+                    clear_collect_condition_local();
+                })
+            })
+            .collect::<Vec<_>>();
+        // joining a thread waits for its dumpster to be delivered as the thread exits
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    });
+    drop(take(&mut *heads.lock().unwrap()));
+    collect();
+    assert_eq!(counter.count(), N_THREADS * N_PER_THREAD);
+}
+
+#[test]
+/// Test that cycles marked dirty after this thread's table of dirty allocations fills up are still
+/// collected.
+fn overfull_dirty_table() {
+    use crate::testing::sync::Node;
+
+    // more than a thread's table holds before it is handed to the garbage truck
+    const N_PAIRS: usize = 60_000;
+
+    let counter = DropCounter::new();
+    std::thread::spawn({
+        let counter = counter.clone();
+        move || {
+            set_collect_condition_local(|_| false);
+            let pairs = (0..N_PAIRS)
+                .map(|_| {
+                    let a = Gc::new(Node::new(&counter));
+                    let b = Gc::new(Node::new(&counter));
+                    Node::link(&a, &b);
+                    Node::link(&b, &a);
+                    a
+                })
+                .collect::<Vec<_>>();
+            drop(pairs);
+            collect();
+            assert_eq!(counter.count(), 2 * N_PAIRS);
+            clear_collect_condition_local();
+        }
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+/// Test that every finalizer in a garbage cycle runs before any value in it is dropped, and can
+/// still read the other allocations in the cycle.
+fn finalizer_before_drop() {
+    static EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct Handle {
+        name: &'static str,
+        peer: Mutex<Option<Gc<Handle>>>,
+    }
+
+    unsafe impl Collectable for Handle {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.peer.accept(visitor)
+        }
+    }
+
+    impl Finalize for Handle {
+        fn finalize(&self) {
+            let peer_name = self.peer.lock().unwrap().as_ref().unwrap().name;
+            EVENTS
+                .lock()
+                .unwrap()
+                .push(format!("finalize {} (peer {peer_name})", self.name));
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            EVENTS.lock().unwrap().push(format!("drop {}", self.name));
+        }
+    }
+
+    let a = Gc::new_finalized(Handle {
+        name: "a",
+        peer: Mutex::new(None),
+    });
+    let b = Gc::new_finalized(Handle {
+        name: "b",
+        peer: Mutex::new(Some(a.clone())),
+    });
+    *a.peer.lock().unwrap() = Some(b.clone());
+    drop((a, b));
+    collect();
+
+    let mut events = take(&mut *EVENTS.lock().unwrap());
+    assert_eq!(events.len(), 4);
+    events[..2].sort();
+    events[2..].sort();
+    assert_eq!(
+        events,
+        [
+            "finalize a (peer b)",
+            "finalize b (peer a)",
+            "drop a",
+            "drop b"
+        ]
+    );
+}
+
+#[test]
+/// Test that a finalizer can resurrect its cycle, and that it does not run again when the cycle
+/// dies for good.
+fn finalizer_resurrection() {
+    static SAVED: Mutex<Option<Gc<Node>>> = Mutex::new(None);
+    static N_FINALIZED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Node {
+        next: Mutex<Option<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    impl Finalize for Node {
+        fn finalize(&self) {
+            N_FINALIZED.fetch_add(1, Ordering::Relaxed);
+            *SAVED.lock().unwrap() = self.next.lock().unwrap().clone();
+        }
+    }
+
+    let counter = DropCounter::new();
+    let a = Gc::new_finalized(Node {
+        next: Mutex::new(None),
+        _token: counter.token(),
+    });
+    *a.next.lock().unwrap() = Some(a.clone());
+    drop(a);
+    collect();
+    assert_eq!(N_FINALIZED.load(Ordering::Relaxed), 1);
+    assert_eq!(counter.count(), 0);
+
+    let saved = SAVED.lock().unwrap().take().unwrap();
+    assert!(Gc::ptr_eq(
+        &saved,
+        saved.next.lock().unwrap().as_ref().unwrap()
+    ));
+    drop(saved);
+    collect();
+    assert_eq!(N_FINALIZED.load(Ordering::Relaxed), 1);
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+/// Test that values dropped by a collection may make, use, and drop `Gc`s of their own, and that
+/// the ones they keep survive the collection.
+fn allocate_while_collecting() {
+    struct Node {
+        id: usize,
+        next: Mutex<Option<Gc<Node>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    struct LogEntry {
+        id: usize,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for LogEntry {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    // a collection may drop the nodes on any thread, so the log is shared
+    static LOG: Mutex<Vec<Gc<LogEntry>>> = Mutex::new(Vec::new());
+    #[allow(clippy::non_std_lazy_statics)]
+    static COUNTER: once_cell::sync::Lazy<DropCounter> =
+        once_cell::sync::Lazy::new(DropCounter::new);
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            let entry = Gc::new(LogEntry {
+                id: self.id,
+                _token: COUNTER.token(),
+            });
+            let copy = entry.clone();
+            assert_eq!(copy.id, self.id);
+            LOG.lock().unwrap().push(entry);
+            drop(copy);
+        }
+    }
+
+    const N: usize = 1000;
+    let first = Gc::new(Node {
+        id: 0,
+        next: Mutex::new(None),
+    });
+    let mut last = first.clone();
+    for id in 1..N {
+        let node = Gc::new(Node {
+            id,
+            next: Mutex::new(None),
+        });
+        *last.next.lock().unwrap() = Some(node.clone());
+        last = node;
+    }
+    *last.next.lock().unwrap() = Some(first);
+    drop(last);
+    collect();
+
+    let mut ids = LOG.lock().unwrap().iter().map(|e| e.id).collect::<Vec<_>>();
+    ids.sort_unstable();
+    assert_eq!(ids, (0..N).collect::<Vec<_>>());
+    collect();
+    assert_eq!(COUNTER.count(), 0);
+
+    LOG.lock().unwrap().clear();
+    collect();
+    assert_eq!(COUNTER.count(), N);
+}
+
+#[test]
+/// Test that a value dropped by a collection cannot resurrect the garbage it points to, but may
+/// still keep a clone of a `Gc` to an allocation which survives the collection.
+fn resurrect_in_drop() {
+    struct Node {
+        next: Mutex<Option<Gc<Node>>>,
+        keep: Gc<u8>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)?;
+            self.keep.accept(visitor)
+        }
+    }
+
+    // a collection may drop the nodes on any thread, so everything they record is shared
+    static RESURRECTED: Mutex<Vec<Gc<Node>>> = Mutex::new(Vec::new());
+    static KEPT: Mutex<Vec<Gc<u8>>> = Mutex::new(Vec::new());
+    static REFUSED: AtomicUsize = AtomicUsize::new(0);
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            let next = self.next.lock().unwrap();
+            let next = next.as_ref().unwrap();
+            assert!(Gc::try_clone(next).is_none());
+            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| next.clone())) {
+                Ok(gc) => RESURRECTED.lock().unwrap().push(gc),
+                Err(payload) => {
+                    let message = payload
+                        .downcast_ref::<String>()
+                        .map(String::as_str)
+                        .or_else(|| payload.downcast_ref::<&str>().copied())
+                        .unwrap();
+                    assert!(message.contains("attempt to clone Gc"), "{message}");
+                    REFUSED.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            KEPT.lock()
+                .unwrap()
+                .push(Gc::try_clone(&self.keep).unwrap());
+        }
+    }
+
+    const N: usize = 3;
+    let counter = DropCounter::new();
+    let keep = Gc::new(7);
+    let first = Gc::new(Node {
+        next: Mutex::new(None),
+        keep: keep.clone(),
+        _token: counter.token(),
+    });
+    let mut last = first.clone();
+    for _ in 1..N {
+        let node = Gc::new(Node {
+            next: Mutex::new(None),
+            keep: keep.clone(),
+            _token: counter.token(),
+        });
+        *last.next.lock().unwrap() = Some(node.clone());
+        last = node;
+    }
+    *last.next.lock().unwrap() = Some(first);
+    drop(last);
+    collect();
+    settle();
+
+    assert_eq!(counter.count(), N);
+    assert_eq!(REFUSED.load(Ordering::Relaxed), N);
+    assert!(RESURRECTED.lock().unwrap().is_empty());
+    let kept = take(&mut *KEPT.lock().unwrap());
+    assert_eq!(kept.len(), N);
+    assert!(kept.iter().all(|k| **k == 7));
+}
+
+#[test]
+/// Test that a value which panics while it is collected is leaked, and that everything else in
+/// the collection is still freed.
+fn panic_leak_allocation() {
+    struct Node {
+        next: Mutex<Option<Gc<Node>>>,
+        panics: bool,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            assert!(!self.panics, "node panicked on drop");
+        }
+    }
+
+    let _guard = crate::unwind::POLICY_LOCK.lock().unwrap();
+    crate::set_collection_panic_policy(crate::PanicPolicy::LeakAllocation);
+
+    let counter = DropCounter::new();
+    let nodes: Vec<Gc<Node>> = (0..4)
+        .map(|i| {
+            Gc::new(Node {
+                next: Mutex::new(None),
+                panics: i == 1,
+                _token: counter.token(),
+            })
+        })
+        .collect();
+    for (i, node) in nodes.iter().enumerate() {
+        *node.next.lock().unwrap() = Some(nodes[(i + 1) % nodes.len()].clone());
+    }
+    drop(nodes);
+
+    let result = std::panic::catch_unwind(collect);
+    crate::set_collection_panic_policy(crate::PanicPolicy::Propagate);
+    assert!(result.is_err());
+    // the panicking node still dropped its fields while unwinding
+    assert_eq!(counter.count(), 4);
+
+    // the collector is still usable afterwards
+    let counter = DropCounter::new();
+    let a = Gc::new(Node {
+        next: Mutex::new(None),
+        panics: false,
+        _token: counter.token(),
+    });
+    *a.next.lock().unwrap() = Some(a.clone());
+    drop(a);
+    collect();
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+/// Test that a value which panics while it is collected does not stop the rest of its cycle from
+/// being dropped, and that the panic resumes once the collection is done.
+fn panic_propagate() {
+    struct Node {
+        next: Mutex<Option<Gc<Node>>>,
+        panics: bool,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            assert!(!self.panics, "node panicked on drop");
+        }
+    }
+
+    let _guard = crate::unwind::POLICY_LOCK.lock().unwrap();
+    assert_eq!(
+        crate::collection_panic_policy(),
+        crate::PanicPolicy::Propagate
+    );
+
+    let counter = DropCounter::new();
+    let nodes: Vec<Gc<Node>> = (0..8)
+        .map(|i| {
+            Gc::new(Node {
+                next: Mutex::new(None),
+                panics: i == 3,
+                _token: counter.token(),
+            })
+        })
+        .collect();
+    for (i, node) in nodes.iter().enumerate() {
+        *node.next.lock().unwrap() = Some(nodes[(i + 1) % nodes.len()].clone());
+    }
+    drop(nodes);
+
+    let result = std::panic::catch_unwind(collect);
+    assert!(result.is_err());
+    assert_eq!(counter.count(), 8);
+
+    // the collector is still usable afterwards, and the panic is not raised again
+    let counter = DropCounter::new();
+    let a = Gc::new(Node {
+        next: Mutex::new(None),
+        panics: false,
+        _token: counter.token(),
+    });
+    *a.next.lock().unwrap() = Some(a.clone());
+    drop(a);
+    collect();
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+/// Test that a deterministic collection drops garbage in the order in which it was allocated.
+fn deterministic_drop_order() {
+    struct Node {
+        id: usize,
+        edges: Mutex<Vec<Gc<Node>>>,
+        dropped: Arc<Mutex<Vec<usize>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.dropped.lock().unwrap().push(self.id);
+        }
+    }
+
+    fn scenario() -> Vec<usize> {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let nodes = (0..16)
+            .map(|id| {
+                Gc::new(Node {
+                    id,
+                    edges: Mutex::new(Vec::new()),
+                    dropped: dropped.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        for (i, node) in nodes.iter().enumerate() {
+            let mut edges = node.edges.lock().unwrap();
+            edges.push(nodes[(i * 7 + 3) % nodes.len()].clone());
+            edges.push(nodes[(i * 5 + 11) % nodes.len()].clone());
+        }
+        drop(nodes);
+        collect();
+        let order = take(&mut *dropped.lock().unwrap());
+        order
+    }
+
+    set_deterministic(true);
+    let first = scenario();
+    let second = scenario();
+    set_deterministic(false);
+
+    assert_eq!(first, (0..16).collect::<Vec<_>>());
+    assert_eq!(first, second);
+}
+
+#[test]
+/// Test that nothing reachable from a root is freed, and that the rooted allocations are collected
+/// once the root is gone.
+fn root_keeps_reachable() {
+    struct Node {
+        edges: Mutex<Vec<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    let counter = DropCounter::new();
+    let node = || {
+        Gc::new(Node {
+            edges: Mutex::new(Vec::new()),
+            _token: counter.token(),
+        })
+    };
+
+    // a rooted cycle, with a child which is only reachable through the root
+    let root = Gc::into_root(node());
+    let child = node();
+    root.edges.lock().unwrap().push(child.clone());
+    child.edges.lock().unwrap().push(Root::as_gc(&root).clone());
+
+    // a garbage cycle which also points into the rooted cycle
+    let garbage = node();
+    garbage.edges.lock().unwrap().push(garbage.clone());
+    garbage.edges.lock().unwrap().push(child.clone());
+    drop(garbage);
+
+    // make everything dirty
+    drop(child.clone());
+    drop(Root::as_gc(&root).clone());
+    drop(child);
+
+    collect();
+    assert_eq!(counter.count(), 1);
+
+    let clone = root.clone();
+    drop(root);
+    collect();
+    assert_eq!(counter.count(), 1);
+
+    drop(Root::into_gc(clone));
+    collect();
+    assert_eq!(counter.count(), 3);
+}
+#[test]
+/// Test reachability queries over a diamond, a cycle, and a pair of disconnected allocations.
+fn reachability_queries() {
+    fn ids<T: Collectable + Send + Sync + ?Sized>(gcs: &[&Gc<T>]) -> Vec<GcId> {
+        gcs.iter()
+            .map(|gc| GcId::of(Gc::peek(gc).unwrap()))
+            .collect()
+    }
+
+    let counter = DropCounter::new();
+
+    // a diamond
+    graph!(sync, counter; top, left, right, bottom;
+        top -> left, top -> right, left -> bottom, right -> bottom);
+    assert!(is_reachable_from(&top, &bottom));
+    assert!(is_reachable_from(&left, &bottom));
+    assert!(!is_reachable_from(&left, &right));
+    assert!(!is_reachable_from(&bottom, &top));
+    let path = path_from(&top, &bottom).unwrap();
+    assert!(path == ids(&[&top, &left, &bottom]) || path == ids(&[&top, &right, &bottom]));
+    assert_eq!(path_from(&top, &top), Some(ids(&[&top])));
+
+    // a cycle, which every allocation in it can reach
+    graph!(sync, counter; a, b, c; a -> b, b -> c, c -> a);
+    for (from, to) in [(&a, &b), (&b, &a), (&c, &b), (&a, &a)] {
+        assert!(is_reachable_from(from, to));
+    }
+    assert_eq!(path_from(&b, &a), Some(ids(&[&b, &c, &a])));
+    assert!(!is_reachable_from(&a, &top));
+
+    // a disconnected pair, of different types
+    let lonely = Gc::new(0u32);
+    graph!(sync, counter; other; );
+    assert!(!is_reachable_from(&lonely, &other));
+    assert!(!is_reachable_from(&other, &lonely));
+    assert_eq!(path_from(&other, &lonely), None);
+
+    // the queries left the collector alone, so the cycle is still freed as usual
+    drop((top, left, right, bottom, a, b, c, other));
+    collect();
+    assert_eq!(counter.count(), 8);
+}
+
+#[test]
+/// Test that a dry run reports exactly the allocations which the next collection frees, without
+/// freeing any of them.
+fn dry_run_matches_collection() {
+    struct Node {
+        edges: Mutex<Vec<Gc<Node>>>,
+        dropped: Arc<Mutex<HashSet<usize>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.dropped
+                .lock()
+                .unwrap()
+                .insert(std::ptr::from_ref(self) as usize);
+        }
+    }
+
+    let dropped = Arc::new(Mutex::new(HashSet::new()));
+    let node = || {
+        Gc::new(Node {
+            edges: Mutex::new(Vec::new()),
+            dropped: dropped.clone(),
+        })
+    };
+
+    // a live cycle, which is made dirty
+    let live = node();
+    let other = node();
+    live.edges.lock().unwrap().push(other.clone());
+    other.edges.lock().unwrap().push(live.clone());
+    drop(other);
+
+    // a garbage cycle which points into the live one
+    let a = node();
+    let b = node();
+    a.edges.lock().unwrap().push(b.clone());
+    b.edges.lock().unwrap().push(a.clone());
+    b.edges.lock().unwrap().push(live.clone());
+    drop(a);
+    drop(b);
+
+    let report = collect_dry_run();
+    assert!(dropped.lock().unwrap().is_empty());
+    let reported = report
+        .allocations
+        .iter()
+        .map(|alloc| alloc.addr)
+        .collect::<HashSet<_>>();
+    assert!(reported.len() >= 2);
+
+    collect();
+    // other tests may drop garbage of their own, but only this test's nodes record their drops
+    let freed = take(&mut *dropped.lock().unwrap());
+    assert_eq!(freed.len(), 2);
+    assert!(freed.is_subset(&reported));
+
+    drop(live);
+    collect();
+}
+
+#[test]
+#[cfg(feature = "allocator-api")]
+/// Test that every allocation made through a custom allocator is freed through it, whether it is
+/// dropped or collected, even when it is dropped on another thread.
+fn new_in_frees_through_allocator() {
+    use crate::testing::{sync::Node, CountingAllocator};
+
+    let alloc = CountingAllocator::new();
+    let counter = DropCounter::new();
+
+    // acyclic, and so freed as soon as it is dropped
+    drop(Gc::new_in(Node::new(&counter), alloc.clone()));
+    assert_eq!(counter.count(), 1);
+    assert_eq!(alloc.bytes_freed(), alloc.bytes_allocated());
+
+    // a cycle mixing both allocators, dropped on another thread and then collected
+    let a = Gc::new_in(Node::new(&counter), alloc.clone());
+    let b = Gc::new(Node::new(&counter));
+    let c = Gc::new_in(Node::new(&counter), alloc.clone());
+    Node::link(&a, &b);
+    Node::link(&b, &c);
+    Node::link(&c, &a);
+    std::thread::spawn(move || drop((a, b, c))).join().unwrap();
+    collect();
+    assert_eq!(counter.count(), 4);
+    assert!(alloc.bytes_allocated() > 0);
+    assert_eq!(alloc.bytes_freed(), alloc.bytes_allocated());
+}
+
+#[test]
+/// Test that allocations whose references are dropped on threads other than the one which made
+/// them are freed by a collection, even when the owning thread drops the last reference without
+/// knowing it.
+fn biased_counts_dropped_elsewhere() {
+    let counter = DropCounter::new();
+
+    // the only reference, dropped on another thread
+    let gc = Gc::new(counter.token());
+    std::thread::spawn(move || drop(gc)).join().unwrap();
+    collect();
+    assert_eq!(counter.count(), 1);
+
+    // references made by this thread and dropped elsewhere, before this thread drops the last one
+    let gc = Gc::new(counter.token());
+    let clones = (0..10).map(|_| gc.clone()).collect::<Vec<_>>();
+    std::thread::spawn(move || drop(clones)).join().unwrap();
+    collect();
+    assert_eq!(counter.count(), 1);
+    drop(gc);
+    collect();
+    assert_eq!(counter.count(), 2);
+
+    // references made elsewhere and dropped here, so that the owner's count is the last to go
+    let gc = Gc::new(counter.token());
+    let clones = std::thread::scope(|s| {
+        s.spawn(|| (0..10).map(|_| gc.clone()).collect::<Vec<_>>())
+            .join()
+            .unwrap()
+    });
+    drop(clones);
+    assert_eq!(counter.count(), 2);
+    drop(gc);
+    assert_eq!(counter.count(), 3);
+}
+
+#[test]
+/// Test that references cloned and dropped on many threads at once, while collections run, are
+/// counted exactly, so that every allocation is freed once and only once.
+fn biased_counts_stress() {
+    use crate::testing::sync::Node;
+    use std::sync::mpsc::{channel, Sender};
+
+    const N_THREADS: usize = 4;
+    const N_NODES: usize = 200;
+    const N_STEPS: usize = 20_000;
+
+    let counter = DropCounter::new();
+    let done = AtomicBool::new(false);
+    std::thread::scope(|s| {
+        let (senders, receivers): (Vec<Sender<Gc<Node>>>, Vec<_>) =
+            (0..N_THREADS).map(|_| channel()).unzip();
+        let workers = receivers
+            .into_iter()
+            .enumerate()
+            .map(|(i, receiver)| {
+                let senders = senders.clone();
+                let counter = &counter;
+                s.spawn(move || {
+                    fastrand::seed(0xb1a5 + i as u64);
+                    // each thread owns some allocations, some of them in cycles
+                    let mut held = (0..N_NODES / N_THREADS)
+                        .map(|_| Gc::new(Node::new(counter)))
+                        .collect::<Vec<_>>();
+                    for pair in held.chunks(2) {
+                        Node::link(&pair[0], &pair[1]);
+                        Node::link(&pair[1], &pair[0]);
+                    }
+                    for _ in 0..N_STEPS {
+                        held.extend(receiver.try_iter());
+                        if held.is_empty() {
+                            continue;
+                        }
+                        let i = fastrand::usize(..held.len());
+                        let to = &senders[fastrand::usize(..N_THREADS)];
+                        // a send to a thread which has finished fails, and drops the reference
+                        match fastrand::u8(..4) {
+                            0 => held.push(held[i].clone()),
+                            1 => drop(to.send(held[i].clone())),
+                            2 => drop(to.send(held.swap_remove(i))),
+                            _ => drop(held.swap_remove(i)),
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(senders);
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                collect();
+            }
+        });
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+    collect();
+    assert_eq!(counter.count(), N_NODES);
+}
+
+#[test]
+/// Test that cloning a `Gc` on a thread which has never made one does not need to give that
+/// thread a token, and that the clones are still counted exactly.
+fn clone_touches_only_allocation() {
+    let counter = DropCounter::new();
+    let gc = Gc::new(counter.token());
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let clones = (0..10).map(|_| gc.clone()).collect::<Vec<_>>();
+            assert_eq!(THREAD_TOKEN.with(Cell::get), 0);
+            drop(clones);
+            assert_eq!(THREAD_TOKEN.with(Cell::get), 0);
+        });
+    });
+    assert_eq!(counter.count(), 0);
+    drop(gc);
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+/// Test that the counts of `Gc`s which threads keep for themselves are added to the global counts
+/// once those threads exit.
+fn counts_flushed_at_thread_exit() {
+    const N_THREADS: usize = 8;
+    const N_CLONES: usize = 10_000;
+    // other tests make and drop `Gc`s at the same time, so the counts can only be checked roughly
+    const SLACK: usize = N_THREADS * N_CLONES / 4;
+
+    let gc = Gc::new(());
+    let before = n_gcs_existing();
+    let clones = std::thread::scope(|s| {
+        let handles = (0..N_THREADS)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut clones = (0..=N_CLONES).map(|_| gc.clone()).collect::<Vec<_>>();
+                    // a thread which has never dropped a `Gc` has no dumpster to flush at exit
+                    clones.pop();
+                    clones
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    let after = n_gcs_existing();
+    assert!(after + SLACK >= before + N_THREADS * N_CLONES);
+
+    drop(clones);
+    assert!(n_gcs_existing() <= after + SLACK - N_THREADS * N_CLONES);
+}
+
+#[test]
+/// Test that many threads building cycles, linking them into older ones, and collecting now and
+/// then, free every allocation exactly once.
+fn cycles_stress() {
+    use crate::testing::sync::Node;
+
+    const N_THREADS: usize = 8;
+    const N_CYCLES: usize = 500;
+    const CYCLE_LEN: usize = 4;
+
+    let counter = DropCounter::new();
+    std::thread::scope(|s| {
+        let threads = (0..N_THREADS)
+            .map(|i| {
+                let counter = &counter;
+                s.spawn(move || {
+                    fastrand::seed(0xe90c + i as u64);
+                    let mut kept = Vec::new();
+                    for n in 0..N_CYCLES {
+                        let nodes = (0..CYCLE_LEN)
+                            .map(|_| Gc::new(Node::new(counter)))
+                            .collect::<Vec<_>>();
+                        for (j, node) in nodes.iter().enumerate() {
+                            Node::link(node, &nodes[(j + 1) % CYCLE_LEN]);
+                        }
+                        if !kept.is_empty() && fastrand::bool() {
+                            let k = fastrand::usize(..kept.len());
+                            Node::link(&nodes[0], &kept[k]);
+                            if fastrand::bool() {
+                                kept.swap_remove(k);
+                            }
+                        }
+                        if fastrand::u8(..4) == 0 {
+                            kept.push(nodes[0].clone());
+                        }
+                        if n % 64 == 0 {
+                            collect();
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        // joining a thread waits for its dumpster to be delivered as the thread exits
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    });
+    collect();
+    assert_eq!(counter.count(), N_THREADS * N_CYCLES * CYCLE_LEN);
+}
+
+#[test]
+/// Test that a collection of many cycles mixing several types, some of which point into an
+/// allocation which is still reachable, drops every unreachable value exactly once and no others.
+fn mixed_type_cycles() {
+    struct Head {
+        tail: Mutex<Option<Gc<Tail>>>,
+        _token: DropToken,
+    }
+
+    struct Tail {
+        head: Gc<Head>,
+        leaves: Vec<Gc<Leaf>>,
+        _token: DropToken,
+    }
+
+    struct Leaf {
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Head {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.tail.accept(visitor)
+        }
+    }
+
+    unsafe impl Collectable for Tail {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.head.accept(visitor)?;
+            self.leaves.accept(visitor)
+        }
+    }
+
+    unsafe impl Collectable for Leaf {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    const N: usize = 10_000;
+
+    let heads = DropCounter::new();
+    let tails = DropCounter::new();
+    let leaves = DropCounter::new();
+    let kept = Gc::new(Leaf {
+        _token: leaves.token(),
+    });
+    for i in 0..N {
+        let head = Gc::new(Head {
+            tail: Mutex::new(None),
+            _token: heads.token(),
+        });
+        let mut tail_leaves = vec![Gc::new(Leaf {
+            _token: leaves.token(),
+        })];
+        if i % 2 == 0 {
+            tail_leaves.push(kept.clone());
+        }
+        let tail = Gc::new(Tail {
+            head: head.clone(),
+            leaves: tail_leaves,
+            _token: tails.token(),
+        });
+        *head.tail.lock().unwrap() = Some(tail);
+    }
+    collect();
+    assert_eq!(heads.count(), N);
+    assert_eq!(tails.count(), N);
+    assert_eq!(leaves.count(), N);
+
+    drop(kept);
+    collect();
+    assert_eq!(leaves.count(), N + 1);
+}
+
+#[cfg(feature = "epoch")]
+#[test]
+/// Test that garbage retired by automatic collections is destroyed by other threads as they make
+/// and drop `Gc`s, without anyone collecting explicitly.
+fn retired_destroyed_elsewhere() {
+    use crate::testing::sync::Node;
+
+    const N: usize = 100;
+
+    fn always_collect(_: &CollectInfo) -> bool {
+        true
+    }
+
+    let counter = DropCounter::new();
+    // a background collector would destroy the garbage itself
+    let _guard = GLOBAL_POLICY_LOCK.lock().unwrap();
+    set_collect_condition_local(always_collect);
+    for _ in 0..N {
+        let gc = Gc::new(Node::new(&counter));
+        Node::link(&gc, &gc);
+    }
+    clear_collect_condition_local();
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..1_000_000 {
+                if counter.count() == N {
+                    break;
+                }
+                drop(Gc::new(()));
+            }
+        });
+    });
+    assert_eq!(counter.count(), N);
+}
+
+#[test]
+/// Test that a `Gc` is only as big as its pointer with the `tagged-ptr` feature, and holds its tag
+/// in a word of its own otherwise.
+fn gc_size() {
+    #[cfg(feature = "tagged-ptr")]
+    const TAG_SIZE: usize = 0;
+    #[cfg(not(feature = "tagged-ptr"))]
+    const TAG_SIZE: usize = size_of::<usize>();
+
+    const { assert!(size_of::<Gc<u8>>() == size_of::<*const u8>() + TAG_SIZE) };
+    const { assert!(size_of::<Gc<[u8]>>() == size_of::<*const [u8]>() + TAG_SIZE) };
+}
+
+#[test]
+/// Test that the flags packed in with the shared count of an allocation are left alone as the
+/// count goes below zero and up to its maximum, and that they keep the header small.
+fn packed_header() {
+    /// The number of times `count_finalizer` has been called.
+    static N_FINALIZED: AtomicUsize = AtomicUsize::new(0);
+
+    /// A finalizer which only counts how many times it is run.
+    unsafe fn count_finalizer(_: NonNull<()>) {
+        N_FINALIZED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(not(feature = "allocator-api"))]
+    const {
+        assert!(size_of::<GcBox<()>>() == 2 * size_of::<u32>() + 3 * size_of::<usize>());
+    };
+
+    let flags = |header: &GcBox<()>| header.shared.load(Ordering::Relaxed) & (ONE_SHARED - 1);
+
+    // this thread owns the header, so other threads' drops overdraw the shared count
+    let header = Box::leak(Box::new(GcBox::new(())));
+    let ptr = NonNull::from(&*header);
+    collect::register_side_entries(ptr, Some(count_finalizer));
+    header.condemn();
+    assert_eq!(header.release_shared(), None);
+    assert!(header.is_overdrawn());
+    assert!(header.is_condemned());
+    assert_eq!(flags(header), FINALIZER | CONDEMNED);
+    assert!(header.finalize());
+    assert!(!header.finalize());
+    assert_eq!(N_FINALIZED.load(Ordering::Relaxed), 1);
+    assert_eq!(flags(header), CONDEMNED);
+    assert_eq!(shared_count(header.shared.load(Ordering::Relaxed)), -1);
+    assert_eq!(header.ref_count(), 0);
+    unsafe { free_box(ptr.as_ptr(), Layout::new::<GcBox<()>>()) };
+
+    // once merged, the shared count is the whole count, up to its maximum
+    let header = GcBox::new(());
+    header.biased.store(0, Ordering::Relaxed);
+    header.shared.store(
+        ((MAX_SHARED - 1).cast_unsigned() * ONE_SHARED) | MERGED | DIRTY,
+        Ordering::Relaxed,
+    );
+    header.acquire();
+    assert_eq!(
+        shared_count(header.shared.load(Ordering::Relaxed)),
+        MAX_SHARED
+    );
+    assert_eq!(header.ref_count(), MAX_SHARED.cast_unsigned());
+    assert_eq!(flags(&header), MERGED | DIRTY);
+    assert_eq!(header.release_shared(), Some(false));
+    header.condemn();
+    assert_eq!(flags(&header), MERGED | DIRTY | CONDEMNED);
+    assert!(!header.is_overdrawn());
+
+    header
+        .shared
+        .store(ONE_SHARED | MERGED | CONDEMNED, Ordering::Relaxed);
+    assert_eq!(header.release_shared(), Some(true));
+    assert_eq!(flags(&header), MERGED | CONDEMNED);
+    assert_eq!(header.ref_count(), 0);
+
+    // a full biased count spills over into the shared count
+    let header = GcBox::new(());
+    header.biased.store(u32::MAX, Ordering::Relaxed);
+    header.acquire();
+    assert_eq!(header.biased.load(Ordering::Relaxed), u32::MAX);
+    assert_eq!(shared_count(header.shared.load(Ordering::Relaxed)), 1);
+    assert_eq!(header.ref_count(), u32::MAX as usize + 1);
+}
+
+#[test]
+/// Test that a cycle is collected by a single collection even when its `Gc`s were last tagged by a
+/// sweep whose tag has the same low bits as the sweep which finds it.
+fn stale_tag_collected() {
+    struct Cycle {
+        next: Mutex<Option<Gc<Cycle>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Cycle {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    let counter = DropCounter::new();
+    let gc = Gc::new(Cycle {
+        next: Mutex::new(None),
+        _token: counter.token(),
+    });
+    *gc.next.lock().unwrap() = Some(gc.clone());
+
+    // unless another thread collects first, the next sweep has the tag `next`, and the inner `Gc`
+    // is given the tag of a sweep long before it
+    let period = align_of::<GcBox<()>>();
+    let next = CURRENT_TAG.fetch_add(period, Ordering::Relaxed) + period + 1;
+    gc.next
+        .lock()
+        .unwrap()
+        .as_ref()
+        .unwrap()
+        .tag_with(next - period);
+    drop(gc);
+
+    collect();
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+/// Test that a collection which condemns many allocations pointing at each other does not touch
+/// any of them once it has freed them, and that the `Gc`s inside them stop being counted.
+fn condemned_web() {
+    struct Node {
+        refs: Mutex<Vec<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.refs.accept(visitor)
+        }
+    }
+
+    /// Memory allocated as each node is dropped, which is likely to reuse the memory of the node
+    /// freed just before it, so that writing to a freed node shows up as a nonzero byte.
+    static FILLERS: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            FILLERS
+                .lock()
+                .unwrap()
+                .push(vec![0; size_of::<GcBox<Node>>()]);
+        }
+    }
+
+    const N_NODES: usize = 2_000;
+    const N_ROUNDS: usize = 10;
+    // other tests make and drop `Gc`s at the same time, so the count can only be checked roughly
+    // against the four `Gc`s inside every node
+    const SLACK: usize = N_ROUNDS * N_NODES;
+
+    let counter = DropCounter::new();
+    let before = n_gcs_existing();
+    for round in 0..N_ROUNDS as u64 {
+        fastrand::seed(round);
+        FILLERS.lock().unwrap().reserve(N_NODES);
+        let nodes = (0..N_NODES)
+            .map(|_| {
+                Gc::new(Node {
+                    refs: Mutex::new(Vec::new()),
+                    _token: counter.token(),
+                })
+            })
+            .collect::<Vec<_>>();
+        for node in &nodes {
+            let mut refs = node.refs.lock().unwrap();
+            for _ in 0..4 {
+                refs.push(nodes[fastrand::usize(..N_NODES)].clone());
+            }
+        }
+        drop(nodes);
+        collect();
+        settle();
+        let mut fillers = FILLERS.lock().unwrap();
+        assert!(fillers.iter().all(|f| f.iter().all(|&b| b == 0)));
+        fillers.clear();
+    }
+    assert_eq!(counter.count(), N_ROUNDS * N_NODES);
+    assert!(n_gcs_existing() < before + SLACK);
+}
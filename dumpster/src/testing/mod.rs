@@ -0,0 +1,247 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Scaffolding for testing code which uses garbage-collected values.
+//!
+//! This module is only available with the `testing` feature.
+//! It provides:
+//!
+//! - [`DropCounter`], for counting how many values have been dropped.
+//! - [`unsync::assert_heap_empty`] and [`sync::assert_heap_empty`], for checking that no garbage
+//!   was left behind.
+//! - [`unsync::with_aggressive_collection`] and [`sync::with_aggressive_collection`], for running a
+//!   collection on every drop of a `Gc`, which quickly shakes out incorrect implementations of
+//!   [`Collectable`](crate::Collectable).
+//! - [`graph!`](crate::graph), for building small cyclic graphs of [`unsync::Node`]s or
+//!   [`sync::Node`]s.
+//! - `CountingAllocator`, with the `allocator-api` feature, for checking that everything allocated
+//!   through `Gc::new_in` is freed through the same allocator.
+//!
+//! # Examples
+//!
+//! ```
+//! use dumpster::{
+//!     graph,
+//!     testing::{unsync::assert_heap_empty, DropCounter},
+//! };
+//!
+//! let counter = DropCounter::new();
+//! graph!(unsync, counter; a, b; a -> b, b -> a);
+//! drop(a);
+//! drop(b);
+//!
+//! assert_heap_empty();
+//! assert_eq!(counter.count(), 2);
+//! ```
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+#[cfg(feature = "allocator-api")]
+use std::{
+    alloc::{AllocError, Allocator, Global, Layout},
+    ptr::NonNull,
+};
+
+use crate::{Collectable, GcFree, Visitor};
+
+pub mod sync;
+pub mod unsync;
+
+#[derive(Clone, Debug, Default)]
+/// A counter of how many [`DropToken`]s created from it have been dropped.
+///
+/// Cloning a `DropCounter` produces another handle to the same count.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{testing::DropCounter, unsync::Gc};
+///
+/// let counter = DropCounter::new();
+/// let gc = Gc::new(counter.token());
+/// assert_eq!(counter.count(), 0);
+///
+/// drop(gc);
+/// assert_eq!(counter.count(), 1);
+/// ```
+pub struct DropCounter {
+    /// The number of tokens dropped so far.
+    count: Arc<AtomicUsize>,
+}
+
+impl DropCounter {
+    #[must_use]
+    /// Construct a new counter, starting at zero.
+    pub fn new() -> DropCounter {
+        DropCounter::default()
+    }
+
+    #[must_use]
+    /// Create a token which increments this counter when it is dropped.
+    pub fn token(&self) -> DropToken {
+        DropToken {
+            count: Arc::clone(&self.count),
+        }
+    }
+
+    #[must_use]
+    /// Get the number of tokens from this counter which have been dropped.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Debug)]
+/// A value which increments a [`DropCounter`] when it is dropped.
+///
+/// Store one of these in a garbage-collected value to find out when that value is dropped.
+pub struct DropToken {
+    /// The count to increment.
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for DropToken {
+    fn drop(&mut self) {
+        self.count.fetch_add(1, Ordering::Release);
+    }
+}
+
+unsafe impl Collectable for DropToken {
+    #[inline]
+    fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+        Ok(())
+    }
+
+    const IS_LEAF: bool = true;
+}
+
+// SAFETY: a `DropToken` only contains a counter.
+unsafe impl GcFree for DropToken {}
+
+#[cfg(feature = "allocator-api")]
+#[derive(Clone, Debug, Default)]
+/// An [`Allocator`] which passes everything on to the global allocator, while counting how much
+/// memory it has allocated and freed.
+///
+/// Cloning a `CountingAllocator` produces another handle to the same counts.
+///
+/// This is only available with the `testing` and `allocator-api` features.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(allocator_api)]
+///
+/// use dumpster::{testing::CountingAllocator, unsync::Gc};
+///
+/// let alloc = CountingAllocator::new();
+/// let gc = Gc::new_in(0u64, alloc.clone());
+/// assert!(alloc.bytes_allocated() > 0);
+///
+/// drop(gc);
+/// assert_eq!(alloc.bytes_freed(), alloc.bytes_allocated());
+/// ```
+pub struct CountingAllocator {
+    /// The number of bytes allocated so far.
+    allocated: Arc<AtomicUsize>,
+    /// The number of bytes freed so far.
+    freed: Arc<AtomicUsize>,
+}
+
+#[cfg(feature = "allocator-api")]
+impl CountingAllocator {
+    #[must_use]
+    /// Construct a new allocator, which has allocated nothing.
+    pub fn new() -> CountingAllocator {
+        CountingAllocator::default()
+    }
+
+    #[must_use]
+    /// Get the number of bytes which this allocator has allocated.
+    pub fn bytes_allocated(&self) -> usize {
+        self.allocated.load(Ordering::Acquire)
+    }
+
+    #[must_use]
+    /// Get the number of bytes which have been freed through this allocator.
+    pub fn bytes_freed(&self) -> usize {
+        self.freed.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+unsafe impl Allocator for CountingAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = Global.allocate(layout)?;
+        self.allocated.fetch_add(layout.size(), Ordering::Release);
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.freed.fetch_add(layout.size(), Ordering::Release);
+        Global.deallocate(ptr, layout);
+    }
+}
+
+#[macro_export]
+/// Declare some garbage-collected [`Node`](crate::testing::unsync::Node)s and link them
+/// together.
+///
+/// The first argument is either `unsync` or `sync`, choosing which kind of [`Node`] and `Gc` to
+/// use, and the second is a [`DropCounter`](crate::testing::DropCounter) which is incremented
+/// whenever one of the nodes is dropped.
+/// Next comes a list of names for the nodes, each of which is bound to a new `Gc` in the
+/// enclosing scope, and then a list of edges of the form `from -> to`.
+/// Since this macro declares variables, it can only be used as a statement.
+///
+/// This macro is only available with the `testing` feature.
+///
+/// [`Node`]: crate::testing::unsync::Node
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{graph, testing::DropCounter};
+///
+/// let counter = DropCounter::new();
+/// // a ring of three nodes, one of which also points to itself
+/// graph!(sync, counter; a, b, c; a -> b, b -> c, c -> a, c -> c);
+/// assert_eq!(a.edges().len(), 1);
+/// assert_eq!(c.edges().len(), 2);
+///
+/// drop((a, b, c));
+/// dumpster::sync::collect();
+/// assert_eq!(counter.count(), 3);
+/// ```
+macro_rules! graph {
+    (
+        $flavor:ident, $counter:expr;
+        $($node:ident),+ $(,)?;
+        $($from:ident -> $to:ident),* $(,)?
+    ) => {
+        let counter: &$crate::testing::DropCounter = &$counter;
+        $(
+            let $node = $crate::$flavor::Gc::new($crate::testing::$flavor::Node::new(counter));
+        )+
+        $(
+            $crate::testing::$flavor::Node::link(&$from, &$to);
+        )*
+    };
+}
@@ -0,0 +1,128 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Testing scaffolding for [`crate::sync`].
+
+use std::panic::resume_unwind;
+
+use parking_lot::Mutex;
+
+use crate::{
+    sync::{collect_await, replace_collect_condition_local, stats, CollectCondition, Gc},
+    Collectable, Visitor,
+};
+
+use super::{DropCounter, DropToken};
+
+/// Collect all garbage, then assert that no garbage-collected allocations are left on any thread.
+///
+/// Since the `sync` heap is shared by every thread, this should only be used when no other threads
+/// are using `Gc`s, such as in a test which runs in its own process.
+///
+/// # Panics
+///
+/// This function panics if any allocations still exist after collecting.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{sync::Gc, testing::sync::assert_heap_empty};
+///
+/// let gc = Gc::new(0);
+/// drop(gc);
+/// assert_heap_empty();
+/// ```
+pub fn assert_heap_empty() {
+    collect_await();
+    let stats = stats();
+    assert!(
+        stats.live_allocations == 0,
+        "{} garbage-collected allocations ({} bytes) are still alive",
+        stats.live_allocations,
+        stats.live_bytes
+    );
+}
+
+/// Run `f` while a collection is started every time a [`Gc`] is dropped on this thread, then
+/// restore the previous collect condition for this thread.
+///
+/// This only affects the calling thread, as with
+/// [`set_collect_condition_local`](crate::sync::set_collect_condition_local).
+/// Collecting this often is slow, but it makes mistakes in implementations of [`Collectable`]
+/// much more likely to cause a failure close to where they were made.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{sync::Gc, testing::sync::with_aggressive_collection};
+///
+/// let sum = with_aggressive_collection(|| {
+///     let gcs = (0..10).map(Gc::new).collect::<Vec<_>>();
+///     gcs.iter().map(|gc| **gc).sum::<i32>()
+/// });
+/// assert_eq!(sum, 45);
+/// ```
+pub fn with_aggressive_collection<R>(f: impl FnOnce() -> R) -> R {
+    let previous = replace_collect_condition_local(Some(CollectCondition::custom(|_| true)));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    replace_collect_condition_local(previous);
+    result.unwrap_or_else(|panic| resume_unwind(panic))
+}
+
+#[derive(Debug)]
+/// A node in a graph of garbage-collected values, as built by [`graph!`](crate::graph).
+pub struct Node {
+    /// The nodes which this node points to.
+    edges: Mutex<Vec<Gc<Node>>>,
+    /// The token which records when this node is dropped.
+    _token: DropToken,
+}
+
+impl Node {
+    #[must_use]
+    /// Construct a node with no edges, which increments `counter` when it is dropped.
+    pub fn new(counter: &DropCounter) -> Node {
+        Node {
+            edges: Mutex::new(Vec::new()),
+            _token: counter.token(),
+        }
+    }
+
+    /// Add an edge from `from` to `to`.
+    pub fn link(from: &Gc<Node>, to: &Gc<Node>) {
+        from.edges.lock().push(to.clone());
+    }
+
+    #[must_use]
+    /// Get the nodes which this node points to.
+    pub fn edges(&self) -> Vec<Gc<Node>> {
+        self.edges.lock().clone()
+    }
+
+    /// Remove every edge out of this node.
+    pub fn clear(&self) {
+        self.edges.lock().clear();
+    }
+}
+
+unsafe impl Collectable for Node {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        // if the edges are locked, they may be changing, so the node can't be inspected
+        self.edges.try_lock().ok_or(())?.accept(visitor)
+    }
+}
@@ -19,52 +19,287 @@
 //! Implementations of the single-threaded garbage-collection logic.
 
 use std::{
-    alloc::{dealloc, Layout},
+    alloc::Layout,
+    any::Any,
     cell::{Cell, RefCell},
     collections::{hash_map::Entry, HashMap, HashSet},
-    num::NonZeroUsize,
-    ptr::{drop_in_place, NonNull},
+    mem::{forget, take},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    ptr::{addr_of_mut, NonNull},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
 };
 
 use crate::{
+    hash::BuildPtrHasher,
+    instrument::{trace_error, CollectSpan},
     ptr::Erased,
-    unsync::{default_collect_condition, CollectInfo, Gc},
-    Collectable, Visitor,
+    ratio,
+    unsync::{CollectCondition, CollectHooks, CollectInfo, CollectResult, Gc},
+    unwind::{drop_collected, resume_caught, take_caught},
+    Collectable, CollectionMode, DryRunReport, GarbageAllocation, Visitor,
 };
 
-use super::{CollectCondition, GcBox};
+use super::{
+    destroy, ephemeron::EphemeronInner, release_cleared, Finalizer, GcBox, CLEARED, COLLECTED,
+    EPHEMERON_KEY, FINALIZER, KNOWN_LIVE, ROOTED, SERIAL,
+};
+
+#[cfg(feature = "heap-dump")]
+use crate::dot::{value_addr, DotNode, EdgeFinder};
+#[cfg(feature = "heap-inspection")]
+use crate::AllocationInfo;
+#[cfg(feature = "leak-detection")]
+use crate::{LeakReport, LeakedAllocation};
+#[cfg(feature = "leak-detection")]
+use parking_lot::Mutex;
+
+use self::incremental::{step_assist, Incremental, Step};
+
+mod incremental;
 
 thread_local! {
     /// Whether the current thread is running a cleanup process.
     pub(super) static COLLECTING: Cell<bool> = const { Cell::new(false) };
+    /// The allocations made on this thread while a cleanup process was running, which that
+    /// process knows nothing about.
+    static FRESH: RefCell<AllocationSet> = RefCell::new(AllocationSet::default());
+    /// The capacity with which `DUMPSTER` will be created, or `None` if it already has been.
+    pub(super) static INITIAL_CAPACITY: Cell<Option<usize>> = const { Cell::new(Some(0)) };
     /// The global collection of allocation information for this thread.
-    pub(super) static DUMPSTER: Dumpster = Dumpster {
-        to_collect: RefCell::new(HashMap::new()),
-        n_ref_drops: Cell::new(0),
-        n_refs_living: Cell::new(0),
-        collect_condition: Cell::new(default_collect_condition),
-    };
+    pub(super) static DUMPSTER: Dumpster = Dumpster::with_capacity(
+        INITIAL_CAPACITY.with(Cell::take).unwrap_or(0),
+    );
+    /// The ephemerons which were dropped while a collection was running, which are dropped
+    /// again once it is over.
+    static DEFERRED_DROPS: RefCell<Vec<Box<dyn Any>>> = const { RefCell::new(Vec::new()) };
 }
 
+/// Drop `value` once the running collection is over.
+///
+/// If the thread is exiting, `value` is leaked instead.
+pub(super) fn defer_drop(value: Box<dyn Any>) {
+    let mut value = Some(value);
+    let _ = DEFERRED_DROPS.try_with(|v| v.borrow_mut().extend(value.take()));
+    forget(value);
+}
+
+/// Start or finish a cleanup process on the current thread.
+///
+/// Once it is finished, the allocations made while it ran are no different from any other.
+pub(super) fn set_collecting(collecting: bool) {
+    COLLECTING.with(|c| c.set(collecting));
+    if !collecting {
+        let _ = FRESH.try_with(|f| f.borrow_mut().clear());
+    }
+}
+
+/// Note that the allocation `id` has just been made, in case a cleanup process is running.
+pub(super) fn note_allocated(id: AllocationId) {
+    if COLLECTING.with(Cell::get) {
+        let _ = FRESH.try_with(|f| f.borrow_mut().insert(id));
+    }
+}
+
+/// Note that the allocation `id` is being destroyed, so that its address may be reused.
+pub(super) fn note_destroyed(id: AllocationId) {
+    if COLLECTING.with(Cell::get) {
+        let _ = FRESH.try_with(|f| f.borrow_mut().remove(&id));
+    }
+}
+
+/// Determine whether the references to the allocation `id` are out of reach of the running
+/// cleanup process, and so are counted as usual.
+///
+/// This is the case for every allocation when no cleanup process is running, and otherwise only
+/// for the ones made while it ran, which it treats as reachable.
+pub(super) fn is_fresh(id: AllocationId) -> bool {
+    !COLLECTING.with(Cell::get) || FRESH.try_with(|f| f.borrow().contains(&id)) == Ok(true)
+}
+
+/// Drop the values set aside by [`defer_drop`].
+///
+/// This must only be called once the collection is no longer running.
+fn run_deferred_drops() {
+    let deferred = DEFERRED_DROPS.try_with(RefCell::take).unwrap_or_default();
+    drop(deferred);
+}
+
+/// The `dirty_index` of an allocation which is not in the list of dirty allocations of its heap.
+pub(super) const NOT_DIRTY: usize = usize::MAX;
+
+/// The heap which an allocation was made from: either a [`Heap`](super::Heap), or `None` for the
+/// thread's default heap in [`DUMPSTER`].
+pub(super) type HeapRef = Option<NonNull<Dumpster>>;
+
+/// Run `f` on the dumpster of `heap`.
+pub(super) fn with_heap<R>(heap: HeapRef, f: impl FnOnce(&Dumpster) -> R) -> R {
+    match heap {
+        // a `Heap` outlives every allocation made from it which has not been cleared
+        Some(dumpster) => f(unsafe { dumpster.as_ref() }),
+        None => DUMPSTER.with(f),
+    }
+}
+
+impl Dumpster {
+    /// Construct an empty dumpster which can track `capacity` dirty allocations without
+    /// reallocating.
+    fn with_capacity(capacity: usize) -> Dumpster {
+        Dumpster {
+            to_collect: RefCell::new(Vec::with_capacity(capacity)),
+            scratch_capacity: Cell::new(capacity),
+            scratch: RefCell::new(Scratch::default()),
+            n_ref_drops: Cell::new(0),
+            n_refs_living: Cell::new(0),
+            drop_ratio: Cell::new(ratio::INITIAL_RATIO),
+            n_allocations: Cell::new(0),
+            heap_bytes: Cell::new(0),
+            collect_condition: RefCell::new(CollectCondition::default()),
+            n_pauses: Cell::new(0),
+            mode: Cell::new(CollectionMode::Automatic),
+            collection_due: Cell::new(false),
+            full_interval: Cell::new(1),
+            n_partial: Cell::new(0),
+            stamp_live: Cell::new(false),
+            hooks: RefCell::new(None),
+            in_hook: Cell::new(false),
+            finalizing: Cell::new(false),
+            deferred: RefCell::new(Vec::new()),
+            deterministic: Cell::new(false),
+            next_serial: Cell::new(0),
+            #[cfg(any(
+                feature = "leak-detection",
+                feature = "heap-dump",
+                feature = "heap-inspection"
+            ))]
+            live: RefCell::new(AllocationMap::default()),
+            n_collections: Cell::new(0),
+            total_freed: Cell::new(0),
+            total_bytes_freed: Cell::new(0),
+            time_collecting: Cell::new(Duration::ZERO),
+            incremental: RefCell::new(None),
+            handle: Cell::new(None),
+            owned: RefCell::new(AllocationMap::default()),
+            ephemerons: RefCell::new(AllocationMap::default()),
+            finalizers: RefCell::new(AllocationMap::default()),
+            roots: RefCell::new(AllocationMap::default()),
+            serials: RefCell::new(AllocationMap::default()),
+        }
+    }
+
+    /// Construct the dumpster for a new [`Heap`](super::Heap), which must eventually be freed with
+    /// [`Box::from_raw`].
+    pub fn new_heap() -> NonNull<Dumpster> {
+        let dumpster = NonNull::from(Box::leak(Box::new(Dumpster::with_capacity(0))));
+        unsafe { dumpster.as_ref() }.handle.set(Some(dumpster));
+        dumpster
+    }
+}
+
+/// A function which destroys an allocation to which no `Gc`s remain.
+type DestroyFn = unsafe fn(&Dumpster, Erased);
+
 /// A dumpster is a collection of all the garbage that may or may not need to be cleaned up.
 /// It also contains information relevant to when a cleanup should be triggered.
 pub(super) struct Dumpster {
-    /// A map from allocation IDs for allocations which may need to be collected to pointers to
-    /// their allocations.
-    to_collect: RefCell<HashMap<AllocationId, Cleanup>>,
+    /// The allocations which may need to be collected, each of which records its position in this
+    /// list in its `dirty_index`, so that it is never listed twice and can be removed quickly.
+    to_collect: RefCell<Vec<(AllocationId, Cleanup)>>,
+    /// The number of allocations which the scratch structures of a collection are created to hold.
+    scratch_capacity: Cell<usize>,
+    /// The scratch structures left over from the last collection, cleared and ready to be reused.
+    scratch: RefCell<Scratch>,
     /// The number of times a reference has been dropped since the last collection was triggered.
     pub n_ref_drops: Cell<usize>,
     /// The number of references that currently exist in the entire heap and stack.
     pub n_refs_living: Cell<usize>,
+    /// The ratio of dropped to living references at which the default collect condition starts a
+    /// collection, which adapts to how much each collection frees.
+    pub drop_ratio: Cell<f64>,
+    /// The number of allocations that currently exist.
+    pub n_allocations: Cell<usize>,
+    /// The total size, in bytes, of all allocations that currently exist.
+    pub heap_bytes: Cell<usize>,
     /// The function for determining whether a collection should be run.
-    pub collect_condition: Cell<CollectCondition>,
+    pub collect_condition: RefCell<CollectCondition>,
+    /// The number of [`PauseGuard`](super::PauseGuard)s alive on this thread.
+    pub n_pauses: Cell<usize>,
+    /// When dropping a `Gc` may start a collection on this thread.
+    pub mode: Cell<CollectionMode>,
+    /// Whether the collect condition has fired since the last safe point, while collections were
+    /// only allowed at safe points or queued.
+    collection_due: Cell<bool>,
+    /// How often an automatic collection is a full one rather than a partial one: every
+    /// `full_interval`-th collection is full.
+    pub full_interval: Cell<usize>,
+    /// The number of partial collections run since the last full one.
+    n_partial: Cell<usize>,
+    /// Whether full collections stamp the allocations they find reachable, so that partial
+    /// collections can skip them.
+    /// This is turned on once partial collections are used, so that no other heap pays for it.
+    pub stamp_live: Cell<bool>,
+    /// The hooks to call around each collection.
+    pub hooks: RefCell<Option<Rc<CollectHooks>>>,
+    /// Whether a collection hook is currently running on this thread.
+    in_hook: Cell<bool>,
+    /// Whether a collection is currently running finalizers on this thread.
+    finalizing: Cell<bool>,
+    /// The allocations whose last `Gc` was dropped while finalizers were running, which must not be
+    /// destroyed until the finalizers are done.
+    /// Each is paired with the function which destroys it.
+    deferred: RefCell<Vec<(Erased, DestroyFn)>>,
+    /// Whether collections destroy garbage in the order in which it was allocated.
+    pub deterministic: Cell<bool>,
+    /// The serial number which will be given to the next allocation.
+    next_serial: Cell<u64>,
+    #[cfg(any(
+        feature = "leak-detection",
+        feature = "heap-dump",
+        feature = "heap-inspection"
+    ))]
+    /// Every allocation which currently exists on this thread.
+    live: RefCell<AllocationMap<LiveAllocation>>,
+    /// The number of collections finished since statistics were last reset.
+    pub n_collections: Cell<usize>,
+    /// The number of allocations freed by collections since statistics were last reset.
+    pub total_freed: Cell<usize>,
+    /// The number of bytes freed by collections since statistics were last reset.
+    pub total_bytes_freed: Cell<usize>,
+    /// The time spent collecting since statistics were last reset.
+    pub time_collecting: Cell<Duration>,
+    /// The state of the incremental collection in progress, if there is one.
+    incremental: RefCell<Option<Incremental>>,
+    /// The handle which allocations made from this dumpster refer to it by.
+    handle: Cell<HeapRef>,
+    /// Every allocation made from this dumpster, so that they can all be cleared at once.
+    /// This is only filled in for a [`Heap`](super::Heap).
+    owned: RefCell<AllocationMap<Owned>>,
+    /// The ephemerons keyed by each allocation made from this dumpster which is the key of any.
+    ephemerons: RefCell<AllocationMap<Vec<EphemeronRef>>>,
+    /// The finalizer of each allocation made from this dumpster which has one that has not been
+    /// run.
+    finalizers: RefCell<AllocationMap<Finalizer>>,
+    /// The number of [`Root`](super::Root)s to each allocation made from this dumpster which has
+    /// any.
+    roots: RefCell<AllocationMap<usize>>,
+    /// The serial number of each allocation made from this dumpster while collections were
+    /// deterministic, which orders its garbage for a deterministic collection.
+    serials: RefCell<AllocationMap<u64>>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 /// A unique identifier for an allocated garbage-collected block.
 ///
-/// It contains a pointer to the reference count of the allocation.
-struct AllocationId(pub NonNull<Cell<NonZeroUsize>>);
+/// It contains a pointer to the state of the allocation.
+pub(super) struct AllocationId(pub NonNull<Cell<usize>>);
+
+/// A table keyed by allocation, which hashes the address of each allocation instead of using the
+/// standard library's default hasher.
+pub(super) type AllocationMap<V> = HashMap<AllocationId, V, BuildPtrHasher>;
+
+/// A set of allocations, hashed by address like an [`AllocationMap`].
+pub(super) type AllocationSet = HashSet<AllocationId, BuildPtrHasher>;
 
 impl<T> From<NonNull<GcBox<T>>> for AllocationId
 where
@@ -76,7 +311,225 @@ where
     }
 }
 
-#[derive(Debug)]
+impl AllocationId {
+    /// Get the allocation with this ID, without regard for the type of its value.
+    ///
+    /// # Safety
+    ///
+    /// The allocation must not have been freed.
+    unsafe fn header<'a>(self) -> &'a GcBox<()> {
+        self.0.cast().as_ref()
+    }
+}
+
+#[cfg(any(
+    feature = "leak-detection",
+    feature = "heap-dump",
+    feature = "heap-inspection"
+))]
+#[derive(Clone, Copy)]
+/// The information recorded about every allocation for diagnostics.
+struct LiveAllocation {
+    #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+    /// The name of the type stored in the allocation.
+    type_name: &'static str,
+    #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+    /// The size of the allocation, in bytes.
+    size: usize,
+    #[cfg(any(feature = "heap-dump", feature = "heap-inspection"))]
+    /// An erased pointer to the allocation.
+    ptr: Erased,
+    #[cfg(feature = "heap-dump")]
+    /// The function which describes the allocation for a heap dump.
+    describe_fn: unsafe fn(Erased) -> DotNode,
+    #[cfg(feature = "heap-inspection")]
+    /// The function which describes the allocation for `iter_allocations`.
+    inspect_fn: unsafe fn(Erased) -> AllocationInfo,
+}
+
+#[derive(Clone, Copy)]
+/// An [`Ephemeron`](super::Ephemeron) whose key is still alive, as seen by the collector.
+struct EphemeronRef {
+    /// An erased pointer to the state of the ephemeron.
+    ptr: Erased,
+    /// The function which visits the value of the ephemeron to build the reference graph.
+    dfs_fn: unsafe fn(Erased, &mut Dfs),
+    /// The function which visits the value of the ephemeron to mark what it holds as reachable.
+    mark_fn: unsafe fn(Erased, &mut Mark),
+    /// The function which visits and then drops the value of the ephemeron when its key is
+    /// collected.
+    drop_fn: unsafe fn(Erased, &mut DropAlloc<'_>),
+    /// The function which visits the value of the ephemeron in an incremental collection.
+    step_fn: unsafe fn(Erased, &mut Step<'_>),
+    /// The function which counts the references in the value of the ephemeron.
+    count_fn: unsafe fn(Erased, &mut InternalRefs),
+    /// The function which marks the key of the ephemeron as freed and drops its value.
+    kill_fn: unsafe fn(Erased),
+}
+
+impl EphemeronRef {
+    /// Construct a reference to the ephemeron behind `inner`.
+    fn new<K: Collectable + ?Sized + 'static, V: Collectable + 'static>(
+        inner: NonNull<EphemeronInner<K, V>>,
+    ) -> EphemeronRef {
+        EphemeronRef {
+            ptr: Erased::new(inner),
+            dfs_fn: visit_ephemeron::<K, V, Dfs>,
+            mark_fn: visit_ephemeron::<K, V, Mark>,
+            drop_fn: drop_ephemeron::<K, V>,
+            step_fn: step_ephemeron::<K, V>,
+            count_fn: visit_ephemeron::<K, V, InternalRefs>,
+            kill_fn: kill_ephemeron::<K, V>,
+        }
+    }
+}
+
+/// A visitor used by the collector, which also finds the references held by ephemerons through
+/// their keys.
+trait CollectVisitor: Visitor + Sized {
+    /// Get the function which visits the value of `ephemeron` with this visitor.
+    fn ephemeron_fn(ephemeron: &EphemeronRef) -> unsafe fn(Erased, &mut Self);
+
+    /// Note that the value of the allocation behind `ptr` could not be traced by this visitor.
+    fn untraced<T: Collectable + ?Sized>(&mut self, ptr: NonNull<GcBox<T>>) {
+        let _ = ptr;
+        trace_error(std::any::type_name::<T>());
+    }
+}
+
+impl CollectVisitor for Dfs {
+    fn ephemeron_fn(ephemeron: &EphemeronRef) -> unsafe fn(Erased, &mut Self) {
+        ephemeron.dfs_fn
+    }
+
+    fn untraced<T: Collectable + ?Sized>(&mut self, ptr: NonNull<GcBox<T>>) {
+        trace_error(std::any::type_name::<T>());
+        self.untraced
+            .push((AllocationId::from(ptr), Cleanup::new(ptr)));
+    }
+}
+
+impl CollectVisitor for Mark {
+    fn ephemeron_fn(ephemeron: &EphemeronRef) -> unsafe fn(Erased, &mut Self) {
+        ephemeron.mark_fn
+    }
+}
+
+impl CollectVisitor for DropAlloc<'_> {
+    fn ephemeron_fn(ephemeron: &EphemeronRef) -> unsafe fn(Erased, &mut Self) {
+        ephemeron.drop_fn
+    }
+}
+
+impl CollectVisitor for Step<'_> {
+    fn ephemeron_fn(ephemeron: &EphemeronRef) -> unsafe fn(Erased, &mut Self) {
+        ephemeron.step_fn
+    }
+}
+
+impl CollectVisitor for InternalRefs {
+    fn ephemeron_fn(ephemeron: &EphemeronRef) -> unsafe fn(Erased, &mut Self) {
+        ephemeron.count_fn
+    }
+}
+
+/// Apply `visitor` to the value of an allocation, and then to the values of the ephemerons keyed by
+/// it, which are only reachable through it.
+///
+/// # Safety
+///
+/// `box_ref` must belong to a live allocation.
+unsafe fn accept_contents<T: Collectable + ?Sized, V: CollectVisitor>(
+    box_ref: &GcBox<T>,
+    visitor: &mut V,
+) -> Result<(), ()> {
+    let result = box_ref.value.accept(visitor);
+    if box_ref.has_flag(EPHEMERON_KEY) {
+        let id = AllocationId::from(NonNull::from(box_ref));
+        let ephemerons = with_heap(box_ref.heap, |d| {
+            d.ephemerons.borrow().get(&id).cloned().unwrap_or_default()
+        });
+        for ephemeron in ephemerons {
+            V::ephemeron_fn(&ephemeron)(ephemeron.ptr, visitor);
+        }
+    }
+    result
+}
+
+/// Apply a visitor to the value of an ephemeron, if it is not currently borrowed.
+///
+/// # Safety
+///
+/// `K` and `V` must be the same types that `ptr` was created with via [`ErasedPtr::new`].
+unsafe fn visit_ephemeron<K: Collectable + ?Sized + 'static, V: Collectable, Vis: Visitor>(
+    ptr: Erased,
+    visitor: &mut Vis,
+) {
+    let inner = ptr.specify::<EphemeronInner<K, V>>().as_ref();
+    if let Ok(value) = inner.value.try_borrow() {
+        if let Some(value) = &*value {
+            let _ = value.accept(visitor);
+        }
+    }
+}
+
+/// Apply a step of an incremental collection to the value of an ephemeron.
+///
+/// # Safety
+///
+/// `K` and `V` must be the same types that `ptr` was created with via [`ErasedPtr::new`].
+unsafe fn step_ephemeron<K: Collectable + ?Sized + 'static, V: Collectable>(
+    ptr: Erased,
+    step: &mut Step<'_>,
+) {
+    visit_ephemeron::<K, V, _>(ptr, step);
+}
+
+/// Release the references held by the value of an ephemeron whose key is being freed by a
+/// collection, and then drop the value.
+///
+/// # Safety
+///
+/// `K` and `V` must be the same types that `ptr` was created with via [`ErasedPtr::new`], and the
+/// collection must be running.
+unsafe fn drop_ephemeron<K: Collectable + ?Sized + 'static, V: Collectable>(
+    ptr: Erased,
+    visitor: &mut DropAlloc<'_>,
+) {
+    visit_ephemeron::<K, V, _>(ptr, visitor);
+    let inner = ptr.specify::<EphemeronInner<K, V>>().as_ref();
+    // the `Gc`s in the value have been accounted for, so dropping them while collecting does
+    // nothing more
+    drop(inner.value.take());
+}
+
+/// Mark the key of an ephemeron as freed, and drop its value.
+///
+/// # Safety
+///
+/// `K` and `V` must be the same types that `ptr` was created with via [`ErasedPtr::new`], and the
+/// ephemeron must not be registered with any dumpster.
+unsafe fn kill_ephemeron<K: Collectable + ?Sized + 'static, V>(ptr: Erased) {
+    let inner = ptr.specify::<EphemeronInner<K, V>>().as_ref();
+    inner.key.set(None);
+    drop(inner.value.take());
+}
+
+#[derive(Clone, Copy)]
+/// The information needed to clear an allocation made from a [`Heap`](super::Heap).
+struct Owned {
+    /// An erased pointer to the allocation.
+    ptr: Erased,
+    /// The size of the allocation, in bytes.
+    size: usize,
+    /// The function which drops the value in the allocation when its heap is cleared.
+    clear_fn: unsafe fn(Erased),
+    #[cfg(debug_assertions)]
+    /// The function which counts the references out of the allocation into the same heap.
+    count_fn: unsafe fn(Erased, &mut InternalRefs),
+}
+
+#[derive(Clone, Copy, Debug)]
 /// The necessary information required to collect some garbage-collected data.
 /// This data is stored in a map from allocation IDs to the necessary cleanup operation.
 struct Cleanup {
@@ -87,6 +540,9 @@ struct Cleanup {
     mark_fn: unsafe fn(Erased, &mut Mark),
     /// A function used for dropping the allocation.
     drop_fn: unsafe fn(Erased, &mut DropAlloc<'_>),
+    /// The function which is called to perform one unit of work on this allocation in an
+    /// incremental collection.
+    step_fn: unsafe fn(Erased, &mut Step<'_>) -> Result<(), ()>,
     /// An erased pointer to the allocation.
     ptr: Erased,
 }
@@ -98,95 +554,703 @@ impl Cleanup {
             dfs_fn: apply_visitor::<T, Dfs>,
             mark_fn: apply_visitor::<T, Mark>,
             drop_fn: drop_assist::<T>,
+            step_fn: step_assist::<T>,
             ptr: Erased::new(box_ptr),
         }
     }
 }
 
+#[cfg(feature = "heap-dump")]
+/// Describe the allocation behind an erased pointer for a heap dump, without modifying it.
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`].
+unsafe fn describe<T: Collectable + ?Sized>(ptr: Erased) -> DotNode {
+    let box_ref = ptr.specify::<GcBox<T>>().as_ref();
+    let mut finder = EdgeFinder::default();
+    // if the value can't be inspected right now, its edges are simply left out
+    let _ = box_ref.value.accept(&mut finder);
+    DotNode {
+        addr: value_addr(&box_ref.value),
+        type_name: std::any::type_name::<T>(),
+        size: Layout::for_value(box_ref).size(),
+        ref_count: box_ref.ref_count(),
+        edges: finder.edges,
+    }
+}
+
+#[cfg(feature = "heap-inspection")]
+/// Describe the allocation behind an erased pointer for
+/// [`iter_allocations`](super::iter_allocations).
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`].
+unsafe fn inspect<T: Collectable + ?Sized>(ptr: Erased) -> AllocationInfo {
+    let box_ref = ptr.specify::<GcBox<T>>().as_ref();
+    AllocationInfo {
+        addr: std::ptr::from_ref(&box_ref.value).cast::<()>() as usize,
+        type_name: std::any::type_name::<T>(),
+        size: Layout::for_value(box_ref).size(),
+        ref_count: box_ref.ref_count(),
+    }
+}
+
+/// Describe the allocation behind an erased pointer for a [`DryRunReport`].
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`].
+unsafe fn describe_garbage<T: Collectable + ?Sized>(ptr: Erased) -> GarbageAllocation {
+    let box_ref = ptr.specify::<GcBox<T>>().as_ref();
+    GarbageAllocation {
+        addr: std::ptr::from_ref(&box_ref.value).cast::<()>() as usize,
+        type_name: std::any::type_name::<T>(),
+        size: Layout::for_value(box_ref).size(),
+    }
+}
+
+/// Destroy an allocation to which no `Gc`s remain, as in [`destroy`].
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`], and `ptr` must
+/// satisfy the requirements of [`destroy`].
+unsafe fn destroy_erased<T: Collectable + ?Sized>(dumpster: &Dumpster, ptr: Erased) {
+    destroy(dumpster, ptr.specify::<GcBox<T>>());
+}
+
+/// Drop the value in an allocation whose heap is being cleared, and then give up the reference
+/// which the clearing holds to it.
+///
+/// If dropping the value panics under [`PanicPolicy::LeakAllocation`](crate::PanicPolicy), the
+/// allocation is leaked instead.
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`], and the allocation
+/// must have been cleared by [`Dumpster::clear`].
+unsafe fn clear_erased<T: Collectable + ?Sized>(ptr: Erased) {
+    let ptr = ptr.specify::<GcBox<T>>();
+    if drop_collected(addr_of_mut!((*ptr.as_ptr()).value)) {
+        release_cleared(ptr);
+    }
+}
+
 /// Apply a visitor to some erased pointer.
 ///
 /// # Safety
 ///
 /// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`].
-unsafe fn apply_visitor<T: Collectable + ?Sized, V: Visitor>(ptr: Erased, visitor: &mut V) {
+unsafe fn apply_visitor<T: Collectable + ?Sized, V: CollectVisitor>(ptr: Erased, visitor: &mut V) {
     let specified: NonNull<GcBox<T>> = ptr.specify();
-    let _ = specified.as_ref().value.accept(visitor);
+    if specified.as_ref().is_rooted() {
+        // a rooted allocation is known to be reachable, so there is nothing to find inside it
+        return;
+    }
+    if accept_contents(specified.as_ref(), visitor).is_err() {
+        visitor.untraced(specified);
+    }
 }
 
 impl Dumpster {
-    /// Collect all unreachable allocations that this dumpster is responsible for.
-    pub fn collect_all(&self) {
+    /// Collect all unreachable allocations that this dumpster is responsible for, calling the
+    /// collection hooks before and after.
+    ///
+    /// If this is called from inside a hook, or from a value dropped by a collection, nothing is
+    /// collected.
+    pub fn collect_all(&self) -> CollectResult {
+        self.run_collection(false)
+    }
+
+    /// Collect the unreachable allocations which can be found from the dirty allocations without
+    /// searching inside any allocation which the last full collection found to be reachable,
+    /// calling the collection hooks before and after.
+    ///
+    /// If this is called from inside a hook, or from a value dropped by a collection, nothing is
+    /// collected.
+    pub fn collect_partial(&self) -> CollectResult {
+        self.stamp_live.set(true);
+        self.run_collection(true)
+    }
+
+    /// Run an automatic collection, which is partial unless enough partial collections have run
+    /// since the last full one.
+    fn collect_scheduled(&self) -> CollectResult {
+        let partial = self.n_partial.get() + 1 < self.full_interval.get();
+        self.run_collection(partial)
+    }
+
+    /// Run a collection, either `partial` or full, calling the collection hooks before and after.
+    ///
+    /// If this is called from inside a hook, or from a value dropped by a collection, nothing is
+    /// collected.
+    fn run_collection(&self, partial: bool) -> CollectResult {
+        // a value dropped by a collection may make and drop `Gc`s of its own, which must not start
+        // another collection while this one is still running
+        if self.in_hook.get() || COLLECTING.with(Cell::get) {
+            return CollectResult::default();
+        }
+        // this collection does the work of any which was put off until a safe point
+        self.collection_due.set(false);
+        let span = CollectSpan::enter("unsync");
+        let hooks = self.hooks.borrow().clone();
+        if let Some(on_start) = hooks.as_ref().and_then(|h| h.on_start.as_ref()) {
+            let _guard = HookGuard::new(&self.in_hook);
+            on_start(&CollectInfo {
+                heap: self.handle.get(),
+            });
+        }
+        let result = self.collect_unhooked(partial);
+        if let Some(on_end) = hooks.as_ref().and_then(|h| h.on_end.as_ref()) {
+            let _guard = HookGuard::new(&self.in_hook);
+            on_end(&result);
+        }
+        span.finish(
+            result.allocations_examined,
+            result.allocations_freed,
+            result.bytes_freed,
+            result.duration,
+        );
+        resume_caught();
+        result
+    }
+
+    /// Collect the unreachable allocations that this dumpster is responsible for, without calling
+    /// any hooks.
+    ///
+    /// A `partial` collection treats every clean allocation which the last full collection found
+    /// to be reachable as still reachable, and so does not search inside it.
+    /// Returns a summary of the work done by the collection.
+    fn collect_unhooked(&self, partial: bool) -> CollectResult {
+        let start = Instant::now();
         self.n_ref_drops.set(0);
+        self.n_partial
+            .set(if partial { self.n_partial.get() + 1 } else { 0 });
+        // a full collection may free allocations which an incremental collection has already
+        // found, so any incremental progress must be thrown away
+        self.incremental.borrow_mut().take();
 
         unsafe {
-            let mut dfs = Dfs {
-                visited: HashSet::with_capacity(self.to_collect.borrow().len()),
-                ref_graph: HashMap::with_capacity(self.to_collect.borrow().len()),
+            let mut allocations_examined = 0;
+            let analysis = loop {
+                let analysis = self.analyze(partial);
+                allocations_examined += analysis.dfs.visited.len();
+                // finalizers may resurrect the allocations they can see, so the heap must be
+                // checked again before anything can be destroyed
+                if !self.finalize(analysis.garbage().map(|(id, _)| id)) {
+                    break analysis;
+                }
+                self.recycle_scratch(analysis.into_scratch());
             };
+            if !partial && self.stamp_live.get() {
+                // destroying the garbage takes the stamp back from anything it refers to
+                for id in &analysis.mark.visited {
+                    id.header().set_flag(KNOWN_LIVE, true);
+                }
+            }
+            let (allocations_freed, bytes_freed, dropped) = self.destroy_garbage(analysis);
 
-            for (k, v) in &*self.to_collect.borrow() {
-                if dfs.visited.insert(*k) {
-                    (v.dfs_fn)(v.ptr, &mut dfs);
+            let duration = start.elapsed();
+            self.n_collections.set(self.n_collections.get() + 1);
+            self.time_collecting
+                .set(self.time_collecting.get() + duration);
+            self.drop_ratio.set(ratio::adapt(
+                self.drop_ratio.get(),
+                allocations_examined,
+                allocations_freed,
+            ));
+            if let Err(payload) = dropped {
+                resume_unwind(payload);
+            }
+            CollectResult {
+                allocations_examined,
+                allocations_freed,
+                bytes_freed,
+                duration,
+            }
+        }
+    }
+
+    /// Find the allocations which a collection would free, without freeing them or running any
+    /// finalizers.
+    pub fn collect_dry_run(&self) -> DryRunReport {
+        let analysis = unsafe { self.analyze(false) };
+        let allocations = analysis
+            .garbage()
+            .map(|(_, reachability)| unsafe { (reachability.describe_fn)(reachability.ptr) })
+            .collect();
+        self.recycle_scratch(analysis.into_scratch());
+        DryRunReport { allocations }
+    }
+
+    /// Free every allocation which `analysis` found to be garbage, and forget about every dirty
+    /// allocation.
+    ///
+    /// If the analysis was partial and skipped any allocation, the dirty allocations which it
+    /// found to be reachable may only be reachable through garbage it could not see, so they stay
+    /// dirty, along with every surviving allocation which lost a reference to the garbage.
+    /// Every allocation which the analysis could not trace stays dirty too.
+    ///
+    /// Returns the number of allocations and of bytes freed, along with the panic raised while
+    /// dropping the garbage, if there was one.
+    ///
+    /// # Safety
+    ///
+    /// The heap must not have changed since `analysis` was made.
+    unsafe fn destroy_garbage(&self, analysis: Analysis) -> (usize, usize, thread::Result<()>) {
+        let Analysis { dfs, mark } = analysis;
+        let Dfs {
+            mut visited,
+            ref_graph,
+            partial,
+            pruned,
+            untraced,
+            ..
+        } = dfs;
+        // the visited set of the search is reused to track the garbage which has been dropped
+        visited.clear();
+        let mut decrementer = DropAlloc {
+            visited,
+            // a partial analysis may reach past its graph through an allocation it skipped, and
+            // nothing outside of the graph is known to be garbage
+            doomed: if partial {
+                Doomed::Unmarked(&ref_graph, &mark.visited)
+            } else {
+                Doomed::AllBut(&mark.visited)
+            },
+            heap: self.handle.get(),
+            dumpster: self,
+            survivors: Vec::new(),
+            orphans: Vec::new(),
+            foreign: Vec::new(),
+            pending: self.deterministic.get().then(Vec::new),
+            n_freed: 0,
+            bytes_freed: 0,
+        };
+
+        let mut kept = Vec::new();
+
+        set_collecting(true);
+        // if a value panics while it is dropped, the bookkeeping must still be finished before the
+        // panic resumes
+        // the dirty allocations are taken out of the dumpster, since the values which are dropped
+        // may dirty the allocations they make
+        let mut to_collect = take(&mut *self.to_collect.borrow_mut());
+        let dropped = catch_unwind(AssertUnwindSafe(|| {
+            // the positions must be forgotten before any of the garbage is freed
+            for (id, _) in &to_collect {
+                id.header().dirty_index.set(NOT_DIRTY);
+            }
+            for (id, cleanup) in to_collect.drain(..) {
+                if decrementer.is_doomed(id) {
+                    (cleanup.drop_fn)(cleanup.ptr, &mut decrementer);
+                } else if !pruned.is_empty() {
+                    kept.push((id, cleanup));
                 }
             }
+            decrementer.free_pending();
+        }));
+        set_collecting(false);
+        // the list is emptied as it is drained, so the allocations dirtied meanwhile keep their
+        // positions when they are moved back into it
+        to_collect.append(&mut self.to_collect.borrow_mut());
+        *self.to_collect.borrow_mut() = to_collect;
+        self.account_freed(&decrementer);
+        // an allocation which was not known to be garbage is freed when the garbage held its last
+        // reference, so only the ones which are still live are dirtied again
+        // the allocations which could not be traced are dirtied as well, so that the next
+        // collection tries them again
+        for (id, cleanup) in kept
+            .into_iter()
+            .chain(untraced)
+            .chain(decrementer.survivors.drain(..))
+        {
+            if !decrementer.visited.contains(&id) {
+                self.add_dirty(id, cleanup);
+            }
+        }
+        run_deferred_drops();
+        let DropAlloc {
+            visited,
+            foreign,
+            n_freed,
+            bytes_freed,
+            ..
+        } = decrementer;
+        release_foreign(foreign);
+        self.recycle_scratch(Scratch {
+            visited,
+            ref_graph,
+            marked: mark.visited,
+            ..Scratch::default()
+        });
+        (n_freed, bytes_freed, dropped)
+    }
+
+    /// Find every allocation reachable from the allocations in `to_collect`, and then which of
+    /// them are reachable from outside of the garbage-collected heap.
+    ///
+    /// A `partial` analysis does not search inside any allocation stamped as reachable by the last
+    /// full collection, and instead marks it as reachable.
+    ///
+    /// The structures of the analysis are taken from the scratch kept by this dumpster, and should
+    /// be handed back with [`Dumpster::recycle_scratch`] once the analysis is done with.
+    unsafe fn analyze(&self, partial: bool) -> Analysis {
+        let capacity = self
+            .to_collect
+            .borrow()
+            .len()
+            .max(self.scratch_capacity.get());
+        // if an analysis is already underway, as when a finalizer asks for a dry run, its scratch
+        // is missing and this one starts from empty structures instead
+        let mut scratch = self.scratch.take();
+        scratch.visited.reserve(capacity);
+        scratch.ref_graph.reserve(capacity);
+        scratch.marked.reserve(capacity);
+        let mut dfs = Dfs {
+            heap: self.handle.get(),
+            visited: scratch.visited,
+            ref_graph: scratch.ref_graph,
+            partial,
+            pruned: Vec::new(),
+            untraced: Vec::new(),
+        };
+
+        for (k, v) in self.to_collect.borrow().iter() {
+            if dfs.visited.insert(*k) {
+                (v.dfs_fn)(v.ptr, &mut dfs);
+            }
+        }
 
-            let mut mark = Mark {
-                visited: HashSet::with_capacity(dfs.visited.len()),
-            };
-            for (id, reachability) in dfs
-                .ref_graph
-                .iter()
-                .filter(|(_, reachability)| reachability.n_unaccounted != 0)
-            {
-                mark.visited.insert(*id);
+        let mut mark = Mark {
+            heap: dfs.heap,
+            visited: scratch.marked,
+            partial,
+        };
+        // the allocations a partial search skipped are reachable, but were not searched, so they
+        // are not searched here either
+        mark.visited.extend(dfs.pruned.iter().copied());
+        for (id, reachability) in dfs
+            .ref_graph
+            .iter()
+            .filter(|(_, reachability)| reachability.n_unaccounted != 0)
+        {
+            if mark.visited.insert(*id) {
                 (reachability.mark_fn)(reachability.ptr, &mut mark);
             }
+        }
 
-            // any allocations which we didn't find must also be roots
-            for (id, cleanup) in self
-                .to_collect
-                .borrow()
-                .iter()
-                .filter(|(id, _)| !dfs.ref_graph.contains_key(id))
-            {
-                mark.visited.insert(*id);
+        // an allocation which could not be traced may hold references which were never counted, so
+        // it is assumed to be reachable, along with whatever it could be seen to refer to
+        for (id, cleanup) in &dfs.untraced {
+            if mark.visited.insert(*id) {
                 (cleanup.mark_fn)(cleanup.ptr, &mut mark);
             }
+        }
 
-            dfs.visited.clear();
-            let mut decrementer = DropAlloc {
-                visited: dfs.visited,
-                reachable: &mark.visited,
-            };
+        // any allocations which we didn't find must also be roots
+        for (id, cleanup) in self
+            .to_collect
+            .borrow()
+            .iter()
+            .filter(|(id, _)| !dfs.ref_graph.contains_key(id))
+        {
+            mark.visited.insert(*id);
+            (cleanup.mark_fn)(cleanup.ptr, &mut mark);
+        }
 
-            COLLECTING.with(|c| c.set(true));
-            for cleanup in self
-                .to_collect
-                .borrow_mut()
-                .drain()
-                .filter_map(|(id, cleanup)| (!mark.visited.contains(&id)).then_some(cleanup))
-            {
-                (cleanup.drop_fn)(cleanup.ptr, &mut decrementer);
+        Analysis { dfs, mark }
+    }
+
+    /// Clear the structures of a finished analysis and keep them for the next one.
+    ///
+    /// Every [`SHRINK_INTERVAL`] collections, structures which have grown much larger than any of
+    /// those collections needed are shrunk back down.
+    fn recycle_scratch(&self, mut scratch: Scratch) {
+        // every allocation in the graph or marked as reachable was visited first
+        let used = scratch.visited.len().max(scratch.ref_graph.len());
+        scratch.visited.clear();
+        scratch.ref_graph.clear();
+        scratch.marked.clear();
+
+        let mut kept = self.scratch.borrow_mut();
+        // when analyses are nested, the larger of their structures is the one worth keeping
+        if kept.visited.capacity() > scratch.visited.capacity() {
+            return;
+        }
+        scratch.high_water = kept.high_water.max(used);
+        scratch.n_uses = kept.n_uses + 1;
+        if scratch.n_uses >= SHRINK_INTERVAL {
+            let target = (scratch.high_water * 2).max(self.scratch_capacity.get());
+            scratch.visited.shrink_to(target);
+            scratch.ref_graph.shrink_to(target);
+            scratch.marked.shrink_to(target);
+            scratch.high_water = 0;
+            scratch.n_uses = 0;
+        }
+        *kept = scratch;
+    }
+
+    /// Run the finalizers of the allocations in `ids` which have not yet been run.
+    ///
+    /// No collections are run, and no allocations are freed, until all of the finalizers are done.
+    /// Returns whether any finalizer was run.
+    ///
+    /// # Safety
+    ///
+    /// Every allocation in `ids` must be live.
+    unsafe fn finalize<'a>(&self, ids: impl IntoIterator<Item = &'a AllocationId>) -> bool {
+        let mut ran = false;
+        {
+            let _finalizing = HookGuard::new(&self.finalizing);
+            let _in_hook = HookGuard::new(&self.in_hook);
+            if self.deterministic.get() {
+                let mut ids = ids.into_iter().copied().collect::<Vec<_>>();
+                ids.sort_unstable_by_key(|&id| self.serial(id));
+                for id in ids {
+                    ran |= self.finalize_one(id);
+                }
+            } else {
+                for &id in ids {
+                    ran |= self.finalize_one(id);
+                }
             }
-            COLLECTING.with(|c| c.set(false));
+        }
+        let deferred = self.deferred.take();
+        for (ptr, destroy_fn) in deferred {
+            destroy_fn(self, ptr);
+        }
+        ran
+    }
+
+    /// Destroy an allocation whose last `Gc` has just been dropped.
+    ///
+    /// If finalizers are running, the allocation may be one which is waiting to be finalized, so
+    /// it is instead destroyed once they are done.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live allocation, and no `Gc`s may point to it.
+    pub unsafe fn destroy_unreferenced<T: Collectable + ?Sized>(&self, ptr: NonNull<GcBox<T>>) {
+        if self.finalizing.get() {
+            self.deferred
+                .borrow_mut()
+                .push((Erased::new(ptr), destroy_erased::<T>));
+        } else {
+            destroy(self, ptr);
+        }
+    }
+
+    /// Update the bookkeeping for the allocations which `decrementer` has freed.
+    fn account_freed(&self, decrementer: &DropAlloc<'_>) {
+        #[cfg(any(
+            feature = "leak-detection",
+            feature = "heap-dump",
+            feature = "heap-inspection"
+        ))]
+        self.forget_live(&decrementer.visited);
+        self.forget_owned(&decrementer.visited);
+        self.n_allocations
+            .set(self.n_allocations.get() - decrementer.n_freed);
+        self.heap_bytes
+            .set(self.heap_bytes.get() - decrementer.bytes_freed);
+        self.record_freed(decrementer.n_freed, decrementer.bytes_freed);
+    }
+
+    /// Add `n_freed` allocations totalling `bytes_freed` bytes to the statistics for freed
+    /// allocations.
+    fn record_freed(&self, n_freed: usize, bytes_freed: usize) {
+        self.total_freed.set(self.total_freed.get() + n_freed);
+        self.total_bytes_freed
+            .set(self.total_bytes_freed.get() + bytes_freed);
+    }
+
+    /// Make room for at least `additional` more dirty allocations, and for collections to visit
+    /// `additional` more allocations than currently exist, without reallocating.
+    pub fn reserve(&self, additional: usize) {
+        self.to_collect.borrow_mut().reserve(additional);
+        self.scratch_capacity.set(
+            self.scratch_capacity
+                .get()
+                .max(self.n_allocations.get() + additional),
+        );
+    }
+
+    /// Get the serial number for a new allocation.
+    fn next_serial(&self) -> u64 {
+        let serial = self.next_serial.get();
+        self.next_serial.set(serial + 1);
+        serial
+    }
+
+    /// Record the entries of the new allocation `id` in the side tables of this dumpster: its
+    /// finalizer, if it has one, and its serial number, if collections are deterministic.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be a live allocation made from this dumpster.
+    pub unsafe fn register_side_entries(&self, id: AllocationId, finalizer: Option<Finalizer>) {
+        let header = id.header();
+        if let Some(finalizer) = finalizer {
+            self.finalizers.borrow_mut().insert(id, finalizer);
+            header.set_flag(FINALIZER, true);
+        }
+        if self.deterministic.get() {
+            let serial = self.next_serial();
+            self.serials.borrow_mut().insert(id, serial);
+            header.set_flag(SERIAL, true);
         }
     }
 
+    /// Remove every entry of the allocation `id`, which is about to die, from the side tables of
+    /// this dumpster, and return its serial number if it had one.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be a live allocation made from this dumpster.
+    pub unsafe fn forget_side_entries(&self, id: AllocationId) -> Option<u64> {
+        let header = id.header();
+        if header.has_flag(FINALIZER) {
+            self.finalizers.borrow_mut().remove(&id);
+        }
+        if header.has_flag(ROOTED) {
+            self.roots.borrow_mut().remove(&id);
+        }
+        let serial = if header.has_flag(SERIAL) {
+            self.serials.borrow_mut().remove(&id)
+        } else {
+            None
+        };
+        header.set_flag(FINALIZER | ROOTED | SERIAL, false);
+        serial
+    }
+
+    /// Get the serial number of the allocation `id`, if it was given one.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be a live allocation made from this dumpster.
+    unsafe fn serial(&self, id: AllocationId) -> Option<u64> {
+        if id.header().has_flag(SERIAL) {
+            self.serials.borrow().get(&id).copied()
+        } else {
+            None
+        }
+    }
+
+    /// Run the finalizer of the allocation `id`, unless it has none or it has already been run.
+    ///
+    /// Returns whether the finalizer was run.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be a live allocation made from this dumpster.
+    pub unsafe fn finalize_one(&self, id: AllocationId) -> bool {
+        let header = id.header();
+        if !header.has_flag(FINALIZER) {
+            return false;
+        }
+        header.set_flag(FINALIZER, false);
+        // the finalizer may make allocations of its own, so the table must not stay borrowed
+        let Some(finalizer) = self.finalizers.borrow_mut().remove(&id) else {
+            return false;
+        };
+        finalizer(id.0.cast());
+        true
+    }
+
+    /// Count one more [`Root`](super::Root) to the allocation `id`.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be a live allocation made from this dumpster.
+    pub unsafe fn add_root(&self, id: AllocationId) {
+        *self.roots.borrow_mut().entry(id).or_insert(0) += 1;
+        id.header().set_flag(ROOTED, true);
+    }
+
+    /// Count one fewer [`Root`](super::Root) to the allocation `id`.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be a live allocation made from this dumpster, with at least one root.
+    pub unsafe fn remove_root(&self, id: AllocationId) {
+        let mut roots = self.roots.borrow_mut();
+        let Entry::Occupied(mut entry) = roots.entry(id) else {
+            return;
+        };
+        *entry.get_mut() -= 1;
+        if *entry.get() == 0 {
+            entry.remove();
+            id.header().set_flag(ROOTED, false);
+        }
+    }
+
+    /// Get the number of dirty allocations which can be tracked without reallocating.
+    pub fn dirty_capacity(&self) -> usize {
+        self.to_collect.borrow().capacity()
+    }
+
     /// Mark an allocation as "dirty," implying that it may need to be swept through later to find
     /// out if it has any references pointing to it.
     pub fn mark_dirty<T: Collectable + ?Sized>(&self, box_ptr: NonNull<GcBox<T>>) {
-        self.to_collect
-            .borrow_mut()
-            .entry(AllocationId::from(box_ptr))
-            .or_insert_with(|| Cleanup::new(box_ptr));
+        unsafe { self.add_dirty(AllocationId::from(box_ptr), Cleanup::new(box_ptr)) };
+    }
+
+    /// Add the allocation `id`, whose cleanup is `cleanup`, to the dirty allocations, unless it is
+    /// already one of them.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be live.
+    unsafe fn add_dirty(&self, id: AllocationId, cleanup: Cleanup) {
+        // a dirty allocation is always searched
+        id.header().set_flag(KNOWN_LIVE, false);
+        let dirty_index = &id.header().dirty_index;
+        if dirty_index.get() == NOT_DIRTY {
+            let mut to_collect = self.to_collect.borrow_mut();
+            dirty_index.set(to_collect.len());
+            to_collect.push((id, cleanup));
+        }
     }
 
     /// Mark an allocation as "cleaned," implying that the allocation is about to be destroyed and
     /// therefore should not be cleaned up later.
     pub fn mark_cleaned<T: Collectable + ?Sized>(&self, box_ptr: NonNull<GcBox<T>>) {
-        self.to_collect
-            .borrow_mut()
-            .remove(&AllocationId::from(box_ptr));
+        let id = AllocationId::from(box_ptr);
+        let dirty_index = unsafe { &box_ptr.as_ref().dirty_index };
+        if dirty_index.get() != NOT_DIRTY {
+            let mut to_collect = self.to_collect.borrow_mut();
+            to_collect.swap_remove(dirty_index.get());
+            if let Some((moved, _)) = to_collect.get(dirty_index.get()) {
+                unsafe { moved.header() }.dirty_index.set(dirty_index.get());
+            }
+            dirty_index.set(NOT_DIRTY);
+        }
+        if let Some(incremental) = &mut *self.incremental.borrow_mut() {
+            incremental.forget(id);
+        }
+    }
+
+    /// Stop tracking every dirty allocation for which `f` returns `true`.
+    ///
+    /// # Safety
+    ///
+    /// Every dirty allocation must be live.
+    unsafe fn forget_dirty(&self, mut f: impl FnMut(&AllocationId) -> bool) {
+        let mut to_collect = self.to_collect.borrow_mut();
+        to_collect.retain(|(id, _)| {
+            let forget = f(id);
+            if forget {
+                id.header().dirty_index.set(NOT_DIRTY);
+            }
+            !forget
+        });
+        for (i, (id, _)) in to_collect.iter().enumerate() {
+            id.header().dirty_index.set(i);
+        }
     }
 
     /// Notify the dumpster that a garbage-collected pointer has been dropped.
@@ -201,33 +1265,412 @@ impl Dumpster {
         );
         self.n_refs_living.set(old_refs_living - 1);
 
+        if self.n_pauses.get() == 0 {
+            self.maybe_collect();
+        }
+    }
+
+    /// Run a collection if the collect condition says so.
+    ///
+    /// If collections may only run at safe points, or are queued, the collection is instead put
+    /// off until the next call to [`Dumpster::safe_point`].
+    pub fn maybe_collect(&self) {
+        let put_off = self.mode.get() != CollectionMode::Automatic;
+        if put_off && self.collection_due.get() {
+            return;
+        }
         // check if it's been a long time since the last time we collected all
         // the garbage.
         // if so, go and collect it all again (amortized O(1))
-        if (self.collect_condition.get())(&CollectInfo { _private: () }) {
-            self.collect_all();
+        // the condition may replace itself, so it must not be borrowed while it runs
+        let condition = self.collect_condition.borrow().clone();
+        if condition.should_collect(&CollectInfo {
+            heap: self.handle.get(),
+        }) {
+            if put_off {
+                self.collection_due.set(true);
+            } else {
+                self.collect_scheduled();
+            }
         }
     }
 
+    /// Run the collection which was put off since the last safe point, if there is one.
+    ///
+    /// Returns whether a collection was run.
+    pub fn safe_point(&self) -> bool {
+        if !self.collection_due.get() || self.in_hook.get() {
+            return false;
+        }
+        self.collect_scheduled();
+        true
+    }
+
     /// Notify the dumpster that a new [`Gc`] has been created.
     pub fn notify_created_gc(&self) {
         self.n_refs_living.set(self.n_refs_living.get() + 1);
     }
+
+    /// Notify the dumpster that a new allocation with layout `layout` has been made.
+    pub fn notify_allocated(&self, layout: Layout) {
+        self.n_allocations.set(self.n_allocations.get() + 1);
+        self.heap_bytes.set(self.heap_bytes.get() + layout.size());
+    }
+
+    #[cfg(any(
+        feature = "leak-detection",
+        feature = "heap-dump",
+        feature = "heap-inspection"
+    ))]
+    /// Record that the allocation behind `box_ptr` has been made.
+    pub fn register_live<T: Collectable + ?Sized>(&self, box_ptr: NonNull<GcBox<T>>) {
+        self.live.borrow_mut().insert(
+            AllocationId::from(box_ptr),
+            LiveAllocation {
+                #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+                type_name: std::any::type_name::<T>(),
+                #[cfg(any(feature = "leak-detection", feature = "heap-inspection"))]
+                size: Layout::for_value(unsafe { box_ptr.as_ref() }).size(),
+                #[cfg(any(feature = "heap-dump", feature = "heap-inspection"))]
+                ptr: Erased::new(box_ptr),
+                #[cfg(feature = "heap-dump")]
+                describe_fn: describe::<T>,
+                #[cfg(feature = "heap-inspection")]
+                inspect_fn: inspect::<T>,
+            },
+        );
+    }
+
+    #[cfg(any(
+        feature = "leak-detection",
+        feature = "heap-dump",
+        feature = "heap-inspection"
+    ))]
+    /// Record that the allocations in `ids` have been freed.
+    pub fn forget_live<'a>(&self, ids: impl IntoIterator<Item = &'a AllocationId>) {
+        let mut live = self.live.borrow_mut();
+        for id in ids {
+            live.remove(id);
+        }
+    }
+
+    #[cfg(feature = "heap-dump")]
+    /// Describe every allocation which currently exists on this thread, for a heap dump.
+    pub fn describe_live(&self) -> Vec<DotNode> {
+        self.live
+            .borrow()
+            .values()
+            .map(|live| unsafe { (live.describe_fn)(live.ptr) })
+            .collect()
+    }
+
+    #[cfg(feature = "heap-inspection")]
+    /// Describe every allocation which currently exists on this thread, for
+    /// [`iter_allocations`](super::iter_allocations).
+    pub fn inspect_live(&self) -> Vec<AllocationInfo> {
+        self.live
+            .borrow()
+            .values()
+            .map(|live| unsafe { (live.inspect_fn)(live.ptr) })
+            .collect()
+    }
+
+    #[cfg(feature = "heap-inspection")]
+    /// Count the allocations which currently exist on this thread by type, for
+    /// [`type_census`](super::type_census).
+    pub fn census(&self) -> Vec<(String, usize, usize)> {
+        crate::inspect::census(
+            self.live
+                .borrow()
+                .values()
+                .map(|live| (live.type_name, live.size)),
+        )
+    }
+
+    /// Get the handle which allocations made from this dumpster refer to it by.
+    pub fn handle(&self) -> HeapRef {
+        self.handle.get()
+    }
+
+    /// Record that the allocation behind `box_ptr`, of `size` bytes, has been made, if this is
+    /// the dumpster of a [`Heap`](super::Heap).
+    pub fn register_owned<T: Collectable + ?Sized>(&self, box_ptr: NonNull<GcBox<T>>, size: usize) {
+        if self.handle.get().is_some() {
+            self.owned.borrow_mut().insert(
+                AllocationId::from(box_ptr),
+                Owned {
+                    ptr: Erased::new(box_ptr),
+                    size,
+                    clear_fn: clear_erased::<T>,
+                    #[cfg(debug_assertions)]
+                    count_fn: apply_visitor::<T, InternalRefs>,
+                },
+            );
+        }
+    }
+
+    /// Record that the allocations in `ids` have been freed, if this is the dumpster of a
+    /// [`Heap`](super::Heap).
+    pub fn forget_owned<'a>(&self, ids: impl IntoIterator<Item = &'a AllocationId>) {
+        if self.handle.get().is_some() {
+            let mut owned = self.owned.borrow_mut();
+            for id in ids {
+                owned.remove(id);
+            }
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    /// Count the allocations made from this dumpster which are pointed to by a `Gc` that does not
+    /// belong to another one of its allocations.
+    pub fn count_escaped(&self) -> usize {
+        let owned = self
+            .owned
+            .borrow()
+            .iter()
+            .map(|(&id, &o)| (id, o))
+            .collect::<Vec<_>>();
+        let mut internal = InternalRefs {
+            heap: self.handle.get(),
+            counts: AllocationMap::with_capacity_and_hasher(owned.len(), BuildPtrHasher),
+        };
+        for (_, o) in &owned {
+            unsafe { (o.count_fn)(o.ptr, &mut internal) };
+        }
+        owned
+            .iter()
+            .filter(|(id, _)| {
+                internal.counts.get(id).copied().unwrap_or(0) < unsafe { id.header() }.ref_count()
+            })
+            .count()
+    }
+
+    /// Register the ephemeron behind `inner` as keyed by the allocation behind `key`.
+    pub fn register_ephemeron<K: Collectable + ?Sized + 'static, V: Collectable + 'static>(
+        &self,
+        key: NonNull<GcBox<K>>,
+        inner: NonNull<EphemeronInner<K, V>>,
+    ) {
+        unsafe { key.as_ref() }.set_flag(EPHEMERON_KEY, true);
+        self.ephemerons
+            .borrow_mut()
+            .entry(AllocationId::from(key))
+            .or_default()
+            .push(EphemeronRef::new(inner));
+    }
+
+    /// Forget about the ephemeron behind `inner`, whose key is the live allocation `key`.
+    pub fn unregister_ephemeron(&self, key: AllocationId, inner: NonNull<()>) {
+        let mut ephemerons = self.ephemerons.borrow_mut();
+        let Entry::Occupied(mut entry) = ephemerons.entry(key) else {
+            return;
+        };
+        entry
+            .get_mut()
+            .retain(|e| unsafe { e.ptr.specify::<()>() } != inner);
+        if entry.get().is_empty() {
+            entry.remove();
+            unsafe { key.header() }.set_flag(EPHEMERON_KEY, false);
+        }
+    }
+
+    /// Mark every ephemeron keyed by the allocation `key`, which is being freed, as dead and drop
+    /// their values.
+    pub fn kill_ephemerons(&self, key: AllocationId) {
+        let ephemerons = self.ephemerons.borrow_mut().remove(&key);
+        for ephemeron in ephemerons.into_iter().flatten() {
+            unsafe { (ephemeron.kill_fn)(ephemeron.ptr) };
+        }
+    }
+
+    /// Determine whether any allocation made from this dumpster has not yet been cleared.
+    pub fn owns_any(&self) -> bool {
+        !self.owned.borrow().is_empty()
+    }
+
+    /// Drop the value in every allocation made from this dumpster, reachable or not.
+    ///
+    /// Every `Gc` which still points to one of them is left dead, and the memory of each
+    /// allocation is freed once the last of them is dropped.
+    /// Allocations made while the values are being dropped are not cleared.
+    pub fn clear(&self) {
+        let ids = self.owned.borrow().keys().copied().collect::<Vec<_>>();
+        // finalizers may look at the rest of the heap, so they must run before any of it is torn
+        // down
+        unsafe { self.finalize(&ids) };
+        self.incremental.borrow_mut().take();
+        unsafe { self.forget_dirty(|_| true) };
+
+        let mut owned = self.owned.take().into_iter().collect::<Vec<_>>();
+        if self.deterministic.get() {
+            owned.sort_unstable_by_key(|&(id, _)| unsafe { self.serial(id) });
+        }
+        let mut n_gcs = 0;
+        for (id, _) in &owned {
+            let header = unsafe { id.header() };
+            n_gcs += header.ref_count();
+            unsafe { self.forget_side_entries(*id) };
+            // the clearing holds a reference of its own, so that the allocation outlives its
+            // value even if every `Gc` to it is dropped along the way
+            header.increment();
+            header.set_flag(CLEARED, true);
+        }
+        for (id, _) in &owned {
+            if unsafe { id.header() }.has_flag(EPHEMERON_KEY) {
+                self.kill_ephemerons(*id);
+            }
+        }
+        // the `Gc`s to cleared allocations no longer belong to this heap
+        self.n_refs_living
+            .set(self.n_refs_living.get().saturating_sub(n_gcs));
+        self.n_allocations
+            .set(self.n_allocations.get() - owned.len());
+        self.heap_bytes
+            .set(self.heap_bytes.get() - owned.iter().map(|(_, o)| o.size).sum::<usize>());
+        #[cfg(any(
+            feature = "leak-detection",
+            feature = "heap-dump",
+            feature = "heap-inspection"
+        ))]
+        self.forget_live(owned.iter().map(|(id, _)| id));
+
+        // a value which panics while it is dropped does not stop the rest from being dropped
+        for (_, o) in owned {
+            unsafe { (o.clear_fn)(o.ptr) };
+        }
+        resume_caught();
+    }
+
+    /// Notify the dumpster that an allocation with layout `layout` has been freed.
+    pub fn notify_deallocated(&self, layout: Layout) {
+        self.n_allocations.set(self.n_allocations.get() - 1);
+        self.heap_bytes.set(self.heap_bytes.get() - layout.size());
+    }
 }
 
 impl Drop for Dumpster {
     fn drop(&mut self) {
         // cleanup any leftover allocations
-        self.collect_all();
+        // hooks may refer to other thread-locals which have already been destroyed, so they are
+        // not called here
+        // a panic cannot unwind out of a thread-local's destructor, so any panic from dropping
+        // garbage is discarded, having already been reported by the panic hook
+        let _ = catch_unwind(AssertUnwindSafe(|| self.collect_unhooked(false)));
+        drop(take_caught());
+
+        #[cfg(feature = "leak-detection")]
+        {
+            let leaked = self
+                .live
+                .get_mut()
+                .drain()
+                .map(|(_, a)| LeakedAllocation {
+                    type_name: a.type_name,
+                    size: a.size,
+                })
+                .collect::<Vec<_>>();
+            if !leaked.is_empty() {
+                let report = LeakReport {
+                    allocations: leaked,
+                };
+                eprint!("{report}");
+                LEAKS.lock().allocations.extend(report.allocations);
+            }
+        }
     }
 }
 
+#[cfg(feature = "leak-detection")]
+/// The allocations leaked by every thread which has exited so far.
+pub(super) static LEAKS: Mutex<LeakReport> = Mutex::new(LeakReport {
+    allocations: Vec::new(),
+});
+
+/// A guard which marks that a collection hook is running until it is dropped, even if the hook
+/// panics.
+struct HookGuard<'a>(&'a Cell<bool>);
+
+impl<'a> HookGuard<'a> {
+    /// Mark that a hook is running.
+    fn new(in_hook: &'a Cell<bool>) -> HookGuard<'a> {
+        in_hook.set(true);
+        HookGuard(in_hook)
+    }
+}
+
+impl Drop for HookGuard<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+/// The outcome of searching the heap for garbage.
+struct Analysis {
+    /// The traversal which found every allocation reachable from the dirty allocations.
+    dfs: Dfs,
+    /// The traversal which found every one of those which is reachable from outside of the
+    /// garbage-collected heap.
+    mark: Mark,
+}
+
+impl Analysis {
+    /// Get every allocation which was found to be garbage.
+    fn garbage(&self) -> impl Iterator<Item = (&AllocationId, &Reachability)> {
+        // a dirty allocation which was never reached from another one is always marked, so every
+        // piece of garbage has a node in the reference graph
+        self.dfs
+            .ref_graph
+            .iter()
+            .filter(|(id, _)| !self.mark.visited.contains(id))
+    }
+
+    /// Take apart this analysis so that its structures can be reused.
+    fn into_scratch(self) -> Scratch {
+        Scratch {
+            visited: self.dfs.visited,
+            ref_graph: self.dfs.ref_graph,
+            marked: self.mark.visited,
+            ..Scratch::default()
+        }
+    }
+}
+
+/// The number of collections after which the scratch structures of a dumpster are shrunk to fit
+/// the largest of those collections.
+const SHRINK_INTERVAL: usize = 64;
+
+#[derive(Default)]
+/// The structures built by every full collection, which are kept between collections so that a
+/// large heap does not pay to allocate them again each time it is collected.
+struct Scratch {
+    /// The set of allocations visited while building the reference graph.
+    visited: AllocationSet,
+    /// The graph of reachable allocations.
+    ref_graph: AllocationMap<Reachability>,
+    /// The set of allocations marked as reachable from outside of the heap.
+    marked: AllocationSet,
+    /// The most allocations visited by any collection since the structures were last shrunk.
+    high_water: usize,
+    /// The number of collections since the structures were last shrunk.
+    n_uses: usize,
+}
+
 /// The data required to construct the graph of reachable allocations.
 struct Dfs {
+    /// The heap being collected.
+    heap: HeapRef,
     /// The set of allocations which have already been visited.
-    visited: HashSet<AllocationId>,
+    visited: AllocationSet,
     /// A map from allocation identifiers to information about their reachability.
-    ref_graph: HashMap<AllocationId, Reachability>,
+    ref_graph: AllocationMap<Reachability>,
+    /// Whether this is a search for a partial collection.
+    partial: bool,
+    /// The allocations which a partial search did not search inside, because the last full
+    /// collection found them to be reachable.
+    pruned: Vec<AllocationId>,
+    /// The allocations whose values could not be traced, such as because a `RefCell` in them was
+    /// mutably borrowed, along with their cleanups.
+    untraced: Vec<(AllocationId, Cleanup)>,
 }
 
 #[derive(Debug)]
@@ -240,6 +1683,8 @@ struct Reachability {
     ptr: Erased,
     /// A function used to mark descendants of this allocation as accessible.
     mark_fn: unsafe fn(Erased, &mut Mark),
+    /// A function used to describe this allocation in a [`DryRunReport`].
+    describe_fn: unsafe fn(Erased) -> GarbageAllocation,
 }
 
 impl Visitor for Dfs {
@@ -255,7 +1700,12 @@ impl Visitor for Dfs {
     where
         T: Collectable + ?Sized,
     {
-        let ptr = gc.ptr.get().unwrap();
+        let ptr = gc.ptr;
+        if !unsafe { ptr.as_ref() }.belongs_to(self.heap) {
+            // another heap is responsible for this allocation, and this reference to it is left
+            // unaccounted for
+            return;
+        }
         let next_id = AllocationId::from(ptr);
         match self.ref_graph.entry(next_id) {
             Entry::Occupied(ref mut o) => {
@@ -263,22 +1713,41 @@ impl Visitor for Dfs {
             }
             Entry::Vacant(v) => {
                 v.insert(Reachability {
-                    n_unaccounted: unsafe { next_id.0.as_ref().get().get() - 1 },
+                    n_unaccounted: unsafe { next_id.header().ref_count() - 1 },
                     ptr: Erased::new(ptr),
                     mark_fn: apply_visitor::<T, Mark>,
+                    describe_fn: describe_garbage::<T>,
                 });
             }
         }
-        if self.visited.insert(next_id) {
-            let _ = unsafe { ptr.as_ref() }.value.accept(self);
+        let box_ref = unsafe { ptr.as_ref() };
+        // a rooted allocation is always marked, so its references are left unaccounted for and
+        // anything else found behind it is marked as well
+        if !self.visited.insert(next_id) || box_ref.is_rooted() {
+            return;
+        }
+        if self.partial && box_ref.has_flag(KNOWN_LIVE) {
+            // the same goes for an allocation which has lost no reference since it was last found
+            // to be reachable; if whatever held it has since become garbage, it is left for a full
+            // collection
+            self.pruned.push(next_id);
+            return;
+        }
+        if unsafe { accept_contents(box_ref, self) }.is_err() {
+            self.untraced(ptr);
         }
     }
 }
 
 /// A mark traversal, which marks allocations as reachable.
 struct Mark {
+    /// The heap being collected.
+    heap: HeapRef,
     /// The set of allocations which have been marked as reachable.
-    visited: HashSet<AllocationId>,
+    visited: AllocationSet,
+    /// Whether this is a mark for a partial collection, which does not search inside the
+    /// allocations stamped as reachable by the last full collection.
+    partial: bool,
 }
 
 impl Visitor for Mark {
@@ -294,19 +1763,88 @@ impl Visitor for Mark {
     where
         T: Collectable + ?Sized,
     {
-        let ptr = gc.ptr.get().unwrap();
-        if self.visited.insert(AllocationId::from(ptr)) {
-            let _ = unsafe { ptr.as_ref().value.accept(self) };
+        let ptr = gc.ptr;
+        let box_ref = unsafe { ptr.as_ref() };
+        if box_ref.belongs_to(self.heap)
+            && self.visited.insert(AllocationId::from(ptr))
+            && !box_ref.is_rooted()
+            && !(self.partial && box_ref.has_flag(KNOWN_LIVE))
+        {
+            let _ = unsafe { accept_contents(box_ref, self) };
+        }
+    }
+}
+
+/// A visitor which counts the references between the allocations of a single heap.
+struct InternalRefs {
+    /// The heap whose allocations are counted.
+    heap: HeapRef,
+    /// The number of references found to each allocation.
+    counts: AllocationMap<usize>,
+}
+
+impl Visitor for InternalRefs {
+    fn visit_sync<T>(&mut self, _: &crate::sync::Gc<T>)
+    where
+        T: Collectable + Send + Sync + ?Sized,
+    {
+        // sync `Gc`s never point into an unsync heap
+    }
+
+    fn visit_unsync<T>(&mut self, gc: &Gc<T>)
+    where
+        T: Collectable + ?Sized,
+    {
+        let ptr = gc.ptr;
+        if unsafe { ptr.as_ref() }.belongs_to(self.heap) {
+            *self.counts.entry(AllocationId::from(ptr)).or_insert(0) += 1;
         }
     }
 }
 
+/// The allocations which a [`DropAlloc`] is allowed to free.
+#[derive(Clone, Copy)]
+enum Doomed<'a> {
+    /// Every allocation except for the reachable ones in this set.
+    AllBut(&'a AllocationSet),
+    /// Only the unreachable allocations in this set.
+    Only(&'a AllocationSet),
+    /// Only the allocations in this reference graph which are not in this set of reachable ones.
+    Unmarked(&'a AllocationMap<Reachability>, &'a AllocationSet),
+}
+
 /// A visitor for dropping allocations.
 struct DropAlloc<'a> {
     /// The set of unreachable allocations we've already visited.
-    visited: HashSet<AllocationId>,
-    /// The set of unreachable allocations.
-    reachable: &'a HashSet<AllocationId>,
+    visited: AllocationSet,
+    /// The allocations which may be freed.
+    doomed: Doomed<'a>,
+    /// The heap being collected.
+    heap: HeapRef,
+    /// The dumpster of the heap being collected, which may no longer be reachable through
+    /// [`with_heap`] if its thread is exiting.
+    dumpster: &'a Dumpster,
+    /// Allocations which survived but lost a reference to a freed allocation, and so may have
+    /// become garbage.
+    /// This is not filled in for [`Doomed::AllBut`], since a full collection has already found
+    /// everything it could reach.
+    survivors: Vec<(AllocationId, Cleanup)>,
+    /// Allocations which were not known to be garbage but lost their last reference to it, with
+    /// the functions which free them.
+    /// The garbage may still hold `Gc`s to them whose references have already been accounted for,
+    /// so they are only freed once all of the garbage is gone.
+    orphans: Vec<(Erased, OrphanFn)>,
+    /// References from the garbage to allocations owned by other heaps, which are dropped as usual
+    /// once the collection is done.
+    foreign: Vec<(Erased, ReleaseFn)>,
+    /// If the collection is deterministic, the allocations which are ready to be freed once every
+    /// piece of garbage has been found, with their serial numbers if they were given any.
+    /// Otherwise, allocations are freed as soon as they are found, and this is `None`.
+    pending: Option<Vec<(Option<u64>, Erased, FreeFn)>>,
+    /// The number of allocations which have been freed so far.
+    n_freed: usize,
+    /// The total size, in bytes, of the allocations which have been freed so far.
+    bytes_freed: usize,
 }
 
 impl Visitor for DropAlloc<'_> {
@@ -321,44 +1859,209 @@ impl Visitor for DropAlloc<'_> {
     where
         T: Collectable + ?Sized,
     {
-        let ptr = gc.ptr.get().unwrap();
+        let ptr = gc.ptr;
+        let box_ref = unsafe { ptr.as_ref() };
+        if box_ref.is_dead() {
+            // this reference is given up when the value holding it is dropped
+            return;
+        }
+        if !box_ref.belongs_to(self.heap) {
+            self.foreign.push((Erased::new(ptr), release_erased::<T>));
+            return;
+        }
         let id = AllocationId::from(ptr);
-        if self.reachable.contains(&id) {
-            unsafe {
-                let cell_ref = &ptr.as_ref().ref_count;
-                cell_ref.set(NonZeroUsize::new(cell_ref.get().get() - 1).unwrap());
+        if is_fresh(id) {
+            // an allocation made while the garbage is dropped is not part of this collection, and
+            // its references are counted as usual
+            return;
+        }
+        if !self.is_doomed(id) {
+            box_ref.set_flag(KNOWN_LIVE, false);
+            if box_ref.ref_count() > 1 {
+                box_ref.decrement();
+                if !matches!(self.doomed, Doomed::AllBut(_)) {
+                    self.survivors.push((id, Cleanup::new(ptr)));
+                }
+            } else {
+                // this was the last reference to an allocation not known to be garbage, which can
+                // only happen if it was linked into the garbage after an incremental collection
+                // found it, or if a partial collection could not see what else held it, so it is
+                // garbage now too, and its remaining count is kept as the hold of the collection
+                self.orphans.push((Erased::new(ptr), free_orphan::<T>));
             }
             return;
         }
-        gc.ptr.set(gc.ptr.get().as_null());
-        if self.visited.insert(id) {
-            unsafe {
-                ptr.as_ref().value.accept(self).unwrap();
-                let layout = Layout::for_value(ptr.as_ref());
-                drop_in_place(ptr.as_ptr());
-                dealloc(ptr.as_ptr().cast(), layout);
+        unsafe {
+            if self.doom(ptr) {
+                accept_contents(box_ref, self).unwrap();
+                self.free(ptr);
+            }
+        }
+    }
+}
+
+/// A function which drops a reference to an allocation owned by another heap.
+type ReleaseFn = unsafe fn(Erased);
+
+/// Drop a reference to an allocation owned by another heap, as if a `Gc` to it had been dropped.
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`], and the reference
+/// must have been taken from a `Gc` which no longer drops it.
+unsafe fn release_erased<T: Collectable + ?Sized + 'static>(ptr: Erased) {
+    drop(Gc {
+        ptr: ptr.specify::<GcBox<T>>(),
+    });
+}
+
+/// Drop the references to other heaps which were found in the garbage of a collection.
+///
+/// This must only be called once the collection is no longer running, so that the other heaps may
+/// collect in turn.
+fn release_foreign(foreign: Vec<(Erased, ReleaseFn)>) {
+    for (ptr, release_fn) in foreign {
+        unsafe { release_fn(ptr) };
+    }
+}
+
+/// A function which drops the value in and frees an allocation, returning the number of bytes
+/// freed, or `None` if the value panicked and the allocation was leaked.
+type FreeFn = unsafe fn(Erased) -> Option<usize>;
+
+/// A function which frees an allocation which lost its last reference to the garbage of a
+/// collection.
+type OrphanFn = unsafe fn(Erased, &mut DropAlloc<'_>);
+
+impl DropAlloc<'_> {
+    /// Determine whether the allocation `id` may be freed.
+    fn is_doomed(&self, id: AllocationId) -> bool {
+        match self.doomed {
+            Doomed::AllBut(reachable) => !reachable.contains(&id),
+            Doomed::Only(garbage) => garbage.contains(&id),
+            Doomed::Unmarked(graph, reachable) => {
+                graph.contains_key(&id) && !reachable.contains(&id)
+            }
+        }
+    }
+
+    /// Mark the allocation behind `ptr` as garbage, so that every `Gc` to it is dead, unless it has
+    /// already been marked.
+    ///
+    /// The collection holds a reference of its own to each allocation it marks, so that the memory
+    /// outlives every `Gc` to it which is dropped along with the garbage.
+    /// This reference is given up once the value is dropped.
+    ///
+    /// Returns whether the allocation was newly marked.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live allocation which is garbage.
+    unsafe fn doom<T: Collectable + ?Sized>(&mut self, ptr: NonNull<GcBox<T>>) -> bool {
+        if !self.visited.insert(AllocationId::from(ptr)) {
+            return false;
+        }
+        let box_ref = ptr.as_ref();
+        box_ref.increment();
+        box_ref.set_flag(COLLECTED, true);
+        true
+    }
+
+    /// Free an allocation whose references to other garbage have all been found, or set it aside
+    /// to be freed by [`DropAlloc::free_pending`] if the collection is deterministic.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live allocation which is garbage.
+    unsafe fn free<T: Collectable + ?Sized>(&mut self, ptr: NonNull<GcBox<T>>) {
+        let serial = self.dumpster.forget_side_entries(AllocationId::from(ptr));
+        if let Some(pending) = &mut self.pending {
+            pending.push((serial, Erased::new(ptr), free_erased::<T>));
+        } else {
+            self.record(free_erased::<T>(Erased::new(ptr)));
+        }
+    }
+
+    /// Free every allocation set aside by [`DropAlloc::free`], oldest first, and then every
+    /// orphan, along with anything which only the orphans held.
+    ///
+    /// # Safety
+    ///
+    /// Every garbage allocation must have been found, so that no value which is dropped can reach
+    /// another allocation through a `Gc`.
+    unsafe fn free_pending(&mut self) {
+        loop {
+            if let Some(mut pending) = self.pending.as_mut().map(take) {
+                pending.sort_unstable_by_key(|&(serial, ..)| serial);
+                for (_, ptr, free_fn) in pending {
+                    self.record(free_fn(ptr));
+                }
             }
+            let Some((ptr, free_fn)) = self.orphans.pop() else {
+                break;
+            };
+            free_fn(ptr, self);
+        }
+        self.pending = None;
+    }
+
+    /// Count an allocation of `size` bytes as freed, unless it was leaked.
+    fn record(&mut self, size: Option<usize>) {
+        if let Some(size) = size {
+            self.n_freed += 1;
+            self.bytes_freed += size;
         }
     }
 }
 
+/// Drop the value in and free an allocation on behalf of a collection.
+///
+/// The memory of the allocation is freed once the last `Gc` to it is dropped, which is usually
+/// right away.
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`], and the allocation
+/// must have been marked by [`DropAlloc::doom`].
+unsafe fn free_erased<T: Collectable + ?Sized>(ptr: Erased) -> Option<usize> {
+    let ptr = ptr.specify::<GcBox<T>>();
+    let size = size_of_val(ptr.as_ref());
+    let (heap, is_ephemeron_key) = (ptr.as_ref().heap, ptr.as_ref().has_flag(EPHEMERON_KEY));
+    // if dropping the value panicked, the allocation is leaked
+    let freed = drop_collected(ptr.as_ptr()).then(|| {
+        release_cleared(ptr);
+        size
+    });
+    if is_ephemeron_key {
+        with_heap(heap, |d| d.kill_ephemerons(AllocationId::from(ptr)));
+    }
+    freed
+}
+
+/// Free an allocation which lost its last reference to the garbage of a collection, once all of
+/// the garbage is gone.
+///
+/// # Safety
+///
+/// `T` must be the same type that `ptr` was created with via [`ErasedPtr::new`], and the allocation
+/// must have been set aside as an orphan by `visitor`, which still holds its last reference.
+unsafe fn free_orphan<T: Collectable + ?Sized>(ptr: Erased, visitor: &mut DropAlloc<'_>) {
+    let ptr = ptr.specify::<GcBox<T>>();
+    let box_ref = ptr.as_ref();
+    if visitor.visited.insert(AllocationId::from(ptr)) {
+        box_ref.set_flag(COLLECTED, true);
+        accept_contents(box_ref, visitor).unwrap();
+        visitor.free(ptr);
+    }
+}
+
 /// Decrement the outbound reference counts for any reachable allocations which this allocation can
 /// find.
 /// Also, drop the allocation when done.
 unsafe fn drop_assist<T: Collectable + ?Sized>(ptr: Erased, visitor: &mut DropAlloc<'_>) {
-    if visitor
-        .visited
-        .insert(AllocationId::from(ptr.specify::<GcBox<T>>()))
-    {
-        ptr.specify::<GcBox<T>>()
-            .as_ref()
-            .value
-            .accept(visitor)
-            .unwrap();
-
-        let mut_spec = ptr.specify::<GcBox<T>>().as_mut();
-        let layout = Layout::for_value(mut_spec);
-        drop_in_place(mut_spec);
-        dealloc(std::ptr::from_mut::<GcBox<T>>(mut_spec).cast(), layout);
+    let ptr = ptr.specify::<GcBox<T>>();
+    if visitor.doom(ptr) {
+        accept_contents(ptr.as_ref(), visitor).unwrap();
+        visitor.free(ptr);
     }
 }
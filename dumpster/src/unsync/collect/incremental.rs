@@ -0,0 +1,464 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Incremental collection, which spreads the work of a single collection across many calls.
+//!
+//! An incremental collection runs in three phases.
+//! First, it scans outward from the allocations which were dirty when it started, building a
+//! reference graph much like a full collection does.
+//! Next, it marks every allocation in that graph which it believes to be reachable.
+//! Both of these phases can be interrupted after any unit of work, and the program is free to
+//! mutate the heap in between, so what they find may be stale by the time they finish.
+//! Their results are therefore only used to pick out candidate garbage.
+//! In the final phase, which runs without interruption, the collector counts the references
+//! between candidates from scratch, and only frees the candidates whose every reference comes from
+//! another freed candidate.
+//! The cost of this last phase is proportional to the number of candidates, not the size of the
+//! heap.
+
+use std::{
+    collections::hash_map::Entry,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    time::Instant,
+};
+
+use crate::{
+    hash::BuildPtrHasher,
+    ptr::Erased,
+    unsync::{CollectProgress, Gc, GcBox, FINALIZER},
+    unwind::resume_caught,
+    Collectable, Visitor,
+};
+
+use super::{
+    accept_contents, release_foreign, run_deferred_drops, AllocationId, AllocationMap,
+    AllocationSet, Cleanup, Doomed, DropAlloc, Dumpster, HeapRef, COLLECTING,
+};
+
+/// The state of an incremental collection which has been started but not yet finished.
+pub(super) struct Incremental {
+    /// Every allocation found so far.
+    graph: AllocationMap<Node>,
+    /// Allocations which have been found but whose references have not yet been scanned.
+    to_scan: Vec<AllocationId>,
+    /// Allocations which have been marked as reachable but whose children have not yet been
+    /// marked.
+    /// This is `None` until scanning has finished.
+    to_mark: Option<Vec<AllocationId>>,
+}
+
+/// An allocation found by an incremental collection.
+pub(super) struct Node {
+    /// The information needed to visit or free the allocation.
+    cleanup: Cleanup,
+    /// The number of references to this allocation which have not been found while scanning.
+    /// If this is nonzero once scanning is finished, the allocation is a root.
+    n_unaccounted: usize,
+    /// Whether the references of this allocation have been scanned.
+    scanned: bool,
+    /// Whether this allocation is believed to be reachable.
+    marked: bool,
+}
+
+/// An allocation which may be freed at the end of an incremental collection.
+pub(super) struct Candidate {
+    /// The information needed to visit or free the allocation.
+    cleanup: Cleanup,
+    /// The number of references to this allocation from other candidates.
+    n_internal: usize,
+    /// Whether this allocation has been found to be alive.
+    live: bool,
+}
+
+/// A visitor which performs one unit of work in an incremental collection.
+pub(super) enum Step<'a> {
+    /// Add the children of an allocation to the reference graph.
+    Scan {
+        /// The heap being collected.
+        heap: HeapRef,
+        /// The reference graph.
+        graph: &'a mut AllocationMap<Node>,
+        /// The allocations which have yet to be scanned.
+        to_scan: &'a mut Vec<AllocationId>,
+    },
+    /// Mark the children of a reachable allocation as reachable.
+    Mark {
+        /// The reference graph.
+        graph: &'a mut AllocationMap<Node>,
+        /// The marked allocations whose children have yet to be marked.
+        to_mark: &'a mut Vec<AllocationId>,
+    },
+    /// Count the references from one candidate to the others.
+    Count {
+        /// The candidates for freeing.
+        candidates: &'a mut AllocationMap<Candidate>,
+    },
+    /// Mark the children of a live candidate as live.
+    Rescue {
+        /// The candidates for freeing.
+        candidates: &'a mut AllocationMap<Candidate>,
+        /// The live candidates whose children have yet to be rescued.
+        to_rescue: &'a mut Vec<AllocationId>,
+    },
+}
+
+/// Get the current reference count of an allocation.
+///
+/// # Safety
+///
+/// `id` must refer to an allocation which has not been freed.
+unsafe fn ref_count(id: AllocationId) -> usize {
+    id.header().ref_count()
+}
+
+/// Determine whether an allocation is held by a [`Root`](crate::unsync::Root).
+///
+/// # Safety
+///
+/// `id` must refer to an allocation which has not been freed.
+unsafe fn is_rooted(id: AllocationId) -> bool {
+    id.header().is_rooted()
+}
+
+/// Perform one unit of incremental collection work on the allocation behind `ptr`.
+///
+/// # Safety
+///
+/// `ptr` must have been created as a pointer to a `GcBox<T>`.
+pub(super) unsafe fn step_assist<T: Collectable + ?Sized>(
+    ptr: Erased,
+    step: &mut Step<'_>,
+) -> Result<(), ()> {
+    accept_contents(ptr.specify::<GcBox<T>>().as_ref(), step)
+}
+
+impl Visitor for Step<'_> {
+    fn visit_sync<T>(&mut self, _: &crate::sync::Gc<T>)
+    where
+        T: Collectable + Send + Sync + ?Sized,
+    {
+        // sync `Gc`s are never owned by the thread-local heap's reference graph
+    }
+
+    fn visit_unsync<T>(&mut self, gc: &Gc<T>)
+    where
+        T: Collectable + ?Sized,
+    {
+        let ptr = gc.ptr;
+        let id = AllocationId::from(ptr);
+        match self {
+            Step::Scan {
+                heap,
+                graph,
+                to_scan,
+            } => {
+                if !unsafe { ptr.as_ref() }.belongs_to(*heap) {
+                    // another heap is responsible for this allocation
+                    return;
+                }
+                match graph.entry(id) {
+                    Entry::Occupied(mut o) => {
+                        let node = o.get_mut();
+                        node.n_unaccounted = node.n_unaccounted.saturating_sub(1);
+                    }
+                    Entry::Vacant(v) => {
+                        v.insert(Node {
+                            cleanup: Cleanup::new(ptr),
+                            n_unaccounted: unsafe { ref_count(id) } - 1,
+                            scanned: false,
+                            marked: false,
+                        });
+                        to_scan.push(id);
+                    }
+                }
+            }
+            Step::Mark { graph, to_mark } => {
+                if let Some(node) = graph.get_mut(&id) {
+                    if !node.marked {
+                        node.marked = true;
+                        to_mark.push(id);
+                    }
+                }
+            }
+            Step::Count { candidates } => {
+                if let Some(candidate) = candidates.get_mut(&id) {
+                    candidate.n_internal += 1;
+                }
+            }
+            Step::Rescue {
+                candidates,
+                to_rescue,
+            } => {
+                if let Some(candidate) = candidates.get_mut(&id) {
+                    if !candidate.live {
+                        candidate.live = true;
+                        to_rescue.push(id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Incremental {
+    /// Begin an incremental collection, starting from the allocations in `to_collect`.
+    fn new(to_collect: &[(AllocationId, Cleanup)]) -> Incremental {
+        let graph = to_collect
+            .iter()
+            .map(|&(id, cleanup)| {
+                let node = Node {
+                    cleanup,
+                    n_unaccounted: unsafe { ref_count(id) },
+                    scanned: false,
+                    marked: false,
+                };
+                (id, node)
+            })
+            .collect::<AllocationMap<_>>();
+        Incremental {
+            to_scan: graph.keys().copied().collect(),
+            graph,
+            to_mark: None,
+        }
+    }
+
+    /// Forget about an allocation which is about to be freed.
+    pub(super) fn forget(&mut self, id: AllocationId) {
+        // any copies of `id` in the work lists are skipped once it is missing from the graph
+        self.graph.remove(&id);
+    }
+
+    /// Perform one unit of work on the collection of `heap`.
+    ///
+    /// Returns `false` if there was no work left to do, in which case the collection is ready to
+    /// be finished.
+    fn step(&mut self, heap: HeapRef) -> bool {
+        if let Some(to_mark) = &mut self.to_mark {
+            let Some(id) = to_mark.pop() else {
+                return false;
+            };
+            // a rooted allocation is known to be reachable, and so is everything behind it
+            if let Some(node) = self.graph.get(&id).filter(|_| !unsafe { is_rooted(id) }) {
+                let cleanup = node.cleanup;
+                let _ = unsafe {
+                    (cleanup.step_fn)(
+                        cleanup.ptr,
+                        &mut Step::Mark {
+                            graph: &mut self.graph,
+                            to_mark,
+                        },
+                    )
+                };
+            }
+            return true;
+        }
+
+        if let Some(id) = self.to_scan.pop() {
+            if let Some(node) = self.graph.get_mut(&id) {
+                // the references out of a rooted allocation are left unaccounted for, so that
+                // anything found behind it is marked
+                if !node.scanned && !unsafe { is_rooted(id) } {
+                    node.scanned = true;
+                    let cleanup = node.cleanup;
+                    let _ = unsafe {
+                        (cleanup.step_fn)(
+                            cleanup.ptr,
+                            &mut Step::Scan {
+                                heap,
+                                graph: &mut self.graph,
+                                to_scan: &mut self.to_scan,
+                            },
+                        )
+                    };
+                }
+            }
+        } else {
+            // scanning is done; anything with unaccounted references is a root
+            self.to_mark = Some(
+                self.graph
+                    .iter_mut()
+                    .filter(|(_, node)| node.n_unaccounted > 0)
+                    .map(|(&id, node)| {
+                        node.marked = true;
+                        id
+                    })
+                    .collect(),
+            );
+        }
+        true
+    }
+}
+
+impl Dumpster {
+    /// Do incremental collection work until `out_of_budget` returns `true` or the collection
+    /// finishes.
+    ///
+    /// At least one unit of work is always done, so that repeated calls always make progress.
+    pub fn collect_incremental(&self, mut out_of_budget: impl FnMut() -> bool) -> CollectProgress {
+        let start = Instant::now();
+        let mut state = self
+            .incremental
+            .borrow_mut()
+            .take()
+            .unwrap_or_else(|| Incremental::new(&self.to_collect.borrow()));
+
+        while state.step(self.handle()) {
+            if out_of_budget() {
+                *self.incremental.borrow_mut() = Some(state);
+                self.time_collecting
+                    .set(self.time_collecting.get() + start.elapsed());
+                return CollectProgress::Pending;
+            }
+        }
+
+        let Some(freed) = self.finish_incremental(state) else {
+            self.time_collecting
+                .set(self.time_collecting.get() + start.elapsed());
+            let freed = self.collect_unhooked(false).allocations_freed;
+            resume_caught();
+            return CollectProgress::Finished { freed };
+        };
+        self.n_collections.set(self.n_collections.get() + 1);
+        self.time_collecting
+            .set(self.time_collecting.get() + start.elapsed());
+        resume_caught();
+        CollectProgress::Finished { freed }
+    }
+
+    /// Free all the garbage found by an incremental collection whose scanning and marking are
+    /// done.
+    ///
+    /// Returns the number of allocations freed, or `None` if some of the garbage has a finalizer
+    /// which must be run first.
+    /// Finalizers can resurrect the allocations they see, which only a full collection can account
+    /// for, so in that case nothing is freed and a full collection must be run instead.
+    fn finish_incremental(&self, state: Incremental) -> Option<usize> {
+        self.n_ref_drops.set(0);
+        let mut candidates = state
+            .graph
+            .into_iter()
+            .filter(|(_, node)| !node.marked)
+            .map(|(id, node)| {
+                let candidate = Candidate {
+                    cleanup: node.cleanup,
+                    n_internal: 0,
+                    live: false,
+                };
+                (id, candidate)
+            })
+            .collect::<AllocationMap<_>>();
+        let cleanups = candidates
+            .iter()
+            .map(|(&id, candidate)| (id, candidate.cleanup))
+            .collect::<Vec<_>>();
+
+        // The heap may have changed since the candidates were found, so count the references
+        // between them again.
+        // A candidate is only garbage if every reference to it comes from another garbage
+        // candidate.
+        let mut to_rescue = Vec::new();
+        for (id, cleanup) in &cleanups {
+            let counted = unsafe {
+                (cleanup.step_fn)(
+                    cleanup.ptr,
+                    &mut Step::Count {
+                        candidates: &mut candidates,
+                    },
+                )
+            };
+            if counted.is_err() {
+                // we can't see this allocation's references, so it can't be freed
+                to_rescue.push(*id);
+            }
+        }
+        for &id in &to_rescue {
+            candidates.get_mut(&id).unwrap().live = true;
+        }
+        for (&id, candidate) in &mut candidates {
+            if !candidate.live && candidate.n_internal != unsafe { ref_count(id) } {
+                candidate.live = true;
+                to_rescue.push(id);
+            }
+        }
+        while let Some(id) = to_rescue.pop() {
+            let cleanup = candidates[&id].cleanup;
+            let _ = unsafe {
+                (cleanup.step_fn)(
+                    cleanup.ptr,
+                    &mut Step::Rescue {
+                        candidates: &mut candidates,
+                        to_rescue: &mut to_rescue,
+                    },
+                )
+            };
+        }
+
+        let garbage = candidates
+            .iter()
+            .filter(|(_, candidate)| !candidate.live)
+            .map(|(&id, _)| id)
+            .collect::<AllocationSet>();
+        if garbage
+            .iter()
+            .any(|id| unsafe { id.header().has_flag(FINALIZER) })
+        {
+            return None;
+        }
+        // Only candidates have been checked against the current state of the heap.
+        // Allocations which were marked as reachable may have become garbage since, and so must
+        // stay dirty.
+        unsafe { self.forget_dirty(|id| candidates.contains_key(id)) };
+        let mut decrementer = DropAlloc {
+            visited: AllocationSet::with_capacity_and_hasher(garbage.len(), BuildPtrHasher),
+            doomed: Doomed::Only(&garbage),
+            heap: self.handle(),
+            dumpster: self,
+            survivors: Vec::new(),
+            orphans: Vec::new(),
+            foreign: Vec::new(),
+            pending: self.deterministic.get().then(Vec::new),
+            n_freed: 0,
+            bytes_freed: 0,
+        };
+
+        COLLECTING.with(|c| c.set(true));
+        // if a value panics while it is dropped, the bookkeeping must still be finished before
+        // the panic resumes
+        let dropped = catch_unwind(AssertUnwindSafe(|| {
+            for (_, cleanup) in cleanups.iter().filter(|(id, _)| garbage.contains(id)) {
+                unsafe { (cleanup.drop_fn)(cleanup.ptr, &mut decrementer) };
+            }
+            unsafe { decrementer.free_pending() };
+        }));
+        COLLECTING.with(|c| c.set(false));
+        self.account_freed(&decrementer);
+
+        for (id, cleanup) in decrementer.survivors {
+            if !decrementer.visited.contains(&id) {
+                unsafe { self.add_dirty(id, cleanup) };
+            }
+        }
+
+        run_deferred_drops();
+        release_foreign(decrementer.foreign);
+        if let Err(payload) = dropped {
+            resume_unwind(payload);
+        }
+        Some(decrementer.n_freed)
+    }
+}
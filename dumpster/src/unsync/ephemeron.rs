@@ -0,0 +1,471 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Ephemerons, which keep a value alive only for as long as their key is alive.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem::ManuallyDrop,
+    ptr::NonNull,
+};
+
+use crate::{Collectable, Visitor};
+
+use super::{
+    collect::{defer_drop, AllocationId, Dumpster, COLLECTING, DUMPSTER},
+    Gc, GcBox,
+};
+
+/// The state of an [`Ephemeron`], which the collector refers to while the key is alive.
+pub(super) struct EphemeronInner<K: Collectable + ?Sized + 'static, V> {
+    /// The allocation of the key, or `None` once it has been freed.
+    pub key: Cell<Option<NonNull<GcBox<K>>>>,
+    /// The value, or `None` once the key has been freed.
+    pub value: RefCell<Option<V>>,
+}
+
+/// A pair of a key and a value, where the value is only kept alive for as long as the key is.
+///
+/// An ephemeron does not keep its key alive.
+/// Its value is reachable only through the key: the collector treats the references in the value
+/// as though they were held by the key's allocation, and not by whatever holds the ephemeron.
+/// Once the key is freed, the value is dropped, even if the value itself refers back to the key.
+///
+/// This is the building block for caches and side tables keyed by garbage-collected objects, such
+/// as [`WeakKeyHashMap`].
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect, Ephemeron, Gc};
+///
+/// let key = Gc::new(1);
+/// let ephemeron = Ephemeron::new(&key, "one");
+/// assert_eq!(ephemeron.value(), Some("one"));
+///
+/// drop(key);
+/// collect();
+/// assert!(ephemeron.key().is_none());
+/// assert_eq!(ephemeron.value(), None);
+/// ```
+pub struct Ephemeron<K: Collectable + ?Sized + 'static, V: Collectable + 'static> {
+    /// The state of this ephemeron, which is owned by it and freed when it is dropped.
+    /// It is kept behind a pointer so that the collector can refer to it.
+    inner: NonNull<EphemeronInner<K, V>>,
+}
+
+impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> Ephemeron<K, V> {
+    /// Construct a new ephemeron, which keeps `value` alive for as long as the allocation which
+    /// `key` points to is alive.
+    ///
+    /// If `key` is dead, the ephemeron is created dead, and `value` is dropped right away.
+    pub fn new(key: &Gc<K>, value: V) -> Ephemeron<K, V> {
+        let inner = NonNull::from(Box::leak(Box::new(EphemeronInner {
+            key: Cell::new(None),
+            value: RefCell::new(None),
+        })));
+        if let Some(key) = Some(key.ptr).filter(|ptr| !unsafe { ptr.as_ref() }.is_dead()) {
+            let inner_ref = unsafe { inner.as_ref() };
+            inner_ref.key.set(Some(key));
+            *inner_ref.value.borrow_mut() = Some(value);
+            super::with_heap(unsafe { key.as_ref() }.heap, |d| {
+                d.register_ephemeron(key, inner);
+            });
+        }
+        Ephemeron { inner }
+    }
+
+    /// Get the state of this ephemeron.
+    fn inner(&self) -> &EphemeronInner<K, V> {
+        unsafe { self.inner.as_ref() }
+    }
+
+    #[must_use]
+    /// Get a `Gc` to the key of this ephemeron, or `None` if the key has been freed.
+    pub fn key(&self) -> Option<Gc<K>> {
+        let gc = ManuallyDrop::new(Gc {
+            ptr: self.inner().key.get()?,
+        });
+        Some(Gc::clone(&gc))
+    }
+
+    #[must_use]
+    /// Get a copy of the value of this ephemeron, or `None` if the key has been freed.
+    pub fn value(&self) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.inner().value.borrow().clone()
+    }
+
+    #[must_use]
+    /// Determine whether the key of this ephemeron is still alive.
+    pub fn is_alive(&self) -> bool {
+        self.inner().key.get().is_some()
+    }
+
+    /// Determine whether the key of this ephemeron is the allocation that `key` points to.
+    fn is_keyed_by(&self, key: &Gc<K>) -> bool {
+        self.inner()
+            .key
+            .get()
+            .is_some_and(|ours| std::ptr::addr_eq(ours.as_ptr(), key.ptr.as_ptr()))
+    }
+
+    /// Take the value out of this ephemeron and drop the ephemeron.
+    fn into_value(self) -> Option<V> {
+        self.inner().value.take()
+    }
+}
+
+impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> Drop for Ephemeron<K, V> {
+    fn drop(&mut self) {
+        if COLLECTING.with(Cell::get) {
+            // the collection did not look inside the value, and may yet free the key, so the
+            // ephemeron must stay registered until the collection is done with it
+            defer_drop(Box::new(Ephemeron { inner: self.inner }));
+            return;
+        }
+        let inner = unsafe { Box::from_raw(self.inner.as_ptr()) };
+        if let Some(key) = inner.key.get() {
+            // a key which has not been freed still belongs to a heap which exists, unless this is
+            // the default heap of a thread which is exiting
+            let unregister = |d: &Dumpster| {
+                d.unregister_ephemeron(AllocationId::from(key), self.inner.cast());
+            };
+            match unsafe { key.as_ref() }.heap {
+                heap @ Some(_) => super::with_heap(heap, unregister),
+                None => {
+                    let _ = DUMPSTER.try_with(unregister);
+                }
+            }
+        }
+    }
+}
+
+unsafe impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> Collectable
+    for Ephemeron<K, V>
+{
+    fn accept<Vis: Visitor>(&self, _: &mut Vis) -> Result<(), ()> {
+        // the collector finds the references in the value through the key instead
+        Ok(())
+    }
+}
+
+impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> std::fmt::Debug
+    for Ephemeron<K, V>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ephemeron")
+            .field("alive", &self.is_alive())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Get the address by which `key` is stored in a map keyed by identity.
+///
+/// # Panics
+///
+/// This function panics if `key` is dead.
+fn key_addr<T: Collectable + ?Sized>(key: &Gc<T>) -> usize {
+    Gc::as_ptr(key).cast::<()>() as usize
+}
+
+/// A hash map keyed by garbage-collected allocations, which does not keep its keys alive.
+///
+/// Keys are compared by address, as with [`Gc::ptr_eq`].
+/// Each entry is an [`Ephemeron`], so a value is kept alive only while its key is, even if the
+/// value refers back to the key.
+/// Once a key is freed, its entry disappears from the map.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect, Gc, WeakKeyHashMap};
+///
+/// let mut cache = WeakKeyHashMap::new();
+/// let key = Gc::new("expensive input");
+/// cache.insert(&key, 42);
+/// assert_eq!(cache.get(&key), Some(42));
+///
+/// drop(key);
+/// collect();
+/// assert!(cache.is_empty());
+/// ```
+pub struct WeakKeyHashMap<K: Collectable + ?Sized + 'static, V: Collectable + 'static> {
+    /// The entries of the map, keyed by the address of their key's value.
+    /// Entries whose keys have been freed are removed lazily.
+    entries: HashMap<usize, Ephemeron<K, V>>,
+    /// The number of entries at which the entries with freed keys will next be removed.
+    purge_at: usize,
+}
+
+impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> WeakKeyHashMap<K, V> {
+    #[must_use]
+    /// Construct a new, empty map.
+    pub fn new() -> WeakKeyHashMap<K, V> {
+        WeakKeyHashMap {
+            entries: HashMap::new(),
+            purge_at: 0,
+        }
+    }
+
+    /// Insert `value` under `key`, returning the value which was previously stored under it, if
+    /// there was one.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is dead.
+    pub fn insert(&mut self, key: &Gc<K>, value: V) -> Option<V> {
+        if self.entries.len() >= self.purge_at {
+            self.entries.retain(|_, e| e.is_alive());
+            self.purge_at = (self.entries.len() * 2).max(8);
+        }
+        self.entries
+            .insert(key_addr(key), Ephemeron::new(key, value))
+            .and_then(Ephemeron::into_value)
+    }
+
+    #[must_use]
+    /// Get a copy of the value stored under `key`, if there is one.
+    pub fn get(&self, key: &Gc<K>) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.entries
+            .get(&key_addr(key))
+            .filter(|e| e.is_keyed_by(key))?
+            .value()
+    }
+
+    #[must_use]
+    /// Determine whether a value is stored under `key`.
+    pub fn contains_key(&self, key: &Gc<K>) -> bool {
+        self.entries
+            .get(&key_addr(key))
+            .is_some_and(|e| e.is_keyed_by(key))
+    }
+
+    /// Remove the value stored under `key`, returning it if there was one.
+    pub fn remove(&mut self, key: &Gc<K>) -> Option<V> {
+        let addr = key_addr(key);
+        if !self.contains_key(key) {
+            return None;
+        }
+        self.entries.remove(&addr).and_then(Ephemeron::into_value)
+    }
+
+    #[must_use]
+    /// Get the number of entries whose keys are still alive.
+    pub fn len(&self) -> usize {
+        self.entries.values().filter(|e| e.is_alive()).count()
+    }
+
+    #[must_use]
+    /// Determine whether no entry's key is still alive.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> Default for WeakKeyHashMap<K, V> {
+    fn default() -> WeakKeyHashMap<K, V> {
+        WeakKeyHashMap::new()
+    }
+}
+
+unsafe impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> Collectable
+    for WeakKeyHashMap<K, V>
+{
+    fn accept<Vis: Visitor>(&self, _: &mut Vis) -> Result<(), ()> {
+        // every entry is an ephemeron, whose value is found through its key
+        Ok(())
+    }
+}
+
+impl<K: Collectable + ?Sized + 'static, V: Collectable + 'static> std::fmt::Debug
+    for WeakKeyHashMap<K, V>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeakKeyHashMap")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A hash map whose keys are garbage-collected allocations, compared by identity.
+///
+/// Keys are compared by address, as with [`Gc::ptr_eq`], and the map does not keep them alive.
+/// Once a key is freed, its entry disappears from the map.
+/// The entry's value is dropped once the map prunes it, which happens as the map grows, or when
+/// [`GcHashMap::prune`] is called.
+///
+/// Unlike a [`WeakKeyHashMap`], the map holds its values like any other container, and they can be
+/// borrowed from it.
+/// This means that a value which refers back to its own key keeps the key, and the entry, alive for
+/// as long as the map is.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect, Gc, GcHashMap};
+///
+/// let mut names = GcHashMap::new();
+/// let a = Gc::new(1);
+/// let b = Gc::new(1);
+/// names.insert(&a, "a");
+/// names.insert(&b, "b");
+/// assert_eq!(names.get(&a), Some(&"a"));
+///
+/// drop(a);
+/// collect();
+/// assert_eq!(names.len(), 1);
+/// ```
+pub struct GcHashMap<T: Collectable + ?Sized + 'static, V> {
+    /// The entries of the map, keyed by the address of their key's value.
+    /// Each key is held by an ephemeron with no value, which tells whether it is still alive.
+    entries: HashMap<usize, (Ephemeron<T, ()>, V)>,
+    /// The number of entries at which the entries with freed keys will next be removed.
+    prune_at: usize,
+}
+
+impl<T: Collectable + ?Sized + 'static, V> GcHashMap<T, V> {
+    #[must_use]
+    /// Construct a new, empty map.
+    pub fn new() -> GcHashMap<T, V> {
+        GcHashMap {
+            entries: HashMap::new(),
+            prune_at: 0,
+        }
+    }
+
+    /// Drop every entry whose key has been freed.
+    pub fn prune(&mut self) {
+        self.entries.retain(|_, (key, _)| key.is_alive());
+        self.prune_at = (self.entries.len() * 2).max(8);
+    }
+
+    /// Insert `value` under `key`, returning the value which was previously stored under it, if
+    /// there was one.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is dead.
+    pub fn insert(&mut self, key: &Gc<T>, value: V) -> Option<V> {
+        if self.entries.len() >= self.prune_at {
+            self.prune();
+        }
+        self.entries
+            .insert(key_addr(key), (Ephemeron::new(key, ()), value))
+            .filter(|(old, _)| old.is_alive())
+            .map(|(_, value)| value)
+    }
+
+    #[must_use]
+    /// Get a reference to the value stored under `key`, if there is one.
+    pub fn get(&self, key: &Gc<T>) -> Option<&V> {
+        self.entries
+            .get(&key_addr(key))
+            .filter(|(ours, _)| ours.is_keyed_by(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Get a mutable reference to the value stored under `key`, if there is one.
+    pub fn get_mut(&mut self, key: &Gc<T>) -> Option<&mut V> {
+        self.entries
+            .get_mut(&key_addr(key))
+            .filter(|(ours, _)| ours.is_keyed_by(key))
+            .map(|(_, value)| value)
+    }
+
+    #[must_use]
+    /// Determine whether a value is stored under `key`.
+    pub fn contains_key(&self, key: &Gc<T>) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Remove the value stored under `key`, returning it if there was one.
+    pub fn remove(&mut self, key: &Gc<T>) -> Option<V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        self.entries.remove(&key_addr(key)).map(|(_, value)| value)
+    }
+
+    #[must_use]
+    /// Get the number of entries whose keys are still alive.
+    pub fn len(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|(key, _)| key.is_alive())
+            .count()
+    }
+
+    #[must_use]
+    /// Determine whether no entry's key is still alive.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the entries whose keys are still alive, in no particular order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{Gc, GcHashMap};
+    ///
+    /// let mut map = GcHashMap::new();
+    /// let key = Gc::new(());
+    /// map.insert(&key, 3);
+    ///
+    /// for (k, v) in map.iter() {
+    ///     assert!(Gc::ptr_eq(&k, &key));
+    ///     assert_eq!(*v, 3);
+    /// }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (Gc<T>, &V)> {
+        self.entries
+            .values()
+            .filter_map(|(key, value)| Some((key.key()?, value)))
+    }
+}
+
+impl<T: Collectable + ?Sized + 'static, V> Default for GcHashMap<T, V> {
+    fn default() -> GcHashMap<T, V> {
+        GcHashMap::new()
+    }
+}
+
+unsafe impl<T: Collectable + ?Sized + 'static, V: Collectable> Collectable for GcHashMap<T, V> {
+    fn accept<Vis: Visitor>(&self, visitor: &mut Vis) -> Result<(), ()> {
+        // the keys are not held by the map, but the values of entries whose keys were freed are,
+        // until they are pruned
+        self.entries
+            .values()
+            .try_for_each(|(_, value)| value.accept(visitor))
+    }
+}
+
+impl<T: Collectable + ?Sized + 'static, V: std::fmt::Debug> std::fmt::Debug for GcHashMap<T, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.iter().map(|(key, value)| (Gc::as_ptr(&key), value)))
+            .finish()
+    }
+}
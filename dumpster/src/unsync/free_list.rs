@@ -0,0 +1,164 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A per-thread list of freed allocations, whose memory is reused by new allocations of the same
+//! layout instead of going back through the global allocator.
+
+use std::{
+    alloc::{dealloc, Layout},
+    cell::{Cell, RefCell},
+    ptr::NonNull,
+};
+
+thread_local! {
+    /// The blocks of freed memory kept for reuse by this thread.
+    static FREE_LIST: FreeList = const {
+        FreeList {
+            capacity: Cell::new(0),
+            bytes: Cell::new(0),
+            buckets: RefCell::new(Vec::new()),
+            last: Cell::new(0),
+        }
+    };
+}
+
+/// Blocks of freed memory, sorted by layout, which new allocations of the same layout may reuse.
+struct FreeList {
+    /// The most bytes which may be kept at once, or 0 if no blocks are kept.
+    capacity: Cell<usize>,
+    /// The total size, in bytes, of the blocks kept.
+    bytes: Cell<usize>,
+    /// The blocks kept for each layout which has had any.
+    /// A program usually allocates only a few layouts, so these are searched in order.
+    buckets: RefCell<Vec<Bucket>>,
+    /// The index in `buckets` of the last bucket used, which is checked first.
+    last: Cell<usize>,
+}
+
+/// The blocks of freed memory kept for one layout.
+struct Bucket {
+    /// The layout with which every block in this bucket was allocated by the global allocator.
+    layout: Layout,
+    /// The most recently freed block, or `None` if there are none.
+    /// Each block holds a pointer to the one freed before it.
+    head: Option<NonNull<Link>>,
+}
+
+/// The contents of a block of memory while it is kept in a [`Bucket`].
+struct Link {
+    /// The block kept before this one.
+    next: Option<NonNull<Link>>,
+}
+
+impl FreeList {
+    /// Find the index of the bucket for `layout` in `buckets`.
+    fn find(&self, buckets: &[Bucket], layout: Layout) -> Option<usize> {
+        let last = self.last.get();
+        if buckets.get(last).is_some_and(|b| b.layout == layout) {
+            return Some(last);
+        }
+        let i = buckets.iter().position(|b| b.layout == layout)?;
+        self.last.set(i);
+        Some(i)
+    }
+
+    /// Give blocks back to the global allocator until no more than `capacity` bytes are kept.
+    fn trim(&self) {
+        for bucket in &mut *self.buckets.borrow_mut() {
+            while self.bytes.get() > self.capacity.get() {
+                let Some(block) = bucket.head else {
+                    break;
+                };
+                unsafe {
+                    bucket.head = block.as_ref().next;
+                    dealloc(block.as_ptr().cast(), bucket.layout);
+                }
+                self.bytes.set(self.bytes.get() - bucket.layout.size());
+            }
+        }
+    }
+}
+
+impl Drop for FreeList {
+    fn drop(&mut self) {
+        self.capacity.set(0);
+        self.trim();
+    }
+}
+
+/// Set the most bytes of freed memory which this thread keeps for reuse, giving back any blocks
+/// beyond it.
+pub(super) fn set_capacity(capacity: usize) {
+    FREE_LIST.with(|f| {
+        f.capacity.set(capacity);
+        f.trim();
+    });
+}
+
+/// Take a block of memory with `layout` from this thread's free list, if there is one.
+///
+/// The block is uninitialized, and was allocated by the global allocator with `layout`.
+pub(super) fn take(layout: Layout) -> Option<NonNull<u8>> {
+    FREE_LIST
+        .try_with(|f| {
+            if f.bytes.get() == 0 {
+                return None;
+            }
+            let mut buckets = f.buckets.borrow_mut();
+            let i = f.find(&buckets, layout)?;
+            let block = buckets[i].head?;
+            buckets[i].head = unsafe { block.as_ref() }.next;
+            f.bytes.set(f.bytes.get() - layout.size());
+            Some(block.cast())
+        })
+        .ok()
+        .flatten()
+}
+
+/// Free a block of memory, keeping it for reuse if this thread's free list has room for it.
+///
+/// # Safety
+///
+/// `ptr` must have been allocated by the global allocator with `layout`, which must be large
+/// enough and aligned enough to hold a pointer, and `ptr` must not be used again.
+pub(super) unsafe fn release(ptr: NonNull<u8>, layout: Layout) {
+    debug_assert!(layout.size() >= size_of::<Link>() && layout.align() >= align_of::<Link>());
+    // once the thread has started exiting, nothing more is kept
+    let kept = FREE_LIST
+        .try_with(|f| {
+            if f.bytes.get() + layout.size() > f.capacity.get() {
+                return false;
+            }
+            let mut buckets = f.buckets.borrow_mut();
+            let i = f.find(&buckets, layout).unwrap_or_else(|| {
+                buckets.push(Bucket { layout, head: None });
+                buckets.len() - 1
+            });
+            let block = ptr.cast::<Link>();
+            block.as_ptr().write(Link {
+                next: buckets[i].head,
+            });
+            buckets[i].head = Some(block);
+            f.bytes.set(f.bytes.get() + layout.size());
+            true
+        })
+        .unwrap_or(false);
+    if !kept {
+        dealloc(ptr.as_ptr(), layout);
+    }
+}
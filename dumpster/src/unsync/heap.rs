@@ -0,0 +1,274 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Heaps which are collected and torn down independently of the thread's default heap, and regions
+//! which tear one down when they end.
+
+use std::ptr::NonNull;
+
+use crate::Collectable;
+
+use super::{collect::Dumpster, CollectResult, Gc};
+
+/// A garbage-collected heap which is separate from the thread's default heap.
+///
+/// [`Gc::new`] allocates from the default heap of the current thread, which the free functions of
+/// this module, such as [`collect`](super::collect), act on.
+/// A `Heap` works the same way, but keeps its own dirty allocations, statistics and collect
+/// condition.
+/// Collecting it never looks at the allocations of any other heap, and collecting another heap
+/// never looks at its allocations.
+///
+/// A value in one heap may hold a `Gc` to an allocation in another.
+/// Each heap treats references from other heaps as roots, so a cycle which spans two heaps is
+/// never collected, and is only freed once one of the heaps is cleared.
+///
+/// # Clearing
+///
+/// [`Heap::clear`] drops the value of every allocation made from the heap, whether or not it is
+/// still reachable, and dropping the `Heap` does the same.
+/// Any `Gc` which still points into the heap becomes dead: dereferencing or cloning it panics,
+/// while [`Gc::try_deref`] and [`Gc::try_clone`] return `None`.
+/// The memory of a cleared allocation is freed once the last `Gc` to it is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{Gc, Heap};
+///
+/// let heap = Heap::new();
+/// let gc = heap.alloc(5);
+/// assert_eq!(*gc, 5);
+///
+/// heap.clear();
+/// assert!(Gc::try_deref(&gc).is_none());
+/// ```
+pub struct Heap {
+    /// The dumpster which tracks the allocations made from this heap.
+    /// It is owned by this heap, and freed when the heap is dropped.
+    dumpster: NonNull<Dumpster>,
+}
+
+impl Heap {
+    #[must_use]
+    /// Construct a new, empty heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::Heap;
+    ///
+    /// let heap = Heap::new();
+    /// ```
+    pub fn new() -> Heap {
+        Heap {
+            dumpster: Dumpster::new_heap(),
+        }
+    }
+
+    /// Get the dumpster of this heap.
+    fn dumpster(&self) -> &Dumpster {
+        unsafe { self.dumpster.as_ref() }
+    }
+
+    /// Construct a new garbage-collected allocation in this heap, with `value` as its value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::Heap;
+    ///
+    /// let heap = Heap::new();
+    /// let gc = heap.alloc("hello".to_owned());
+    /// assert_eq!(*gc, "hello");
+    /// ```
+    pub fn alloc<T: Collectable>(&self, value: T) -> Gc<T> {
+        Gc::allocate_in(self.dumpster(), value, None)
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    /// Collect all unreachable allocations in this heap.
+    ///
+    /// This does the same as [`collect`](super::collect), but for this heap instead of the default
+    /// one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::{unsync::Heap, Collectable};
+    /// use std::cell::RefCell;
+    ///
+    /// #[derive(Collectable)]
+    /// struct Cycle(RefCell<Option<dumpster::unsync::Gc<Cycle>>>);
+    ///
+    /// let heap = Heap::new();
+    /// let cyclic = heap.alloc(Cycle(RefCell::new(None)));
+    /// *cyclic.0.borrow_mut() = Some(cyclic.clone());
+    /// drop(cyclic);
+    ///
+    /// assert_eq!(heap.collect().allocations_freed, 1);
+    /// ```
+    pub fn collect(&self) -> CollectResult {
+        self.dumpster().collect_all()
+    }
+
+    /// Drop the value of every allocation in this heap, including the ones which are still
+    /// reachable.
+    ///
+    /// Finalizers are run first, as they would be by a collection.
+    /// Every `Gc` which still points into this heap is left dead afterward.
+    /// Allocations made from this heap while the values are dropped are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{Gc, Heap};
+    ///
+    /// let heap = Heap::new();
+    /// let gc = heap.alloc(vec![1, 2, 3]);
+    ///
+    /// heap.clear();
+    /// assert!(Gc::try_clone(&gc).is_none());
+    ///
+    /// // the heap can still be used after it is cleared
+    /// assert_eq!(*heap.alloc(4), 4);
+    /// ```
+    pub fn clear(&self) {
+        self.dumpster().clear();
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        Heap::new()
+    }
+}
+
+impl std::fmt::Debug for Heap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Heap")
+            .field("n_allocations", &self.dumpster().n_allocations.get())
+            .field("heap_bytes", &self.dumpster().heap_bytes.get())
+            .finish()
+    }
+}
+
+impl Drop for Heap {
+    /// Clear this heap, and then free it.
+    fn drop(&mut self) {
+        // dropping a value may make new allocations in this heap, which must be cleared as well
+        while self.dumpster().owns_any() {
+            self.dumpster().clear();
+        }
+        drop(unsafe { Box::from_raw(self.dumpster.as_ptr()) });
+    }
+}
+
+/// A scope whose allocations are all freed when it ends, as passed to the closure given to
+/// [`region`].
+///
+/// A `Region` is a [`Heap`] which is cleared once the closure returns.
+pub struct Region {
+    /// The heap which holds the allocations made through this region.
+    heap: Heap,
+}
+
+impl Region {
+    /// Construct a new garbage-collected allocation in this region, with `value` as its value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::region;
+    ///
+    /// let sum = region(|r| *r.alloc(1) + *r.alloc(2));
+    /// assert_eq!(sum, 3);
+    /// ```
+    pub fn alloc<T: Collectable>(&self, value: T) -> Gc<T> {
+        self.heap.alloc(value)
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    /// Collect all unreachable allocations in this region before it ends.
+    ///
+    /// See [`Heap::collect`].
+    pub fn collect(&self) -> CollectResult {
+        self.heap.collect()
+    }
+}
+
+impl std::fmt::Debug for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Region").field("heap", &self.heap).finish()
+    }
+}
+
+/// Run `f` with a new [`Region`], and free every allocation made through it once `f` returns,
+/// even the ones which are still part of a cycle.
+///
+/// This gives a scope the teardown of an arena while keeping the ergonomics of a [`Gc`]: nothing
+/// allocated through the region outlives the call, no matter how it is linked together, and no
+/// collection is needed to find it.
+/// The region is freed as described in [`Heap::clear`], even if `f` panics.
+///
+/// # Panics
+///
+/// With debug assertions enabled, this function panics if any `Gc` to an allocation in the region
+/// escaped it, such as by being returned from `f`, since that `Gc` is left dead once the region
+/// ends.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::region, Collectable};
+/// use std::cell::RefCell;
+///
+/// #[derive(Collectable)]
+/// struct Node {
+///     value: i32,
+///     edges: RefCell<Vec<dumpster::unsync::Gc<Node>>>,
+/// }
+///
+/// let sum = region(|r| {
+///     let a = r.alloc(Node {
+///         value: 1,
+///         edges: RefCell::new(Vec::new()),
+///     });
+///     let b = r.alloc(Node {
+///         value: 2,
+///         edges: RefCell::new(vec![a.clone()]),
+///     });
+///     a.edges.borrow_mut().push(b.clone());
+///     a.value + b.value
+/// });
+/// // the cycle between `a` and `b` has already been freed
+/// assert_eq!(sum, 3);
+/// ```
+pub fn region<R>(f: impl FnOnce(&Region) -> R) -> R {
+    let region = Region { heap: Heap::new() };
+    let result = f(&region);
+    #[cfg(debug_assertions)]
+    let escaped = region.heap.dumpster().count_escaped();
+    drop(region);
+    #[cfg(debug_assertions)]
+    assert!(
+        escaped == 0,
+        "{escaped} allocations escaped from a region, and are now dead"
+    );
+    result
+}
@@ -42,20 +42,41 @@
 //! foo.refs.borrow_mut().push(foo.clone());
 //! ```
 
+#[cfg(feature = "allocator-api")]
+use std::alloc::Allocator;
 use std::{
-    alloc::{dealloc, Layout},
+    alloc::{alloc, handle_alloc_error, Layout},
     borrow::Borrow,
     cell::Cell,
-    num::NonZeroUsize,
+    marker::PhantomData,
+    mem::ManuallyDrop,
     ops::Deref,
-    ptr::{addr_of, addr_of_mut, drop_in_place, NonNull},
+    ptr::{addr_of, addr_of_mut, drop_in_place, read, NonNull},
+    rc::Rc,
+    time::{Duration, Instant},
 };
 
-use crate::{contains_gcs, ptr::Nullable, Collectable, Visitor};
+#[cfg(feature = "allocator-api")]
+use crate::allocator::AllocHandle;
+use crate::{
+    contains_gcs, ratio,
+    reach::{GcId, PathFinder},
+    Collectable, CollectionMode, DryRunReport, Finalize, GcStats, Visitor,
+};
+
+use self::collect::{
+    is_fresh, note_allocated, note_destroyed, with_heap, AllocationId, Dumpster, HeapRef, DUMPSTER,
+};
 
-use self::collect::{Dumpster, COLLECTING, DUMPSTER};
+pub use self::{
+    ephemeron::{Ephemeron, GcHashMap, WeakKeyHashMap},
+    heap::{region, Heap, Region},
+};
 
 mod collect;
+mod ephemeron;
+mod free_list;
+mod heap;
 #[cfg(test)]
 mod tests;
 
@@ -79,23 +100,29 @@ mod tests;
 ///
 /// # Interaction with `Drop`
 ///
-/// While collecting cycles, it's possible for a `Gc` to exist that points to some deallocated
-/// object.
-/// To prevent undefined behavior, these `Gc`s are marked as dead during collection and rendered
-/// inaccessible.
+/// While collecting cycles, it's possible for a `Gc` to exist that points to some object whose
+/// value has been dropped.
+/// To prevent undefined behavior, such objects are marked as dead during collection, and their
+/// memory is kept until the last `Gc` to them is dropped.
 /// Dereferencing or cloning a `Gc` during the `Drop` implementation of a `Collectable` type could
 /// result in the program panicking to keep the program from accessing memory after freeing it.
 /// If you're accessing a `Gc` during a `Drop` implementation, make sure to use the fallible
 /// operations [`Gc::try_deref`] and [`Gc::try_clone`].
+///
+/// Once a collection has found an object to be unreachable, nothing can keep it alive: cloning a
+/// `Gc` to it panics, and [`Gc::try_clone`] returns `None`, so a `Drop` implementation cannot
+/// resurrect the object.
+/// A [finalizer](crate::Finalize) runs before the object is destroyed, and may resurrect it
+/// instead.
 pub struct Gc<T: Collectable + ?Sized + 'static> {
     /// A pointer to the heap allocation containing the data under concern.
     /// The pointee box should never be mutated.
     ///
-    /// If `ptr` is `None`, then this is a dead `Gc`, meaning that the allocation it points to has
+    /// If the allocation is dead, then so is this `Gc`, meaning that the value it points to has
     /// been dropped.
     /// This can only happen observably if this `Gc` is accessed during the [`Drop`] implementation
-    /// of a [`Collectable`] type.
-    ptr: Cell<Nullable<GcBox<T>>>,
+    /// of a [`Collectable`] type, or if its [`Heap`] has been cleared.
+    ptr: NonNull<GcBox<T>>,
 }
 
 /// Collect all existing unreachable allocations.
@@ -125,32 +152,778 @@ pub struct Gc<T: Collectable + ?Sized + 'static> {
 /// # Ok(())
 /// # }
 /// ```
-pub fn collect() {
-    DUMPSTER.with(Dumpster::collect_all);
+///
+/// The returned [`CollectResult`] describes what the collection did, which is useful when tuning
+/// a [`CollectCondition`]:
+///
+/// ```
+/// use dumpster::{
+///     unsync::{collect, Gc},
+///     Collectable,
+/// };
+/// use std::cell::RefCell;
+///
+/// #[derive(Collectable)]
+/// struct Cycle(RefCell<Option<Gc<Cycle>>>);
+///
+/// let cyclic = Gc::new(Cycle(RefCell::new(None)));
+/// *cyclic.0.borrow_mut() = Some(cyclic.clone());
+/// drop(cyclic);
+///
+/// let result = collect();
+/// assert_eq!(result.allocations_freed, 1);
+/// println!(
+///     "freed {} bytes in {:?}",
+///     result.bytes_freed, result.duration
+/// );
+/// ```
+pub fn collect() -> CollectResult {
+    DUMPSTER.with(Dumpster::collect_all)
 }
 
-/// Information passed to a [`CollectCondition`] used to determine whether the garbage collector
-/// should start collecting.
-pub struct CollectInfo {
-    /// Dummy value so this is a private structure.
-    _private: (),
+/// Do part of the work of a collection, stopping once `budget` has elapsed.
+///
+/// A large heap can take a long time to collect all at once.
+/// Instead, calling this function repeatedly (say, once per frame) spreads a single collection
+/// across many calls, picking up where the last call left off.
+/// The program may freely use and mutate the heap in between calls: the information gathered
+/// by earlier calls is checked again before anything is freed, so a live allocation is never
+/// freed.
+///
+/// At least one unit of work is done on each call, so repeated calls always finish eventually.
+/// The final call, which frees the garbage, is not interrupted; its duration grows with the amount
+/// of garbage found rather than with the size of the heap.
+///
+/// Calling [`collect`], or letting the collect condition trigger a collection, throws away the
+/// progress of any incremental collection.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{
+///     unsync::{collect_with_budget, CollectProgress, Gc},
+///     Collectable,
+/// };
+/// use std::{cell::RefCell, time::Duration};
+///
+/// #[derive(Collectable)]
+/// struct Cycle(RefCell<Option<Gc<Cycle>>>);
+///
+/// let cyclic = Gc::new(Cycle(RefCell::new(None)));
+/// *cyclic.0.borrow_mut() = Some(cyclic.clone());
+/// drop(cyclic);
+///
+/// // in a real program, this would be spread across many frames
+/// while collect_with_budget(Duration::from_micros(100)) == CollectProgress::Pending {}
+/// ```
+pub fn collect_with_budget(budget: Duration) -> CollectProgress {
+    let deadline = Instant::now().checked_add(budget);
+    DUMPSTER.with(|d| {
+        d.collect_incremental(|| deadline.is_some_and(|deadline| Instant::now() >= deadline))
+    })
 }
 
-/// A function which determines whether the garbage collector should start collecting.
-/// This function primarily exists so that it can be used with [`set_collect_condition`].
+/// Do part of the work of a collection, stopping after `units` units of work.
+///
+/// This behaves like [`collect_with_budget`], but measures work instead of time, which makes it
+/// deterministic.
+/// A unit of work is roughly the cost of visiting a single allocation.
+/// At least one unit of work is always done, even if `units` is zero.
 ///
 /// # Examples
 ///
-/// ```rust
-/// use dumpster::unsync::{set_collect_condition, CollectInfo};
+/// ```
+/// use dumpster::unsync::{collect_with_work, CollectProgress, Gc};
+///
+/// let gc = Gc::new(0);
+/// drop(gc);
+///
+/// while collect_with_work(16) == CollectProgress::Pending {}
+/// ```
+pub fn collect_with_work(units: usize) -> CollectProgress {
+    let mut remaining = units;
+    DUMPSTER.with(|d| {
+        d.collect_incremental(|| {
+            remaining = remaining.saturating_sub(1);
+            remaining == 0
+        })
+    })
+}
+
+/// Collect the garbage which can be found without searching the parts of the heap that have not
+/// changed since the last full collection.
+///
+/// Every [`collect`] is a full collection, which searches everything reachable from the
+/// allocations which have lost a reference since the last collection.
+/// Once partial collections are in use, each full collection also remembers which allocations it
+/// found to be reachable.
+/// A partial collection treats every one of those which has not lost a reference since as still
+/// reachable, and does not search inside it, so a program with a large live set which rarely
+/// changes only pays for the part of the heap that did.
+///
+/// Any garbage which is freed by a full collection is eventually freed by partial ones, except for
+/// cycles which run through an allocation that a full collection found to be reachable: those are
+/// left for the next full collection.
+/// Use [`set_full_collection_interval`] to make automatic collections partial while still running
+/// a full one every so often.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{
+///     unsync::{collect_partial, Gc},
+///     Collectable,
+/// };
+/// use std::cell::RefCell;
+///
+/// #[derive(Collectable)]
+/// struct Cycle(RefCell<Option<Gc<Cycle>>>);
+///
+/// let cyclic = Gc::new(Cycle(RefCell::new(None)));
+/// *cyclic.0.borrow_mut() = Some(cyclic.clone());
+/// drop(cyclic);
+///
+/// assert_eq!(collect_partial().allocations_freed, 1);
+/// ```
+pub fn collect_partial() -> CollectResult {
+    DUMPSTER.with(Dumpster::collect_partial)
+}
+
+/// Make only every `interval`-th automatic collection on this thread a full collection, and the
+/// others partial, as if by [`collect_partial`].
+///
+/// The default interval is 1, which makes every automatic collection a full one; passing 0 does the
+/// same.
+/// Every partial collection counts towards the next full one, including calls to
+/// [`collect_partial`], and explicit calls to [`collect`] are always full collections which restart
+/// the count.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{set_full_collection_interval, Gc};
+///
+/// // a full collection for every 7 partial ones
+/// set_full_collection_interval(8);
+///
+/// let gcs = (0..1000).map(Gc::new).collect::<Vec<_>>();
+/// drop(gcs);
+/// ```
+pub fn set_full_collection_interval(interval: usize) {
+    DUMPSTER.with(|d| {
+        d.full_interval.set(interval);
+        if interval > 1 {
+            d.stamp_live.set(true);
+        }
+    });
+}
+
+#[must_use]
+/// Get a snapshot of the garbage collector's statistics for this thread.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{stats, Gc};
+///
+/// let gc = Gc::new(0);
+/// assert!(stats().live_allocations >= 1);
+/// ```
+pub fn stats() -> GcStats {
+    DUMPSTER.with(|d| GcStats {
+        collections: d.n_collections.get(),
+        allocations_freed: d.total_freed.get(),
+        bytes_freed: d.total_bytes_freed.get(),
+        live_allocations: d.n_allocations.get(),
+        live_bytes: d.heap_bytes.get(),
+        time_collecting: d.time_collecting.get(),
+    })
+}
+
+/// Reset the cumulative statistics returned by [`stats`] for this thread to zero.
+///
+/// The number and size of live allocations are not affected.
+pub fn reset_stats() {
+    DUMPSTER.with(|d| {
+        d.n_collections.set(0);
+        d.total_freed.set(0);
+        d.total_bytes_freed.set(0);
+        d.time_collecting.set(Duration::ZERO);
+    });
+}
+
+/// Reserve room in this thread's garbage collector for at least `additional` more allocations.
+///
+/// After reserving, the table of allocations which may need to be collected can grow by
+/// `additional` entries without reallocating, and the next collections will allocate their
+/// working memory up front for that many more allocations than exist now.
+/// This is useful ahead of a burst of allocation and deallocation, to avoid paying for rehashing in
+/// the middle of it.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{dirty_capacity, reserve};
+///
+/// reserve(1000);
+/// assert!(dirty_capacity() >= 1000);
+/// ```
+pub fn reserve(additional: usize) {
+    DUMPSTER.with(|d| d.reserve(additional));
+}
+
+#[allow(clippy::must_use_candidate)]
+/// Set the number of allocations which this thread's garbage collector will have room for when it
+/// is first used.
+///
+/// This must be called before any [`Gc`] is created or dropped on this thread, and before any other
+/// function in this module is called.
+/// Returns `true` if the capacity was set, and `false` if this thread's garbage collector was
+/// already in use, in which case [`reserve`] must be used instead.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{dirty_capacity, set_initial_capacity, Gc};
+///
+/// std::thread::spawn(|| {
+///     assert!(set_initial_capacity(1000));
+///     let gc = Gc::new(0);
+///     assert!(!set_initial_capacity(2000));
+///     assert!(dirty_capacity() >= 1000);
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub fn set_initial_capacity(capacity: usize) -> bool {
+    collect::INITIAL_CAPACITY.with(|c| {
+        let unused = c.get().is_some();
+        if unused {
+            c.set(Some(capacity));
+        }
+        unused
+    })
+}
+
+#[must_use]
+/// Get the number of allocations which may need to be collected that this thread's garbage
+/// collector can track without reallocating.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{dirty_capacity, reserve};
+///
+/// reserve(100);
+/// assert!(dirty_capacity() >= 100);
+/// ```
+pub fn dirty_capacity() -> usize {
+    DUMPSTER.with(Dumpster::dirty_capacity)
+}
+
+/// Keep up to `bytes` bytes of memory from freed allocations on this thread for reuse by new
+/// allocations of the same layout.
+///
+/// A program which allocates and frees many values of the same few types spends much of its time
+/// in the global allocator.
+/// With a free list, the memory of each freed allocation is instead set aside, and the next
+/// allocation of the same size and alignment on this thread takes it back, so long as the free
+/// list holds no more than `bytes` bytes in total.
+/// Memory which does not fit is given back to the global allocator as usual.
+///
+/// The free list is off by default, which is the same as a capacity of 0.
+/// Lowering the capacity gives back whatever memory no longer fits, and the rest is given back
+/// when the thread exits.
+/// Memory held by the free list is not part of any heap, so it does not count towards
+/// [`GcStats::live_bytes`] or [`CollectInfo::heap_bytes`].
+///
+/// # Examples
 ///
-/// fn always_collect(_: &CollectInfo) -> bool {
-///     true
+/// ```
+/// use dumpster::unsync::{set_free_list_capacity, stats, Gc};
+///
+/// set_free_list_capacity(1 << 20);
+/// let before = stats().live_bytes;
+/// for i in 0..1000 {
+///     // each allocation reuses the memory of the one before it
+///     drop(Gc::new(i));
+/// }
+/// assert_eq!(stats().live_bytes, before);
+/// set_free_list_capacity(0);
+/// ```
+pub fn set_free_list_capacity(bytes: usize) {
+    free_list::set_capacity(bytes);
+}
+
+/// Find the allocations which a collection on this thread would free right now, without freeing
+/// anything.
+///
+/// This searches the heap for garbage exactly as [`collect`] does, but instead of destroying the
+/// garbage, it returns a description of every allocation it found.
+/// No finalizers are run, so an allocation which a [finalizer](crate::Finalize) would resurrect
+/// is still listed.
+/// Like a collection, this only looks at allocations which may have become unreachable since the
+/// last collection.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::{collect, collect_dry_run, Gc}, Collectable};
+/// use std::cell::RefCell;
+///
+/// #[derive(Collectable)]
+/// struct Cycle(RefCell<Option<Gc<Cycle>>>);
+///
+/// let gc = Gc::new(Cycle(RefCell::new(None)));
+/// *gc.0.borrow_mut() = Some(gc.clone());
+/// let addr = Gc::as_ptr(&gc) as usize;
+/// drop(gc);
+///
+/// let report = collect_dry_run();
+/// assert_eq!(report.count(), 1);
+/// assert_eq!(report.allocations[0].addr, addr);
+///
+/// // nothing was freed, so the cycle is still there to be collected
+/// assert_eq!(collect().allocations_freed, 1);
+/// ```
+pub fn collect_dry_run() -> DryRunReport {
+    DUMPSTER.with(Dumpster::collect_dry_run)
+}
+
+/// Set whether collections on this thread destroy unreachable allocations in the order in which
+/// they were allocated.
+///
+/// By default, the order in which a collection drops garbage is unspecified, and may change from
+/// one run of a program to the next.
+/// When this is enabled, finalizers are run and values are dropped oldest first, so that the side
+/// effects of a collection can be reproduced exactly.
+/// Only allocations made while this is enabled are ordered: the garbage allocated before it was
+/// enabled is destroyed first, in no particular order.
+/// This makes collections somewhat slower.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::{collect, set_deterministic, Gc}, Collectable};
+/// use std::cell::{Cell, RefCell};
+///
+/// thread_local! {
+///     static DROPPED: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+/// }
+///
+/// #[derive(Collectable)]
+/// struct Node {
+///     id: u8,
+///     next: RefCell<Option<Gc<Node>>>,
+/// }
+///
+/// impl Drop for Node {
+///     fn drop(&mut self) {
+///         DROPPED.with(|d| d.borrow_mut().push(self.id));
+///     }
 /// }
 ///
-/// set_collect_condition(always_collect);
+/// set_deterministic(true);
+///
+/// let nodes = (0..4)
+///     .map(|id| Gc::new(Node { id, next: RefCell::new(None) }))
+///     .collect::<Vec<_>>();
+/// for (i, node) in nodes.iter().enumerate() {
+///     *node.next.borrow_mut() = Some(nodes[(i + 3) % 4].clone());
+/// }
+/// drop(nodes);
+///
+/// collect();
+/// assert_eq!(DROPPED.with(|d| d.take()), [0, 1, 2, 3]);
+/// ```
+pub fn set_deterministic(deterministic: bool) {
+    DUMPSTER.with(|d| d.deterministic.set(deterministic));
+}
+
+#[cfg(feature = "leak-detection")]
+#[must_use]
+/// Take the report of every allocation which a thread could not free before it exited, leaving an
+/// empty report in its place.
+///
+/// When a thread exits, its garbage collector runs one last collection.
+/// Any allocations which still exist afterward can never be freed, and are added to this report.
+/// This is only available with the `leak-detection` feature.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{take_leak_report, Gc};
+///
+/// std::thread::spawn(|| std::mem::forget(Gc::new(0u32)))
+///     .join()
+///     .unwrap();
+///
+/// let report = take_leak_report();
+/// assert_eq!(report.count(), 1);
+/// assert_eq!(report.allocations[0].type_name, "u32");
+/// ```
+pub fn take_leak_report() -> crate::LeakReport {
+    std::mem::take(&mut *collect::LEAKS.lock())
+}
+
+#[cfg(feature = "heap-dump")]
+/// Write every garbage-collected allocation on this thread to `writer` as a Graphviz digraph.
+///
+/// Each allocation is drawn as one node, labeled with the type of its value, its size, and its
+/// reference count.
+/// Each `Gc` owned by an allocation is drawn as an edge to the allocation it points to, labeled
+/// with the fields through which it was found when the value's type reports them (as
+/// `#[derive(Collectable)]` does).
+/// Allocations are identified by the address of their value, as returned by [`Gc::as_ptr`].
+///
+/// The heap is only inspected, not modified, so this does not collect anything and may be called
+/// at any time outside of a `Drop` implementation.
+/// A value which cannot be inspected right now (such as one inside a mutably borrowed
+/// [`RefCell`](std::cell::RefCell)) is drawn without its outgoing edges.
+/// This is only available with the `heap-dump` feature.
+///
+/// # Errors
+///
+/// This function returns an error if writing to `writer` fails.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{dump_heap_dot, Gc};
+///
+/// let gc = Gc::new(0u32);
+/// let mut dot = Vec::new();
+/// dump_heap_dot(&mut dot).unwrap();
+///
+/// let dot = String::from_utf8(dot).unwrap();
+/// assert!(dot.starts_with("digraph"));
+/// assert!(dot.contains(&format!("{:p}", Gc::as_ptr(&gc))));
+/// ```
+pub fn dump_heap_dot(writer: impl std::io::Write) -> std::io::Result<()> {
+    crate::dot::write_dot(writer, DUMPSTER.with(Dumpster::describe_live))
+}
+
+#[cfg(feature = "heap-inspection")]
+/// Iterate over a snapshot of every garbage-collected allocation on this thread.
+///
+/// The snapshot is taken all at once when this function is called, so `Gc`s may be created and
+/// dropped freely while iterating.
+/// This includes allocations which are garbage but have not been collected yet.
+/// This may be called at any time outside of a `Drop` implementation.
+/// This is only available with the `heap-inspection` feature.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect, iter_allocations, Gc};
+///
+/// let gc = Gc::new(0u32);
+/// let addr = Gc::as_ptr(&gc) as usize;
+/// let info = iter_allocations().find(|info| info.addr == addr).unwrap();
+/// assert_eq!(info.type_name, "u32");
+/// assert_eq!(info.ref_count, 1);
+///
+/// drop(gc);
+/// collect();
+/// assert!(iter_allocations().all(|info| info.addr != addr));
+/// ```
+pub fn iter_allocations() -> impl Iterator<Item = crate::AllocationInfo> {
+    DUMPSTER.with(Dumpster::inspect_live).into_iter()
+}
+
+#[cfg(feature = "heap-inspection")]
+#[must_use]
+/// Count the garbage-collected allocations on this thread by the type of their value.
+///
+/// Each entry of the census holds the name of a type, as given by [`std::any::type_name`], the
+/// number of allocations storing that type, and their total size in bytes.
+/// The entries are sorted by type name, and types with no allocations are left out.
+/// This includes allocations which are garbage but have not been collected yet.
+///
+/// The census is built from the table which the collector already keeps of every allocation,
+/// without looking at any values, so it may be called at any time, even from a `Drop`
+/// implementation.
+/// This is only available with the `heap-inspection` feature.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{type_census, Gc};
+///
+/// let gcs = [Gc::new(1u32), Gc::new(2u32)];
+/// let census = type_census();
+/// let (_, count, _) = census.iter().find(|(name, ..)| name == "u32").unwrap();
+/// assert_eq!(*count, 2);
 /// ```
-pub type CollectCondition = fn(&CollectInfo) -> bool;
+pub fn type_census() -> Vec<(String, usize, usize)> {
+    DUMPSTER.with(Dumpster::census)
+}
+
+#[must_use]
+/// Determine whether `target` can be reached from `root` by following `Gc`s.
+///
+/// This is useful for finding out why an allocation is still alive: if some allocation which is
+/// itself reachable can reach `target`, then `target` will not be collected.
+/// Every allocation is reachable from itself.
+/// See [`path_from`] for the allocations on the way from `root` to `target`.
+///
+/// The search traces each allocation at most once, so it terminates even if the heap has cycles.
+/// It only reads the heap, and never changes the state of the garbage collector.
+/// Values which cannot be traced right now (such as those inside a mutably borrowed
+/// [`RefCell`](std::cell::RefCell)) are treated as though they
+/// own no `Gc`s.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::{is_reachable_from, Gc}, Collectable};
+///
+/// #[derive(Collectable)]
+/// struct Node(Vec<Gc<Node>>);
+///
+/// let leaf = Gc::new(Node(Vec::new()));
+/// let parent = Gc::new(Node(vec![leaf.clone()]));
+/// assert!(is_reachable_from(&parent, &leaf));
+/// assert!(!is_reachable_from(&leaf, &parent));
+/// ```
+pub fn is_reachable_from<T, U>(root: &Gc<T>, target: &Gc<U>) -> bool
+where
+    T: Collectable + ?Sized,
+    U: Collectable + ?Sized,
+{
+    path_from(root, target).is_some()
+}
+
+#[must_use]
+/// Find a way to reach `target` from `root` by following `Gc`s.
+///
+/// The path lists the allocations passed through on the way, starting with `root` and ending with
+/// `target`, or is `None` if `target` cannot be reached from `root`.
+/// No guarantee is made about which path is found if there are several.
+/// The search works as described in [`is_reachable_from`].
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{unsync::{path_from, Gc}, Collectable};
+///
+/// #[derive(Collectable)]
+/// struct Node(Vec<Gc<Node>>);
+///
+/// let leaf = Gc::new(Node(Vec::new()));
+/// let middle = Gc::new(Node(vec![leaf.clone()]));
+/// let root = Gc::new(Node(vec![middle.clone()]));
+///
+/// let path = path_from(&root, &leaf).unwrap();
+/// let addrs = path.iter().map(|id| id.addr()).collect::<Vec<_>>();
+/// assert_eq!(
+///     addrs,
+///     [&root, &middle, &leaf].map(|gc| Gc::as_ptr(gc).cast::<()>() as usize),
+/// );
+/// ```
+pub fn path_from<T, U>(root: &Gc<T>, target: &Gc<U>) -> Option<Vec<GcId>>
+where
+    T: Collectable + ?Sized,
+    U: Collectable + ?Sized,
+{
+    let target = GcId::of(Gc::try_deref(target)?);
+    PathFinder::search(Gc::try_deref(root)?, target)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[must_use]
+/// The state of an incremental collection, as returned by [`collect_with_budget`] and
+/// [`collect_with_work`].
+pub enum CollectProgress {
+    /// The collection finished.
+    Finished {
+        /// The number of allocations which were freed.
+        freed: usize,
+    },
+    /// The collection ran out of budget before finishing, and will continue on the next call.
+    Pending,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// A summary of the work done by a single collection, as returned by [`collect`].
+pub struct CollectResult {
+    /// The number of allocations which the collector traversed while looking for garbage.
+    pub allocations_examined: usize,
+    /// The number of allocations which were found to be unreachable and freed.
+    pub allocations_freed: usize,
+    /// The total size, in bytes, of the allocations which were freed.
+    ///
+    /// This counts the whole allocation backing each [`Gc`], including its reference count.
+    pub bytes_freed: usize,
+    /// How long the collection took.
+    pub duration: Duration,
+}
+
+/// Information passed to a collect condition used to determine whether the garbage collector
+/// should start collecting.
+pub struct CollectInfo {
+    /// The heap whose collection is being considered.
+    heap: HeapRef,
+}
+
+#[derive(Clone)]
+/// A condition which determines whether the garbage collector should start collecting, built out
+/// of common policies.
+///
+/// Conditions are combined with [`CollectCondition::and`] and [`CollectCondition::or`], and
+/// installed with [`set_collect_condition_with`].
+/// Building a condition allocates, but checking one never does, so even an elaborate condition is
+/// cheap to check every time a [`Gc`] is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{set_collect_condition_with, CollectCondition};
+///
+/// // collect when the heap is over 64 MiB, or after every 10,000 drops, whichever comes first
+/// set_collect_condition_with(
+///     CollectCondition::heap_bytes_above(64 << 20).or(CollectCondition::every_n_drops(10_000)),
+/// );
+/// ```
+pub struct CollectCondition(Rc<Condition>);
+
+/// The policies out of which a [`CollectCondition`] is built.
+enum Condition {
+    /// Never collect.
+    Never,
+    /// Collect as [`default_collect_condition`] does.
+    Default,
+    /// Collect once at least this many `Gc`s have been dropped since the last collection.
+    EveryNDrops(usize),
+    /// Collect once more than this many times as many `Gc`s have been dropped since the last
+    /// collection as now exist.
+    DropRatio(f64),
+    /// Collect whenever the heap is larger than this many bytes.
+    HeapBytesAbove(usize),
+    /// Collect when both conditions say so.
+    And(CollectCondition, CollectCondition),
+    /// Collect when either condition says so.
+    Or(CollectCondition, CollectCondition),
+    /// Collect when a function says so.
+    Custom(Box<dyn Fn(&CollectInfo) -> bool>),
+}
+
+impl CollectCondition {
+    #[must_use]
+    /// Construct a condition which never starts a collection.
+    ///
+    /// Garbage is then only freed by explicit calls to [`collect`].
+    pub fn never() -> CollectCondition {
+        CollectCondition(Rc::new(Condition::Never))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection once at least `n` [`Gc`]s have been dropped
+    /// since the last collection.
+    pub fn every_n_drops(n: usize) -> CollectCondition {
+        CollectCondition(Rc::new(Condition::EveryNDrops(n)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection once more than `ratio` times as many
+    /// [`Gc`]s have been dropped since the last collection as now exist.
+    ///
+    /// Unlike the default condition, which adapts its ratio to how much each collection frees,
+    /// this condition always uses `ratio`.
+    /// A smaller ratio collects more often, which suits heaps where most dropped `Gc`s leave
+    /// behind unreachable cycles, and a larger one collects less often, which suits heaps where
+    /// most allocations are freed by their reference counts alone.
+    /// The ratio the default condition starts with is `1.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{set_collect_condition_with, CollectCondition};
+    ///
+    /// // collect once four times as many `Gc`s have been dropped as exist
+    /// set_collect_condition_with(CollectCondition::drop_ratio(4.0));
+    /// ```
+    pub fn drop_ratio(ratio: f64) -> CollectCondition {
+        CollectCondition(Rc::new(Condition::DropRatio(ratio)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection whenever the garbage-collected heap is larger
+    /// than `bytes`, as [`collect_when_heap_exceeds`] does.
+    pub fn heap_bytes_above(bytes: usize) -> CollectCondition {
+        CollectCondition(Rc::new(Condition::HeapBytesAbove(bytes)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection whenever `f` returns `true`.
+    pub fn custom(f: impl Fn(&CollectInfo) -> bool + 'static) -> CollectCondition {
+        CollectCondition(Rc::new(Condition::Custom(Box::new(f))))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection only when both `self` and `other` would.
+    ///
+    /// `other` is not checked if `self` returns `false`.
+    pub fn and(self, other: CollectCondition) -> CollectCondition {
+        CollectCondition(Rc::new(Condition::And(self, other)))
+    }
+
+    #[must_use]
+    /// Construct a condition which starts a collection when either `self` or `other` would.
+    ///
+    /// `other` is not checked if `self` returns `true`.
+    pub fn or(self, other: CollectCondition) -> CollectCondition {
+        CollectCondition(Rc::new(Condition::Or(self, other)))
+    }
+
+    #[must_use]
+    /// Determine whether this condition would start a collection, given the state of the heap in
+    /// `info`.
+    pub fn should_collect(&self, info: &CollectInfo) -> bool {
+        match &*self.0 {
+            Condition::Never => false,
+            Condition::Default => default_collect_condition(info),
+            Condition::EveryNDrops(n) => info.n_gcs_dropped_since_last_collect() >= *n,
+            Condition::DropRatio(ratio) => ratio::exceeds(
+                info.n_gcs_dropped_since_last_collect(),
+                info.n_gcs_existing(),
+                *ratio,
+            ),
+            Condition::HeapBytesAbove(bytes) => info.heap_bytes() > *bytes,
+            Condition::And(a, b) => a.should_collect(info) && b.should_collect(info),
+            Condition::Or(a, b) => a.should_collect(info) || b.should_collect(info),
+            Condition::Custom(f) => f(info),
+        }
+    }
+}
+
+impl Default for CollectCondition {
+    /// Construct a condition which behaves like [`default_collect_condition`].
+    fn default() -> CollectCondition {
+        CollectCondition(Rc::new(Condition::Default))
+    }
+}
+
+impl std::fmt::Debug for CollectCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &*self.0 {
+            Condition::Never => f.write_str("Never"),
+            Condition::Default => f.write_str("Default"),
+            Condition::EveryNDrops(n) => f.debug_tuple("EveryNDrops").field(n).finish(),
+            Condition::DropRatio(ratio) => f.debug_tuple("DropRatio").field(ratio).finish(),
+            Condition::HeapBytesAbove(bytes) => {
+                f.debug_tuple("HeapBytesAbove").field(bytes).finish()
+            }
+            Condition::And(a, b) => f.debug_tuple("And").field(a).field(b).finish(),
+            Condition::Or(a, b) => f.debug_tuple("Or").field(a).field(b).finish(),
+            Condition::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// A collect condition which may capture state, as returned by [`set_collect_condition_boxed`].
+pub type BoxedCollectCondition = Box<dyn Fn(&CollectInfo) -> bool>;
 
 #[must_use]
 /// The default collection condition used by the garbage collector.
@@ -158,6 +931,13 @@ pub type CollectCondition = fn(&CollectInfo) -> bool;
 /// There are no guarantees about what this function returns, other than that it will return `true`
 /// with sufficient frequency to ensure that all `Gc` operations are amortized _O(1)_ in runtime.
 ///
+/// Currently, it collects once more than [`CollectInfo::drop_ratio`] times as many `Gc`s have
+/// been dropped since the last collection as now exist.
+/// That ratio adapts to the heap: it grows after collections which free little, so that heaps
+/// whose garbage is mostly freed by reference counting are collected rarely, and shrinks after
+/// collections which free a lot, so that heaps full of unreachable cycles are collected often.
+/// To use a fixed ratio instead, see [`CollectCondition::drop_ratio`].
+///
 /// This function isn't really meant to be called by users, but rather it's supposed to be handed
 /// off to [`set_collect_condition`] to return to the default operating mode of the library.
 ///
@@ -166,49 +946,510 @@ pub type CollectCondition = fn(&CollectInfo) -> bool;
 ///
 /// # Examples
 ///
-/// ```rust
-/// use dumpster::unsync::{default_collect_condition, set_collect_condition};
+/// ```rust
+/// use dumpster::unsync::{default_collect_condition, set_collect_condition};
+///
+/// set_collect_condition(default_collect_condition);
+/// ```
+pub fn default_collect_condition(info: &CollectInfo) -> bool {
+    ratio::exceeds(
+        info.n_gcs_dropped_since_last_collect(),
+        info.n_gcs_existing(),
+        info.drop_ratio(),
+    )
+}
+
+/// Construct a collection condition which starts a collection whenever the garbage-collected heap
+/// on this thread is larger than `bytes`.
+///
+/// The size of the heap is measured as in [`CollectInfo::heap_bytes`].
+/// This is a better measure of memory pressure than the default condition when the sizes of
+/// allocations vary a lot.
+///
+/// If the live data alone is larger than `bytes`, every drop of a [`Gc`] will start a collection,
+/// so `bytes` should be chosen with some headroom above the expected amount of live data.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect_when_heap_exceeds, set_collect_condition_boxed};
+///
+/// // collect whenever the heap grows past 64 MiB
+/// set_collect_condition_boxed(collect_when_heap_exceeds(64 << 20));
+/// ```
+pub fn collect_when_heap_exceeds(
+    bytes: usize,
+) -> impl Fn(&CollectInfo) -> bool + Copy + Send + Sync + 'static {
+    move |info| info.heap_bytes() > bytes
+}
+
+#[allow(clippy::missing_panics_doc)]
+/// Set the function which determines whether the garbage collector should be run.
+///
+/// `f` will be periodically called by the garbage collector to determine whether it should perform
+/// a full cleanup of the heap.
+/// When `f` returns true, a cleanup will begin.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{set_collect_condition, CollectInfo};
+///
+/// /// This function will make sure a GC cleanup never happens unless directly activated.
+/// fn never_collect(_: &CollectInfo) -> bool {
+///     false
+/// }
+///
+/// set_collect_condition(never_collect);
+/// ```
+pub fn set_collect_condition(f: fn(&CollectInfo) -> bool) {
+    set_collect_condition_with(CollectCondition::custom(f));
+}
+
+/// Set the condition which determines whether the garbage collector should be run.
+///
+/// This works like [`set_collect_condition`], but takes a [`CollectCondition`], which may be
+/// combined out of several policies.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{set_collect_condition_with, CollectCondition};
+///
+/// set_collect_condition_with(CollectCondition::never());
+/// // dropping a `Gc` will now never start a collection
+/// set_collect_condition_with(CollectCondition::default());
+/// ```
+pub fn set_collect_condition_with(condition: CollectCondition) {
+    DUMPSTER.with(|d| d.collect_condition.replace(condition));
+}
+
+#[allow(clippy::must_use_candidate)]
+/// Set a closure which determines whether the garbage collector should be run, returning the
+/// condition which was previously set.
+///
+/// This works like [`set_collect_condition`], except that `f` may capture state, and that the
+/// previous condition is handed back so that it can be restored later.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{set_collect_condition_boxed, CollectInfo};
+/// use std::{cell::Cell, rc::Rc};
+///
+/// let enabled = Rc::new(Cell::new(true));
+/// let switch = enabled.clone();
+/// let previous = set_collect_condition_boxed(move |info: &CollectInfo| {
+///     switch.get() && info.n_gcs_dropped_since_last_collect() > 100
+/// });
+///
+/// // turn off automatic collection for a while
+/// enabled.set(false);
+///
+/// // go back to whatever was there before
+/// set_collect_condition_boxed(previous);
+/// ```
+pub fn set_collect_condition_boxed(
+    f: impl Fn(&CollectInfo) -> bool + 'static,
+) -> BoxedCollectCondition {
+    let previous = DUMPSTER.with(|d| d.collect_condition.replace(CollectCondition::custom(f)));
+    Box::new(move |info| previous.should_collect(info))
+}
+
+/// Set the hooks which are called around every full collection on this thread, replacing any
+/// hooks which were previously set.
+///
+/// To remove the hooks, set them to [`CollectHooks::default`].
+/// Hooks may be replaced from inside a hook, in which case the new hooks take effect at the next
+/// collection.
+///
+/// A hook must not try to start another collection: any collection requested while a hook is
+/// running, whether explicitly through [`collect`] or by dropping a [`Gc`], does nothing.
+/// Incremental collections (see [`collect_with_budget`]) do not call the hooks.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect, set_collect_hooks, CollectHooks};
+/// use std::cell::Cell;
+///
+/// thread_local! {
+///     static N_COLLECTIONS: Cell<usize> = const { Cell::new(0) };
+/// }
+///
+/// set_collect_hooks(CollectHooks::new().on_end(|_| N_COLLECTIONS.with(|n| n.set(n.get() + 1))));
+/// collect();
+/// assert_eq!(N_COLLECTIONS.with(Cell::get), 1);
+/// ```
+pub fn set_collect_hooks(hooks: CollectHooks) {
+    DUMPSTER.with(|d| d.hooks.replace(Some(Rc::new(hooks))));
+}
+
+/// A hook called when a collection starts.
+type StartHook = Box<dyn Fn(&CollectInfo)>;
+/// A hook called when a collection finishes.
+type EndHook = Box<dyn Fn(&CollectResult)>;
+
+#[derive(Default)]
+/// Callbacks which are called around every full collection, as set by [`set_collect_hooks`].
+pub struct CollectHooks {
+    /// Called before each collection.
+    on_start: Option<StartHook>,
+    /// Called after each collection.
+    on_end: Option<EndHook>,
+}
+
+impl CollectHooks {
+    #[must_use]
+    /// Construct a set of hooks which do nothing.
+    pub fn new() -> CollectHooks {
+        CollectHooks::default()
+    }
+
+    #[must_use]
+    /// Set the hook which is called before each collection.
+    pub fn on_start(mut self, f: impl Fn(&CollectInfo) + 'static) -> CollectHooks {
+        self.on_start = Some(Box::new(f));
+        self
+    }
+
+    #[must_use]
+    /// Set the hook which is called after each collection, with a summary of its work.
+    pub fn on_end(mut self, f: impl Fn(&CollectResult) + 'static) -> CollectHooks {
+        self.on_end = Some(Box::new(f));
+        self
+    }
+}
+
+impl std::fmt::Debug for CollectHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CollectHooks")
+            .field("on_start", &self.on_start.is_some())
+            .field("on_end", &self.on_end.is_some())
+            .finish()
+    }
+}
+
+#[must_use = "collection is only paused while the guard is alive"]
+/// Prevent dropping a [`Gc`] from starting a collection on this thread until the returned guard is
+/// dropped.
+///
+/// While any guard is alive, dropping a `Gc` still records that it was dropped, but the collect
+/// condition is not consulted.
+/// When the last guard on this thread is dropped, the collect condition is checked once, and a
+/// collection is run if it returns `true`.
+/// This is useful for short sections of code where an unexpected collection would take too long.
+///
+/// Guards may be nested.
+/// Explicit calls to [`collect`] are not affected by a guard.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{pause_collection, Gc};
+///
+/// let gc = Gc::new(0);
+/// let guard = pause_collection();
+/// // this cannot cause a collection
+/// drop(gc);
+/// // but this can
+/// drop(guard);
+/// ```
+pub fn pause_collection() -> PauseGuard {
+    DUMPSTER.with(|d| d.n_pauses.set(d.n_pauses.get() + 1));
+    PauseGuard {
+        _not_send: PhantomData,
+    }
+}
+
+#[derive(Debug)]
+/// A guard which prevents automatic collections on this thread while it is alive.
+///
+/// This is created by [`pause_collection`].
+pub struct PauseGuard {
+    /// The guard must be dropped on the thread which created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        let _ = DUMPSTER.try_with(|d| {
+            d.n_pauses.set(d.n_pauses.get() - 1);
+            if d.n_pauses.get() == 0 {
+                d.maybe_collect();
+            }
+        });
+    }
+}
+
+/// Set when dropping a [`Gc`] may start a collection on this thread.
+///
+/// With [`CollectionMode::AtSafePointsOnly`], a collection never starts inside of a `Drop`
+/// implementation unless it is started explicitly.
+/// When the collect condition fires, that is remembered, and the collection is run at the next
+/// call to [`safe_point`].
+/// [`CollectionMode::Queued`] behaves the same way, with the collection run by
+/// [`service_collections`].
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{
+///     unsync::{safe_point, set_collection_mode, Gc},
+///     CollectionMode,
+/// };
+///
+/// set_collection_mode(CollectionMode::AtSafePointsOnly);
+/// for _ in 0..3 {
+///     // any number of `Gc`s may be dropped here without a collection starting
+///     drop(Gc::new(0));
+///     safe_point();
+/// }
+/// # set_collection_mode(CollectionMode::Automatic);
+/// ```
+pub fn set_collection_mode(mode: CollectionMode) {
+    DUMPSTER.with(|d| d.mode.set(mode));
+}
+
+#[must_use]
+/// Get the collection mode set for this thread by [`set_collection_mode`].
+pub fn collection_mode() -> CollectionMode {
+    DUMPSTER.with(|d| d.mode.get())
+}
+
+/// Run a collection on this thread if the collect condition has fired since the last safe point or
+/// collection.
+///
+/// This is meant to be called regularly, such as once per frame, when the collection mode is
+/// [`CollectionMode::AtSafePointsOnly`].
+/// In the default [`CollectionMode::Automatic`], the collect condition is acted on as soon as it
+/// fires, so this only runs a collection which was put off before the mode was changed.
 ///
-/// set_collect_condition(default_collect_condition);
-/// ```
-pub fn default_collect_condition(info: &CollectInfo) -> bool {
-    info.n_gcs_dropped_since_last_collect() > info.n_gcs_existing()
+/// Returns whether a collection was run.
+pub fn safe_point() -> bool {
+    DUMPSTER.with(Dumpster::safe_point)
 }
 
-#[allow(clippy::missing_panics_doc)]
-/// Set the function which determines whether the garbage collector should be run.
+/// Run the collection which was queued on this thread in [`CollectionMode::Queued`], if there is
+/// one.
 ///
-/// `f` will be periodically called by the garbage collector to determine whether it should perform
-/// a full cleanup of the heap.
-/// When `f` returns true, a cleanup will begin.
+/// However many times the collect condition fired since the last collection, the requests are
+/// coalesced into a single collection.
+/// Each thread has its own heap, so only the thread which queued a collection can run it.
+///
+/// Returns whether a collection was run.
 ///
 /// # Examples
 ///
 /// ```
-/// use dumpster::unsync::{set_collect_condition, CollectInfo};
+/// use dumpster::{
+///     unsync::{service_collections, set_collection_mode, Gc},
+///     CollectionMode,
+/// };
 ///
-/// /// This function will make sure a GC cleanup never happens unless directly activated.
-/// fn never_collect(_: &CollectInfo) -> bool {
-///     false
+/// set_collection_mode(CollectionMode::Queued);
+/// // dropping these never runs a collection
+/// for _ in 0..1000 {
+///     drop(Gc::new(0));
 /// }
-///
-/// set_collect_condition(never_collect);
+/// // the collection is run here instead, if one was due
+/// service_collections();
+/// # set_collection_mode(CollectionMode::Automatic);
 /// ```
-pub fn set_collect_condition(f: CollectCondition) {
-    DUMPSTER.with(|d| d.collect_condition.set(f));
+pub fn service_collections() -> bool {
+    DUMPSTER.with(Dumpster::safe_point)
 }
 
 #[repr(C)]
 /// The underlying heap allocation for a [`Gc`].
+///
+/// Only what every allocation needs is stored inline.
+/// Whatever only some allocations need, such as a finalizer, the number of [`Root`]s, or a serial
+/// number for deterministic collections, is kept in a table in the dumpster of the allocation's
+/// heap, and a flag in the allocation's state says whether it has an entry there.
 struct GcBox<T: Collectable + ?Sized> {
-    /// The number of extant references to this garbage-collected data.
-    /// If the stored reference count is zero, then this value is a "zombie" - in the process of
-    /// being dropped - and should not be dropped again.
-    ref_count: Cell<NonZeroUsize>,
+    /// The number of extant references to this garbage-collected data, along with the flags
+    /// describing this allocation, packed together as described for [`REF_SHIFT`].
+    state: Cell<usize>,
+    /// The heap which this allocation was made from.
+    heap: HeapRef,
+    /// The position of this allocation in the list of dirty allocations of its heap, or
+    /// [`NOT_DIRTY`](collect::NOT_DIRTY) if it is not dirty.
+    dirty_index: Cell<usize>,
+    #[cfg(feature = "allocator-api")]
+    /// The allocator which made this allocation, or `None` if it came from the global allocator.
+    alloc: Option<AllocHandle>,
     /// The stored value inside this garbage-collected box.
     value: T,
 }
 
+/// The flag of an allocation's state which is set once the heap which it was made from has been
+/// cleared, in which case its value is being or has been dropped, and every `Gc` to it is dead.
+const CLEARED: usize = 1 << 0;
+/// The flag of an allocation's state which is set once a collection has found it to be garbage, in
+/// which case its value is being or has been dropped, and every `Gc` to it is dead.
+const COLLECTED: usize = 1 << 1;
+/// The flag of an allocation's state which is set while it is the key of any [`Ephemeron`].
+const EPHEMERON_KEY: usize = 1 << 2;
+/// The flag of an allocation's state which is set while the last full collection found it to be
+/// reachable and it has not lost a reference since, in which case partial collections do not
+/// search inside it.
+const KNOWN_LIVE: usize = 1 << 3;
+/// The flag of an allocation's state which is set while it has a finalizer which has not been run.
+const FINALIZER: usize = 1 << 4;
+/// The flag of an allocation's state which is set while it is held by any [`Root`].
+const ROOTED: usize = 1 << 5;
+/// The flag of an allocation's state which is set if it was given a serial number.
+const SERIAL: usize = 1 << 6;
+
+/// The position of the reference count in the state of an allocation.
+///
+/// The low `REF_SHIFT` bits of the state hold the flags [`CLEARED`], [`COLLECTED`],
+/// [`EPHEMERON_KEY`], [`KNOWN_LIVE`], [`FINALIZER`], [`ROOTED`], and [`SERIAL`], and the remaining
+/// high bits hold the reference count, which can therefore be at most [`MAX_REF_COUNT`].
+const REF_SHIFT: u32 = 7;
+
+/// The largest number of references an allocation may have.
+///
+/// This is `2^57 - 1` on 64-bit targets and `2^25 - 1` on 32-bit ones.
+/// Making another reference to an allocation which already has this many aborts the process.
+const MAX_REF_COUNT: usize = usize::MAX >> REF_SHIFT;
+
+/// A function which runs the finalizer of the value in the allocation behind a pointer.
+type Finalizer = unsafe fn(NonNull<()>);
+
+/// Run the finalizer of the value in an allocation.
+///
+/// # Safety
+///
+/// `ptr` must point to a live `GcBox<T>`.
+unsafe fn finalize_value<T: Collectable + Finalize>(ptr: NonNull<()>) {
+    ptr.cast::<GcBox<T>>().as_ref().value.finalize();
+}
+
+impl<T: Collectable> GcBox<T> {
+    /// Construct the contents of a new allocation in the heap of `dumpster`, with `value` as its
+    /// value.
+    fn new(dumpster: &Dumpster, value: T) -> GcBox<T> {
+        GcBox {
+            state: Cell::new(1 << REF_SHIFT),
+            heap: dumpster.handle(),
+            dirty_index: Cell::new(collect::NOT_DIRTY),
+            #[cfg(feature = "allocator-api")]
+            alloc: None,
+            value,
+        }
+    }
+}
+
+impl<T: Collectable + ?Sized> GcBox<T> {
+    /// Get the number of references to this allocation.
+    fn ref_count(&self) -> usize {
+        self.state.get() >> REF_SHIFT
+    }
+
+    /// Count one more reference to this allocation.
+    ///
+    /// This aborts the process if the allocation already has [`MAX_REF_COUNT`] references.
+    fn increment(&self) {
+        if self.ref_count() == MAX_REF_COUNT {
+            std::process::abort();
+        }
+        self.state.set(self.state.get() + (1 << REF_SHIFT));
+    }
+
+    /// Count one fewer reference to this allocation, and return the number which remain.
+    fn decrement(&self) -> usize {
+        self.state.set(self.state.get() - (1 << REF_SHIFT));
+        self.ref_count()
+    }
+
+    /// Determine whether the flag `flag` is set in the state of this allocation.
+    fn has_flag(&self, flag: usize) -> bool {
+        self.state.get() & flag != 0
+    }
+
+    /// Set the flag `flag` in the state of this allocation to `value`.
+    fn set_flag(&self, flag: usize, value: bool) {
+        if value {
+            self.state.set(self.state.get() | flag);
+        } else {
+            self.state.set(self.state.get() & !flag);
+        }
+    }
+
+    /// Determine whether this allocation is held by a [`Root`], and so is known to be reachable.
+    fn is_rooted(&self) -> bool {
+        self.has_flag(ROOTED)
+    }
+
+    /// Determine whether the value of this allocation has been, or is being, dropped while `Gc`s
+    /// to it remain.
+    fn is_dead(&self) -> bool {
+        self.has_flag(CLEARED | COLLECTED)
+    }
+
+    /// Determine whether a collection of `heap` is responsible for this allocation.
+    fn belongs_to(&self, heap: HeapRef) -> bool {
+        self.heap == heap && !self.is_dead()
+    }
+}
+
+/// Give up a reference to a dead allocation, freeing its memory if it was the last one.
+///
+/// # Safety
+///
+/// `ptr` must point to a dead allocation whose value has been dropped, or is still held by another
+/// reference while it is dropped.
+unsafe fn release_cleared<T: Collectable + ?Sized>(ptr: NonNull<GcBox<T>>) {
+    let box_ref = ptr.as_ref();
+    if box_ref.decrement() == 0 {
+        free_box(ptr, Layout::for_value(box_ref));
+    }
+}
+
+/// Free the memory of an allocation with layout `layout`, whose value has been dropped, through
+/// whichever allocator made it.
+///
+/// # Safety
+///
+/// `ptr` must point to an allocation whose value has been dropped, and which may not be used again.
+unsafe fn free_box<T: Collectable + ?Sized>(ptr: NonNull<GcBox<T>>, layout: Layout) {
+    #[cfg(feature = "allocator-api")]
+    if let Some(alloc) = ptr.as_ref().alloc {
+        alloc.free(ptr.cast(), layout);
+        return;
+    }
+    free_list::release(ptr.cast(), layout);
+}
+
+/// Run the finalizer of, drop the value in, and free an allocation to which no `Gc`s remain.
+///
+/// # Safety
+///
+/// `ptr` must point to a live allocation, and no `Gc`s may point to it.
+unsafe fn destroy<T: Collectable + ?Sized>(dumpster: &Dumpster, mut ptr: NonNull<GcBox<T>>) {
+    dumpster.mark_cleaned(ptr);
+    dumpster.finalize_one(collect::AllocationId::from(ptr));
+    let is_ephemeron_key = ptr.as_ref().has_flag(EPHEMERON_KEY);
+    dumpster.forget_side_entries(collect::AllocationId::from(ptr));
+    let layout = Layout::for_value(ptr.as_ref());
+    drop_in_place(addr_of_mut!(ptr.as_mut().value));
+    free_box(ptr, layout);
+    dumpster.notify_deallocated(layout);
+    #[cfg(any(
+        feature = "leak-detection",
+        feature = "heap-dump",
+        feature = "heap-inspection"
+    ))]
+    dumpster.forget_live([&collect::AllocationId::from(ptr)]);
+    dumpster.forget_owned([&collect::AllocationId::from(ptr)]);
+    note_destroyed(collect::AllocationId::from(ptr));
+    if is_ephemeron_key {
+        dumpster.kill_ephemerons(collect::AllocationId::from(ptr));
+    }
+}
+
 impl<T: Collectable + ?Sized> Gc<T> {
     /// Construct a new garbage-collected allocation, with `value` as its value.
     ///
@@ -223,22 +1464,146 @@ impl<T: Collectable + ?Sized> Gc<T> {
     where
         T: Sized,
     {
-        DUMPSTER.with(Dumpster::notify_created_gc);
-        Gc {
-            ptr: Cell::new(Nullable::new(NonNull::from(Box::leak(Box::new(GcBox {
-                ref_count: Cell::new(NonZeroUsize::MIN),
-                value,
-            }))))),
-        }
+        Gc::allocate(value, None)
+    }
+
+    /// Construct a new garbage-collected allocation, with `value` as its value, which runs the
+    /// finalizer of `value` just before it is destroyed.
+    ///
+    /// See [`Finalize`] for when the finalizer runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::{unsync::Gc, Collectable, Finalize};
+    /// use std::cell::Cell;
+    ///
+    /// thread_local! {
+    ///     static FINALIZED: Cell<bool> = const { Cell::new(false) };
+    /// }
+    ///
+    /// #[derive(Collectable)]
+    /// struct File;
+    ///
+    /// impl Finalize for File {
+    ///     fn finalize(&self) {
+    ///         FINALIZED.with(|f| f.set(true));
+    ///     }
+    /// }
+    ///
+    /// drop(Gc::new_finalized(File));
+    /// assert!(FINALIZED.with(Cell::get));
+    /// ```
+    pub fn new_finalized(value: T) -> Gc<T>
+    where
+        T: Finalize + Sized,
+    {
+        Gc::allocate(value, Some(finalize_value::<T>))
+    }
+
+    /// Construct a new garbage-collected allocation with `value` as its value and `finalizer` as
+    /// the function which runs its finalizer.
+    fn allocate(value: T, finalizer: Option<Finalizer>) -> Gc<T>
+    where
+        T: Sized,
+    {
+        DUMPSTER.with(|d| Gc::allocate_in(d, value, finalizer))
+    }
+
+    /// Construct a new garbage-collected allocation in the heap of `dumpster`, with `value` as its
+    /// value and `finalizer` as the function which runs its finalizer.
+    fn allocate_in(dumpster: &Dumpster, value: T, finalizer: Option<Finalizer>) -> Gc<T>
+    where
+        T: Sized,
+    {
+        let layout = Layout::new::<GcBox<T>>();
+        // a recycled block is overwritten in full, so nothing is left over from its last use
+        let block = free_list::take(layout).unwrap_or_else(|| {
+            NonNull::new(unsafe { alloc(layout) }).unwrap_or_else(|| handle_alloc_error(layout))
+        });
+        let ptr = block.cast::<GcBox<T>>();
+        unsafe { ptr.as_ptr().write(GcBox::new(dumpster, value)) };
+        Gc::register_in(dumpster, ptr, finalizer)
+    }
+
+    #[cfg(feature = "allocator-api")]
+    /// Construct a new garbage-collected allocation in memory from `alloc`, with `value` as its
+    /// value.
+    ///
+    /// The allocation is freed through `alloc` once it is destroyed, which may happen during any
+    /// later collection on this thread, so `alloc` is kept alive until then.
+    ///
+    /// This is only available with the `allocator-api` feature, which requires nightly Rust.
+    ///
+    /// # Panics
+    ///
+    /// This function calls [`handle_alloc_error`] if `alloc` fails to allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    ///
+    /// use dumpster::unsync::Gc;
+    /// use std::alloc::System;
+    ///
+    /// let gc = Gc::new_in(0, System);
+    /// assert_eq!(*gc, 0);
+    /// ```
+    pub fn new_in<A: Allocator + 'static>(value: T, alloc: A) -> Gc<T>
+    where
+        T: Sized,
+    {
+        DUMPSTER.with(|d| {
+            let (block, handle) = AllocHandle::allocate(alloc, Layout::new::<GcBox<T>>());
+            let ptr = block.cast::<GcBox<T>>();
+            unsafe {
+                ptr.as_ptr().write(GcBox {
+                    alloc: Some(handle),
+                    ..GcBox::new(d, value)
+                });
+            }
+            Gc::register_in(d, ptr, None)
+        })
+    }
+
+    /// Account for a newly constructed allocation in the heap of `dumpster`, whose value has
+    /// `finalizer` as the function which runs its finalizer, and make the first `Gc` to it.
+    fn register_in(
+        dumpster: &Dumpster,
+        ptr: NonNull<GcBox<T>>,
+        finalizer: Option<Finalizer>,
+    ) -> Gc<T>
+    where
+        T: Sized,
+    {
+        unsafe { dumpster.register_side_entries(AllocationId::from(ptr), finalizer) };
+        dumpster.notify_created_gc();
+        dumpster.notify_allocated(Layout::new::<GcBox<T>>());
+        #[cfg(any(
+            feature = "leak-detection",
+            feature = "heap-dump",
+            feature = "heap-inspection"
+        ))]
+        dumpster.register_live(ptr);
+        dumpster.register_owned(ptr, Layout::new::<GcBox<T>>().size());
+        note_allocated(AllocationId::from(ptr));
+        Gc { ptr }
+    }
+
+    /// Determine whether this `Gc` still points to a value, rather than being dead.
+    fn is_live(gc: &Gc<T>) -> bool {
+        !unsafe { gc.ptr.as_ref() }.is_dead()
     }
 
     #[allow(clippy::unnecessary_lazy_evaluations)]
+    #[must_use]
     /// Attempt to dereference this `Gc`.
     ///
     /// This function will return `None` if `self` is a "dead" `Gc`, which points to an
     /// already-deallocated object.
     /// This can only occur if a `Gc` is accessed during the `Drop` implementation of a
-    /// [`Collectable`] object.
+    /// [`Collectable`] object, or if the [`Heap`] it points into has been cleared.
     ///
     /// For a version which panics instead of returning `None`, consider using [`Deref`].
     ///
@@ -276,15 +1641,17 @@ impl<T: Collectable + ?Sized> Gc<T> {
     /// # dumpster::unsync::collect();
     /// ```
     pub fn try_deref(gc: &Gc<T>) -> Option<&T> {
-        (!gc.ptr.get().is_null()).then(|| &**gc)
+        Gc::is_live(gc).then(|| &**gc)
     }
 
+    #[must_use]
     /// Attempt to clone this `Gc`.
     ///
     /// This function will return `None` if `self` is a "dead" `Gc`, which points to an
-    /// already-deallocated object.
+    /// already-deallocated object, or to an object which a collection is destroying.
     /// This can only occur if a `Gc` is accessed during the `Drop` implementation of a
-    /// [`Collectable`] object.
+    /// [`Collectable`] object, which can never keep a collected object alive, or if the [`Heap`]
+    /// it points into has been cleared.
     ///
     /// For a version which panics instead of returning `None`, consider using [`Clone`].
     ///
@@ -322,9 +1689,10 @@ impl<T: Collectable + ?Sized> Gc<T> {
     /// # dumpster::unsync::collect();
     /// ```
     pub fn try_clone(gc: &Gc<T>) -> Option<Gc<T>> {
-        (!gc.ptr.get().is_null()).then(|| gc.clone())
+        Gc::is_live(gc).then(|| gc.clone())
     }
 
+    #[must_use]
     /// Provides a raw pointer to the data.
     ///
     /// Panics if `self` is a "dead" `Gc`,
@@ -343,10 +1711,11 @@ impl<T: Collectable + ?Sized> Gc<T> {
     /// assert_eq!(unsafe { &*x_ptr }, "hello");
     /// ```
     pub fn as_ptr(gc: &Gc<T>) -> *const T {
-        let ptr = NonNull::as_ptr(gc.ptr.get().unwrap());
+        let ptr = NonNull::as_ptr(gc.ptr);
         unsafe { addr_of_mut!((*ptr).value) }
     }
 
+    #[must_use]
     /// Determine whether two `Gc`s are equivalent by reference.
     /// Returns `true` if both `this` and `other` point to the same value, in the same style as
     /// [`std::ptr::eq`].
@@ -364,7 +1733,36 @@ impl<T: Collectable + ?Sized> Gc<T> {
     /// assert!(!Gc::ptr_eq(&gc1, &gc3));
     /// ```
     pub fn ptr_eq(this: &Gc<T>, other: &Gc<T>) -> bool {
-        this.ptr.get().as_option() == other.ptr.get().as_option()
+        std::ptr::addr_eq(this.ptr.as_ptr(), other.ptr.as_ptr())
+    }
+
+    #[must_use]
+    /// Register this `Gc` as a root of the heap.
+    ///
+    /// See [`Root`] for details.
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if `this` points to an already-collected object, which can only
+    /// happen during the `Drop` implementation of a [`Collectable`] value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::Gc;
+    ///
+    /// let root = Gc::into_root(Gc::new(5));
+    /// assert_eq!(*root, 5);
+    /// ```
+    pub fn into_root(this: Gc<T>) -> Root<T> {
+        let box_ref = unsafe { this.ptr.as_ref() };
+        // a dead allocation is never searched again, so there is nothing to keep it reachable for
+        if !box_ref.is_dead() {
+            with_heap(box_ref.heap, |d| unsafe {
+                d.add_root(AllocationId::from(this.ptr));
+            });
+        }
+        Root { gc: this }
     }
 }
 
@@ -418,13 +1816,20 @@ impl<T: Collectable + ?Sized> Deref for Gc<T> {
     /// ```
     fn deref(&self) -> &Self::Target {
         assert!(
-            !COLLECTING.with(Cell::get),
+            is_fresh(AllocationId::from(self.ptr)),
             "dereferencing GC to already-collected object"
         );
-        unsafe {
-            &self.ptr.get().expect("dereferencing Gc to already-collected object. \
-            This means a Gc escaped from a Drop implementation, likely implying a bug in your code.").as_ref().value
-        }
+        let box_ref = unsafe { self.ptr.as_ref() };
+        assert!(
+            !box_ref.has_flag(COLLECTED),
+            "dereferencing Gc to already-collected object. \
+            This means a Gc escaped from a Drop implementation, likely implying a bug in your code."
+        );
+        assert!(
+            !box_ref.has_flag(CLEARED),
+            "dereferencing Gc to an object whose heap has been cleared"
+        );
+        &box_ref.value
     }
 }
 
@@ -435,9 +1840,11 @@ impl<T: Collectable + ?Sized> Clone for Gc<T> {
     ///
     /// # Panics
     ///
-    /// This function will panic if the `Gc` being cloned points to a deallocated object.
+    /// This function will panic if the `Gc` being cloned points to a deallocated object, or to an
+    /// object which a collection is destroying.
     /// This is only possible if said `Gc` is accessed during the `Drop` implementation of a
-    /// `Collectable` value.
+    /// `Collectable` value, so such an implementation cannot resurrect the object by storing a
+    /// clone of the `Gc` somewhere reachable.
     ///
     /// For a fallible version, refer to [`Gc::try_clone`].
     ///
@@ -475,20 +1882,19 @@ impl<T: Collectable + ?Sized> Clone for Gc<T> {
     /// # dumpster::unsync::collect();
     /// ```
     fn clone(&self) -> Self {
-        unsafe {
-            let box_ref = self.ptr.get().expect("Attempt to clone Gc to already-collected object. \
-            This means a Gc escaped from a Drop implementation, likely implying a bug in your code.").as_ref();
-            box_ref
-                .ref_count
-                .set(box_ref.ref_count.get().saturating_add(1));
-        }
-        DUMPSTER.with(|d| {
-            d.notify_created_gc();
-            // d.mark_cleaned(self.ptr);
-        });
-        Self {
-            ptr: self.ptr.clone(),
-        }
+        let box_ref = unsafe { self.ptr.as_ref() };
+        assert!(
+            !box_ref.has_flag(COLLECTED),
+            "attempt to clone Gc to an object which is being collected. \
+            A Drop implementation cannot keep a collected object alive."
+        );
+        assert!(
+            !box_ref.has_flag(CLEARED),
+            "Attempt to clone Gc to an object whose heap has been cleared"
+        );
+        box_ref.increment();
+        with_heap(box_ref.heap, Dumpster::notify_created_gc);
+        Self { ptr: self.ptr }
     }
 }
 
@@ -498,36 +1904,32 @@ impl<T: Collectable + ?Sized> Drop for Gc<T> {
     /// If this is the last reference which can reach the pointed-to data, the allocation that it
     /// points to will be destroyed.
     fn drop(&mut self) {
-        if COLLECTING.with(Cell::get) {
+        let ptr = self.ptr;
+        let box_ref = unsafe { ptr.as_ref() };
+        if box_ref.is_dead() {
+            // whatever killed the allocation drops the value, so only the memory is left to free
+            unsafe { release_cleared(ptr) };
             return;
         }
-        let Some(mut ptr) = self.ptr.get().as_option() else {
+        if !is_fresh(AllocationId::from(ptr)) {
+            // the collection has already accounted for this reference
             return;
-        };
-        DUMPSTER.with(|d| {
-            let box_ref = unsafe { ptr.as_ref() };
-            match box_ref.ref_count.get() {
-                NonZeroUsize::MIN => {
-                    d.mark_cleaned(ptr);
-                    unsafe {
-                        // this was the last reference, drop unconditionally
-                        drop_in_place(addr_of_mut!(ptr.as_mut().value));
-                        // note: `box_ref` is no longer usable
-                        dealloc(ptr.as_ptr().cast::<u8>(), Layout::for_value(ptr.as_ref()));
-                    }
-                }
-                n => {
-                    // decrement the ref count - but another reference to this data still
-                    // lives
-                    box_ref
-                        .ref_count
-                        .set(NonZeroUsize::new(n.get() - 1).unwrap());
-
-                    if contains_gcs(&box_ref.value).unwrap_or(true) {
-                        // remaining references could be a cycle - therefore, mark it as dirty
-                        // so we can check later
-                        d.mark_dirty(ptr);
-                    }
+        }
+        with_heap(box_ref.heap, |d| {
+            if box_ref.ref_count() == 1 {
+                // this was the last reference, drop unconditionally
+                unsafe { d.destroy_unreferenced(ptr) };
+            } else {
+                // decrement the ref count - but another reference to this data still lives
+                box_ref.decrement();
+                box_ref.set_flag(KNOWN_LIVE, false);
+
+                // an ephemeron's value may refer back to its key, so a key may be part of a cycle
+                // even if its own value has no `Gc`s
+                if box_ref.has_flag(EPHEMERON_KEY) || contains_gcs(&box_ref.value).unwrap_or(true) {
+                    // remaining references could be a cycle - therefore, mark it as dirty so we
+                    // can check later
+                    d.mark_dirty(ptr);
                 }
             }
             // Notify that a GC has been dropped, potentially triggering a cleanup
@@ -591,7 +1993,7 @@ impl CollectInfo {
     /// set_collect_condition(have_many_gcs_dropped);
     /// ```
     pub fn n_gcs_dropped_since_last_collect(&self) -> usize {
-        DUMPSTER.with(|d| d.n_ref_drops.get())
+        with_heap(self.heap, |d| d.n_ref_drops.get())
     }
 
     #[must_use]
@@ -610,7 +2012,76 @@ impl CollectInfo {
     /// set_collect_condition(do_many_gcs_exist);
     /// ```
     pub fn n_gcs_existing(&self) -> usize {
-        DUMPSTER.with(|d| d.n_refs_living.get())
+        with_heap(self.heap, |d| d.n_refs_living.get())
+    }
+
+    #[must_use]
+    /// Get the number of garbage-collected allocations which currently exist on this thread.
+    ///
+    /// Many [`Gc`]s may point to the same allocation, so this is at most
+    /// [`CollectInfo::n_gcs_existing`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{set_collect_condition, CollectInfo};
+    ///
+    /// // Collection condition for whether many allocations currently exist.
+    /// fn do_many_allocations_exist(info: &CollectInfo) -> bool {
+    ///     info.n_allocations() > 1000
+    /// }
+    ///
+    /// set_collect_condition(do_many_allocations_exist);
+    /// ```
+    pub fn n_allocations(&self) -> usize {
+        with_heap(self.heap, |d| d.n_allocations.get())
+    }
+
+    #[must_use]
+    /// Get the total size, in bytes, of all garbage-collected allocations which currently exist on
+    /// this thread.
+    ///
+    /// This counts the whole allocation backing each [`Gc`], including its reference count, but not
+    /// any memory which the stored values own indirectly (such as the buffer of a `Vec`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{set_collect_condition, CollectInfo};
+    ///
+    /// // Collection condition for whether the heap has grown past a megabyte.
+    /// fn is_heap_large(info: &CollectInfo) -> bool {
+    ///     info.heap_bytes() > 1 << 20
+    /// }
+    ///
+    /// set_collect_condition(is_heap_large);
+    /// ```
+    pub fn heap_bytes(&self) -> usize {
+        with_heap(self.heap, |d| d.heap_bytes.get())
+    }
+
+    #[must_use]
+    /// Get the ratio of dropped to existing [`Gc`]s at which [`default_collect_condition`] would
+    /// currently start a collection.
+    ///
+    /// The ratio starts at `1.0`, grows after each collection which frees little of what it
+    /// examines, and shrinks after each which frees most of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{set_collect_condition, CollectInfo};
+    ///
+    /// // collect half as often as the default condition would
+    /// fn lazy_collect_condition(info: &CollectInfo) -> bool {
+    ///     info.n_gcs_dropped_since_last_collect() as f64
+    ///         > info.n_gcs_existing() as f64 * info.drop_ratio() * 2.0
+    /// }
+    ///
+    /// set_collect_condition(lazy_collect_condition);
+    /// ```
+    pub fn drop_ratio(&self) -> f64 {
+        with_heap(self.heap, |d| d.drop_ratio.get())
     }
 }
 
@@ -652,3 +2123,114 @@ where
     U: Collectable + ?Sized,
 {
 }
+
+/// A [`Gc`] which is registered as a root of the heap.
+///
+/// The garbage collector knows that a rooted allocation is reachable, so it never looks inside it
+/// while searching for garbage.
+/// This makes collections cheaper when a large structure lives for a long time, such as the global
+/// environment of an interpreter: instead of counting the references across the whole structure
+/// every time, a collection stops as soon as it reaches the root.
+/// Nothing reachable from a `Root` is ever freed.
+/// Once every `Root` to an allocation is dropped, it is collected as usual.
+///
+/// A `Root` is created with [`Gc::into_root`].
+/// It is not [`Collectable`], since a `Root` stored inside the garbage-collected heap would keep
+/// itself alive forever.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::unsync::{collect, Gc, Root};
+/// use std::cell::RefCell;
+///
+/// let globals: Root<RefCell<Vec<Gc<i32>>>> = Gc::into_root(Gc::new(RefCell::new(Vec::new())));
+/// globals.borrow_mut().push(Gc::new(1));
+///
+/// // this collection does not look inside `globals`
+/// collect();
+/// assert_eq!(*globals.borrow()[0], 1);
+///
+/// // `globals` can be collected like any other allocation from now on
+/// let globals: Gc<_> = Root::into_gc(globals);
+/// ```
+pub struct Root<T: Collectable + ?Sized + 'static> {
+    /// The pointer to the rooted allocation.
+    gc: Gc<T>,
+}
+
+impl<T: Collectable + ?Sized> Root<T> {
+    #[must_use]
+    /// Get the [`Gc`] held by this root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{Gc, Root};
+    ///
+    /// let gc = Gc::new(3);
+    /// let root = Gc::into_root(gc.clone());
+    /// assert!(Gc::ptr_eq(Root::as_gc(&root), &gc));
+    /// ```
+    pub fn as_gc(this: &Root<T>) -> &Gc<T> {
+        &this.gc
+    }
+
+    #[must_use]
+    /// Unregister this root, turning it back into an ordinary [`Gc`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dumpster::unsync::{Gc, Root};
+    ///
+    /// let root = Gc::into_root(Gc::new(3));
+    /// let gc: Gc<i32> = Root::into_gc(root);
+    /// ```
+    pub fn into_gc(this: Root<T>) -> Gc<T> {
+        let this = ManuallyDrop::new(this);
+        Root::unroot(&this);
+        unsafe { read(addr_of!(this.gc)) }
+    }
+
+    /// Remove this root's registration from its allocation.
+    fn unroot(this: &Root<T>) {
+        let box_ref = unsafe { this.gc.ptr.as_ref() };
+        // nor is a dead allocation's root counted
+        if !box_ref.is_dead() {
+            with_heap(box_ref.heap, |d| unsafe {
+                d.remove_root(AllocationId::from(this.gc.ptr));
+            });
+        }
+    }
+}
+
+impl<T: Collectable + ?Sized> Deref for Root<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.gc
+    }
+}
+
+impl<T: Collectable + ?Sized> Clone for Root<T> {
+    fn clone(&self) -> Self {
+        Gc::into_root(self.gc.clone())
+    }
+}
+
+impl<T: Collectable + ?Sized> Drop for Root<T> {
+    /// Unregister this root.
+    ///
+    /// If no other `Root` to the same allocation remains, the allocation may be collected once it
+    /// becomes unreachable.
+    fn drop(&mut self) {
+        Root::unroot(self);
+    }
+}
+
+impl<T: Collectable + ?Sized + std::fmt::Debug> std::fmt::Debug for Root<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Root").field(&&**self).finish()
+    }
+}
@@ -18,14 +18,23 @@
 
 //! Simple tests using manual implementations of [`Collectable`].
 
-use crate::Visitor;
+use crate::{
+    graph,
+    reach::GcId,
+    testing::{
+        unsync::{assert_heap_empty, with_aggressive_collection},
+        DropCounter, DropToken,
+    },
+    CollectionMode, Finalize, Visitor,
+};
 
 use super::*;
 use std::{
     cell::RefCell,
+    collections::{HashMap, HashSet},
     sync::{
         atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
-        Mutex,
+        Arc, Mutex,
     },
 };
 
@@ -108,32 +117,15 @@ fn self_referential() {
 
 #[test]
 fn cyclic() {
-    static DROPPED: AtomicU8 = AtomicU8::new(0);
-    struct Foo(RefCell<Option<Gc<Foo>>>);
-
-    unsafe impl Collectable for Foo {
-        #[inline]
-        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
-            self.0.accept(visitor)
-        }
-    }
-
-    impl Drop for Foo {
-        fn drop(&mut self) {
-            DROPPED.fetch_add(1, Ordering::Relaxed);
-        }
-    }
-
-    let foo1 = Gc::new(Foo(RefCell::new(None)));
-    let foo2 = Gc::new(Foo(RefCell::new(Some(Gc::clone(&foo1)))));
-    foo1.0.replace(Some(Gc::clone(&foo2)));
+    let counter = DropCounter::new();
+    graph!(unsync, counter; foo1, foo2; foo1 -> foo2, foo2 -> foo1);
 
-    assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+    assert_eq!(counter.count(), 0);
     drop(foo1);
-    assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+    assert_eq!(counter.count(), 0);
     drop(foo2);
-    collect();
-    assert_eq!(DROPPED.load(Ordering::Relaxed), 2);
+    assert_heap_empty();
+    assert_eq!(counter.count(), 2);
 }
 
 /// Construct a complete graph of garbage-collected
@@ -183,54 +175,38 @@ fn complete4() {
 
 #[test]
 fn parallel_loop() {
-    static COUNT_1: AtomicUsize = AtomicUsize::new(0);
-    static COUNT_2: AtomicUsize = AtomicUsize::new(0);
-    static COUNT_3: AtomicUsize = AtomicUsize::new(0);
-    static COUNT_4: AtomicUsize = AtomicUsize::new(0);
-
-    let gc1 = Gc::new(MultiRef {
-        drop_count: &COUNT_1,
-        refs: RefCell::new(Vec::new()),
-    });
-    let gc2 = Gc::new(MultiRef {
-        drop_count: &COUNT_2,
-        refs: RefCell::new(vec![Gc::clone(&gc1)]),
-    });
-    let gc3 = Gc::new(MultiRef {
-        drop_count: &COUNT_3,
-        refs: RefCell::new(vec![Gc::clone(&gc1)]),
-    });
-    let gc4 = Gc::new(MultiRef {
-        drop_count: &COUNT_4,
-        refs: RefCell::new(vec![Gc::clone(&gc2), Gc::clone(&gc3)]),
-    });
-    gc1.refs.borrow_mut().push(Gc::clone(&gc4));
+    let counter = DropCounter::new();
+    graph!(
+        unsync, counter;
+        gc1, gc2, gc3, gc4;
+        gc2 -> gc1, gc3 -> gc1, gc4 -> gc2, gc4 -> gc3, gc1 -> gc4
+    );
 
-    assert_eq!(COUNT_1.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_2.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_3.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_4.load(Ordering::Relaxed), 0);
     drop(gc1);
-    assert_eq!(COUNT_1.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_2.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_3.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_4.load(Ordering::Relaxed), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc2);
-    assert_eq!(COUNT_1.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_2.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_3.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_4.load(Ordering::Relaxed), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc3);
-    assert_eq!(COUNT_1.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_2.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_3.load(Ordering::Relaxed), 0);
-    assert_eq!(COUNT_4.load(Ordering::Relaxed), 0);
+    assert_eq!(counter.count(), 0);
     drop(gc4);
-    collect();
-    assert_eq!(COUNT_1.load(Ordering::Relaxed), 1);
-    assert_eq!(COUNT_2.load(Ordering::Relaxed), 1);
-    assert_eq!(COUNT_3.load(Ordering::Relaxed), 1);
-    assert_eq!(COUNT_4.load(Ordering::Relaxed), 1);
+    assert_heap_empty();
+    assert_eq!(counter.count(), 4);
+}
+
+#[test]
+/// Test that a graph of nodes survives a collection on every drop until it becomes garbage.
+fn aggressive_collection() {
+    let counter = DropCounter::new();
+    with_aggressive_collection(|| {
+        graph!(unsync, counter; a, b, c; a -> b, b -> c, c -> a, b -> b);
+        drop(a);
+        drop(c);
+        assert_eq!(counter.count(), 0);
+        assert_eq!(b.edges().len(), 2);
+        drop(b);
+        assert_eq!(counter.count(), 3);
+    });
+    assert_heap_empty();
 }
 
 #[test]
@@ -254,6 +230,224 @@ fn double_borrow() {
     assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 1);
 }
 
+#[test]
+/// Test that a collection which cannot trace an allocation, because a `RefCell` in it is mutably
+/// borrowed, treats it as reachable and tries it again in the next collection.
+fn collect_while_borrowed() {
+    struct Opaque {
+        next: RefCell<Option<Gc<Opaque>>>,
+        hidden: Cell<bool>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Opaque {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            if self.hidden.get() {
+                return Err(());
+            }
+            self.next.accept(visitor)
+        }
+    }
+
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let a = Gc::new(MultiRef {
+        refs: RefCell::new(Vec::new()),
+        drop_count: &DROP_COUNT,
+    });
+    let b = Gc::new(MultiRef {
+        refs: RefCell::new(vec![a.clone()]),
+        drop_count: &DROP_COUNT,
+    });
+    a.refs.borrow_mut().push(b.clone());
+    let a2 = a.clone();
+    let borrow = a2.refs.borrow_mut();
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+    assert_eq!(borrow.len(), 1);
+    drop(borrow);
+    drop(a2);
+    collect();
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 2);
+
+    // a value which cannot be traced stays dirty, so the cycle is found once it can be, even
+    // though no `Gc` to it was dropped in between
+    let counter = DropCounter::new();
+    let a = Gc::new(Opaque {
+        next: RefCell::new(None),
+        hidden: Cell::new(true),
+        _token: counter.token(),
+    });
+    let b = Gc::new(Opaque {
+        next: RefCell::new(Some(a.clone())),
+        hidden: Cell::new(false),
+        _token: counter.token(),
+    });
+    *a.next.borrow_mut() = Some(b.clone());
+    let hidden = std::ptr::from_ref(&a.hidden);
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(counter.count(), 0);
+    // the cycle is still alive, so the flag may still be reached
+    unsafe { (*hidden).set(false) };
+    collect();
+    assert_eq!(counter.count(), 2);
+}
+
+#[test]
+/// Test that values dropped by a collection may make, use, and drop `Gc`s of their own, and that
+/// the ones they keep survive the collection.
+fn allocate_while_collecting() {
+    struct Node {
+        id: usize,
+        next: RefCell<Option<Gc<Node>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    struct LogEntry {
+        id: usize,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for LogEntry {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    thread_local! {
+        static LOG: RefCell<Vec<Gc<LogEntry>>> = const { RefCell::new(Vec::new()) };
+        static COUNTER: DropCounter = DropCounter::new();
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            let entry = Gc::new(LogEntry {
+                id: self.id,
+                _token: COUNTER.with(DropCounter::token),
+            });
+            let copy = entry.clone();
+            assert_eq!(copy.id, self.id);
+            LOG.with(|log| log.borrow_mut().push(entry));
+            drop(copy);
+        }
+    }
+
+    const N: usize = 1000;
+    let live = stats().live_allocations;
+    let first = Gc::new(Node {
+        id: 0,
+        next: RefCell::new(None),
+    });
+    let mut last = first.clone();
+    for id in 1..N {
+        let node = Gc::new(Node {
+            id,
+            next: RefCell::new(None),
+        });
+        *last.next.borrow_mut() = Some(node.clone());
+        last = node;
+    }
+    *last.next.borrow_mut() = Some(first);
+    drop(last);
+    collect();
+
+    let mut ids = LOG.with(|log| log.borrow().iter().map(|e| e.id).collect::<Vec<_>>());
+    ids.sort_unstable();
+    assert_eq!(ids, (0..N).collect::<Vec<_>>());
+    assert_eq!(stats().live_allocations, live + N);
+    collect();
+    assert_eq!(COUNTER.with(DropCounter::count), 0);
+
+    LOG.with(|log| log.borrow_mut().clear());
+    assert_eq!(COUNTER.with(DropCounter::count), N);
+    assert_eq!(stats().live_allocations, live);
+}
+
+#[test]
+/// Test that a value dropped by a collection cannot resurrect the garbage it points to, but may
+/// still keep a clone of a `Gc` to an allocation which survives the collection.
+fn resurrect_in_drop() {
+    struct Node {
+        next: RefCell<Option<Gc<Node>>>,
+        keep: Gc<u8>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)?;
+            self.keep.accept(visitor)
+        }
+    }
+
+    thread_local! {
+        static RESURRECTED: RefCell<Vec<Gc<Node>>> = const { RefCell::new(Vec::new()) };
+        static KEPT: RefCell<Vec<Gc<u8>>> = const { RefCell::new(Vec::new()) };
+        static REFUSED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            let next = self.next.borrow();
+            let next = next.as_ref().unwrap();
+            assert!(Gc::try_clone(next).is_none());
+            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| next.clone())) {
+                Ok(gc) => RESURRECTED.with(|r| r.borrow_mut().push(gc)),
+                Err(payload) => {
+                    let message = payload.downcast_ref::<&str>().unwrap();
+                    assert!(message.contains("being collected"), "{message}");
+                    REFUSED.with(|r| r.set(r.get() + 1));
+                }
+            }
+            KEPT.with(|k| k.borrow_mut().push(Gc::try_clone(&self.keep).unwrap()));
+        }
+    }
+
+    const N: usize = 3;
+    let counter = DropCounter::new();
+    let keep = Gc::new(7);
+    let live = stats().live_allocations;
+    let first = Gc::new(Node {
+        next: RefCell::new(None),
+        keep: keep.clone(),
+        _token: counter.token(),
+    });
+    let mut last = first.clone();
+    for _ in 1..N {
+        let node = Gc::new(Node {
+            next: RefCell::new(None),
+            keep: keep.clone(),
+            _token: counter.token(),
+        });
+        *last.next.borrow_mut() = Some(node.clone());
+        last = node;
+    }
+    *last.next.borrow_mut() = Some(first);
+    drop(last);
+    collect();
+
+    assert_eq!(counter.count(), N);
+    assert_eq!(REFUSED.with(Cell::get), N);
+    assert!(RESURRECTED.with(|r| r.borrow().is_empty()));
+    assert_eq!(stats().live_allocations, live);
+    KEPT.with(|k| {
+        let kept = k.take();
+        assert_eq!(kept.len(), N);
+        assert!(kept.iter().all(|k| **k == 7));
+    });
+    drop(keep);
+    assert_eq!(stats().live_allocations, live - 1);
+}
+
 #[test]
 #[cfg(feature = "coerce-unsized")]
 fn coerce_array() {
@@ -266,6 +460,28 @@ fn coerce_array() {
     );
 }
 
+#[test]
+/// Test that an `Option<Gc<T>>` is no bigger than a `Gc<T>`.
+fn gc_niche() {
+    use std::mem::size_of;
+
+    assert_eq!(size_of::<Gc<u8>>(), size_of::<usize>());
+    assert_eq!(size_of::<Option<Gc<u8>>>(), size_of::<usize>());
+    assert_eq!(size_of::<Option<Gc<String>>>(), size_of::<usize>());
+    assert_eq!(size_of::<Option<Gc<[u8]>>>(), 2 * size_of::<usize>());
+}
+
+#[test]
+#[cfg(not(feature = "allocator-api"))]
+/// Test that the header of an allocation is only three words: its state, its heap, and its place
+/// among the dirty allocations.
+fn header_size() {
+    use std::mem::size_of;
+
+    assert_eq!(size_of::<GcBox<()>>(), 3 * size_of::<usize>());
+    assert_eq!(size_of::<GcBox<usize>>(), 4 * size_of::<usize>());
+}
+
 #[test]
 #[should_panic = "dereferencing Gc to already-collected object. This means a Gc escaped from a Drop implementation, likely implying a bug in your code."]
 fn escape_dead_pointer() {
@@ -306,3 +522,2390 @@ fn escape_dead_pointer() {
         ESCAPED.with(|e| e.lock().unwrap().as_ref().unwrap().x)
     );
 }
+
+#[test]
+/// Test that a collection reports exactly the allocations it freed.
+fn collect_result() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    set_collect_condition(|_| false);
+
+    // nothing has been dropped yet, so there is nothing to examine
+    let result = collect();
+    assert_eq!(result.allocations_examined, 0);
+    assert_eq!(result.allocations_freed, 0);
+    assert_eq!(result.bytes_freed, 0);
+
+    let gc1 = Gc::new(MultiRef {
+        refs: RefCell::new(Vec::new()),
+        drop_count: &DROP_COUNT,
+    });
+    let gc2 = Gc::new(MultiRef {
+        refs: RefCell::new(vec![gc1.clone()]),
+        drop_count: &DROP_COUNT,
+    });
+    gc1.refs.borrow_mut().push(gc2.clone());
+    let bytes = Layout::for_value(unsafe { gc1.ptr.as_ref() }).size()
+        + Layout::for_value(unsafe { gc2.ptr.as_ref() }).size();
+    drop(gc1);
+    drop(gc2);
+
+    let result = collect();
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 2);
+    assert_eq!(result.allocations_examined, 2);
+    assert_eq!(result.allocations_freed, 2);
+    assert_eq!(result.bytes_freed, bytes);
+
+    // the garbage is gone, so a second collection has nothing left to do
+    let result = collect();
+    assert_eq!(result.allocations_examined, 0);
+    assert_eq!(result.allocations_freed, 0);
+    assert_eq!(result.bytes_freed, 0);
+
+    set_collect_condition(default_collect_condition);
+}
+#[test]
+/// Test that no collection starts between safe points, however much garbage is dropped, and that
+/// the next safe point frees all of it.
+fn safe_points_only() {
+    struct Node {
+        edges: RefCell<Vec<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    const N: usize = 10_000;
+    let counter = DropCounter::new();
+    set_collect_condition(|_| true);
+    set_collection_mode(CollectionMode::AtSafePointsOnly);
+    assert_eq!(collection_mode(), CollectionMode::AtSafePointsOnly);
+    assert!(!safe_point());
+
+    let collections = stats().collections;
+    for _ in 0..N {
+        let a = Gc::new(Node {
+            edges: RefCell::new(Vec::new()),
+            _token: counter.token(),
+        });
+        let b = Gc::new(Node {
+            edges: RefCell::new(vec![a.clone()]),
+            _token: counter.token(),
+        });
+        a.edges.borrow_mut().push(b);
+    }
+    assert_eq!(counter.count(), 0);
+    assert_eq!(stats().collections, collections);
+
+    assert!(safe_point());
+    assert_eq!(counter.count(), 2 * N);
+    assert_eq!(stats().collections, collections + 1);
+    // the collection has been done, so there is nothing left for the next safe point
+    assert!(!safe_point());
+
+    set_collection_mode(CollectionMode::Automatic);
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test that dropping `Gc`s in the queued mode never runs a collection, however often the collect
+/// condition fires, and that one serviced collection frees all the garbage.
+fn queued_collections() {
+    struct Node {
+        edges: RefCell<Vec<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    thread_local! {
+        static N_STARTS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    const N: usize = 10_000;
+    let counter = DropCounter::new();
+    set_collect_hooks(CollectHooks::new().on_start(|_| N_STARTS.with(|n| n.set(n.get() + 1))));
+    set_collect_condition(|_| true);
+    set_collection_mode(CollectionMode::Queued);
+    assert_eq!(collection_mode(), CollectionMode::Queued);
+    assert!(!service_collections());
+
+    for _ in 0..N {
+        let a = Gc::new(Node {
+            edges: RefCell::new(Vec::new()),
+            _token: counter.token(),
+        });
+        a.edges.borrow_mut().push(a.clone());
+    }
+    assert_eq!(N_STARTS.with(Cell::get), 0);
+    assert_eq!(counter.count(), 0);
+
+    // the requests were coalesced into a single collection
+    assert!(service_collections());
+    assert_eq!(N_STARTS.with(Cell::get), 1);
+    assert_eq!(counter.count(), N);
+    assert!(!service_collections());
+
+    set_collection_mode(CollectionMode::Automatic);
+    set_collect_condition(default_collect_condition);
+    set_collect_hooks(CollectHooks::default());
+}
+
+#[test]
+/// Test that the heap statistics given to a collect condition match the real heap.
+fn collect_info_statistics() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        /// The statistics last seen by the collect condition.
+        static SEEN: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+    }
+
+    fn past_1000(info: &CollectInfo) -> bool {
+        SEEN.with(|s| s.set((info.n_allocations(), info.heap_bytes())));
+        info.n_allocations() > 1000
+    }
+
+    let size = Layout::new::<GcBox<MultiRef>>().size();
+    set_collect_condition(past_1000);
+
+    for i in 1..=1200 {
+        let gc = Gc::new(MultiRef {
+            refs: RefCell::new(Vec::new()),
+            drop_count: &DROP_COUNT,
+        });
+        gc.refs.borrow_mut().push(gc.clone());
+        drop(gc);
+
+        // the condition sees the heap before collecting, and the first 1001 cycles are all
+        // collected at once right after the 1001st is dropped
+        let live = if i > 1001 { i - 1001 } else { i };
+        SEEN.with(|s| assert_eq!(s.get(), (live, live * size)));
+    }
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 1001);
+
+    set_collect_condition(|_| false);
+    collect();
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 1200);
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test that an incremental collection with a tiny budget eventually frees a large cycle.
+fn incremental_large_cycle() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+    const N: usize = 2000;
+
+    set_collect_condition(|_| false);
+
+    let live = Gc::new(MultiRef {
+        refs: RefCell::new(Vec::new()),
+        drop_count: &DROP_COUNT,
+    });
+    let first = Gc::new(MultiRef {
+        refs: RefCell::new(vec![live.clone()]),
+        drop_count: &DROP_COUNT,
+    });
+    let mut last = first.clone();
+    for _ in 1..N {
+        let next = Gc::new(MultiRef {
+            refs: RefCell::new(vec![live.clone()]),
+            drop_count: &DROP_COUNT,
+        });
+        last.refs.borrow_mut().push(next.clone());
+        last = next;
+    }
+    last.refs.borrow_mut().push(first.clone());
+    drop(first);
+    drop(last);
+
+    let mut n_calls = 0;
+    let freed = loop {
+        n_calls += 1;
+        if let CollectProgress::Finished { freed } = collect_with_work(10) {
+            break freed;
+        }
+        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+    };
+    assert!(n_calls > N / 10);
+    assert_eq!(freed, N);
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), N);
+    assert_eq!(unsafe { live.ptr.as_ref() }.ref_count(), 1);
+
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test that mutating the heap between increments of a collection never frees a live allocation.
+fn incremental_mutation() {
+    thread_local! {
+        /// The IDs of every node which has been dropped.
+        static DROPPED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+    }
+
+    struct Node {
+        id: usize,
+        refs: RefCell<Vec<Gc<Node>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.refs.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            DROPPED.with(|d| assert!(d.borrow_mut().insert(self.id)));
+        }
+    }
+
+    /// Check that nothing reachable from `roots` has been dropped.
+    fn check_alive(roots: &[Gc<Node>]) {
+        let mut seen = HashSet::new();
+        let mut stack = roots.to_vec();
+        while let Some(node) = stack.pop() {
+            assert!(
+                !DROPPED.with(|d| d.borrow().contains(&node.id)),
+                "live node {} was freed",
+                node.id
+            );
+            if seen.insert(node.id) {
+                stack.extend(node.refs.borrow().iter().cloned());
+            }
+        }
+    }
+
+    set_collect_condition(|_| false);
+    fastrand::seed(0xd0_ba55);
+
+    let mut n_nodes = 0;
+    let mut n_freed = 0;
+    let mut roots: Vec<Gc<Node>> = Vec::new();
+    for _ in 0..5000 {
+        match fastrand::u8(0..6) {
+            0 => {
+                roots.push(Gc::new(Node {
+                    id: n_nodes,
+                    refs: RefCell::new(Vec::new()),
+                }));
+                n_nodes += 1;
+            }
+            1 if !roots.is_empty() => {
+                // link two roots together, possibly forming a cycle
+                let from = &roots[fastrand::usize(..roots.len())];
+                let to = roots[fastrand::usize(..roots.len())].clone();
+                from.refs.borrow_mut().push(to);
+            }
+            2 if !roots.is_empty() => {
+                // move a reference out of the heap and onto the stack without touching its count
+                let from = &roots[fastrand::usize(..roots.len())];
+                let moved = from.refs.borrow_mut().pop();
+                roots.extend(moved);
+            }
+            3 if !roots.is_empty() => {
+                // move a reference from the stack into the heap
+                let moved = roots.swap_remove(fastrand::usize(..roots.len()));
+                if let Some(to) = roots.get(fastrand::usize(..=roots.len())) {
+                    to.refs.borrow_mut().push(moved);
+                }
+            }
+            4 if !roots.is_empty() => {
+                drop(roots.swap_remove(fastrand::usize(..roots.len())));
+            }
+            _ => {
+                if let CollectProgress::Finished { freed } =
+                    collect_with_work(fastrand::usize(1..20))
+                {
+                    n_freed += freed;
+                }
+            }
+        }
+        check_alive(&roots);
+    }
+    // make sure the collections in the middle actually did something
+    assert!(n_freed > 0);
+
+    drop(roots);
+    for _ in 0..1000 {
+        if DROPPED.with(|d| d.borrow().len()) == n_nodes {
+            break;
+        }
+        let _ = collect_with_work(50);
+    }
+    assert_eq!(DROPPED.with(|d| d.borrow().len()), n_nodes);
+
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test that a cyclic graph dropped while collection is paused is only freed once every guard has
+/// been released.
+fn pause_guard() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+    const N: usize = 10;
+
+    set_collect_condition(|_| true);
+    let gcs = (0..N)
+        .map(|_| {
+            Gc::new(MultiRef {
+                refs: RefCell::new(Vec::new()),
+                drop_count: &DROP_COUNT,
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, gc) in gcs.iter().enumerate() {
+        gc.refs.borrow_mut().push(gcs[(i + 1) % N].clone());
+    }
+    let n_allocations = DUMPSTER.with(|d| d.n_allocations.get());
+
+    let outer = pause_collection();
+    let inner = pause_collection();
+    drop(gcs);
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+    assert_eq!(DUMPSTER.with(|d| d.n_allocations.get()), n_allocations);
+
+    drop(inner);
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+
+    drop(outer);
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), N);
+    assert_eq!(DUMPSTER.with(|d| d.n_allocations.get()), n_allocations - N);
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test that the statistics snapshot follows a scripted sequence of allocations and collections.
+fn gc_stats() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+    const N: usize = 3;
+
+    set_collect_condition(|_| false);
+    reset_stats();
+    let before = stats();
+    assert_eq!(before.collections, 0);
+    assert_eq!(before.allocations_freed, 0);
+    assert_eq!(before.bytes_freed, 0);
+    assert_eq!(before.time_collecting, Duration::ZERO);
+
+    let size = Layout::new::<GcBox<MultiRef>>().size();
+    let gcs = (0..N)
+        .map(|_| {
+            Gc::new(MultiRef {
+                refs: RefCell::new(Vec::new()),
+                drop_count: &DROP_COUNT,
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, gc) in gcs.iter().enumerate() {
+        gc.refs.borrow_mut().push(gcs[(i + 1) % N].clone());
+    }
+    let allocated = stats();
+    assert_eq!(allocated.live_allocations, before.live_allocations + N);
+    assert_eq!(allocated.live_bytes, before.live_bytes + N * size);
+    assert_eq!(allocated.collections, 0);
+
+    drop(gcs);
+    let result = collect();
+    let collected = stats();
+    assert_eq!(collected.collections, 1);
+    assert_eq!(collected.allocations_freed, N);
+    assert_eq!(collected.bytes_freed, N * size);
+    assert_eq!(collected.live_allocations, before.live_allocations);
+    assert_eq!(collected.live_bytes, before.live_bytes);
+    assert_eq!(collected.time_collecting, result.duration);
+
+    reset_stats();
+    let reset = stats();
+    assert_eq!(reset.collections, 0);
+    assert_eq!(reset.allocations_freed, 0);
+    assert_eq!(reset.live_allocations, before.live_allocations);
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test that collection hooks are called around every collection, can't start a collection
+/// themselves, and can be removed.
+fn collect_hooks() {
+    thread_local! {
+        static STARTS: Cell<usize> = const { Cell::new(0) };
+        static ENDS: Cell<usize> = const { Cell::new(0) };
+        static PAUSE: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+        static NESTED: Cell<Option<CollectResult>> = const { Cell::new(None) };
+    }
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    set_collect_condition(|_| false);
+    set_collect_hooks(
+        CollectHooks::new()
+            .on_start(|_| {
+                STARTS.with(|s| s.set(s.get() + 1));
+                NESTED.with(|n| n.set(Some(collect())));
+            })
+            .on_end(|result| {
+                ENDS.with(|e| e.set(e.get() + 1));
+                PAUSE.with(|p| p.set(p.get() + result.duration));
+            }),
+    );
+
+    let gc = Gc::new(MultiRef {
+        refs: RefCell::new(Vec::new()),
+        drop_count: &DROP_COUNT,
+    });
+    gc.refs.borrow_mut().push(gc.clone());
+    drop(gc);
+    let first = collect();
+    let second = collect();
+
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 1);
+    assert_eq!(STARTS.with(Cell::get), 2);
+    assert_eq!(ENDS.with(Cell::get), 2);
+    assert_eq!(PAUSE.with(Cell::get), first.duration + second.duration);
+    // the collection attempted from inside the hook did nothing
+    assert_eq!(NESTED.with(Cell::get), Some(CollectResult::default()));
+
+    set_collect_hooks(CollectHooks::default());
+    collect();
+    assert_eq!(STARTS.with(Cell::get), 2);
+    assert_eq!(ENDS.with(Cell::get), 2);
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test that a closure can be installed as the collect condition, and that the previous condition
+/// can be restored.
+fn boxed_collect_condition() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    fn make_cycle() {
+        let gc = Gc::new(MultiRef {
+            refs: RefCell::new(Vec::new()),
+            drop_count: &DROP_COUNT,
+        });
+        gc.refs.borrow_mut().push(gc.clone());
+    }
+
+    let enabled = Arc::new(AtomicBool::new(false));
+    let switch = Arc::clone(&enabled);
+    let previous = set_collect_condition_boxed(move |_| switch.load(Ordering::Relaxed));
+
+    // the kill-switch is off, so nothing is collected automatically
+    make_cycle();
+    make_cycle();
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+
+    // dropping the next `Gc` collects everything
+    enabled.store(true, Ordering::Relaxed);
+    make_cycle();
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 3);
+
+    let ours = set_collect_condition_boxed(previous);
+    assert!(ours(&CollectInfo { heap: None }));
+    enabled.store(false, Ordering::Relaxed);
+    assert!(!ours(&CollectInfo { heap: None }));
+    drop(ours);
+    assert_eq!(Arc::strong_count(&enabled), 1);
+}
+
+#[test]
+/// Test that the heap size counts large payloads, and that a byte-threshold condition only fires
+/// once the threshold is crossed.
+fn heap_threshold() {
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+    const N: usize = 4;
+
+    struct Big {
+        this: RefCell<Option<Gc<Big>>>,
+        _payload: [u8; 1 << 13],
+    }
+
+    unsafe impl Collectable for Big {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.this.accept(visitor)
+        }
+    }
+
+    impl Drop for Big {
+        fn drop(&mut self) {
+            DROP_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn make_cycle() {
+        let gc = Gc::new(Big {
+            this: RefCell::new(None),
+            _payload: [0; 1 << 13],
+        });
+        *gc.this.borrow_mut() = Some(gc.clone());
+    }
+
+    let size = Layout::new::<GcBox<Big>>().size();
+    assert!(size > 1 << 13);
+    set_collect_condition(|_| false);
+    collect();
+    let baseline = stats().live_bytes;
+
+    let previous = set_collect_condition_boxed(collect_when_heap_exceeds(baseline + N * size));
+    for i in 1..=N {
+        make_cycle();
+        assert_eq!(stats().live_bytes, baseline + i * size);
+    }
+    // the heap is exactly at the threshold, so nothing has been collected yet
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+
+    make_cycle();
+    assert_eq!(DROP_COUNT.load(Ordering::Relaxed), N + 1);
+    assert_eq!(stats().live_bytes, baseline);
+
+    drop(set_collect_condition_boxed(previous));
+    set_collect_condition(default_collect_condition);
+}
+#[test]
+/// Test that a composed collect condition fires when either of its parts would, under scripted
+/// workloads of many small drops and of a large heap.
+fn composed_collect_condition() {
+    struct Big {
+        _payload: [u8; 1 << 14],
+    }
+
+    unsafe impl Collectable for Big {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    set_collect_condition_with(
+        CollectCondition::heap_bytes_above(1_000_000).or(CollectCondition::every_n_drops(10_000)),
+    );
+    collect();
+    let collections = stats().collections;
+
+    // a small heap only collects on every 10,000th drop
+    let small = Gc::new(0u8);
+    for _ in 0..9_999 {
+        drop(small.clone());
+    }
+    assert_eq!(stats().collections, collections);
+    drop(small.clone());
+    assert_eq!(stats().collections, collections + 1);
+    for _ in 0..10_000 {
+        drop(small.clone());
+    }
+    assert_eq!(stats().collections, collections + 2);
+
+    // a heap over the threshold collects on every drop
+    let big = (0..100)
+        .map(|_| {
+            Gc::new(Big {
+                _payload: [0; 1 << 14],
+            })
+        })
+        .collect::<Vec<_>>();
+    assert!(stats().live_bytes > 1_000_000);
+    drop(small.clone());
+    drop(small.clone());
+    assert_eq!(stats().collections, collections + 4);
+
+    // both parts must agree for a conjunction to fire
+    set_collect_condition_with(
+        CollectCondition::heap_bytes_above(1_000_000).and(CollectCondition::every_n_drops(3)),
+    );
+    drop(small.clone());
+    drop(small.clone());
+    assert_eq!(stats().collections, collections + 4);
+    drop(small.clone());
+    assert_eq!(stats().collections, collections + 5);
+
+    // nothing is collected while the big allocations are freed, leaving the heap small
+    set_collect_condition_with(CollectCondition::never());
+    drop(big);
+    for _ in 0..20_000 {
+        drop(small.clone());
+    }
+    assert_eq!(stats().collections, collections + 5);
+    collect();
+    set_collect_condition_with(
+        CollectCondition::heap_bytes_above(1_000_000).and(CollectCondition::every_n_drops(3)),
+    );
+    for _ in 0..3 {
+        drop(small.clone());
+    }
+    assert_eq!(stats().collections, collections + 6);
+
+    set_collect_condition_with(CollectCondition::default());
+}
+
+#[test]
+#[cfg(feature = "leak-detection")]
+/// Test that allocations which a thread could not free are reported when it exits.
+fn leak_detection() {
+    struct Leaked(#[allow(unused)] u64);
+    struct Freed;
+
+    unsafe impl Collectable for Leaked {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    unsafe impl Collectable for Freed {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    std::thread::spawn(|| {
+        std::mem::forget(Gc::new(Leaked(0)));
+        std::mem::forget(Gc::new(Leaked(1)));
+        drop(Gc::new(Freed));
+    })
+    .join()
+    .unwrap();
+
+    // other tests' threads may leak too, so only look at our own types
+    let report = take_leak_report();
+    let leaked = report
+        .allocations
+        .iter()
+        .filter(|a| a.type_name.ends_with("Leaked"))
+        .collect::<Vec<_>>();
+    assert_eq!(leaked.len(), 2);
+    assert!(leaked
+        .iter()
+        .all(|a| a.size == Layout::new::<GcBox<Leaked>>().size()));
+    assert!(!report
+        .allocations
+        .iter()
+        .any(|a| a.type_name.ends_with("Freed")));
+
+    // the report was taken, so it is now empty of our types
+    assert!(!take_leak_report()
+        .allocations
+        .iter()
+        .any(|a| a.type_name.ends_with("Leaked")));
+}
+
+#[test]
+#[cfg(feature = "heap-dump")]
+/// Test that a heap dump draws every allocation in a cycle once, with its edges labeled by field.
+fn heap_dump_dot() {
+    struct Pair {
+        left: RefCell<Option<Gc<Pair>>>,
+        right: RefCell<Option<Gc<Pair>>>,
+    }
+
+    unsafe impl Collectable for Pair {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            visitor.enter_field("left");
+            self.left.accept(visitor)?;
+            visitor.exit_field();
+            visitor.enter_field("right");
+            self.right.accept(visitor)?;
+            visitor.exit_field();
+            Ok(())
+        }
+    }
+
+    fn pair() -> Gc<Pair> {
+        Gc::new(Pair {
+            left: RefCell::new(None),
+            right: RefCell::new(None),
+        })
+    }
+
+    let a = pair();
+    let b = pair();
+    *a.left.borrow_mut() = Some(b.clone());
+    *b.right.borrow_mut() = Some(a.clone());
+    *b.left.borrow_mut() = Some(b.clone());
+    let a_addr = format!("{:p}", Gc::as_ptr(&a));
+    let b_addr = format!("{:p}", Gc::as_ptr(&b));
+    let counts = DUMPSTER.with(|d| (d.n_ref_drops.get(), d.n_refs_living.get()));
+
+    let mut dot = Vec::new();
+    dump_heap_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+
+    assert!(dot.starts_with("digraph dumpster {\n"));
+    assert!(dot.ends_with("}\n"));
+    let size = Layout::new::<GcBox<Pair>>().size();
+    let type_name = std::any::type_name::<Pair>();
+    assert_eq!(
+        dot.matches(&format!(
+            r#""{a_addr}" [label="{type_name}\n{size} bytes, 2 refs"]"#
+        ))
+        .count(),
+        1
+    );
+    assert_eq!(
+        dot.matches(&format!(
+            r#""{b_addr}" [label="{type_name}\n{size} bytes, 3 refs"]"#
+        ))
+        .count(),
+        1
+    );
+    assert!(dot.contains(&format!(r#""{a_addr}" -> "{b_addr}" [label="left"];"#)));
+    assert!(dot.contains(&format!(r#""{b_addr}" -> "{a_addr}" [label="right"];"#)));
+    assert!(dot.contains(&format!(r#""{b_addr}" -> "{b_addr}" [label="left"];"#)));
+    assert_eq!(dot.matches(&format!(r#""{a_addr}" -> "#)).count(), 1);
+
+    // dumping the heap must not change what the collector knows about it
+    assert_eq!(
+        DUMPSTER.with(|d| (d.n_ref_drops.get(), d.n_refs_living.get())),
+        counts
+    );
+    drop((a, b));
+    collect();
+    let mut dot = Vec::new();
+    dump_heap_dot(&mut dot).unwrap();
+    assert!(!String::from_utf8(dot).unwrap().contains(&a_addr));
+}
+
+#[test]
+#[cfg(feature = "heap-inspection")]
+/// Test that iterating over the live allocations finds a cycle with the right type names and
+/// reference counts, and that it is gone once collected.
+fn iter_allocations_lists_live() {
+    let counter = DropCounter::new();
+    graph!(unsync, counter; a, b; a -> b, b -> a, b -> b);
+    let name = Gc::new(String::from("name"));
+    let addrs = [
+        Gc::as_ptr(&a) as usize,
+        Gc::as_ptr(&b) as usize,
+        Gc::as_ptr(&name) as usize,
+    ];
+
+    let mut found = iter_allocations()
+        .filter(|info| addrs.contains(&info.addr))
+        .map(|info| (info.addr, info.type_name, info.ref_count, info.size))
+        .collect::<Vec<_>>();
+    found.sort_unstable_by_key(|&(addr, ..)| addrs.iter().position(|&a| a == addr));
+    let node = std::any::type_name::<crate::testing::unsync::Node>();
+    let node_size = Layout::new::<GcBox<crate::testing::unsync::Node>>().size();
+    assert_eq!(
+        found,
+        [
+            (addrs[0], node, 2, node_size),
+            (addrs[1], node, 3, node_size),
+            (
+                addrs[2],
+                "alloc::string::String",
+                1,
+                Layout::new::<GcBox<String>>().size()
+            ),
+        ]
+    );
+
+    // creating and dropping allocations while iterating is fine
+    for info in iter_allocations() {
+        drop(Gc::new(info.size));
+    }
+
+    drop((a, b, name));
+    collect();
+    assert_eq!(counter.count(), 2);
+    assert!(iter_allocations().all(|info| !addrs.contains(&info.addr)));
+}
+#[test]
+#[cfg(feature = "heap-inspection")]
+/// Test that the type census counts the allocations of each type, and drops a collected cycle.
+fn type_census_counts() {
+    struct Cycle {
+        next: RefCell<Option<Gc<Cycle>>>,
+    }
+
+    unsafe impl Collectable for Cycle {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    struct Leaf {
+        _bytes: [u8; 24],
+    }
+
+    unsafe impl Collectable for Leaf {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    fn count_of<T>() -> (usize, usize) {
+        let name = std::any::type_name::<T>();
+        type_census()
+            .into_iter()
+            .find(|(type_name, ..)| type_name == name)
+            .map_or((0, 0), |(_, count, bytes)| (count, bytes))
+    }
+
+    let cycle_size = Layout::new::<GcBox<Cycle>>().size();
+    let leaf_size = Layout::new::<GcBox<Leaf>>().size();
+    let cycles = (0..3)
+        .map(|_| {
+            Gc::new(Cycle {
+                next: RefCell::new(None),
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, gc) in cycles.iter().enumerate() {
+        *gc.next.borrow_mut() = Some(cycles[(i + 1) % cycles.len()].clone());
+    }
+    let leaves = (0..5)
+        .map(|_| Gc::new(Leaf { _bytes: [0; 24] }))
+        .collect::<Vec<_>>();
+    assert_eq!(count_of::<Cycle>(), (3, 3 * cycle_size));
+    assert_eq!(count_of::<Leaf>(), (5, 5 * leaf_size));
+
+    let census = type_census();
+    assert!(census.windows(2).all(|w| w[0].0 < w[1].0));
+
+    // the cycle is still counted until it is collected
+    drop(cycles);
+    assert_eq!(count_of::<Cycle>(), (3, 3 * cycle_size));
+    collect();
+    assert_eq!(count_of::<Cycle>(), (0, 0));
+    assert_eq!(count_of::<Leaf>(), (5, 5 * leaf_size));
+
+    drop(leaves);
+    assert_eq!(count_of::<Leaf>(), (0, 0));
+}
+
+#[test]
+#[cfg(feature = "tracing")]
+/// Test that a collection is reported as a `tracing` span with a summary of its work.
+fn tracing_span() {
+    use tracing_subscriber::fmt::{format::FmtSpan, MakeWriter};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Buffer {
+            self.clone()
+        }
+    }
+
+    let buffer = Buffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_writer(buffer.clone())
+        .finish();
+
+    let counter = DropCounter::new();
+    let size = Layout::new::<GcBox<crate::testing::unsync::Node>>().size();
+    set_collect_condition(|_| false);
+    tracing::subscriber::with_default(subscriber, || {
+        graph!(unsync, counter; a, b; a -> b, b -> a);
+        drop((a, b));
+        collect();
+    });
+    assert_eq!(counter.count(), 2);
+    set_collect_condition(default_collect_condition);
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let line = output
+        .lines()
+        .find(|line| line.contains("dumpster::collect{"))
+        .expect("no collection span was emitted");
+    assert!(line.contains("flavor=\"unsync\""));
+    assert!(line.contains("allocations_examined=2"));
+    assert!(line.contains("allocations_freed=2"));
+    assert!(line.contains(&format!("bytes_freed={}", 2 * size)));
+    assert!(line.contains("duration="));
+}
+
+#[test]
+/// Test that reserving room ahead of a burst of dirty allocations means that the table tracking
+/// them is never reallocated.
+fn reserve_avoids_reallocation() {
+    const N: usize = 10_000;
+    std::thread::spawn(|| {
+        assert!(set_initial_capacity(16));
+        assert!(dirty_capacity() >= 16);
+        assert!(!set_initial_capacity(N));
+
+        set_collect_condition(|_| false);
+        reserve(N);
+        let capacity = dirty_capacity();
+        assert!(capacity >= N);
+
+        let gcs = (0..N).map(Gc::new).collect::<Vec<_>>();
+        for gc in &gcs {
+            drop(gc.clone());
+        }
+        assert_eq!(dirty_capacity(), capacity);
+
+        collect();
+        drop(gcs);
+        set_collect_condition(default_collect_condition);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+/// Test that every finalizer in a garbage cycle runs before any value in it is dropped, and can
+/// still read the other allocations in the cycle.
+fn finalizer_before_drop() {
+    thread_local! {
+        static EVENTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct Handle {
+        name: &'static str,
+        peer: RefCell<Option<Gc<Handle>>>,
+    }
+
+    unsafe impl Collectable for Handle {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.peer.accept(visitor)
+        }
+    }
+
+    impl Finalize for Handle {
+        fn finalize(&self) {
+            let peer = self.peer.borrow();
+            let peer_name = peer.as_ref().unwrap().name;
+            EVENTS.with(|e| {
+                e.borrow_mut()
+                    .push(format!("finalize {} (peer {peer_name})", self.name));
+            });
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            EVENTS.with(|e| e.borrow_mut().push(format!("drop {}", self.name)));
+        }
+    }
+
+    let a = Gc::new_finalized(Handle {
+        name: "a",
+        peer: RefCell::new(None),
+    });
+    let b = Gc::new_finalized(Handle {
+        name: "b",
+        peer: RefCell::new(Some(a.clone())),
+    });
+    *a.peer.borrow_mut() = Some(b.clone());
+    drop((a, b));
+    collect();
+
+    let mut events = EVENTS.with(RefCell::take);
+    assert_eq!(events.len(), 4);
+    events[..2].sort();
+    events[2..].sort();
+    assert_eq!(
+        events,
+        [
+            "finalize a (peer b)",
+            "finalize b (peer a)",
+            "drop a",
+            "drop b"
+        ]
+    );
+}
+
+#[test]
+/// Test that an allocation whose last `Gc` is dropped is finalized once, before it is dropped.
+fn finalizer_acyclic() {
+    thread_local! {
+        static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct Handle;
+
+    unsafe impl Collectable for Handle {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    impl Finalize for Handle {
+        fn finalize(&self) {
+            EVENTS.with(|e| e.borrow_mut().push("finalize"));
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            EVENTS.with(|e| e.borrow_mut().push("drop"));
+        }
+    }
+
+    let gc = Gc::new_finalized(Handle);
+    drop(gc.clone());
+    drop(gc);
+    collect();
+    assert_eq!(EVENTS.with(RefCell::take), ["finalize", "drop"]);
+}
+
+#[test]
+/// Test that a finalizer can resurrect its cycle, and that it does not run again when the cycle
+/// dies for good.
+fn finalizer_resurrection() {
+    thread_local! {
+        static SAVED: RefCell<Option<Gc<Node>>> = const { RefCell::new(None) };
+        static N_FINALIZED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Node {
+        next: RefCell<Option<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    impl Finalize for Node {
+        fn finalize(&self) {
+            N_FINALIZED.with(|n| n.set(n.get() + 1));
+            let next = self.next.borrow().clone();
+            SAVED.with(|s| *s.borrow_mut() = next);
+        }
+    }
+
+    let counter = DropCounter::new();
+    let a = Gc::new_finalized(Node {
+        next: RefCell::new(None),
+        _token: counter.token(),
+    });
+    *a.next.borrow_mut() = Some(a.clone());
+    drop(a);
+    collect();
+    assert_eq!(N_FINALIZED.with(Cell::get), 1);
+    assert_eq!(counter.count(), 0);
+
+    let saved = SAVED.with(RefCell::take).unwrap();
+    assert!(Gc::ptr_eq(&saved, saved.next.borrow().as_ref().unwrap()));
+    drop(saved);
+    collect();
+    assert_eq!(N_FINALIZED.with(Cell::get), 1);
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+/// Test that a value which panics while it is collected is leaked, and that everything else in
+/// the collection is still freed.
+fn panic_leak_allocation() {
+    struct Node {
+        next: RefCell<Option<Gc<Node>>>,
+        panics: bool,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            assert!(!self.panics, "node panicked on drop");
+        }
+    }
+
+    let _guard = crate::unwind::POLICY_LOCK.lock().unwrap();
+    crate::set_collection_panic_policy(crate::PanicPolicy::LeakAllocation);
+
+    let live = stats().live_allocations;
+    let counter = DropCounter::new();
+    let nodes: Vec<Gc<Node>> = (0..4)
+        .map(|i| {
+            Gc::new(Node {
+                next: RefCell::new(None),
+                panics: i == 1,
+                _token: counter.token(),
+            })
+        })
+        .collect();
+    for (i, node) in nodes.iter().enumerate() {
+        *node.next.borrow_mut() = Some(nodes[(i + 1) % nodes.len()].clone());
+    }
+    drop(nodes);
+
+    let result = std::panic::catch_unwind(collect);
+    crate::set_collection_panic_policy(crate::PanicPolicy::Propagate);
+    assert!(result.is_err());
+    // the panicking node still dropped its fields while unwinding
+    assert_eq!(counter.count(), 4);
+    // only the allocation of the panicking node was leaked
+    assert_eq!(stats().live_allocations, live + 1);
+
+    // the collector is still usable afterwards
+    let counter = DropCounter::new();
+    let a = Gc::new(Node {
+        next: RefCell::new(None),
+        panics: false,
+        _token: counter.token(),
+    });
+    *a.next.borrow_mut() = Some(a.clone());
+    drop(a);
+    collect();
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+/// Test that a value which panics while it is collected does not stop the rest of its cycle from
+/// being freed, and that the panic resumes once the collection is done.
+fn panic_propagate() {
+    struct Node {
+        next: RefCell<Option<Gc<Node>>>,
+        panics: bool,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            assert!(!self.panics, "node panicked on drop");
+        }
+    }
+
+    fn cycle(counter: &DropCounter, len: usize, panicking: usize) {
+        let nodes: Vec<Gc<Node>> = (0..len)
+            .map(|i| {
+                Gc::new(Node {
+                    next: RefCell::new(None),
+                    panics: i == panicking,
+                    _token: counter.token(),
+                })
+            })
+            .collect();
+        for (i, node) in nodes.iter().enumerate() {
+            *node.next.borrow_mut() = Some(nodes[(i + 1) % nodes.len()].clone());
+        }
+    }
+
+    let _guard = crate::unwind::POLICY_LOCK.lock().unwrap();
+    assert_eq!(
+        crate::collection_panic_policy(),
+        crate::PanicPolicy::Propagate
+    );
+
+    let live = stats().live_allocations;
+    for deterministic in [false, true] {
+        set_deterministic(deterministic);
+        let counter = DropCounter::new();
+        cycle(&counter, 8, 3);
+        let result = std::panic::catch_unwind(collect);
+        set_deterministic(false);
+        let payload = result.unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<&str>(),
+            Some(&"node panicked on drop")
+        );
+        // every node was dropped and freed, including the one which panicked
+        assert_eq!(counter.count(), 8);
+        assert_eq!(stats().live_allocations, live);
+    }
+
+    // the collector is still usable afterwards, and the panic is not raised again
+    let counter = DropCounter::new();
+    cycle(&counter, 4, usize::MAX);
+    collect();
+    assert_eq!(counter.count(), 4);
+    assert_eq!(stats().live_allocations, live);
+    collect();
+}
+
+#[test]
+/// Test that a deterministic collection drops garbage in the order in which it was allocated.
+fn deterministic_drop_order() {
+    struct Node {
+        id: usize,
+        edges: RefCell<Vec<Gc<Node>>>,
+        dropped: Rc<RefCell<Vec<usize>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.dropped.borrow_mut().push(self.id);
+        }
+    }
+
+    fn scenario() -> Vec<usize> {
+        let dropped = Rc::new(RefCell::new(Vec::new()));
+        let nodes = (0..16)
+            .map(|id| {
+                Gc::new(Node {
+                    id,
+                    edges: RefCell::new(Vec::new()),
+                    dropped: dropped.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        for (i, node) in nodes.iter().enumerate() {
+            let mut edges = node.edges.borrow_mut();
+            edges.push(nodes[(i * 7 + 3) % nodes.len()].clone());
+            edges.push(nodes[(i * 5 + 11) % nodes.len()].clone());
+        }
+        drop(nodes);
+        collect();
+        dropped.take()
+    }
+
+    set_deterministic(true);
+    let first = scenario();
+    let second = scenario();
+    set_deterministic(false);
+
+    assert_eq!(first, (0..16).collect::<Vec<_>>());
+    assert_eq!(first, second);
+}
+
+#[test]
+/// Test that nothing reachable from a root is freed, and that the rooted allocations are collected
+/// once the root is gone.
+fn root_keeps_reachable() {
+    struct Node {
+        edges: RefCell<Vec<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    let counter = DropCounter::new();
+    let node = || {
+        Gc::new(Node {
+            edges: RefCell::new(Vec::new()),
+            _token: counter.token(),
+        })
+    };
+
+    // a rooted cycle, with a child which is only reachable through the root
+    let root = Gc::into_root(node());
+    let child = node();
+    root.edges.borrow_mut().push(child.clone());
+    child.edges.borrow_mut().push(Root::as_gc(&root).clone());
+
+    // a garbage cycle which also points into the rooted cycle
+    let garbage = node();
+    garbage.edges.borrow_mut().push(garbage.clone());
+    garbage.edges.borrow_mut().push(child.clone());
+    drop(garbage);
+
+    // make everything dirty
+    drop(child.clone());
+    drop(Root::as_gc(&root).clone());
+    drop(child);
+
+    collect();
+    assert_eq!(counter.count(), 1);
+    while collect_with_work(1) == CollectProgress::Pending {}
+    assert_eq!(counter.count(), 1);
+
+    let clone = root.clone();
+    drop(root);
+    collect();
+    assert_eq!(counter.count(), 1);
+
+    drop(Root::into_gc(clone));
+    collect();
+    assert_eq!(counter.count(), 3);
+}
+#[test]
+/// Test reachability queries over a diamond, a cycle, and a pair of disconnected allocations.
+fn reachability_queries() {
+    fn ids<T: Collectable + ?Sized>(gcs: &[&Gc<T>]) -> Vec<GcId> {
+        gcs.iter()
+            .map(|gc| GcId::of(Gc::try_deref(gc).unwrap()))
+            .collect()
+    }
+
+    let counter = DropCounter::new();
+
+    // a diamond
+    graph!(unsync, counter; top, left, right, bottom;
+        top -> left, top -> right, left -> bottom, right -> bottom);
+    assert!(is_reachable_from(&top, &bottom));
+    assert!(is_reachable_from(&left, &bottom));
+    assert!(!is_reachable_from(&left, &right));
+    assert!(!is_reachable_from(&bottom, &top));
+    let path = path_from(&top, &bottom).unwrap();
+    assert!(path == ids(&[&top, &left, &bottom]) || path == ids(&[&top, &right, &bottom]));
+    assert_eq!(path_from(&top, &top), Some(ids(&[&top])));
+
+    // a cycle, which every allocation in it can reach
+    graph!(unsync, counter; a, b, c; a -> b, b -> c, c -> a);
+    for (from, to) in [(&a, &b), (&b, &a), (&c, &b), (&a, &a)] {
+        assert!(is_reachable_from(from, to));
+    }
+    assert_eq!(path_from(&b, &a), Some(ids(&[&b, &c, &a])));
+    assert!(!is_reachable_from(&a, &top));
+
+    // a disconnected pair, of different types
+    let lonely = Gc::new(0u32);
+    graph!(unsync, counter; other; );
+    assert!(!is_reachable_from(&lonely, &other));
+    assert!(!is_reachable_from(&other, &lonely));
+    assert_eq!(path_from(&other, &lonely), None);
+
+    // the queries left the collector alone, so the cycle is still freed as usual
+    drop((top, left, right, bottom, a, b, c, other));
+    collect();
+    assert_eq!(counter.count(), 8);
+}
+
+#[test]
+/// Test that a dry run reports exactly the allocations which the next collection frees, without
+/// freeing any of them.
+fn dry_run_matches_collection() {
+    struct Node {
+        edges: RefCell<Vec<Gc<Node>>>,
+        dropped: Rc<RefCell<HashSet<usize>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.dropped
+                .borrow_mut()
+                .insert(std::ptr::from_ref(self) as usize);
+        }
+    }
+
+    let dropped = Rc::new(RefCell::new(HashSet::new()));
+    let node = || {
+        Gc::new(Node {
+            edges: RefCell::new(Vec::new()),
+            dropped: dropped.clone(),
+        })
+    };
+
+    // a live cycle, which is made dirty
+    let live = node();
+    let other = node();
+    live.edges.borrow_mut().push(other.clone());
+    other.edges.borrow_mut().push(live.clone());
+    drop(other);
+
+    // a garbage cycle which points into the live one
+    let a = node();
+    let b = node();
+    a.edges.borrow_mut().push(b.clone());
+    b.edges.borrow_mut().push(a.clone());
+    b.edges.borrow_mut().push(live.clone());
+    drop(a);
+    drop(b);
+
+    let report = collect_dry_run();
+    assert!(dropped.take().is_empty());
+    assert_eq!(report.count(), 2);
+
+    let result = collect();
+    assert_eq!(report.count(), result.allocations_freed);
+    assert_eq!(report.bytes(), result.bytes_freed);
+    let reported = report
+        .allocations
+        .iter()
+        .map(|alloc| alloc.addr)
+        .collect::<HashSet<_>>();
+    assert_eq!(reported, dropped.take());
+
+    drop(live);
+    collect();
+}
+
+#[test]
+/// Test that a finalizer can ask for a dry run while the collection which runs it is using the
+/// scratch structures, and that collections afterwards are unaffected.
+fn dry_run_in_finalizer() {
+    thread_local! {
+        static REPORTED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Node {
+        next: RefCell<Option<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.next.accept(visitor)
+        }
+    }
+
+    impl Finalize for Node {
+        fn finalize(&self) {
+            REPORTED.with(|r| r.set(r.get() + collect_dry_run().count()));
+        }
+    }
+
+    let counter = DropCounter::new();
+    for round in 1..=3 {
+        let a = Gc::new_finalized(Node {
+            next: RefCell::new(None),
+            _token: counter.token(),
+        });
+        let b = Gc::new_finalized(Node {
+            next: RefCell::new(Some(a.clone())),
+            _token: counter.token(),
+        });
+        *a.next.borrow_mut() = Some(b);
+        drop(a);
+        REPORTED.with(|r| r.set(0));
+        collect();
+        // each finalizer sees both nodes of the cycle as garbage
+        assert_eq!(REPORTED.with(Cell::get), 4);
+        assert_eq!(counter.count(), 2 * round);
+    }
+}
+
+/// A node in a graph whose allocations come from several heaps.
+struct HeapNode {
+    edges: RefCell<Vec<Gc<HeapNode>>>,
+    _token: DropToken,
+}
+
+unsafe impl Collectable for HeapNode {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.edges.accept(visitor)
+    }
+}
+
+impl HeapNode {
+    fn new(token: DropToken) -> HeapNode {
+        HeapNode {
+            edges: RefCell::new(Vec::new()),
+            _token: token,
+        }
+    }
+}
+
+#[test]
+/// Test that collecting one heap leaves the allocations of every other heap alone.
+fn independent_heaps() {
+    let a_counter = DropCounter::new();
+    let b_counter = DropCounter::new();
+    let a = Heap::new();
+    let b = Heap::new();
+
+    // a garbage cycle in each heap, allocated in turn
+    let a1 = a.alloc(HeapNode::new(a_counter.token()));
+    let b1 = b.alloc(HeapNode::new(b_counter.token()));
+    let a2 = a.alloc(HeapNode::new(a_counter.token()));
+    let b2 = b.alloc(HeapNode::new(b_counter.token()));
+    a1.edges.borrow_mut().push(a2.clone());
+    a2.edges.borrow_mut().push(a1.clone());
+    b1.edges.borrow_mut().push(b2.clone());
+    b2.edges.borrow_mut().push(b1.clone());
+
+    // the garbage in `a` also holds a live allocation in `b`
+    let shared = b.alloc(HeapNode::new(b_counter.token()));
+    a2.edges.borrow_mut().push(shared.clone());
+    drop((a1, a2, b1, b2));
+
+    collect();
+    assert_eq!(a_counter.count(), 0);
+    assert_eq!(b_counter.count(), 0);
+
+    assert_eq!(b.collect().allocations_freed, 2);
+    assert_eq!(a_counter.count(), 0);
+    assert_eq!(b_counter.count(), 2);
+
+    assert_eq!(a.collect().allocations_freed, 2);
+    assert_eq!(a_counter.count(), 2);
+    assert_eq!(b_counter.count(), 2);
+
+    // the garbage in `a` gave up its reference, so this is the last one
+    drop(shared);
+    assert_eq!(b_counter.count(), 3);
+}
+
+#[test]
+/// Test that clearing a heap drops every value in it, leaving the handles into it dead.
+fn clear_heap_with_live_handles() {
+    let counter = DropCounter::new();
+    let heap = Heap::new();
+
+    let first = heap.alloc(HeapNode::new(counter.token()));
+    let second = heap.alloc(HeapNode::new(counter.token()));
+    first.edges.borrow_mut().push(second.clone());
+    second.edges.borrow_mut().push(first.clone());
+    // a value in the default heap which points into the cleared one
+    let outside = Gc::new(HeapNode::new(counter.token()));
+    outside.edges.borrow_mut().push(second.clone());
+
+    heap.clear();
+    assert_eq!(counter.count(), 2);
+    assert!(Gc::try_deref(&first).is_none());
+    assert!(Gc::try_clone(&second).is_none());
+    assert!(
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| first.edges.borrow().len()))
+            .is_err()
+    );
+    assert!(Gc::try_deref(&outside).is_some());
+
+    // the heap can still be used once it has been cleared
+    let third = heap.alloc(HeapNode::new(counter.token()));
+    drop((first, second, outside));
+    assert_eq!(counter.count(), 3);
+
+    drop(heap);
+    assert_eq!(counter.count(), 4);
+    assert!(Gc::try_deref(&third).is_none());
+}
+
+#[test]
+/// Test that the cycles made inside a region are freed as soon as it ends.
+fn region_frees_cycles() {
+    let counter = DropCounter::new();
+    region(|r| {
+        let a = r.alloc(HeapNode::new(counter.token()));
+        let b = r.alloc(HeapNode::new(counter.token()));
+        a.edges.borrow_mut().push(b.clone());
+        b.edges.borrow_mut().push(a.clone());
+        a.edges.borrow_mut().push(a.clone());
+    });
+    assert_eq!(counter.count(), 2);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic = "1 allocations escaped from a region"]
+/// Test that a handle which escapes a region is caught.
+fn region_escape() {
+    let counter = DropCounter::new();
+    let escaped = region(|r| {
+        let a = r.alloc(HeapNode::new(counter.token()));
+        a.edges.borrow_mut().push(a.clone());
+        a
+    });
+    drop(escaped);
+}
+
+#[test]
+/// Test that the entry of a weak-keyed map is freed along with its key, while the map is alive.
+fn weak_key_map_drops_dead_entries() {
+    let keys = DropCounter::new();
+    let values = DropCounter::new();
+    let mut map = WeakKeyHashMap::new();
+    let a = Gc::new(HeapNode::new(keys.token()));
+    let b = Gc::new(HeapNode::new(keys.token()));
+    map.insert(&a, Gc::new(HeapNode::new(values.token())));
+    map.insert(&b, Gc::new(HeapNode::new(values.token())));
+    assert_eq!(map.len(), 2);
+
+    // a cycle through the key keeps it from being freed right away
+    a.edges.borrow_mut().push(a.clone());
+    drop(a);
+    collect();
+    assert_eq!(keys.count(), 1);
+    assert_eq!(values.count(), 1);
+    assert_eq!(map.len(), 1);
+
+    // a live key keeps its value alive
+    assert!(map.get(&b).is_some());
+    drop(map);
+    drop(b);
+    assert_eq!(keys.count(), 2);
+    assert_eq!(values.count(), 2);
+}
+
+#[test]
+/// Test that the value of an ephemeron which points back at its key does not keep the key alive.
+fn ephemeron_value_refers_to_key() {
+    let counter = DropCounter::new();
+    let key = Gc::new(HeapNode::new(counter.token()));
+    let value = Gc::new(HeapNode::new(counter.token()));
+    value.edges.borrow_mut().push(key.clone());
+    let ephemeron = Ephemeron::new(&key, value);
+
+    collect();
+    assert_eq!(counter.count(), 0);
+    assert!(ephemeron.is_alive());
+
+    drop(key);
+    collect();
+    assert_eq!(counter.count(), 2);
+    assert!(ephemeron.key().is_none());
+    assert!(ephemeron.value().is_none());
+    assert_heap_empty();
+}
+
+#[test]
+/// Test that an ephemeron which is collected along with its key does not leave its value dangling.
+fn ephemeron_in_garbage_with_key() {
+    /// A node which holds an ephemeron keyed by itself.
+    struct Holder {
+        /// An ephemeron keyed by the allocation which holds it.
+        ephemeron: RefCell<Option<Ephemeron<Holder, Gc<HeapNode>>>>,
+        /// A reference to another holder, or to itself.
+        next: RefCell<Option<Gc<Holder>>>,
+    }
+
+    unsafe impl Collectable for Holder {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.ephemeron.accept(visitor)?;
+            self.next.accept(visitor)
+        }
+    }
+
+    let counter = DropCounter::new();
+    with_aggressive_collection(|| {
+        let a = Gc::new(Holder {
+            ephemeron: RefCell::new(None),
+            next: RefCell::new(None),
+        });
+        let b = Gc::new(Holder {
+            ephemeron: RefCell::new(None),
+            next: RefCell::new(Some(a.clone())),
+        });
+        *a.next.borrow_mut() = Some(b.clone());
+        let value = Gc::new(HeapNode::new(counter.token()));
+        value.edges.borrow_mut().push(value.clone());
+        // the ephemeron in `a` is keyed by `b`, and its value refers back to `b` through `next`
+        *a.ephemeron.borrow_mut() = Some(Ephemeron::new(&b, value));
+        *b.ephemeron.borrow_mut() =
+            Some(Ephemeron::new(&a, Gc::new(HeapNode::new(counter.token()))));
+    });
+    collect();
+    assert_eq!(counter.count(), 2);
+    assert_heap_empty();
+}
+
+#[test]
+/// Test that the entries of a `GcHashMap` disappear once their keys are collected.
+fn gc_hash_map_prunes_dead_keys() {
+    let keys = DropCounter::new();
+    let values = DropCounter::new();
+    let mut map = GcHashMap::new();
+    let a = Gc::new(HeapNode::new(keys.token()));
+    let b = Gc::new(HeapNode::new(keys.token()));
+    let c = Gc::new(HeapNode::new(keys.token()));
+    for key in [&a, &b, &c] {
+        map.insert(key, values.token());
+    }
+    assert_eq!(map.len(), 3);
+
+    // `b` and `c` die as part of a cycle
+    b.edges.borrow_mut().push(c.clone());
+    c.edges.borrow_mut().push(b.clone());
+    drop(b);
+    drop(c);
+    collect();
+    assert_eq!(keys.count(), 2);
+    assert_eq!(map.len(), 1);
+    assert!(map.get(&a).is_some());
+    assert_eq!(map.iter().count(), 1);
+    assert!(map.iter().all(|(k, _)| Gc::ptr_eq(&k, &a)));
+
+    map.prune();
+    assert_eq!(values.count(), 2);
+    drop(map);
+    assert_eq!(values.count(), 3);
+}
+
+#[test]
+/// Test that a `GcHashMap` which is itself garbage-collected is freed along with what it holds.
+fn gc_hash_map_in_gc() {
+    /// A node holding a map from other nodes to their names.
+    struct Named {
+        /// The names of the nodes this node knows about.
+        names: RefCell<GcHashMap<Named, Gc<HeapNode>>>,
+        /// The nodes this node refers to.
+        edges: RefCell<Vec<Gc<Named>>>,
+    }
+
+    unsafe impl Collectable for Named {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.names.accept(visitor)?;
+            self.edges.accept(visitor)
+        }
+    }
+
+    let counter = DropCounter::new();
+    let a = Gc::new(Named {
+        names: RefCell::new(GcHashMap::new()),
+        edges: RefCell::new(Vec::new()),
+    });
+    let b = Gc::new(Named {
+        names: RefCell::new(GcHashMap::new()),
+        edges: RefCell::new(vec![a.clone()]),
+    });
+    a.edges.borrow_mut().push(b.clone());
+    a.names
+        .borrow_mut()
+        .insert(&b, Gc::new(HeapNode::new(counter.token())));
+    b.names
+        .borrow_mut()
+        .insert(&b, Gc::new(HeapNode::new(counter.token())));
+
+    collect();
+    assert_eq!(counter.count(), 0);
+
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(counter.count(), 2);
+    assert_heap_empty();
+}
+
+#[test]
+/// Test that dirty allocations which are destroyed in an arbitrary order leave the list of dirty
+/// allocations consistent, so that every cycle left in it is still collected.
+fn dirty_destroyed_out_of_order() {
+    const N: usize = 1000;
+    std::thread::spawn(|| {
+        set_collect_condition(|_| false);
+        let counter = DropCounter::new();
+        let mut singles = Vec::new();
+        for i in 0..N {
+            // a cycle, which only a collection can free
+            let a = Gc::new(HeapNode::new(counter.token()));
+            let b = Gc::new(HeapNode::new(counter.token()));
+            a.edges.borrow_mut().push(b.clone());
+            b.edges.borrow_mut().push(a);
+
+            // an allocation which is dirtied, and later destroyed by dropping its last `Gc`
+            let single = Gc::new(HeapNode::new(counter.token()));
+            single.edges.borrow_mut().push(b);
+            drop(single.clone());
+            singles.push((i * 7919 % N, single));
+        }
+        singles.sort_unstable_by_key(|(key, _)| *key);
+        drop(singles);
+        assert_eq!(counter.count(), N);
+
+        collect();
+        assert_eq!(counter.count(), 3 * N);
+        set_collect_condition(default_collect_condition);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+/// Test that partial collections free garbage cycles on both sides of the boundary between the
+/// allocations which have lost a reference and those a full collection found to be reachable.
+fn partial_straddling_cycles() {
+    std::thread::spawn(|| {
+        set_collect_condition(|_| false);
+        set_full_collection_interval(8);
+        let counter = DropCounter::new();
+
+        // a live chain, which the full collection finds to be reachable
+        let head = Gc::new(HeapNode::new(counter.token()));
+        let tail = Gc::new(HeapNode::new(counter.token()));
+        head.edges.borrow_mut().push(tail.clone());
+        drop(head.clone());
+        collect();
+        assert_eq!(counter.count(), 0);
+
+        // a new garbage cycle which refers into the clean allocations
+        let a = Gc::new(HeapNode::new(counter.token()));
+        let b = Gc::new(HeapNode::new(counter.token()));
+        a.edges.borrow_mut().push(b.clone());
+        b.edges.borrow_mut().push(a.clone());
+        a.edges.borrow_mut().push(tail.clone());
+        drop((a, b));
+        assert_eq!(collect_partial().allocations_freed, 2);
+        assert_eq!(counter.count(), 2);
+        assert_eq!(tail.edges.borrow().len(), 0);
+
+        // a garbage cycle through a clean allocation which has since lost its last outside
+        // reference, with the rest of the chain behind it
+        let c = Gc::new(HeapNode::new(counter.token()));
+        c.edges.borrow_mut().push(head.clone());
+        head.edges.borrow_mut().push(c);
+        drop((head, tail));
+        assert_eq!(collect_partial().allocations_freed, 3);
+        assert_eq!(counter.count(), 5);
+
+        set_full_collection_interval(1);
+        set_collect_condition(default_collect_condition);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+/// Test that a garbage cycle hidden behind an allocation a full collection found to be reachable
+/// is left alone by partial collections, and freed by the next full one.
+fn partial_leaves_clean_cycles() {
+    std::thread::spawn(|| {
+        set_collect_condition(|_| false);
+        set_full_collection_interval(3);
+        let counter = DropCounter::new();
+
+        let x = Gc::new(HeapNode::new(counter.token()));
+        let y = Gc::new(HeapNode::new(counter.token()));
+        x.edges.borrow_mut().push(y.clone());
+        y.edges.borrow_mut().push(x.clone());
+        drop(y);
+        collect();
+
+        // `y` is still stamped as reachable, so the cycle cannot be seen from `x` alone
+        drop(x);
+        assert_eq!(collect_partial().allocations_freed, 0);
+        assert_eq!(counter.count(), 0);
+
+        // every partial collection counts towards the next full one, so the second automatic
+        // collection is full
+        let unit = Gc::new(());
+        set_collect_condition(|_| true);
+        drop(unit.clone());
+        assert_eq!(counter.count(), 0);
+        drop(unit);
+        assert_eq!(counter.count(), 2);
+
+        set_full_collection_interval(1);
+        set_collect_condition(default_collect_condition);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+/// Test that mutating the heap between partial and full collections never frees a live
+/// allocation, and that full collections eventually free all of the garbage.
+fn partial_mutation() {
+    thread_local! {
+        /// The IDs of every node which has been dropped.
+        static DROPPED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+    }
+
+    struct Node {
+        id: usize,
+        refs: RefCell<Vec<Gc<Node>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.refs.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            DROPPED.with(|d| assert!(d.borrow_mut().insert(self.id)));
+        }
+    }
+
+    /// Check that nothing reachable from `node` has been dropped, without cloning any `Gc`s, which
+    /// would take the stamps of a full collection away.
+    fn check_alive(node: &Node, seen: &mut HashSet<usize>) {
+        assert!(
+            !DROPPED.with(|d| d.borrow().contains(&node.id)),
+            "live node {} was freed",
+            node.id
+        );
+        if seen.insert(node.id) {
+            for next in node.refs.borrow().iter() {
+                check_alive(next, seen);
+            }
+        }
+    }
+
+    std::thread::spawn(|| {
+        set_collect_condition(|_| false);
+        set_full_collection_interval(8);
+        fastrand::seed(0x9a_471a1);
+
+        let mut n_nodes = 0;
+        let mut n_freed = 0;
+        let mut roots: Vec<Gc<Node>> = Vec::new();
+        for _ in 0..5000 {
+            match fastrand::u8(0..32) {
+                0..=4 => {
+                    roots.push(Gc::new(Node {
+                        id: n_nodes,
+                        refs: RefCell::new(Vec::new()),
+                    }));
+                    n_nodes += 1;
+                }
+                5..=14 if !roots.is_empty() => {
+                    // link two roots together, possibly forming a cycle
+                    let from = &roots[fastrand::usize(..roots.len())];
+                    let to = roots[fastrand::usize(..roots.len())].clone();
+                    from.refs.borrow_mut().push(to);
+                }
+                15..=19 if !roots.is_empty() => {
+                    // move a reference out of the heap and onto the stack without touching its
+                    // count
+                    let from = &roots[fastrand::usize(..roots.len())];
+                    let moved = from.refs.borrow_mut().pop();
+                    roots.extend(moved);
+                }
+                20..=24 if !roots.is_empty() => {
+                    // move a reference from the stack into the heap
+                    let moved = roots.swap_remove(fastrand::usize(..roots.len()));
+                    if let Some(to) = roots.get(fastrand::usize(..=roots.len())) {
+                        to.refs.borrow_mut().push(moved);
+                    }
+                }
+                25..=28 if !roots.is_empty() => {
+                    drop(roots.swap_remove(fastrand::usize(..roots.len())));
+                }
+                29 => n_freed += collect_partial().allocations_freed,
+                30 | 31 => n_freed += collect().allocations_freed,
+                _ => {}
+            }
+            let mut seen = HashSet::new();
+            for root in &roots {
+                check_alive(root, &mut seen);
+            }
+        }
+        // make sure the collections in the middle actually did something
+        assert!(n_freed > 0);
+
+        drop(roots);
+        collect();
+        assert_eq!(DROPPED.with(|d| d.borrow().len()), n_nodes);
+
+        set_full_collection_interval(1);
+        set_collect_condition(default_collect_condition);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+/// Test that an allocation made from a recycled block starts out the same as a fresh one.
+fn free_list_reinitializes() {
+    std::thread::spawn(|| {
+        set_free_list_capacity(1 << 16);
+        let counter = DropCounter::new();
+
+        // leave as much behind in the header as possible
+        let old = Gc::new(HeapNode::new(counter.token()));
+        old.edges
+            .borrow_mut()
+            .push(Gc::new(HeapNode::new(counter.token())));
+        drop(old.clone());
+        let root = Gc::into_root(old.clone());
+        drop(root);
+        let old_ptr = Gc::as_ptr(&old);
+        drop(old);
+        assert_eq!(counter.count(), 2);
+
+        let new = Gc::new(HeapNode::new(counter.token()));
+        assert_eq!(Gc::as_ptr(&new), old_ptr);
+        let header = unsafe { new.ptr.as_ref() };
+        assert_eq!(header.ref_count(), 1);
+        assert!(!header.is_rooted());
+        assert_eq!(header.dirty_index.get(), collect::NOT_DIRTY);
+        assert!(!header.is_dead());
+        assert!(new.edges.borrow().is_empty());
+
+        // the recycled allocation is collected like any other
+        new.edges.borrow_mut().push(new.clone());
+        drop(new);
+        collect();
+        assert_eq!(counter.count(), 3);
+
+        set_free_list_capacity(0);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+/// Test that recycling the memory of collected garbage never lets a live `Gc` see a new value, and
+/// that the heap size only counts live allocations.
+fn free_list_recycles_garbage() {
+    struct Node {
+        id: usize,
+        edges: RefCell<Vec<Gc<Node>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    std::thread::spawn(|| {
+        set_collect_condition(|_| false);
+        // only enough room for some of the garbage, so that blocks are both kept and given back
+        set_free_list_capacity(64 * size_of::<GcBox<Node>>());
+        fastrand::seed(0xf4ee);
+        let base_bytes = stats().live_bytes;
+
+        let mut next_id = 0;
+        let mut roots: Vec<Gc<Node>> = Vec::new();
+        let mut expected = HashMap::new();
+        for _ in 0..200 {
+            for _ in 0..fastrand::usize(1..100) {
+                let node = Gc::new(Node {
+                    id: next_id,
+                    edges: RefCell::new(Vec::new()),
+                });
+                expected.insert(next_id, Vec::new());
+                next_id += 1;
+                roots.push(node);
+            }
+            for _ in 0..fastrand::usize(..100) {
+                let from = &roots[fastrand::usize(..roots.len())];
+                let to = roots[fastrand::usize(..roots.len())].clone();
+                expected.get_mut(&from.id).unwrap().push(to.id);
+                from.edges.borrow_mut().push(to);
+            }
+            for _ in 0..fastrand::usize(..roots.len()) {
+                drop(roots.swap_remove(fastrand::usize(..roots.len())));
+            }
+            if fastrand::bool() {
+                collect();
+            }
+
+            // every reachable node must still hold exactly the edges it was given
+            let mut seen = HashSet::new();
+            let mut stack = roots.iter().map(Gc::as_ptr).collect::<Vec<_>>();
+            while let Some(node) = stack.pop() {
+                let node = unsafe { &*node };
+                if seen.insert(node.id) {
+                    let edges = node.edges.borrow();
+                    let ids = edges.iter().map(|e| e.id).collect::<Vec<_>>();
+                    assert_eq!(ids, expected[&node.id], "node {} was overwritten", node.id);
+                    stack.extend(edges.iter().map(Gc::as_ptr));
+                }
+            }
+        }
+
+        drop(roots);
+        collect();
+        assert_eq!(stats().live_bytes, base_bytes);
+        set_free_list_capacity(0);
+        set_collect_condition(default_collect_condition);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+#[cfg(feature = "allocator-api")]
+/// Test that every allocation made through a custom allocator is freed through it, whether it is
+/// dropped, collected, or cleared along with the heap of an exiting thread.
+fn new_in_frees_through_allocator() {
+    use crate::testing::{unsync::Node, CountingAllocator};
+
+    let alloc = CountingAllocator::new();
+    let counter = DropCounter::new();
+    std::thread::spawn({
+        let (alloc, counter) = (alloc.clone(), counter.clone());
+        move || {
+            // recycled blocks must never come from, or go back to, the custom allocator
+            set_free_list_capacity(1 << 16);
+
+            // acyclic, and so freed as soon as it is dropped
+            drop(Gc::new_in(Node::new(&counter), alloc.clone()));
+            assert_eq!(counter.count(), 1);
+            assert_eq!(alloc.bytes_freed(), alloc.bytes_allocated());
+
+            // a cycle mixing both allocators, freed by a collection
+            let a = Gc::new_in(Node::new(&counter), alloc.clone());
+            let b = Gc::new(Node::new(&counter));
+            let c = Gc::new_in(Node::new(&counter), alloc.clone());
+            Node::link(&a, &b);
+            Node::link(&b, &c);
+            Node::link(&c, &a);
+            drop((a, b, c));
+            collect();
+            assert_eq!(counter.count(), 4);
+            assert_eq!(alloc.bytes_freed(), alloc.bytes_allocated());
+
+            // a cycle which is still around when the thread exits
+            let d = Gc::new_in(Node::new(&counter), alloc.clone());
+            Node::link(&d, &d);
+            drop(d);
+            set_free_list_capacity(0);
+        }
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(counter.count(), 5);
+    assert!(alloc.bytes_allocated() > 0);
+    assert_eq!(alloc.bytes_freed(), alloc.bytes_allocated());
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+/// Test that the ratio used by the default condition grows after collections which free nothing,
+/// shrinks after collections which free everything they examine, and stays within its bounds.
+fn drop_ratio_adapts() {
+    let ratio = || DUMPSTER.with(|d| d.drop_ratio.get());
+    assert_eq!(ratio(), 1.0);
+
+    // an empty collection frees nothing
+    collect();
+    assert_eq!(ratio(), 2.0);
+    for _ in 0..16 {
+        collect();
+    }
+    assert_eq!(ratio(), 16.0);
+
+    let counter = DropCounter::new();
+    for i in 1..=8 {
+        graph!(unsync, counter; a, b; a -> b, b -> a);
+        drop((a, b));
+        collect();
+        assert_eq!(counter.count(), 2 * i);
+    }
+    assert_eq!(ratio(), 0.25);
+}
+
+#[test]
+/// Test that a fixed drop ratio collects once more than that many times as many `Gc`s have been
+/// dropped as exist.
+fn fixed_drop_ratio() {
+    set_collect_condition_with(CollectCondition::drop_ratio(4.0));
+    collect();
+    let collections = stats().collections;
+
+    let gc = Gc::new(0u8);
+    for _ in 0..4 {
+        drop(gc.clone());
+    }
+    assert_eq!(stats().collections, collections);
+    drop(gc.clone());
+    assert_eq!(stats().collections, collections + 1);
+
+    set_collect_condition(default_collect_condition);
+}
+
+#[test]
+/// Test which types are leaves, including types whose flags are computed by the derive.
+fn leaf_flags() {
+    #[cfg(feature = "derive")]
+    {
+        #[derive(Collectable)]
+        #[collectable(crate = "crate", leaf)]
+        #[allow(unused)]
+        struct Plain {
+            id: u64,
+            names: Vec<String>,
+        }
+
+        #[derive(Collectable)]
+        #[collectable(crate = "crate")]
+        #[allow(unused)]
+        struct Holder {
+            id: u64,
+            next: Option<Gc<Holder>>,
+        }
+
+        #[derive(Collectable)]
+        #[collectable(crate = "crate")]
+        #[allow(unused)]
+        struct List {
+            value: u8,
+            next: Option<Box<List>>,
+        }
+
+        #[derive(Collectable)]
+        #[collectable(crate = "crate")]
+        struct Opaque(#[allow(unused)] u8);
+
+        const {
+            assert!(Plain::IS_LEAF);
+            assert!(Vec::<Plain>::IS_LEAF);
+            assert!(!Holder::IS_LEAF);
+            assert!(!List::IS_LEAF);
+            assert!(!Opaque::IS_LEAF);
+        }
+    }
+
+    const {
+        assert!(<Vec<u8>>::IS_LEAF);
+        assert!(<HashMap<String, Vec<(u8, char)>>>::IS_LEAF);
+        assert!(<Option<Box<[u8; 4]>>>::IS_LEAF);
+        assert!(<RefCell<Vec<u8>>>::IS_LEAF);
+        assert!(<Rc<Vec<u8>>>::IS_LEAF);
+        assert!(!<Vec<Gc<u8>>>::IS_LEAF);
+        assert!(!<(u8, Gc<u8>)>::IS_LEAF);
+        assert!(!<RefCell<Vec<Option<Gc<u8>>>>>::IS_LEAF);
+    }
+}
+
+#[test]
+/// Test that containers of leaves do not visit their elements, while containers of anything else
+/// visit every one.
+fn leaf_elements_skipped() {
+    static VISITS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Clone)]
+    struct Counted<const LEAF: bool>;
+
+    unsafe impl<const LEAF: bool> Collectable for Counted<LEAF> {
+        fn accept<V: Visitor>(&self, _: &mut V) -> Result<(), ()> {
+            VISITS.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        const IS_LEAF: bool = LEAF;
+    }
+
+    // the `Gc` beside each vector keeps it from being a leaf itself, so it is still traced
+    let gc = Gc::new((vec![Counted::<true>; 1000], None::<Gc<()>>));
+    drop(gc.clone());
+    collect();
+    assert_eq!(VISITS.load(Ordering::Relaxed), 0);
+
+    let gc = Gc::new((vec![Counted::<false>; 1000], None::<Gc<()>>));
+    drop(gc.clone());
+    collect();
+    assert!(VISITS.load(Ordering::Relaxed) >= 1000);
+}
+
+#[test]
+/// Test that a cycle through a container of non-leaf elements is still found and collected.
+fn non_leaf_elements_traced() {
+    struct Node {
+        bytes: Vec<u8>,
+        children: RefCell<Vec<Gc<Node>>>,
+        _token: DropToken,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.bytes.accept(visitor)?;
+            self.children.accept(visitor)
+        }
+    }
+
+    let counter = DropCounter::new();
+    let a = Gc::new(Node {
+        bytes: vec![0; 1 << 16],
+        children: RefCell::new(Vec::new()),
+        _token: counter.token(),
+    });
+    let b = Gc::new(Node {
+        bytes: vec![0; 1 << 16],
+        children: RefCell::new(vec![a.clone()]),
+        _token: counter.token(),
+    });
+    a.children.borrow_mut().push(b.clone());
+    assert_eq!(a.bytes.len() + b.bytes.len(), 2 << 16);
+
+    drop((a, b));
+    collect();
+    assert_eq!(counter.count(), 2);
+}
+
+#[test]
+/// Test that a collection frees exactly the allocations which are unreachable from the remaining
+/// `Gc`s, so that keying the collector's tables by address loses or confuses none of them.
+fn collects_exactly_unreachable() {
+    thread_local! {
+        static DROPPED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+    }
+
+    struct Node {
+        id: usize,
+        edges: RefCell<Vec<Gc<Node>>>,
+    }
+
+    unsafe impl Collectable for Node {
+        fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+            self.edges.accept(visitor)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            DROPPED.with(|d| assert!(d.borrow_mut().insert(self.id), "dropped twice"));
+        }
+    }
+
+    std::thread::spawn(|| {
+        set_collect_condition(|_| false);
+        fastrand::seed(0x5eed);
+
+        let mut nodes = (0..2000)
+            .map(|id| {
+                Gc::new(Node {
+                    id,
+                    edges: RefCell::new(Vec::new()),
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut edges = vec![Vec::new(); nodes.len()];
+        for _ in 0..4000 {
+            let (from, to) = (
+                fastrand::usize(..nodes.len()),
+                fastrand::usize(..nodes.len()),
+            );
+            edges[from].push(to);
+            nodes[from].edges.borrow_mut().push(nodes[to].clone());
+        }
+        let mut roots = (0..nodes.len()).collect::<Vec<_>>();
+        fastrand::shuffle(&mut roots);
+        roots.truncate(50);
+        roots.sort_unstable();
+        let kept = roots
+            .iter()
+            .map(|&id| nodes[id].clone())
+            .collect::<Vec<_>>();
+        nodes.clear();
+
+        let mut reachable = HashSet::new();
+        let mut stack = roots;
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                stack.extend(&edges[id]);
+            }
+        }
+
+        collect();
+        DROPPED.with(|d| {
+            let dropped = d.borrow();
+            assert_eq!(dropped.len() + reachable.len(), edges.len());
+            assert!(dropped.iter().all(|id| !reachable.contains(id)));
+        });
+
+        drop(kept);
+        collect();
+        DROPPED.with(|d| assert_eq!(d.borrow().len(), edges.len()));
+        set_collect_condition(default_collect_condition);
+    })
+    .join()
+    .unwrap();
+}
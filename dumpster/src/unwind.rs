@@ -0,0 +1,174 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Handling of panics raised while a collection drops garbage.
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    process::abort,
+    ptr::drop_in_place,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// What a collection does when dropping an unreachable value panics.
+///
+/// The policy is set for the whole program with [`set_collection_panic_policy`], and applies to
+/// both [`sync`](crate::sync) and [`unsync`](crate::unsync) collections.
+/// It only covers values dropped by a collection: a value whose last `Gc` is dropped is dropped in
+/// place, and a panic there unwinds as usual.
+pub enum PanicPolicy {
+    #[default]
+    /// Free the allocation whose value panicked, finish the collection, and then resume the first
+    /// panic from the thread which ran the collection.
+    ///
+    /// A value's fields are still dropped while its `Drop` unwinds, so every unreachable
+    /// allocation in the collection is freed.
+    Propagate,
+    /// Leak the allocation whose value panicked, finish the collection, and then resume the first
+    /// panic from the thread which ran the collection.
+    ///
+    /// Every other unreachable allocation in the collection is still freed.
+    /// This suits values whose `Drop` may leave something pointing into their memory when it
+    /// panics partway through.
+    LeakAllocation,
+    /// Abort the process.
+    Abort,
+}
+
+/// The current [`PanicPolicy`], as its discriminant.
+static POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Propagate as u8);
+
+#[cfg(feature = "epoch")]
+/// The first panic caught while destroying retired garbage which has not yet been resumed.
+static RETIRED_PANIC: parking_lot::Mutex<Option<Box<dyn Any + Send>>> =
+    parking_lot::Mutex::new(None);
+
+thread_local! {
+    /// The first panic caught on this thread under [`PanicPolicy::LeakAllocation`] which has not
+    /// yet been resumed.
+    static CAUGHT: RefCell<Option<Box<dyn Any + Send>>> = const { RefCell::new(None) };
+}
+
+/// Set what collections do when dropping an unreachable value panics.
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{collection_panic_policy, set_collection_panic_policy, PanicPolicy};
+///
+/// set_collection_panic_policy(PanicPolicy::LeakAllocation);
+/// assert_eq!(collection_panic_policy(), PanicPolicy::LeakAllocation);
+/// # set_collection_panic_policy(PanicPolicy::Propagate);
+/// ```
+pub fn set_collection_panic_policy(policy: PanicPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+#[must_use]
+/// Get the policy set by [`set_collection_panic_policy`].
+///
+/// # Examples
+///
+/// ```
+/// use dumpster::{collection_panic_policy, PanicPolicy};
+///
+/// assert_eq!(collection_panic_policy(), PanicPolicy::Propagate);
+/// ```
+pub fn collection_panic_policy() -> PanicPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        1 => PanicPolicy::LeakAllocation,
+        2 => PanicPolicy::Abort,
+        _ => PanicPolicy::Propagate,
+    }
+}
+
+/// Drop the value behind `ptr` on behalf of a collection, following the panic policy.
+///
+/// A panic is kept until [`resume_caught`] is called, so that the collection can finish first.
+/// Returns `false` if dropping the value panicked and the policy is
+/// [`PanicPolicy::LeakAllocation`], in which case the allocation holding the value must be leaked.
+///
+/// # Safety
+///
+/// The same as for [`drop_in_place`].
+pub(crate) unsafe fn drop_collected<T: ?Sized>(ptr: *mut T) -> bool {
+    let Err(payload) = catch_unwind(AssertUnwindSafe(|| drop_in_place(ptr))) else {
+        return true;
+    };
+    let policy = collection_panic_policy();
+    if policy == PanicPolicy::Abort {
+        abort();
+    }
+    // while the thread is exiting, the panic has nowhere to be kept, and so is discarded
+    let _ = CAUGHT.try_with(|c| {
+        c.borrow_mut().get_or_insert(payload);
+    });
+    policy == PanicPolicy::Propagate
+}
+
+/// Resume the panic caught by [`drop_collected`] on this thread, if there is one.
+pub(crate) fn resume_caught() {
+    if let Some(payload) = take_caught() {
+        resume_unwind(payload);
+    }
+}
+
+/// Take the panic caught by [`drop_collected`] on this thread, if there is one, without resuming
+/// it.
+pub(crate) fn take_caught() -> Option<Box<dyn Any + Send>> {
+    CAUGHT.try_with(RefCell::take).ok().flatten()
+}
+
+#[cfg(feature = "epoch")]
+/// Drop the value behind `ptr`, which a collection retired, on whichever thread destroys it.
+///
+/// That thread may be doing something else entirely, so a panic is never unwound out of it.
+/// Unless the policy is [`PanicPolicy::Abort`], the first such panic is kept until
+/// [`resume_retired`] is called, and this returns `false` if the allocation must be leaked, as in
+/// [`drop_collected`].
+///
+/// # Safety
+///
+/// The same as for [`drop_in_place`].
+pub(crate) unsafe fn drop_retired<T: ?Sized>(ptr: *mut T) -> bool {
+    let Err(payload) = catch_unwind(AssertUnwindSafe(|| drop_in_place(ptr))) else {
+        return true;
+    };
+    let policy = collection_panic_policy();
+    if policy == PanicPolicy::Abort {
+        abort();
+    }
+    RETIRED_PANIC.lock().get_or_insert(payload);
+    policy == PanicPolicy::Propagate
+}
+
+#[cfg(feature = "epoch")]
+/// Resume the panic caught by [`drop_retired`] on any thread, if there is one.
+pub(crate) fn resume_retired() {
+    let payload = RETIRED_PANIC.lock().take();
+    if let Some(payload) = payload {
+        resume_unwind(payload);
+    }
+}
+
+#[cfg(test)]
+/// A lock held by tests which change the panic policy, since it is shared by the whole process.
+pub(crate) static POLICY_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
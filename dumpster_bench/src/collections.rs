@@ -0,0 +1,126 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Counting of the collections which dumpster runs and the time spent in them, so that the
+//! duration of a benchmark can be split between the collector and the mutator.
+//!
+//! The counts come from dumpster's collection hooks, which the other libraries have no equivalent
+//! of.
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+/// The number of collections which have finished on any watched thread.
+static N_COLLECTIONS: AtomicUsize = AtomicUsize::new(0);
+/// The total time spent in those collections, in nanoseconds.
+static NANOS_COLLECTING: AtomicU64 = AtomicU64::new(0);
+
+/// Count one collection which took `duration`.
+fn count(duration: Duration) {
+    N_COLLECTIONS.fetch_add(1, Ordering::Relaxed);
+    let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+    NANOS_COLLECTING.fetch_add(nanos, Ordering::Relaxed);
+}
+
+/// Count every collection of a `sync::Gc`, and every collection of an `unsync::Gc` on this thread.
+///
+/// This replaces any collection hooks which were set before.
+pub fn watch() {
+    dumpster::sync::set_collect_hooks(
+        dumpster::sync::CollectHooks::new().on_end(|result| count(result.duration)),
+    );
+    watch_unsync();
+}
+
+/// Count every collection of an `unsync::Gc` on this thread, since the hooks of unsync collections
+/// belong to a single thread.
+pub fn watch_unsync() {
+    dumpster::unsync::set_collect_hooks(
+        dumpster::unsync::CollectHooks::new().on_end(|result| count(result.duration)),
+    );
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+/// How a benchmark's duration was split between collecting and everything else.
+pub struct Split {
+    /// The number of collections which finished.
+    pub n_collections: usize,
+    /// The time spent in those collections, in microseconds.
+    pub collect_us: u64,
+    /// The rest of the benchmark's duration, in microseconds.
+    pub mutator_us: u64,
+}
+
+/// A point from which collections are counted.
+pub struct Tally {
+    /// The number of collections when counting started.
+    n_collections: usize,
+    /// The time spent collecting when counting started, in nanoseconds.
+    nanos: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// The collections counted by a [`Tally`].
+pub struct Counted {
+    /// The number of collections which finished.
+    n_collections: usize,
+    /// The time spent in those collections, in nanoseconds.
+    nanos: u64,
+}
+
+impl Tally {
+    /// Start counting from now.
+    pub fn start() -> Tally {
+        Tally {
+            n_collections: N_COLLECTIONS.load(Ordering::Relaxed),
+            nanos: NANOS_COLLECTING.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get the collections counted since counting started, and start counting over from now.
+    pub fn restart(&mut self) -> Counted {
+        let now = Tally::start();
+        let counted = Counted {
+            n_collections: now.n_collections - self.n_collections,
+            nanos: now.nanos - self.nanos,
+        };
+        *self = now;
+        counted
+    }
+}
+
+impl Counted {
+    /// Split `duration` between these collections and everything else.
+    ///
+    /// Collections on several threads at once may add up to more than `duration`, in which case
+    /// the mutator is counted as taking no time at all.
+    pub fn split(self, duration: Duration) -> Split {
+        let collect_us = self.nanos / 1000;
+        Split {
+            n_collections: self.n_collections,
+            collect_us,
+            mutator_us: u64::try_from(duration.as_micros())
+                .unwrap_or(u64::MAX)
+                .saturating_sub(collect_us),
+        }
+    }
+}
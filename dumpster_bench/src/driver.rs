@@ -0,0 +1,235 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Driving the scenarios on collectors whose pointers cannot be held on their own, the way a
+//! [`Multiref`] can.
+//!
+//! `gc-arena` only hands out pointers inside a callback which borrows its arena, and `broom` needs
+//! its heap to follow any pointer.
+//! A [`Driver`] instead owns a heap of nodes along with a list of roots into it, and is told what
+//! to do in terms of positions in that list, so each collector can carry out the same workload in
+//! its own idiom.
+
+use broom::prelude::{Handle, Heap, Rooted, Trace, Tracer};
+use gc_arena::{lock::RefLock, Arena, Collect, Gc, Rootable};
+
+use crate::Multiref;
+
+/// A heap of nodes which each point to any number of other nodes, along with a list of the nodes
+/// which are rooted.
+pub trait Driver {
+    /// Make an empty heap, with no roots.
+    fn new() -> Self;
+    /// Get the number of roots.
+    fn n_roots(&self) -> usize;
+    /// Make a new node which points to the nodes at the roots `points_to`, and root it after every
+    /// other root.
+    fn alloc(&mut self, points_to: &[usize]);
+    /// Make the node at the root `from` also point to the nodes at the roots `to`.
+    fn link(&mut self, from: usize, to: &[usize]);
+    /// Make the `i`th reference of the node at the root `from` point to the node at the root `to`
+    /// instead.
+    fn relink(&mut self, from: usize, i: usize, to: usize);
+    /// Remove the root at `index`, moving the last root into its place.
+    fn unroot(&mut self, index: usize);
+    /// Remove every root.
+    fn clear(&mut self);
+    /// Free every node, once there are no roots left.
+    fn free_all(&mut self);
+}
+
+/// Any multiref can be driven by holding its roots in a `Vec`.
+impl<M: Multiref> Driver for Vec<M> {
+    fn new() -> Self {
+        Vec::new()
+    }
+
+    fn n_roots(&self) -> usize {
+        self.len()
+    }
+
+    fn alloc(&mut self, points_to: &[usize]) {
+        let node = M::new(points_to.iter().map(|&i| self[i].clone()).collect());
+        self.push(node);
+    }
+
+    fn link(&mut self, from: usize, to: &[usize]) {
+        self[from].apply(|v| v.extend(to.iter().map(|&i| self[i].clone())));
+    }
+
+    fn relink(&mut self, from: usize, i: usize, to: usize) {
+        let to = self[to].clone();
+        self[from].apply(|v| v[i] = to);
+    }
+
+    fn unroot(&mut self, index: usize) {
+        self.swap_remove(index);
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+
+    fn free_all(&mut self) {
+        M::free_all();
+    }
+}
+
+#[derive(Clone, Copy, Collect)]
+#[collect(no_drop)]
+/// A node in a `gc-arena` arena.
+pub struct GcArenaRef<'gc>(Gc<'gc, RefLock<Vec<GcArenaRef<'gc>>>>);
+
+#[derive(Collect)]
+#[collect(no_drop)]
+/// The root of a `gc-arena` arena, which holds every rooted node.
+pub struct GcArenaRoots<'gc> {
+    roots: Vec<GcArenaRef<'gc>>,
+}
+
+/// A driver for `gc-arena`.
+///
+/// `gc-arena` is incremental, and only collects when asked to pay off the debt its allocations
+/// have run up, which a program would do between batches of work.
+/// Here every operation is its own batch, paying off the debt after each one, so this is
+/// necessarily a pessimistic comparison: a real program would make many changes in one call to
+/// [`Arena::mutate`].
+pub struct GcArenaDriver {
+    arena: Arena<Rootable![GcArenaRoots<'_>]>,
+}
+
+impl Driver for GcArenaDriver {
+    fn new() -> Self {
+        GcArenaDriver {
+            arena: Arena::new(|_| GcArenaRoots { roots: Vec::new() }),
+        }
+    }
+
+    fn n_roots(&self) -> usize {
+        self.arena.mutate(|_, root| root.roots.len())
+    }
+
+    fn alloc(&mut self, points_to: &[usize]) {
+        self.arena.mutate_root(|mc, root| {
+            let refs = points_to.iter().map(|&i| root.roots[i]).collect();
+            root.roots.push(GcArenaRef(Gc::new(mc, RefLock::new(refs))));
+        });
+        self.arena.collect_debt();
+    }
+
+    fn link(&mut self, from: usize, to: &[usize]) {
+        self.arena.mutate(|mc, root| {
+            let mut refs = root.roots[from].0.borrow_mut(mc);
+            refs.extend(to.iter().map(|&i| root.roots[i]));
+        });
+        self.arena.collect_debt();
+    }
+
+    fn relink(&mut self, from: usize, i: usize, to: usize) {
+        self.arena.mutate(|mc, root| {
+            root.roots[from].0.borrow_mut(mc)[i] = root.roots[to];
+        });
+        self.arena.collect_debt();
+    }
+
+    fn unroot(&mut self, index: usize) {
+        self.arena.mutate_root(|_, root| {
+            root.roots.swap_remove(index);
+        });
+        self.arena.collect_debt();
+    }
+
+    fn clear(&mut self) {
+        self.arena.mutate_root(|_, root| root.roots.clear());
+        self.arena.collect_debt();
+    }
+
+    fn free_all(&mut self) {
+        self.arena.collect_all();
+    }
+}
+
+/// A node in a `broom` heap.
+pub struct BroomNode {
+    refs: Vec<Handle<BroomNode>>,
+}
+
+impl Trace<Self> for BroomNode {
+    fn trace(&self, tracer: &mut Tracer<Self>) {
+        self.refs.trace(tracer);
+    }
+}
+
+/// A driver for `broom`.
+///
+/// `broom` never collects on its own: a program calls [`Heap::clean`] whenever it sees fit.
+/// This cleans whenever the heap has doubled in size since it was last cleaned, a common policy
+/// for collectors which do decide for themselves, but the comparison depends on that choice.
+pub struct BroomDriver {
+    heap: Heap<BroomNode>,
+    roots: Vec<Rooted<BroomNode>>,
+    /// The number of nodes in the heap after it was last cleaned.
+    n_after_clean: usize,
+}
+
+impl Driver for BroomDriver {
+    fn new() -> Self {
+        BroomDriver {
+            heap: Heap::default(),
+            roots: Vec::new(),
+            n_after_clean: 0,
+        }
+    }
+
+    fn n_roots(&self) -> usize {
+        self.roots.len()
+    }
+
+    fn alloc(&mut self, points_to: &[usize]) {
+        let refs = points_to.iter().map(|&i| self.roots[i].handle()).collect();
+        self.roots.push(self.heap.insert(BroomNode { refs }));
+        if self.heap.len() >= 2 * self.n_after_clean.max(1024) {
+            self.heap.clean();
+            self.n_after_clean = self.heap.len();
+        }
+    }
+
+    fn link(&mut self, from: usize, to: &[usize]) {
+        let to = to.iter().map(|&i| self.roots[i].handle());
+        let node = self.heap.get_mut(self.roots[from].handle()).unwrap();
+        node.refs.extend(to);
+    }
+
+    fn relink(&mut self, from: usize, i: usize, to: usize) {
+        let to = self.roots[to].handle();
+        self.heap.get_mut(self.roots[from].handle()).unwrap().refs[i] = to;
+    }
+
+    fn unroot(&mut self, index: usize) {
+        self.roots.swap_remove(index);
+    }
+
+    fn clear(&mut self) {
+        self.roots.clear();
+    }
+
+    fn free_all(&mut self) {
+        self.heap.clean();
+        self.n_after_clean = self.heap.len();
+    }
+}
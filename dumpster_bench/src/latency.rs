@@ -0,0 +1,187 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Histograms of the latencies of individual operations, for finding out how long an operation
+//! stalls when a collection happens to run inside it.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// The number of bits below the highest set bit of a latency which pick its bucket, so that each
+/// power of two is split into 2<sup>`SUB_BITS`</sup> buckets.
+const SUB_BITS: u32 = 4;
+
+/// The number of buckets each power of two is split into.
+const N_SUB: usize = 1 << SUB_BITS;
+
+/// The number of buckets needed to hold any latency which fits in a `u64` of nanoseconds.
+const N_BUCKETS: usize = (u64::BITS - SUB_BITS + 1) as usize * N_SUB;
+
+/// Get the index of the bucket holding a latency of `ns` nanoseconds.
+fn bucket(ns: u64) -> usize {
+    if ns < N_SUB as u64 {
+        return ns as usize;
+    }
+    let shift = u64::BITS - 1 - ns.leading_zeros() - SUB_BITS;
+    (shift as usize + 1) * N_SUB + (ns >> shift) as usize - N_SUB
+}
+
+/// Get the greatest latency, in nanoseconds, which falls in the bucket at `index`.
+fn bucket_high(index: usize) -> u64 {
+    if index < N_SUB {
+        return index as u64;
+    }
+    let shift = index / N_SUB - 1;
+    let low = ((index % N_SUB + N_SUB) as u64) << shift;
+    low + ((1 << shift) - 1)
+}
+
+/// A histogram of latencies, bucketed logarithmically like an HDR histogram.
+///
+/// Latencies under 16 nanoseconds have a bucket each, and each power of two above that is split
+/// into 16 buckets, so a reported percentile is never more than 1/16 above the true one.
+pub struct Histogram {
+    /// The number of latencies in each bucket.
+    counts: Vec<u64>,
+    /// The number of latencies recorded.
+    total: u64,
+    /// The greatest latency recorded, in nanoseconds.
+    max: u64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+/// A summary of the tail of a [`Histogram`], in nanoseconds.
+pub struct Latency {
+    /// The median latency.
+    pub p50_ns: u64,
+    /// The 99th-percentile latency.
+    pub p99_ns: u64,
+    /// The 99.9th-percentile latency.
+    pub p999_ns: u64,
+    /// The greatest latency.
+    pub max_ns: u64,
+}
+
+impl Histogram {
+    /// Construct an empty histogram.
+    pub fn new() -> Histogram {
+        Histogram {
+            counts: vec![0; N_BUCKETS],
+            total: 0,
+            max: 0,
+        }
+    }
+
+    /// Record one latency.
+    pub fn record(&mut self, latency: Duration) {
+        let ns = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[bucket(ns)] += 1;
+        self.total += 1;
+        self.max = self.max.max(ns);
+    }
+
+    /// Add every latency recorded in `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+
+    /// Get the latency, in nanoseconds, below which a fraction `q` of the recorded latencies fall,
+    /// rounded up to the top of its bucket.
+    ///
+    /// Returns 0 if nothing has been recorded.
+    pub fn percentile(&self, q: f64) -> u64 {
+        let rank = ((q * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_high(index).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Summarize the tail of the recorded latencies.
+    pub fn summary(&self) -> Latency {
+        Latency {
+            p50_ns: self.percentile(0.5),
+            p99_ns: self.percentile(0.99),
+            p999_ns: self.percentile(0.999),
+            max_ns: self.max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that every latency falls in a bucket whose top is no more than 1/16 above it, and that
+    /// buckets are in order.
+    fn buckets() {
+        let mut last = 0;
+        for ns in (0..100_000).chain([u64::MAX / 3, u64::MAX - 1, u64::MAX]) {
+            let index = bucket(ns);
+            assert!(index < N_BUCKETS);
+            assert!(index >= last, "{ns} went into an earlier bucket");
+            last = index;
+            let high = bucket_high(index);
+            assert!(
+                high >= ns && high - ns <= ns / N_SUB as u64,
+                "{ns} is in ..={high}"
+            );
+            assert_eq!(bucket(high), index);
+        }
+    }
+
+    #[test]
+    /// Test that the percentiles of evenly spread latencies are within a bucket of the true ones,
+    /// and that merging histograms gives the same percentiles as recording into one.
+    fn percentiles() {
+        let mut whole = Histogram::new();
+        let mut halves = [Histogram::new(), Histogram::new()];
+        for ns in 1..=10_000 {
+            whole.record(Duration::from_nanos(ns));
+            halves[ns as usize % 2].record(Duration::from_nanos(ns));
+        }
+        let [mut merged, other] = halves;
+        merged.merge(&other);
+
+        for histogram in [&whole, &merged] {
+            let summary = histogram.summary();
+            for (got, want) in [
+                (summary.p50_ns, 5_000),
+                (summary.p99_ns, 9_900),
+                (summary.p999_ns, 9_990),
+            ] {
+                assert!(
+                    got >= want && got - want <= want / N_SUB as u64,
+                    "{got} vs {want}"
+                );
+            }
+            assert_eq!(summary.max_ns, 10_000);
+        }
+        assert_eq!(Histogram::new().summary().max_ns, 0);
+    }
+}
@@ -16,9 +16,17 @@
    along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+pub mod driver;
+
 use std::{
-    rc::Rc,
-    sync::{Arc, Mutex},
+    cell::RefCell,
+    marker::PhantomData,
+    rc::{self, Rc},
+    sync::{
+        self,
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 
 /// A garbage-collected structure which points to an arbitrary number of other garbage-collected
@@ -32,6 +40,14 @@ pub trait Multiref: Clone {
     fn apply(&self, f: impl FnOnce(&mut Vec<Self>));
     /// Collect all the floating GCs out there.
     fn collect();
+    /// Free everything allocated through this type of multiref, once no multiref is left to any of
+    /// it.
+    ///
+    /// By default, this collects, which frees everything for any collector.
+    /// An arena frees nothing before this is called.
+    fn free_all() {
+        Self::collect();
+    }
 }
 
 /// A trait for thread-safe synchronized multirefs.
@@ -51,6 +67,57 @@ pub struct ArcMultiref {
     refs: Mutex<Vec<Arc<Self>>>,
 }
 
+/// The next unused [`RcWeakMultiref::id`] or [`ArcWeakMultiref::id`].
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A multi-reference managed by hand with `Rc` and `RefCell`, which avoids leaking cycles the way a
+/// careful programmer would: a node points strongly only to nodes made before it, and weakly to
+/// every other node, so that its strong references never form a cycle.
+///
+/// Which references to make weak usually follows from the shape of the data, as with the parent
+/// pointers of a tree; the order in which nodes were made stands in for that in the benchmarks.
+pub struct RcWeakMultiref {
+    /// The order in which this node was made among all nodes.
+    id: u64,
+    refs: RefCell<Vec<RcWeakRef>>,
+}
+
+#[derive(Clone)]
+/// A reference to an [`RcWeakMultiref`].
+pub enum RcWeakRef {
+    Strong(Rc<RcWeakMultiref>),
+    Weak(rc::Weak<RcWeakMultiref>),
+}
+
+/// The thread-safe counterpart to [`RcWeakMultiref`], managed by hand with `Arc` and `Mutex`.
+pub struct ArcWeakMultiref {
+    /// The order in which this node was made among all nodes.
+    id: u64,
+    refs: Mutex<Vec<ArcWeakRef>>,
+}
+
+#[derive(Clone)]
+/// A reference to an [`ArcWeakMultiref`].
+pub enum ArcWeakRef {
+    Strong(Arc<ArcWeakMultiref>),
+    Weak(sync::Weak<ArcWeakMultiref>),
+}
+
+thread_local! {
+    /// The references held by every node in this thread's arena, indexed by [`ArenaRef::index`].
+    static ARENA: RefCell<Vec<Vec<ArenaRef>>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Copy)]
+/// A reference to a node in this thread's arena, in the style of a typed arena: making a node only
+/// pushes it onto the arena, dropping a reference does nothing, and every node is freed at once by
+/// [`Multiref::free_all`].
+pub struct ArenaRef {
+    index: usize,
+    /// Keeps each reference on the thread whose arena it points into.
+    _not_send: PhantomData<*const ()>,
+}
+
 #[derive(dumpster::Collectable, Debug)]
 pub struct DumpsterSyncMultiref {
     refs: Mutex<Vec<dumpster::sync::Gc<Self>>>,
@@ -137,7 +204,7 @@ impl Multiref for dumpster::unsync::Gc<DumpsterUnsyncMultiref> {
     }
 
     fn collect() {
-        dumpster::unsync::collect()
+        dumpster::unsync::collect();
     }
 }
 
@@ -233,3 +300,78 @@ impl Multiref for Arc<ArcMultiref> {
 
     fn collect() {}
 }
+
+impl Multiref for RcWeakRef {
+    fn new(points_to: Vec<Self>) -> Self {
+        RcWeakRef::Strong(Rc::new(RcWeakMultiref {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            refs: RefCell::new(points_to),
+        }))
+    }
+
+    fn apply(&self, f: impl FnOnce(&mut Vec<Self>)) {
+        let RcWeakRef::Strong(node) = self else {
+            panic!("only roots are ever changed, and roots are strong");
+        };
+        let mut refs = node.refs.borrow_mut();
+        f(&mut refs);
+        for r in refs.iter_mut() {
+            if let RcWeakRef::Strong(to) = r {
+                if to.id >= node.id {
+                    *r = RcWeakRef::Weak(Rc::downgrade(to));
+                }
+            }
+        }
+    }
+
+    fn collect() {}
+}
+
+impl Multiref for ArcWeakRef {
+    fn new(points_to: Vec<Self>) -> Self {
+        ArcWeakRef::Strong(Arc::new(ArcWeakMultiref {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            refs: Mutex::new(points_to),
+        }))
+    }
+
+    fn apply(&self, f: impl FnOnce(&mut Vec<Self>)) {
+        let ArcWeakRef::Strong(node) = self else {
+            panic!("only roots are ever changed, and roots are strong");
+        };
+        let mut refs = node.refs.lock().unwrap();
+        f(&mut refs);
+        for r in refs.iter_mut() {
+            if let ArcWeakRef::Strong(to) = r {
+                if to.id >= node.id {
+                    *r = ArcWeakRef::Weak(Arc::downgrade(to));
+                }
+            }
+        }
+    }
+
+    fn collect() {}
+}
+
+impl Multiref for ArenaRef {
+    fn new(points_to: Vec<Self>) -> Self {
+        ARENA.with(|arena| {
+            let mut arena = arena.borrow_mut();
+            arena.push(points_to);
+            ArenaRef {
+                index: arena.len() - 1,
+                _not_send: PhantomData,
+            }
+        })
+    }
+
+    fn apply(&self, f: impl FnOnce(&mut Vec<Self>)) {
+        ARENA.with(|arena| f(&mut arena.borrow_mut()[self.index]));
+    }
+
+    fn collect() {}
+
+    fn free_all() {
+        ARENA.with(|arena| drop(arena.take()));
+    }
+}
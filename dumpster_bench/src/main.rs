@@ -18,42 +18,38 @@
 
 //! Benchmarks for the `dumpster` garbage collection library.
 
+mod collections;
+mod latency;
+mod memory;
+mod output;
+
 use std::{
-    fmt::Display,
+    any::Any,
+    collections::HashMap,
     rc::Rc,
-    sync::Arc,
-    thread::{self, available_parallelism, scope},
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    thread::{self, scope},
+    time::Instant,
 };
 
 use dumpster_bench::{
-    ArcMultiref, BaconRajanMultiref, DumpsterSyncMultiref, DumpsterUnsyncMultiref, GcMultiref,
-    Multiref, RcMultiref, ShredderMultiref, ShredderSyncMultiref, SyncMultiref,
+    driver::{BroomDriver, Driver, GcArenaDriver},
+    ArcMultiref, ArcWeakRef, ArenaRef, BaconRajanMultiref, DumpsterSyncMultiref,
+    DumpsterUnsyncMultiref, GcMultiref, Multiref, RcMultiref, RcWeakRef, ShredderMultiref,
+    ShredderSyncMultiref, SyncMultiref,
 };
 
 use parking_lot::Mutex;
 
-struct BenchmarkData {
-    name: &'static str,
-    test: &'static str,
-    n_threads: usize,
-    n_ops: usize,
-    duration: Duration,
-}
+use latency::Histogram;
+use output::{Args, BenchmarkData, Output, USAGE};
 
-impl Display for BenchmarkData {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{},{},{},{},{}",
-            self.name,
-            self.test,
-            self.n_threads,
-            self.n_ops,
-            self.duration.as_micros()
-        )
-    }
-}
+#[global_allocator]
+/// Counts the memory allocated by every benchmark, whichever library it uses.
+static ALLOCATOR: memory::Counting = memory::Counting;
 
 fn unsync_never_collect(_: &dumpster::unsync::CollectInfo) -> bool {
     false
@@ -63,104 +59,488 @@ fn sync_never_collect(_: &dumpster::sync::CollectInfo) -> bool {
     false
 }
 
-fn main() {
-    const N_ITERS: usize = 1_000_000;
-    for _ in 0..100 {
-        dumpster::unsync::set_collect_condition(dumpster::unsync::default_collect_condition);
-        println!(
-            "{}",
-            single_threaded::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
+/// The seed of every random workload, as set by `--seed`.
+static SEED: AtomicU64 = AtomicU64::new(12345);
+
+/// Get the seed of every random workload.
+fn seed() -> u64 {
+    SEED.load(Ordering::Relaxed)
+}
+
+/// A library which can be benchmarked, which `--only` and `--skip` pick out by its name.
+struct Backend {
+    /// The name of this backend on the command line.
+    name: &'static str,
+    /// Run this backend only once every this many runs, for libraries which are slow to benchmark.
+    every: usize,
+    /// Whether this backend is dumpster, whose collections can be counted.
+    dumpster: bool,
+    /// Run every benchmark of this backend once.
+    run: fn(&mut Output, &Args),
+}
+
+/// The depths of the channels which migrating cycles are sent over, and the names of their tests.
+const MIGRATION_DEPTHS: [(&str, usize); 3] = [
+    ("migration (depth 1)", 1),
+    ("migration (depth 64)", 64),
+    ("migration (depth 4096)", 4096),
+];
+
+/// Every backend which can be benchmarked.
+static BACKENDS: [Backend; 10] = [
+    Backend {
+        name: "dumpster-unsync",
+        every: 1,
+        dumpster: true,
+        run: |out, args| {
+            dumpster::unsync::set_collect_condition(dumpster::unsync::default_collect_condition);
+            out.record(&single_threaded::<
+                dumpster::unsync::Gc<DumpsterUnsyncMultiref>,
+            >("dumpster (unsync)", args.iters));
+            dumpster::unsync::set_collect_condition(unsync_never_collect);
+            out.record(&single_threaded::<
+                dumpster::unsync::Gc<DumpsterUnsyncMultiref>,
+            >("dumpster (unsync/manual)", args.iters));
+            dumpster::unsync::set_collect_condition(dumpster::unsync::default_collect_condition);
+
+            {
+                // each run happens on a fresh thread, so the dumpster starts out empty
+                const N_BURSTS: usize = 100;
+                const BURST_SIZE: usize = 10_000;
+                out.record(&bursty::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
+                    "dumpster (unsync)",
+                    args.scaled(N_BURSTS),
+                    args.scaled(BURST_SIZE),
+                    |_| {},
+                ));
+                out.record(&bursty::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
+                    "dumpster (unsync/reserved)",
+                    args.scaled(N_BURSTS),
+                    args.scaled(BURST_SIZE),
+                    dumpster::unsync::reserve,
+                ));
+            }
+
+            {
+                const N_COLLECTIONS: usize = 100;
+                const HEAP_SIZE: usize = 100_000;
+                out.record(&rooted::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
+                    "dumpster (unsync)",
+                    args.scaled(N_COLLECTIONS),
+                    args.scaled(HEAP_SIZE),
+                    |gc| Box::new(gc),
+                ));
+                out.record(&rooted::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
+                    "dumpster (unsync/rooted)",
+                    args.scaled(N_COLLECTIONS),
+                    args.scaled(HEAP_SIZE),
+                    |gc| Box::new(dumpster::unsync::Gc::into_root(gc)),
+                ));
+                out.record(&pauses::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
+                    "dumpster (unsync)",
+                    args.scaled(N_COLLECTIONS),
+                    args.scaled(HEAP_SIZE),
+                ));
+            }
+
+            {
+                const N_ROUNDS: usize = 100;
+                const LIVE_SIZE: usize = 100_000;
+                const CHURN_SIZE: usize = 1_000;
+                out.record(&partial(
+                    "dumpster (unsync)",
+                    args.scaled(N_ROUNDS),
+                    args.scaled(LIVE_SIZE),
+                    args.scaled(CHURN_SIZE),
+                    1,
+                ));
+                out.record(&partial(
+                    "dumpster (unsync/partial)",
+                    args.scaled(N_ROUNDS),
+                    args.scaled(LIVE_SIZE),
+                    args.scaled(CHURN_SIZE),
+                    8,
+                ));
+            }
+
+            {
+                const N_OPS: usize = 1_000_000;
+                const LIVE_SIZE: usize = 1_000;
+                out.record(&recycle(
+                    "dumpster (unsync)",
+                    args.scaled(N_OPS),
+                    args.scaled(LIVE_SIZE),
+                    0,
+                ));
+                out.record(&recycle(
+                    "dumpster (unsync/free-list)",
+                    args.scaled(N_OPS),
+                    args.scaled(LIVE_SIZE),
+                    1 << 20,
+                ));
+            }
+
+            {
+                const N_COLLECTIONS: usize = 1_000;
+                for payload_len in [1_000, 100_000, 1_000_000] {
+                    out.record(&leaf_payload(
+                        "dumpster (unsync)",
+                        args.scaled(N_COLLECTIONS),
+                        args.scaled(payload_len),
+                    ));
+                }
+            }
+
+            scenarios::<Vec<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>>(
+                out,
+                args,
                 "dumpster (unsync)",
-                N_ITERS,
-            )
-        );
-        dumpster::unsync::set_collect_condition(unsync_never_collect);
-        println!(
-            "{}",
-            single_threaded::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
-                "dumpster (unsync/manual)",
-                N_ITERS,
-            )
-        );
-        dumpster::sync::set_collect_condition(dumpster::sync::default_collect_condition);
-        println!(
-            "{}",
-            single_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>("dumpster (sync)", N_ITERS)
-        );
-        dumpster::sync::set_collect_condition(sync_never_collect);
-        println!(
-            "{}",
-            single_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
-                "dumpster (sync/manual)",
-                N_ITERS
-            )
-        );
-        println!("{}", single_threaded::<gc::Gc<GcMultiref>>("gc", N_ITERS));
-        println!(
-            "{}",
-            single_threaded::<bacon_rajan_cc::Cc<BaconRajanMultiref>>("bacon-rajan-cc", N_ITERS)
-        );
-        for n_threads in 1..=available_parallelism().unwrap().get() {
-            // println!("--- {n_threads} threads");
+            );
+        },
+    },
+    Backend {
+        name: "dumpster-sync",
+        every: 1,
+        dumpster: true,
+        run: |out, args| {
             dumpster::sync::set_collect_condition(dumpster::sync::default_collect_condition);
-            println!(
-                "{}",
-                multi_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+            out.record(
+                &single_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
                     "dumpster (sync)",
-                    N_ITERS,
-                    n_threads,
-                )
+                    args.iters,
+                ),
             );
-
             dumpster::sync::set_collect_condition(sync_never_collect);
-            println!(
-                "{}",
-                multi_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+            out.record(
+                &single_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
                     "dumpster (sync/manual)",
-                    N_ITERS,
-                    n_threads,
-                )
+                    args.iters,
+                ),
             );
-        }
-    }
+            for &n_threads in &args.threads {
+                dumpster::sync::set_collect_condition(dumpster::sync::default_collect_condition);
+                out.record(&multi_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync)",
+                    args.iters,
+                    n_threads,
+                ));
 
-    for _ in 0..20 {
-        // run fewer tests of shredder because it takes forever
+                dumpster::sync::set_collect_condition(sync_never_collect);
+                out.record(&multi_threaded::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync/manual)",
+                    args.iters,
+                    n_threads,
+                ));
+            }
+            dumpster::sync::set_collect_condition(dumpster::sync::default_collect_condition);
 
-        println!(
-            "{}",
-            single_threaded::<shredder::Gc<ShredderMultiref>>("shredder", N_ITERS)
-        );
+            {
+                const N_BURSTS: usize = 100;
+                const BURST_SIZE: usize = 10_000;
+                out.record(&bursty::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync)",
+                    args.scaled(N_BURSTS),
+                    args.scaled(BURST_SIZE),
+                    |_| {},
+                ));
+                out.record(&bursty::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync/reserved)",
+                    args.scaled(N_BURSTS),
+                    args.scaled(BURST_SIZE),
+                    dumpster::sync::reserve,
+                ));
+            }
+
+            {
+                const N_COLLECTIONS: usize = 100;
+                const HEAP_SIZE: usize = 100_000;
+                out.record(&rooted::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync)",
+                    args.scaled(N_COLLECTIONS),
+                    args.scaled(HEAP_SIZE),
+                    |gc| Box::new(gc),
+                ));
+                out.record(&rooted::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync/rooted)",
+                    args.scaled(N_COLLECTIONS),
+                    args.scaled(HEAP_SIZE),
+                    |gc| Box::new(dumpster::sync::Gc::into_root(gc)),
+                ));
+                out.record(&pauses::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync)",
+                    args.scaled(N_COLLECTIONS),
+                    args.scaled(HEAP_SIZE),
+                ));
+            }
+
+            {
+                const N_NODES: usize = 1_000_000;
+                const CYCLE_LEN: usize = 4;
+                out.record(&garbage_heap::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                    "dumpster (sync)",
+                    args.scaled(N_NODES),
+                    CYCLE_LEN,
+                ));
+            }
+
+            {
+                const N_BATCHES: usize = 10_000;
+                const BATCH_SIZE: usize = 100;
+                const HEAP_SIZE: usize = 10_000;
+                out.record(&clone_latency(
+                    "dumpster (sync)",
+                    args.scaled(N_BATCHES),
+                    BATCH_SIZE,
+                    args.scaled(HEAP_SIZE),
+                ));
+            }
+
+            {
+                const N_CYCLES: usize = 10_000;
+                const CYCLE_LEN: usize = 100;
+                let name = if cfg!(feature = "epoch") {
+                    "dumpster (sync/epoch)"
+                } else {
+                    "dumpster (sync)"
+                };
+                for &n_threads in &args.threads {
+                    out.record_all(&drop_pauses(
+                        name,
+                        n_threads,
+                        args.scaled(N_CYCLES),
+                        CYCLE_LEN,
+                    ));
+                }
+            }
+
+            // only with `parallel-collect`, so that the other sync benchmarks run the same
+            // single-threaded collector as the other crates
+            #[cfg(feature = "parallel-collect")]
+            {
+                const N_BLOCKS: usize = 50_000;
+                const BLOCK_SIZE: usize = 8;
+                for &n_workers in &args.threads {
+                    dumpster::sync::set_collect_workers(n_workers);
+                    out.record(&parallel_graph::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                        "dumpster (sync)",
+                        args.scaled(N_BLOCKS),
+                        BLOCK_SIZE,
+                        n_workers,
+                    ));
+                }
+                dumpster::sync::set_collect_workers(0);
+            }
+
+            {
+                // collections never run, so that only the cost of marking allocations as dirty is
+                // measured
+                const N_MARKS: usize = 1_000_000;
+                const N_LIVE: usize = 10_000;
+                dumpster::sync::set_collect_condition(sync_never_collect);
+                for &n_threads in &args.threads {
+                    out.record(&dirty_marks::<dumpster::sync::Gc<DumpsterSyncMultiref>>(
+                        "dumpster (sync)",
+                        args.scaled(N_MARKS),
+                        args.scaled(N_LIVE),
+                        n_threads,
+                    ));
+                }
+                dumpster::sync::collect();
+                dumpster::sync::set_collect_condition(dumpster::sync::default_collect_condition);
+            }
 
-        for n_threads in 1..=available_parallelism().unwrap().get() {
-            println!(
-                "{}",
-                multi_threaded::<shredder::Gc<ShredderSyncMultiref>>(
-                    "shredder", N_ITERS, n_threads
-                )
+            owner_heavies::<dumpster::sync::Gc<DumpsterSyncMultiref>>(out, args, "dumpster (sync)");
+            dumpster::sync::collect();
+            migrations::<dumpster::sync::Gc<DumpsterSyncMultiref>>(out, args, "dumpster (sync)");
+            scenarios::<Vec<dumpster::sync::Gc<DumpsterSyncMultiref>>>(
+                out,
+                args,
+                "dumpster (sync)",
             );
+        },
+    },
+    Backend {
+        name: "gc",
+        every: 1,
+        dumpster: false,
+        run: |out, args| {
+            out.record(&single_threaded::<gc::Gc<GcMultiref>>("gc", args.iters));
+            scenarios::<Vec<gc::Gc<GcMultiref>>>(out, args, "gc");
+        },
+    },
+    Backend {
+        name: "bacon-rajan-cc",
+        every: 1,
+        dumpster: false,
+        run: |out, args| {
+            out.record(&single_threaded::<bacon_rajan_cc::Cc<BaconRajanMultiref>>(
+                "bacon-rajan-cc",
+                args.iters,
+            ));
+            scenarios::<Vec<bacon_rajan_cc::Cc<BaconRajanMultiref>>>(out, args, "bacon-rajan-cc");
+        },
+    },
+    Backend {
+        name: "gc-arena",
+        every: 1,
+        dumpster: false,
+        run: |out, args| scenarios::<GcArenaDriver>(out, args, "gc-arena"),
+    },
+    Backend {
+        name: "broom",
+        every: 1,
+        dumpster: false,
+        run: |out, args| scenarios::<BroomDriver>(out, args, "broom"),
+    },
+    Backend {
+        name: "shredder",
+        // run fewer tests of shredder because it takes forever
+        every: 5,
+        dumpster: false,
+        run: |out, args| {
+            scenarios::<Vec<shredder::Gc<ShredderMultiref>>>(out, args, "shredder");
+            out.record(&single_threaded::<shredder::Gc<ShredderMultiref>>(
+                "shredder", args.iters,
+            ));
+            for &n_threads in &args.threads {
+                out.record(&multi_threaded::<shredder::Gc<ShredderSyncMultiref>>(
+                    "shredder", args.iters, n_threads,
+                ));
+            }
+            migrations::<shredder::Gc<ShredderSyncMultiref>>(out, args, "shredder");
+        },
+    },
+    Backend {
+        name: "rc",
+        every: 1,
+        dumpster: false,
+        run: |out, args| {
+            out.record(&single_threaded::<Rc<RcMultiref>>("Rc", args.iters));
+            out.record(&single_threaded::<RcWeakRef>("Rc (weak)", args.iters));
+            scenarios::<Vec<Rc<RcMultiref>>>(out, args, "Rc");
+            scenarios::<Vec<RcWeakRef>>(out, args, "Rc (weak)");
+        },
+    },
+    Backend {
+        name: "arc",
+        every: 1,
+        dumpster: false,
+        run: |out, args| {
+            out.record(&single_threaded::<Arc<ArcMultiref>>("Arc", args.iters));
+            out.record(&single_threaded::<ArcWeakRef>("Arc (weak)", args.iters));
+            scenarios::<Vec<Arc<ArcMultiref>>>(out, args, "Arc");
+            scenarios::<Vec<ArcWeakRef>>(out, args, "Arc (weak)");
+            for &n_threads in &args.threads {
+                out.record(&multi_threaded::<Arc<ArcMultiref>>(
+                    "Arc", args.iters, n_threads,
+                ));
+                out.record(&multi_threaded::<ArcWeakRef>(
+                    "Arc (weak)",
+                    args.iters,
+                    n_threads,
+                ));
+            }
+            owner_heavies::<Arc<ArcMultiref>>(out, args, "Arc");
+            // `Arc` leaks every cycle, so it only bounds how fast the others could go
+            migrations::<Arc<ArcMultiref>>(out, args, "Arc");
+        },
+    },
+    Backend {
+        name: "arena",
+        every: 1,
+        dumpster: false,
+        run: |out, args| {
+            out.record(&single_threaded::<ArenaRef>("arena", args.iters));
+            scenarios::<Vec<ArenaRef>>(out, args, "arena");
+        },
+    },
+];
+
+fn main() {
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|message| {
+        if message.is_empty() {
+            println!("{USAGE}");
+            std::process::exit(0);
+        }
+        eprintln!("{message}\n{USAGE}");
+        std::process::exit(2);
+    });
+    let backends = select(&args).unwrap_or_else(|message| {
+        eprintln!("{message}");
+        std::process::exit(2);
+    });
+    let mut out = Output::open(&args).unwrap_or_else(|e| {
+        eprintln!("could not open output: {e}");
+        std::process::exit(1);
+    });
+    run(&mut out, &args, &backends);
+}
+
+/// Pick out the backends which `--only` and `--skip` leave, in the order they are listed in
+/// [`BACKENDS`].
+///
+/// # Errors
+///
+/// Returns a message if either option names a backend which does not exist.
+fn select(args: &Args) -> Result<Vec<&'static Backend>, String> {
+    for name in args.only.iter().flatten().chain(&args.skip) {
+        if !BACKENDS.iter().any(|b| b.name == name) {
+            let names = BACKENDS.iter().map(|b| b.name).collect::<Vec<_>>();
+            return Err(format!(
+                "unknown backend `{name}`; the backends are {}",
+                names.join(", ")
+            ));
         }
     }
+    Ok(BACKENDS
+        .iter()
+        .filter(|b| {
+            args.only
+                .as_ref()
+                .is_none_or(|only| only.iter().any(|n| n == b.name))
+                && !args.skip.iter().any(|n| n == b.name)
+        })
+        .collect())
+}
 
-    for _ in 0..100 {
-        println!("{}", single_threaded::<Rc<RcMultiref>>("Rc", N_ITERS));
-        println!("{}", single_threaded::<Arc<ArcMultiref>>("Arc", N_ITERS));
-        for n_threads in 1..=available_parallelism().unwrap().get() {
-            println!(
-                "{}",
-                multi_threaded::<Arc<ArcMultiref>>("Arc", N_ITERS, n_threads)
-            );
+/// Run every benchmark of each of `backends` as many times as `args` asks for, taking turns
+/// between the backends in each run.
+fn run(out: &mut Output, args: &Args, backends: &[&Backend]) {
+    SEED.store(args.seed, Ordering::Relaxed);
+    collections::watch();
+    for i in 0..args.runs {
+        for backend in backends {
+            if i % backend.every == 0 {
+                out.count_collections(backend.dumpster);
+                (backend.run)(out, args);
+            }
         }
     }
 }
 
+/// Run `f` on a fresh thread, whose unsync dumpster starts out empty, counting the collections it
+/// runs.
+fn fresh_thread<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    thread::spawn(|| {
+        collections::watch_unsync();
+        f()
+    })
+    .join()
+    .unwrap()
+}
+
 /// Run a benchmark of a multi-threaded garbage collector.
+///
+/// The latency of every operation is recorded, but tearing down the heap at the end only counts
+/// towards the total duration.
 fn single_threaded<M: Multiref>(name: &'static str, n_iters: usize) -> BenchmarkData {
-    fastrand::seed(12345);
+    fastrand::seed(seed());
     let mut gcs = (0..50).map(|_| M::new(Vec::new())).collect::<Vec<_>>();
 
     // println!("{name}: running...");
+    let mut latencies = Histogram::new();
     let tic = Instant::now();
+    let mut last = tic;
     for _n in 0..n_iters {
         // println!("iter {_n}");
         if gcs.is_empty() {
@@ -201,9 +581,12 @@ fn single_threaded<M: Multiref>(name: &'static str, n_iters: usize) -> Benchmark
                 _ => unreachable!(),
             }
         }
+        let now = Instant::now();
+        latencies.record(now - last);
+        last = now;
     }
     drop(gcs);
-    M::collect();
+    M::free_all();
     let toc = Instant::now();
     // println!("finished {name} in {:?}", (toc - tic));
     BenchmarkData {
@@ -212,9 +595,834 @@ fn single_threaded<M: Multiref>(name: &'static str, n_iters: usize) -> Benchmark
         n_threads: 1,
         n_ops: n_iters,
         duration: toc.duration_since(tic),
+        latency: Some(latencies.summary()),
+    }
+}
+
+/// Run a benchmark of a garbage collector under bursts of allocation, where each burst creates
+/// `burst_size` allocations, marks all of them dirty, and then throws them away.
+///
+/// `reserve` is called with `burst_size` before the first burst.
+fn bursty<M: Multiref + 'static>(
+    name: &'static str,
+    n_bursts: usize,
+    burst_size: usize,
+    reserve: fn(usize),
+) -> BenchmarkData {
+    let duration = fresh_thread(move || {
+        let tic = Instant::now();
+        reserve(burst_size);
+        for _ in 0..n_bursts {
+            let mut gcs: Vec<M> = Vec::with_capacity(burst_size);
+            for _ in 0..burst_size {
+                let points_to = gcs.last().cloned().into_iter().collect();
+                gcs.push(M::new(points_to));
+            }
+            for gc in &gcs {
+                drop(gc.clone());
+            }
+            drop(gcs);
+            M::collect();
+        }
+        tic.elapsed()
+    });
+    BenchmarkData {
+        name,
+        test: "bursty",
+        n_threads: 1,
+        n_ops: n_bursts * burst_size,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of a garbage collector with a large, long-lived heap, where every collection
+/// starts from the entry point of that heap.
+///
+/// `hold` is given the entry point, and returns whatever keeps it alive during the benchmark.
+fn rooted<M: Multiref + 'static>(
+    name: &'static str,
+    n_collections: usize,
+    heap_size: usize,
+    hold: fn(M) -> Box<dyn Any>,
+) -> BenchmarkData {
+    let duration = fresh_thread(move || {
+        // a long cycle, so that the whole heap must be traversed to find out that it is reachable
+        let first = M::new(Vec::new());
+        let mut entry = first.clone();
+        for _ in 1..heap_size {
+            entry = M::new(vec![entry]);
+        }
+        first.apply(|v| v.push(entry.clone()));
+        drop(first);
+        let holder = hold(entry.clone());
+
+        let tic = Instant::now();
+        for _ in 0..n_collections {
+            drop(entry.clone());
+            M::collect();
+        }
+        let toc = tic.elapsed();
+        drop((entry, holder));
+        M::collect();
+        toc
+    });
+    BenchmarkData {
+        name,
+        test: "rooted",
+        n_threads: 1,
+        n_ops: n_collections,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of the pause times of repeated collections of a large heap which does not
+/// change between them, where every collection must traverse the whole heap.
+///
+/// The reported duration is that of the longest collection after the first, which shows the cost
+/// of a collection once the collector has warmed up.
+fn pauses<M: Multiref + 'static>(
+    name: &'static str,
+    n_collections: usize,
+    heap_size: usize,
+) -> BenchmarkData {
+    let duration = fresh_thread(move || {
+        let first = M::new(Vec::new());
+        let mut entry = first.clone();
+        for _ in 1..heap_size {
+            entry = M::new(vec![entry]);
+        }
+        first.apply(|v| v.push(entry.clone()));
+        drop(first);
+
+        // the first collection pays for anything the collector sets up once
+        drop(entry.clone());
+        M::collect();
+        let longest = (0..n_collections)
+            .map(|_| {
+                drop(entry.clone());
+                let tic = Instant::now();
+                M::collect();
+                tic.elapsed()
+            })
+            .max()
+            .unwrap_or_default();
+        drop(entry);
+        M::collect();
+        longest
+    });
+    BenchmarkData {
+        name,
+        test: "pauses",
+        n_threads: 1,
+        n_ops: n_collections,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of a single collection of a heap made only of garbage, which is `n_nodes`
+/// uniform allocations in cycles of `cycle_len`.
+///
+/// Everything the collection searches must also be torn down, so this shows the cost of destroying
+/// garbage more than the other benchmarks do.
+fn garbage_heap<M: Multiref>(
+    name: &'static str,
+    n_nodes: usize,
+    cycle_len: usize,
+) -> BenchmarkData {
+    for _ in 0..n_nodes / cycle_len {
+        let first = M::new(Vec::new());
+        let mut entry = first.clone();
+        for _ in 1..cycle_len {
+            entry = M::new(vec![entry]);
+        }
+        first.apply(|v| v.push(entry));
+    }
+
+    let tic = Instant::now();
+    M::collect();
+    BenchmarkData {
+        name,
+        test: "garbage heap",
+        n_threads: 1,
+        n_ops: n_nodes,
+        duration: tic.elapsed(),
+        latency: None,
+    }
+}
+
+/// Run every benchmark of a realistic topology on one garbage collector.
+///
+/// `Rc` and `Arc` leak every cyclic structure these build.
+fn scenarios<D: Driver>(out: &mut Output, args: &Args, name: &'static str) {
+    const N_LISTS: usize = 10;
+    const LIST_LEN: usize = 10_000;
+    const N_TREES: usize = 10;
+    const TREE_DEPTH: usize = 14;
+    const N_CLIQUES: usize = 10;
+    const CLIQUE_SIZE: usize = 100;
+    const N_NODES: usize = 10_000;
+    const DEGREE: usize = 4;
+    const N_REWIRES: usize = 1_000_000;
+
+    let (n_lists, list_len) = (args.scaled(N_LISTS), args.scaled(LIST_LEN));
+    // a tree doubles in size with each level
+    let tree_depth = TREE_DEPTH.saturating_sub(args.scale.ilog2() as usize);
+    out.record(&linked_lists::<D>(name, n_lists, list_len, false));
+    out.record(&linked_lists::<D>(name, n_lists, list_len, true));
+    out.record(&binary_trees::<D>(name, args.scaled(N_TREES), tree_depth));
+    out.record(&cliques::<D>(
+        name,
+        args.scaled(N_CLIQUES),
+        args.scaled(CLIQUE_SIZE),
+    ));
+    out.record(&rewire::<D>(
+        name,
+        args.scaled(N_NODES),
+        DEGREE,
+        args.scaled(N_REWIRES),
+    ));
+}
+
+/// Run the benchmark of threads which mostly touch their own allocations on every number of
+/// threads asked for.
+fn owner_heavies<M: SyncMultiref>(out: &mut Output, args: &Args, name: &'static str) {
+    const N_OPS: usize = 4_000_000;
+    const N_LIVE: usize = 1_000;
+    // one in twenty references is to an allocation made by another thread
+    const FOREIGN_PER_MILLE: u16 = 50;
+    for &n_threads in &args.threads {
+        out.record(&owner_heavy::<M>(
+            name,
+            args.scaled(N_OPS),
+            args.scaled(N_LIVE),
+            FOREIGN_PER_MILLE,
+            n_threads,
+        ));
+    }
+}
+
+/// Run the benchmark of allocations migrating between threads with every channel depth, with as
+/// many producer and consumer pairs as fit in each number of threads asked for.
+fn migrations<M: SyncMultiref>(out: &mut Output, args: &Args, name: &'static str) {
+    const N_GRAPHS: usize = 10_000;
+    const GRAPH_SIZE: usize = 8;
+    let mut pairs = args
+        .threads
+        .iter()
+        .map(|&n| (n / 2).max(1))
+        .collect::<Vec<_>>();
+    pairs.dedup();
+    for (test, depth) in MIGRATION_DEPTHS {
+        for &n_pairs in &pairs {
+            out.record(&migration::<M>(
+                name,
+                test,
+                depth,
+                args.scaled(N_GRAPHS),
+                GRAPH_SIZE,
+                n_pairs,
+            ));
+        }
+    }
+}
+
+/// Run a benchmark which builds `n_lists` linked lists of `len` nodes each, holding only the head
+/// of each, and then drops and collects each list.
+///
+/// If `doubly` is set, every node also points at the node made after it, so that each list is one
+/// long chain of two-node cycles.
+fn linked_lists<D: Driver>(
+    name: &'static str,
+    n_lists: usize,
+    len: usize,
+    doubly: bool,
+) -> BenchmarkData {
+    let mut heap = D::new();
+    let tic = Instant::now();
+    for _ in 0..n_lists {
+        heap.alloc(&[]);
+        for _ in 1..len {
+            heap.alloc(&[0]);
+            if doubly {
+                heap.link(0, &[1]);
+            }
+            // the new node takes the place of the old head
+            heap.unroot(0);
+        }
+        heap.clear();
+        heap.free_all();
+    }
+    BenchmarkData {
+        name,
+        test: if doubly {
+            "doubly linked list"
+        } else {
+            "linked list"
+        },
+        n_threads: 1,
+        n_ops: n_lists * len,
+        duration: tic.elapsed(),
+        latency: None,
+    }
+}
+
+/// Run a benchmark which builds `n_trees` complete binary trees of depth `depth`, and then drops
+/// and collects each tree.
+fn binary_trees<D: Driver>(name: &'static str, n_trees: usize, depth: usize) -> BenchmarkData {
+    /// Build a complete binary tree of depth `depth`, rooted after every other root.
+    fn build<D: Driver>(heap: &mut D, depth: usize) {
+        if depth == 0 {
+            heap.alloc(&[]);
+        } else {
+            build(heap, depth - 1);
+            build(heap, depth - 1);
+            let n = heap.n_roots();
+            heap.alloc(&[n - 2, n - 1]);
+            // move the new node into the place of the left subtree, then drop the right one
+            heap.unroot(n - 2);
+            heap.unroot(n - 1);
+        }
+    }
+
+    let mut heap = D::new();
+    let tic = Instant::now();
+    for _ in 0..n_trees {
+        build(&mut heap, depth);
+        heap.clear();
+        heap.free_all();
+    }
+    BenchmarkData {
+        name,
+        test: "binary tree",
+        n_threads: 1,
+        n_ops: n_trees * ((2 << depth) - 1),
+        duration: tic.elapsed(),
+        latency: None,
+    }
+}
+
+/// Run a benchmark which builds `n_cliques` cliques of `size` nodes, in which every node points at
+/// every node including itself, and then drops and collects each clique.
+///
+/// The number of operations is the number of references made.
+fn cliques<D: Driver>(name: &'static str, n_cliques: usize, size: usize) -> BenchmarkData {
+    let all = (0..size).collect::<Vec<_>>();
+    let mut heap = D::new();
+    let tic = Instant::now();
+    for _ in 0..n_cliques {
+        for _ in 0..size {
+            heap.alloc(&[]);
+        }
+        for from in 0..size {
+            heap.link(from, &all);
+        }
+        heap.clear();
+        heap.free_all();
+    }
+    BenchmarkData {
+        name,
+        test: "clique",
+        n_threads: 1,
+        n_ops: n_cliques * size * size,
+        duration: tic.elapsed(),
+        latency: None,
+    }
+}
+
+/// Run a benchmark which builds a random graph of `n_nodes` nodes that each point at `degree`
+/// others, and then makes `n_rewires` changes to it, each of which points one reference of a
+/// random node at another random node.
+///
+/// Every node stays reachable, so the graph stays the same size while its shape keeps changing.
+/// Only the changes and the final teardown of the graph are timed.
+fn rewire<D: Driver>(
+    name: &'static str,
+    n_nodes: usize,
+    degree: usize,
+    n_rewires: usize,
+) -> BenchmarkData {
+    fastrand::seed(seed());
+    let mut heap = D::new();
+    for _ in 0..n_nodes {
+        heap.alloc(&[]);
+    }
+    for from in 0..n_nodes {
+        let to = (0..degree)
+            .map(|_| fastrand::usize(..n_nodes))
+            .collect::<Vec<_>>();
+        heap.link(from, &to);
+    }
+
+    let tic = Instant::now();
+    for _ in 0..n_rewires {
+        let to = fastrand::usize(..n_nodes);
+        let from = fastrand::usize(..n_nodes);
+        heap.relink(from, fastrand::usize(..degree), to);
+    }
+    heap.clear();
+    heap.free_all();
+    BenchmarkData {
+        name,
+        test: "rewire",
+        n_threads: 1,
+        n_ops: n_rewires,
+        duration: tic.elapsed(),
+        latency: None,
     }
 }
 
+/// Run a benchmark of a large, static live set alongside churning garbage, where each of
+/// `n_rounds` rounds throws away `churn_size` allocations in small cycles which refer into the live
+/// set, and then collects them.
+///
+/// Only every `full_interval`-th collection is a full one, and the others are partial, so with an
+/// interval of 1 every collection traverses the whole live set.
+fn partial(
+    name: &'static str,
+    n_rounds: usize,
+    live_size: usize,
+    churn_size: usize,
+    full_interval: usize,
+) -> BenchmarkData {
+    type M = dumpster::unsync::Gc<DumpsterUnsyncMultiref>;
+    let duration = fresh_thread(move || {
+        dumpster::unsync::set_full_collection_interval(full_interval);
+        let first = <M as Multiref>::new(Vec::new());
+        let mut entry = first.clone();
+        for _ in 1..live_size {
+            entry = <M as Multiref>::new(vec![entry]);
+        }
+        first.apply(|v| v.push(entry.clone()));
+        drop(first);
+        // the first full collection finds the live set, which the partial ones then skip
+        drop(entry.clone());
+        dumpster::unsync::collect();
+
+        let tic = Instant::now();
+        for round in 1..=n_rounds {
+            for _ in 0..churn_size / 2 {
+                let a = <M as Multiref>::new(vec![entry.clone()]);
+                let b = <M as Multiref>::new(vec![a.clone()]);
+                a.apply(|v| v.push(b));
+            }
+            if round % full_interval == 0 {
+                dumpster::unsync::collect();
+            } else {
+                dumpster::unsync::collect_partial();
+            }
+        }
+        let toc = tic.elapsed();
+        drop(entry);
+        dumpster::unsync::collect();
+        toc
+    });
+    BenchmarkData {
+        name,
+        test: "partial",
+        n_threads: 1,
+        n_ops: n_rounds * churn_size,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of `n_ops` short-lived allocations, each referring into a live set of
+/// `live_size` allocations, of which every fourth refers to itself and so must be collected.
+///
+/// Up to `free_list_capacity` bytes of freed allocations are kept for reuse by new ones.
+fn recycle(
+    name: &'static str,
+    n_ops: usize,
+    live_size: usize,
+    free_list_capacity: usize,
+) -> BenchmarkData {
+    type M = dumpster::unsync::Gc<DumpsterUnsyncMultiref>;
+    let duration = fresh_thread(move || {
+        dumpster::unsync::set_free_list_capacity(free_list_capacity);
+        let live = (0..live_size)
+            .map(|_| <M as Multiref>::new(Vec::new()))
+            .collect::<Vec<_>>();
+
+        let tic = Instant::now();
+        for i in 0..n_ops {
+            let gc = <M as Multiref>::new(vec![live[i % live_size].clone()]);
+            if i % 4 == 0 {
+                gc.apply(|v| v.push(gc.clone()));
+            }
+        }
+        dumpster::unsync::collect();
+        let toc = tic.elapsed();
+        drop(live);
+        dumpster::unsync::collect();
+        toc
+    });
+    BenchmarkData {
+        name,
+        test: "recycle",
+        n_threads: 1,
+        n_ops,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of the latency of cloning a `Gc` while another thread continuously collects a
+/// heap of `heap_size` allocations.
+///
+/// The clones are timed in `n_batches` batches of `batch_size` clones each, and the reported
+/// duration is that of the 99th-percentile batch.
+fn clone_latency(
+    name: &'static str,
+    n_batches: usize,
+    batch_size: usize,
+    heap_size: usize,
+) -> BenchmarkData {
+    type M = dumpster::sync::Gc<DumpsterSyncMultiref>;
+    let done = AtomicBool::new(false);
+    let duration = scope(|s| {
+        s.spawn(|| {
+            let first = <M as Multiref>::new(Vec::new());
+            let mut entry = first.clone();
+            for _ in 1..heap_size {
+                entry = <M as Multiref>::new(vec![entry]);
+            }
+            first.apply(|v| v.push(entry.clone()));
+            drop(first);
+            while !done.load(Ordering::Relaxed) {
+                drop(entry.clone());
+                dumpster::sync::collect();
+            }
+            drop(entry);
+            dumpster::sync::collect();
+        });
+
+        let gc = <M as Multiref>::new(Vec::new());
+        let mut clones = Vec::with_capacity(batch_size);
+        let mut batches = (0..n_batches)
+            .map(|_| {
+                let tic = Instant::now();
+                for _ in 0..batch_size {
+                    clones.push(gc.clone());
+                }
+                let toc = tic.elapsed();
+                clones.clear();
+                toc
+            })
+            .collect::<Vec<_>>();
+        done.store(true, Ordering::Relaxed);
+        batches.sort_unstable();
+        batches[n_batches * 99 / 100]
+    });
+    BenchmarkData {
+        name,
+        test: "clone latency",
+        n_threads: 2,
+        n_ops: n_batches * batch_size,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of the pauses seen when dropping `Gc`s, where each of `n_threads` threads
+/// builds `n_cycles` cycles of `cycle_len` allocations and times the drop of each cycle's last
+/// handle, under the default collection condition.
+///
+/// A collection triggered by one of these drops also destroys the garbage it finds, unless the
+/// `epoch` feature hands that work over to every thread.
+/// The reported durations are those of the 99th-percentile drop and of the longest one.
+fn drop_pauses(
+    name: &'static str,
+    n_threads: usize,
+    n_cycles: usize,
+    cycle_len: usize,
+) -> [BenchmarkData; 2] {
+    type M = dumpster::sync::Gc<DumpsterSyncMultiref>;
+    let mut pauses = scope(|s| {
+        let threads = (0..n_threads)
+            .map(|_| {
+                s.spawn(move || {
+                    (0..n_cycles)
+                        .map(|_| {
+                            let first = <M as Multiref>::new(Vec::new());
+                            let mut entry = first.clone();
+                            for _ in 1..cycle_len {
+                                entry = <M as Multiref>::new(vec![entry]);
+                            }
+                            first.apply(|v| v.push(entry));
+                            let tic = Instant::now();
+                            drop(first);
+                            tic.elapsed()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    dumpster::sync::collect();
+    pauses.sort_unstable();
+    let n_ops = pauses.len();
+    [
+        BenchmarkData {
+            name,
+            test: "drop pauses (p99)",
+            n_threads,
+            n_ops,
+            duration: pauses[n_ops * 99 / 100],
+            latency: None,
+        },
+        BenchmarkData {
+            name,
+            test: "drop pauses (max)",
+            n_threads,
+            n_ops,
+            duration: pauses[n_ops - 1],
+            latency: None,
+        },
+    ]
+}
+
+/// Run a benchmark of `n_collections` collections of an allocation holding a map of `payload_len`
+/// integers.
+///
+/// The integers are leaves, so the time taken should not depend on `payload_len`.
+fn leaf_payload(name: &'static str, n_collections: usize, payload_len: usize) -> BenchmarkData {
+    let duration = fresh_thread(move || {
+        let gc = dumpster::unsync::Gc::new((
+            (0..payload_len).map(|i| (i, i)).collect::<HashMap<_, _>>(),
+            None::<dumpster::unsync::Gc<()>>,
+        ));
+
+        let tic = Instant::now();
+        for _ in 0..n_collections {
+            drop(gc.clone());
+            dumpster::unsync::collect();
+        }
+        tic.elapsed()
+    });
+    BenchmarkData {
+        name,
+        test: "leaf payload",
+        n_threads: 1,
+        n_ops: n_collections,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of a single collection of a large heap in which every allocation is dirty,
+/// made of `n_blocks` small cycles of `block_size` allocations each.
+///
+/// `n_workers` is only reported; the collector must already be set to use that many threads.
+#[cfg(feature = "parallel-collect")]
+fn parallel_graph<M: SyncMultiref>(
+    name: &'static str,
+    n_blocks: usize,
+    block_size: usize,
+    n_workers: usize,
+) -> BenchmarkData {
+    let nodes = (0..n_blocks * block_size)
+        .map(|_| M::new(Vec::new()))
+        .collect::<Vec<_>>();
+    for block in nodes.chunks(block_size) {
+        for (i, node) in block.iter().enumerate() {
+            let next = block[(i + 1) % block_size].clone();
+            node.apply(|v| v.push(next));
+        }
+    }
+    drop(nodes);
+
+    let tic = Instant::now();
+    M::collect();
+    BenchmarkData {
+        name,
+        test: "parallel_graph",
+        n_threads: n_workers,
+        n_ops: n_blocks * block_size,
+        duration: tic.elapsed(),
+        latency: None,
+    }
+}
+
+/// Run a benchmark of how quickly allocations can be marked as possibly garbage, by repeatedly
+/// cloning and dropping references to `n_live` allocations on each of `n_threads` threads.
+fn dirty_marks<M: SyncMultiref>(
+    name: &'static str,
+    n_marks: usize,
+    n_live: usize,
+    n_threads: usize,
+) -> BenchmarkData {
+    let duration = scope(|s| {
+        let handles = (0..n_threads)
+            .map(|i| {
+                s.spawn(move || {
+                    fastrand::seed(seed() + i as u64);
+                    let gcs = (0..n_live).map(|_| M::new(Vec::new())).collect::<Vec<_>>();
+                    let tic = Instant::now();
+                    for _ in 0..(n_marks / n_threads) {
+                        drop(gcs[fastrand::usize(0..n_live)].clone());
+                    }
+                    tic.elapsed()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .max()
+            .unwrap()
+    });
+    BenchmarkData {
+        name,
+        test: "dirty_marks",
+        n_threads,
+        n_ops: (n_marks / n_threads) * n_threads,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of cloning and dropping references on `n_threads` threads, each of which
+/// mostly touches the `n_live` allocations which it made itself.
+///
+/// Out of every thousand references, `foreign_per_mille` are instead to allocations made by the
+/// main thread, which every thread shares.
+fn owner_heavy<M: SyncMultiref>(
+    name: &'static str,
+    n_ops: usize,
+    n_live: usize,
+    foreign_per_mille: u16,
+    n_threads: usize,
+) -> BenchmarkData {
+    let shared = (0..n_live).map(|_| M::new(Vec::new())).collect::<Vec<_>>();
+    let duration = scope(|s| {
+        let handles = (0..n_threads)
+            .map(|i| {
+                let shared = &shared;
+                s.spawn(move || {
+                    fastrand::seed(seed() + i as u64);
+                    let owned = (0..n_live).map(|_| M::new(Vec::new())).collect::<Vec<_>>();
+                    let tic = Instant::now();
+                    for _ in 0..(n_ops / n_threads) {
+                        let gcs = if fastrand::u16(0..1000) < foreign_per_mille {
+                            shared
+                        } else {
+                            &owned
+                        };
+                        let gc = gcs[fastrand::usize(0..n_live)].clone();
+                        let again = gc.clone();
+                        drop(gc);
+                        drop(again);
+                    }
+                    tic.elapsed()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .max()
+            .unwrap()
+    });
+    BenchmarkData {
+        name,
+        test: "owner_heavy",
+        n_threads,
+        n_ops: (n_ops / n_threads) * n_threads,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark in which each of `n_pairs` producer threads allocates `n_graphs` cycles of
+/// `graph_size` nodes and sends them over channels holding up to `depth` cycles each to as many
+/// consumer threads, which link them into their own structures and later drop them.
+///
+/// Every allocation is made on one thread and dies on another.
+/// The peak memory shows how much garbage piles up when drops happen far from allocations, and
+/// tearing down the consumers' structures at the end is not timed.
+fn migration<M: SyncMultiref>(
+    name: &'static str,
+    test: &'static str,
+    depth: usize,
+    n_graphs: usize,
+    graph_size: usize,
+    n_pairs: usize,
+) -> BenchmarkData {
+    /// The number of nodes each consumer links the cycles it receives into.
+    const N_SLOTS: usize = 16;
+    /// The number of cycles each slot holds before it drops one at random.
+    const KEEP: usize = 8;
+
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        (0..n_pairs).map(|_| mpsc::sync_channel::<M>(depth)).unzip();
+    let tic = Instant::now();
+    let slots = scope(|s| {
+        for i in 0..n_pairs {
+            let senders = senders.clone();
+            s.spawn(move || {
+                for k in 0..n_graphs {
+                    let first = M::new(Vec::new());
+                    let mut last = first.clone();
+                    for _ in 1..graph_size {
+                        last = M::new(vec![last]);
+                    }
+                    first.apply(|v| v.push(last.clone()));
+                    // spread each producer's cycles over every consumer
+                    senders[(i + k) % n_pairs].send(last).unwrap();
+                }
+            });
+        }
+        // the consumers stop once every producer is done and has dropped its senders
+        drop(senders);
+
+        let handles = receivers
+            .into_iter()
+            .enumerate()
+            .map(|(i, receiver)| {
+                s.spawn(move || {
+                    fastrand::seed(seed() + i as u64);
+                    let slots = (0..N_SLOTS).map(|_| M::new(Vec::new())).collect::<Vec<_>>();
+                    for graph in receiver {
+                        slots[fastrand::usize(..N_SLOTS)].apply(|v| {
+                            v.push(graph);
+                            if v.len() > KEEP {
+                                v.swap_remove(fastrand::usize(..v.len()));
+                            }
+                        });
+                    }
+                    slots
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    let duration = tic.elapsed();
+    drop(slots);
+    M::free_all();
+    BenchmarkData {
+        name,
+        test,
+        n_threads: 2 * n_pairs,
+        n_ops: n_pairs * n_graphs * graph_size,
+        duration,
+        latency: None,
+    }
+}
+
+/// Run a benchmark of a garbage collector shared by `n_threads` threads, which each make random
+/// changes to a common heap.
+///
+/// The latencies of the operations on every thread are recorded together.
 fn multi_threaded<M: SyncMultiref>(
     name: &'static str,
     n_iters: usize,
@@ -226,81 +1434,97 @@ fn multi_threaded<M: SyncMultiref>(
 
     let tic = Mutex::new(Instant::now());
     let toc = Mutex::new(Instant::now());
-    scope(|s| {
+    let latencies = scope(|s| {
+        let mut handles = Vec::with_capacity(n_threads);
         for i in 0..n_threads {
             let vecs = &vecs;
             let tic = &tic;
             let toc = &toc;
-            thread::Builder::new()
+            let handle = thread::Builder::new()
                 .name(format!("multi_threaded{i}"))
                 .spawn_scoped(s, move || {
-                    *tic.lock() = Instant::now();
-                    fastrand::seed(12345 + i as u64);
+                    let mut latencies = Histogram::new();
+                    let mut last = Instant::now();
+                    *tic.lock() = last;
+                    fastrand::seed(seed() + i as u64);
 
                     for _n in 0..(n_iters / n_threads) {
                         let v1_id = fastrand::usize(0..vecs.len());
-                        match fastrand::u8(0..4) {
-                            // create
-                            0 => vecs[v1_id].lock().push(M::new(Vec::new())),
-                            // add ref
-                            1 => {
-                                let v2_id = fastrand::usize(0..vecs.len());
-                                if v1_id == v2_id {
-                                    let g1 = vecs[v1_id].lock();
-                                    if g1.len() < 2 {
-                                        continue;
-                                    }
-                                    let i1 = fastrand::usize(0..g1.len());
-                                    let i2 = fastrand::usize(0..g1.len());
-                                    let new_gc = g1[i2].clone();
-                                    g1[i1].apply(|v| v.push(new_gc));
-                                } else {
-                                    // prevent deadlock by locking lower one first
-                                    let (g1, g2) = if v1_id < v2_id {
-                                        (vecs[v1_id].lock(), vecs[v2_id].lock())
+                        'op: {
+                            match fastrand::u8(0..4) {
+                                // create
+                                0 => vecs[v1_id].lock().push(M::new(Vec::new())),
+                                // add ref
+                                1 => {
+                                    let v2_id = fastrand::usize(0..vecs.len());
+                                    if v1_id == v2_id {
+                                        let g1 = vecs[v1_id].lock();
+                                        if g1.len() < 2 {
+                                            break 'op;
+                                        }
+                                        let i1 = fastrand::usize(0..g1.len());
+                                        let i2 = fastrand::usize(0..g1.len());
+                                        let new_gc = g1[i2].clone();
+                                        g1[i1].apply(|v| v.push(new_gc));
                                     } else {
-                                        let g2 = vecs[v2_id].lock();
-                                        (vecs[v1_id].lock(), g2)
-                                    };
-                                    if g1.is_empty() || g2.is_empty() {
-                                        continue;
+                                        // prevent deadlock by locking lower one first
+                                        let (g1, g2) = if v1_id < v2_id {
+                                            (vecs[v1_id].lock(), vecs[v2_id].lock())
+                                        } else {
+                                            let g2 = vecs[v2_id].lock();
+                                            (vecs[v1_id].lock(), g2)
+                                        };
+                                        if g1.is_empty() || g2.is_empty() {
+                                            break 'op;
+                                        }
+                                        let i1 = fastrand::usize(0..g1.len());
+                                        let i2 = fastrand::usize(0..g2.len());
+                                        let new_gc = g2[i2].clone();
+                                        g1[i1].apply(|v| v.push(new_gc));
                                     }
-                                    let i1 = fastrand::usize(0..g1.len());
-                                    let i2 = fastrand::usize(0..g2.len());
-                                    let new_gc = g2[i2].clone();
-                                    g1[i1].apply(|v| v.push(new_gc));
-                                }
-                            }
-                            // destroy gc
-                            2 => {
-                                let mut guard = vecs[v1_id].lock();
-                                if guard.is_empty() {
-                                    continue;
                                 }
-                                let idx = fastrand::usize(0..guard.len());
-                                guard.swap_remove(idx);
-                            }
-                            // destroy ref
-                            3 => {
-                                let guard = vecs[v1_id].lock();
-                                if guard.is_empty() {
-                                    continue;
+                                // destroy gc
+                                2 => {
+                                    let mut guard = vecs[v1_id].lock();
+                                    if guard.is_empty() {
+                                        break 'op;
+                                    }
+                                    let idx = fastrand::usize(0..guard.len());
+                                    guard.swap_remove(idx);
                                 }
-                                guard[fastrand::usize(0..guard.len())].apply(|v| {
-                                    if !v.is_empty() {
-                                        v.swap_remove(fastrand::usize(0..v.len()));
+                                // destroy ref
+                                3 => {
+                                    let guard = vecs[v1_id].lock();
+                                    if guard.is_empty() {
+                                        break 'op;
                                     }
-                                });
+                                    guard[fastrand::usize(0..guard.len())].apply(|v| {
+                                        if !v.is_empty() {
+                                            v.swap_remove(fastrand::usize(0..v.len()));
+                                        }
+                                    });
+                                }
+                                _ => unreachable!(),
                             }
-                            _ => unreachable!(),
-                        };
+                        }
+                        let now = Instant::now();
+                        latencies.record(now - last);
+                        last = now;
                     }
-                    *toc.lock() = Instant::now();
+                    *toc.lock() = last;
+                    latencies
                 })
                 .unwrap();
+            handles.push(handle);
         }
+        handles
+            .into_iter()
+            .fold(Histogram::new(), |mut latencies, handle| {
+                latencies.merge(&handle.join().unwrap());
+                latencies
+            })
     });
-    M::collect(); // This op is single threaded and shouldn't count
+    M::free_all(); // This op is single threaded and shouldn't count
     let duration = toc.lock().duration_since(*tic.lock());
 
     // println!("finished {name} in {duration:?}");
@@ -310,5 +1534,260 @@ fn multi_threaded<M: SyncMultiref>(
         n_threads,
         n_ops: (n_iters / n_threads) * n_threads,
         duration,
+        latency: Some(latencies.summary()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Mutex};
+
+    use super::*;
+    use memory::Window;
+    use output::Format;
+
+    /// Held by every test which measures memory, since there is only one count of it for the whole
+    /// process.
+    static MEMORY: Mutex<()> = Mutex::new(());
+
+    /// Get a path in the temporary directory which no other test process uses.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("dumpster_bench_{}_{name}", std::process::id()))
+    }
+
+    /// Record a tiny run of the single-threaded benchmark, first replacing the file at `path` and
+    /// then appending to it.
+    fn record_twice(path: &std::path::Path, format: Format) {
+        let _guard = MEMORY.lock().unwrap();
+        for append in [false, true] {
+            let mut out = Output::open(&Args {
+                out: Some(path.to_owned()),
+                format,
+                append,
+                ..Args::parse(Vec::new()).unwrap()
+            })
+            .unwrap();
+            out.record(&single_threaded::<Rc<RcMultiref>>("Rc", 100));
+            out.record(&single_threaded::<
+                dumpster::unsync::Gc<DumpsterUnsyncMultiref>,
+            >("dumpster (unsync)", 100));
+        }
+    }
+
+    #[test]
+    /// Test that a CSV file of results has a single header row, and that every row has a value for
+    /// every column.
+    fn csv_smoke() {
+        let path = temp_path("results.csv");
+        record_twice(&path, Format::Csv);
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut lines = contents.lines();
+        let header = lines.next().unwrap().split(',').collect::<Vec<_>>();
+        assert_eq!(header[0], "name");
+        let rows = lines
+            .map(|l| l.split(',').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 4);
+        for row in rows {
+            assert_eq!(row.len(), header.len());
+            assert_eq!(row[1], "single_threaded");
+            row[4].parse::<u128>().unwrap();
+            let latencies = row[5..9]
+                .iter()
+                .map(|l| l.parse::<u64>().unwrap())
+                .collect::<Vec<_>>();
+            assert!(latencies.is_sorted(), "{latencies:?}");
+            row[9].parse::<usize>().unwrap();
+            row[10].parse::<isize>().unwrap();
+            // collections are only counted when asked for
+            assert_eq!(row[11..14], ["", "", ""]);
+            row[14].parse::<u64>().unwrap();
+            assert!(row[16].parse::<usize>().unwrap() > 0);
+        }
+    }
+
+    #[test]
+    /// Test that every line of a JSON file of results parses as an object holding the result and
+    /// the information about its run.
+    fn json_smoke() {
+        let path = temp_path("results.json");
+        record_twice(&path, Format::Json);
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let records = contents
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 4);
+        for record in records {
+            assert_eq!(record["test"], "single_threaded");
+            assert_eq!(record["n_ops"], 100);
+            assert!(record["duration_us"].is_u64());
+            assert!(record["p50_ns"].as_u64().unwrap() <= record["max_ns"].as_u64().unwrap());
+            assert!(record["peak_bytes"].is_u64());
+            assert!(record["end_bytes"].is_i64());
+            assert!(record["timestamp"].is_u64());
+            assert!(record["revision"].is_string() || record["revision"].is_null());
+            assert!(record["parallelism"].as_u64().unwrap() > 0);
+        }
+    }
+
+    #[test]
+    /// Test that the memory counted as allocated goes back down to where it started once a
+    /// benchmark which frees everything is done, and that its peak is counted along the way.
+    fn memory_returns_to_zero() {
+        let _guard = MEMORY.lock().unwrap();
+        let mut window = Window::start();
+        // the dumpster of a thread is freed along with it
+        thread::spawn(|| {
+            binary_trees::<Vec<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>>(
+                "dumpster (unsync)",
+                1,
+                12,
+            )
+        })
+        .join()
+        .unwrap();
+        let usage = window.restart();
+        // other tests may run alongside this one, so small differences are allowed
+        assert!(
+            usage.peak_bytes > (1 << 13) * size_of::<DumpsterUnsyncMultiref>(),
+            "{usage:?}"
+        );
+        assert!(usage.end_bytes.unsigned_abs() < 16 << 10, "{usage:?}");
+    }
+
+    #[test]
+    /// Test that the hand-managed baselines free the cyclic structures of the scenarios, which
+    /// plain `Rc` leaks.
+    fn baselines_free_cycles() {
+        fn run<M: Multiref>(name: &'static str) {
+            linked_lists::<Vec<M>>(name, 2, 1_000, true);
+            cliques::<Vec<M>>(name, 2, 50);
+            rewire::<Vec<M>>(name, 100, 4, 1_000);
+        }
+
+        let _guard = MEMORY.lock().unwrap();
+        let mut window = Window::start();
+        thread::spawn(|| {
+            run::<RcWeakRef>("Rc (weak)");
+            run::<ArcWeakRef>("Arc (weak)");
+            run::<ArenaRef>("arena");
+        })
+        .join()
+        .unwrap();
+        let usage = window.restart();
+        assert!(usage.end_bytes.unsigned_abs() < 16 << 10, "{usage:?}");
+
+        // the same structures made with plain `Rc` are never freed
+        run::<Rc<RcMultiref>>("Rc");
+        assert!(window.restart().end_bytes > 16 << 10);
+    }
+
+    #[test]
+    /// Test that the time spent collecting and the time spent outside of collections in a
+    /// single-threaded run add up to about the whole time it took.
+    fn collections_add_up() {
+        let path = temp_path("collections.csv");
+        let args = Args::parse(["--out".to_owned(), path.display().to_string()]).unwrap();
+        let wall = {
+            let _guard = MEMORY.lock().unwrap();
+            let mut out = Output::open(&args).unwrap();
+            collections::watch();
+            out.count_collections(true);
+            let tic = Instant::now();
+            let data = single_threaded::<dumpster::unsync::Gc<DumpsterUnsyncMultiref>>(
+                "dumpster (unsync)",
+                100_000,
+            );
+            let wall = tic.elapsed();
+            out.record(&data);
+            wall
+        };
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let row = contents
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .collect::<Vec<_>>();
+        let n_collections = row[11].parse::<usize>().unwrap();
+        let collect_us = row[12].parse::<u64>().unwrap();
+        let mutator_us = row[13].parse::<u64>().unwrap();
+        assert!(n_collections > 0);
+        assert!(collect_us > 0);
+        let wall_us = u64::try_from(wall.as_micros()).unwrap();
+        let total_us = collect_us + mutator_us;
+        assert!(
+            total_us <= wall_us && wall_us - total_us <= wall_us / 20 + 1000,
+            "{collect_us} + {mutator_us} vs {wall_us}"
+        );
+    }
+
+    #[test]
+    /// Test that bad arguments are rejected and good ones are understood.
+    fn parse_args() {
+        let parse = |args: &[&str]| Args::parse(args.iter().map(|&a| String::from(a)));
+        let args = parse(&["--out", "r.json", "--format", "json", "--append"]).unwrap();
+        assert_eq!(args.out.as_deref(), Some(std::path::Path::new("r.json")));
+        assert_eq!(args.format, Format::Json);
+        assert!(args.append);
+        assert_eq!(parse(&[]).unwrap().format, Format::Csv);
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--out"]).is_err());
+        assert!(parse(&["--append"]).is_err());
+        assert_eq!(parse(&["--help"]).unwrap_err(), "");
+
+        let args = parse(&["--quick", "--runs", "3", "--threads", "1,4", "--seed", "7"]).unwrap();
+        assert_eq!(args.iters, 10_000);
+        assert_eq!(args.runs, 3);
+        assert_eq!(args.threads, [1, 4]);
+        assert_eq!(args.seed, 7);
+        assert!(args.scale > 1);
+        let args = parse(&["--only", "gc,broom", "--skip", "rc"]).unwrap();
+        assert_eq!(
+            args.only.as_deref(),
+            Some(&[String::from("gc"), String::from("broom")][..])
+        );
+        assert_eq!(args.skip, ["rc"]);
+        assert_eq!(args.scale, 1);
+        assert!(parse(&["--iters", "many"]).is_err());
+        assert!(parse(&["--threads", "0"]).is_err());
+        assert!(select(&parse(&["--skip", "nonexistent"]).unwrap()).is_err());
+    }
+
+    #[test]
+    /// Test that a quick run of a single backend picks out just that backend and finishes every
+    /// one of its benchmarks.
+    fn quick_dispatch() {
+        let path = temp_path("quick.csv");
+        let args = Args::parse(
+            ["--quick", "--only", "dumpster-unsync", "--out"]
+                .into_iter()
+                .map(String::from)
+                .chain([path.display().to_string()]),
+        )
+        .unwrap();
+        let backends = select(&args).unwrap();
+        assert_eq!(backends.len(), 1);
+        {
+            let _guard = MEMORY.lock().unwrap();
+            let mut out = Output::open(&args).unwrap();
+            run(&mut out, &args, &backends);
+        }
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let rows = contents.lines().skip(1).collect::<Vec<_>>();
+        assert!(rows.len() > 10);
+        assert!(
+            rows.iter().all(|row| row.starts_with("dumpster (unsync")),
+            "{rows:?}"
+        );
     }
 }
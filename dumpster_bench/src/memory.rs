@@ -0,0 +1,126 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Measurement of the memory taken up by each benchmark.
+//!
+//! Every library being compared allocates through the global allocator, so a global allocator
+//! which counts the bytes it hands out measures them all the same way, which sampling the resident
+//! set size of the process could not do as precisely or as portably.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The number of bytes currently allocated through [`Counting`].
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+/// The most bytes allocated through [`Counting`] at once since the current [`Window`] started.
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// A global allocator which counts the bytes allocated through it, and otherwise defers to the
+/// system allocator.
+///
+/// The counts are shared by every thread, so they add a contended atomic operation to every
+/// allocation, which slows down every library alike.
+pub struct Counting;
+
+/// Count `size` more bytes as allocated.
+fn grow(size: usize) {
+    let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(live, Ordering::Relaxed);
+}
+
+/// Count `size` fewer bytes as allocated.
+fn shrink(size: usize) {
+    LIVE.fetch_sub(size, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                grow(new_size - layout.size());
+            } else {
+                shrink(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+/// The memory taken up during a [`Window`].
+pub struct Usage {
+    /// The most bytes allocated at once, beyond those allocated when the window started.
+    pub peak_bytes: usize,
+    /// The bytes allocated when the window finished, beyond those allocated when it started.
+    /// This is negative if the window freed memory allocated before it.
+    pub end_bytes: isize,
+}
+
+/// A span of time over which the memory taken up is measured.
+///
+/// There is only one peak count for the whole process, so only one window should be open at a
+/// time.
+pub struct Window {
+    /// The number of bytes allocated when the window started.
+    start: usize,
+}
+
+impl Window {
+    /// Start measuring from now.
+    pub fn start() -> Window {
+        let live = LIVE.load(Ordering::Relaxed);
+        PEAK.store(live, Ordering::Relaxed);
+        Window { start: live }
+    }
+
+    /// Get the memory taken up since this window started, and start it over from now.
+    pub fn restart(&mut self) -> Usage {
+        let live = LIVE.load(Ordering::Relaxed);
+        let peak = PEAK.swap(live, Ordering::Relaxed);
+        let usage = Usage {
+            peak_bytes: peak.saturating_sub(self.start),
+            end_bytes: live as isize - self.start as isize,
+        };
+        self.start = live;
+        usage
+    }
+}
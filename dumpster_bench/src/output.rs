@@ -0,0 +1,427 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Command-line options for the benchmarks, and the files their results are written to.
+
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process::Command,
+    str::FromStr,
+    thread::available_parallelism,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+    collections::{Split, Tally},
+    latency::Latency,
+    memory::{Usage, Window},
+};
+
+/// The usage message printed for `--help` or a bad argument.
+pub const USAGE: &str = "\
+usage: dumpster_bench [OPTIONS]
+
+  --out PATH        write results to PATH instead of standard output
+  --format FMT      write results as `csv` (the default) or as `json`, one object per line
+  --append          add to the end of PATH instead of replacing it
+  --iters N         make N operations in each single- and multi-threaded benchmark
+                    (default 1000000)
+  --runs N          run every benchmark N times (default 100)
+  --threads N,...   run multi-threaded benchmarks on each of these numbers of threads
+                    (default every number up to the available parallelism)
+  --seed N          seed the random workloads with N (default 12345)
+  --only NAME,...   only benchmark these backends
+  --skip NAME,...   do not benchmark these backends
+  --quick           shrink every benchmark and run it once, to check that it works";
+
+/// How much smaller `--quick` makes every workload.
+const QUICK_SCALE: usize = 1000;
+
+/// The column names of a CSV file of results, in the order [`Output::record`] writes them.
+const CSV_HEADER: &str = "name,test,n_threads,n_ops,duration_us,p50_ns,p99_ns,p999_ns,max_ns,\
+    peak_bytes,end_bytes,n_collections,collect_us,mutator_us,timestamp,revision,parallelism";
+
+#[derive(Serialize)]
+/// The result of running one benchmark.
+pub struct BenchmarkData {
+    /// The name of the garbage collector that was benchmarked.
+    pub name: &'static str,
+    /// The name of the benchmark.
+    pub test: &'static str,
+    /// The number of threads the benchmark ran on.
+    pub n_threads: usize,
+    /// The number of operations the benchmark performed.
+    pub n_ops: usize,
+    #[serde(rename = "duration_us", serialize_with = "micros")]
+    /// How long the benchmark took.
+    pub duration: Duration,
+    #[serde(flatten)]
+    /// The latencies of the individual operations, for benchmarks which measure them.
+    pub latency: Option<Latency>,
+}
+
+impl Display for BenchmarkData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},",
+            self.name,
+            self.test,
+            self.n_threads,
+            self.n_ops,
+            self.duration.as_micros()
+        )?;
+        match self.latency {
+            Some(l) => write!(f, "{},{},{},{}", l.p50_ns, l.p99_ns, l.p999_ns, l.max_ns),
+            None => write!(f, ",,,"),
+        }
+    }
+}
+
+/// Serialize a duration as a whole number of microseconds, like the CSV output does.
+fn micros<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_micros())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The format in which results are written.
+pub enum Format {
+    /// Comma-separated values, with a header row.
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+#[derive(Debug)]
+/// The options given to the benchmark binary on the command line.
+pub struct Args {
+    /// The file to write results to, or `None` for standard output.
+    pub out: Option<PathBuf>,
+    /// The format in which to write results.
+    pub format: Format,
+    /// Whether to add to the end of `out` instead of replacing it.
+    pub append: bool,
+    /// The number of operations in each single- and multi-threaded benchmark.
+    pub iters: usize,
+    /// The number of times to run every benchmark.
+    pub runs: usize,
+    /// The numbers of threads to run each multi-threaded benchmark on.
+    pub threads: Vec<usize>,
+    /// The seed of every random workload.
+    pub seed: u64,
+    /// The names of the only backends to benchmark, or `None` to benchmark all of them.
+    pub only: Option<Vec<String>>,
+    /// The names of the backends not to benchmark.
+    pub skip: Vec<String>,
+    /// How many times smaller every other workload is made than its usual size.
+    pub scale: usize,
+}
+
+impl Args {
+    /// Parse the command-line arguments which follow the name of the binary.
+    ///
+    /// `--quick` only changes the defaults, so `--iters`, `--runs` and `--threads` still apply
+    /// along with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message describing the problem if the arguments are not valid.
+    /// `--help` is reported as an error with an empty message.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut out = None;
+        let mut format = Format::Csv;
+        let mut append = false;
+        let mut iters = None;
+        let mut runs = None;
+        let mut threads = None;
+        let mut seed = 12345;
+        let mut only = None;
+        let mut skip = Vec::new();
+        let mut quick = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--out" => {
+                    let path = args.next().ok_or("--out requires a path")?;
+                    out = Some(PathBuf::from(path));
+                }
+                "--format" => {
+                    format = match args.next().as_deref() {
+                        Some("csv") => Format::Csv,
+                        Some("json") => Format::Json,
+                        Some(other) => return Err(format!("unknown format `{other}`")),
+                        None => return Err(String::from("--format requires `csv` or `json`")),
+                    };
+                }
+                "--append" => append = true,
+                "--iters" => iters = Some(number(&arg, args.next())?),
+                "--runs" => runs = Some(number(&arg, args.next())?),
+                "--threads" => {
+                    let list = list(&arg, args.next())?;
+                    let counts = list
+                        .iter()
+                        .map(|n| number(&arg, Some(n.clone())))
+                        .collect::<Result<Vec<usize>, _>>()?;
+                    if counts.contains(&0) {
+                        return Err(String::from("--threads requires positive numbers"));
+                    }
+                    threads = Some(counts);
+                }
+                "--seed" => seed = number(&arg, args.next())?,
+                "--only" => only = Some(list(&arg, args.next())?),
+                "--skip" => skip.extend(list(&arg, args.next())?),
+                "--quick" => quick = true,
+                "-h" | "--help" => return Err(String::new()),
+                other => return Err(format!("unknown argument `{other}`")),
+            }
+        }
+        if append && out.is_none() {
+            return Err(String::from("--append requires --out"));
+        }
+        let threads = threads.unwrap_or_else(|| {
+            let max = if quick {
+                2
+            } else {
+                available_parallelism().map_or(1, usize::from)
+            };
+            (1..=max).collect()
+        });
+        Ok(Args {
+            out,
+            format,
+            append,
+            iters: iters.unwrap_or(if quick { 10_000 } else { 1_000_000 }),
+            runs: runs.unwrap_or(if quick { 1 } else { 100 }),
+            threads,
+            seed,
+            only,
+            skip,
+            scale: if quick { QUICK_SCALE } else { 1 },
+        })
+    }
+
+    /// Get the size of a workload whose usual size is `n`, shrunk by `--quick`.
+    pub fn scaled(&self, n: usize) -> usize {
+        (n / self.scale).max(1)
+    }
+}
+
+/// Parse the value following the option `flag` as a number.
+fn number<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{flag} requires a number"))?;
+    value
+        .parse()
+        .map_err(|_| format!("{flag} requires a number, not `{value}`"))
+}
+
+/// Parse the value following the option `flag` as a comma-separated list.
+fn list(flag: &str, value: Option<String>) -> Result<Vec<String>, String> {
+    let value = value.ok_or_else(|| format!("{flag} requires a comma-separated list"))?;
+    Ok(value.split(',').map(String::from).collect())
+}
+
+/// The destination of benchmark results, which stamps every result with the memory taken up and the
+/// collections run while it was measured, and with enough information about the run to compare it
+/// against others later.
+pub struct Output {
+    /// Where the results are written.
+    writer: Box<dyn Write>,
+    /// The format in which results are written.
+    format: Format,
+    /// The git revision of the benchmarked code, if it could be found.
+    revision: Option<String>,
+    /// The number of threads the host can run in parallel.
+    parallelism: usize,
+    /// The window over which the memory taken up by the next result is measured, which starts
+    /// when the last result was written.
+    window: Window,
+    /// The count of the collections run by the next result, which starts when the last result was
+    /// written.
+    tally: Tally,
+    /// Whether the collections run by each result are written along with it.
+    counting: bool,
+}
+
+#[derive(Serialize)]
+/// A single result, as written to a JSON file.
+struct Record<'a> {
+    #[serde(flatten)]
+    /// The result itself.
+    data: &'a BenchmarkData,
+    /// The most bytes allocated at once while the result was measured, beyond those allocated
+    /// before.
+    peak_bytes: usize,
+    /// The bytes still allocated once the result was measured, beyond those allocated before.
+    end_bytes: isize,
+    #[serde(flatten)]
+    /// How the result's duration was split between collecting and everything else, if the
+    /// collections were counted.
+    collections: Option<Split>,
+    /// When the result was recorded, in seconds since the Unix epoch.
+    timestamp: u64,
+    /// The git revision of the benchmarked code.
+    revision: Option<&'a str>,
+    /// The number of threads the host can run in parallel.
+    parallelism: usize,
+}
+
+impl Output {
+    /// Open the destination described by `args`, writing a CSV header first unless results are
+    /// being appended to a file which already has some.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output file cannot be opened or written to.
+    pub fn open(args: &Args) -> io::Result<Output> {
+        let (writer, empty): (Box<dyn Write>, bool) = match &args.out {
+            None => (Box::new(io::stdout()), true),
+            Some(path) => {
+                let file = if args.append {
+                    OpenOptions::new().create(true).append(true).open(path)?
+                } else {
+                    File::create(path)?
+                };
+                let empty = file.metadata()?.len() == 0;
+                (Box::new(BufWriter::new(file)), empty)
+            }
+        };
+        let mut output = Output {
+            writer,
+            format: args.format,
+            revision: git_revision(),
+            parallelism: available_parallelism().map_or(1, usize::from),
+            window: Window::start(),
+            tally: Tally::start(),
+            counting: false,
+        };
+        if output.format == Format::Csv && empty {
+            writeln!(output.writer, "{CSV_HEADER}")?;
+        }
+        output.window.restart();
+        Ok(output)
+    }
+
+    /// Set whether the collections run by the next results are counted and written along with
+    /// them, which only makes sense for dumpster.
+    ///
+    /// The collections of results which are not counted are left empty.
+    pub fn count_collections(&mut self, counting: bool) {
+        self.counting = counting;
+        self.tally.restart();
+    }
+
+    /// Write one result, along with the memory taken up since the last result was written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result cannot be written, since there is no point in running the rest of the
+    /// benchmarks.
+    pub fn record(&mut self, data: &BenchmarkData) {
+        self.record_all(std::slice::from_ref(data));
+    }
+
+    /// Write several results measured by a single benchmark, all along with the memory taken up
+    /// since the last result was written.
+    ///
+    /// The results are flushed immediately, so that a long run which is cut short keeps everything
+    /// it measured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the results cannot be written, since there is no point in running the rest of the
+    /// benchmarks.
+    pub fn record_all(&mut self, results: &[BenchmarkData]) {
+        let usage = self.window.restart();
+        let counted = self.tally.restart();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        for data in results {
+            let collections = self.counting.then(|| counted.split(data.duration));
+            self.write(data, usage, collections, timestamp)
+                .expect("failed to write benchmark result");
+        }
+        self.writer
+            .flush()
+            .expect("failed to write benchmark result");
+        // writing the results allocates too, which should not count against the next benchmark
+        self.window.restart();
+    }
+
+    /// Write one result in the chosen format.
+    fn write(
+        &mut self,
+        data: &BenchmarkData,
+        usage: Usage,
+        collections: Option<Split>,
+        timestamp: u64,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Csv => {
+                write!(
+                    self.writer,
+                    "{data},{},{},",
+                    usage.peak_bytes, usage.end_bytes
+                )?;
+                match collections {
+                    Some(c) => write!(
+                        self.writer,
+                        "{},{},{}",
+                        c.n_collections, c.collect_us, c.mutator_us
+                    )?,
+                    None => write!(self.writer, ",,")?,
+                }
+                writeln!(
+                    self.writer,
+                    ",{timestamp},{},{}",
+                    self.revision.as_deref().unwrap_or(""),
+                    self.parallelism
+                )
+            }
+            Format::Json => {
+                let record = Record {
+                    data,
+                    peak_bytes: usage.peak_bytes,
+                    end_bytes: usage.end_bytes,
+                    collections,
+                    timestamp,
+                    revision: self.revision.as_deref(),
+                    parallelism: self.parallelism,
+                };
+                serde_json::to_writer(&mut self.writer, &record)?;
+                writeln!(self.writer)
+            }
+        }
+    }
+}
+
+/// Get the git revision of the working directory, marked as dirty if it has uncommitted changes, or
+/// `None` if git is unavailable or this is not a repository.
+fn git_revision() -> Option<String> {
+    let output = Command::new("git")
+        .args(["describe", "--always", "--dirty", "--abbrev=12"])
+        .output()
+        .ok()?;
+    let revision = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !revision.trim().is_empty()).then(|| revision.trim().to_owned())
+}
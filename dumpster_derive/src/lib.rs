@@ -20,76 +20,491 @@
 #![warn(clippy::cargo)]
 #![allow(clippy::multiple_crate_versions)]
 
+use std::collections::{HashMap, HashSet};
+
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote, quote_spanned};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
 use syn::{
-    parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Fields, GenericParam,
-    Generics, Ident, Index,
+    ext::IdentExt,
+    meta::ParseNestedMeta,
+    parse_macro_input, parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    visit::{self, Visit},
+    Data, DeriveInput, Field, Fields, GenericParam, Generics, Ident, Index, LitStr, Path,
+    PathSegment, Token, Type, TypePath, TypeReference, WherePredicate,
 };
 
-#[proc_macro_derive(Collectable)]
+#[proc_macro_derive(Collectable, attributes(collectable))]
 pub fn derive_collectable(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     // name of the type being implemented
     let name = &input.ident;
 
+    if let Data::Union(u) = &input.data {
+        return syn::Error::new_spanned(
+            u.union_token,
+            "`Collectable` cannot be derived for unions",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let options = match Options::parse(&input) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
     // generic parameters of the type being implemented
-    let generics = add_trait_bounds(input.generics);
+    let generics = add_trait_bounds(input.generics.clone(), &input.data, &options);
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let do_visitor = delegate_methods(name, &input.data);
+    let do_visitor = delegate_methods(name, &input.data, &options);
+    let krate = &options.krate;
+
+    // Assert that every traced field is `Collectable`, so that an error for a field which isn't
+    // points at that field's type instead of at the derive.
+    let field_assertions = fields_of(&input.data)
+        .into_iter()
+        .filter(|f| {
+            let id = std::ptr::from_ref(*f);
+            !options.skipped.contains(&id) && !options.with.contains_key(&id)
+        })
+        .map(|f| {
+            let ty = &f.ty;
+            quote_spanned! {ty.span() => __assert_collectable::<#ty>();}
+        });
+
+    let is_leaf = leaf_flag(name, &input.data, &options);
+
+    let skip_note = (!options.skipped.is_empty()).then(|| {
+        let note = format!(
+            "Derived with {} field(s) marked `#[collectable(unsafe_skip)]`, which are never \
+             traced.",
+            options.skipped.len()
+        );
+        quote! { #[doc = #note] }
+    });
+
+    let finalize = options.noop_finalize.then(|| {
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        quote! {
+            impl #impl_generics #krate::Finalize for #name #ty_generics #where_clause {
+                #[inline]
+                fn finalize(&self) {}
+            }
+        }
+    });
 
     let generated = quote! {
-        unsafe impl #impl_generics dumpster::Collectable for #name #ty_generics #where_clause {
+        #skip_note
+        unsafe impl #impl_generics #krate::Collectable for #name #ty_generics #where_clause {
             #[inline]
-            fn accept<V: dumpster::Visitor>(&self, visitor: &mut V) -> std::result::Result<(), ()> {
+            fn accept<__V: #krate::Visitor>(&self, visitor: &mut __V) -> std::result::Result<(), ()> {
+                fn __assert_collectable<T: #krate::Collectable + ?std::marker::Sized>() {}
+                #(#field_assertions)*
+
                 #do_visitor
             }
+
+            const IS_LEAF: bool = #is_leaf;
         }
+
+        #finalize
     };
 
     generated.into()
 }
 
-/// Collect the trait bounds for some generic expression.
-fn add_trait_bounds(mut generics: Generics) -> Generics {
+/// The options given to the derive through `#[collectable(...)]` attributes.
+struct Options {
+    /// The path to the `dumpster` crate, which may be changed with
+    /// `#[collectable(crate = "...")]` if `dumpster` is re-exported under another name.
+    krate: Path,
+    /// Bounds which replace all the inferred bounds of the implementation, given by
+    /// `#[collectable(bound = "...")]` on the type itself.
+    bound: Option<Vec<WherePredicate>>,
+    /// The fields marked with `#[collectable(unsafe_skip)]`, which the user has promised contain
+    /// no `Gc`s.
+    ///
+    /// Each field is identified by its address, since fields have no other unique identity across
+    /// structs and enum variants.
+    skipped: HashSet<*const Field>,
+    /// Bounds which replace the bounds inferred from a single field, given by
+    /// `#[collectable(bound = "...")]` on that field.
+    field_bounds: HashMap<*const Field, Vec<WherePredicate>>,
+    /// Modules whose `accept` function is used to visit a field instead of
+    /// `Collectable::accept`, given by `#[collectable(unsafe_with = "...")]` on that field.
+    with: HashMap<*const Field, Path>,
+    /// Whether to also implement `Finalize` with a finalizer that does nothing, as requested by
+    /// `#[collectable(noop_finalize)]` on the type itself.
+    noop_finalize: bool,
+    /// Whether the type is a leaf whenever all its traced fields are, as requested by
+    /// `#[collectable(leaf)]` on the type itself.
+    leaf: bool,
+}
+
+impl Options {
+    /// Parse all the `collectable` attributes on a type and its fields.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if a `collectable` attribute is malformed, or if a field
+    /// which plainly contains a `Gc` is skipped.
+    fn parse(input: &DeriveInput) -> syn::Result<Options> {
+        let mut options = Options {
+            krate: parse_quote!(dumpster),
+            bound: None,
+            skipped: HashSet::new(),
+            field_bounds: HashMap::new(),
+            with: HashMap::new(),
+            noop_finalize: false,
+            leaf: false,
+        };
+
+        for attr in &input.attrs {
+            if !attr.path().is_ident("collectable") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("bound") {
+                    options.bound = Some(parse_bound(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("crate") {
+                    let krate: LitStr = meta.value()?.parse()?;
+                    options.krate = krate.parse()?;
+                    Ok(())
+                } else if meta.path.is_ident("noop_finalize") {
+                    options.noop_finalize = true;
+                    Ok(())
+                } else if meta.path.is_ident("leaf") {
+                    options.leaf = true;
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unknown `collectable` attribute; expected `bound`, `crate`, \
+                         `noop_finalize` or `leaf`",
+                    ))
+                }
+            })?;
+        }
+
+        for field in fields_of(&input.data) {
+            let id = std::ptr::from_ref(field);
+            for attr in &field.attrs {
+                if !attr.path().is_ident("collectable") {
+                    continue;
+                }
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("unsafe_skip") {
+                        if mentions_gc(&field.ty) {
+                            return Err(syn::Error::new_spanned(
+                                &field.ty,
+                                "a field containing a `Gc` cannot be skipped by \
+                                 `#[collectable(unsafe_skip)]`",
+                            ));
+                        }
+                        options.skipped.insert(id);
+                        Ok(())
+                    } else if meta.path.is_ident("bound") {
+                        options.field_bounds.insert(id, parse_bound(&meta)?);
+                        Ok(())
+                    } else if meta.path.is_ident("unsafe_with") {
+                        let with: LitStr = meta.value()?.parse()?;
+                        options.with.insert(id, with.parse()?);
+                        Ok(())
+                    } else {
+                        Err(meta.error(
+                            "unknown `collectable` attribute; expected `unsafe_skip`, `bound` or \
+                             `unsafe_with`",
+                        ))
+                    }
+                })?;
+            }
+            if options.skipped.contains(&id) && options.with.contains_key(&id) {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "a field cannot have both `unsafe_skip` and `unsafe_with`",
+                ));
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Parse the value of a `bound = "..."` attribute as a list of `where` predicates.
+fn parse_bound(meta: &ParseNestedMeta) -> syn::Result<Vec<WherePredicate>> {
+    let bound: LitStr = meta.value()?.parse()?;
+    Ok(bound
+        .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?
+        .into_iter()
+        .collect())
+}
+
+/// Determine whether a type syntactically contains a `Gc`.
+fn mentions_gc(ty: &Type) -> bool {
+    mentions_segment(ty, |ident| ident == "Gc")
+}
+
+/// Determine whether a type syntactically contains a path with a segment for which `f` returns
+/// `true`.
+fn mentions_segment(ty: &Type, f: impl Fn(&Ident) -> bool) -> bool {
+    /// A visitor which checks whether a type mentions a path with a matching segment.
+    struct Mentions<F>(F, bool);
+
+    impl<'ast, F: Fn(&Ident) -> bool> Visit<'ast> for Mentions<F> {
+        fn visit_path_segment(&mut self, segment: &'ast PathSegment) {
+            self.1 |= (self.0)(&segment.ident);
+            visit::visit_path_segment(self, segment);
+        }
+    }
+
+    let mut mentions = Mentions(f, false);
+    mentions.visit_type(ty);
+    mentions.1
+}
+
+/// Generate the value of `Collectable::IS_LEAF` for a data type.
+///
+/// Only a type marked with `#[collectable(leaf)]` can be a leaf, in which case the flag is the
+/// conjunction of the flags of all its traced fields.
+/// Computing it for every type would be a const-evaluation cycle for types which contain each
+/// other.
+/// A field whose type names the type being derived would make the flag depend on itself, so such
+/// a type is never a leaf, and neither is a type with a field visited by an `unsafe_with` module.
+fn leaf_flag(name: &Ident, data: &Data, options: &Options) -> TokenStream {
+    let krate = &options.krate;
+    if !options.leaf {
+        return quote! { false };
+    }
+    let mut flags = Vec::new();
+    for field in fields_of(data) {
+        let id = std::ptr::from_ref(field);
+        if options.skipped.contains(&id) {
+            continue;
+        }
+        let ty = &field.ty;
+        if options.with.contains_key(&id)
+            || mentions_segment(ty, |ident| ident == name || ident == "Self")
+        {
+            return quote! { false };
+        }
+        flags.push(quote! { <#ty as #krate::Collectable>::IS_LEAF });
+    }
+    if flags.is_empty() {
+        quote! { true }
+    } else {
+        quote! { #(#flags)&&* }
+    }
+}
+
+/// Add `Collectable` bounds to the generic parameters of a type, based on its fields.
+///
+/// Only the type parameters which are actually used in the type of some field are bounded.
+/// Parameters which appear only inside of a `PhantomData` or behind a shared reference are left
+/// alone, and associated-type projections such as `T::Item` are bounded directly instead of
+/// requiring `T: Collectable`.
+/// Fields skipped with `#[collectable(unsafe_skip)]` or visited with
+/// `#[collectable(unsafe_with = "...")]` impose no bounds.
+/// Any `where` clause written by the user is kept as-is.
+///
+/// A `#[collectable(bound = "...")]` attribute replaces the inferred bounds: on the type itself it
+/// replaces all of them, and on a field it replaces those inferred from that field.
+fn add_trait_bounds(mut generics: Generics, data: &Data, options: &Options) -> Generics {
+    let krate = &options.krate;
+    if let Some(bound) = &options.bound {
+        generics
+            .make_where_clause()
+            .predicates
+            .extend(bound.iter().cloned());
+        return generics;
+    }
+
+    let mut finder = BoundFinder {
+        params: generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect(),
+        used: HashSet::new(),
+        projections: Vec::new(),
+    };
+    for field in fields_of(data) {
+        let id = std::ptr::from_ref(field);
+        if !options.skipped.contains(&id)
+            && !options.with.contains_key(&id)
+            && !options.field_bounds.contains_key(&id)
+        {
+            finder.visit_type(&field.ty);
+        }
+    }
+
     for param in &mut generics.params {
         if let GenericParam::Type(ref mut type_param) = *param {
-            type_param.bounds.push(parse_quote!(heapsize::HeapSize));
+            if finder.used.contains(&type_param.ident) {
+                type_param.bounds.push(parse_quote!(#krate::Collectable));
+            }
+        }
+    }
+    let where_clause = generics.make_where_clause();
+    for ty in finder.projections {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: #krate::Collectable));
+    }
+    for field in fields_of(data) {
+        if let Some(bound) = options.field_bounds.get(&std::ptr::from_ref(field)) {
+            where_clause.predicates.extend(bound.iter().cloned());
         }
     }
     generics
 }
 
+/// Get every field of a data type, across all of its variants.
+fn fields_of(data: &Data) -> Vec<&Field> {
+    match data {
+        Data::Struct(data) => data.fields.iter().collect(),
+        Data::Enum(data) => data.variants.iter().flat_map(|v| v.fields.iter()).collect(),
+        Data::Union(data) => data.fields.named.iter().collect(),
+    }
+}
+
+/// A visitor over field types which finds out which generic parameters need bounds.
+struct BoundFinder {
+    /// The names of all the type parameters of the type being derived.
+    params: HashSet<Ident>,
+    /// The type parameters which are used directly in some field.
+    used: HashSet<Ident>,
+    /// Associated-type projections on type parameters, such as `T::Item`, which are used in some
+    /// field.
+    projections: Vec<TypePath>,
+}
+
+impl BoundFinder {
+    /// Determine whether a type mentions any of the type parameters.
+    fn mentions_param(&self, ty: &Type) -> bool {
+        /// A visitor which checks whether a type mentions some type parameter.
+        struct Mentions<'a>(&'a HashSet<Ident>, bool);
+
+        impl<'ast> Visit<'ast> for Mentions<'_> {
+            fn visit_path(&mut self, path: &'ast Path) {
+                if path.leading_colon.is_none()
+                    && path
+                        .segments
+                        .first()
+                        .is_some_and(|s| self.0.contains(&s.ident))
+                {
+                    self.1 = true;
+                }
+                visit::visit_path(self, path);
+            }
+        }
+
+        let mut mentions = Mentions(&self.params, false);
+        mentions.visit_type(ty);
+        mentions.1
+    }
+
+    /// Record that a projection type must be bounded, unless it already has been.
+    fn add_projection(&mut self, ty: &TypePath) {
+        let text = ty.to_token_stream().to_string();
+        if !self
+            .projections
+            .iter()
+            .any(|p| p.to_token_stream().to_string() == text)
+        {
+            self.projections.push(ty.clone());
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for BoundFinder {
+    fn visit_type_reference(&mut self, _: &'ast TypeReference) {
+        // a shared reference is collectable no matter what it points to
+    }
+
+    fn visit_type_path(&mut self, ty: &'ast TypePath) {
+        if let Some(qself) = &ty.qself {
+            // a qualified projection such as `<T as Trait>::Assoc`
+            if self.mentions_param(&qself.ty) {
+                self.add_projection(ty);
+                return;
+            }
+        } else if ty.path.leading_colon.is_none() {
+            if let Some(first) = ty.path.segments.first() {
+                if self.params.contains(&first.ident) {
+                    if ty.path.segments.len() == 1 {
+                        self.used.insert(first.ident.clone());
+                    } else {
+                        // an associated type projection such as `T::Item`
+                        self.add_projection(ty);
+                    }
+                    return;
+                }
+            }
+        }
+
+        if ty
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == "PhantomData")
+        {
+            // `PhantomData<T>` is collectable no matter what `T` is
+            return;
+        }
+
+        visit::visit_type_path(self, ty);
+    }
+}
+
 #[allow(clippy::too_many_lines)]
 /// Generate method implementations for [`Collectable`] for some data type.
-fn delegate_methods(name: &Ident, data: &Data) -> TokenStream {
+fn delegate_methods(name: &Ident, data: &Data, options: &Options) -> TokenStream {
+    let krate = &options.krate;
+    let is_skipped = |f: &Field| options.skipped.contains(&std::ptr::from_ref(f));
+    // the function which accepts a visitor for a field
+    let accept_fn = |f: &Field| {
+        options.with.get(&std::ptr::from_ref(f)).map_or_else(
+            || quote! { #krate::Collectable::accept },
+            |with| quote! { #with::accept },
+        )
+    };
+    // accept a visitor for a field, telling the visitor which field it is in
+    let visit_field = |f: &Field, label: &str, value: TokenStream| {
+        let accept = accept_fn(f);
+        quote_spanned! {f.span() =>
+            #krate::Visitor::enter_field(visitor, #label);
+            #accept(#value, visitor)?;
+            #krate::Visitor::exit_field(visitor);
+        }
+    };
+
     match data {
         Data::Struct(data) => match data.fields {
             Fields::Named(ref f) => {
-                let delegate_visit = f.named.iter().map(|f| {
-                    let name = &f.ident;
-                    quote_spanned! {f.span() =>
-                        dumpster::Collectable::accept(
-                            &self.#name,
-                            visitor
-                        )?;
-                    }
+                let delegate_visit = f.named.iter().filter(|f| !is_skipped(f)).map(|f| {
+                    let name = f.ident.as_ref().unwrap();
+                    visit_field(
+                        f,
+                        &name.unraw().to_string(),
+                        quote_spanned! {f.span() => &self.#name},
+                    )
                 });
 
                 quote! { #(#delegate_visit)* std::result::Result::Ok(()) }
             }
             Fields::Unnamed(ref f) => {
-                let delegate_visit = f.unnamed.iter().enumerate().map(|(i, f)| {
-                    let index = Index::from(i);
-                    quote_spanned! {f.span() =>
-                        dumpster::Collectable::accept(
-                            &self.#index,
-                            visitor
-                        )?;
-                    }
-                });
+                let delegate_visit = f
+                    .unnamed
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, f)| !is_skipped(f))
+                    .map(|(i, f)| {
+                        let index = Index::from(i);
+                        visit_field(f, &i.to_string(), quote_spanned! {f.span() => &self.#index})
+                    });
 
                 quote! { #(#delegate_visit)* std::result::Result::Ok(()) }
             }
@@ -99,14 +514,18 @@ fn delegate_methods(name: &Ident, data: &Data) -> TokenStream {
             let mut delegate_visit = TokenStream::new();
             for var in &e.variants {
                 let var_name = &var.ident;
+                let var_label = var_name.unraw().to_string();
 
                 match &var.fields {
                     Fields::Named(n) => {
                         let mut binding = TokenStream::new();
                         let mut execution_visit = TokenStream::new();
-                        let mut execution_destroy = TokenStream::new();
                         for (i, name) in n.named.iter().enumerate() {
-                            let field_name = format_ident!("field{i}");
+                            let field_name = if is_skipped(name) {
+                                format_ident!("_")
+                            } else {
+                                format_ident!("field{i}")
+                            };
                             let field_ident = name.ident.as_ref().unwrap();
                             if i == 0 {
                                 binding.extend(quote! {
@@ -118,30 +537,31 @@ fn delegate_methods(name: &Ident, data: &Data) -> TokenStream {
                                 });
                             }
 
-                            execution_visit.extend(quote! {
-                                dumpster::Collectable::accept(
-                                    #field_name,
-                                    visitor
-                                )?;
-                            });
-
-                            execution_destroy.extend(quote! {
-                                dumpster::Collectable::destroy_gcs(
-                                    #field_name, destroyer
-                                );
-                            });
+                            if field_name != "_" {
+                                execution_visit.extend(visit_field(
+                                    name,
+                                    &field_ident.unraw().to_string(),
+                                    quote! { #field_name },
+                                ));
+                            }
                         }
 
-                        delegate_visit.extend(
-                            quote! {#name::#var_name{#binding} => {#execution_visit std::result::Result::Ok(())},},
-                        );
+                        delegate_visit.extend(quote! {#name::#var_name{#binding} => {
+                            #krate::Visitor::enter_variant(visitor, #var_label);
+                            #execution_visit
+                            #krate::Visitor::exit_variant(visitor);
+                            std::result::Result::Ok(())
+                        },});
                     }
                     Fields::Unnamed(u) => {
                         let mut binding = TokenStream::new();
                         let mut execution_visit = TokenStream::new();
-                        let mut execution_destroy = TokenStream::new();
-                        for (i, _) in u.unnamed.iter().enumerate() {
-                            let field_name = format_ident!("field{i}");
+                        for (i, field) in u.unnamed.iter().enumerate() {
+                            let field_name = if is_skipped(field) {
+                                format_ident!("_")
+                            } else {
+                                format_ident!("field{i}")
+                            };
                             if i == 0 {
                                 binding.extend(quote! {
                                     #field_name
@@ -152,35 +572,39 @@ fn delegate_methods(name: &Ident, data: &Data) -> TokenStream {
                                 });
                             }
 
-                            execution_visit.extend(quote! {
-                                dumpster::Collectable::accept(
-                                    #field_name,
-                                    visitor
-                                )?;
-                            });
-
-                            execution_destroy.extend(quote! {
-                                dumpster::Collectable::destroy_gcs(#field_name, destroyer);
-                            });
+                            if field_name != "_" {
+                                execution_visit.extend(visit_field(
+                                    field,
+                                    &i.to_string(),
+                                    quote! { #field_name },
+                                ));
+                            }
                         }
 
-                        delegate_visit.extend(
-                            quote! {#name::#var_name(#binding) => {#execution_visit std::result::Result::Ok(())},},
-                        );
+                        delegate_visit.extend(quote! {#name::#var_name(#binding) => {
+                            #krate::Visitor::enter_variant(visitor, #var_label);
+                            #execution_visit
+                            #krate::Visitor::exit_variant(visitor);
+                            std::result::Result::Ok(())
+                        },});
                     }
                     Fields::Unit => {
-                        delegate_visit
-                            .extend(quote! {#name::#var_name => std::result::Result::Ok(()),});
+                        delegate_visit.extend(quote! {#name::#var_name => {
+                            #krate::Visitor::enter_variant(visitor, #var_label);
+                            #krate::Visitor::exit_variant(visitor);
+                            std::result::Result::Ok(())
+                        },});
                     }
                 }
             }
 
-            quote! {match self {#delegate_visit}}
-        }
-        Data::Union(u) => {
-            quote_spanned! {
-                u.union_token.span => compile_error!("`Collectable` must be manually implemented for unions");
+            if e.variants.is_empty() {
+                // an empty enum can never be constructed, so `self` can never be matched
+                quote! { match *self {} }
+            } else {
+                quote! { match self {#delegate_visit} }
             }
         }
+        Data::Union(_) => unreachable!("unions are rejected before generating an implementation"),
     }
 }
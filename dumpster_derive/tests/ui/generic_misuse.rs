@@ -0,0 +1,24 @@
+use std::marker::PhantomData;
+
+use dumpster::Collectable;
+
+struct NotCollectable;
+
+#[derive(Collectable)]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[derive(Collectable)]
+struct Marker<T> {
+    marker: PhantomData<T>,
+}
+
+fn assert_collectable<T: Collectable>() {}
+
+fn main() {
+    // fine: `T` only appears in a `PhantomData`
+    assert_collectable::<Marker<NotCollectable>>();
+    // error: `T` is traced, so it must be `Collectable`
+    assert_collectable::<Wrapper<NotCollectable>>();
+}
@@ -0,0 +1,11 @@
+use dumpster::{unsync::Gc, Collectable};
+
+struct NotCollectable;
+
+#[derive(Collectable)]
+struct Node {
+    next: Option<Gc<Node>>,
+    data: NotCollectable,
+}
+
+fn main() {}
@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+
+use dumpster::{
+    unsync::{collect, Gc},
+    Collectable,
+};
+
+#[derive(Collectable)]
+struct A {
+    b: Option<Box<B>>,
+    g: Option<Gc<C>>,
+}
+
+#[derive(Collectable)]
+struct B {
+    a: Option<Box<A>>,
+}
+
+#[derive(Collectable)]
+struct C {
+    list: RefCell<Option<Gc<Vec<A>>>>,
+}
+
+fn main() {
+    let c = Gc::new(C {
+        list: RefCell::new(None),
+    });
+    let list = Gc::new(vec![A {
+        b: Some(Box::new(B {
+            a: Some(Box::new(A { b: None, g: None })),
+        })),
+        g: Some(c.clone()),
+    }]);
+    *c.list.borrow_mut() = Some(list);
+    drop(c);
+    collect();
+}
@@ -0,0 +1,9 @@
+use dumpster::{unsync::Gc, Collectable};
+
+#[derive(Collectable)]
+struct Node {
+    #[collectable(unsafe_skip)]
+    next: Option<Gc<Node>>,
+}
+
+fn main() {}
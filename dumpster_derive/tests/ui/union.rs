@@ -0,0 +1,9 @@
+use dumpster::Collectable;
+
+#[derive(Collectable)]
+union Bits {
+    int: u32,
+    float: f32,
+}
+
+fn main() {}
@@ -0,0 +1,71 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A stand-in for a framework which re-exports `dumpster` under its own name.
+//!
+//! This crate has no dependency named `dumpster`, so deriving `Collectable` only works if the
+//! derive is told where to find the crate with `#[collectable(crate = "...")]`.
+
+#![warn(clippy::pedantic)]
+
+/// The garbage collector used by this "framework".
+pub use dumpster_renamed as gc;
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::gc::{unsync::Gc, Collectable};
+
+    #[derive(Collectable)]
+    #[collectable(crate = "crate::gc")]
+    #[allow(dead_code)]
+    enum Shape<T: Collectable + 'static> {
+        Point(T),
+        Group(Vec<Gc<Shape<T>>>),
+    }
+
+    #[test]
+    fn derive_through_reexport() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Collectable)]
+        #[collectable(crate = "crate::gc")]
+        struct Node {
+            next: RefCell<Option<Gc<Node>>>,
+        }
+
+        impl Drop for Node {
+            fn drop(&mut self) {
+                COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let node = Gc::new(Node {
+            next: RefCell::new(None),
+        });
+        *node.next.borrow_mut() = Some(node.clone());
+
+        drop(node);
+        crate::gc::unsync::collect();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    }
+}
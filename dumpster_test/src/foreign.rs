@@ -0,0 +1,1082 @@
+/*
+   dumpster, a cycle-tracking garbage collector for Rust.
+   Copyright (C) 2023 Clayton Ramsey.
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Tests for implementations of `Collectable` on types from other crates.
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    },
+};
+
+use arcstr::{ArcStr, Substr};
+use arrayvec::{ArrayString, ArrayVec};
+use bytes::{Bytes, BytesMut};
+use compact_str::CompactString;
+use dashmap::{DashMap, DashSet};
+use dumpster::{
+    sync,
+    unsync::{collect, Gc},
+};
+use dumpster_derive::Collectable;
+use either::Either;
+use generational_arena::{Arena, Index};
+use im_rc::{HashMap, HashSet, OrdMap, OrdSet, Vector};
+use indexmap::{IndexMap, IndexSet};
+use parking_lot::{FairMutex, Mutex, ReentrantMutex, RwLock};
+use serde_json::{json, Map, Number, Value};
+use slab::Slab;
+use slotmap::{DefaultKey, DenseSlotMap, SecondaryMap, SlotMap, SparseSecondaryMap};
+use smol_str::SmolStr;
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Indexed {
+    by_name: IndexMap<String, Gc<Indexed>>,
+    seen: IndexSet<u32>,
+}
+
+#[test]
+fn indexmap_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        children: RefCell<IndexMap<String, Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let root = Gc::new(Node {
+        children: RefCell::new(IndexMap::new()),
+    });
+    let child = Gc::new(Node {
+        children: RefCell::new(IndexMap::new()),
+    });
+    root.children
+        .borrow_mut()
+        .insert("child".into(), child.clone());
+    child
+        .children
+        .borrow_mut()
+        .insert("parent".into(), root.clone());
+    child
+        .children
+        .borrow_mut()
+        .insert("self".into(), child.clone());
+
+    drop(root);
+    drop(child);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Inline {
+    name: ArrayString<16>,
+    neighbors: ArrayVec<Gc<Inline>, 4>,
+}
+
+#[test]
+fn arrayvec_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        edges: RefCell<ArrayVec<Gc<Node>, 8>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let a = Gc::new(Node {
+        edges: RefCell::new(ArrayVec::new()),
+    });
+    let b = Gc::new(Node {
+        edges: RefCell::new(ArrayVec::new()),
+    });
+    // only part of the capacity is initialized
+    a.edges.borrow_mut().push(b.clone());
+    b.edges.borrow_mut().push(a.clone());
+    b.edges.borrow_mut().push(b.clone());
+
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn either_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Expr {
+        operand: RefCell<Option<Either<Gc<Expr>, Gc<Literal>>>>,
+    }
+
+    #[derive(Collectable)]
+    struct Literal {
+        parent: RefCell<Option<Gc<Expr>>>,
+    }
+
+    impl Drop for Expr {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    impl Drop for Literal {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // cycle through the left arm
+    let expr = Gc::new(Expr {
+        operand: RefCell::new(None),
+    });
+    *expr.operand.borrow_mut() = Some(Either::Left(expr.clone()));
+    drop(expr);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+
+    // cycle through the right arm
+    let literal = Gc::new(Literal {
+        parent: RefCell::new(None),
+    });
+    let expr = Gc::new(Expr {
+        operand: RefCell::new(Some(Either::Right(literal.clone()))),
+    });
+    *literal.parent.borrow_mut() = Some(expr.clone());
+    drop(literal);
+    drop(expr);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Locks {
+    mutex: Mutex<Option<sync::Gc<Locks>>>,
+    fair: FairMutex<Vec<sync::Gc<Locks>>>,
+    rw: RwLock<Option<sync::Gc<Locks>>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Reentrant {
+    inner: ReentrantMutex<Option<Gc<Reentrant>>>,
+}
+
+#[test]
+fn parking_lot_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        next: Mutex<Option<sync::Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let node = sync::Gc::new(Node {
+        next: Mutex::new(None),
+    });
+    *node.next.lock() = Some(node.clone());
+
+    let (locked_tx, locked_rx) = channel();
+    let (release_tx, release_rx) = channel::<()>();
+    let other = node.clone();
+    let handle = std::thread::spawn(move || {
+        let guard = other.next.lock();
+        locked_tx.send(()).unwrap();
+        release_rx.recv().unwrap();
+        drop(guard);
+    });
+
+    locked_rx.recv().unwrap();
+    drop(node);
+    // the lock is held by another thread, so collecting must not block or free the node
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+
+    release_tx.send(()).unwrap();
+    handle.join().unwrap();
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Actor {
+    state: tokio::sync::Mutex<Vec<sync::Gc<Actor>>>,
+    config: tokio::sync::RwLock<Option<sync::Gc<Actor>>>,
+    parent: tokio::sync::OnceCell<sync::Gc<Actor>>,
+    mailbox: tokio::sync::mpsc::Sender<sync::Gc<Actor>>,
+    status: tokio::sync::watch::Receiver<u32>,
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tokio_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        peers: tokio::sync::Mutex<Vec<sync::Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let a = sync::Gc::new(Node {
+        peers: tokio::sync::Mutex::new(Vec::new()),
+    });
+    let b = sync::Gc::new(Node {
+        peers: tokio::sync::Mutex::new(Vec::new()),
+    });
+
+    // form the cycle from two separate tasks, handing the spare handles back so that they are not
+    // dropped on a worker thread (whose dumpster would keep them rooted)
+    let (a1, b1) = (a.clone(), b.clone());
+    let (a2, b2) = (a.clone(), b.clone());
+    let t1 = tokio::spawn(async move {
+        a1.peers.lock().await.push(b1);
+        a1
+    });
+    let t2 = tokio::spawn(async move {
+        b2.peers.lock().await.push(a2);
+        b2
+    });
+    drop(t1.await.unwrap());
+    drop(t2.await.unwrap());
+
+    // hold a lock while collecting to make sure the collector doesn't block on it
+    let guard = a.peers.lock().await;
+    sync::collect();
+    drop(guard);
+    assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+
+    drop(a);
+    drop(b);
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[derive(Collectable, Clone)]
+#[allow(dead_code)]
+struct Persistent {
+    list: Vector<u64>,
+    by_name: HashMap<String, u32>,
+    tags: HashSet<u32>,
+    by_id: OrdMap<u32, String>,
+    ids: OrdSet<u32>,
+    next: RefCell<Option<Gc<Persistent>>>,
+}
+
+#[test]
+fn im_rc_shared_structure() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        ids: Vector<u64>,
+        this: RefCell<Option<Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let ids = (0..200).collect::<Vector<u64>>();
+    let a = Gc::new(Node {
+        ids: ids.clone(),
+        this: RefCell::new(None),
+    });
+    *a.this.borrow_mut() = Some(a.clone());
+
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+    assert_eq!(a.ids, ids);
+
+    drop(a);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    assert_eq!(ids.len(), 200);
+}
+
+#[test]
+fn im_sync_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        by_id: im::OrdMap<u32, String>,
+        peer: std::sync::Mutex<Option<sync::Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let names = im::OrdMap::unit(0, String::from("shared"));
+    let a = sync::Gc::new(Node {
+        by_id: names.clone(),
+        peer: std::sync::Mutex::new(None),
+    });
+    let b = sync::Gc::new(Node {
+        by_id: names.clone(),
+        peer: std::sync::Mutex::new(Some(a.clone())),
+    });
+    *a.peer.lock().unwrap() = Some(b.clone());
+
+    drop(a);
+    drop(b);
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+    assert_eq!(names[&0], "shared");
+}
+
+slotmap::new_key_type! {
+    struct EntityKey;
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Slots {
+    dense: DenseSlotMap<EntityKey, Gc<Slots>>,
+    secondary: SecondaryMap<EntityKey, Gc<Slots>>,
+    sparse: SparseSecondaryMap<DefaultKey, Gc<Slots>>,
+    key: DefaultKey,
+}
+
+#[test]
+fn slotmap_remove_breaks_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        links: RefCell<SlotMap<EntityKey, Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let root = Gc::new(Node {
+        links: RefCell::new(SlotMap::with_key()),
+    });
+    let child = Gc::new(Node {
+        links: RefCell::new(SlotMap::with_key()),
+    });
+    let key = root.links.borrow_mut().insert(child.clone());
+    child.links.borrow_mut().insert(root.clone());
+    drop(child);
+
+    root.links.borrow_mut().remove(key);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+
+    drop(root);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn slotmap_world_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct World {
+        entities: RefCell<SlotMap<DefaultKey, Gc<Entity>>>,
+    }
+
+    #[derive(Collectable)]
+    struct Entity {
+        world: Gc<World>,
+    }
+
+    impl Drop for Entity {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let world = Gc::new(World {
+        entities: RefCell::new(SlotMap::new()),
+    });
+    for _ in 0..3 {
+        let entity = Gc::new(Entity {
+            world: world.clone(),
+        });
+        world.entities.borrow_mut().insert(entity);
+    }
+
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+
+    drop(world);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Frame {
+    header: Bytes,
+    payload: BytesMut,
+    next: Option<Gc<Frame>>,
+}
+
+#[test]
+fn bytes_session_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Session {
+        inbox: RefCell<BytesMut>,
+        last_sent: RefCell<Bytes>,
+        peer: RefCell<Option<Gc<Session>>>,
+    }
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let new_session = || {
+        Gc::new(Session {
+            inbox: RefCell::new(BytesMut::with_capacity(64)),
+            last_sent: RefCell::new(Bytes::new()),
+            peer: RefCell::new(None),
+        })
+    };
+    let client = new_session();
+    let server = new_session();
+    *client.peer.borrow_mut() = Some(server.clone());
+    *server.peer.borrow_mut() = Some(client.clone());
+
+    let hello = Bytes::from_static(b"hello");
+    *client.last_sent.borrow_mut() = hello.clone();
+    server.inbox.borrow_mut().extend_from_slice(&hello);
+
+    drop(client);
+    drop(server);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+    assert_eq!(hello, "hello");
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Script {
+    source: Value,
+    globals: Map<String, Value>,
+    version: Number,
+    parent: Option<Gc<Script>>,
+}
+
+#[test]
+fn serde_json_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Object {
+        data: Value,
+        other: RefCell<Option<Gc<Object>>>,
+    }
+
+    impl Drop for Object {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// A visitor which panics if it is ever called.
+    struct NoVisits;
+
+    impl dumpster::Visitor for NoVisits {
+        fn visit_sync<T>(&mut self, _: &sync::Gc<T>)
+        where
+            T: dumpster::Collectable + Send + Sync + ?Sized,
+        {
+            unreachable!();
+        }
+
+        fn visit_unsync<T>(&mut self, _: &Gc<T>)
+        where
+            T: dumpster::Collectable + ?Sized,
+        {
+            unreachable!();
+        }
+    }
+
+    let document = Value::Array(
+        (0..10_000)
+            .map(|i| json!({ "id": i, "tags": ["a", "b"], "nested": { "value": i * 2 } }))
+            .collect(),
+    );
+    assert_eq!(
+        dumpster::Collectable::accept(&document, &mut NoVisits),
+        Ok(())
+    );
+
+    let a = Gc::new(Object {
+        data: document.clone(),
+        other: RefCell::new(None),
+    });
+    let b = Gc::new(Object {
+        data: document,
+        other: RefCell::new(Some(a.clone())),
+    });
+    *a.other.borrow_mut() = Some(b.clone());
+
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Cache {
+    entries: DashMap<String, sync::Gc<Cache>>,
+    keys: DashSet<u64>,
+}
+
+#[test]
+fn dashmap_cycle_concurrent_writers() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    const N_WRITERS: usize = 4;
+    const N_WRITES: usize = 1_000;
+
+    #[derive(Collectable)]
+    struct Node {
+        links: DashMap<usize, sync::Gc<Node>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let a = sync::Gc::new(Node {
+        links: DashMap::new(),
+    });
+    let b = sync::Gc::new(Node {
+        links: DashMap::new(),
+    });
+    a.links.insert(0, b.clone());
+    b.links.insert(0, a.clone());
+
+    let writers = (0..N_WRITERS)
+        .map(|i| {
+            let a = a.clone();
+            std::thread::spawn(move || {
+                for j in 1..=N_WRITES {
+                    let key = i * N_WRITES + j;
+                    a.links.insert(
+                        key,
+                        sync::Gc::new(Node {
+                            links: DashMap::new(),
+                        }),
+                    );
+                    a.links.remove(&key);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    while !writers.iter().all(std::thread::JoinHandle::is_finished) {
+        sync::collect();
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert_eq!(COUNT.load(Ordering::Relaxed), N_WRITERS * N_WRITES);
+
+    drop(a);
+    drop(b);
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), N_WRITERS * N_WRITES + 2);
+}
+
+#[test]
+fn once_cell_unsync() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    type Children = Vec<Gc<Node>>;
+
+    #[derive(Collectable)]
+    struct Node {
+        next: once_cell::unsync::OnceCell<Gc<Node>>,
+        lazy: once_cell::unsync::Lazy<Children, fn() -> Children>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn never() -> Children {
+        panic!("collection must not force a lazy value");
+    }
+
+    // uninitialized cells are not traced and not forced
+    let lonely = Gc::new(Node {
+        next: once_cell::unsync::OnceCell::new(),
+        lazy: once_cell::unsync::Lazy::new(never),
+    });
+    drop(lonely);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+
+    // an initialized cell may form a cycle
+    let a = Gc::new(Node {
+        next: once_cell::unsync::OnceCell::new(),
+        lazy: once_cell::unsync::Lazy::new(never),
+    });
+    let b = Gc::new(Node {
+        next: once_cell::unsync::OnceCell::with_value(a.clone()),
+        lazy: once_cell::unsync::Lazy::new(never),
+    });
+    a.next.set(b.clone()).ok().unwrap();
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn once_cell_sync() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    type Children = Vec<sync::Gc<Node>>;
+
+    #[derive(Collectable)]
+    struct Node {
+        next: once_cell::sync::OnceCell<sync::Gc<Node>>,
+        lazy: once_cell::sync::Lazy<Children, fn() -> Children>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn never() -> Children {
+        panic!("collection must not force a lazy value");
+    }
+
+    fn empty() -> Children {
+        Vec::new()
+    }
+
+    let lonely = sync::Gc::new(Node {
+        next: once_cell::sync::OnceCell::new(),
+        lazy: once_cell::sync::Lazy::new(never),
+    });
+    drop(lonely);
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+
+    let a = sync::Gc::new(Node {
+        next: once_cell::sync::OnceCell::new(),
+        lazy: once_cell::sync::Lazy::new(empty),
+    });
+    let b = sync::Gc::new(Node {
+        next: once_cell::sync::OnceCell::with_value(a.clone()),
+        lazy: once_cell::sync::Lazy::new(never),
+    });
+    a.next.set(b.clone()).ok().unwrap();
+    assert!(a.lazy.is_empty());
+    drop(a);
+    drop(b);
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn tinyvec_spill_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable, Default)]
+    struct Node {
+        children: RefCell<tinyvec::TinyVec<[Option<Gc<Node>>; 2]>>,
+        recent: RefCell<tinyvec::ArrayVec<[Option<Gc<Node>>; 2]>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let root = Gc::new(Node::default());
+    root.children.borrow_mut().push(Some(root.clone()));
+    root.recent.borrow_mut().push(Some(root.clone()));
+    assert!(root.children.borrow().is_inline());
+
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+
+    // spill the inline storage onto the heap
+    for _ in 0..3 {
+        let child = Gc::new(Node::default());
+        child.children.borrow_mut().push(Some(root.clone()));
+        root.children.borrow_mut().push(Some(child));
+    }
+    assert!(root.children.borrow().is_heap());
+
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+
+    drop(root);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 4);
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+enum Expr {
+    Ident(SmolStr),
+    Literal(CompactString),
+    Source {
+        text: ArcStr,
+        span: Substr,
+    },
+    Call {
+        callee: Gc<Expr>,
+        args: Vec<Gc<Expr>>,
+    },
+}
+
+#[test]
+fn small_string_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Binding {
+        name: SmolStr,
+        doc: CompactString,
+        source: ArcStr,
+        value: RefCell<Option<Gc<Binding>>>,
+    }
+
+    impl Drop for Binding {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let source = ArcStr::from("let f = g; let g = f;");
+    let f = Gc::new(Binding {
+        name: SmolStr::new("f"),
+        doc: CompactString::new("refers to g"),
+        source: source.clone(),
+        value: RefCell::new(None),
+    });
+    let g = Gc::new(Binding {
+        name: SmolStr::new("g"),
+        doc: CompactString::new("refers to f"),
+        source: source.clone(),
+        value: RefCell::new(Some(f.clone())),
+    });
+    *f.value.borrow_mut() = Some(g.clone());
+
+    drop(f);
+    drop(g);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+    assert_eq!(ArcStr::strong_count(&source), Some(1));
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Pool {
+    slab: Slab<Gc<Pool>>,
+    arena: Arena<Gc<Pool>>,
+    current: Option<Index>,
+}
+
+#[test]
+fn slab_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Manager {
+        objects: RefCell<Slab<Gc<Object>>>,
+    }
+
+    #[derive(Collectable)]
+    struct Object {
+        manager: Gc<Manager>,
+        id: usize,
+    }
+
+    impl Drop for Object {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let manager = Gc::new(Manager {
+        objects: RefCell::new(Slab::new()),
+    });
+    let keys = (0..4)
+        .map(|id| {
+            let object = Gc::new(Object {
+                manager: manager.clone(),
+                id,
+            });
+            manager.objects.borrow_mut().insert(object)
+        })
+        .collect::<Vec<_>>();
+
+    // removing an entry breaks its cycle with the manager
+    drop(manager.objects.borrow_mut().remove(keys[1]));
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+
+    // the remaining entries are untouched by a collection
+    let ids = manager
+        .objects
+        .borrow()
+        .iter()
+        .map(|(key, object)| (key, object.id))
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [(keys[0], 0), (keys[2], 2), (keys[3], 3)]);
+
+    drop(manager);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn generational_arena_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        arena: RefCell<Arena<Gc<Node>>>,
+        parent: RefCell<Option<Gc<Node>>>,
+        name: &'static str,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let new_node = |name| {
+        Gc::new(Node {
+            arena: RefCell::new(Arena::new()),
+            parent: RefCell::new(None),
+            name,
+        })
+    };
+
+    let root = new_node("root");
+    let mut children = Vec::new();
+    for name in ["a", "b", "c"] {
+        let child = new_node(name);
+        *child.parent.borrow_mut() = Some(root.clone());
+        children.push(root.arena.borrow_mut().insert(child));
+    }
+
+    let removed = root.arena.borrow_mut().remove(children[0]).unwrap();
+    drop(removed);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    assert!(root.arena.borrow().get(children[0]).is_none());
+
+    let names = root
+        .arena
+        .borrow()
+        .iter()
+        .map(|(_, node)| node.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["b", "c"]);
+
+    drop(root);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn futures_mutex_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        peers: futures::lock::Mutex<Vec<sync::Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    futures::executor::block_on(async {
+        let a = sync::Gc::new(Node {
+            peers: futures::lock::Mutex::new(Vec::new()),
+        });
+        let b = sync::Gc::new(Node {
+            peers: futures::lock::Mutex::new(Vec::new()),
+        });
+
+        // form the cycle from two concurrent tasks
+        let link_a = async { a.peers.lock().await.push(b.clone()) };
+        let link_b = async { b.peers.lock().await.push(a.clone()) };
+        futures::join!(link_a, link_b);
+
+        // while the lock is held, the cycle must be kept alive
+        let guard = a.peers.lock().await;
+        sync::collect();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+        drop(guard);
+
+        drop(a);
+        drop(b);
+    });
+
+    sync::collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn futures_bilock_shared_half() {
+    use futures::lock::BiLock;
+
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        counter: BiLock<u32>,
+        this: std::sync::Mutex<Option<sync::Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    futures::executor::block_on(async {
+        let (half_a, half_b) = BiLock::new(0);
+        let a = sync::Gc::new(Node {
+            counter: half_a,
+            this: std::sync::Mutex::new(None),
+        });
+        *a.this.lock().unwrap() = Some(a.clone());
+
+        // a live `a` must survive a collection, even while the other half holds the lock
+        let mut guard = half_b.lock().await;
+        *guard += 1;
+        sync::collect();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+        drop(guard);
+        assert_eq!(*a.counter.lock().await, 1);
+
+        drop(a);
+        sync::collect();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+        assert_eq!(*half_b.lock().await, 1);
+    });
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Entity {
+    id: uuid::Uuid,
+    created_at: chrono::DateTime<chrono::Utc>,
+    birthday: chrono::NaiveDate,
+    updated_at: time::OffsetDateTime,
+    ttl: time::Duration,
+    owner: RefCell<Option<Gc<Entity>>>,
+    followers: RefCell<Vec<Gc<Entity>>>,
+}
+
+#[test]
+fn entity_timestamps_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Account {
+        id: uuid::Uuid,
+        created_at: chrono::DateTime<chrono::Utc>,
+        expires_at: time::OffsetDateTime,
+        friend: RefCell<Option<Gc<Account>>>,
+    }
+
+    impl Drop for Account {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let new_account = || {
+        Gc::new(Account {
+            id: uuid::Uuid::new_v4(),
+            created_at: chrono::Utc::now(),
+            expires_at: time::OffsetDateTime::UNIX_EPOCH + time::Duration::days(365),
+            friend: RefCell::new(None),
+        })
+    };
+    let alice = new_account();
+    let bob = new_account();
+    assert_ne!(alice.id, bob.id);
+    assert!(alice.created_at <= bob.created_at);
+    assert_eq!(alice.expires_at, bob.expires_at);
+    *alice.friend.borrow_mut() = Some(bob.clone());
+    *bob.friend.borrow_mut() = Some(alice.clone());
+
+    drop(alice);
+    drop(bob);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
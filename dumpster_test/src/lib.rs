@@ -21,13 +21,37 @@
 #![cfg(test)]
 
 use std::{
+    any::TypeId,
     cell::RefCell,
-    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+    cmp::{Ordering as CmpOrdering, Reverse},
+    collections::BinaryHeap,
+    ffi::{c_void, CString, OsString},
+    marker::PhantomData,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    num::{NonZeroI128, NonZeroU32, NonZeroUsize, Saturating, Wrapping},
+    ops::{Bound, ControlFlow, Range, RangeFrom, RangeInclusive, RangeTo},
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{
+            AtomicBool, AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize, AtomicPtr,
+            AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+        },
+        Arc,
+    },
+    task::Poll,
+    time::{Duration, Instant, SystemTime},
 };
 
-use dumpster::unsync::{collect, Gc};
+use dumpster::{
+    unsync::{collect, Gc},
+    GcCallback, GcFree,
+};
 use dumpster_derive::Collectable;
 
+mod foreign;
+
 #[derive(Collectable)]
 struct Empty;
 
@@ -61,6 +85,237 @@ enum B {
     One(Gc<B>),
 }
 
+#[derive(Collectable)]
+#[allow(unused)]
+enum Never {}
+
+#[derive(Collectable)]
+#[allow(unused)]
+enum Fieldless {
+    Unit,
+    Tuple(),
+    Struct {},
+}
+
+#[derive(Collectable)]
+#[allow(unused)]
+enum Generic<'a, T: dumpster::Collectable + 'static, const N: usize>
+where
+    T: Clone,
+{
+    Borrowed(PhantomData<&'a T>),
+    Inline([T; N]),
+    Named { first: T, rest: Vec<Gc<T>> },
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Wrapper<T: dumpster::Collectable + 'static> {
+    value: T,
+    next: Option<Gc<Wrapper<T>>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Marker<T> {
+    id: u32,
+    marker: PhantomData<T>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Projections<I: Iterator, J: Iterator> {
+    current: Option<I::Item>,
+    other: Vec<<J as Iterator>::Item>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Buffer<T, const N: usize>
+where
+    T: Copy,
+{
+    data: [T; N],
+    len: usize,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Nested<K, V: dumpster::Collectable + 'static> {
+    entries: Vec<Option<(K, Wrapper<V>)>>,
+}
+
+/// A handle to some foreign resource, which is not `Collectable`.
+struct FfiHandle(*mut c_void);
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Skipped<T> {
+    #[collectable(unsafe_skip)]
+    raw: *mut c_void,
+    #[collectable(unsafe_skip)]
+    handle: FfiHandle,
+    #[collectable(unsafe_skip)]
+    cache: T,
+    next: Option<Gc<Empty>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+enum SkippedVariants {
+    Raw(
+        #[collectable(unsafe_skip)] *const c_void,
+        Gc<SkippedVariants>,
+    ),
+    Handle {
+        #[collectable(unsafe_skip)]
+        handle: FfiHandle,
+        next: Gc<SkippedVariants>,
+    },
+}
+
+/// A map which only ever traces its values.
+struct KeyedMap<K, V> {
+    keys: Vec<K>,
+    values: Vec<V>,
+}
+
+unsafe impl<K, V: dumpster::Collectable> dumpster::Collectable for KeyedMap<K, V> {
+    fn accept<Z: dumpster::Visitor>(&self, visitor: &mut Z) -> Result<(), ()> {
+        self.values.accept(visitor)
+    }
+}
+
+#[derive(Collectable)]
+#[collectable(bound = "V: dumpster::Collectable")]
+struct Registry<K, V> {
+    entries: RefCell<KeyedMap<K, V>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Lookup<K, V> {
+    #[collectable(bound = "V: dumpster::Collectable")]
+    entries: KeyedMap<K, V>,
+    len: usize,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+#[collectable(bound = "")]
+struct Unbounded<T> {
+    marker: PhantomData<T>,
+}
+
+/// Custom tracing for `AssertUnwindSafe`, which is not `Collectable`.
+mod unwind_safe {
+    use std::panic::AssertUnwindSafe;
+
+    pub fn accept<T: dumpster::Collectable, V: dumpster::Visitor>(
+        field: &AssertUnwindSafe<T>,
+        visitor: &mut V,
+    ) -> Result<(), ()> {
+        field.0.accept(visitor)
+    }
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+enum Guarded<T: dumpster::Collectable + 'static> {
+    Tuple(#[collectable(unsafe_with = "unwind_safe")] AssertUnwindSafe<Vec<T>>),
+    Named {
+        #[collectable(unsafe_with = "crate::unwind_safe")]
+        value: AssertUnwindSafe<Gc<T>>,
+        count: usize,
+    },
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Token<'src, 'tree, T: ?Sized> {
+    text: &'src str,
+    value: &'tree T,
+    next: Option<Box<Token<'src, 'static, str>>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Atomics {
+    flag: AtomicBool,
+    u8: AtomicU8,
+    u16: AtomicU16,
+    u32: AtomicU32,
+    u64: AtomicU64,
+    usize: AtomicUsize,
+    i8: AtomicI8,
+    i16: AtomicI16,
+    i32: AtomicI32,
+    i64: AtomicI64,
+    isize: AtomicIsize,
+    ptr: AtomicPtr<u8>,
+    cycle: RefCell<Option<Gc<Atomics>>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Wrappers {
+    range: Range<Gc<Empty>>,
+    range_inclusive: RangeInclusive<Gc<Empty>>,
+    range_from: RangeFrom<Gc<Empty>>,
+    range_to: RangeTo<Gc<Empty>>,
+    bound: Bound<Gc<Empty>>,
+    control_flow: ControlFlow<Gc<Empty>, Gc<Empty>>,
+    poll: Poll<Gc<Empty>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Numeric {
+    id: NonZeroU32,
+    len: NonZeroUsize,
+    offset: NonZeroI128,
+    counter: Wrapping<u64>,
+    budget: Saturating<i32>,
+    priority: Reverse<Gc<Empty>>,
+    wrapped: Wrapping<Gc<Empty>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct PlainData {
+    timeout: Duration,
+    started: Instant,
+    modified: SystemTime,
+    ip: IpAddr,
+    ipv4: Ipv4Addr,
+    socket: SocketAddr,
+    path: PathBuf,
+    os_name: OsString,
+    c_name: CString,
+    type_id: TypeId,
+    ordering: CmpOrdering,
+    atomic_ordering: Ordering,
+    next: Option<Gc<PlainData>>,
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+enum Tree<T: dumpster::Collectable + 'static> {
+    Leaf(Gc<T>),
+    Node(Box<Tree<T>>, Box<Tree<T>>),
+    Branches(Box<[Tree<T>]>),
+}
+
+#[derive(Collectable)]
+#[allow(dead_code)]
+struct Handlers {
+    open: fn(),
+    read: fn(u8) -> usize,
+    write: unsafe fn(*const u8, usize) -> isize,
+    close: extern "C" fn(i32) -> i32,
+    event: GcCallback<u32, bool, Gc<Handlers>>,
+}
+
 impl Drop for MultiRef {
     fn drop(&mut self) {
         self.counter.fetch_add(1, Ordering::Relaxed);
@@ -187,3 +442,726 @@ fn unsync_as_ptr() {
     assert_ne!(Gc::as_ptr(&b.0), Gc::as_ptr(&b2.0));
     assert_ne!(Gc::as_ptr(&b.0), empty2_ptr);
 }
+
+#[test]
+fn atomic_cycle() {
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Collectable)]
+    struct Foo {
+        n_visits: AtomicUsize,
+        ptr: AtomicPtr<()>,
+        cycle: RefCell<Option<Gc<Foo>>>,
+    }
+
+    impl Drop for Foo {
+        fn drop(&mut self) {
+            DROPPED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let foo = Gc::new(Foo {
+        n_visits: AtomicUsize::new(0),
+        ptr: AtomicPtr::new(std::ptr::null_mut()),
+        cycle: RefCell::new(None),
+    });
+    *foo.cycle.borrow_mut() = Some(foo.clone());
+    foo.n_visits.fetch_add(1, Ordering::Relaxed);
+    assert!(foo.ptr.load(Ordering::Relaxed).is_null());
+
+    drop(foo);
+    collect();
+    assert!(DROPPED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn bound_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Interval {
+        lower: RefCell<Bound<Gc<Interval>>>,
+    }
+
+    impl Drop for Interval {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let a = Gc::new(Interval {
+        lower: RefCell::new(Bound::Unbounded),
+    });
+    let b = Gc::new(Interval {
+        lower: RefCell::new(Bound::Included(a.clone())),
+    });
+    *a.lower.borrow_mut() = Bound::Excluded(b.clone());
+
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn reverse_heap_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Task {
+        waiting: RefCell<BinaryHeap<Reverse<Waiting>>>,
+    }
+
+    /// A task waiting on another, ordered by the priority it waits with.
+    #[derive(Collectable)]
+    struct Waiting {
+        priority: u8,
+        task: Gc<Task>,
+    }
+
+    impl PartialEq for Waiting {
+        fn eq(&self, other: &Self) -> bool {
+            self.priority == other.priority
+        }
+    }
+
+    impl Eq for Waiting {}
+
+    impl PartialOrd for Waiting {
+        fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Waiting {
+        fn cmp(&self, other: &Self) -> CmpOrdering {
+            self.priority.cmp(&other.priority)
+        }
+    }
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let low = Gc::new(Task {
+        waiting: RefCell::new(BinaryHeap::new()),
+    });
+    let high = Gc::new(Task {
+        waiting: RefCell::new(BinaryHeap::new()),
+    });
+    let wait = |priority, task: &Gc<Task>| {
+        Reverse(Waiting {
+            priority,
+            task: task.clone(),
+        })
+    };
+    low.waiting.borrow_mut().push(wait(1, &high));
+    high.waiting.borrow_mut().push(wait(0, &low));
+    high.waiting.borrow_mut().push(wait(1, &high));
+    assert_eq!(high.waiting.borrow().peek().unwrap().0.priority, 0);
+
+    drop(low);
+    drop(high);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn boxed_enum_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Binding {
+        value: RefCell<Option<Expr>>,
+    }
+
+    #[derive(Collectable)]
+    enum Expr {
+        Add(Box<Expr>, Box<Expr>),
+        Many(Box<[Expr]>),
+        Var(Gc<Binding>),
+    }
+
+    impl Drop for Binding {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let x = Gc::new(Binding {
+        value: RefCell::new(None),
+    });
+    let y = Gc::new(Binding {
+        value: RefCell::new(None),
+    });
+    // x = y + y
+    *x.value.borrow_mut() = Some(Expr::Add(
+        Box::new(Expr::Var(y.clone())),
+        Box::new(Expr::Var(y.clone())),
+    ));
+    // y = [x]
+    *y.value.borrow_mut() = Some(Expr::Many(Box::new([Expr::Var(x.clone())])));
+
+    drop(x);
+    drop(y);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn derive_bounds() {
+    fn assert_collectable<T: dumpster::Collectable>() {}
+
+    /// Shared references are leaves, whatever their lifetime and pointee.
+    fn borrowed<'a>(_: &'a str) {
+        assert_collectable::<Token<'a, 'a, Opaque>>();
+        assert_collectable::<Token<'a, 'static, [Gc<Empty>]>>();
+    }
+
+    /// A type which is not `Collectable`.
+    struct Opaque;
+
+    assert_collectable::<Wrapper<u8>>();
+    assert_collectable::<Marker<Opaque>>();
+    assert_collectable::<Marker<*const Opaque>>();
+    assert_collectable::<Projections<std::vec::IntoIter<Gc<Empty>>, std::ops::Range<u8>>>();
+    assert_collectable::<Buffer<u64, 4>>();
+    assert_collectable::<Nested<String, Gc<Empty>>>();
+    borrowed(&String::from("borrowed"));
+
+    // skipped fields impose no bounds
+    assert_collectable::<Skipped<FfiHandle>>();
+    // explicit bounds replace the inferred ones
+    assert_collectable::<Lookup<FfiHandle, u8>>();
+    assert_collectable::<Unbounded<FfiHandle>>();
+}
+
+#[test]
+fn skipped_field_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        #[collectable(unsafe_skip)]
+        handle: FfiHandle,
+        next: RefCell<Option<Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let a = Gc::new(Node {
+        handle: FfiHandle(std::ptr::null_mut()),
+        next: RefCell::new(None),
+    });
+    let b = Gc::new(Node {
+        handle: FfiHandle(std::ptr::null_mut()),
+        next: RefCell::new(Some(a.clone())),
+    });
+    *a.next.borrow_mut() = Some(b.clone());
+    assert!(a.handle.0.is_null());
+
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn relaxed_bound_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Node {
+        registry: Gc<Registry<FfiHandle, Gc<Node>>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let registry = Gc::new(Registry {
+        entries: RefCell::new(KeyedMap {
+            keys: Vec::new(),
+            values: Vec::new(),
+        }),
+    });
+    for _ in 0..3 {
+        let node = Gc::new(Node {
+            registry: registry.clone(),
+        });
+        let mut entries = registry.entries.borrow_mut();
+        entries.keys.push(FfiHandle(std::ptr::null_mut()));
+        entries.values.push(node);
+    }
+    assert_eq!(registry.entries.borrow().keys.len(), 3);
+
+    drop(registry);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn custom_tracing_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Task {
+        #[collectable(unsafe_with = "unwind_safe")]
+        children: AssertUnwindSafe<RefCell<Vec<Gc<Task>>>>,
+        status: RefCell<Option<Guarded<Task>>>,
+    }
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let new_task = || {
+        Gc::new(Task {
+            children: AssertUnwindSafe(RefCell::new(Vec::new())),
+            status: RefCell::new(None),
+        })
+    };
+    let parent = new_task();
+    let child = new_task();
+    parent.children.borrow_mut().push(child.clone());
+    *child.status.borrow_mut() = Some(Guarded::Named {
+        value: AssertUnwindSafe(parent.clone()),
+        count: 1,
+    });
+
+    drop(parent);
+    drop(child);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn lifetime_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Span<'src>
+    where
+        'src: 'static,
+    {
+        text: &'src str,
+        parent: RefCell<Option<Gc<Span<'src>>>>,
+        children: RefCell<Vec<Gc<Span<'src>>>>,
+    }
+
+    impl Drop for Span<'_> {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let source = "fn main() {}";
+    let root = Gc::new(Span {
+        text: source,
+        parent: RefCell::new(None),
+        children: RefCell::new(Vec::new()),
+    });
+    for (start, end) in [(0, 2), (3, 7), (10, 12)] {
+        let child = Gc::new(Span {
+            text: &source[start..end],
+            parent: RefCell::new(Some(root.clone())),
+            children: RefCell::new(Vec::new()),
+        });
+        root.children.borrow_mut().push(child);
+    }
+    assert_eq!(root.children.borrow()[1].text, "main");
+
+    drop(root);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn enum_parent_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    enum Node {
+        Root { children: RefCell<Vec<Gc<Node>>> },
+        Child(Gc<Node>, u32),
+        Orphan,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let root = Gc::new(Node::Root {
+        children: RefCell::new(Vec::new()),
+    });
+    let Node::Root { children } = &*root else {
+        unreachable!()
+    };
+    for i in 0..3 {
+        children
+            .borrow_mut()
+            .push(Gc::new(Node::Child(root.clone(), i)));
+    }
+    children.borrow_mut().push(Gc::new(Node::Orphan));
+
+    drop(root);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 5);
+}
+
+#[test]
+fn opaque_shared_pointers() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    struct Config {
+        #[allow(dead_code)]
+        verbose: bool,
+    }
+
+    unsafe impl GcFree for Config {}
+
+    #[derive(Collectable)]
+    struct Actor {
+        config: Arc<Config>,
+        name: Rc<str>,
+        history: Arc<[u64]>,
+        peer: RefCell<Option<Gc<Actor>>>,
+    }
+
+    impl Drop for Actor {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let config = Arc::new(Config { verbose: true });
+    let name: Rc<str> = Rc::from("actor");
+    let history: Arc<[u64]> = Arc::from([1, 2, 3]);
+
+    let a = Gc::new(Actor {
+        config: config.clone(),
+        name: name.clone(),
+        history: history.clone(),
+        peer: RefCell::new(None),
+    });
+    let b = Gc::new(Actor {
+        config: config.clone(),
+        name: name.clone(),
+        history: history.clone(),
+        peer: RefCell::new(Some(a.clone())),
+    });
+    *a.peer.borrow_mut() = Some(b.clone());
+    assert_eq!(Arc::strong_count(&config), 3);
+    assert_eq!(&*b.name, "actor");
+
+    drop(a);
+    drop(b);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+    assert_eq!(Arc::strong_count(&config), 1);
+    assert_eq!(Rc::strong_count(&name), 1);
+    assert_eq!(Arc::strong_count(&history), 1);
+}
+
+dumpster::collectable_leaf! {
+    /// Configuration shared between actors.
+    #[derive(Debug, PartialEq)]
+    struct LeafConfig {
+        name: String,
+        retries: u32,
+    }
+
+    struct LeafStats(u64, pub(crate) Option<Box<[u8]>>);
+
+    struct LeafMarker;
+
+    #[allow(dead_code)]
+    enum LeafLevel {
+        Quiet,
+        Verbose { depth: u8 },
+        Custom(String, usize),
+    }
+}
+
+#[test]
+fn leaf_macro() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Holder {
+        config: Arc<LeafConfig>,
+        stats: LeafStats,
+        level: LeafLevel,
+        marker: LeafMarker,
+        peer: RefCell<Option<Gc<Holder>>>,
+    }
+
+    impl Drop for Holder {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let config = Arc::new(LeafConfig {
+        name: "leaf".into(),
+        retries: 3,
+    });
+    let holder = Gc::new(Holder {
+        config: config.clone(),
+        stats: LeafStats(1, None),
+        level: LeafLevel::Verbose { depth: 2 },
+        marker: LeafMarker,
+        peer: RefCell::new(None),
+    });
+    *holder.peer.borrow_mut() = Some(holder.clone());
+    assert_eq!(holder.stats.0, 1);
+    assert!(holder.stats.1.is_none());
+    assert!(matches!(holder.level, LeafLevel::Verbose { depth: 2 }));
+    let LeafMarker = holder.marker;
+    drop(holder);
+
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    assert_eq!(Arc::strong_count(&config), 1);
+    assert_eq!(config.name, "leaf");
+    assert_eq!(config.retries, 3);
+}
+
+/// A stand-in for a crate whose types cannot be annotated with `#[derive(Collectable)]`.
+mod third_party {
+    use std::{
+        cell::RefCell,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use dumpster::unsync::Gc;
+
+    pub static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    pub struct Thing {
+        pub name: String,
+        pub field_a: RefCell<Option<Gc<Thing>>>,
+        pub field_b: Wrapper,
+    }
+
+    impl Drop for Thing {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Not `Collectable` itself, so only its field is visited.
+    pub struct Wrapper {
+        pub inner: RefCell<Vec<Gc<Thing>>>,
+        pub hits: u32,
+    }
+
+    pub struct Pair<T, U> {
+        pub left: T,
+        pub right: U,
+        pub tag: Opaque,
+    }
+
+    pub struct Opaque(pub u64);
+}
+
+// SAFETY: `name` cannot own a `Gc`, and the listed fields share none.
+dumpster::impl_collectable!(unsafe impl third_party::Thing { field_a, field_b.inner, });
+// SAFETY: every field is listed once, and none of them is shared.
+dumpster::impl_collectable!(
+    unsafe impl<T, U> third_party::Pair<T, U> { left, right, tag }
+    where T: dumpster::Collectable, U: dumpster::Collectable
+);
+// SAFETY: `Opaque` only holds a `u64`.
+dumpster::impl_collectable!(unsafe impl leaf third_party::Opaque);
+
+/// A local wrapper around a foreign type which has no implementation of its own.
+struct Local(third_party::Wrapper);
+
+// SAFETY: `hits` cannot own a `Gc`.
+dumpster::impl_collectable!(unsafe impl Local { 0.inner });
+
+#[test]
+fn remote_impls() {
+    use dumpster::Collectable;
+    use third_party::{Opaque, Pair, Thing, Wrapper, DROPPED};
+
+    fn thing(name: &str) -> Gc<Thing> {
+        Gc::new(Thing {
+            name: name.into(),
+            field_a: RefCell::new(None),
+            field_b: Wrapper {
+                inner: RefCell::new(Vec::new()),
+                hits: 0,
+            },
+        })
+    }
+
+    const { assert!(<Opaque as Collectable>::IS_LEAF) };
+    const { assert!(!<Thing as Collectable>::IS_LEAF) };
+
+    // a cycle through each of the listed fields of `Thing`
+    let a = thing("a");
+    let b = thing("b");
+    *a.field_a.borrow_mut() = Some(b.clone());
+    b.field_b.inner.borrow_mut().push(a.clone());
+    b.field_b.inner.borrow_mut().push(b.clone());
+    let c = thing("c");
+    *c.field_a.borrow_mut() = Some(c.clone());
+
+    // the cycles are kept alive through the generic type and the local wrapper
+    let pair = Gc::new(Pair {
+        left: a,
+        right: Local(Wrapper {
+            inner: RefCell::new(vec![c]),
+            hits: 1,
+        }),
+        tag: Opaque(7),
+    });
+    drop(b);
+    collect();
+    assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+    assert_eq!(pair.left.name, "a");
+    assert_eq!(pair.right.0.inner.borrow()[0].name, "c");
+    assert_eq!(pair.right.0.hits + pair.left.field_b.hits, 1);
+    assert_eq!(pair.tag.0, 7);
+
+    drop(pair);
+    collect();
+    assert_eq!(DROPPED.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn callback_cycle() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Collectable)]
+    struct Widget {
+        n_clicks: RefCell<u32>,
+        on_click: RefCell<Vec<GcCallback<u32, u32, Gc<Widget>>>>,
+    }
+
+    impl Drop for Widget {
+        fn drop(&mut self) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let widget = Gc::new(Widget {
+        n_clicks: RefCell::new(0),
+        on_click: RefCell::new(Vec::new()),
+    });
+    let callback = GcCallback::new(|roots: &[Gc<Widget>], n| {
+        let mut n_clicks = roots[0].n_clicks.borrow_mut();
+        *n_clicks += n;
+        *n_clicks
+    })
+    .capturing(widget.clone());
+    widget.on_click.borrow_mut().push(callback);
+
+    assert_eq!(widget.on_click.borrow()[0].call(3), 3);
+    assert_eq!(*widget.n_clicks.borrow(), 3);
+
+    drop(widget);
+    collect();
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn field_paths() {
+    /// A visitor which records the field path leading to every `Gc` it finds.
+    #[derive(Default)]
+    struct PathRecorder {
+        stack: Vec<&'static str>,
+        paths: Vec<String>,
+    }
+
+    impl dumpster::Visitor for PathRecorder {
+        fn visit_sync<T>(&mut self, _: &dumpster::sync::Gc<T>)
+        where
+            T: dumpster::Collectable + Send + Sync + ?Sized,
+        {
+            self.paths.push(self.stack.join("."));
+        }
+
+        fn visit_unsync<T>(&mut self, _: &Gc<T>)
+        where
+            T: dumpster::Collectable + ?Sized,
+        {
+            self.paths.push(self.stack.join("."));
+        }
+
+        fn enter_field(&mut self, name: &'static str) {
+            self.stack.push(name);
+        }
+
+        fn exit_field(&mut self) {
+            self.stack.pop();
+        }
+
+        fn enter_variant(&mut self, name: &'static str) {
+            self.stack.push(name);
+        }
+
+        fn exit_variant(&mut self) {
+            self.stack.pop();
+        }
+    }
+
+    #[derive(Collectable)]
+    enum Edge {
+        Leaf,
+        Single(Gc<Empty>),
+        Labeled { r#ref: Gc<Empty>, weight: u32 },
+    }
+
+    #[derive(Collectable)]
+    struct Pair(u8, dumpster::sync::Gc<u8>);
+
+    #[derive(Collectable)]
+    struct Graph {
+        name: String,
+        root: Gc<Empty>,
+        edges: Vec<Edge>,
+        pair: Pair,
+        #[collectable(unsafe_skip)]
+        #[allow(dead_code)]
+        cache: Option<u32>,
+    }
+
+    let graph = Graph {
+        name: "graph".into(),
+        root: Gc::new(Empty),
+        edges: vec![
+            Edge::Leaf,
+            Edge::Single(Gc::new(Empty)),
+            Edge::Labeled {
+                r#ref: Gc::new(Empty),
+                weight: 1,
+            },
+        ],
+        pair: Pair(0, dumpster::sync::Gc::new(0)),
+        cache: None,
+    };
+    assert_eq!(graph.name, "graph");
+
+    let mut recorder = PathRecorder::default();
+    dumpster::Collectable::accept(&graph, &mut recorder).unwrap();
+    assert!(recorder.stack.is_empty());
+    assert_eq!(
+        recorder.paths,
+        ["root", "edges.Single.0", "edges.Labeled.ref", "pair.1"]
+    );
+}